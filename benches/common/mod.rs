@@ -0,0 +1,20 @@
+//! The input the benchmarks share.
+
+const UNIT: &str = "#include <stdio.h>\n\
+/// A point on the plane.\n\
+struct point {\n    int x; // horizontal\n    unsigned long long int y;\n};\n\
+\n\
+static inline double scale(double value, float factor) {\n\
+    /* widen before multiplying */\n\
+    return value * factor + 0x1.8p3 - 1'000 + 'a';\n\
+}\n\
+\n\
+class Counter {\n    int count;\n    void add(int n) { count = count + n; }\n\
+    Counter *const *operator(+)(int n);\n};\n\
+\n\
+int main() {\n    char *greeting = \"Hello, \" \"World!\\n\";\n    printf(greeting);\n    return 0;\n}\n";
+
+/// Repeats a small translation unit until the input is roughly `bytes` long.
+pub fn input(bytes: usize) -> String {
+    UNIT.repeat(bytes / UNIT.len() + 1)
+}
@@ -0,0 +1,39 @@
+use c_with_classes::incremental::Document;
+use c_with_classes::logos_lexer::lex_with_trivia;
+use c_with_classes::parser::Parser;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+mod common;
+
+use common::input;
+
+/// Changes one digit in the middle of the input, the way a keystroke does, and brings the
+/// tree up to date: all over again, or with an incremental document.
+fn reparse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("reparse");
+    for size in [64 * 1024, 512 * 1024] {
+        let source = input(size);
+        let digit = source[..source.len() / 2].rfind("return 0;").unwrap() + "return ".len();
+
+        group.bench_with_input(BenchmarkId::new("full", size), &source, |b, source| {
+            let mut source = source.clone();
+            b.iter(|| {
+                let replacement = if &source[digit..digit + 1] == "0" { "1" } else { "0" };
+                source.replace_range(digit..digit + 1, replacement);
+                let tokens = lex_with_trivia(black_box(&source)).unwrap();
+                Parser::with_trivia(&tokens).parse().unwrap()
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("incremental", size), &source, |b, source| {
+            let mut document = Document::new(source);
+            b.iter(|| {
+                let replacement = if &document.source()[digit..digit + 1] == "0" { "1" } else { "0" };
+                document.edit(black_box(digit..digit + 1), replacement).unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, reparse);
+criterion_main!(benches);
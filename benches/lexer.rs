@@ -0,0 +1,21 @@
+use c_with_classes::logos_lexer::lex;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+mod common;
+
+use common::input;
+
+fn lexer(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lex");
+    for size in [64 * 1024, 512 * 1024, 4 * 1024 * 1024] {
+        let input = input(size);
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &input, |b, input| {
+            b.iter(|| lex(black_box(input)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, lexer);
+criterion_main!(benches);
@@ -0,0 +1,25 @@
+use c_with_classes::logos_lexer::lex_with_trivia;
+use c_with_classes::parser::Parser;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+mod common;
+
+use common::input;
+
+/// Parses tokens lexed beforehand, so that the time per byte stays flat as the input grows
+/// as long as the parser takes linear time.
+fn parser(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    for size in [64 * 1024, 512 * 1024, 4 * 1024 * 1024] {
+        let input = input(size);
+        let tokens = lex_with_trivia(&input).unwrap();
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &tokens, |b, tokens| {
+            b.iter(|| Parser::with_trivia(black_box(tokens)).parse().unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parser);
+criterion_main!(benches);
@@ -0,0 +1,34 @@
+//! Writes the seed corpus for the `lex` and `parse` targets: programs from the generator, made
+//! from fixed pseudo-random input so that the checked-in seeds are reproducible.
+//!
+//!     cargo run --manifest-path fuzz/Cargo.toml --example corpus
+
+use std::fs;
+use std::path::Path;
+
+use arbitrary::Unstructured;
+use c_with_classes_fuzz::{check, program};
+
+
+const SEEDS: usize = 32;
+
+fn main() {
+    let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("corpus");
+    let mut state = 0x9e37_79b9_7f4a_7c15u64;
+    for seed in 0..SEEDS {
+        let data = (0..2048).map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        }).collect::<Vec<u8>>();
+        let source = program(&mut Unstructured::new(&data)).unwrap();
+        check(&source).unwrap_or_else(|err| panic!("{}\n{}", err, source));
+        for target in ["lex", "parse"] {
+            let directory = corpus.join(target);
+            fs::create_dir_all(&directory).unwrap();
+            fs::write(directory.join(format!("seed-{:02}.cwc", seed)), &source).unwrap();
+        }
+    }
+    println!("Wrote {} seeds to {}", SEEDS, corpus.display());
+}
@@ -0,0 +1,13 @@
+#![no_main]
+
+use arbitrary::Unstructured;
+use c_with_classes_fuzz::{check, program};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(source) = program(&mut Unstructured::new(data)) {
+        if let Err(err) = check(&source) {
+            panic!("{}\n{}", err, source);
+        }
+    }
+});
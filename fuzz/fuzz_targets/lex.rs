@@ -0,0 +1,19 @@
+#![no_main]
+
+use c_with_classes::logos_lexer::{lex, lex_with_trivia};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|source: &str| {
+    let plain = lex(source);
+    let trivia = lex_with_trivia(source);
+    match (plain, trivia) {
+        (Ok(plain), Ok(trivia)) => {
+            assert_eq!(plain, trivia.iter().map(|token| token.token.clone()).collect::<Vec<_>>());
+            for token in &trivia {
+                assert!(source.get(token.span.clone()).is_some(), "{:?} is not in the source", token.span);
+            }
+        },
+        (Err(plain), Err(trivia)) => assert_eq!(plain, trivia),
+        (plain, trivia) => panic!("The lexers disagree: {:?} and {:?}", plain, trivia),
+    }
+});
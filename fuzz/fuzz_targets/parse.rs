@@ -0,0 +1,14 @@
+#![no_main]
+
+use c_with_classes::logos_lexer::lex_with_trivia;
+use c_with_classes::parser::Parser;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|source: &str| {
+    if let Ok(tokens) = lex_with_trivia(source) {
+        let mut parser = Parser::with_trivia(&tokens);
+        if parser.parse().is_err() {
+            assert!(parser.position() <= tokens.len());
+        }
+    }
+});
@@ -0,0 +1,455 @@
+//! A grammar-aware generator of programs the parser accepts. The `generated` fuzz target
+//! turns fuzzer input into programs with it, so that mutations explore the grammar instead of
+//! stopping at the first syntax error, and `examples/corpus.rs` uses it to seed the corpus.
+//!
+//! The targets run with cargo-fuzz from the repository root:
+//!
+//! ```text
+//! cargo +nightly fuzz run lex
+//! cargo +nightly fuzz run parse
+//! cargo +nightly fuzz run generated
+//! ```
+
+use arbitrary::{Result, Unstructured};
+use c_with_classes::logos_lexer::lex_with_trivia;
+use c_with_classes::parser::Parser;
+use c_with_classes::printer::print;
+
+
+const TYPES: &[&str] = &[
+    "int", "char", "short", "long", "unsigned", "unsigned long long", "signed char", "float", "double",
+    "long double", "bool", "const int", "volatile long", "char *", "const char *", "void *", "int **",
+];
+const NAMES: &[&str] = &["a", "b", "c", "count", "total", "value", "next", "x", "y", "i"];
+const FUNCTIONS: &[&str] = &["f", "g", "compute", "report"];
+const BINARY: &[&str] = &[
+    "+", "-", "*", "/", "%", "<<", ">>", "<", ">", "<=", ">=", "==", "!=", "&", "|", "^", "&&", "||", ",",
+];
+const ASSIGNMENT: &[&str] = &["=", "+=", "-=", "*=", "/=", "%=", "<<=", ">>=", "&=", "^=", "|="];
+const UNARY: &[&str] = &["-", "+", "!", "~", "*", "&"];
+const OPERATORS: &[&str] = &["+", "-", "*", "==", "<", "!", "++"];
+const ATTRIBUTES: &[&str] = &["[[nodiscard]] ", "[[maybe_unused]] ", "[[deprecated]] ", "__attribute__((cold)) "];
+
+/// How deeply expressions and statements nest, which keeps programs small.
+const DEPTH: u32 = 4;
+
+/// Writes a program of top-level declarations.
+pub fn program(u: &mut Unstructured) -> Result<String> {
+    let mut generator = Generator {u, output: String::new(), indent: 0, structs: Vec::new(), classes: Vec::new()};
+    for _ in 0..generator.u.int_in_range(1..=8)? {
+        generator.declaration()?;
+    }
+    Ok(generator.output)
+}
+
+/// Checks that a generated program lexes and parses, and that the printed syntax tree parses
+/// back to the same tree.
+pub fn check(source: &str) -> std::result::Result<(), String> {
+    let tokens = lex_with_trivia(source).map_err(|errors| format!("Failed to lex: {:?}", errors))?;
+    let mut parser = Parser::with_trivia(&tokens);
+    let header = parser.parse().map_err(|err| {
+        let offset = tokens.get(parser.position()).map_or(source.len(), |token| token.span.start);
+        format!("Failed to parse at line {}: {}", source[..offset].matches('\n').count() + 1, err)
+    })?;
+    let printed = print(&header);
+    let tokens = lex_with_trivia(&printed).map_err(|errors| format!("Failed to lex the printed program: {:?}", errors))?;
+    let reparsed = Parser::with_trivia(&tokens).parse()
+        .map_err(|err| format!("Failed to parse the printed program: {}\n{}", err, printed))?;
+    if reparsed != header {
+        return Err(format!("The printed program parses to a different tree:\n{}", printed));
+    }
+    Ok(())
+}
+
+struct Generator<'a, 'b> {
+    u: &'a mut Unstructured<'b>,
+    output: String,
+    indent: usize,
+    structs: Vec<String>,
+    classes: Vec<String>,
+}
+
+impl Generator<'_, '_> {
+    fn line(&mut self, text: &str) {
+        self.output += &"    ".repeat(self.indent);
+        self.output += text;
+        self.output.push('\n');
+    }
+
+    fn pick(&mut self, choices: &[&str]) -> Result<String> {
+        Ok(self.u.choose(choices)?.to_string())
+    }
+
+    fn name(&mut self) -> Result<String> {
+        self.pick(NAMES)
+    }
+
+    fn type_(&mut self) -> Result<String> {
+        if !self.structs.is_empty() && self.u.ratio(1, 4)? {
+            let name = self.u.choose(&self.structs)?.clone();
+            return Ok(format!("struct {}", name));
+        }
+        self.pick(TYPES)
+    }
+
+    /// A declarator of `type_` named `name`, such as `*name` or `name[4]`, in front of which the
+    /// type is written.
+    fn declaration_of(&mut self, type_: &str, name: &str) -> Result<String> {
+        Ok(match self.u.int_in_range(0..=5)? {
+            0 => format!("{} *{}", type_, name),
+            1 => format!("{} {}[{}]", type_, name, self.u.int_in_range(1..=16)?),
+            2 => format!("{} (*{})({})", type_, name, self.parameter_types()?),
+            _ => format!("{} {}", type_, name),
+        })
+    }
+
+    fn parameter_types(&mut self) -> Result<String> {
+        let count = self.u.int_in_range(0..=3)?;
+        if count == 0 {
+            return Ok("void".to_string());
+        }
+        let types = (0..count).map(|_| self.pick(TYPES)).collect::<Result<Vec<String>>>()?;
+        Ok(types.join(", "))
+    }
+
+    fn parameters(&mut self) -> Result<String> {
+        let count = self.u.int_in_range(0..=3)?;
+        if count == 0 {
+            return Ok("void".to_string());
+        }
+        let parameters = (0..count)
+            .map(|index| {
+                let type_ = self.pick(TYPES)?;
+                Ok(format!("{} {}{}", type_, self.name()?, index))
+            })
+            .collect::<Result<Vec<String>>>()?;
+        Ok(parameters.join(", "))
+    }
+
+    fn literal(&mut self) -> Result<String> {
+        Ok(match self.u.int_in_range(0..=6)? {
+            0 => format!("{:#x}", self.u.int_in_range(0..=0xffffu32)?),
+            1 => format!("{}u", self.u.int_in_range(0..=1000u32)?),
+            2 => format!("{}.{}", self.u.int_in_range(0..=99u32)?, self.u.int_in_range(0..=99u32)?),
+            3 => self.pick(&["'a'", "'\\n'", "'\\0'", "'\\''"])?,
+            4 => self.pick(&["\"text\"", "\"tab\\t\"", "\"a\" \"b\"", "\"\""])?,
+            _ => self.u.int_in_range(0..=1000u32)?.to_string(),
+        })
+    }
+
+    /// An expression, parenthesized wherever precedence would matter.
+    fn expression(&mut self, depth: u32) -> Result<String> {
+        if depth == 0 || self.u.ratio(1, 3)? {
+            return if self.u.ratio(1, 2)? { self.name() } else { self.literal() };
+        }
+        let depth = depth - 1;
+        Ok(match self.u.int_in_range(0..=12)? {
+            0 => format!("{}({})", self.pick(UNARY)?, self.expression(depth)?),
+            1 => format!("{}{}", self.pick(&["++", "--"])?, self.name()?),
+            2 => format!("{}{}", self.name()?, self.pick(&["++", "--"])?),
+            3 => {
+                let count = self.u.int_in_range(0..=3)?;
+                let arguments = (0..count).map(|_| self.assignment(depth)).collect::<Result<Vec<String>>>()?;
+                format!("{}({})", self.pick(FUNCTIONS)?, arguments.join(", "))
+            },
+            4 => format!("{}[{}]", self.name()?, self.expression(depth)?),
+            5 => format!("{}.{}", self.name()?, self.name()?),
+            6 => format!("{}->{}", self.name()?, self.name()?),
+            7 => format!("({}) ? ({}) : ({})", self.expression(depth)?, self.expression(depth)?, self.expression(depth)?),
+            8 => format!("({})({})", self.pick(TYPES)?, self.expression(depth)?),
+            9 => if self.u.ratio(1, 2)? {
+                format!("sizeof({})", self.type_()?)
+            }
+            else {
+                format!("sizeof {}", self.name()?)
+            },
+            10 => format!("{} {} ({})", self.name()?, self.pick(ASSIGNMENT)?, self.expression(depth)?),
+            _ => format!("({}) {} ({})", self.expression(depth)?, self.pick(BINARY)?, self.expression(depth)?),
+        })
+    }
+
+    /// An expression that can stand where a comma would end it, as an argument or initializer.
+    fn assignment(&mut self, depth: u32) -> Result<String> {
+        let expression = self.expression(depth)?;
+        Ok(if expression.contains(',') { format!("({})", expression) } else { expression })
+    }
+
+    fn initializer(&mut self) -> Result<String> {
+        if self.u.ratio(1, 4)? {
+            let count = self.u.int_in_range(1..=4)?;
+            let values = (0..count).map(|_| self.assignment(1)).collect::<Result<Vec<String>>>()?;
+            return Ok(format!("{{{}}}", values.join(", ")));
+        }
+        self.assignment(2)
+    }
+
+    fn statement(&mut self, depth: u32) -> Result<()> {
+        let choice = if depth == 0 { self.u.int_in_range(0..=2)? } else { self.u.int_in_range(0..=12)? };
+        let depth = depth.saturating_sub(1);
+        match choice {
+            0 => {
+                let expression = self.expression(2)?;
+                self.line(&format!("{};", expression));
+            },
+            1 => {
+                let type_ = self.pick(TYPES)?;
+                let name = self.name()?;
+                let declaration = self.declaration_of(&type_, &name)?;
+                if declaration.contains('[') || !self.u.ratio(1, 2)? {
+                    self.line(&format!("{};", declaration));
+                }
+                else {
+                    let value = self.initializer()?;
+                    self.line(&format!("{} = {};", declaration, value));
+                }
+            },
+            2 => {
+                let expression = if self.u.ratio(1, 2)? { format!(" {}", self.expression(2)?) } else { String::new() };
+                self.line(&format!("return{};", expression));
+            },
+            3 => {
+                let condition = self.expression(2)?;
+                self.line(&format!("if ({}) {{", condition));
+                self.block(depth)?;
+                if self.u.ratio(1, 2)? {
+                    self.line("} else {");
+                    self.block(depth)?;
+                }
+                self.line("}");
+            },
+            4 => {
+                let condition = self.expression(2)?;
+                self.line(&format!("while ({}) {{", condition));
+                self.block(depth)?;
+                self.line("}");
+            },
+            5 => {
+                self.line("do {");
+                self.block(depth)?;
+                let condition = self.expression(2)?;
+                self.line(&format!("}} while ({});", condition));
+            },
+            6 => {
+                let name = self.name()?;
+                let start = if self.u.ratio(1, 2)? { format!("int {} = 0", name) } else { format!("{} = 0", name) };
+                let bound = self.u.int_in_range(1..=10u32)?;
+                self.line(&format!("for ({}; {} < {}; {}++) {{", start, name, bound, name));
+                self.block(depth)?;
+                self.line("}");
+            },
+            7 => {
+                let value = self.expression(2)?;
+                self.line(&format!("switch ({}) {{", value));
+                // A label is followed by a statement rather than a declaration, so the cases
+                // are blocks.
+                for case in 0..self.u.int_in_range(1..=3u32)? {
+                    self.line(&format!("case {}: {{", case));
+                    self.block(depth)?;
+                    self.line("}");
+                    self.line("break;");
+                }
+                if self.u.ratio(1, 2)? {
+                    self.line("default: {");
+                    self.block(depth)?;
+                    self.line("}");
+                }
+                self.line("}");
+            },
+            8 => {
+                self.line("{");
+                self.block(depth)?;
+                self.line("}");
+            },
+            9 => {
+                let keyword = self.pick(&["break;", "continue;", "goto done;", ";"])?;
+                self.line(&keyword);
+            },
+            10 => {
+                self.line("done: {");
+                self.block(depth)?;
+                self.line("}");
+            },
+            11 => {
+                let type_ = self.type_()?;
+                self.line(&format!("static_assert(sizeof({}) > 0, \"checked\");", type_));
+            },
+            _ => {
+                let expression = self.expression(2)?;
+                self.line(&format!("[[maybe_unused]] {};", expression));
+            },
+        }
+        Ok(())
+    }
+
+    /// The statements of a block, without its braces.
+    fn block(&mut self, depth: u32) -> Result<()> {
+        self.indent += 1;
+        for _ in 0..self.u.int_in_range(0..=4)? {
+            self.statement(depth)?;
+        }
+        self.indent -= 1;
+        Ok(())
+    }
+
+    fn members(&mut self) -> Result<()> {
+        self.indent += 1;
+        for index in 0..self.u.int_in_range(1..=4)? {
+            let type_ = self.pick(TYPES)?;
+            let member = if self.u.ratio(1, 5)? && !type_.contains('*') && type_ != "bool" &&
+                !type_.contains("float") && !type_.contains("double") {
+                format!("{} bits{} : {}", type_, index, self.u.int_in_range(1..=7)?)
+            }
+            else {
+                let name = format!("{}{}", self.name()?, index);
+                self.declaration_of(&type_, &name)?
+            };
+            self.line(&format!("{};", member));
+        }
+        self.indent -= 1;
+        Ok(())
+    }
+
+    fn function_body(&mut self) -> Result<()> {
+        self.block(DEPTH)?;
+        self.line("}");
+        Ok(())
+    }
+
+    fn declaration(&mut self) -> Result<()> {
+        match self.u.int_in_range(0..=11)? {
+            0 => {
+                let line = self.pick(&["#include <stdio.h>", "#include \"local.h\"", "#define LIMIT 16",
+                                       "#define MAX(a, b) ((a) > (b) ? (a) : (b))", "#pragma once"])?;
+                self.line(&line);
+            },
+            1 | 2 => {
+                let keyword = self.pick(&["struct", "union"])?;
+                let name = format!("s{}", self.structs.len());
+                self.line(&format!("{} {} {{", keyword, name));
+                self.members()?;
+                self.line("};");
+                if keyword == "struct" {
+                    self.structs.push(name);
+                }
+            },
+            3 => {
+                let count = self.u.int_in_range(1..=4)?;
+                let members = (0..count)
+                    .map(|index| Ok(if self.u.ratio(1, 2)? {
+                        format!("E{}", index)
+                    }
+                    else {
+                        format!("E{} = {}", index, self.u.int_in_range(0..=100u32)?)
+                    }))
+                    .collect::<Result<Vec<String>>>()?;
+                self.line(&format!("enum e{} {{ {} }};", self.output.len(), members.join(", ")));
+            },
+            4 => {
+                self.line(&format!("tagged t{} {{", self.output.len()));
+                self.indent += 1;
+                for index in 0..self.u.int_in_range(1..=3)? {
+                    if self.u.ratio(1, 3)? {
+                        self.line(&format!("empty{},", index));
+                    }
+                    else {
+                        self.line(&format!("variant{} {{", index));
+                        self.members()?;
+                        self.line("},");
+                    }
+                }
+                self.indent -= 1;
+                self.line("};");
+            },
+            5 => self.class()?,
+            6 | 7 => {
+                let type_ = self.type_()?;
+                let declaration = self.declaration_of(&type_, &format!("global{}", self.output.len()))?;
+                if declaration.contains('(') || !self.u.ratio(1, 2)? {
+                    self.line(&format!("{};", declaration));
+                }
+                else {
+                    let value = self.initializer()?;
+                    self.line(&format!("{} = {};", declaration, value));
+                }
+            },
+            8 => {
+                let return_type = self.pick(TYPES)?;
+                let parameters = self.parameters()?;
+                let name = self.pick(FUNCTIONS)?;
+                self.line(&format!("{} {}({});", return_type, name, parameters));
+            },
+            _ => {
+                let attribute = if self.u.ratio(1, 4)? { self.pick(ATTRIBUTES)? } else { String::new() };
+                let return_type = self.pick(TYPES)?;
+                let parameters = self.parameters()?;
+                self.line(&format!("/// Function {}.", self.output.len()));
+                self.line(&format!("{}{} function{}({}) {{", attribute, return_type, self.output.len(), parameters));
+                self.function_body()?;
+            },
+        }
+        Ok(())
+    }
+
+    fn class(&mut self) -> Result<()> {
+        let name = format!("C{}", self.classes.len());
+        let abstract_ = if self.u.ratio(1, 3)? { "abstract " } else { "" };
+        let parent = if !self.classes.is_empty() && self.u.ratio(1, 2)? {
+            format!(" : {}", self.u.choose(&self.classes)?)
+        }
+        else {
+            String::new()
+        };
+        self.line(&format!("{}class {}{} {{", abstract_, name, parent));
+        self.indent += 1;
+        for index in 0..self.u.int_in_range(1..=4)? {
+            let return_type = self.pick(TYPES)?;
+            match self.u.int_in_range(0..=3)? {
+                0 => {
+                    let field = format!("field{}", index);
+                    let declaration = self.declaration_of(&return_type, &field)?;
+                    self.line(&format!("{};", declaration));
+                },
+                1 => {
+                    let parameters = self.parameters()?;
+                    self.line(&format!("{} method{}({});", return_type, index, parameters));
+                },
+                2 => {
+                    let parameters = self.parameters()?;
+                    self.line(&format!("{} method{}({}) {{", return_type, index, parameters));
+                    self.function_body()?;
+                },
+                _ => {
+                    let operator = self.pick(OPERATORS)?;
+                    self.line(&format!("{} operator({})({} other);", name, operator, name));
+                },
+            }
+        }
+        self.indent -= 1;
+        self.line("};");
+        self.classes.push(name);
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod generator_tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_programs_parse() {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        for _ in 0..500 {
+            let data = (0..4096).map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            }).collect::<Vec<u8>>();
+            let source = program(&mut Unstructured::new(&data)).unwrap();
+            if let Err(err) = check(&source) {
+                panic!("{}\n{}", err, source);
+            }
+        }
+    }
+}
@@ -1,14 +1,16 @@
+use std::fmt;
+
+use crate::literal::{CharLiteral, NumberLiteral, StringLiteral};
 use crate::logos_lexer::Token;
 
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AstNode {
     None,
     Header(Header),
     HeaderStatement(HeaderStatement),
     Preprocessor(Preprocessor),
-    TypedefType(TypedefType),
-    Typedef(Typedef),
     Struct(Struct),
     Union(Union),
     Enum(Enum),
@@ -17,19 +19,22 @@ pub enum AstNode {
     VariableArray(VariableArray),
     Variable(Variable),
     VariableList(VariableList),
+    Member(Member),
+    BitFieldList(BitFieldList),
+    Definition(Definition),
+    Parameter(Parameter),
     FunctionArgument(FunctionArgument),
     FunctionPrototype(FunctionPrototype),
     Function(Function),
     Class(Class),
     ClassMember(ClassMember),
     Method(Method),
-    BaseType(BaseType),
-    CompositeType(CompositeType),
-    TypeType(TypeType),
+    DeclarationSpecifiers(DeclarationSpecifiers),
+    StorageClass(StorageClass),
+    TypeSpecifier(TypeSpecifier),
+    TypeQualifiers(TypeQualifiers),
     Type(Type),
-    TypeModifier(TypeModifier),
-    PrefixTypeModifier(PrefixTypeModifier),
-    SuffixTypeModifier(SuffixTypeModifier),
+    DeclaredType(DeclaredType),
     CodeBlock(CodeBlock),
     StatementList(StatementList),
     BlockOrStatement(BlockOrStatement),
@@ -44,18 +49,22 @@ pub enum AstNode {
     Initializer(Initializer),
     Designator(Designator),
     TaggedUnion(TaggedUnion),
+    StaticAssert(StaticAssert),
+    GenericAssociation(GenericAssociation),
+    Attribute(Attribute),
 
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Header {
     pub statements: Vec<HeaderStatement>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum HeaderStatement {
     Preprocessor(Preprocessor),
-    Typedef(Typedef),
     Struct(Struct),
     Union(Union),
     Enum(Enum),
@@ -64,80 +73,210 @@ pub enum HeaderStatement {
     Function(Function),
     Class(Class),
     TaggedUnion(TaggedUnion),
+    StaticAssert(StaticAssert),
     Whitespace,
 }
 
+impl HeaderStatement {
+    /// Attaches a doc comment to the declaration, if it is one that can carry documentation.
+    pub fn set_doc(&mut self, text: Option<String>) {
+        match self {
+            HeaderStatement::Struct(struct_) => struct_.doc = text,
+            HeaderStatement::Union(union_) => union_.doc = text,
+            HeaderStatement::Enum(enum_) => enum_.doc = text,
+            HeaderStatement::Variable(variable_list) => variable_list.set_doc(text),
+            HeaderStatement::FunctionPrototype(prototype) => prototype.doc = text,
+            HeaderStatement::Function(function) => function.doc = text,
+            HeaderStatement::Class(class) => class.doc = text,
+            HeaderStatement::TaggedUnion(tagged) => tagged.doc = text,
+            HeaderStatement::Preprocessor(_) | HeaderStatement::StaticAssert(_) |
+            HeaderStatement::Whitespace => {},
+        }
+    }
+
+    /// The name the statement declares, or the first one for a list of variables.
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            HeaderStatement::Struct(Struct {name, ..}) | HeaderStatement::Union(Union {name, ..}) |
+            HeaderStatement::Enum(Enum {name, ..}) => name.as_deref(),
+            HeaderStatement::Variable(variable_list) => variable_list.variables.first().map(|variable| variable.name.as_str()),
+            HeaderStatement::FunctionPrototype(FunctionPrototype {name, ..}) | HeaderStatement::Function(Function {name, ..}) |
+            HeaderStatement::Class(Class {name, ..}) | HeaderStatement::TaggedUnion(TaggedUnion {name, ..}) => Some(name),
+            HeaderStatement::Preprocessor(_) | HeaderStatement::StaticAssert(_) | HeaderStatement::Whitespace => None,
+        }
+    }
+}
+
+/// A problem that checking a header found. The tree has no positions, so a finding is
+/// placed by the top-level statement it is in and the name it is about.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Finding {
+    pub message: String,
+    /// The index of the statement in `Header::statements`.
+    pub statement: usize,
+    /// The name, or words such as `case 2`, that the finding is about.
+    pub name: Option<String>,
+}
+
+impl Finding {
+    pub fn new(message: String, statement: usize, name: Option<&str>) -> Finding {
+        Finding {message, statement, name: name.map(str::to_string)}
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl From<Finding> for String {
+    fn from(finding: Finding) -> String {
+        finding.message
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Preprocessor {
     pub value: String,
 }
 
+/// A `static_assert` declaration. C23 allows leaving out the message.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StaticAssert {
+    pub condition: Expression,
+    pub message: Option<StringLiteral>,
+}
+
+
+/// A struct definition; `name` is `None` for an anonymous struct.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Struct {
+    pub name: Option<String>,
+    pub attributes: Vec<Attribute>,
+    pub members: Vec<Member>,
+    pub doc: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Union {
+    pub name: Option<String>,
+    pub attributes: Vec<Attribute>,
+    pub members: Vec<Member>,
+    pub doc: Option<String>,
+}
 
+/// A member of a struct, union or tagged union payload.
 #[derive(Debug, Clone, PartialEq)]
-pub enum TypedefType {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Member {
+    Variables(VariableList),
+    BitFields(BitFieldList),
+    /// A struct defined inside another without declaring a member of its type. When it has
+    /// no name it is a C11 anonymous struct and its members belong to the enclosing type.
     Struct(Struct),
     Union(Union),
-    Enum(Enum),
-    Variable(Variable),
-    FunctionPrototype(FunctionPrototype),
-    Function(Function),
-    Class(Class),
-    Type(Type),
-    PointerType(Type, usize),
-    ArrayType(Type, String),
 }
 
-#[derive(Debug, Clone, PartialEq)]
-pub struct Typedef {
-    pub r#type: TypedefType,
-    pub name: String,
+impl Member {
+    pub fn set_doc(&mut self, text: Option<String>) {
+        match self {
+            Member::Variables(variable_list) => variable_list.set_doc(text),
+            Member::BitFields(bit_fields) => bit_fields.doc = text,
+            Member::Struct(struct_) => struct_.doc = text,
+            Member::Union(union_) => union_.doc = text,
+        }
+    }
 }
 
+/// A declaration of bit-fields such as `unsigned ready : 1, : 3, mode : 4;`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BitFieldList {
+    pub specifiers: DeclarationSpecifiers,
+    pub fields: Vec<BitField>,
+    pub doc: Option<String>,
+}
 
+/// A bit-field; unnamed bit-fields only pad, and a zero width one ends the current unit.
 #[derive(Debug, Clone, PartialEq)]
-pub struct Struct {
-    pub name: String,
-    pub members: Vec<VariableList>,
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BitField {
+    pub name: Option<String>,
+    pub width: Expression,
 }
 
+/// A struct, union, enum or tagged union defined in the specifiers of a declaration,
+/// as in `typedef struct { int x, y; } point;`.
 #[derive(Debug, Clone, PartialEq)]
-pub struct Union {
-    pub name: String,
-    pub members: Vec<VariableList>,
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Definition {
+    Struct(Struct),
+    Union(Union),
+    Enum(Enum),
+    TaggedUnion(TaggedUnion),
+}
+
+impl Definition {
+    pub fn attributes_mut(&mut self) -> &mut Vec<Attribute> {
+        match self {
+            Definition::Struct(struct_) => &mut struct_.attributes,
+            Definition::Union(union_) => &mut union_.attributes,
+            Definition::Enum(enum_) => &mut enum_.attributes,
+            Definition::TaggedUnion(tagged) => &mut tagged.attributes,
+        }
+    }
 }
 
 #[derive(Debug, Clone,  PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Enum {
-    pub name: String,
+    pub name: Option<String>,
+    pub attributes: Vec<Attribute>,
     pub members: Vec<EnumMember>,
+    pub doc: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EnumMember {
     pub name: String,
+    pub attributes: Vec<Attribute>,
     pub value: Option<Expression>,
+    pub doc: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TaggedUnion {
     pub name: String,
+    pub attributes: Vec<Attribute>,
     pub members: Vec<TaggedUnionMember>,
+    pub doc: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TaggedUnionMember {
     pub name: String,
-    pub value: Option<Vec<VariableList>>,
+    pub value: Option<Vec<Member>>,
+    pub doc: Option<String>,
 }
 
 
 #[derive(Debug, Clone,  PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum VariableValue {
     Expression(Expression),
-    String(String),
+    String(StringLiteral),
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum VariableArray {
     Size(Expression),
     NoSize,
@@ -145,241 +284,469 @@ pub enum VariableArray {
 }
 
 #[derive(Debug, Clone, PartialEq)]
-pub enum Variable {
-    BasicVar {
-        name: String,
-        pointer: usize,
-        restrict: bool,
-        array: Option<Vec<VariableArray>>,
-        value: Option<VariableValue>,
-    },
-    FunctionPointer {
-        return_type: Type,
-        return_pointer: usize,
-        pointer: usize,
-        name: Option<String>,
-        array: Option<Vec<VariableArray>>,
-        arguments: Vec<FunctionArgument>,
-    }, 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Variable {
+    pub name: String,
+    /// The attributes written after this declarator, as in `int x [[maybe_unused]];`.
+    pub attributes: Vec<Attribute>,
+    pub type_: DeclaredType,
+    pub value: Option<VariableValue>,
 }
 
 
 #[derive(Debug, Clone, PartialEq)]
-pub enum VariableList {
-    BasicVars {
-        type_: Type,
-        generic: bool,
-        variables: Vec<Variable>,
-    },
-    FunctionPointer(Variable),
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VariableList {
+    pub specifiers: DeclarationSpecifiers,
+    pub generic: bool,
+    pub variables: Vec<Variable>,
+    pub doc: Option<String>,
+}
+
+impl VariableList {
+    pub fn set_doc(&mut self, text: Option<String>) {
+        self.doc = text;
+    }
+}
+
+/// A function parameter; abstract declarators such as `char *` have no name.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Parameter {
+    pub register: bool,
+    pub attributes: Vec<Attribute>,
+    pub name: Option<String>,
+    pub type_: DeclaredType,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum FunctionArgument {
-    FunctionPointer(Variable),
-    Variable(Type, Variable),
-    Type(Type,usize),
+    Parameter(Parameter),
     Ellipsis,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FunctionPrototype {
-    pub return_type: Type,
-    pub return_pointer: usize,
+    pub specifiers: DeclarationSpecifiers,
+    pub return_type: DeclaredType,
     pub name: String,
     pub arguments: Vec<FunctionArgument>,
+    pub doc: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Function {
-    pub inline: bool,
-    pub static_: bool,
+    pub specifiers: DeclarationSpecifiers,
     pub generic: bool,
-    pub return_type: Type,
-    pub return_pointer: usize,
+    pub return_type: DeclaredType,
     pub name: String,
     pub arguments: Vec<FunctionArgument>,
     pub body: CodeBlock,
+    pub doc: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Class {
     pub name: String,
+    pub attributes: Vec<Attribute>,
     pub generic: Option<String>,
     pub abstract_: bool,
     pub parent: Option<String>,
     pub members: Vec<ClassMember>,
+    pub doc: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ClassMember {
-    Variable(VariableList),
+    Field(Member),
     Method(Method),
     OperatorOverload(OperatorOverload),
 }
+impl ClassMember {
+    pub fn set_doc(&mut self, text: Option<String>) {
+        match self {
+            ClassMember::Field(member) => member.set_doc(text),
+            ClassMember::Method(Method::Normal(function)) => function.doc = text,
+            ClassMember::Method(Method::Abstract(prototype)) => prototype.doc = text,
+            ClassMember::OperatorOverload(OperatorOverload::Normal { doc, .. }) |
+            ClassMember::OperatorOverload(OperatorOverload::Abstract { doc, .. }) => *doc = text,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum OperatorOverload {
     Normal {
-        return_type: Type,
-        return_pointer: usize,
+        attributes: Vec<Attribute>,
+        return_type: DeclaredType,
         op: String,
         arguments: Vec<FunctionArgument>,
         body: CodeBlock,
+        doc: Option<String>,
     },
     Abstract {
-        return_type: Type,
-        return_pointer: usize,
+        attributes: Vec<Attribute>,
+        return_type: DeclaredType,
         op: String,
         arguments: Vec<FunctionArgument>,
+        doc: Option<String>,
     }
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Method {
     Normal(Function),
     Abstract(FunctionPrototype),
 }
 
+/// The declaration specifiers in front of a declarator, e.g. `static const unsigned long`.
 #[derive(Debug, Clone, PartialEq)]
-pub enum Types {
-    Void,
-    Char,
-    Short,
-    Int,
-    Long,
-    Float,
-    Double,
-    Signed,
-    Unsigned,
-    Bool,
-    Struct(String),
-    Union(String),
-    Enum(String),
-    Pointer(Box<Type>),
-    Array(Box<Type>, usize,Option<String>),
-    FunctionPointer(Box<Type>, Vec<FunctionArgument>),
-    Class(String),
-    Identifier(String),
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeclarationSpecifiers {
+    pub storage: Option<StorageClass>,
+    pub thread_local: bool,
+    pub constexpr: bool,
+    pub inline: bool,
+    pub noreturn: bool,
+    /// The operands of any `alignas` specifiers; the strictest one applies.
+    pub alignas: Vec<TypeOrExpression>,
+    /// The attributes written before or among the specifiers. They apply to every
+    /// name the declaration declares.
+    pub attributes: Vec<Attribute>,
+    pub type_: Type,
+    /// The type defined in place by these specifiers, if any.
+    pub definition: Option<Box<Definition>>,
+}
+
+/// Which syntax an attribute was written in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AttributeSyntax {
+    /// A C23 attribute such as `[[nodiscard]]` or `[[gnu::packed]]`.
+    Standard,
+    /// A GNU attribute such as `__attribute__((packed))`.
+    Gnu,
+}
+
+/// One attribute of an attribute specifier. The arguments are kept as the tokens
+/// between the parentheses, since each attribute gives them its own meaning.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Attribute {
+    pub syntax: AttributeSyntax,
+    /// The namespace of a C23 attribute, such as `gnu` in `[[gnu::packed]]`.
+    pub prefix: Option<String>,
+    pub name: String,
+    pub arguments: Option<Vec<Token>>,
+}
+
+impl Attribute {
+    /// Whether this is the attribute `name`, ignoring the `__name__` spelling and
+    /// the `gnu` namespace.
+    pub fn is(&self, name: &str) -> bool {
+        let unwrap = |word: &str| word.strip_prefix("__").and_then(|word| word.strip_suffix("__")).map(str::to_string)
+            .unwrap_or_else(|| word.to_string());
+        match self.prefix.as_deref().map(unwrap).as_deref() {
+            None | Some("gnu") => unwrap(&self.name) == name,
+            Some(_) => false,
+        }
+    }
+
+    /// The string literal argument of an attribute such as `[[deprecated("use g")]]`.
+    pub fn message(&self) -> Option<String> {
+        match self.arguments.as_deref() {
+            Some([Token::String(message)]) => Some(message.to_text()),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Attribute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.syntax {
+            AttributeSyntax::Standard => write!(f, "[[")?,
+            AttributeSyntax::Gnu => write!(f, "__attribute__((")?,
+        }
+        if let Some(prefix) = &self.prefix {
+            write!(f, "{}::", prefix)?;
+        }
+        write!(f, "{}", self.name)?;
+        if let Some(arguments) = &self.arguments {
+            write!(f, "(")?;
+            for (i, token) in arguments.iter().enumerate() {
+                let joined = i == 0 || matches!(token, Token::Comma | Token::RightParen | Token::LeftParen) ||
+                    matches!(arguments[i - 1], Token::LeftParen);
+                if !joined {
+                    write!(f, " ")?;
+                }
+                write!(f, "{}", token)?;
+            }
+            write!(f, ")")?;
+        }
+        match self.syntax {
+            AttributeSyntax::Standard => write!(f, "]]"),
+            AttributeSyntax::Gnu => write!(f, "))"),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StorageClass {
+    Typedef,
+    Extern,
+    Static,
+    Auto,
+    Register,
 }
 
+/// A type specifier after the keywords that spell it have been combined,
+/// so `long unsigned int` and `unsigned long` are both `UnsignedLong`.
 #[derive(Debug, Clone, PartialEq)]
-pub enum BaseType {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TypeSpecifier {
     Void,
+    Bool,
     Char,
+    SignedChar,
+    UnsignedChar,
     Short,
+    UnsignedShort,
     Int,
+    UnsignedInt,
     Long,
+    UnsignedLong,
+    LongLong,
+    UnsignedLongLong,
     Float,
     Double,
-    Signed,
-    Unsigned,
-    Bool,
-}
-
-
-#[derive(Debug, Clone, PartialEq)]
-pub enum CompositeType {
-    Mixed(Vec<BaseType>),
-    Struct(String),
-    Union(String),
-    Enum(String),
-    Pointer(Box<Type>, usize),
-    Array(Box<Type>, usize),//type, pointer amount, array amount
-    FunctionPointer(Box<Type>, Vec<FunctionArgument>),
-    Identifier(String),
-}
-
-
-#[derive(Debug, Clone, PartialEq)]
-pub enum TypeType {
-    BaseType(String),
-    CompositeType(CompositeType),
+    LongDouble,
+    FloatComplex,
+    DoubleComplex,
+    LongDoubleComplex,
+    FloatImaginary,
+    DoubleImaginary,
+    LongDoubleImaginary,
+    /// A struct by its tag, or `None` for the anonymous struct defined by the same specifiers.
+    Struct(Option<String>),
+    Union(Option<String>),
+    Enum(Option<String>),
+    Tagged(String),
+    /// A typedef or class name.
+    Named(String),
+    Generic,
+    /// The type of an expression or type name, as in `typeof(x)`.
+    Typeof(Box<TypeOrExpression>),
+    /// Like `Typeof`, but without the qualifiers of the operand's type.
+    TypeofUnqual(Box<TypeOrExpression>),
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TypeQualifiers {
+    pub const_: bool,
+    pub volatile: bool,
+    pub restrict: bool,
+    pub atomic: bool,
+}
+
+impl TypeQualifiers {
+    pub fn is_empty(&self) -> bool {
+        *self == TypeQualifiers::default()
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Type {
-    types: TypeType,
+    pub specifier: TypeSpecifier,
+    pub qualifiers: TypeQualifiers,
 }
 
-
 impl Type {
-    pub fn from_token(token: Token) -> Result<Self,String> {
-        match token {
-            Token::Type(types) => Ok(Self {
-                types: TypeType::BaseType(types),
-            }),
-            Token::Word(name) => Ok(Self {
-                types: TypeType::CompositeType(CompositeType::Identifier(name)),
-            }),
-            Token::Generic => Ok(Self {
-                types: TypeType::BaseType("Generic".to_string()),
-            }),
-            _ => Err(format!("Expected type, got {:?}",token)),
+    pub fn new(specifier: TypeSpecifier) -> Self {
+        Self {
+            specifier,
+            qualifiers: TypeQualifiers::default(),
         }
     }
 }
 
+/// The full type of a declared name: the base type of the declaration specifiers
+/// wrapped in the pointers, arrays and functions of its declarator, outermost first.
+/// `int *(*fp[4])(char)` is an array of pointers to functions returning `int *`.
 #[derive(Debug, Clone, PartialEq)]
-pub enum TypeModifier {
-    Prefix(PrefixTypeModifier),
-    Suffix(SuffixTypeModifier),
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DeclaredType {
+    Base(Type),
+    Pointer(Box<DeclaredType>, TypeQualifiers),
+    Array(Box<DeclaredType>, VariableArray),
+    Function(Box<DeclaredType>, Vec<FunctionArgument>),
 }
 
-#[derive(Debug, Clone, PartialEq)]
-pub enum PrefixTypeModifier {
-    Const,
-    Volatile,
-    Restrict,
-    Atomic,
-    Static,
-    Extern,
-    Register,
+impl DeclaredType {
+    /// The type named by the declaration specifiers underneath every declarator.
+    pub fn base(&self) -> &Type {
+        match self {
+            DeclaredType::Base(type_) => type_,
+            DeclaredType::Pointer(inner, _) | DeclaredType::Array(inner, _) |
+            DeclaredType::Function(inner, _) => inner.base(),
+        }
+    }
+
+    pub fn is_function(&self) -> bool {
+        matches!(self, DeclaredType::Function(..))
+    }
 }
 
-#[derive(Debug, Clone, PartialEq)]
-pub enum SuffixTypeModifier {
-    Restrict,
+/// The keywords of a type specifier in the order they were written.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TypeKeyword {
+    Void,
+    Bool,
+    Char,
+    Short,
+    Int,
+    Long,
+    Float,
+    Double,
+    Signed,
+    Unsigned,
     Complex,
     Imaginary,
 }
 
+impl TypeSpecifier {
+    /// Whether the type is an integer type; typedef names and the types of
+    /// expressions are assumed to be.
+    pub fn is_integer(&self) -> bool {
+        match self {
+            TypeSpecifier::Typeof(operand) | TypeSpecifier::TypeofUnqual(operand) => match operand.as_ref() {
+                TypeOrExpression::Type(type_) => matches!(type_.as_ref(), DeclaredType::Base(base) if base.specifier.is_integer()),
+                TypeOrExpression::Expression(_) => true,
+            },
+            _ => matches!(self,
+                TypeSpecifier::Bool | TypeSpecifier::Char | TypeSpecifier::SignedChar |
+                TypeSpecifier::UnsignedChar | TypeSpecifier::Short | TypeSpecifier::UnsignedShort |
+                TypeSpecifier::Int | TypeSpecifier::UnsignedInt | TypeSpecifier::Long |
+                TypeSpecifier::UnsignedLong | TypeSpecifier::LongLong | TypeSpecifier::UnsignedLongLong |
+                TypeSpecifier::Enum(_) | TypeSpecifier::Named(_)),
+        }
+    }
+
+    /// Combines type specifier keywords as C allows them to be combined, in any order.
+    pub fn from_keywords(keywords: &[TypeKeyword]) -> Result<Self, String> {
+        let count = |keyword| keywords.iter().filter(|k| **k == keyword).count();
+        let (signed, unsigned) = (count(TypeKeyword::Signed), count(TypeKeyword::Unsigned));
+        let (complex, imaginary) = (count(TypeKeyword::Complex), count(TypeKeyword::Imaginary));
+        let (long, int) = (count(TypeKeyword::Long), count(TypeKeyword::Int));
+        let base = keywords.iter()
+            .filter(|k| !matches!(k, TypeKeyword::Signed | TypeKeyword::Unsigned | TypeKeyword::Complex |
+                                  TypeKeyword::Imaginary | TypeKeyword::Long | TypeKeyword::Int))
+            .copied()
+            .collect::<Vec<TypeKeyword>>();
+        let invalid = || Err(format!("Invalid combination of type specifiers: {:?}", keywords));
+
+        if signed + unsigned > 1 || complex + imaginary > 1 || long > 2 || int > 1 || base.len() > 1 {
+            return invalid();
+        }
+        if int == 1 && !matches!(base.first(), None | Some(TypeKeyword::Short)) {
+            return invalid();
+        }
+        if complex + imaginary == 1 {
+            let floating = match (base.first(), long) {
+                (Some(TypeKeyword::Float), 0) => 0,
+                (Some(TypeKeyword::Double), 0) => 1,
+                (Some(TypeKeyword::Double), 1) => 2,
+                _ => return invalid(),
+            };
+            if signed + unsigned != 0 {
+                return invalid();
+            }
+            let specifier = match (complex, floating) {
+                (1, 0) => TypeSpecifier::FloatComplex,
+                (1, 1) => TypeSpecifier::DoubleComplex,
+                (1, _) => TypeSpecifier::LongDoubleComplex,
+                (_, 0) => TypeSpecifier::FloatImaginary,
+                (_, 1) => TypeSpecifier::DoubleImaginary,
+                _ => TypeSpecifier::LongDoubleImaginary,
+            };
+            return Ok(specifier);
+        }
+
+        let specifier = match (base.first(), long, unsigned == 1, signed == 1) {
+            (Some(TypeKeyword::Void), 0, false, false) => TypeSpecifier::Void,
+            (Some(TypeKeyword::Bool), 0, false, false) => TypeSpecifier::Bool,
+            (Some(TypeKeyword::Float), 0, false, false) => TypeSpecifier::Float,
+            (Some(TypeKeyword::Double), 0, false, false) => TypeSpecifier::Double,
+            (Some(TypeKeyword::Double), 1, false, false) => TypeSpecifier::LongDouble,
+            (Some(TypeKeyword::Char), 0, false, false) => TypeSpecifier::Char,
+            (Some(TypeKeyword::Char), 0, false, true) => TypeSpecifier::SignedChar,
+            (Some(TypeKeyword::Char), 0, true, false) => TypeSpecifier::UnsignedChar,
+            (Some(TypeKeyword::Short), 0, false, _) => TypeSpecifier::Short,
+            (Some(TypeKeyword::Short), 0, true, _) => TypeSpecifier::UnsignedShort,
+            (None, 0, false, _) if !keywords.is_empty() => TypeSpecifier::Int,
+            (None, 0, true, _) => TypeSpecifier::UnsignedInt,
+            (None, 1, false, _) => TypeSpecifier::Long,
+            (None, 1, true, _) => TypeSpecifier::UnsignedLong,
+            (None, 2, false, _) => TypeSpecifier::LongLong,
+            (None, 2, true, _) => TypeSpecifier::UnsignedLongLong,
+            _ => return invalid(),
+        };
+        Ok(specifier)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CodeBlock {
     Code(StatementList),
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StatementList {
     pub statements: Vec<Statement>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BlockOrStatement {
     Block(CodeBlock),
     Statement(Statement),
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum VariableListOrStatement {
     VariableList(VariableList),
     Statement(Statement),
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TypeOrExpression {
-    Type(Type,usize),
+    Type(Box<DeclaredType>),
     Expression(Box<Expression>),
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Statement {
     Preprocessor(Preprocessor),
-    Comment(String),
     VariableList(VariableList),
     Expression(Expression),
     Return(Expression),
-    If(Expression, Box<BlockOrStatement>),
-    Else(Box<BlockOrStatement>),
+    /// An `if` and its `else` branch, if it has one.
+    If(Expression, Box<BlockOrStatement>, Option<Box<BlockOrStatement>>),
     While(Expression, Box<BlockOrStatement>),
     DoWhile(Expression, Box<BlockOrStatement>),
     For(
@@ -389,6 +756,11 @@ pub enum Statement {
         Box<BlockOrStatement>,
         ),
     Switch(Expression, Vec<SwitchCase>),
+    StaticAssert(StaticAssert),
+    /// An attribute declaration such as `[[fallthrough]];`.
+    Attributes(Vec<Attribute>),
+    /// A statement with attributes in front of it.
+    Attributed(Vec<Attribute>, Box<Statement>),
     Break,
     Continue,
     Goto(String),
@@ -397,6 +769,7 @@ pub enum Statement {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SwitchCase {
     pub default: bool,
     pub expression: Option<Expression>,
@@ -404,16 +777,23 @@ pub struct SwitchCase {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Expression {
     Blank,
     Identifier(String),
     Literal(Literal),
     Sizeof(TypeOrExpression),
-    Alignof(Type, usize),
+    Alignof(Box<DeclaredType>),
+    /// `offsetof(type, member)`, which is a macro in C but parsed as an operator here.
+    Offsetof(Box<DeclaredType>, String),
     Unary(UnaryOperator, Box<Expression>),
     Binary(BinaryOperator, Box<Expression>, Box<Expression>),
     Ternary(Box<Expression>, Box<Expression>, Box<Expression>),
     CallFunction(String, Option<Box<Expression>>),
+    /// A call through an expression other than a name, such as `_Generic(x, float: sinf, default: sin)(x)`.
+    Call(Box<Expression>, Option<Box<Expression>>),
+    /// A `_Generic` selection: the controlling expression and its associations.
+    Generic(Box<Expression>, Vec<GenericAssociation>),
     //CallMethod(bool, String, String, Option<Box<Expression>>),
     InitializerList(Box<Expression>),
     TaggedInitializer(String, Box<Expression>),
@@ -428,10 +808,11 @@ impl Expression {
     pub fn get_value(&self) -> Option<String> {
         match self {
             Expression::Literal(literal) => match literal {
-                Literal::Number(number) => Some(number.clone()),
-                Literal::Char(character) => Some(character.clone()),
-                Literal::String(string) => Some(string.clone()),
+                Literal::Number(number) => Some(number.raw.clone()),
+                Literal::Char(character) => Some(character.raw.clone()),
+                Literal::String(string) => Some(string.raw.clone()),
                 Literal::Bool(boolean) => Some(boolean.to_string()),
+                Literal::Nullptr => Some("nullptr".to_string()),
             },
             Expression::Identifier(name) => Some(name.clone()),
             _ => None,
@@ -440,15 +821,26 @@ impl Expression {
 
 }
 
+/// One association of a `_Generic` selection; `default` has no type.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GenericAssociation {
+    pub type_: Option<DeclaredType>,
+    pub expression: Expression,
+}
+
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Literal {
-    Number(String),
-    Char(String),
-    String(String),
+    Number(NumberLiteral),
+    Char(CharLiteral),
+    String(StringLiteral),
     Bool(bool),
+    Nullptr,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum UnaryOperator {
     Plus,
     Minus,
@@ -461,11 +853,12 @@ pub enum UnaryOperator {
     PreDecrement,
     PostIncrement,
     PostDecrement,
-    Cast(Type,usize),
+    Cast(Box<DeclaredType>),
     MemberSet
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BinaryOperator {
     Add,
     Subtract,
@@ -506,6 +899,7 @@ pub enum BinaryOperator {
 
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Initializer {
     Expression(Box<Expression>),
     List(Vec<Initializer>),
@@ -513,6 +907,7 @@ pub enum Initializer {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Designator {
     Member(String),
     Index(Box<Expression>),
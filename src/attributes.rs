@@ -0,0 +1,847 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::*;
+use crate::scoped::ScopedMap;
+
+
+/// What an attribute is written on, for checking where the standard attributes may go.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Target {
+    Function,
+    Type,
+    Object,
+    Statement,
+}
+
+fn nodiscard(attributes: &[Attribute]) -> Option<&Attribute> {
+    attributes.iter().find(|attribute| attribute.is("nodiscard") || attribute.is("warn_unused_result"))
+}
+
+fn deprecated(attributes: &[Attribute]) -> Option<&Attribute> {
+    attributes.iter().find(|attribute| attribute.is("deprecated"))
+}
+
+fn maybe_unused(attributes: &[Attribute]) -> bool {
+    attributes.iter().any(|attribute| attribute.is("maybe_unused") || attribute.is("unused"))
+}
+
+fn is_fallthrough(statement: &Statement) -> bool {
+    matches!(statement, Statement::Attributes(attributes) if attributes.iter().any(|attribute| attribute.is("fallthrough")))
+}
+
+/// Adds the reason an attribute gives, if any, to a warning.
+fn with_reason(warning: String, attribute: &Attribute) -> String {
+    match attribute.message() {
+        Some(reason) => format!("{}: {}", warning, reason),
+        None => warning,
+    }
+}
+
+/// Checks the arguments of the standard attributes and that each is written on
+/// something it can apply to. Other attributes are left alone.
+fn check_attributes(attributes: &[Attribute], target: Target, name: &str) -> Result<(), String> {
+    for attribute in attributes {
+        let takes_reason = attribute.is("nodiscard") || attribute.is("deprecated");
+        let takes_nothing = ["maybe_unused", "fallthrough", "noreturn", "unsequenced", "reproducible"]
+            .iter().any(|standard| attribute.is(standard));
+        if attribute.arguments.is_some() && (takes_nothing || (takes_reason && attribute.message().is_none())) {
+            return Err(format!("Invalid arguments to {} on {}", attribute, name));
+        }
+
+        let allowed = if attribute.is("fallthrough") {
+            false
+        }
+        else if attribute.is("nodiscard") {
+            matches!(target, Target::Function | Target::Type)
+        }
+        else if attribute.is("noreturn") || attribute.is("unsequenced") || attribute.is("reproducible") {
+            target == Target::Function
+        }
+        else if attribute.is("deprecated") || attribute.is("maybe_unused") {
+            target != Target::Statement
+        }
+        else {
+            true
+        };
+        if !allowed {
+            return Err(format!("{} cannot be used on {}", attribute, name));
+        }
+    }
+    Ok(())
+}
+
+/// Collects the `[[fallthrough]];` declarations that end a list of statements,
+/// looking into trailing blocks and both arms of a trailing `if`/`else`.
+fn trailing_fallthroughs<'a>(statements: &'a [Statement], found: &mut Vec<&'a Statement>) {
+    let Some(last) = statements.last() else {
+        return;
+    };
+    match last {
+        statement if is_fallthrough(statement) => found.push(statement),
+        Statement::Block(block) => {
+            let CodeBlock::Code(list) = block.as_ref();
+            trailing_fallthroughs(&list.statements, found);
+        },
+        Statement::Attributed(_, statement) => trailing_fallthroughs(std::slice::from_ref(statement), found),
+        Statement::If(_, body, else_) => {
+            trailing_body_fallthroughs(body, found);
+            if let Some(else_) = else_ {
+                trailing_body_fallthroughs(else_, found);
+            }
+        },
+        _ => {},
+    }
+}
+
+fn trailing_body_fallthroughs<'a>(body: &'a BlockOrStatement, found: &mut Vec<&'a Statement>) {
+    match body {
+        BlockOrStatement::Block(CodeBlock::Code(list)) => trailing_fallthroughs(&list.statements, found),
+        BlockOrStatement::Statement(statement) => trailing_fallthroughs(std::slice::from_ref(statement), found),
+    }
+}
+
+/// What the checker remembers about a declared name.
+#[derive(Debug, Clone)]
+struct Entity {
+    attributes: Vec<Attribute>,
+    type_: DeclaredType,
+    noreturn: bool,
+}
+
+/// The names visible at some point. Tags of structs, unions, enums, tagged unions
+/// and classes share one namespace here.
+#[derive(Debug, Default)]
+struct Scope {
+    names: ScopedMap<Entity>,
+    tags: ScopedMap<Vec<Attribute>>,
+    /// The attributes of each class's methods.
+    methods: ScopedMap<HashMap<String, Vec<Attribute>>>,
+}
+
+impl Scope {
+    /// Starts a block whose names are forgotten when it is closed.
+    fn open(&mut self) {
+        self.names.open();
+        self.tags.open();
+        self.methods.open();
+    }
+
+    fn close(&mut self) {
+        self.names.close();
+        self.tags.close();
+        self.methods.close();
+    }
+
+    /// The attributes of the method a member call refers to, if the object's class is known.
+    fn method(&self, object: &Expression, method: &str) -> Option<&Vec<Attribute>> {
+        let Expression::Identifier(name) = object else {
+            return None;
+        };
+        let class = match &self.names.get(name)?.type_ {
+            DeclaredType::Base(base) => &base.specifier,
+            DeclaredType::Pointer(inner, _) => match inner.as_ref() {
+                DeclaredType::Base(base) => &base.specifier,
+                _ => return None,
+            },
+            _ => return None,
+        };
+        match class {
+            TypeSpecifier::Named(class) => self.methods.get(class)?.get(method),
+            _ => None,
+        }
+    }
+}
+
+/// The locals and parameters of a function and the names its body uses.
+#[derive(Debug, Default)]
+struct Usage {
+    declared: Vec<(String, bool)>,
+    used: HashSet<String>,
+}
+
+#[derive(Debug, Default)]
+struct Checker<'a> {
+    warnings: Vec<Finding>,
+    /// The index of the top-level statement being checked.
+    statement: usize,
+    /// The `[[fallthrough]];` declarations that come right before a case label.
+    fallthroughs: Vec<&'a Statement>,
+}
+
+/// Checks the attributes in a header and returns the warnings the standard ones ask
+/// for: ignored results of `nodiscard` functions, uses of `deprecated` entities,
+/// unused locals and parameters that are not `maybe_unused`, and `switch` cases
+/// that fall through without `[[fallthrough]]`. An attribute in the wrong place or
+/// with the wrong arguments is an error.
+pub fn check(header: &Header) -> Result<Vec<Finding>, Finding> {
+    let mut checker = Checker::default();
+    let mut scope = Scope::default();
+    for (index, statement) in header.statements.iter().enumerate() {
+        checker.statement = index;
+        checker.header_statement(&mut scope, statement).map_err(|message| Finding::new(message, index, statement.name()))?;
+    }
+    Ok(checker.warnings)
+}
+
+impl<'a> Checker<'a> {
+    fn header_statement(&mut self, scope: &mut Scope, statement: &'a HeaderStatement) -> Result<(), String> {
+        match statement {
+            HeaderStatement::Struct(struct_) => self.define_struct(scope, struct_)?,
+            HeaderStatement::Union(union_) => self.define_union(scope, union_)?,
+            HeaderStatement::Enum(enum_) => self.define_enum(scope, enum_)?,
+            HeaderStatement::TaggedUnion(tagged) => self.define_tagged_union(scope, tagged)?,
+            HeaderStatement::Class(class) => self.class(scope, class)?,
+            HeaderStatement::Variable(variable_list) => self.declaration(scope, variable_list, &mut Usage::default())?,
+            HeaderStatement::FunctionPrototype(prototype) => {
+                self.function(scope, &prototype.specifiers, &prototype.return_type, &prototype.name, &prototype.arguments)?;
+            },
+            HeaderStatement::Function(function) => {
+                self.function(scope, &function.specifiers, &function.return_type, &function.name, &function.arguments)?;
+                self.body(scope, &function.arguments, &function.body)?;
+            },
+            HeaderStatement::StaticAssert(assertion) => self.expression(scope, &assertion.condition, &mut Usage::default()),
+            HeaderStatement::Preprocessor(_) | HeaderStatement::Whitespace => {},
+        }
+        Ok(())
+    }
+
+    /// Warns about a name, or the words of a label, in the statement being checked.
+    fn warn(&mut self, message: String, name: &str) {
+        self.warnings.push(Finding::new(message, self.statement, Some(name)));
+    }
+
+    fn deprecated_use(&mut self, name: &str, attributes: &[Attribute]) {
+        if let Some(attribute) = deprecated(attributes) {
+            self.warn(with_reason(format!("{} is deprecated", name), attribute), name);
+        }
+    }
+
+    fn define(&mut self, scope: &mut Scope, definition: &Definition) -> Result<(), String> {
+        match definition {
+            Definition::Struct(struct_) => self.define_struct(scope, struct_),
+            Definition::Union(union_) => self.define_union(scope, union_),
+            Definition::Enum(enum_) => self.define_enum(scope, enum_),
+            Definition::TaggedUnion(tagged) => self.define_tagged_union(scope, tagged),
+        }
+    }
+
+    fn define_struct(&mut self, scope: &mut Scope, struct_: &Struct) -> Result<(), String> {
+        let name = struct_.name.as_deref().unwrap_or("an anonymous struct");
+        check_attributes(&struct_.attributes, Target::Type, name)?;
+        self.members(scope, &struct_.members)?;
+        if let Some(name) = &struct_.name {
+            scope.tags.insert(name.clone(), struct_.attributes.clone());
+        }
+        Ok(())
+    }
+
+    fn define_union(&mut self, scope: &mut Scope, union_: &Union) -> Result<(), String> {
+        let name = union_.name.as_deref().unwrap_or("an anonymous union");
+        check_attributes(&union_.attributes, Target::Type, name)?;
+        self.members(scope, &union_.members)?;
+        if let Some(name) = &union_.name {
+            scope.tags.insert(name.clone(), union_.attributes.clone());
+        }
+        Ok(())
+    }
+
+    fn define_enum(&mut self, scope: &mut Scope, enum_: &Enum) -> Result<(), String> {
+        check_attributes(&enum_.attributes, Target::Type, enum_.name.as_deref().unwrap_or("an anonymous enum"))?;
+        if let Some(name) = &enum_.name {
+            scope.tags.insert(name.clone(), enum_.attributes.clone());
+        }
+        for member in &enum_.members {
+            check_attributes(&member.attributes, Target::Object, &member.name)?;
+            if let Some(value) = &member.value {
+                self.expression(scope, value, &mut Usage::default());
+            }
+            scope.names.insert(member.name.clone(), Entity {
+                attributes: member.attributes.clone(),
+                type_: DeclaredType::Base(Type::new(TypeSpecifier::Int)),
+                noreturn: false,
+            });
+        }
+        Ok(())
+    }
+
+    fn define_tagged_union(&mut self, scope: &mut Scope, tagged: &TaggedUnion) -> Result<(), String> {
+        check_attributes(&tagged.attributes, Target::Type, &tagged.name)?;
+        for member in &tagged.members {
+            if let Some(members) = &member.value {
+                self.members(scope, members)?;
+            }
+        }
+        scope.tags.insert(tagged.name.clone(), tagged.attributes.clone());
+        Ok(())
+    }
+
+    fn members(&mut self, scope: &mut Scope, members: &[Member]) -> Result<(), String> {
+        for member in members {
+            match member {
+                Member::Variables(variable_list) => {
+                    self.specifiers(scope, &variable_list.specifiers, Target::Object, "a member")?;
+                    for variable in &variable_list.variables {
+                        check_attributes(&variable.attributes, Target::Object, &variable.name)?;
+                        self.use_declarator(scope, &variable.type_);
+                    }
+                },
+                Member::BitFields(bit_fields) => {
+                    self.specifiers(scope, &bit_fields.specifiers, Target::Object, "a bit-field")?;
+                },
+                Member::Struct(struct_) => self.define_struct(scope, struct_)?,
+                Member::Union(union_) => self.define_union(scope, union_)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the attributes of some declaration specifiers and the types they use,
+    /// defining any type they define.
+    fn specifiers(&mut self, scope: &mut Scope, specifiers: &DeclarationSpecifiers, target: Target, name: &str) -> Result<(), String> {
+        check_attributes(&specifiers.attributes, target, name)?;
+        match specifiers.definition.as_deref() {
+            Some(definition) => self.define(scope, definition),
+            None => {
+                self.use_type(scope, &DeclaredType::Base(specifiers.type_.clone()));
+                Ok(())
+            },
+        }
+    }
+
+    /// Warns about the deprecated types a type is built from.
+    fn use_type(&mut self, scope: &Scope, type_: &DeclaredType) {
+        match type_ {
+            DeclaredType::Base(base) => match &base.specifier {
+                TypeSpecifier::Struct(Some(tag)) | TypeSpecifier::Union(Some(tag)) |
+                TypeSpecifier::Enum(Some(tag)) | TypeSpecifier::Tagged(tag) => {
+                    if let Some(attributes) = scope.tags.get(tag) {
+                        self.deprecated_use(tag, attributes);
+                    }
+                },
+                TypeSpecifier::Named(name) => {
+                    if let Some(entity) = scope.names.get(name) {
+                        self.deprecated_use(name, &entity.attributes);
+                    }
+                    else if let Some(attributes) = scope.tags.get(name) {
+                        self.deprecated_use(name, attributes);
+                    }
+                },
+                TypeSpecifier::Typeof(operand) | TypeSpecifier::TypeofUnqual(operand) => match operand.as_ref() {
+                    TypeOrExpression::Type(type_) => self.use_type(scope, type_),
+                    TypeOrExpression::Expression(expression) => self.expression(scope, expression, &mut Usage::default()),
+                },
+                _ => {},
+            },
+            DeclaredType::Pointer(inner, _) | DeclaredType::Array(inner, _) => self.use_type(scope, inner),
+            DeclaredType::Function(return_type, arguments) => {
+                self.use_type(scope, return_type);
+                for argument in arguments {
+                    if let FunctionArgument::Parameter(parameter) = argument {
+                        self.use_type(scope, &parameter.type_);
+                    }
+                }
+            },
+        }
+    }
+
+    /// Like `use_type`, but leaves out the base type, which the declaration
+    /// specifiers have already been checked for.
+    fn use_declarator(&mut self, scope: &Scope, type_: &DeclaredType) {
+        match type_ {
+            DeclaredType::Base(_) => {},
+            DeclaredType::Pointer(inner, _) | DeclaredType::Array(inner, _) => self.use_declarator(scope, inner),
+            DeclaredType::Function(return_type, arguments) => {
+                self.use_declarator(scope, return_type);
+                for argument in arguments {
+                    if let FunctionArgument::Parameter(parameter) = argument {
+                        self.use_type(scope, &parameter.type_);
+                    }
+                }
+            },
+        }
+    }
+
+    /// Checks a declaration and brings its names into scope. Locals are recorded in
+    /// `usage` so unused ones can be reported.
+    fn declaration(&mut self, scope: &mut Scope, variable_list: &VariableList, usage: &mut Usage) -> Result<(), String> {
+        let specifiers = &variable_list.specifiers;
+        let functions = variable_list.variables.iter().any(|variable| variable.type_.is_function());
+        let target = if functions { Target::Function } else { Target::Object };
+        let name = variable_list.variables.first().map_or("a declaration", |variable| variable.name.as_str());
+        self.specifiers(scope, specifiers, target, name)?;
+        for variable in &variable_list.variables {
+            check_attributes(&variable.attributes, target, &variable.name)?;
+            self.use_declarator(scope, &variable.type_);
+            if let Some(VariableValue::Expression(value)) = &variable.value {
+                self.expression(scope, value, usage);
+            }
+
+            let mut attributes = specifiers.attributes.clone();
+            attributes.extend(variable.attributes.iter().cloned());
+            let typedef = matches!(specifiers.storage, Some(StorageClass::Typedef) | Some(StorageClass::Extern));
+            if !typedef && !variable.type_.is_function() && !maybe_unused(&attributes) {
+                usage.declared.push((variable.name.clone(), false));
+            }
+            let noreturn = specifiers.noreturn || attributes.iter().any(|attribute| attribute.is("noreturn"));
+            self.insert(scope, &variable.name, Entity {attributes, type_: variable.type_.clone(), noreturn});
+        }
+        Ok(())
+    }
+
+    /// Brings a name into scope. A function keeps the attributes of its earlier declarations.
+    fn insert(&mut self, scope: &mut Scope, name: &str, mut entity: Entity) {
+        if entity.type_.is_function() {
+            if let Some(previous) = scope.names.get(name).filter(|previous| previous.type_.is_function()) {
+                entity.attributes.extend(previous.attributes.iter().cloned());
+                entity.noreturn |= previous.noreturn;
+            }
+        }
+        scope.names.insert(name.to_string(), entity);
+    }
+
+    fn function(&mut self, scope: &mut Scope, specifiers: &DeclarationSpecifiers, return_type: &DeclaredType,
+                name: &str, arguments: &[FunctionArgument]) -> Result<(), String> {
+        self.specifiers(scope, specifiers, Target::Function, name)?;
+        let type_ = DeclaredType::Function(Box::new(return_type.clone()), arguments.to_vec());
+        self.use_declarator(scope, &type_);
+        let noreturn = specifiers.noreturn || specifiers.attributes.iter().any(|attribute| attribute.is("noreturn"));
+        self.insert(scope, name, Entity {attributes: specifiers.attributes.clone(), type_, noreturn});
+        Ok(())
+    }
+
+    /// Checks a function body and reports the parameters and locals it never uses.
+    fn body(&mut self, scope: &mut Scope, arguments: &'a [FunctionArgument], body: &'a CodeBlock) -> Result<(), String> {
+        scope.open();
+        let result = self.parameters_and_body(scope, arguments, body);
+        scope.close();
+        result
+    }
+
+    fn parameters_and_body(&mut self, scope: &mut Scope, arguments: &'a [FunctionArgument], body: &'a CodeBlock) -> Result<(), String> {
+        let mut usage = Usage::default();
+        for argument in arguments {
+            let FunctionArgument::Parameter(parameter) = argument else {
+                continue;
+            };
+            let Some(name) = &parameter.name else {
+                continue;
+            };
+            check_attributes(&parameter.attributes, Target::Object, name)?;
+            if !maybe_unused(&parameter.attributes) {
+                usage.declared.push((name.clone(), true));
+            }
+            scope.names.insert(name.clone(), Entity {
+                attributes: parameter.attributes.clone(),
+                type_: parameter.type_.clone(),
+                noreturn: false,
+            });
+        }
+
+        let CodeBlock::Code(list) = body;
+        self.block(scope, &list.statements, &mut usage)?;
+        for (name, parameter) in usage.declared {
+            if !usage.used.contains(&name) {
+                let kind = if parameter { "parameter" } else { "variable" };
+                self.warn(format!("Unused {} {}", kind, name), &name);
+            }
+        }
+        Ok(())
+    }
+
+    fn class(&mut self, scope: &mut Scope, class: &'a Class) -> Result<(), String> {
+        check_attributes(&class.attributes, Target::Type, &class.name)?;
+        if let Some(parent) = &class.parent {
+            if let Some(attributes) = scope.tags.get(parent) {
+                self.deprecated_use(parent, attributes);
+            }
+        }
+        scope.tags.insert(class.name.clone(), class.attributes.clone());
+
+        let mut methods = match &class.parent {
+            Some(parent) => scope.methods.get(parent).cloned().unwrap_or_default(),
+            None => HashMap::new(),
+        };
+        for member in &class.members {
+            match member {
+                ClassMember::Field(member) => self.members(scope, std::slice::from_ref(member))?,
+                ClassMember::Method(Method::Normal(Function {specifiers, name, ..})) |
+                ClassMember::Method(Method::Abstract(FunctionPrototype {specifiers, name, ..})) => {
+                    check_attributes(&specifiers.attributes, Target::Function, name)?;
+                    methods.insert(name.clone(), specifiers.attributes.clone());
+                },
+                ClassMember::OperatorOverload(OperatorOverload::Normal {attributes, op, ..}) |
+                ClassMember::OperatorOverload(OperatorOverload::Abstract {attributes, op, ..}) => {
+                    check_attributes(attributes, Target::Function, &format!("operator {}", op))?;
+                },
+            }
+        }
+        scope.methods.insert(class.name.clone(), methods.clone());
+
+        scope.open();
+        let result = self.methods(scope, class, &methods);
+        scope.close();
+        result
+    }
+
+    /// Checks the bodies of a class's methods, which can call each other by their bare names.
+    fn methods(&mut self, scope: &mut Scope, class: &'a Class, methods: &HashMap<String, Vec<Attribute>>) -> Result<(), String> {
+        for member in &class.members {
+            let (name, type_, noreturn) = match member {
+                ClassMember::Method(Method::Normal(function)) => {
+                    (&function.name, DeclaredType::Function(Box::new(function.return_type.clone()), function.arguments.clone()),
+                     function.specifiers.noreturn)
+                },
+                ClassMember::Method(Method::Abstract(prototype)) => {
+                    (&prototype.name, DeclaredType::Function(Box::new(prototype.return_type.clone()), prototype.arguments.clone()),
+                     prototype.specifiers.noreturn)
+                },
+                _ => continue,
+            };
+            self.use_type(scope, &type_);
+            scope.names.insert(name.clone(), Entity {attributes: methods[name].clone(), type_, noreturn});
+        }
+        for member in &class.members {
+            match member {
+                ClassMember::Method(Method::Normal(function)) => self.body(scope, &function.arguments, &function.body)?,
+                ClassMember::OperatorOverload(OperatorOverload::Normal {arguments, body, ..}) => self.body(scope, arguments, body)?,
+                _ => {},
+            }
+        }
+        Ok(())
+    }
+
+    fn block(&mut self, scope: &mut Scope, statements: &'a [Statement], usage: &mut Usage) -> Result<(), String> {
+        for statement in statements {
+            match statement {
+                Statement::VariableList(variable_list) => self.declaration(scope, variable_list, usage)?,
+                Statement::Expression(expression) => {
+                    self.discarded(scope, expression);
+                    self.expression(scope, expression, usage);
+                },
+                Statement::Return(expression) => self.expression(scope, expression, usage),
+                Statement::If(condition, body, else_) => {
+                    self.expression(scope, condition, usage);
+                    self.body_statement(scope, body, usage)?;
+                    if let Some(else_) = else_ {
+                        self.body_statement(scope, else_, usage)?;
+                    }
+                },
+                Statement::While(condition, body) | Statement::DoWhile(condition, body) => {
+                    self.expression(scope, condition, usage);
+                    self.body_statement(scope, body, usage)?;
+                },
+                Statement::For(init, condition, step, body) => {
+                    scope.open();
+                    let result = self.for_loop(scope, init.as_deref(), condition.as_ref(), step.as_ref(), body, usage);
+                    scope.close();
+                    result?;
+                },
+                Statement::Switch(condition, cases) => {
+                    self.expression(scope, condition, usage);
+                    self.switch(scope, cases, usage)?;
+                },
+                Statement::StaticAssert(assertion) => self.expression(scope, &assertion.condition, usage),
+                Statement::Attributes(attributes) => {
+                    let (fallthrough, others) = attributes.iter().cloned()
+                        .partition::<Vec<Attribute>, _>(|attribute| attribute.is("fallthrough"));
+                    check_attributes(&others, Target::Statement, "an attribute declaration")?;
+                    if let Some(attribute) = fallthrough.iter().find(|attribute| attribute.arguments.is_some()) {
+                        return Err(format!("Invalid arguments to {}", attribute));
+                    }
+                    if !fallthrough.is_empty() && !self.fallthroughs.iter().any(|allowed| std::ptr::eq(*allowed, statement)) {
+                        return Err("[[fallthrough]] must come right before a case or default label".to_string());
+                    }
+                },
+                Statement::Attributed(attributes, statement) => {
+                    check_attributes(attributes, Target::Statement, "a statement")?;
+                    self.block(scope, std::slice::from_ref(statement), usage)?;
+                },
+                Statement::Block(block) => {
+                    let CodeBlock::Code(list) = block.as_ref();
+                    scope.open();
+                    let result = self.block(scope, &list.statements, usage);
+                    scope.close();
+                    result?;
+                },
+                Statement::Preprocessor(_) | Statement::Break | Statement::Continue |
+                Statement::Goto(_) | Statement::Label(_) => {},
+            }
+        }
+        Ok(())
+    }
+
+    fn for_loop(&mut self, scope: &mut Scope, init: Option<&'a VariableListOrStatement>, condition: Option<&Expression>,
+                step: Option<&Expression>, body: &'a BlockOrStatement, usage: &mut Usage) -> Result<(), String> {
+        match init {
+            Some(VariableListOrStatement::VariableList(variable_list)) => self.declaration(scope, variable_list, usage)?,
+            Some(VariableListOrStatement::Statement(statement)) => self.block(scope, std::slice::from_ref(statement), usage)?,
+            None => {},
+        }
+        if let Some(condition) = condition {
+            self.expression(scope, condition, usage);
+        }
+        if let Some(step) = step {
+            self.discarded(scope, step);
+            self.expression(scope, step, usage);
+        }
+        self.body_statement(scope, body, usage)
+    }
+
+    fn body_statement(&mut self, scope: &mut Scope, body: &'a BlockOrStatement, usage: &mut Usage) -> Result<(), String> {
+        let statements = match body {
+            BlockOrStatement::Block(CodeBlock::Code(list)) => list.statements.as_slice(),
+            BlockOrStatement::Statement(statement) => std::slice::from_ref(statement),
+        };
+        scope.open();
+        let result = self.block(scope, statements, usage);
+        scope.close();
+        result
+    }
+
+    /// Checks the cases of a `switch`. A case that is followed by another must end
+    /// in a jump or `[[fallthrough]];`, and only there can `[[fallthrough]];` appear.
+    fn switch(&mut self, scope: &mut Scope, cases: &'a [SwitchCase], usage: &mut Usage) -> Result<(), String> {
+        for (i, case) in cases.iter().enumerate() {
+            if let Some(label) = &case.expression {
+                self.expression(scope, label, usage);
+            }
+            let statements = match case.body.as_ref() {
+                BlockOrStatement::Block(CodeBlock::Code(list)) => list.statements.as_slice(),
+                BlockOrStatement::Statement(statement) => std::slice::from_ref(statement),
+            };
+            if i + 1 < cases.len() && !statements.is_empty() {
+                trailing_fallthroughs(statements, &mut self.fallthroughs);
+                if !self.ends_in_jump(scope, statements) {
+                    let label = match case.expression.as_ref().map(Expression::get_value) {
+                        Some(Some(value)) => format!("case {}", value),
+                        Some(None) => "case".to_string(),
+                        None => "default".to_string(),
+                    };
+                    self.warn(format!("The {} label falls through to the next one without [[fallthrough]]", label), &label);
+                }
+            }
+            self.body_statement(scope, &case.body, usage)?;
+        }
+        Ok(())
+    }
+
+    /// Whether control never runs off the end of some statements.
+    fn ends_in_jump(&self, scope: &Scope, statements: &[Statement]) -> bool {
+        let Some(last) = statements.last() else {
+            return false;
+        };
+        let body_ends = |body: &BlockOrStatement| match body {
+            BlockOrStatement::Block(CodeBlock::Code(list)) => self.ends_in_jump(scope, &list.statements),
+            BlockOrStatement::Statement(statement) => self.ends_in_jump(scope, std::slice::from_ref(statement)),
+        };
+        match last {
+            Statement::Break | Statement::Continue | Statement::Return(_) | Statement::Goto(_) => true,
+            statement if is_fallthrough(statement) => true,
+            Statement::Block(block) => {
+                let CodeBlock::Code(list) = block.as_ref();
+                self.ends_in_jump(scope, &list.statements)
+            },
+            Statement::Attributed(_, statement) => self.ends_in_jump(scope, std::slice::from_ref(statement)),
+            Statement::If(_, then, Some(else_)) => body_ends(then) && body_ends(else_),
+            Statement::Expression(Expression::CallFunction(name, _)) => {
+                scope.names.get(name).is_some_and(|entity| entity.noreturn)
+            },
+            _ => false,
+        }
+    }
+
+    /// Warns when an expression whose value is thrown away calls a `nodiscard`
+    /// function, or one returning a `nodiscard` type.
+    fn discarded(&mut self, scope: &Scope, expression: &Expression) {
+        match expression {
+            Expression::CallFunction(name, _) => {
+                let Some(entity) = scope.names.get(name) else {
+                    return;
+                };
+                if let Some(attribute) = nodiscard(&entity.attributes) {
+                    let warning = format!("Ignoring the result of {}, which is declared nodiscard", name);
+                    self.warn(with_reason(warning, attribute), name);
+                    return;
+                }
+                let DeclaredType::Function(return_type, _) = &entity.type_ else {
+                    return;
+                };
+                let tag = match &return_type.as_ref() {
+                    DeclaredType::Base(base) => match &base.specifier {
+                        TypeSpecifier::Struct(Some(tag)) | TypeSpecifier::Union(Some(tag)) |
+                        TypeSpecifier::Enum(Some(tag)) | TypeSpecifier::Tagged(tag) | TypeSpecifier::Named(tag) => tag,
+                        _ => return,
+                    },
+                    _ => return,
+                };
+                if let Some(attribute) = scope.tags.get(tag).and_then(|attributes| nodiscard(attributes)) {
+                    let warning = format!("Ignoring the result of {}, whose type {} is declared nodiscard", name, tag);
+                    self.warn(with_reason(warning, attribute), name);
+                }
+            },
+            Expression::Binary(BinaryOperator::MemberAccess | BinaryOperator::PointerMemberAccess, object, method) => {
+                let Expression::CallFunction(method, _) = method.as_ref() else {
+                    return;
+                };
+                if let Some(attribute) = scope.method(object, method).and_then(|attributes| nodiscard(attributes)) {
+                    let warning = format!("Ignoring the result of method {}, which is declared nodiscard", method);
+                    self.warn(with_reason(warning, attribute), method);
+                }
+            },
+            Expression::Binary(BinaryOperator::Comma, left, right) => {
+                self.discarded(scope, left);
+                self.discarded(scope, right);
+            },
+            Expression::Parentheses(inner) | Expression::Expression(inner) => self.discarded(scope, inner),
+            _ => {},
+        }
+    }
+
+    /// Records the names an expression uses and warns about deprecated ones.
+    fn expression(&mut self, scope: &Scope, expression: &Expression, usage: &mut Usage) {
+        match expression {
+            Expression::Identifier(name) | Expression::CallFunction(name, None) => {
+                usage.used.insert(name.clone());
+                if let Some(entity) = scope.names.get(name) {
+                    self.deprecated_use(name, &entity.attributes);
+                }
+            },
+            Expression::CallFunction(name, Some(arguments)) => {
+                usage.used.insert(name.clone());
+                if let Some(entity) = scope.names.get(name) {
+                    self.deprecated_use(name, &entity.attributes);
+                }
+                self.expression(scope, arguments, usage);
+            },
+            Expression::Binary(BinaryOperator::MemberAccess | BinaryOperator::PointerMemberAccess, object, member) => {
+                self.expression(scope, object, usage);
+                if let Expression::CallFunction(method, arguments) = member.as_ref() {
+                    if let Some(attributes) = scope.method(object, method) {
+                        self.deprecated_use(method, attributes);
+                    }
+                    if let Some(arguments) = arguments {
+                        self.expression(scope, arguments, usage);
+                    }
+                }
+            },
+            Expression::Binary(_, left, right) => {
+                self.expression(scope, left, usage);
+                self.expression(scope, right, usage);
+            },
+            Expression::Unary(operator, operand) => {
+                if let UnaryOperator::Cast(type_) = operator {
+                    self.use_type(scope, type_);
+                }
+                self.expression(scope, operand, usage);
+            },
+            Expression::Sizeof(TypeOrExpression::Type(type_)) | Expression::Alignof(type_) |
+            Expression::Offsetof(type_, _) => self.use_type(scope, type_),
+            Expression::Sizeof(TypeOrExpression::Expression(operand)) | Expression::InitializerList(operand) |
+            Expression::TaggedInitializer(_, operand) | Expression::Expression(operand) |
+            Expression::Parentheses(operand) => self.expression(scope, operand, usage),
+            Expression::Ternary(condition, then, otherwise) => {
+                self.expression(scope, condition, usage);
+                self.expression(scope, then, usage);
+                self.expression(scope, otherwise, usage);
+            },
+            Expression::Call(callee, arguments) => {
+                self.expression(scope, callee, usage);
+                if let Some(arguments) = arguments {
+                    self.expression(scope, arguments, usage);
+                }
+            },
+            Expression::Generic(control, associations) => {
+                self.expression(scope, control, usage);
+                for association in associations {
+                    if let Some(type_) = &association.type_ {
+                        self.use_type(scope, type_);
+                    }
+                    self.expression(scope, &association.expression, usage);
+                }
+            },
+            Expression::StatementList(list) => {
+                for statement in &list.statements {
+                    if let Statement::Expression(expression) = statement {
+                        self.expression(scope, expression, usage);
+                    }
+                }
+            },
+            Expression::Blank | Expression::Literal(_) => {},
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod attributes_tests {
+    use super::*;
+    use crate::logos_lexer::lex;
+    use crate::parser::Parser;
+
+    fn warnings(input: &str) -> Result<Vec<String>, String> {
+        let tokens = match lex(input) {
+            Ok(tokens) => tokens,
+            Err(err) => panic!("Failed to lex input: {:?}", err),
+        };
+        match Parser::new(&tokens).parse() {
+            Ok(header) => check(&header)
+                .map(|warnings| warnings.into_iter().map(|warning| warning.message).collect())
+                .map_err(String::from),
+            Err(err) => panic!("Failed to parse {}: {}", input, err),
+        }
+    }
+
+    #[test]
+    fn test_nodiscard() {
+        let result = warnings("[[nodiscard(\"check it\")]] int f(void);\nint g(void) __attribute__((warn_unused_result));\nstruct [[nodiscard]] error { int code; };\nstruct error h(void);\nint main(void) { f(); g(); (void)f(); int x = f(); h(); (f(), g()); return x; }\n");
+        assert_eq!(result, Ok(vec![
+            "Ignoring the result of f, which is declared nodiscard: check it".to_string(),
+            "Ignoring the result of g, which is declared nodiscard".to_string(),
+            "Ignoring the result of h, whose type error is declared nodiscard".to_string(),
+            "Ignoring the result of f, which is declared nodiscard: check it".to_string(),
+            "Ignoring the result of g, which is declared nodiscard".to_string(),
+        ]));
+
+        let result = warnings("class C { [[nodiscard]] int get() { return 1; } void put() { get(); } };\nint main(C *c) { c->get(); return 0; }\n");
+        assert_eq!(result, Ok(vec![
+            "Ignoring the result of get, which is declared nodiscard".to_string(),
+            "Ignoring the result of method get, which is declared nodiscard".to_string(),
+        ]));
+    }
+
+    #[test]
+    fn test_deprecated() {
+        let result = warnings("[[deprecated(\"use g2\")]] int g1;\ntypedef int old_t [[deprecated]];\nenum e { A [[deprecated]], B };\n[[deprecated]] void f(void);\nold_t y;\nint main(void) { int x = A + g1; f(); return x + B; }\nint h(int x) { if (x) { int g1 = x; return g1; } return g1; }\n");
+        assert_eq!(result, Ok(vec![
+            "old_t is deprecated".to_string(),
+            "A is deprecated".to_string(),
+            "g1 is deprecated: use g2".to_string(),
+            "f is deprecated".to_string(),
+            "g1 is deprecated: use g2".to_string(),
+        ]));
+    }
+
+    #[test]
+    fn test_unused() {
+        let result = warnings("int f(int a, [[maybe_unused]] int b, int c __attribute__((unused)), int d) { int x; [[maybe_unused]] int y; int z = d; return z; }\n");
+        assert_eq!(result, Ok(vec!["Unused parameter a".to_string(), "Unused variable x".to_string()]));
+    }
+
+    #[test]
+    fn test_fallthrough() {
+        let result = warnings("int f(int x) { switch (x) { case 1: x++; [[fallthrough]]; case 2: x++; case 3: if (x) return 1; else return 2; case 4: x--; default: break; } return x; }\n");
+        assert_eq!(result, Ok(vec![
+            "The case 2 label falls through to the next one without [[fallthrough]]".to_string(),
+            "The case 4 label falls through to the next one without [[fallthrough]]".to_string(),
+        ]));
+    }
+
+    #[test]
+    fn test_misplaced_attributes() {
+        for input in ["int f(int x) { [[fallthrough]]; return x; }", "int f(int x) { switch (x) { case 1: [[fallthrough]]; x++; case 2: break; } return x; }",
+                      "[[nodiscard]] int x;", "[[fallthrough]] int f(void);", "[[noreturn]] int x;", "int f(void) { [[deprecated]] return 0; }",
+                      "[[nodiscard(1)]] int f(void);", "[[deprecated(\"a\", \"b\")]] int x;", "[[maybe_unused(x)]] int x;"] {
+            assert!(warnings(input).is_err(), "Accepted misplaced attribute: {}", input);
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use std::io::{self, Read};
+use std::process::ExitCode;
+
+use c_with_classes::ast::Header;
+use c_with_classes::dot::{class_graph, to_dot};
+use c_with_classes::logos_lexer::{lex_with_trivia, TokenWithTrivia};
+use c_with_classes::parser::Parser;
+use c_with_classes::printer::print;
+
+
+const USAGE: &str = "\
+usage: cwc-dump [options] [file]
+
+Parses a file, or standard input when no file or `-` is given, and writes it to standard output
+in another form.
+
+options:
+    --emit <form>                json, sexp, c, dot for a Graphviz graph of the syntax tree, or classes
+                                 for a graph of the class hierarchy and tagged unions (default sexp)
+    --tokens                     write the tokens instead of the syntax tree
+    --from-json                  read a syntax tree written by --emit json instead of source
+    -h, --help                   print this message
+
+json, sexp and --from-json need cwc-dump built with the serde feature.";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Emit {
+    Json,
+    Sexp,
+    C,
+    Dot,
+    Classes,
+}
+
+struct Options {
+    emit: Emit,
+    tokens: bool,
+    from_json: bool,
+    file: String,
+}
+
+/// What is read from the input and written out again.
+enum Tree {
+    #[cfg_attr(not(feature = "serde"), allow(dead_code))]
+    Tokens(Vec<TokenWithTrivia>),
+    Header(Header),
+}
+
+fn parse_options(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {emit: Emit::Sexp, tokens: false, from_json: false, file: "-".to_string()};
+    let mut file = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--emit" => options.emit = match args.next().as_deref() {
+                Some("json") => Emit::Json,
+                Some("sexp") => Emit::Sexp,
+                Some("c") => Emit::C,
+                Some("dot") => Emit::Dot,
+                Some("classes") => Emit::Classes,
+                _ => return Err("--emit expects json, sexp, c, dot or classes".to_string()),
+            },
+            "--tokens" => options.tokens = true,
+            "--from-json" => options.from_json = true,
+            _ if arg.starts_with('-') && arg != "-" => return Err(format!("Unknown option {}", arg)),
+            _ if file.is_some() => return Err("Expected at most one file".to_string()),
+            _ => file = Some(arg),
+        }
+    }
+    if options.tokens && (options.from_json || !matches!(options.emit, Emit::Json | Emit::Sexp)) {
+        return Err("--tokens can only be written as json or sexp".to_string());
+    }
+    options.file = file.unwrap_or(options.file);
+
+    Ok(options)
+}
+
+fn read(file: &str) -> io::Result<String> {
+    if file == "-" {
+        let mut source = String::new();
+        io::stdin().read_to_string(&mut source)?;
+        Ok(source)
+    }
+    else {
+        std::fs::read_to_string(file)
+    }
+}
+
+#[cfg(feature = "serde")]
+fn from_json(text: &str) -> Result<Header, String> {
+    serde_json::from_str(text).map_err(|err| format!("Invalid syntax tree: {}", err))
+}
+
+#[cfg(not(feature = "serde"))]
+fn from_json(_text: &str) -> Result<Header, String> {
+    Err("--from-json needs cwc-dump built with the serde feature".to_string())
+}
+
+#[cfg(feature = "serde")]
+fn serialize(tree: &Tree, emit: Emit) -> Result<String, String> {
+    let text = match (tree, emit) {
+        (Tree::Tokens(tokens), Emit::Json) => serde_json::to_string_pretty(tokens).map_err(|err| err.to_string())?,
+        (Tree::Header(header), Emit::Json) => serde_json::to_string_pretty(header).map_err(|err| err.to_string())?,
+        (Tree::Tokens(tokens), _) => c_with_classes::sexp::to_string(tokens).map_err(|err| err.to_string())?,
+        (Tree::Header(header), _) => c_with_classes::sexp::to_string(header).map_err(|err| err.to_string())?,
+    };
+    Ok(text + "\n")
+}
+
+#[cfg(not(feature = "serde"))]
+fn serialize(_tree: &Tree, _emit: Emit) -> Result<String, String> {
+    Err("json and sexp need cwc-dump built with the serde feature".to_string())
+}
+
+fn dump(options: &Options) -> Result<String, String> {
+    let text = read(&options.file).map_err(|err| err.to_string())?;
+    // A tree read from json has no source to take the ranges in a graph from.
+    let mut source_tokens = Vec::new();
+    let tree = if options.from_json {
+        Tree::Header(from_json(&text)?)
+    }
+    else {
+        let tokens = lex_with_trivia(&text).map_err(|errors| format!("{:?}", errors))?;
+        if options.tokens {
+            Tree::Tokens(tokens)
+        }
+        else {
+            let header = Parser::with_trivia(&tokens).parse()?;
+            source_tokens = tokens;
+            Tree::Header(header)
+        }
+    };
+
+    match (&tree, options.emit) {
+        (Tree::Header(header), Emit::C) => Ok(print(header)),
+        (Tree::Header(header), Emit::Dot) => Ok(to_dot(header, &source_tokens)),
+        (Tree::Header(header), Emit::Classes) => Ok(class_graph(header)),
+        _ => serialize(&tree, options.emit),
+    }
+}
+
+fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<String>>();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+    let options = match parse_options(args.into_iter()) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("cwc-dump: {}\n\n{}", err, USAGE);
+            return ExitCode::from(2);
+        },
+    };
+
+    match dump(&options) {
+        Ok(text) => {
+            print!("{}", text);
+            ExitCode::SUCCESS
+        },
+        Err(err) => {
+            let name = if options.file == "-" { "<stdin>" } else { options.file.as_str() };
+            eprintln!("cwc-dump: {}: {}", name, err);
+            ExitCode::FAILURE
+        },
+    }
+}
@@ -0,0 +1,136 @@
+use std::io::{self, Read, Write};
+use std::process::ExitCode;
+
+use c_with_classes::format::format;
+use c_with_classes::printer::{BraceStyle, Style};
+
+
+const USAGE: &str = "\
+usage: cwc-fmt [options] [file...]
+
+Formats each file in place, or standard input to standard output when no file or `-` is given.
+
+options:
+    --check                      write nothing, list the files that are not formatted and exit with 1
+    --indent <width>             spaces per level of nesting (default 4)
+    --line-width <width>         column to break long lists at (default 100)
+    --brace-style <style>        same-line or next-line (default same-line)
+    --trace-parser               log the parser's decisions to standard error (needs the tracing feature)
+    -h, --help                   print this message";
+
+struct Options {
+    style: Style,
+    check: bool,
+    trace_parser: bool,
+    files: Vec<String>,
+}
+
+fn parse_options(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {style: Style::default(), check: false, trace_parser: false, files: Vec::new()};
+    let width = |flag: &str, value: Option<String>| match value.as_deref().map(str::parse::<usize>) {
+        Some(Ok(width)) if width > 0 => Ok(width),
+        _ => Err(format!("{} expects a positive number", flag)),
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--check" => options.check = true,
+            "--trace-parser" if cfg!(feature = "tracing") => options.trace_parser = true,
+            "--trace-parser" => return Err("--trace-parser needs cwc-fmt built with the tracing feature".to_string()),
+            "--indent" => options.style.indent_width = width(&arg, args.next())?,
+            "--line-width" => options.style.line_width = width(&arg, args.next())?,
+            "--brace-style" => options.style.brace_style = match args.next().as_deref() {
+                Some("same-line") => BraceStyle::SameLine,
+                Some("next-line") => BraceStyle::NextLine,
+                _ => return Err("--brace-style expects same-line or next-line".to_string()),
+            },
+            "-" => options.files.push(arg),
+            _ if arg.starts_with('-') => return Err(format!("Unknown option {}", arg)),
+            _ => options.files.push(arg),
+        }
+    }
+    if options.files.is_empty() {
+        options.files.push("-".to_string());
+    }
+
+    Ok(options)
+}
+
+fn read(file: &str) -> io::Result<String> {
+    if file == "-" {
+        let mut source = String::new();
+        io::stdin().read_to_string(&mut source)?;
+        Ok(source)
+    }
+    else {
+        std::fs::read_to_string(file)
+    }
+}
+
+fn write(file: &str, formatted: &str) -> io::Result<()> {
+    if file == "-" {
+        io::stdout().write_all(formatted.as_bytes())
+    }
+    else {
+        std::fs::write(file, formatted)
+    }
+}
+
+fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<String>>();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+    let options = match parse_options(args.into_iter()) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("cwc-fmt: {}\n\n{}", err, USAGE);
+            return ExitCode::from(2);
+        },
+    };
+
+    #[cfg(feature = "tracing")]
+    if options.trace_parser {
+        tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::TRACE)
+            .with_writer(io::stderr)
+            .init();
+    }
+
+    let mut unformatted = false;
+    let mut failed = false;
+    for file in &options.files {
+        let name = if file == "-" { "<stdin>" } else { file.as_str() };
+        let result = read(file).map_err(|err| err.to_string()).and_then(|source| {
+            let formatted = format(&source, &options.style)?;
+            if options.check {
+                if formatted != source {
+                    println!("{}", name);
+                    unformatted = true;
+                }
+                Ok(())
+            }
+            else if formatted != source || file == "-" {
+                write(file, &formatted).map_err(|err| err.to_string())
+            }
+            else {
+                Ok(())
+            }
+        });
+        if let Err(err) = result {
+            eprintln!("cwc-fmt: {}: {}", name, err);
+            failed = true;
+        }
+    }
+
+    if failed {
+        ExitCode::from(2)
+    }
+    else if unformatted {
+        ExitCode::FAILURE
+    }
+    else {
+        ExitCode::SUCCESS
+    }
+}
@@ -0,0 +1,421 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::ops::Range;
+
+use lsp_server::{Connection, Message, Notification, Request, RequestId, Response};
+use lsp_types::notification::{DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, PublishDiagnostics};
+use lsp_types::request::{Completion, DocumentSymbolRequest, GotoDefinition, HoverRequest, References};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, CompletionResponse, Diagnostic,
+    DiagnosticSeverity, DocumentSymbol, DocumentSymbolParams, DocumentSymbolResponse, Documentation,
+    GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents, HoverParams, HoverProviderCapability,
+    Location, MarkupContent, MarkupKind, OneOf, Position, PublishDiagnosticsParams, ReferenceParams,
+    ServerCapabilities, SymbolKind as LspSymbolKind, TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+};
+
+use c_with_classes::ast::Finding;
+use c_with_classes::attributes;
+use c_with_classes::incremental;
+use c_with_classes::layout::{DataModel, LayoutEngine};
+use c_with_classes::logos_lexer::{lex_with_trivia, LexerError, TokenWithTrivia};
+use c_with_classes::parser::Parser;
+use c_with_classes::symbols::{SymbolIndex, SymbolKind};
+
+
+/// An open file, kept parsed as it is edited, and the index of the last version of it that parsed.
+struct Document {
+    parsed: incremental::Document,
+    index: Option<SymbolIndex>,
+}
+
+impl Document {
+    fn text(&self) -> &str {
+        self.parsed.source()
+    }
+
+    /// The index, if it is of the text as it is now.
+    fn current(&self) -> Option<&SymbolIndex> {
+        self.index.as_ref().filter(|index| index.source == self.text())
+    }
+}
+
+struct Server {
+    connection: Connection,
+    documents: HashMap<Url, Document>,
+}
+
+fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::INCREMENTAL)),
+        definition_provider: Some(OneOf::Left(true)),
+        references_provider: Some(OneOf::Left(true)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec![".".to_string(), ">".to_string()]),
+            ..CompletionOptions::default()
+        }),
+        ..ServerCapabilities::default()
+    }
+}
+
+/// The LSP position of a byte offset, which counts UTF-16 code units within the line.
+fn position(text: &str, offset: usize) -> Position {
+    let mut offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    Position::new(before.matches('\n').count() as u32, before[line_start..].encode_utf16().count() as u32)
+}
+
+/// The byte offset of an LSP position, clamped to the line it is on.
+fn offset(text: &str, position: Position) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match text[line_start..].find('\n') {
+            Some(newline) => line_start += newline + 1,
+            None => return text.len(),
+        }
+    }
+    let mut units = 0;
+    for (index, character) in text[line_start..].char_indices() {
+        if units >= position.character as usize || character == '\n' {
+            return line_start + index;
+        }
+        units += character.len_utf16();
+    }
+    text.len()
+}
+
+fn range(text: &str, span: &Range<usize>) -> lsp_types::Range {
+    lsp_types::Range::new(position(text, span.start), position(text, span.end))
+}
+
+fn lexer_message(error: &LexerError) -> String {
+    match error {
+        LexerError::UnterminatedString => "Unterminated string literal".to_string(),
+        LexerError::UnterminatedCharacter => "Unterminated character literal".to_string(),
+        LexerError::UnterminatedComment => "Unterminated comment".to_string(),
+        LexerError::BadEscape(text) => format!("Bad escape sequence {}", text),
+        LexerError::BadLiteral(text) => format!("Bad literal {}", text),
+        LexerError::MismatchedStringPrefix(text) => format!("Mismatched string prefixes in {}", text),
+        LexerError::BadNumber(text) => format!("Bad number {}", text),
+        LexerError::NumberOutOfRange(text) => format!("Number out of range {}", text),
+        LexerError::UnrecognizedToken(text) => format!("Unrecognized token {}", text),
+        LexerError::Empty => "Empty character literal".to_string(),
+    }
+}
+
+fn diagnostic(text: &str, span: &Range<usize>, severity: DiagnosticSeverity, message: String) -> Diagnostic {
+    Diagnostic {
+        range: range(text, span),
+        severity: Some(severity),
+        source: Some("cwc".to_string()),
+        message,
+        ..Diagnostic::default()
+    }
+}
+
+/// Where a finding goes: the place its name is written in its statement, or the whole
+/// statement. Findings that repeat go to the places the name is written in turn.
+fn finding_span(tokens: &[TokenWithTrivia], ranges: &[Range<usize>], finding: &Finding, seen: &mut HashMap<Finding, usize>) -> Range<usize> {
+    let statement = ranges.get(finding.statement).and_then(|range| tokens.get(range.clone())).unwrap_or_default();
+    let (Some(first), Some(last)) = (statement.first(), statement.last()) else {
+        return 0..0;
+    };
+    let Some(name) = &finding.name else {
+        return first.span.start..last.span.end;
+    };
+    let words = name.split(' ').collect::<Vec<&str>>();
+    let earlier = seen.entry(finding.clone()).or_default();
+    let found = statement.windows(words.len())
+        .filter(|window| window.iter().zip(&words).all(|(token, word)| token.token.to_string() == *word))
+        .nth(*earlier);
+    *earlier += 1;
+    match found {
+        Some(window) => window[0].span.start..window[window.len() - 1].span.end,
+        None => first.span.start..last.span.end,
+    }
+}
+
+/// Where a file that does not lex or parse goes wrong, found by lexing and parsing it again.
+fn errors(text: &str) -> Vec<Diagnostic> {
+    let tokens = match lex_with_trivia(text) {
+        Ok(tokens) => tokens,
+        Err(errors) => {
+            return errors.iter()
+                .map(|error| diagnostic(text, &error.span, DiagnosticSeverity::ERROR, lexer_message(&error.error)))
+                .collect();
+        },
+    };
+    let mut parser = Parser::with_trivia(&tokens);
+    match parser.parse() {
+        Ok(_) => Vec::new(),
+        Err(err) => {
+            let span = tokens.get(parser.position()).map_or(text.len()..text.len(), |token| token.span.clone());
+            vec![diagnostic(text, &span, DiagnosticSeverity::ERROR, err)]
+        },
+    }
+}
+
+/// Checks a parsed file.
+fn check(document: &incremental::Document) -> (Vec<Diagnostic>, Option<SymbolIndex>) {
+    let text = document.source();
+    let Ok(header) = document.header() else {
+        return (errors(text), None);
+    };
+    let tokens = document.tokens();
+    let ranges = document.ranges();
+
+    let mut seen = HashMap::new();
+    let mut located = |finding: Finding, severity| {
+        let span = finding_span(tokens, &ranges, &finding, &mut seen);
+        diagnostic(text, &span, severity, finding.message)
+    };
+    let mut diagnostics = Vec::new();
+    if let Err(err) = LayoutEngine::from_header(&header, DataModel::default()) {
+        diagnostics.push(located(err, DiagnosticSeverity::ERROR));
+    }
+    match attributes::check(&header) {
+        Ok(warnings) => diagnostics.extend(warnings.into_iter().map(|warning| located(warning, DiagnosticSeverity::WARNING))),
+        Err(err) => diagnostics.push(located(err, DiagnosticSeverity::ERROR)),
+    }
+    (diagnostics, Some(SymbolIndex::from_tree(text, tokens.to_vec(), header)))
+}
+
+fn symbol_kind(kind: SymbolKind) -> LspSymbolKind {
+    match kind {
+        SymbolKind::Variable | SymbolKind::Parameter => LspSymbolKind::VARIABLE,
+        SymbolKind::Function => LspSymbolKind::FUNCTION,
+        SymbolKind::Typedef => LspSymbolKind::TYPE_PARAMETER,
+        SymbolKind::Struct | SymbolKind::Union => LspSymbolKind::STRUCT,
+        SymbolKind::Enum | SymbolKind::TaggedUnion => LspSymbolKind::ENUM,
+        SymbolKind::EnumMember | SymbolKind::Variant => LspSymbolKind::ENUM_MEMBER,
+        SymbolKind::Class => LspSymbolKind::CLASS,
+        SymbolKind::Field => LspSymbolKind::FIELD,
+        SymbolKind::Method => LspSymbolKind::METHOD,
+        SymbolKind::Label => LspSymbolKind::KEY,
+    }
+}
+
+fn completion_kind(kind: SymbolKind) -> CompletionItemKind {
+    match kind {
+        SymbolKind::Method => CompletionItemKind::METHOD,
+        SymbolKind::Variant => CompletionItemKind::ENUM_MEMBER,
+        _ => CompletionItemKind::FIELD,
+    }
+}
+
+/// The outline of a symbol: the members of a type, or of the struct without a tag a declaration defines.
+fn document_symbol(index: &SymbolIndex, symbol: usize) -> DocumentSymbol {
+    let symbol_ = &index.symbols[symbol];
+    let container = match symbol_.kind {
+        SymbolKind::Typedef | SymbolKind::Variable | SymbolKind::Field => symbol_.record,
+        _ => Some(symbol),
+    };
+    let mut children = Vec::new();
+    let mut pending = container.map_or(Vec::new(), |container| index.children(container).to_vec());
+    pending.reverse();
+    while let Some(child) = pending.pop() {
+        if index.symbols[child].name.is_empty() {
+            pending.extend(index.children(child).iter().rev());
+        }
+        else {
+            children.push(document_symbol(index, child));
+        }
+    }
+
+    let text = &index.source;
+    let end = symbol_.body.as_ref().map_or(symbol_.span.end, |body| body.end);
+    #[allow(deprecated)]
+    DocumentSymbol {
+        name: symbol_.name.clone(),
+        detail: Some(index.signature(symbol)),
+        kind: symbol_kind(symbol_.kind),
+        tags: None,
+        deprecated: None,
+        range: range(text, &(symbol_.span.start..end)),
+        selection_range: range(text, &symbol_.span),
+        children: (!children.is_empty()).then_some(children),
+    }
+}
+
+fn hover_text(index: &SymbolIndex, symbol: usize) -> String {
+    let mut text = format!("```c\n{}\n```", index.signature(symbol));
+    if let Some(doc) = &index.symbols[symbol].doc {
+        text.push_str("\n\n");
+        text.push_str(doc);
+    }
+    text
+}
+
+impl Server {
+    fn run(&mut self) -> Result<(), Box<dyn Error + Sync + Send>> {
+        let receiver = self.connection.receiver.clone();
+        for message in &receiver {
+            match message {
+                Message::Request(request) => {
+                    if self.connection.handle_shutdown(&request)? {
+                        return Ok(());
+                    }
+                    let response = self.request(request);
+                    self.connection.sender.send(Message::Response(response))?;
+                },
+                Message::Notification(notification) => self.notification(notification)?,
+                Message::Response(_) => {},
+            }
+        }
+        Ok(())
+    }
+
+    fn request(&self, request: Request) -> Response {
+        use lsp_types::request::Request as _;
+
+        let Request {id, method, params} = request;
+        let result = match method.as_str() {
+            GotoDefinition::METHOD => serde_json::from_value(params).and_then(|params| serde_json::to_value(self.definition(params))),
+            References::METHOD => serde_json::from_value(params).and_then(|params| serde_json::to_value(self.references(params))),
+            HoverRequest::METHOD => serde_json::from_value(params).and_then(|params| serde_json::to_value(self.hover(params))),
+            DocumentSymbolRequest::METHOD => serde_json::from_value(params).and_then(|params| serde_json::to_value(self.document_symbols(params))),
+            Completion::METHOD => serde_json::from_value(params).and_then(|params| serde_json::to_value(self.completion(params))),
+            _ => return error(id, lsp_server::ErrorCode::MethodNotFound, format!("Unknown method {}", method)),
+        };
+        match result {
+            Ok(result) => Response {id, result: Some(result), error: None},
+            Err(err) => error(id, lsp_server::ErrorCode::InvalidParams, err.to_string()),
+        }
+    }
+
+    fn notification(&mut self, notification: Notification) -> Result<(), Box<dyn Error + Sync + Send>> {
+        use lsp_types::notification::Notification as _;
+
+        let uri = match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: lsp_types::DidOpenTextDocumentParams = serde_json::from_value(notification.params)?;
+                let parsed = incremental::Document::new(&params.text_document.text);
+                self.documents.insert(params.text_document.uri.clone(), Document {parsed, index: None});
+                params.text_document.uri
+            },
+            DidChangeTextDocument::METHOD => {
+                let params: lsp_types::DidChangeTextDocumentParams = serde_json::from_value(notification.params)?;
+                let Some(document) = self.documents.get_mut(&params.text_document.uri) else {
+                    return Ok(());
+                };
+                for change in params.content_changes {
+                    match change.range {
+                        Some(range) => {
+                            let start = offset(document.text(), range.start);
+                            let end = offset(document.text(), range.end).max(start);
+                            // A source that no longer parses is reported below.
+                            let _ = document.parsed.edit(start..end, &change.text);
+                        },
+                        None => document.parsed = incremental::Document::new(&change.text),
+                    }
+                }
+                params.text_document.uri
+            },
+            DidCloseTextDocument::METHOD => {
+                let params: lsp_types::DidCloseTextDocumentParams = serde_json::from_value(notification.params)?;
+                self.documents.remove(&params.text_document.uri);
+                return self.publish(params.text_document.uri, Vec::new());
+            },
+            _ => return Ok(()),
+        };
+
+        let Some(document) = self.documents.get_mut(&uri) else {
+            return Ok(());
+        };
+        let (diagnostics, index) = check(&document.parsed);
+        if index.is_some() {
+            document.index = index;
+        }
+        self.publish(uri, diagnostics)
+    }
+
+    fn publish(&self, uri: Url, diagnostics: Vec<Diagnostic>) -> Result<(), Box<dyn Error + Sync + Send>> {
+        use lsp_types::notification::Notification as _;
+
+        let params = PublishDiagnosticsParams::new(uri, diagnostics, None);
+        let notification = Notification::new(PublishDiagnostics::METHOD.to_string(), params);
+        self.connection.sender.send(Message::Notification(notification))?;
+        Ok(())
+    }
+
+    /// The document, its current index and the symbol named at a position.
+    fn symbol_at(&self, uri: &Url, position: Position) -> Option<(&Document, &SymbolIndex, usize)> {
+        let document = self.documents.get(uri)?;
+        let index = document.current()?;
+        let reference = index.reference_at(offset(document.text(), position))?;
+        Some((document, index, reference.symbol))
+    }
+
+    fn definition(&self, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let uri = params.text_document_position_params.text_document.uri;
+        let (document, index, symbol) = self.symbol_at(&uri, params.text_document_position_params.position)?;
+        let range = range(document.text(), &index.symbols[symbol].span);
+        Some(GotoDefinitionResponse::Scalar(Location::new(uri, range)))
+    }
+
+    fn references(&self, params: ReferenceParams) -> Option<Vec<Location>> {
+        let uri = params.text_document_position.text_document.uri;
+        let (document, index, symbol) = self.symbol_at(&uri, params.text_document_position.position)?;
+        let declaration = &index.symbols[symbol].span;
+        let locations = index.references_to(symbol)
+            .filter(|reference| params.context.include_declaration || reference.span != *declaration)
+            .map(|reference| Location::new(uri.clone(), range(document.text(), &reference.span)))
+            .collect();
+        Some(locations)
+    }
+
+    fn hover(&self, params: HoverParams) -> Option<Hover> {
+        let position = params.text_document_position_params;
+        let (document, index, symbol) = self.symbol_at(&position.text_document.uri, position.position)?;
+        let reference = index.reference_at(offset(document.text(), position.position))?;
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {kind: MarkupKind::Markdown, value: hover_text(index, symbol)}),
+            range: Some(range(document.text(), &reference.span)),
+        })
+    }
+
+    fn document_symbols(&self, params: DocumentSymbolParams) -> Option<DocumentSymbolResponse> {
+        let index = self.documents.get(&params.text_document.uri)?.current()?;
+        let symbols = index.symbols.iter().enumerate()
+            .filter(|(_, symbol)| symbol.parent.is_none() && !symbol.local && !symbol.name.is_empty())
+            .filter(|(_, symbol)| !matches!(symbol.kind, SymbolKind::Parameter | SymbolKind::Label))
+            .map(|(symbol, _)| document_symbol(index, symbol))
+            .collect();
+        Some(DocumentSymbolResponse::Nested(symbols))
+    }
+
+    fn completion(&self, params: CompletionParams) -> Option<CompletionResponse> {
+        let position = params.text_document_position;
+        let document = self.documents.get(&position.text_document.uri)?;
+        let index = document.index.as_ref()?;
+        let items = index.member_completions(document.text(), offset(document.text(), position.position)).into_iter()
+            .map(|member| CompletionItem {
+                label: index.symbols[member].name.clone(),
+                kind: Some(completion_kind(index.symbols[member].kind)),
+                detail: Some(index.signature(member)),
+                documentation: index.symbols[member].doc.clone().map(Documentation::String),
+                ..CompletionItem::default()
+            })
+            .collect();
+        Some(CompletionResponse::Array(items))
+    }
+}
+
+fn error(id: RequestId, code: lsp_server::ErrorCode, message: String) -> Response {
+    Response::new_err(id, code as i32, message)
+}
+
+fn main() -> Result<(), Box<dyn Error + Sync + Send>> {
+    let (connection, io_threads) = Connection::stdio();
+    connection.initialize(serde_json::to_value(capabilities())?)?;
+    let mut server = Server {connection, documents: HashMap::new()};
+    server.run()?;
+    drop(server);
+    io_threads.join()?;
+    Ok(())
+}
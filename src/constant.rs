@@ -0,0 +1,695 @@
+use std::fmt;
+
+use crate::ast::*;
+use crate::layout::{DataModel, LayoutEngine};
+use crate::literal::{number_literal, Encoding, NumberType, NumberValue};
+use crate::logos_lexer::{lex, Token};
+use crate::parser::Parser;
+use crate::printer::print_node;
+use crate::typecheck;
+
+
+/// The value of a constant expression together with its C type. Values of types
+/// narrower than `int` are promoted as soon as they are produced.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Constant {
+    Integer(i128, NumberType),
+    Floating(f64, NumberType),
+}
+
+impl Constant {
+    pub fn number_type(&self) -> NumberType {
+        match self {
+            Constant::Integer(_, type_) | Constant::Floating(_, type_) => *type_,
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        match self {
+            Constant::Integer(value, _) => *value == 0,
+            Constant::Floating(value, _) => *value == 0.0,
+        }
+    }
+}
+
+impl fmt::Display for Constant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Constant::Integer(value, _) => write!(f, "{}", value),
+            Constant::Floating(value, _) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// Evaluates an arithmetic constant expression, such as the initializer of a
+/// static `double`.
+pub fn evaluate(expression: &Expression, engine: &LayoutEngine) -> Result<Constant, String> {
+    Evaluator {engine, integer: false, preprocessor: false}.evaluate(expression)
+}
+
+/// Evaluates an integer constant expression, as required by enum values, array
+/// sizes, bit-field widths and `case` labels. Floating constants may only appear
+/// as the operand of a cast to an integer type.
+pub fn integer(expression: &Expression, engine: &LayoutEngine) -> Result<i128, String> {
+    match (Evaluator {engine, integer: true, preprocessor: false}).evaluate(expression)? {
+        Constant::Integer(value, _) => Ok(value),
+        Constant::Floating(..) => Err("Expected an integer constant expression".to_string()),
+    }
+}
+
+/// The size of the type of an expression, as in `sizeof x` or `typeof(x)`. Only
+/// string literals and arithmetic constant expressions have a known type.
+pub fn size_of_expression(expression: &Expression, engine: &LayoutEngine) -> Result<u64, String> {
+    Evaluator {engine, integer: false, preprocessor: false}.size_of_expression(expression)
+}
+
+/// Checks the initializer of a `constexpr` object. Arithmetic objects need a constant
+/// whose value their type represents exactly, and pointers must be null.
+pub fn check_constexpr(type_: &DeclaredType, value: &VariableValue, engine: &LayoutEngine) -> Result<(), String> {
+    let VariableValue::Expression(expression) = value else {
+        return match type_ {
+            DeclaredType::Array(..) => Ok(()),
+            _ => Err("A string literal can only initialize a constexpr array".to_string()),
+        };
+    };
+    let evaluator = Evaluator {engine, integer: false, preprocessor: false};
+    match type_ {
+        DeclaredType::Pointer(..) => match strip_parentheses(expression) {
+            Expression::Literal(Literal::Nullptr) => Ok(()),
+            expression if evaluator.evaluate(expression) == Ok(Constant::Integer(0, NumberType::Int)) => Ok(()),
+            _ => Err("A constexpr pointer must be initialized with nullptr".to_string()),
+        },
+        DeclaredType::Base(_) if !matches!(expression, Expression::InitializerList(_)) => {
+            let value = evaluator.evaluate(expression)?;
+            let converted = evaluator.cast(value, type_)?;
+            let same = match (value, converted) {
+                (Constant::Integer(value, _), Constant::Integer(converted, _)) => value == converted,
+                (Constant::Integer(value, _), Constant::Floating(converted, _)) => value as f64 == converted && converted as i128 == value,
+                (Constant::Floating(value, _), Constant::Integer(converted, _)) => value == converted as f64,
+                (Constant::Floating(value, _), Constant::Floating(converted, _)) => value == converted,
+            };
+            if !same {
+                return Err(format!("{} cannot be represented exactly in a constexpr {}", value,
+                                   print_node(&AstNode::TypeSpecifier(type_.base().specifier.clone()))));
+            }
+            Ok(())
+        },
+        _ => Ok(()),
+    }
+}
+
+/// Evaluates the labels of a `switch` and reports labels that are not constant
+/// or appear twice.
+pub fn case_values(cases: &[SwitchCase], engine: &LayoutEngine) -> Result<Vec<i128>, String> {
+    let mut values = Vec::new();
+    for case in cases {
+        if let Some(expression) = &case.expression {
+            let value = integer(expression, engine)?;
+            if values.contains(&value) {
+                return Err(format!("Duplicate case value {}", value));
+            }
+            values.push(value);
+        }
+    }
+    if cases.iter().filter(|case| case.default).count() > 1 {
+        return Err("Multiple default labels in one switch".to_string());
+    }
+    Ok(values)
+}
+
+/// Evaluates the condition of an `#if` or `#elif` directive. `defined NAME` and
+/// `defined(NAME)` ask `defined` about the macro, any other identifier is zero, and
+/// arithmetic is done in `intmax_t` and `uintmax_t`.
+pub fn preprocessor_condition(directive: &str, defined: impl Fn(&str) -> bool) -> Result<bool, String> {
+    let text = directive.trim_start().trim_start_matches('#').trim_start();
+    let condition = if let Some(condition) = text.strip_prefix("elif") {
+        condition
+    }
+    else if let Some(condition) = text.strip_prefix("if") {
+        condition
+    }
+    else {
+        return Err(format!("Expected #if or #elif but found {}", directive.trim_end()));
+    };
+    if !condition.starts_with(|c: char| c.is_whitespace() || c == '(') {
+        return Err(format!("Expected #if or #elif but found {}", directive.trim_end()));
+    }
+
+    let tokens = match lex(&condition.replace("\\\n", " ")) {
+        Ok(tokens) => tokens,
+        Err(errors) => return Err(format!("Failed to lex {}: {:?}", directive.trim_end(), errors)),
+    };
+    let mut replaced = Vec::new();
+    let mut tokens = tokens.into_iter().filter(|token| *token != Token::Newline).peekable();
+    while let Some(token) = tokens.next() {
+        if token != Token::Word("defined".to_string()) {
+            replaced.push(token);
+            continue;
+        }
+        let parenthesized = tokens.next_if_eq(&Token::LeftParen).is_some();
+        let Some(Token::Word(name)) = tokens.next() else {
+            return Err("Expected a macro name after defined".to_string());
+        };
+        if parenthesized && tokens.next() != Some(Token::RightParen) {
+            return Err("Expected right parenthesis after defined".to_string());
+        }
+        let value = if defined(&name) { "1" } else { "0" };
+        replaced.push(Token::Number(number_literal(value).map_err(|err| format!("{:?}", err))?));
+    }
+
+    let expression = Parser::new(&replaced).constant_expression()?;
+    let engine = LayoutEngine::default();
+    let value = Evaluator {engine: &engine, integer: true, preprocessor: true}.evaluate(&expression)?;
+    Ok(!value.is_zero())
+}
+
+pub(crate) fn width(type_: NumberType, model: DataModel) -> u32 {
+    match type_ {
+        NumberType::Int | NumberType::UnsignedInt | NumberType::Float => 32,
+        NumberType::Long | NumberType::UnsignedLong => model.long_size() as u32 * 8,
+        NumberType::LongLong | NumberType::UnsignedLongLong | NumberType::Double |
+        NumberType::LongDouble => 64,
+        NumberType::BitInt(width) | NumberType::UnsignedBitInt(width) => width,
+    }
+}
+
+/// Orders integer types for the usual arithmetic conversions. Bit-precise types
+/// rank below standard types of the same width.
+fn rank(type_: NumberType, model: DataModel) -> (u32, u8) {
+    let order = match type_ {
+        NumberType::BitInt(_) | NumberType::UnsignedBitInt(_) => 0,
+        NumberType::Int | NumberType::UnsignedInt => 1,
+        NumberType::Long | NumberType::UnsignedLong => 2,
+        _ => 3,
+    };
+    (width(type_, model), order)
+}
+
+fn unsigned(type_: NumberType) -> NumberType {
+    match type_ {
+        NumberType::Int => NumberType::UnsignedInt,
+        NumberType::Long => NumberType::UnsignedLong,
+        NumberType::LongLong => NumberType::UnsignedLongLong,
+        NumberType::BitInt(width) => NumberType::UnsignedBitInt(width),
+        type_ => type_,
+    }
+}
+
+fn minimum(type_: NumberType, model: DataModel) -> i128 {
+    match type_.is_unsigned() {
+        true => 0,
+        false => -(1 << (width(type_, model) - 1)),
+    }
+}
+
+fn maximum(type_: NumberType, model: DataModel) -> i128 {
+    match type_.is_unsigned() {
+        true => (1 << width(type_, model)) - 1,
+        false => (1 << (width(type_, model) - 1)) - 1,
+    }
+}
+
+fn fits(value: i128, type_: NumberType, model: DataModel) -> bool {
+    minimum(type_, model) <= value && value <= maximum(type_, model)
+}
+
+/// Converts a value to an integer type the way C does: modulo 2^N.
+pub(crate) fn wrap(value: i128, type_: NumberType, model: DataModel) -> i128 {
+    let bits = width(type_, model);
+    let value = value & ((1 << bits) - 1);
+    if !type_.is_unsigned() && value >= 1 << (bits - 1) {
+        value - (1 << bits)
+    }
+    else {
+        value
+    }
+}
+
+/// The type `sizeof` and `alignof` produce.
+pub(crate) fn size_type(model: DataModel) -> NumberType {
+    match model {
+        DataModel::LP64 => NumberType::UnsignedLong,
+        DataModel::LLP64 => NumberType::UnsignedLongLong,
+        DataModel::ILP32 => NumberType::UnsignedInt,
+    }
+}
+
+/// The type an arithmetic operator with these operands computes in.
+pub(crate) fn common_type(left: NumberType, right: NumberType, model: DataModel) -> NumberType {
+    if !left.is_integer() || !right.is_integer() {
+        return [NumberType::LongDouble, NumberType::Double, NumberType::Float].into_iter()
+            .find(|type_| *type_ == left || *type_ == right)
+            .unwrap_or(NumberType::Double);
+    }
+    if left.is_unsigned() == right.is_unsigned() {
+        return if rank(left, model) >= rank(right, model) { left } else { right };
+    }
+    let (signed, unsigned_) = if left.is_unsigned() { (right, left) } else { (left, right) };
+    if rank(unsigned_, model) >= rank(signed, model) {
+        unsigned_
+    }
+    else if width(signed, model) > width(unsigned_, model) {
+        signed
+    }
+    else {
+        unsigned(signed)
+    }
+}
+
+struct Evaluator<'a> {
+    engine: &'a LayoutEngine,
+    /// Whether this is an integer constant expression.
+    integer: bool,
+    /// Whether every integer is widened to `intmax_t` or `uintmax_t`, as in `#if`.
+    preprocessor: bool,
+}
+
+impl Evaluator<'_> {
+    fn model(&self) -> DataModel {
+        self.engine.model()
+    }
+
+    fn integer_constant(&self, value: i128, type_: NumberType) -> Result<Constant, String> {
+        let type_ = match (self.preprocessor, type_.is_unsigned()) {
+            (true, true) => NumberType::UnsignedLongLong,
+            (true, false) => NumberType::LongLong,
+            (false, _) => type_,
+        };
+        if !fits(value, type_, self.model()) {
+            return Err(format!("Integer overflow in constant expression: {} does not fit in {}", value, type_));
+        }
+        Ok(Constant::Integer(value, type_))
+    }
+
+    fn evaluate(&self, expression: &Expression) -> Result<Constant, String> {
+        match expression {
+            Expression::Literal(Literal::Number(number)) => match number.value {
+                NumberValue::Integer(value) => {
+                    let mut type_ = number.c_type;
+                    if matches!(type_, NumberType::BitInt(128..) | NumberType::UnsignedBitInt(128..)) {
+                        return Err(format!("{} is too wide to evaluate", number.raw));
+                    }
+                    let value = value as i128;
+                    // Literals are typed for LP64; a long that does not fit the target's long becomes a long long.
+                    if !fits(value, type_, self.model()) {
+                        type_ = match type_ {
+                            NumberType::Long => NumberType::LongLong,
+                            NumberType::UnsignedLong => NumberType::UnsignedLongLong,
+                            type_ => type_,
+                        };
+                    }
+                    self.integer_constant(value, type_)
+                },
+                NumberValue::Floating(value) => {
+                    if self.integer {
+                        return Err(format!("Floating constant {} in an integer constant expression", number.raw));
+                    }
+                    Ok(Constant::Floating(value, number.c_type))
+                },
+            },
+            Expression::Literal(Literal::Char(character)) => {
+                let type_ = match character.encoding {
+                    Encoding::Utf32 => NumberType::UnsignedInt,
+                    _ => NumberType::Int,
+                };
+                self.integer_constant(character.value as i128, type_)
+            },
+            Expression::Literal(Literal::Bool(value)) => self.integer_constant(*value as i128, NumberType::Int),
+            Expression::Literal(Literal::String(string)) => {
+                Err(format!("String literal {} is not an arithmetic constant", string.raw))
+            },
+            Expression::Literal(Literal::Nullptr) => Err("nullptr is not an arithmetic constant".to_string()),
+            Expression::Identifier(_) if self.preprocessor => self.integer_constant(0, NumberType::Int),
+            Expression::Identifier(name) => match self.engine.enumerator(name) {
+                Some(value) => self.integer_constant(value, NumberType::Int),
+                None => Err(format!("{} is not a constant", name)),
+            },
+            Expression::Parentheses(inner) | Expression::Expression(inner) => self.evaluate(inner),
+            Expression::Generic(control, associations) => {
+                self.evaluate(typecheck::select_association(control, associations, self.engine)?)
+            },
+            Expression::Sizeof(TypeOrExpression::Type(type_)) => {
+                self.integer_constant(self.engine.size_of(type_)? as i128, size_type(self.model()))
+            },
+            Expression::Sizeof(TypeOrExpression::Expression(inner)) => {
+                let size = self.size_of_expression(inner)?;
+                self.integer_constant(size as i128, size_type(self.model()))
+            },
+            Expression::Alignof(type_) => {
+                self.integer_constant(self.engine.align_of(type_)? as i128, size_type(self.model()))
+            },
+            Expression::Offsetof(type_, member) => {
+                self.integer_constant(self.engine.offset_of(type_, member)? as i128, size_type(self.model()))
+            },
+            Expression::Unary(UnaryOperator::Cast(type_), operand) => {
+                let operand = match (self.integer, strip_parentheses(operand)) {
+                    (true, Expression::Literal(Literal::Number(number))) if !number.is_integer() => {
+                        let NumberValue::Floating(value) = number.value else {
+                            unreachable!("floating literals hold floating values");
+                        };
+                        Constant::Floating(value, number.c_type)
+                    },
+                    _ => self.evaluate(operand)?,
+                };
+                self.cast(operand, type_)
+            },
+            Expression::Unary(operator, operand) => self.unary(operator, self.evaluate(operand)?),
+            Expression::Binary(BinaryOperator::LogicalAnd, left, right) => {
+                let value = !self.evaluate(left)?.is_zero() && !self.evaluate(right)?.is_zero();
+                self.integer_constant(value as i128, NumberType::Int)
+            },
+            Expression::Binary(BinaryOperator::LogicalOr, left, right) => {
+                let value = !self.evaluate(left)?.is_zero() || !self.evaluate(right)?.is_zero();
+                self.integer_constant(value as i128, NumberType::Int)
+            },
+            Expression::Binary(operator, left, right) => {
+                self.binary(operator, self.evaluate(left)?, self.evaluate(right)?)
+            },
+            Expression::Ternary(condition, then, otherwise) => {
+                let (chosen, other) = match self.evaluate(condition)?.is_zero() {
+                    false => (then, otherwise),
+                    true => (otherwise, then),
+                };
+                let chosen = self.evaluate(chosen)?;
+                // The branch that is not taken may divide by zero, but still decides the result type.
+                match self.evaluate(other) {
+                    Ok(other) => self.convert(chosen, common_type(chosen.number_type(), other.number_type(), self.model())),
+                    Err(_) => Ok(chosen),
+                }
+            },
+            _ => Err(format!("Expected a constant expression but found {}", print_node(&AstNode::Expression(expression.clone())))),
+        }
+    }
+
+    /// The size of the type of an expression, which is not evaluated beyond what
+    /// is needed to find that type.
+    fn size_of_expression(&self, expression: &Expression) -> Result<u64, String> {
+        match expression {
+            Expression::Literal(Literal::String(string)) => {
+                Ok((string.value.len() as u64 + 1) * string.encoding.unit_bits() as u64 / 8)
+            },
+            Expression::Parentheses(inner) => self.size_of_expression(inner),
+            Expression::Unary(UnaryOperator::Cast(type_), _) => self.engine.size_of(type_),
+            expression => {
+                let type_ = match self.evaluate(expression) {
+                    Ok(value) => value.number_type(),
+                    Err(err) => match typecheck::declared_type_of(expression, self.engine) {
+                        Ok(type_) => return self.engine.size_of(&type_),
+                        Err(_) => return Err(err),
+                    },
+                };
+                match type_ {
+                    NumberType::LongDouble => Ok(self.model().long_double_size()),
+                    type_ => Ok(width(type_, self.model()).div_ceil(8) as u64),
+                }
+            },
+        }
+    }
+
+    fn convert(&self, value: Constant, type_: NumberType) -> Result<Constant, String> {
+        let model = self.model();
+        match (value, type_.is_integer()) {
+            (Constant::Integer(value, _), true) => self.integer_constant(wrap(value, type_, model), type_),
+            (Constant::Integer(value, _), false) => Ok(Constant::Floating(round(value as f64, type_), type_)),
+            (Constant::Floating(value, _), true) => {
+                let truncated = value.trunc();
+                if !truncated.is_finite() || truncated < minimum(type_, model) as f64 || truncated > maximum(type_, model) as f64 {
+                    return Err(format!("{} is out of range for {}", value, type_));
+                }
+                self.integer_constant(truncated as i128, type_)
+            },
+            (Constant::Floating(value, _), false) => Ok(Constant::Floating(round(value, type_), type_)),
+        }
+    }
+
+    fn cast(&self, value: Constant, type_: &DeclaredType) -> Result<Constant, String> {
+        let DeclaredType::Base(base) = type_ else {
+            return Err(format!("Cannot cast to {} in a constant expression", print_node(&AstNode::DeclaredType(type_.clone()))));
+        };
+        let narrow = |bits: u32, unsigned_: bool| -> Result<Constant, String> {
+            let value = match self.convert(value, NumberType::LongLong) {
+                Ok(Constant::Integer(value, _)) => value,
+                _ => return Err(format!("{} is out of range for {}", value, print_node(&AstNode::TypeSpecifier(base.specifier.clone())))),
+            };
+            let target = if unsigned_ { NumberType::UnsignedBitInt(bits) } else { NumberType::BitInt(bits) };
+            self.integer_constant(wrap(value, target, self.model()), NumberType::Int)
+        };
+        match &base.specifier {
+            TypeSpecifier::Bool => self.integer_constant(!value.is_zero() as i128, NumberType::Int),
+            TypeSpecifier::Char | TypeSpecifier::SignedChar => narrow(8, false),
+            TypeSpecifier::UnsignedChar => narrow(8, true),
+            TypeSpecifier::Short => narrow(16, false),
+            TypeSpecifier::UnsignedShort => narrow(16, true),
+            TypeSpecifier::Int | TypeSpecifier::Enum(_) => self.convert(value, NumberType::Int),
+            TypeSpecifier::UnsignedInt => self.convert(value, NumberType::UnsignedInt),
+            TypeSpecifier::Long => self.convert(value, NumberType::Long),
+            TypeSpecifier::UnsignedLong => self.convert(value, NumberType::UnsignedLong),
+            TypeSpecifier::LongLong => self.convert(value, NumberType::LongLong),
+            TypeSpecifier::UnsignedLongLong => self.convert(value, NumberType::UnsignedLongLong),
+            TypeSpecifier::Float => self.convert(value, NumberType::Float),
+            TypeSpecifier::Double => self.convert(value, NumberType::Double),
+            TypeSpecifier::LongDouble => self.convert(value, NumberType::LongDouble),
+            TypeSpecifier::Named(name) => match self.engine.typedef(name) {
+                Some(type_) => self.cast(value, type_),
+                None => Err(format!("Unknown type {}", name)),
+            },
+            TypeSpecifier::Typeof(operand) | TypeSpecifier::TypeofUnqual(operand) => match operand.as_ref() {
+                TypeOrExpression::Type(type_) => self.cast(value, type_),
+                TypeOrExpression::Expression(expression) => {
+                    let type_ = self.evaluate(expression)?.number_type();
+                    self.convert(value, type_)
+                },
+            },
+            specifier => Err(format!("Cannot cast to {} in a constant expression", print_node(&AstNode::TypeSpecifier(specifier.clone())))),
+        }
+    }
+
+    fn unary(&self, operator: &UnaryOperator, value: Constant) -> Result<Constant, String> {
+        let promoted = value.number_type();
+        match (operator, value) {
+            (UnaryOperator::Plus, value) => Ok(value),
+            (UnaryOperator::Minus, Constant::Integer(value, type_)) if type_.is_unsigned() => {
+                self.integer_constant(wrap(-value, type_, self.model()), type_)
+            },
+            (UnaryOperator::Minus, Constant::Integer(value, type_)) => self.integer_constant(-value, type_),
+            (UnaryOperator::Minus, Constant::Floating(value, type_)) => Ok(Constant::Floating(-value, type_)),
+            (UnaryOperator::BitwiseNot, Constant::Integer(value, type_)) => {
+                self.integer_constant(wrap(!value, type_, self.model()), type_)
+            },
+            (UnaryOperator::Not | UnaryOperator::LogicalNot, value) => {
+                self.integer_constant(value.is_zero() as i128, NumberType::Int)
+            },
+            (operator, _) => {
+                Err(format!("{} on {} is not a constant expression", print_node(&AstNode::UnaryOperator(operator.clone())), promoted))
+            },
+        }
+    }
+
+    fn binary(&self, operator: &BinaryOperator, left: Constant, right: Constant) -> Result<Constant, String> {
+        let model = self.model();
+        if let BinaryOperator::LeftShift | BinaryOperator::RightShift = operator {
+            let (Constant::Integer(value, type_), Constant::Integer(count, _)) = (left, right) else {
+                return Err("Shift operands must be integers".to_string());
+            };
+            if count < 0 {
+                return Err(format!("Negative shift count {}", count));
+            }
+            if count >= width(type_, model) as i128 {
+                return Err(format!("Shift count {} is too large for {}", count, type_));
+            }
+            return match operator {
+                BinaryOperator::LeftShift if type_.is_unsigned() => {
+                    self.integer_constant(wrap(value << count, type_, model), type_)
+                },
+                BinaryOperator::LeftShift if value < 0 => Err(format!("Left shift of negative value {}", value)),
+                BinaryOperator::LeftShift => self.integer_constant(value << count, type_),
+                _ => self.integer_constant(value >> count, type_),
+            };
+        }
+
+        let type_ = common_type(left.number_type(), right.number_type(), model);
+        let left = self.convert(left, type_)?;
+        let right = self.convert(right, type_)?;
+        if let BinaryOperator::Divide | BinaryOperator::Modulo = operator {
+            if right.is_zero() {
+                return Err("Division by zero in constant expression".to_string());
+            }
+        }
+
+        let compare = |ordering: Option<std::cmp::Ordering>| -> Result<Constant, String> {
+            use std::cmp::Ordering::*;
+            let value = match (operator, ordering) {
+                (BinaryOperator::LessThan, Some(Less)) |
+                (BinaryOperator::GreaterThan, Some(Greater)) |
+                (BinaryOperator::LessThanOrEqual, Some(Less | Equal)) |
+                (BinaryOperator::GreaterThanOrEqual, Some(Greater | Equal)) |
+                (BinaryOperator::Equal, Some(Equal)) => true,
+                (BinaryOperator::NotEqual, ordering) => ordering != Some(Equal),
+                _ => false,
+            };
+            self.integer_constant(value as i128, NumberType::Int)
+        };
+
+        match (left, right) {
+            (Constant::Integer(left, _), Constant::Integer(right, _)) => {
+                let value = match operator {
+                    BinaryOperator::Add => left.checked_add(right),
+                    BinaryOperator::Subtract => left.checked_sub(right),
+                    BinaryOperator::Multiply => left.checked_mul(right),
+                    BinaryOperator::Divide => left.checked_div(right),
+                    BinaryOperator::Modulo => left.checked_rem(right),
+                    BinaryOperator::BitwiseAnd => Some(left & right),
+                    BinaryOperator::BitwiseOr => Some(left | right),
+                    BinaryOperator::BitwiseXor => Some(left ^ right),
+                    BinaryOperator::LessThan | BinaryOperator::GreaterThan | BinaryOperator::LessThanOrEqual |
+                    BinaryOperator::GreaterThanOrEqual | BinaryOperator::Equal | BinaryOperator::NotEqual => {
+                        return compare(Some(left.cmp(&right)));
+                    },
+                    BinaryOperator::Comma => {
+                        return Err("The comma operator is not allowed in a constant expression".to_string());
+                    },
+                    operator => {
+                        return Err(format!("{} is not allowed in a constant expression", print_node(&AstNode::BinaryOperator(operator.clone()))));
+                    },
+                };
+                let Some(value) = value else {
+                    return Err(format!("Integer overflow in constant expression: {} {} {}", left, print_node(&AstNode::BinaryOperator(operator.clone())), right));
+                };
+                match type_.is_unsigned() {
+                    true => self.integer_constant(wrap(value, type_, model), type_),
+                    false => self.integer_constant(value, type_),
+                }
+            },
+            (Constant::Floating(left, _), Constant::Floating(right, _)) => {
+                let value = match operator {
+                    BinaryOperator::Add => left + right,
+                    BinaryOperator::Subtract => left - right,
+                    BinaryOperator::Multiply => left * right,
+                    BinaryOperator::Divide => left / right,
+                    BinaryOperator::LessThan | BinaryOperator::GreaterThan | BinaryOperator::LessThanOrEqual |
+                    BinaryOperator::GreaterThanOrEqual | BinaryOperator::Equal | BinaryOperator::NotEqual => {
+                        return compare(left.partial_cmp(&right));
+                    },
+                    operator => {
+                        return Err(format!("{} requires integer operands", print_node(&AstNode::BinaryOperator(operator.clone()))));
+                    },
+                };
+                Ok(Constant::Floating(round(value, type_), type_))
+            },
+            _ => unreachable!("both operands were converted to {:?}", type_),
+        }
+    }
+}
+
+/// Rounds a value to the precision of a floating type.
+fn round(value: f64, type_: NumberType) -> f64 {
+    match type_ {
+        NumberType::Float => value as f32 as f64,
+        _ => value,
+    }
+}
+
+fn strip_parentheses(expression: &Expression) -> &Expression {
+    match expression {
+        Expression::Parentheses(inner) => strip_parentheses(inner),
+        expression => expression,
+    }
+}
+
+
+#[cfg(test)]
+mod constant_tests {
+    use super::*;
+    use crate::layout::DataModel;
+
+    fn expression(input: &str) -> Expression {
+        let tokens = match lex(input) {
+            Ok(tokens) => tokens,
+            Err(err) => panic!("Failed to lex input: {:?}", err),
+        };
+        match Parser::new(&tokens).constant_expression() {
+            Ok(expression) => expression,
+            Err(err) => panic!("Failed to parse {}: {}", input, err),
+        }
+    }
+
+    fn value(input: &str, model: DataModel) -> Result<Constant, String> {
+        evaluate(&expression(input), &LayoutEngine::new(model))
+    }
+
+    #[test]
+    fn test_promotions_and_conversions() {
+        let lp64 = |input| value(input, DataModel::LP64);
+        assert_eq!(lp64("-1 < 0u"), Ok(Constant::Integer(0, NumberType::Int)));
+        assert_eq!(lp64("-1L < 0u"), Ok(Constant::Integer(1, NumberType::Int)));
+        assert_eq!(value("-1L < 0u", DataModel::ILP32), Ok(Constant::Integer(0, NumberType::Int)));
+        assert_eq!(lp64("1u - 2"), Ok(Constant::Integer(4294967295, NumberType::UnsignedInt)));
+        assert_eq!(lp64("(unsigned char)300 + (char)200"), Ok(Constant::Integer(-12, NumberType::Int)));
+        assert_eq!(lp64("(bool)0.5 + ~0ul"), Ok(Constant::Integer(0, NumberType::UnsignedLong)));
+        assert_eq!(lp64("(int)2.9 + 'a'"), Ok(Constant::Integer(99, NumberType::Int)));
+        assert_eq!(lp64("1.5 * 2"), Ok(Constant::Floating(3.0, NumberType::Double)));
+        assert_eq!(lp64("1.0f / 3"), Ok(Constant::Floating((1.0f32 / 3.0) as f64, NumberType::Float)));
+        assert_eq!(lp64("sizeof(long) + sizeof \"ab\""), Ok(Constant::Integer(11, NumberType::UnsignedLong)));
+        assert_eq!(value("sizeof(long) + sizeof(int *)", DataModel::LLP64), Ok(Constant::Integer(12, NumberType::UnsignedLongLong)));
+        assert_eq!(value("2147483648", DataModel::ILP32), Ok(Constant::Integer(2147483648, NumberType::LongLong)));
+        assert_eq!(lp64("1 ? 2 : 3.0"), Ok(Constant::Floating(2.0, NumberType::Double)));
+        assert_eq!(lp64("0 && 1 / 0"), Ok(Constant::Integer(0, NumberType::Int)));
+    }
+
+    #[test]
+    fn test_overflow_and_errors() {
+        let engine = LayoutEngine::default();
+        assert_eq!(integer(&expression("4294967295u + 1"), &engine), Ok(0));
+        assert_eq!(integer(&expression("-2147483647 - 1"), &engine), Ok(-2147483648));
+        assert_eq!(integer(&expression("-8 >> 1"), &engine), Ok(-4));
+        for input in ["2147483647 + 1", "-(-2147483647 - 1)", "1 << 31", "1 << 32", "1 >> -1", "-1 << 1", "1 / 0", "5 % 0",
+                      "(-2147483647 - 1) / -1", "x + 1", "2.5 * 2", "(int)1e10", "f(1)", "(1, 2)", "(a = 1)"] {
+            assert!(integer(&expression(input), &engine).is_err(), "Evaluated invalid constant: {}", input);
+        }
+        assert!(evaluate(&expression("1.0 / 0"), &engine).is_err());
+        assert!(evaluate(&expression("1.5 % 2"), &engine).is_err());
+        assert_eq!(integer(&expression("(int)1e10"), &engine), Err("10000000000 is out of range for int".to_string()));
+        assert_eq!(integer(&expression("(char *)0"), &engine), Err("Cannot cast to char * in a constant expression".to_string()));
+        assert_eq!(integer(&expression("f(1)"), &engine), Err("Expected a constant expression but found f(1)".to_string()));
+    }
+
+    #[test]
+    fn test_enums_and_case_labels() {
+        let header = Parser::new(&lex("enum color { RED, GREEN = 5, BLUE, ALIAS = RED + BLUE, LAST };\nstruct palette { char shades[BLUE]; };\nint f(int c) { switch (c) { case RED: return 1; case GREEN + 1: case 'a': return 2; default: return 0; } }\n").unwrap()).parse().unwrap();
+        let engine = match LayoutEngine::from_header(&header, DataModel::LP64) {
+            Ok(engine) => engine,
+            Err(err) => panic!("Failed to lay out header: {}", err),
+        };
+        let values = ["RED", "GREEN", "BLUE", "ALIAS", "LAST"].map(|name| engine.enumerator(name));
+        assert_eq!(values, [Some(0), Some(5), Some(6), Some(6), Some(7)]);
+        assert_eq!(engine.struct_layout("palette").map(|palette| palette.size), Some(6));
+
+        let HeaderStatement::Function(function) = &header.statements[2] else {
+            panic!("Expected a function: {:?}", header.statements[2]);
+        };
+        let CodeBlock::Code(body) = &function.body;
+        let Statement::Switch(_, cases) = &body.statements[0] else {
+            panic!("Expected a switch: {:?}", body.statements[0]);
+        };
+        assert_eq!(case_values(cases, &engine), Ok(vec![0, 6, 97]));
+        assert!(case_values(&[cases[1].clone(), cases[1].clone()], &engine).is_err());
+
+        for input in ["enum big { MAX = 2147483647, OVER };", "enum bad { A = 1.5 };", "enum bad { A = B };"] {
+            let header = Parser::new(&lex(input).unwrap()).parse().unwrap();
+            assert!(LayoutEngine::from_header(&header, DataModel::LP64).is_err(), "Numbered invalid enum: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_preprocessor_conditions() {
+        let defined = |name: &str| name == "FOO";
+        assert_eq!(preprocessor_condition("#if defined(FOO) && !defined BAR\n", defined), Ok(true));
+        assert_eq!(preprocessor_condition("#if defined FOO && VERSION >= 2\n", defined), Ok(false));
+        assert_eq!(preprocessor_condition("#elif -1 > 0u\n", defined), Ok(true));
+        assert_eq!(preprocessor_condition("#if 0xFFFFFFFF + 1 > 0xFFFFFFFF\n", defined), Ok(true));
+        assert_eq!(preprocessor_condition("#  if (2 + 3) * 4 == 20", defined), Ok(true));
+        for input in ["#if 1 / 0\n", "#if\n", "#ifdef FOO\n", "#if defined(\n", "#if 1 2\n"] {
+            assert!(preprocessor_condition(input, defined).is_err(), "Evaluated invalid condition: {}", input);
+        }
+    }
+}
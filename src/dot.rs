@@ -0,0 +1,483 @@
+use std::collections::HashSet;
+use std::ops::Range;
+
+use crate::ast::*;
+use crate::logos_lexer::{lex, TokenWithTrivia};
+use crate::printer::print_node;
+use crate::visit::*;
+
+
+/// A node of the syntax tree that `to_dot` can draw, with the subtree under it.
+pub trait Node {
+    fn accept<'ast, V: Visitor<'ast> + ?Sized>(&'ast self, visitor: &mut V);
+}
+
+macro_rules! nodes {
+    ($($type_:ty => $visit:ident,)*) => {
+        $(impl Node for $type_ {
+            fn accept<'ast, V: Visitor<'ast> + ?Sized>(&'ast self, visitor: &mut V) {
+                visitor.$visit(self);
+            }
+        })*
+    };
+}
+
+nodes! {
+    Header => visit_header,
+    HeaderStatement => visit_header_statement,
+    StaticAssert => visit_static_assert,
+    Struct => visit_struct,
+    Union => visit_union,
+    Member => visit_member,
+    Enum => visit_enum,
+    TaggedUnion => visit_tagged_union,
+    VariableList => visit_variable_list,
+    Variable => visit_variable,
+    FunctionPrototype => visit_function_prototype,
+    Function => visit_function,
+    Class => visit_class,
+    ClassMember => visit_class_member,
+    DeclaredType => visit_declared_type,
+    CodeBlock => visit_code_block,
+    Statement => visit_statement,
+    Expression => visit_expression,
+    Initializer => visit_initializer,
+}
+
+/// Draws a subtree of the syntax tree as a Graphviz graph, one box per node labelled with
+/// its variant, the name it declares or uses and the byte range of its source. `tokens` are
+/// the tokens the subtree was parsed from; a node is given the range of the first tokens
+/// under its parent that it prints as, so nodes the printer writes differently, and all
+/// nodes when `tokens` is empty, have no range. Wrappers that only pick a variant, such as
+/// `HeaderStatement` or `BlockOrStatement`, are drawn as the node they hold.
+pub fn to_dot<N: Node + ?Sized>(node: &N, tokens: &[TokenWithTrivia]) -> String {
+    let mut graph = AstGraph {
+        output: "digraph ast {\n    node [shape=box, fontname=\"monospace\"];\n".to_string(),
+        next: 0,
+        tokens,
+        parents: vec![Parent {id: None, tokens: 0..tokens.len()}],
+    };
+    node.accept(&mut graph);
+    graph.output + "}\n"
+}
+
+/// Writes a DOT string, escaping quotes, backslashes and line breaks.
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n"))
+}
+
+fn named(kind: &str, name: &Option<String>) -> String {
+    match name {
+        Some(name) => format!("{} {}", kind, name),
+        None => kind.to_string(),
+    }
+}
+
+/// A node whose children are being drawn, or the top of the graph when `id` is `None`.
+struct Parent {
+    id: Option<usize>,
+    /// The tokens its children are looked for in, from the end of the last one found.
+    tokens: Range<usize>,
+}
+
+struct AstGraph<'t> {
+    output: String,
+    next: usize,
+    tokens: &'t [TokenWithTrivia],
+    /// Innermost last.
+    parents: Vec<Parent>,
+}
+
+impl AstGraph<'_> {
+    /// Draws a node under the current parent and the subtree `walk` draws under it.
+    /// `source` is the node as the printer writes it, for finding its tokens.
+    fn node(&mut self, label: String, source: Option<AstNode>, walk: impl FnOnce(&mut Self)) {
+        let id = self.next;
+        self.next += 1;
+        let found = source.and_then(|source| self.locate(&source));
+        let label = match &found {
+            Some(found) => format!("{} {}..{}", label, self.tokens[found.start].span.start, self.tokens[found.end - 1].span.end),
+            None => label,
+        };
+        self.output += &format!("    n{} [label={}];\n", id, quote(&label));
+        let Some(parent) = self.parents.last_mut() else {
+            return;
+        };
+        if let Some(parent) = parent.id {
+            self.output += &format!("    n{} -> n{};\n", parent, id);
+        }
+        let tokens = match found {
+            Some(found) => {
+                parent.tokens.start = found.end;
+                found
+            },
+            None => parent.tokens.clone(),
+        };
+        self.parents.push(Parent {id: Some(id), tokens});
+        walk(self);
+        self.parents.pop();
+    }
+
+    /// The first tokens under the current parent that spell the printed node.
+    fn locate(&self, node: &AstNode) -> Option<Range<usize>> {
+        let window = self.parents.last()?.tokens.clone();
+        if window.is_empty() {
+            return None;
+        }
+        let printed = lex(&print_node(node)).ok()?;
+        if printed.is_empty() || printed.len() > window.len() {
+            return None;
+        }
+        (window.start..=window.end - printed.len())
+            .find(|&start| self.tokens[start..start + printed.len()].iter().zip(&printed).all(|(token, printed)| token.token == *printed))
+            .map(|start| start..start + printed.len())
+    }
+}
+
+impl<'ast> Visitor<'ast> for AstGraph<'_> {
+    fn visit_header(&mut self, header: &'ast Header) {
+        self.node("Header".to_string(), Some(AstNode::Header(header.clone())), |graph| walk_header(graph, header));
+    }
+
+    fn visit_preprocessor(&mut self, preprocessor: &'ast Preprocessor) {
+        self.node(format!("Preprocessor {}", preprocessor.value.trim()), Some(AstNode::Preprocessor(preprocessor.clone())), |_| {});
+    }
+
+    fn visit_static_assert(&mut self, static_assert: &'ast StaticAssert) {
+        self.node("StaticAssert".to_string(), Some(AstNode::StaticAssert(static_assert.clone())), |graph| walk_static_assert(graph, static_assert));
+    }
+
+    fn visit_struct(&mut self, struct_: &'ast Struct) {
+        self.node(named("Struct", &struct_.name), Some(AstNode::Struct(struct_.clone())), |graph| walk_struct(graph, struct_));
+    }
+
+    fn visit_union(&mut self, union_: &'ast Union) {
+        self.node(named("Union", &union_.name), Some(AstNode::Union(union_.clone())), |graph| walk_union(graph, union_));
+    }
+
+    fn visit_bit_field_list(&mut self, bit_fields: &'ast BitFieldList) {
+        self.node("BitFields".to_string(), Some(AstNode::BitFieldList(bit_fields.clone())), |graph| walk_bit_field_list(graph, bit_fields));
+    }
+
+    fn visit_bit_field(&mut self, bit_field: &'ast BitField) {
+        self.node(named("BitField", &bit_field.name), None, |graph| walk_bit_field(graph, bit_field));
+    }
+
+    fn visit_enum(&mut self, enum_: &'ast Enum) {
+        self.node(named("Enum", &enum_.name), Some(AstNode::Enum(enum_.clone())), |graph| walk_enum(graph, enum_));
+    }
+
+    fn visit_enum_member(&mut self, member: &'ast EnumMember) {
+        self.node(format!("EnumMember {}", member.name), Some(AstNode::EnumMember(member.clone())), |graph| walk_enum_member(graph, member));
+    }
+
+    fn visit_tagged_union(&mut self, tagged: &'ast TaggedUnion) {
+        self.node(format!("TaggedUnion {}", tagged.name), Some(AstNode::TaggedUnion(tagged.clone())), |graph| walk_tagged_union(graph, tagged));
+    }
+
+    fn visit_tagged_union_member(&mut self, member: &'ast TaggedUnionMember) {
+        self.node(format!("Variant {}", member.name), None, |graph| walk_tagged_union_member(graph, member));
+    }
+
+    fn visit_variable_list(&mut self, variable_list: &'ast VariableList) {
+        self.node("VariableList".to_string(), Some(AstNode::VariableList(variable_list.clone())), |graph| walk_variable_list(graph, variable_list));
+    }
+
+    fn visit_variable(&mut self, variable: &'ast Variable) {
+        self.node(format!("Variable {}", variable.name), Some(AstNode::Variable(variable.clone())), |graph| walk_variable(graph, variable));
+    }
+
+    fn visit_function_prototype(&mut self, prototype: &'ast FunctionPrototype) {
+        self.node(format!("FunctionPrototype {}", prototype.name), Some(AstNode::FunctionPrototype(prototype.clone())), |graph| walk_function_prototype(graph, prototype));
+    }
+
+    fn visit_function(&mut self, function: &'ast Function) {
+        self.node(format!("Function {}", function.name), Some(AstNode::Function(function.clone())), |graph| walk_function(graph, function));
+    }
+
+    fn visit_function_argument(&mut self, argument: &'ast FunctionArgument) {
+        match argument {
+            FunctionArgument::Parameter(parameter) => self.visit_parameter(parameter),
+            FunctionArgument::Ellipsis => self.node("Ellipsis".to_string(), Some(AstNode::FunctionArgument(argument.clone())), |_| {}),
+        }
+    }
+
+    fn visit_parameter(&mut self, parameter: &'ast Parameter) {
+        self.node(named("Parameter", &parameter.name), Some(AstNode::Parameter(parameter.clone())), |graph| walk_parameter(graph, parameter));
+    }
+
+    fn visit_class(&mut self, class: &'ast Class) {
+        let kind = if class.abstract_ { "AbstractClass" } else { "Class" };
+        self.node(format!("{} {}", kind, class.name), Some(AstNode::Class(class.clone())), |graph| walk_class(graph, class));
+    }
+
+    fn visit_operator_overload(&mut self, overload: &'ast OperatorOverload) {
+        let (OperatorOverload::Normal { op, .. } | OperatorOverload::Abstract { op, .. }) = overload;
+        self.node(format!("OperatorOverload {}", op), None, |graph| walk_operator_overload(graph, overload));
+    }
+
+    /// Leaves out the type, which every declarator draws with its pointers and arrays.
+    fn visit_declaration_specifiers(&mut self, specifiers: &'ast DeclarationSpecifiers) {
+        for alignas in &specifiers.alignas {
+            self.visit_type_or_expression(alignas);
+        }
+        for attribute in &specifiers.attributes {
+            self.visit_attribute(attribute);
+        }
+        if let Some(definition) = &specifiers.definition {
+            self.visit_definition(definition);
+        }
+    }
+
+    fn visit_attribute(&mut self, attribute: &'ast Attribute) {
+        self.node(format!("Attribute {}", attribute), Some(AstNode::Attribute(attribute.clone())), |_| {});
+    }
+
+    fn visit_type(&mut self, type_: &'ast Type) {
+        self.node(format!("Type {}", print_node(&AstNode::Type(type_.clone()))), Some(AstNode::Type(type_.clone())), |graph| walk_type(graph, type_));
+    }
+
+    fn visit_declared_type(&mut self, type_: &'ast DeclaredType) {
+        let label = match type_ {
+            DeclaredType::Base(_) => return walk_declared_type(self, type_),
+            DeclaredType::Pointer(..) => "Pointer",
+            DeclaredType::Array(..) => "Array",
+            DeclaredType::Function(..) => "Function",
+        };
+        self.node(label.to_string(), Some(AstNode::DeclaredType(type_.clone())), |graph| walk_declared_type(graph, type_));
+    }
+
+    fn visit_statement(&mut self, statement: &'ast Statement) {
+        let label = match statement {
+            Statement::Preprocessor(_) | Statement::VariableList(_) | Statement::StaticAssert(_) |
+            Statement::Block(_) => return walk_statement(self, statement),
+            Statement::Expression(_) => "Expression".to_string(),
+            Statement::Return(_) => "Return".to_string(),
+            Statement::If(..) => "If".to_string(),
+            Statement::While(..) => "While".to_string(),
+            Statement::DoWhile(..) => "DoWhile".to_string(),
+            Statement::For(..) => "For".to_string(),
+            Statement::Switch(..) => "Switch".to_string(),
+            Statement::Attributes(_) => "Attributes".to_string(),
+            Statement::Attributed(..) => "Attributed".to_string(),
+            Statement::Break => "Break".to_string(),
+            Statement::Continue => "Continue".to_string(),
+            Statement::Goto(label) => format!("Goto {}", label),
+            Statement::Label(label) => format!("Label {}", label),
+        };
+        self.node(label, Some(AstNode::Statement(statement.clone())), |graph| walk_statement(graph, statement));
+    }
+
+    fn visit_code_block(&mut self, block: &'ast CodeBlock) {
+        self.node("Block".to_string(), Some(AstNode::CodeBlock(block.clone())), |graph| walk_code_block(graph, block));
+    }
+
+    fn visit_switch_case(&mut self, case: &'ast SwitchCase) {
+        let label = if case.default { "Default" } else { "Case" };
+        self.node(label.to_string(), Some(AstNode::SwitchCase(case.clone())), |graph| walk_switch_case(graph, case));
+    }
+
+    fn visit_expression(&mut self, expression: &'ast Expression) {
+        let label = match expression {
+            Expression::Blank => "Blank".to_string(),
+            Expression::Identifier(name) => format!("Identifier {}", name),
+            Expression::Literal(_) => format!("Literal {}", expression.get_value().unwrap_or_default()),
+            Expression::Sizeof(_) => "Sizeof".to_string(),
+            Expression::Alignof(_) => "Alignof".to_string(),
+            Expression::Offsetof(_, member) => format!("Offsetof {}", member),
+            Expression::Unary(operator, _) => match operator {
+                UnaryOperator::Cast(_) => "Cast".to_string(),
+                operator => format!("Unary {:?}", operator),
+            },
+            Expression::Binary(operator, ..) => format!("Binary {:?}", operator),
+            Expression::Ternary(..) => "Ternary".to_string(),
+            Expression::CallFunction(name, _) => format!("CallFunction {}", name),
+            Expression::Call(..) => "Call".to_string(),
+            Expression::Generic(..) => "Generic".to_string(),
+            Expression::InitializerList(_) => "InitializerList".to_string(),
+            Expression::TaggedInitializer(name, _) => format!("TaggedInitializer {}", name),
+            Expression::StatementList(_) => "StatementList".to_string(),
+            Expression::Expression(_) => "Expression".to_string(),
+            Expression::Parentheses(_) => "Parentheses".to_string(),
+        };
+        self.node(label, Some(AstNode::Expression(expression.clone())), |graph| walk_expression(graph, expression));
+    }
+
+    fn visit_generic_association(&mut self, association: &'ast GenericAssociation) {
+        let label = if association.type_.is_some() { "Association" } else { "Default" };
+        self.node(label.to_string(), Some(AstNode::GenericAssociation(association.clone())), |graph| walk_generic_association(graph, association));
+    }
+
+    fn visit_initializer(&mut self, initializer: &'ast Initializer) {
+        let label = match initializer {
+            Initializer::Expression(_) => return walk_initializer(self, initializer),
+            Initializer::List(_) => "InitializerList",
+            Initializer::Designated(..) => "Designated",
+        };
+        self.node(label.to_string(), Some(AstNode::Initializer(initializer.clone())), |graph| walk_initializer(graph, initializer));
+    }
+
+    fn visit_designator(&mut self, designator: &'ast Designator) {
+        match designator {
+            Designator::Member(name) => self.node(format!("Member {}", name), Some(AstNode::Designator(designator.clone())), |_| {}),
+            Designator::Index(_) => self.node("Index".to_string(), Some(AstNode::Designator(designator.clone())), |graph| walk_designator(graph, designator)),
+        }
+    }
+}
+
+/// Draws the classes of a program with an arrow from each class to the one it extends, and
+/// the tagged unions with an arrow to each of their variants. Abstract classes are dashed,
+/// and parents that the program does not define are drawn dotted.
+pub fn class_graph(header: &Header) -> String {
+    let mut output = "digraph classes {\n    rankdir=BT;\n    node [shape=box, fontname=\"monospace\"];\n".to_string();
+    let classes = header.statements.iter()
+        .filter_map(|statement| match statement {
+            HeaderStatement::Class(class) => Some(class),
+            _ => None,
+        })
+        .collect::<Vec<&Class>>();
+    let defined = classes.iter().map(|class| class.name.as_str()).collect::<HashSet<&str>>();
+
+    for class in &classes {
+        let style = if class.abstract_ { ", style=dashed" } else { "" };
+        output += &format!("    {} [label={}{}];\n", quote(&class.name), quote(&class_label(class)), style);
+    }
+    let mut undefined = HashSet::new();
+    for class in &classes {
+        let Some(parent) = &class.parent else {
+            continue;
+        };
+        if !defined.contains(parent.as_str()) && undefined.insert(parent) {
+            output += &format!("    {} [style=dotted];\n", quote(parent));
+        }
+        output += &format!("    {} -> {} [arrowhead=empty];\n", quote(&class.name), quote(parent));
+    }
+
+    for statement in &header.statements {
+        let HeaderStatement::TaggedUnion(tagged) = statement else {
+            continue;
+        };
+        let id = format!("tagged {}", tagged.name);
+        output += &format!("    {} [label={}, shape=ellipse];\n", quote(&id), quote(&id));
+        for member in &tagged.members {
+            let variant = format!("{}::{}", tagged.name, member.name);
+            let fields = member.value.iter().flatten().flat_map(member_names).collect::<Vec<String>>();
+            let label = match fields.is_empty() {
+                true => member.name.clone(),
+                false => format!("{} {{ {} }}", member.name, fields.join(", ")),
+            };
+            output += &format!("    {} [label={}];\n", quote(&variant), quote(&label));
+            output += &format!("    {} -> {} [dir=back, arrowtail=diamond];\n", quote(&variant), quote(&id));
+        }
+    }
+    output + "}\n"
+}
+
+/// The name of a class followed by the names of its members, one per line.
+fn class_label(class: &Class) -> String {
+    let mut lines = vec![match class.abstract_ {
+        true => format!("abstract class {}", class.name),
+        false => format!("class {}", class.name),
+    }];
+    for member in &class.members {
+        match member {
+            ClassMember::Field(member) => lines.extend(member_names(member)),
+            ClassMember::Method(Method::Normal(function)) => lines.push(format!("{}()", function.name)),
+            ClassMember::Method(Method::Abstract(prototype)) => lines.push(format!("{}() = 0", prototype.name)),
+            ClassMember::OperatorOverload(OperatorOverload::Normal { op, .. }) => lines.push(format!("operator({})", op)),
+            ClassMember::OperatorOverload(OperatorOverload::Abstract { op, .. }) => lines.push(format!("operator({}) = 0", op)),
+        }
+    }
+    lines.join("\n")
+}
+
+fn member_names(member: &Member) -> Vec<String> {
+    match member {
+        Member::Variables(variable_list) => variable_list.variables.iter().map(|variable| variable.name.clone()).collect(),
+        Member::BitFields(bit_fields) => bit_fields.fields.iter().filter_map(|field| field.name.clone()).collect(),
+        Member::Struct(struct_) => vec![named("struct", &struct_.name)],
+        Member::Union(union_) => vec![named("union", &union_.name)],
+    }
+}
+
+
+#[cfg(test)]
+mod dot_tests {
+    use super::*;
+    use crate::logos_lexer::lex_with_trivia;
+    use crate::parser::Parser;
+
+    fn parse(source: &str) -> Header {
+        Parser::new(&lex(source).unwrap()).parse().unwrap()
+    }
+
+    /// The labels of a graph's nodes and the labels at both ends of each edge.
+    fn shape(dot: &str) -> (Vec<String>, Vec<(String, String)>) {
+        let mut labels = std::collections::HashMap::new();
+        let mut nodes = Vec::new();
+        let mut edges = Vec::new();
+        for line in dot.lines().map(str::trim) {
+            if let Some((id, label)) = line.split_once(" [label=\"") {
+                let label = label.trim_end_matches("\"];").to_string();
+                labels.insert(id.to_string(), label.clone());
+                nodes.push(label);
+            }
+            else if let Some((from, to)) = line.trim_end_matches(';').split_once(" -> ") {
+                edges.push((labels[from].clone(), labels[to].clone()));
+            }
+        }
+        (nodes, edges)
+    }
+
+    #[test]
+    fn test_expression_tree() {
+        let tokens = lex_with_trivia("int x = 1 - 2 - 3 * y;\n").unwrap();
+        let header = Parser::with_trivia(&tokens).parse().unwrap();
+        let HeaderStatement::Variable(variables) = &header.statements[0] else {
+            panic!("Expected a variable: {:?}", header.statements[0]);
+        };
+        let Some(VariableValue::Expression(value)) = &variables.variables[0].value else {
+            panic!("Expected a value: {:?}", variables.variables[0]);
+        };
+        let dot = to_dot(value, &tokens);
+        assert!(dot.starts_with("digraph ast {\n"), "{}", dot);
+        let (nodes, edges) = shape(&dot);
+        assert_eq!(nodes, ["Binary Subtract 8..21", "Binary Subtract 8..13", "Literal 1 8..9", "Literal 2 12..13",
+            "Binary Multiply 16..21", "Literal 3 16..17", "Identifier y 20..21"]);
+        assert_eq!(edges[0], ("Binary Subtract 8..21".to_string(), "Binary Subtract 8..13".to_string()));
+        assert_eq!(edges.len(), nodes.len() - 1);
+
+        // Without the tokens, or for a node printed differently from its source, there is no range.
+        let (nodes, _) = shape(&to_dot(value, &[]));
+        assert_eq!(nodes[0], "Binary Subtract");
+        let tokens = lex_with_trivia("long unsigned x = (1);\n").unwrap();
+        let (nodes, _) = shape(&to_dot(&Parser::with_trivia(&tokens).parse().unwrap(), &tokens));
+        assert_eq!(nodes, ["Header", "VariableList", "Variable x", "Type unsigned long", "Parentheses 18..21", "Literal 1 19..20"]);
+    }
+
+    #[test]
+    fn test_header_tree() {
+        let header = parse("/// Docs with \"quotes\".\nstruct point { int x; };\nint main(void) { switch (1) { case 1: break; } return 0; }\n");
+        let (nodes, edges) = shape(&to_dot(&header, &[]));
+        assert_eq!(nodes, ["Header", "Struct point", "VariableList", "Variable x", "Type int", "Function main",
+            "Type int", "Parameter", "Type void", "Block", "Switch", "Literal 1", "Case", "Literal 1", "Break",
+            "Return", "Literal 0"]);
+        assert!(edges.contains(&("Case".to_string(), "Break".to_string())));
+        assert_eq!(quote("say \"hi\"\\\n"), "\"say \\\"hi\\\"\\\\\\n\"");
+    }
+
+    #[test]
+    fn test_class_graph() {
+        let header = parse("abstract class Shape { int area(); };\n\
+                            class Square : Shape { int side; int area() { return side * side; } int operator(+)(int n); };\n\
+                            class Widget : Base { };\n\
+                            tagged Value { Number { double n; }, Empty };\n");
+        let dot = class_graph(&header);
+        assert!(dot.contains("    \"Shape\" [label=\"abstract class Shape\\narea() = 0\", style=dashed];\n"), "{}", dot);
+        assert!(dot.contains("    \"Square\" [label=\"class Square\\nside\\narea()\\noperator(+) = 0\"];\n"), "{}", dot);
+        assert!(dot.contains("    \"Square\" -> \"Shape\" [arrowhead=empty];\n"), "{}", dot);
+        assert!(dot.contains("    \"Base\" [style=dotted];\n    \"Widget\" -> \"Base\" [arrowhead=empty];\n"), "{}", dot);
+        assert!(dot.contains("    \"Value::Number\" [label=\"Number { n }\"];\n"), "{}", dot);
+        assert!(dot.contains("    \"Value::Empty\" -> \"tagged Value\" [dir=back, arrowtail=diamond];\n"), "{}", dot);
+    }
+}
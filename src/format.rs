@@ -0,0 +1,504 @@
+use crate::ast::Header;
+use crate::logos_lexer::{lex_with_trivia, Token, TokenWithTrivia, Trivia};
+use crate::parser::Parser;
+use crate::printer::{print_with_style, Style};
+
+
+/// How many differences between the source and printed tokens `align` looks through
+/// before it gives up on the shortest edit script and pairs tokens greedily.
+const MAX_EDITS: usize = 2048;
+
+/// How far ahead the greedy pairing looks for a printed token equal to a source token.
+const GREEDY_WINDOW: usize = 16;
+
+/// A line the printer does not produce that the formatter puts back.
+#[derive(Debug, Clone, PartialEq)]
+enum Extra {
+    Blank,
+    Comment(String),
+}
+
+/// What goes around one printed line.
+#[derive(Default)]
+struct Line {
+    /// Comments and blank lines before the line.
+    above: Vec<Extra>,
+    /// Block comments written inside the line, by byte offset in the printed source.
+    inserts: Vec<(usize, String)>,
+    /// Line comments at the end of the line.
+    end: Vec<String>,
+    /// Comments and blank lines after the line.
+    below: Vec<Extra>,
+}
+
+/// Formats source text in the given style. The code is laid out by the printer and the
+/// comments and blank lines, which the tree does not keep, are put back next to the tokens
+/// they were written next to. Formatting the result again gives the same text.
+pub fn format(source: &str, style: &Style) -> Result<String, String> {
+    let (tokens, header) = parse(source)?;
+    let printed = print_with_style(&header, style);
+    let printed_tokens = lex_with_trivia(&printed).map_err(|errors| format!("{:?}", errors))?;
+
+    let matches = align(
+        &tokens.iter().map(|token| token.token.clone()).collect::<Vec<Token>>(),
+        &printed_tokens.iter().map(|token| token.token.clone()).collect::<Vec<Token>>(),
+    );
+    let mut weaver = Weaver::new(&printed, &printed_tokens, style.indent_width);
+    weaver.place(source, &tokens, &matches);
+    let formatted = weaver.finish();
+
+    match parse(&formatted) {
+        Ok((_, reparsed)) if reparsed == header => Ok(formatted),
+        Ok(_) => Err("Formatting would change the meaning of the source".to_string()),
+        Err(err) => Err(format!("The formatted source does not parse: {}", err)),
+    }
+}
+
+fn parse(source: &str) -> Result<(Vec<TokenWithTrivia>, Header), String> {
+    let tokens = lex_with_trivia(source).map_err(|errors| format!("{:?}", errors))?;
+    let header = Parser::with_trivia(&tokens).parse()?;
+    Ok((tokens, header))
+}
+
+/// Pairs each source token with the printed token it became, by the longest common
+/// subsequence of the two token lists as Myers' diff finds it.
+fn align(source: &[Token], printed: &[Token]) -> Vec<Option<usize>> {
+    let (n, m) = (source.len() as isize, printed.len() as isize);
+    let max = (n + m) as usize;
+    let offset = max as isize + 1;
+    let mut v = vec![0isize; 2 * max + 3];
+    // The furthest points of each round, for walking the edit script back.
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    for d in 0..=max.min(MAX_EDITS) as isize {
+        for k in (-d..=d).step_by(2) {
+            let index = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[index - 1] < v[index + 1]) {
+                v[index + 1]
+            }
+            else {
+                v[index - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && source[x as usize] == printed[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[index] = x;
+
+            if x >= n && y >= m {
+                trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+                return backtrack(&trace, source.len(), printed.len());
+            }
+        }
+        trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+    }
+
+    greedy(source, printed)
+}
+
+fn backtrack(trace: &[Vec<isize>], n: usize, m: usize) -> Vec<Option<usize>> {
+    let mut matches = vec![None; n];
+    let (mut x, mut y) = (n as isize, m as isize);
+
+    for d in (1..trace.len() as isize).rev() {
+        let k = x - y;
+        let previous = |k: isize| trace[d as usize - 1][(k + d - 1) as usize];
+        let previous_k = if k == -d || (k != d && previous(k - 1) < previous(k + 1)) {
+            k + 1
+        }
+        else {
+            k - 1
+        };
+        let previous_x = previous(previous_k);
+        let previous_y = previous_x - previous_k;
+        while x > previous_x && y > previous_y {
+            x -= 1;
+            y -= 1;
+            matches[x as usize] = Some(y as usize);
+        }
+        x = previous_x;
+        y = previous_y;
+    }
+    while x > 0 && y > 0 {
+        x -= 1;
+        y -= 1;
+        matches[x as usize] = Some(y as usize);
+    }
+
+    matches
+}
+
+/// Pairs each source token with the next equal printed token close ahead, for sources
+/// that printing changed too much to diff.
+fn greedy(source: &[Token], printed: &[Token]) -> Vec<Option<usize>> {
+    let mut next = 0;
+    source.iter().map(|token| {
+        let found = printed[next..].iter().take(GREEDY_WINDOW).position(|printed| printed == token)?;
+        next += found + 1;
+        Some(next - 1)
+    }).collect()
+}
+
+/// Puts the trivia of the source back into the printed source.
+struct Weaver<'a> {
+    printed: &'a str,
+    tokens: &'a [TokenWithTrivia],
+    indent_width: usize,
+    /// The byte offset each printed line starts at.
+    starts: Vec<usize>,
+    lines: Vec<Line>,
+}
+
+impl<'a> Weaver<'a> {
+    fn new(printed: &'a str, tokens: &'a [TokenWithTrivia], indent_width: usize) -> Weaver<'a> {
+        let starts = std::iter::once(0)
+            .chain(printed.match_indices('\n').map(|(index, _)| index + 1))
+            .filter(|&start| start < printed.len())
+            .collect::<Vec<usize>>();
+        let lines = starts.iter().map(|_| Line::default()).collect();
+        Weaver {printed, tokens, indent_width, starts, lines}
+    }
+
+    fn line_of(&self, offset: usize) -> usize {
+        self.starts.partition_point(|&start| start <= offset).saturating_sub(1)
+    }
+
+    fn text(&self, line: usize) -> &'a str {
+        let end = self.starts.get(line + 1).copied().unwrap_or(self.printed.len());
+        self.printed[self.starts[line]..end].trim_end_matches('\n')
+    }
+
+    fn indent(&self, line: usize) -> &'a str {
+        let text = self.text(line);
+        &text[..text.len() - text.trim_start().len()]
+    }
+
+    fn ends_line(&self, index: usize) -> bool {
+        index + 1 == self.tokens.len() || self.starts_line(index + 1)
+    }
+
+    fn starts_line(&self, index: usize) -> bool {
+        index == 0 || self.line_of(self.tokens[index - 1].span.start) != self.line_of(self.tokens[index].span.start)
+    }
+
+    /// The line that comments in front of a printed token go above: the token's own line,
+    /// or the first line of the doc comment the printer wrote for it.
+    fn anchor(&self, index: usize) -> usize {
+        let mut line = self.line_of(self.tokens[index].span.start);
+        if self.tokens[index].doc_comment().is_some() {
+            while line > 0 && self.text(line - 1).trim_start().starts_with("///") {
+                line -= 1;
+            }
+        }
+        line
+    }
+
+    /// Hands the trivia of every source token to the printed tokens they were paired with.
+    /// Trivia in front of a token goes to the next paired token, trivia after it to the last one.
+    fn place(&mut self, source: &str, tokens: &[TokenWithTrivia], matches: &[Option<usize>]) {
+        if self.tokens.is_empty() {
+            return;
+        }
+        let mut next = vec![None; tokens.len() + 1];
+        for index in (0..tokens.len()).rev() {
+            next[index] = matches[index].or(next[index + 1]);
+        }
+
+        let mut previous = None;
+        for (index, token) in tokens.iter().enumerate() {
+            // A preprocessor line takes its newline with it.
+            let newlines = usize::from(index > 0 && source[..tokens[index - 1].span.end].ends_with('\n'));
+            match next[index] {
+                Some(target) => self.leading(target, &token.leading, newlines),
+                None => self.trailing(self.tokens.len() - 1, &token.leading),
+            }
+
+            if let Some(target) = matches[index] {
+                self.between_pieces(target, &token.pieces);
+            }
+
+            previous = matches[index].or(previous);
+            match (previous, next[index]) {
+                (Some(target), _) => self.trailing(target, &token.trailing),
+                (None, Some(target)) => self.leading(target, &token.trailing, 0),
+                (None, None) => {},
+            }
+        }
+    }
+
+    fn leading(&mut self, index: usize, trivia: &[Trivia], mut newlines: usize) {
+        let keep_docs = self.tokens[index].doc_comment().is_none();
+        let start = self.tokens[index].span.start;
+        let mut above = Vec::new();
+
+        for (position, item) in trivia.iter().enumerate() {
+            match item {
+                Trivia::Whitespace(text) => {
+                    newlines += text.matches('\n').count();
+                    if newlines >= 2 {
+                        above.push(Extra::Blank);
+                        newlines = 0;
+                    }
+                },
+                Trivia::DocComment(_) if !keep_docs => newlines = 0,
+                Trivia::Comment(text) | Trivia::DocComment(text) => {
+                    newlines = 0;
+                    let own_line = text.starts_with("//") || trivia[position + 1..].iter()
+                        .any(|item| matches!(item, Trivia::Whitespace(text) if text.contains('\n')));
+                    if own_line {
+                        above.push(Extra::Comment(text.clone()));
+                    }
+                    else {
+                        let space = if self.printed[..start].ends_with([' ', '\n']) || start == 0 { "" } else { " " };
+                        let line = self.line_of(start);
+                        self.lines[line].inserts.push((start, format!("{}{} ", space, text)));
+                    }
+                },
+            }
+        }
+
+        // Comments inside an empty block open it up.
+        let line = self.line_of(start);
+        let empty_block = self.tokens[index].token == Token::RightBrace
+            && index > 0
+            && self.tokens[index - 1].token == Token::LeftBrace
+            && self.tokens[index - 1].span.end == start;
+        if empty_block && above.iter().any(|extra| matches!(extra, Extra::Comment(_))) {
+            let indent = self.indent(line);
+            let mut insert = "\n".to_string();
+            for extra in above {
+                if let Extra::Comment(text) = extra {
+                    insert.push_str(&format!("{}{}{}\n", indent, " ".repeat(self.indent_width), text));
+                }
+            }
+            insert.push_str(indent);
+            self.lines[line].inserts.push((start, insert));
+            return;
+        }
+
+        // A blank line is only kept in front of a token that starts a line, and not
+        // before a closing brace.
+        if !self.starts_line(index) || self.text(line).trim_start().starts_with('}') {
+            while above.last() == Some(&Extra::Blank) {
+                above.pop();
+            }
+        }
+        let anchor = self.anchor(index);
+        self.lines[anchor].above.extend(above);
+    }
+
+    fn trailing(&mut self, index: usize, trivia: &[Trivia]) {
+        let end = self.tokens[index].span.end;
+        let line = self.line_of(self.tokens[index].span.start);
+        // A preprocessor line takes its newline with it.
+        let mut newlines = usize::from(self.printed[..end].ends_with('\n'));
+        let mut line_ended = newlines > 0;
+
+        for item in trivia {
+            match item {
+                Trivia::Whitespace(text) => {
+                    let count = text.matches('\n').count();
+                    line_ended |= count > 0;
+                    newlines += count;
+                    if newlines >= 2 {
+                        self.lines[line].below.push(Extra::Blank);
+                        newlines = 0;
+                    }
+                },
+                Trivia::Comment(text) | Trivia::DocComment(text) => {
+                    newlines = 0;
+                    if line_ended {
+                        self.lines[line].below.push(Extra::Comment(text.clone()));
+                    }
+                    else if text.starts_with("//") && !self.ends_line(index) {
+                        // The printer joined the line, so break it again after the comment.
+                        let brace = matches!(self.tokens[index + 1].token, Token::LeftBrace | Token::RightBrace | Token::RightParen | Token::RightBracket);
+                        let indent = format!("{}{}", self.indent(line), " ".repeat(if brace { 0 } else { self.indent_width }));
+                        self.lines[line].inserts.push((end, format!(" {}\n{}", text, indent)));
+                    }
+                    else if text.starts_with("//") {
+                        self.lines[line].end.push(text.clone());
+                    }
+                    else {
+                        // Separators and closing brackets stay attached to the comment, as they were to the token.
+                        let space = if self.printed[end..].starts_with([' ', '\n', ',', ';', ')', ']']) || end == self.printed.len() { "" } else { " " };
+                        self.lines[line].inserts.push((end, format!(" {}{}", text, space)));
+                    }
+                },
+            }
+        }
+    }
+
+    /// Puts the comments between the pieces of a joined string literal after the same piece
+    /// of the printed literal, or after the whole literal if it was printed differently.
+    fn between_pieces(&mut self, index: usize, pieces: &[TokenWithTrivia]) {
+        let tokens = self.tokens;
+        let printed = &tokens[index];
+        let line = self.line_of(printed.span.start);
+        let indent = format!("{}{}", self.indent(line), " ".repeat(self.indent_width));
+
+        for (position, pair) in pieces.windows(2).enumerate() {
+            let end = match printed.pieces.get(position) {
+                Some(piece) if printed.pieces.len() == pieces.len() => piece.span.end,
+                _ => printed.span.end,
+            };
+            for item in pair[0].trailing.iter().chain(&pair[1].leading) {
+                match item {
+                    Trivia::Whitespace(_) => {},
+                    // A line comment breaks the literal across lines again.
+                    Trivia::Comment(text) | Trivia::DocComment(text) if text.starts_with("//") => {
+                        self.lines[line].inserts.push((end, format!(" {}\n{}", text, indent)));
+                    },
+                    Trivia::Comment(text) | Trivia::DocComment(text) => self.lines[line].inserts.push((end, format!(" {}", text))),
+                }
+            }
+        }
+    }
+
+    /// Writes an extra line with the indentation of the line it goes next to.
+    fn extra(output: &mut String, extra: &Extra, indent: &str) {
+        match extra {
+            Extra::Blank => {
+                let after_brace = output.trim_end().ends_with('{');
+                if !output.is_empty() && !output.ends_with("\n\n") && !after_brace {
+                    output.push('\n');
+                }
+            },
+            Extra::Comment(text) => {
+                output.push_str(indent);
+                output.push_str(text);
+                output.push('\n');
+            },
+        }
+    }
+
+    fn finish(mut self) -> String {
+        let mut output = String::new();
+
+        for index in 0..self.lines.len() {
+            let text = self.text(index);
+            let start = self.starts[index];
+            let indent = self.indent(index);
+            let mut line = std::mem::take(&mut self.lines[index]);
+
+            // Comments before a closing brace belong to the block it closes.
+            let inner = if text.trim_start().starts_with('}') {
+                format!("{}{}", indent, " ".repeat(self.indent_width))
+            }
+            else {
+                indent.to_string()
+            };
+            for extra in &line.above {
+                Self::extra(&mut output, extra, &inner);
+            }
+
+            if text.is_empty() && (output.is_empty() || output.ends_with("\n\n")) {
+                continue;
+            }
+            line.inserts.sort_by_key(|(offset, _)| *offset);
+            let mut content = String::new();
+            let mut copied = 0;
+            for (offset, insert) in &line.inserts {
+                let offset = (offset - start).min(text.len()).max(copied);
+                content.push_str(&text[copied..offset]);
+                content.push_str(insert);
+                copied = offset;
+                if insert.contains('\n') {
+                    copied = text.len() - text[copied..].trim_start().len();
+                }
+            }
+            content.push_str(&text[copied..]);
+            if !line.end.is_empty() {
+                content.truncate(content.trim_end().len());
+                content.push(' ');
+                content.push_str(&line.end.join(" "));
+            }
+            output.push_str(&content);
+            output.push('\n');
+
+            for extra in &line.below {
+                Self::extra(&mut output, extra, indent);
+            }
+        }
+
+        while output.ends_with("\n\n") {
+            output.pop();
+        }
+        // A preprocessor line at the end of the file keeps going without a newline,
+        // since the newline would be part of it.
+        if !self.printed.ends_with('\n') && output.ends_with('\n') {
+            output.pop();
+        }
+        output
+    }
+}
+
+
+#[cfg(test)]
+mod format_tests {
+    use super::*;
+    use crate::printer::BraceStyle;
+
+    /// Formats `input` and checks that formatting the result changes nothing.
+    fn format_twice(input: &str, style: &Style) -> String {
+        let formatted = match format(input, style) {
+            Ok(formatted) => formatted,
+            Err(err) => panic!("Failed to format {}: {}", input, err),
+        };
+        assert_eq!(format(&formatted, style).as_deref(), Ok(formatted.as_str()), "Formatting is not idempotent for {}", input);
+        formatted
+    }
+
+    #[test]
+    fn test_format_keeps_comments() {
+        let input = "#include <stdio.h> // for printf\n#define N 3\n// Counts.\n\n\n/// The entry point.\nint main() { // body\n\
+                     int x = /* start */ 0;\n\n    // Loop.\n    for (;;) { x++; /* more */ }\n    char *s = \"a\" /* note */\n    \"b\";\n\
+                     char *t = \"c\" // split\n        \"d\" \"e\";\n    f(x /* c */, s /* d */);\n    return x; // done\n}\n// The end.\n";
+        assert_eq!(format_twice(input, &Style::default()),
+                   "#include <stdio.h> // for printf\n#define N 3\n\n// Counts.\n\n/// The entry point.\nint main() { // body\n\
+                    \x20   int x = /* start */ 0;\n\n    // Loop.\n    for (;;) {\n        x++; /* more */\n    }\n    char *s = \"a\" /* note */ \"b\";\n\
+                    \x20   char *t = \"c\" // split\n        \"d\" \"e\";\n    f(x /* c */, s /* d */);\n    return x; // done\n}\n// The end.\n");
+    }
+
+    #[test]
+    fn test_format_breaks_after_line_comments() {
+        let input = "int table[] = {\n    1, // one\n    2\n};\nvoid f(int x, // across\n       int y) // down\n{\n    if (x) {\n        // nothing yet\n    }\n}\n";
+        assert_eq!(format_twice(input, &Style::default()),
+                   "int table[] = {1, // one\n    2};\n\nvoid f(int x, // across\n    int y) // down\n{\n    if (x) {\n        // nothing yet\n    }\n}\n");
+    }
+
+    #[test]
+    fn test_format_styles() {
+        let input = "int f(int a) { if (a) { return 1; } return 0; }";
+        let style = Style {indent_width: 2, brace_style: BraceStyle::NextLine, ..Style::default()};
+        assert_eq!(format_twice(input, &style), "int f(int a)\n{\n  if (a)\n  {\n    return 1;\n  }\n  return 0;\n}\n");
+
+        let input = "int table[] = {100000, 200000, 300000, 400000}; void g(long first, long second) { handle(first, second, table); }";
+        let style = Style {line_width: 30, ..Style::default()};
+        assert_eq!(format_twice(input, &style),
+                   "int table[] = {\n    100000,\n    200000,\n    300000,\n    400000\n};\n\nvoid g(\n    long first,\n    long second\n) {\n    handle(\n        first,\n        second,\n        table\n    );\n}\n");
+    }
+
+    #[test]
+    fn test_format_is_idempotent() {
+        let inputs = [
+            "/* header */\nstruct point { int x; /* x */ int y; // y\n};\n\n\n\nenum color { RED, /* warm */ GREEN, };\n",
+            "int f() {\n    /// Not documentation of anything.\n    return 0;\n}\n/** Tail. */\n",
+            "class A {\n    // fields\n    int x;\n    /// Getter.\n    int get(void) { return this->x; } // inline\n};\n",
+            "int g(int a) { switch (a) { // which\n case 1: return 2; /* one */\n default: break; }\n return a; }\n",
+            "#ifdef X\n// inside\nint y;\n#endif\n\n// trailing\n\n\n",
+        ];
+        for input in inputs {
+            format_twice(input, &Style::default());
+        }
+    }
+
+    #[test]
+    fn test_align() {
+        let source = [Token::Unsigned, Token::Word("x".to_string()), Token::SemiColon];
+        let printed = [Token::Unsigned, Token::Int, Token::Word("x".to_string()), Token::SemiColon];
+        assert_eq!(align(&source, &printed), vec![Some(0), Some(2), Some(3)]);
+        assert_eq!(greedy(&source, &printed), vec![Some(0), Some(2), Some(3)]);
+    }
+}
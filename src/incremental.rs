@@ -0,0 +1,300 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::ast::{Header, HeaderStatement};
+use crate::logos_lexer::{lex_with_trivia, TokenWithTrivia};
+use crate::parser::Parser;
+
+
+/// A top-level declaration, or a run of preprocessor lines, and where its tokens end.
+struct Item {
+    /// The index one past the last token of the item.
+    end: usize,
+    statements: Vec<HeaderStatement>,
+}
+
+struct Tree {
+    tokens: Vec<TokenWithTrivia>,
+    items: Vec<Item>,
+}
+
+/// A source file that stays parsed as it is edited. An edit relexes the source from the
+/// top-level item it starts in to the first item after it, and reparses only those items;
+/// the statements of the others are kept as they are.
+pub struct Document {
+    source: String,
+    /// The tokens and items of the source, or why it does not lex or parse.
+    tree: Result<Tree, String>,
+}
+
+impl Document {
+    pub fn new(source: &str) -> Document {
+        Document {
+            source: source.to_string(),
+            tree: parse(source),
+        }
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Why the source does not lex or parse, if it does not.
+    pub fn error(&self) -> Option<&str> {
+        self.tree.as_ref().err().map(String::as_str)
+    }
+
+    pub fn tokens(&self) -> &[TokenWithTrivia] {
+        self.tree.as_ref().map_or(&[], |tree| &tree.tokens)
+    }
+
+    /// The top-level statements of the source, none if it does not parse.
+    pub fn statements(&self) -> impl Iterator<Item = &HeaderStatement> {
+        self.tree.iter().flat_map(|tree| &tree.items).flat_map(|item| &item.statements)
+    }
+
+    /// The range of tokens each top-level statement was parsed from, as
+    /// [`Parser::parse_with_ranges`] gives them.
+    pub fn ranges(&self) -> Vec<Range<usize>> {
+        let mut start = 0;
+        let mut ranges = Vec::new();
+        for item in self.tree.iter().flat_map(|tree| &tree.items) {
+            ranges.extend(std::iter::repeat_n(start..item.end, item.statements.len()));
+            start = item.end;
+        }
+        ranges
+    }
+
+    pub fn header(&self) -> Result<Header, String> {
+        self.tree.as_ref().map_err(String::clone)?;
+        Ok(Header { statements: self.statements().cloned().collect() })
+    }
+
+    /// Replaces a byte range of the source with `text`. Returns how many top-level items
+    /// were parsed again, or why the new source does not lex or parse.
+    pub fn edit(&mut self, range: Range<usize>, text: &str) -> Result<usize, String> {
+        if range.start > range.end || range.end > self.source.len() ||
+            !self.source.is_char_boundary(range.start) || !self.source.is_char_boundary(range.end) {
+            return Err(format!("Edit {:?} is outside the source", range));
+        }
+        self.source.replace_range(range.clone(), text);
+
+        let tree = std::mem::replace(&mut self.tree, Err(String::new()));
+        let parsed = match tree {
+            Ok(tree) => tree.edit(&self.source, range, text.len()),
+            Err(_) => full(&self.source),
+        };
+        match parsed {
+            Ok((tree, count)) => {
+                self.tree = Ok(tree);
+                Ok(count)
+            },
+            Err(err) => {
+                self.tree = Err(err.clone());
+                Err(err)
+            },
+        }
+    }
+}
+
+fn parse(source: &str) -> Result<Tree, String> {
+    let tokens = lex_with_trivia(source).map_err(|errors| format!("{:?}", errors))?;
+    let mut parser = Parser::with_trivia(&tokens);
+    let mut items = Vec::new();
+    while !parser.is_done() {
+        let statements = parser.header_statements()?;
+        items.push(Item { end: parser.position(), statements });
+    }
+    Ok(Tree { tokens, items })
+}
+
+fn full(source: &str) -> Result<(Tree, usize), String> {
+    let tree = parse(source)?;
+    let count = tree.items.len();
+    Ok((tree, count))
+}
+
+/// The names that `items` declare at file scope, as the parser records them.
+fn declared(items: &[Item]) -> HashMap<String, bool> {
+    let mut parser = Parser::new(&[]);
+    items.iter().flat_map(|item| &item.statements).for_each(|statement| parser.declare(statement));
+    parser.file_scope().clone()
+}
+
+fn shift(span: &Range<usize>, delta: isize) -> Range<usize> {
+    span.start.wrapping_add_signed(delta)..span.end.wrapping_add_signed(delta)
+}
+
+/// Moves a token and the pieces of a joined string literal by `delta` bytes.
+fn shift_token(token: &mut TokenWithTrivia, delta: isize) {
+    token.span = shift(&token.span, delta);
+    for piece in &mut token.pieces {
+        shift_token(piece, delta);
+    }
+}
+
+impl Tree {
+    /// Brings the tree up to date with `source`, which is the old source with the bytes
+    /// in `range` replaced by `inserted` new ones.
+    fn edit(mut self, source: &str, range: Range<usize>, inserted: usize) -> Result<(Tree, usize), String> {
+        let delta = inserted as isize - range.len() as isize;
+        let starts = std::iter::once(0).chain(self.items.iter().map(|item| item.end)).collect::<Vec<usize>>();
+        let count = self.items.len();
+
+        // Relex from the last item that starts before the edit, so that the token it starts
+        // with is untouched, to the first token of the first item whose tokens and leading
+        // trivia, which hold its doc comment, come after the edit.
+        let first = starts[..count].partition_point(|&start| self.tokens[start].span.start < range.start).saturating_sub(1);
+        let last = Some(starts[1..count].partition_point(|&start| self.tokens[start - 1].span.end <= range.end) + 1)
+            .filter(|&last| last < count && last > first);
+        let restart = match first {
+            0 => 0,
+            _ => self.tokens[starts[first]].span.start,
+        };
+        let window_end = last.map_or(source.len(), |last| self.tokens[starts[last]].span.end.wrapping_add_signed(delta));
+        let Ok(mut relexed) = lex_with_trivia(&source[restart..window_end]) else {
+            return full(source);
+        };
+        for token in &mut relexed {
+            shift_token(token, restart as isize);
+        }
+        if first > 0 {
+            relexed[0].leading = self.tokens[starts[first]].leading.clone();
+        }
+        let (old_end, leading_changed) = match last {
+            Some(last) => {
+                // The window ends with the first token that is kept, which has to come out the same.
+                let kept = &self.tokens[starts[last]];
+                let relexed_last = relexed.last_mut().unwrap();
+                if relexed_last.token != kept.token || relexed_last.span != shift(&kept.span, delta) {
+                    return full(source);
+                }
+                relexed_last.trailing = kept.trailing.clone();
+                (starts[last] + 1, relexed_last.leading != kept.leading)
+            },
+            None => (self.tokens.len(), false),
+        };
+        for token in &mut self.tokens[old_end..] {
+            shift_token(token, delta);
+        }
+        let token_delta = relexed.len() as isize - (old_end - starts[first]) as isize;
+        self.tokens.splice(starts[first]..old_end, relexed);
+
+        // Reparse from the first item until the parser lands where a kept item starts, with
+        // the same type names declared as before, since they decide how the rest parses.
+        // An item whose leading trivia changed may have a new doc comment, so it is parsed again too.
+        let kept_start = |kept: usize| starts[kept].wrapping_add_signed(token_delta);
+        let mut kept = last.map_or(count, |last| last + usize::from(leading_changed));
+        let mut parsed = Vec::new();
+        let mut parser = Parser::with_trivia(&self.tokens);
+        self.items[..first].iter().flat_map(|item| &item.statements).for_each(|statement| parser.declare(statement));
+        parser.seek(starts[first]);
+        while !parser.is_done() {
+            while kept < count && kept_start(kept) < parser.position() {
+                kept += 1;
+            }
+            if kept < count && kept_start(kept) == parser.position() && *parser.file_scope() == declared(&self.items[..kept]) {
+                break;
+            }
+            let statements = parser.header_statements()?;
+            parsed.push(Item { end: parser.position(), statements });
+        }
+        if parser.is_done() {
+            kept = count;
+        }
+
+        let reparsed = parsed.len();
+        for item in &mut self.items[kept..] {
+            item.end = item.end.wrapping_add_signed(token_delta);
+        }
+        self.items.splice(first..kept, parsed);
+        Ok((self, reparsed))
+    }
+}
+
+
+#[cfg(test)]
+mod incremental_tests {
+    use super::*;
+
+    const SOURCE: &str = "#include <stdio.h>\n\n/// A point.\nstruct point { int x; int y; };\n\n\
+                          int scale(int value) {\n    return value * 2; // twice\n}\n\n\
+                          class Counter {\n    int count;\n    void add(int n) { count = count + n; }\n};\n\n\
+                          int main() {\n    return scale(1);\n}\n";
+
+    /// Checks that a document matches what lexing and parsing its source from scratch gives.
+    fn assert_fresh(document: &Document) {
+        let tokens = lex_with_trivia(document.source()).unwrap();
+        let (header, ranges) = Parser::with_trivia(&tokens).parse_with_ranges().unwrap();
+        assert_eq!(document.tokens(), tokens.as_slice());
+        assert_eq!(document.header(), Ok(header));
+        assert_eq!(document.ranges(), ranges);
+    }
+
+    fn edit(document: &mut Document, needle: &str, text: &str) -> Result<usize, String> {
+        let start = document.source().find(needle).unwrap();
+        document.edit(start..start + needle.len(), text)
+    }
+
+    #[test]
+    fn test_edits_reparse_only_the_touched_items() {
+        let mut document = Document::new(SOURCE);
+        assert_fresh(&document);
+        let main = document.statements().last().unwrap() as *const HeaderStatement;
+
+        assert_eq!(edit(&mut document, "value * 2", "value * 3"), Ok(1));
+        assert_fresh(&document);
+        assert_eq!(edit(&mut document, "int count;", "int count;\n    int step;"), Ok(1));
+        assert_fresh(&document);
+        assert_eq!(edit(&mut document, "/// A point.", "/// A point on the plane."), Ok(2));
+        assert_fresh(&document);
+        assert_eq!(edit(&mut document, "// twice", "/* twice */"), Ok(1));
+        assert_fresh(&document);
+        assert_eq!(document.statements().last().unwrap() as *const HeaderStatement, main);
+        // The blank lines in front of `main` are its leading trivia.
+        assert_eq!(edit(&mut document, "\n\nint main", "\nint zero;\n\nint main"), Ok(3));
+        assert_fresh(&document);
+
+        // After an edit that breaks the source, the next one parses it all again.
+        assert_eq!(edit(&mut document, "#include <stdio.h>", "#include <stdlib.h>"), Ok(1));
+        assert_fresh(&document);
+        assert!(edit(&mut document, "}\n\nclass", "\nclass").is_err());
+        assert!(document.error().is_some());
+        assert_eq!(edit(&mut document, "\nclass", "}\nclass"), Ok(6));
+        assert_fresh(&document);
+    }
+
+    #[test]
+    fn test_edits_that_change_type_names() {
+        let mut document = Document::new("typedef int myint;\n\nint f(void) {\n    myint *p = 0;\n    return 0;\n}\n\nint g;\n");
+        assert_fresh(&document);
+        // Whether `myint *p = 0;` is a declaration depends on the typedef before it.
+        assert_eq!(edit(&mut document, "typedef int myint;", "int myint;"), Ok(3));
+        assert_fresh(&document);
+        assert_eq!(edit(&mut document, "int myint;", "typedef long myint;"), Ok(3));
+        assert_fresh(&document);
+        assert_eq!(edit(&mut document, "return 0;", "return 1;"), Ok(1));
+        assert_fresh(&document);
+    }
+
+    #[test]
+    fn test_edits_that_change_a_kept_doc_comment() {
+        let mut document = Document::new("int a;\n\n/// A point.\nstruct point { int x; };\n\nint b;\n");
+        // The struct becomes part of the comment, which is now the doc comment of `b`.
+        assert_eq!(edit(&mut document, ".\n", "!"), Ok(2));
+        assert_fresh(&document);
+        let Some(HeaderStatement::Variable(b)) = document.statements().last() else {
+            panic!("Expected a variable");
+        };
+        assert_eq!(b.doc.as_deref(), Some("A point!struct point { int x; };"));
+    }
+
+    #[test]
+    fn test_edits_that_change_lexing_past_the_window() {
+        let mut document = Document::new(SOURCE);
+        edit(&mut document, "// twice", "/* twice").unwrap_err();
+        assert_eq!(document.statements().count(), 0);
+        edit(&mut document, "/* twice", "/* twice */").unwrap();
+        assert_fresh(&document);
+    }
+}
@@ -0,0 +1,2689 @@
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+
+use crate::ast::*;
+use crate::constant::{self, common_type, size_type, width, wrap};
+use crate::layout::{size_literal, BitRange, DataModel, Layout, LayoutEngine};
+use crate::literal::{Encoding, NumberType, NumberValue, StringLiteral};
+use crate::typecheck::{self, number, resolve};
+
+/// Where function and vtable pointers point. Nothing is allocated there, so
+/// reading through one is an error like any other stray pointer.
+const CODE_BASE: u64 = 0x1000;
+const DATA_BASE: u64 = 0x10_0000;
+const MAX_ALLOCATION: u64 = 1 << 28;
+/// How deeply calls may nest. Every call takes several frames of the interpreter's
+/// own stack, so this keeps a runaway recursion within a 2 MiB thread stack.
+const MAX_DEPTH: usize = 64;
+const MAX_STEPS: u64 = 10_000_000;
+/// The widest field `printf` pads to and the most digits it prints.
+const MAX_PRINTF_WIDTH: usize = 4096;
+
+/// The functions of the C library the interpreter provides itself.
+const BUILTINS: [&str; 9] = ["printf", "puts", "putchar", "malloc", "calloc", "free", "strlen", "memcpy", "memset"];
+
+/// A value computed by the interpreter. Integers narrower than `int` are promoted
+/// when they are loaded, so they only appear in memory.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Void,
+    Integer(i128, NumberType),
+    Floating(f64, NumberType),
+    /// An address and the type it points to.
+    Pointer(u64, DeclaredType),
+    /// The bytes of a struct, union, tagged union or class object.
+    Record(Vec<u8>, DeclaredType),
+}
+
+impl Value {
+    /// The value of an integer, or the address held by a pointer.
+    pub fn integer(&self) -> Result<i128, String> {
+        match self {
+            Value::Integer(value, _) => Ok(*value),
+            Value::Pointer(address, _) => Ok(*address as i128),
+            value => Err(format!("Expected an integer but found {:?}", value)),
+        }
+    }
+
+    fn truthy(&self) -> Result<bool, String> {
+        match self {
+            Value::Integer(value, _) => Ok(*value != 0),
+            Value::Floating(value, _) => Ok(*value != 0.0),
+            Value::Pointer(address, _) => Ok(*address != 0),
+            value => Err(format!("Expected a scalar but found {:?}", value)),
+        }
+    }
+
+    fn number_type(&self) -> Option<NumberType> {
+        match self {
+            Value::Integer(_, type_) | Value::Floating(_, type_) => Some(*type_),
+            _ => None,
+        }
+    }
+}
+
+/// A block of simulated memory holding one object, string literal or `malloc` result.
+#[derive(Debug)]
+struct Allocation {
+    bytes: Vec<u8>,
+    heap: bool,
+    read_only: bool,
+    freed: bool,
+}
+
+/// The simulated address space. Addresses are never reused, so a dangling pointer
+/// cannot reach an object allocated after the one it pointed to.
+#[derive(Debug)]
+struct Memory {
+    allocations: BTreeMap<u64, Allocation>,
+    next: u64,
+    limit: u64,
+}
+
+impl Memory {
+    fn new(model: DataModel) -> Self {
+        let limit = match model.pointer_size() {
+            8 => u64::MAX,
+            size => 1 << (size * 8),
+        };
+        Self {allocations: BTreeMap::new(), next: DATA_BASE, limit}
+    }
+
+    fn allocate(&mut self, size: u64, heap: bool) -> Result<u64, String> {
+        if size > MAX_ALLOCATION {
+            return Err(format!("Cannot allocate {} bytes", size));
+        }
+        let address = self.next;
+        // Leave a gap after every allocation so one past the end is never another object.
+        let end = address.checked_add(size.div_ceil(16) * 16 + 16)
+            .filter(|end| *end <= self.limit)
+            .ok_or("Out of memory")?;
+        self.next = end;
+        self.allocations.insert(address, Allocation {bytes: vec![0; size as usize], heap, read_only: false, freed: false});
+        Ok(address)
+    }
+
+    fn find(&self, address: u64, size: u64) -> Result<(u64, &Allocation), String> {
+        if address == 0 {
+            return Err("Null pointer dereference".to_string());
+        }
+        let outside = || format!("Access to {} bytes at {:#x} is outside of any live object", size, address);
+        let (base, allocation) = self.allocations.range(..=address).next_back().ok_or_else(outside)?;
+        if allocation.freed {
+            return Err(format!("Use of {:#x} after it was freed", address));
+        }
+        match address.checked_add(size) {
+            Some(end) if end <= base + allocation.bytes.len() as u64 => Ok((*base, allocation)),
+            _ => Err(outside()),
+        }
+    }
+
+    fn read(&self, address: u64, size: u64) -> Result<&[u8], String> {
+        let (base, allocation) = self.find(address, size)?;
+        let start = (address - base) as usize;
+        Ok(&allocation.bytes[start..start + size as usize])
+    }
+
+    fn write(&mut self, address: u64, bytes: &[u8]) -> Result<(), String> {
+        let (base, allocation) = self.find(address, bytes.len() as u64)?;
+        if allocation.read_only {
+            return Err(format!("Write to the string literal at {:#x}", address));
+        }
+        let start = (address - base) as usize;
+        if let Some(allocation) = self.allocations.get_mut(&base) {
+            allocation.bytes[start..start + bytes.len()].copy_from_slice(bytes);
+        }
+        Ok(())
+    }
+
+    fn c_string(&self, address: u64) -> Result<Vec<u8>, String> {
+        let (base, allocation) = self.find(address, 0)?;
+        let bytes = &allocation.bytes[(address - base) as usize..];
+        match bytes.iter().position(|byte| *byte == 0) {
+            Some(end) => Ok(bytes[..end].to_vec()),
+            None => Err(format!("The string at {:#x} has no terminating null", address)),
+        }
+    }
+
+    fn free(&mut self, address: u64) -> Result<(), String> {
+        if address == 0 {
+            return Ok(());
+        }
+        match self.allocations.get_mut(&address) {
+            Some(allocation) if allocation.freed => Err(format!("Double free of {:#x}", address)),
+            Some(allocation) if allocation.heap => {
+                allocation.freed = true;
+                allocation.bytes = Vec::new();
+                Ok(())
+            },
+            _ => Err(format!("free of {:#x}, which was not returned by malloc", address)),
+        }
+    }
+
+    /// Drops an object whose lifetime has ended.
+    fn release(&mut self, address: u64) {
+        self.allocations.remove(&address);
+    }
+}
+
+/// An object in memory: where it is and what type it has.
+#[derive(Debug, Clone)]
+struct Place {
+    address: u64,
+    type_: DeclaredType,
+    bits: Option<BitRange>,
+}
+
+impl Place {
+    fn new(address: u64, type_: DeclaredType) -> Self {
+        Self {address, type_, bits: None}
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Field {
+    name: String,
+    offset: u64,
+    bits: Option<BitRange>,
+    type_: DeclaredType,
+}
+
+/// The members of a struct, union, tagged union or class with their types and
+/// their offsets from the layout engine.
+#[derive(Debug, Default)]
+struct Record {
+    fields: Vec<Field>,
+    union_: bool,
+    vtable: Option<u64>,
+    /// The members of a tagged union in order; a member's tag is its index.
+    variants: Vec<String>,
+}
+
+impl Record {
+    fn field(&self, name: &str) -> Option<&Field> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+}
+
+/// The methods and operator overloads a class defines itself. Abstract ones have no body.
+#[derive(Debug, Default)]
+struct ClassInfo {
+    parent: Option<String>,
+    methods: HashMap<String, Option<Rc<Function>>>,
+    /// Operator overloads by their operator and how many parameters they take.
+    operators: HashMap<(String, usize), Option<Rc<Function>>>,
+}
+
+/// What a function or vtable pointer points to.
+#[derive(Debug, Clone, PartialEq)]
+enum Code {
+    Function(String),
+    Vtable(String),
+}
+
+#[derive(Debug, Default)]
+struct Scope {
+    variables: HashMap<String, Place>,
+    allocations: Vec<u64>,
+}
+
+enum Flow {
+    Normal,
+    Break,
+    Continue,
+    Return(Value),
+    Goto(String),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Kind {
+    Scalar,
+    Array,
+    Record,
+    Tagged,
+    Other,
+}
+
+/// Runs programs by walking their AST. Objects live in simulated memory with the
+/// layouts the layout engine gives them, so null dereferences, out of bounds
+/// accesses, use after free and double frees are errors rather than undefined behaviour.
+pub struct Interpreter {
+    engine: LayoutEngine,
+    memory: Memory,
+    records: HashMap<String, Rc<Record>>,
+    classes: HashMap<String, ClassInfo>,
+    has_operators: bool,
+    functions: HashMap<String, Rc<Function>>,
+    globals: HashMap<String, Place>,
+    /// Static local variables, by the address of their declaration.
+    statics: HashMap<usize, Place>,
+    strings: HashMap<(u32, Vec<u32>), u64>,
+    codes: Vec<Code>,
+    scopes: Vec<Scope>,
+    /// The object and class of the method being run.
+    this: Option<(u64, String)>,
+    depth: usize,
+    steps: u64,
+    output: Vec<u8>,
+}
+
+impl Interpreter {
+    /// Prepares a program to run: lays out its types and initializes its global variables.
+    pub fn from_header(header: &Header, model: DataModel) -> Result<Self, String> {
+        let mut header = header.clone();
+        name_anonymous_types(&mut header);
+        let mut interpreter = Self {
+            engine: LayoutEngine::from_header(&header, model)?,
+            memory: Memory::new(model),
+            records: HashMap::new(),
+            classes: HashMap::new(),
+            has_operators: false,
+            functions: HashMap::new(),
+            globals: HashMap::new(),
+            statics: HashMap::new(),
+            strings: HashMap::new(),
+            codes: Vec::new(),
+            scopes: vec![Scope::default()],
+            this: None,
+            depth: 0,
+            steps: 0,
+            output: Vec::new(),
+        };
+        for statement in &header.statements {
+            match statement {
+                HeaderStatement::Struct(struct_) => interpreter.define_struct(struct_)?,
+                HeaderStatement::Union(union_) => interpreter.define_union(union_)?,
+                HeaderStatement::TaggedUnion(tagged) => interpreter.define_tagged_union(tagged)?,
+                HeaderStatement::Class(class) => interpreter.define_class(class)?,
+                HeaderStatement::Variable(variable_list) => interpreter.define_specifiers(&variable_list.specifiers)?,
+                HeaderStatement::Function(function) => {
+                    interpreter.functions.insert(function.name.clone(), Rc::new(function.clone()));
+                },
+                _ => {},
+            }
+        }
+        for statement in &header.statements {
+            if let HeaderStatement::Variable(variable_list) = statement {
+                interpreter.declare(variable_list, false)?;
+            }
+        }
+        Ok(interpreter)
+    }
+
+    /// Runs `main` and returns its exit status. `main` may take `argc` and `argv`,
+    /// which describe an empty command line.
+    pub fn run_main(&mut self) -> Result<i32, String> {
+        let main = self.functions.get("main").cloned().ok_or("The program has no main function")?;
+        let arguments = match parameters(&main.arguments).len() {
+            0 => Vec::new(),
+            2 => {
+                let argv = self.memory.allocate(self.model().pointer_size(), false)?;
+                let string = pointer_to(base(TypeSpecifier::Char));
+                vec![Value::Integer(0, NumberType::Int), Value::Pointer(argv, string)]
+            },
+            _ => return Err("main must take no parameters or argc and argv".to_string()),
+        };
+        match self.call_function(&main, None, arguments)? {
+            Value::Void => Ok(0),
+            value => Ok(self.convert(value, &base(TypeSpecifier::Int))?.integer()? as i32),
+        }
+    }
+
+    /// Calls a function of the program or of the built-in C library.
+    pub fn call(&mut self, name: &str, arguments: Vec<Value>) -> Result<Value, String> {
+        match self.functions.get(name).cloned() {
+            Some(function) => self.call_function(&function, None, arguments),
+            None => self.call_builtin(name, arguments),
+        }
+    }
+
+    /// Everything the program has written to standard output.
+    pub fn stdout(&self) -> String {
+        String::from_utf8_lossy(&self.output).into_owned()
+    }
+
+    fn model(&self) -> DataModel {
+        self.engine.model()
+    }
+
+    fn step(&mut self) -> Result<(), String> {
+        self.steps += 1;
+        match self.steps > MAX_STEPS {
+            true => Err(format!("The program did not finish within {} steps", MAX_STEPS)),
+            false => Ok(()),
+        }
+    }
+
+    fn define_specifiers(&mut self, specifiers: &DeclarationSpecifiers) -> Result<(), String> {
+        match specifiers.definition.as_deref() {
+            Some(Definition::Struct(struct_)) => self.define_struct(struct_),
+            Some(Definition::Union(union_)) => self.define_union(union_),
+            Some(Definition::TaggedUnion(tagged)) => self.define_tagged_union(tagged),
+            Some(Definition::Enum(_)) | None => Ok(()),
+        }
+    }
+
+    fn define_struct(&mut self, struct_: &Struct) -> Result<(), String> {
+        let Some(name) = &struct_.name else {
+            return Ok(());
+        };
+        let layout = self.engine.struct_layout(name).cloned().ok_or_else(|| format!("struct {} has no layout", name))?;
+        let fields = self.fields(&struct_.members, &layout, Vec::new())?;
+        self.records.insert(format!("struct {}", name), Rc::new(Record {fields, ..Record::default()}));
+        Ok(())
+    }
+
+    fn define_union(&mut self, union_: &Union) -> Result<(), String> {
+        let Some(name) = &union_.name else {
+            return Ok(());
+        };
+        let layout = self.engine.union_layout(name).cloned().ok_or_else(|| format!("union {} has no layout", name))?;
+        let fields = self.fields(&union_.members, &layout, Vec::new())?;
+        self.records.insert(format!("union {}", name), Rc::new(Record {fields, union_: true, ..Record::default()}));
+        Ok(())
+    }
+
+    /// Defines a tagged union. Each payload is registered as a struct named after
+    /// the tagged union and its member, such as `shape.circle`.
+    fn define_tagged_union(&mut self, tagged: &TaggedUnion) -> Result<(), String> {
+        let layout = self.engine.tagged_union_layout(&tagged.name).cloned()
+            .ok_or_else(|| format!("tagged {} has no layout", tagged.name))?;
+        let offset = |name: &str| layout.field(name).map(|field| field.offset)
+            .ok_or_else(|| format!("tagged {} has no field {}", tagged.name, name));
+        let mut fields = vec![Field {name: "tag".to_string(), offset: offset("tag")?, bits: None, type_: base(TypeSpecifier::Int)}];
+        for member in &tagged.members {
+            if let Some(members) = &member.value {
+                let payload = Struct {
+                    name: Some(format!("{}.{}", tagged.name, member.name)),
+                    attributes: Vec::new(),
+                    members: members.clone(),
+                    doc: None,
+                };
+                self.engine.layout_struct(&payload)?;
+                self.define_struct(&payload)?;
+                let type_ = base(TypeSpecifier::Struct(payload.name));
+                fields.push(Field {name: member.name.clone(), offset: offset(&member.name)?, bits: None, type_});
+            }
+        }
+        let variants = tagged.members.iter().map(|member| member.name.clone()).collect();
+        self.records.insert(format!("tagged {}", tagged.name), Rc::new(Record {fields, variants, ..Record::default()}));
+        Ok(())
+    }
+
+    fn define_class(&mut self, class: &Class) -> Result<(), String> {
+        let layout = self.engine.class_layout(&class.name).cloned()
+            .ok_or_else(|| format!("class {} has no layout", class.name))?;
+        let inherited = match &class.parent {
+            Some(parent) => self.record(&base(TypeSpecifier::Named(parent.clone())))?.fields.iter()
+                .map(|field| (field.name.clone(), field.type_.clone()))
+                .collect(),
+            None => Vec::new(),
+        };
+        let members = class.members.iter()
+            .filter_map(|member| match member {
+                ClassMember::Field(member) => Some(member.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let fields = self.fields(&members, &layout, inherited)?;
+
+        let mut info = ClassInfo {parent: class.parent.clone(), ..ClassInfo::default()};
+        for member in &class.members {
+            match member {
+                ClassMember::Method(Method::Normal(function)) => {
+                    info.methods.insert(function.name.clone(), Some(Rc::new(function.clone())));
+                },
+                ClassMember::Method(Method::Abstract(prototype)) => {
+                    info.methods.entry(prototype.name.clone()).or_insert(None);
+                },
+                ClassMember::OperatorOverload(OperatorOverload::Normal {attributes, return_type, op, arguments, body, ..}) => {
+                    let function = Function {
+                        specifiers: DeclarationSpecifiers {
+                            storage: None,
+                            thread_local: false,
+                            constexpr: false,
+                            inline: false,
+                            noreturn: false,
+                            alignas: Vec::new(),
+                            attributes: attributes.clone(),
+                            type_: return_type.base().clone(),
+                            definition: None,
+                        },
+                        generic: false,
+                        return_type: return_type.clone(),
+                        name: format!("operator{}", op),
+                        arguments: arguments.clone(),
+                        body: body.clone(),
+                        doc: None,
+                    };
+                    info.operators.insert((op.clone(), parameters(arguments).len()), Some(Rc::new(function)));
+                },
+                ClassMember::OperatorOverload(OperatorOverload::Abstract {op, arguments, ..}) => {
+                    info.operators.entry((op.clone(), parameters(arguments).len())).or_insert(None);
+                },
+                ClassMember::Field(_) => {},
+            }
+        }
+        self.has_operators |= !info.operators.is_empty();
+        self.records.insert(format!("class {}", class.name), Rc::new(Record {fields, vtable: layout.vtable, ..Record::default()}));
+        self.classes.insert(class.name.clone(), info);
+        Ok(())
+    }
+
+    /// Pairs the members' types with their offsets in `layout`, after the members
+    /// a class inherits.
+    fn fields(&mut self, members: &[Member], layout: &Layout, mut types: Vec<(String, DeclaredType)>) -> Result<Vec<Field>, String> {
+        self.member_types(members, &mut types)?;
+        Ok(types.into_iter()
+            .filter_map(|(name, type_)| {
+                let field = layout.field(&name)?;
+                Some(Field {offset: field.offset, bits: field.bits, name, type_})
+            })
+            .collect())
+    }
+
+    fn member_types(&mut self, members: &[Member], types: &mut Vec<(String, DeclaredType)>) -> Result<(), String> {
+        for member in members {
+            match member {
+                Member::Variables(variable_list) => {
+                    self.define_specifiers(&variable_list.specifiers)?;
+                    for variable in &variable_list.variables {
+                        types.push((variable.name.clone(), variable.type_.clone()));
+                    }
+                },
+                Member::BitFields(bit_fields) => {
+                    for field in &bit_fields.fields {
+                        if let Some(name) = &field.name {
+                            types.push((name.clone(), DeclaredType::Base(bit_fields.specifiers.type_.clone())));
+                        }
+                    }
+                },
+                Member::Struct(struct_) if struct_.name.is_some() => self.define_struct(struct_)?,
+                Member::Struct(struct_) => self.member_types(&struct_.members, types)?,
+                Member::Union(union_) if union_.name.is_some() => self.define_union(union_)?,
+                Member::Union(union_) => self.member_types(&union_.members, types)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn resolve(&self, type_: &DeclaredType) -> Result<DeclaredType, String> {
+        resolve(type_, &self.engine)
+    }
+
+    fn kind(&self, type_: &DeclaredType) -> Result<Kind, String> {
+        Ok(match self.resolve(type_)? {
+            DeclaredType::Array(..) => Kind::Array,
+            DeclaredType::Pointer(..) => Kind::Scalar,
+            DeclaredType::Function(..) => Kind::Other,
+            DeclaredType::Base(base) => match base.specifier {
+                TypeSpecifier::Void => Kind::Other,
+                TypeSpecifier::Struct(_) | TypeSpecifier::Union(_) | TypeSpecifier::Named(_) => Kind::Record,
+                TypeSpecifier::Tagged(_) => Kind::Tagged,
+                _ => Kind::Scalar,
+            },
+        })
+    }
+
+    fn record(&self, type_: &DeclaredType) -> Result<Rc<Record>, String> {
+        let resolved = self.resolve(type_)?;
+        let key = match &resolved {
+            DeclaredType::Base(base) => match &base.specifier {
+                TypeSpecifier::Struct(Some(name)) => format!("struct {}", name),
+                TypeSpecifier::Union(Some(name)) => format!("union {}", name),
+                TypeSpecifier::Tagged(name) => format!("tagged {}", name),
+                TypeSpecifier::Named(name) => format!("class {}", name),
+                _ => return Err(format!("{:?} is not a struct, union or class", resolved)),
+            },
+            _ => return Err(format!("{:?} is not a struct, union or class", resolved)),
+        };
+        self.records.get(&key).cloned().ok_or_else(|| format!("{} is not defined", key))
+    }
+
+    fn class_name(&self, type_: &DeclaredType) -> Result<String, String> {
+        match self.resolve(type_)? {
+            DeclaredType::Base(Type {specifier: TypeSpecifier::Named(name), ..}) if self.classes.contains_key(&name) => Ok(name),
+            type_ => Err(format!("{:?} is not a class", type_)),
+        }
+    }
+
+    fn array_length(&self, size: &VariableArray) -> Result<u64, String> {
+        match size {
+            VariableArray::Size(size) => {
+                let length = constant::integer(size, &self.engine)?;
+                u64::try_from(length).map_err(|_| format!("Array size {} is negative", length))
+            },
+            VariableArray::NoSize => Ok(0),
+        }
+    }
+
+    /// Fixes the type of an object being declared: array sizes are evaluated, so
+    /// variable length arrays work, an unsized array takes its size from its
+    /// initializer and `typeof` is replaced by the type of its operand.
+    fn concrete_type(&mut self, type_: &DeclaredType, value: Option<&VariableValue>) -> Result<DeclaredType, String> {
+        match type_ {
+            DeclaredType::Base(Type {specifier: TypeSpecifier::Typeof(operand) | TypeSpecifier::TypeofUnqual(operand), ..}) => {
+                match operand.as_ref() {
+                    TypeOrExpression::Type(type_) => self.concrete_type(type_, None),
+                    TypeOrExpression::Expression(expression) => self.static_type(expression),
+                }
+            },
+            DeclaredType::Base(_) | DeclaredType::Function(..) => Ok(type_.clone()),
+            DeclaredType::Pointer(inner, qualifiers) => Ok(DeclaredType::Pointer(Box::new(self.concrete_type(inner, None)?), *qualifiers)),
+            DeclaredType::Array(element, size) => {
+                let element = Box::new(self.concrete_type(element, None)?);
+                let length = match (size, value) {
+                    (VariableArray::Size(size), _) => {
+                        let length = self.evaluate(size)?.integer()?;
+                        u64::try_from(length).map_err(|_| format!("Array size {} is negative", length))?
+                    },
+                    (VariableArray::NoSize, Some(value)) => self.initializer_length(&element, value)?,
+                    (VariableArray::NoSize, None) => return Ok(DeclaredType::Array(element, VariableArray::NoSize)),
+                };
+                Ok(DeclaredType::Array(element, VariableArray::Size(size_literal(length))))
+            },
+        }
+    }
+
+    fn initializer_length(&self, element: &DeclaredType, value: &VariableValue) -> Result<u64, String> {
+        match value {
+            VariableValue::String(string) | VariableValue::Expression(Expression::Literal(Literal::String(string))) => {
+                Ok(string.value.len() as u64 + 1)
+            },
+            VariableValue::Expression(Expression::InitializerList(items)) => {
+                let items = flatten(items);
+                let braced = items.iter().all(|item| matches!(item, Expression::InitializerList(_)));
+                match self.kind(element)? {
+                    Kind::Array | Kind::Record if !braced => Ok((items.len() as u64).div_ceil(self.leaves(element)?.max(1))),
+                    _ => Ok(items.len() as u64),
+                }
+            },
+            _ => Err("An array without a size needs an initializer list".to_string()),
+        }
+    }
+
+    /// How many scalars initialize an object when its braces are left out.
+    fn leaves(&self, type_: &DeclaredType) -> Result<u64, String> {
+        match self.resolve(type_)? {
+            DeclaredType::Array(element, size) => Ok(self.array_length(&size)? * self.leaves(&element)?),
+            resolved if self.kind(&resolved)? == Kind::Record => {
+                let record = self.record(&resolved)?;
+                let mut leaves = 0;
+                for field in &record.fields {
+                    leaves += self.leaves(&field.type_)?;
+                    if record.union_ {
+                        break;
+                    }
+                }
+                Ok(leaves)
+            },
+            _ => Ok(1),
+        }
+    }
+
+    fn allocate(&mut self, type_: &DeclaredType, automatic: bool) -> Result<Place, String> {
+        let size = self.engine.size_of(type_)?;
+        let address = self.memory.allocate(size, false)?;
+        if automatic {
+            if let Some(scope) = self.scopes.last_mut() {
+                scope.allocations.push(address);
+            }
+        }
+        self.construct(address, type_)?;
+        Ok(Place::new(address, type_.clone()))
+    }
+
+    /// Points the vtable pointers of the class objects in a new object at their classes.
+    fn construct(&mut self, address: u64, type_: &DeclaredType) -> Result<(), String> {
+        if self.classes.is_empty() {
+            return Ok(());
+        }
+        match self.resolve(type_)? {
+            DeclaredType::Array(element, size) => {
+                if matches!(self.kind(&element)?, Kind::Array | Kind::Record) {
+                    let step = self.engine.size_of(&element)?;
+                    for index in 0..self.array_length(&size)? {
+                        self.construct(address + index * step, &element)?;
+                    }
+                }
+            },
+            resolved if self.kind(&resolved)? == Kind::Record => {
+                let record = self.record(&resolved)?;
+                if let (Some(offset), Ok(class)) = (record.vtable, self.class_name(&resolved)) {
+                    let vtable = self.code(Code::Vtable(class));
+                    self.store(&Place::new(address + offset, void_pointer()), Value::Pointer(vtable, base(TypeSpecifier::Void)))?;
+                }
+                for field in &record.fields {
+                    if field.bits.is_none() {
+                        self.construct(address + field.offset, &field.type_)?;
+                    }
+                }
+            },
+            _ => {},
+        }
+        Ok(())
+    }
+
+    fn pop_scope(&mut self) {
+        if let Some(scope) = self.scopes.pop() {
+            for address in scope.allocations {
+                self.memory.release(address);
+            }
+        }
+    }
+
+    fn code(&mut self, code: Code) -> u64 {
+        let index = match self.codes.iter().position(|existing| *existing == code) {
+            Some(index) => index,
+            None => {
+                self.codes.push(code);
+                self.codes.len() - 1
+            },
+        };
+        CODE_BASE + index as u64 * 16
+    }
+
+    fn code_at(&self, address: u64) -> Option<&Code> {
+        let offset = address.checked_sub(CODE_BASE)?;
+        match offset % 16 {
+            0 => self.codes.get((offset / 16) as usize),
+            _ => None,
+        }
+    }
+
+    fn function_type(&self, name: &str) -> Option<DeclaredType> {
+        match self.functions.get(name) {
+            Some(function) => Some(DeclaredType::Function(Box::new(function.return_type.clone()), function.arguments.clone())),
+            None if BUILTINS.contains(&name) => {
+                let return_type = builtin_return_type(name, self.model());
+                Some(DeclaredType::Function(Box::new(return_type), vec![FunctionArgument::Ellipsis]))
+            },
+            None => None,
+        }
+    }
+
+    /// Finds a variable in scope: locals, then the fields of `this`, then globals.
+    fn variable(&self, name: &str) -> Result<Option<Place>, String> {
+        if let Some(place) = self.scopes.iter().rev().find_map(|scope| scope.variables.get(name)) {
+            return Ok(Some(place.clone()));
+        }
+        if let Some((address, class)) = &self.this {
+            let record = self.record(&base(TypeSpecifier::Named(class.clone())))?;
+            if let Some(field) = record.field(name) {
+                return Ok(Some(Place {address: address + field.offset, type_: field.type_.clone(), bits: field.bits}));
+            }
+        }
+        Ok(self.globals.get(name).cloned())
+    }
+
+    fn bind(&mut self, name: &str, place: Place, automatic: bool) {
+        match self.scopes.last_mut() {
+            Some(scope) if automatic => {
+                scope.variables.insert(name.to_string(), place);
+            },
+            _ => {
+                self.globals.insert(name.to_string(), place);
+            },
+        }
+    }
+
+    fn load(&mut self, place: &Place) -> Result<Value, String> {
+        let model = self.model();
+        let resolved = self.resolve(&place.type_)?;
+        let base = match &resolved {
+            DeclaredType::Array(element, _) => return Ok(Value::Pointer(place.address, (**element).clone())),
+            DeclaredType::Function(..) => return Ok(Value::Pointer(place.address, resolved)),
+            DeclaredType::Pointer(pointee, _) => {
+                let address = from_le(self.memory.read(place.address, model.pointer_size())?);
+                return Ok(Value::Pointer(address as u64, (**pointee).clone()));
+            },
+            DeclaredType::Base(base) => base,
+        };
+        if let Some(storage) = integer_storage(&base.specifier) {
+            if let Some(bits) = place.bits {
+                let raw = from_le(self.memory.read(place.address, (bits.start + bits.width).div_ceil(8))?);
+                let raw = (raw >> bits.start) & ((1 << bits.width) - 1);
+                let field = match storage.is_unsigned() {
+                    true => NumberType::UnsignedBitInt(bits.width as u32),
+                    false => NumberType::BitInt(bits.width as u32),
+                };
+                let type_ = match bits.width < 32 {
+                    true => NumberType::Int,
+                    false => promote(storage),
+                };
+                return Ok(Value::Integer(wrap(raw as i128, field, model), type_));
+            }
+            let raw = from_le(self.memory.read(place.address, self.engine.size_of(&resolved)?)?);
+            return Ok(Value::Integer(wrap(raw as i128, storage, model), promote(storage)));
+        }
+        match &base.specifier {
+            TypeSpecifier::Float => {
+                let bytes = self.memory.read(place.address, 4)?;
+                Ok(Value::Floating(f32::from_bits(from_le(bytes) as u32) as f64, NumberType::Float))
+            },
+            TypeSpecifier::Double | TypeSpecifier::LongDouble => {
+                // A long double is kept as a double in its first eight bytes.
+                let bytes = self.memory.read(place.address, 8)?;
+                let type_ = floating_type(&base.specifier).unwrap_or(NumberType::Double);
+                Ok(Value::Floating(f64::from_bits(from_le(bytes) as u64), type_))
+            },
+            TypeSpecifier::Struct(_) | TypeSpecifier::Union(_) | TypeSpecifier::Tagged(_) | TypeSpecifier::Named(_) => {
+                let bytes = self.memory.read(place.address, self.engine.size_of(&resolved)?)?.to_vec();
+                Ok(Value::Record(bytes, place.type_.clone()))
+            },
+            TypeSpecifier::Void => Err("Cannot read an object of type void".to_string()),
+            specifier => Err(format!("The interpreter does not support {:?}", specifier)),
+        }
+    }
+
+    fn store(&mut self, place: &Place, value: Value) -> Result<(), String> {
+        let model = self.model();
+        let value = self.convert(value, &place.type_)?;
+        let bytes = match value {
+            Value::Integer(value, _) => {
+                if let Some(bits) = place.bits {
+                    let size = (bits.start + bits.width).div_ceil(8);
+                    let mask = ((1u128 << bits.width) - 1) << bits.start;
+                    let raw = from_le(self.memory.read(place.address, size)?);
+                    let raw = (raw & !mask) | (((value as u128) << bits.start) & mask);
+                    return self.memory.write(place.address, &raw.to_le_bytes()[..size as usize]);
+                }
+                let size = self.engine.size_of(&place.type_)? as usize;
+                (value as u128).to_le_bytes()[..size].to_vec()
+            },
+            Value::Floating(value, NumberType::Float) => (value as f32).to_le_bytes().to_vec(),
+            Value::Floating(value, _) => {
+                let mut bytes = value.to_le_bytes().to_vec();
+                bytes.resize(self.engine.size_of(&place.type_)? as usize, 0);
+                bytes
+            },
+            Value::Pointer(address, _) => (address as u128).to_le_bytes()[..model.pointer_size() as usize].to_vec(),
+            Value::Record(bytes, _) => bytes,
+            Value::Void => return Err("Cannot store a void value".to_string()),
+        };
+        self.memory.write(place.address, &bytes)
+    }
+
+    /// Converts a value to a type as assignment does.
+    fn convert(&self, value: Value, type_: &DeclaredType) -> Result<Value, String> {
+        let model = self.model();
+        let resolved = self.resolve(type_)?;
+        let base = match &resolved {
+            DeclaredType::Pointer(pointee, _) => return match value {
+                Value::Pointer(address, _) => Ok(Value::Pointer(address, (**pointee).clone())),
+                Value::Integer(address, _) => Ok(Value::Pointer(wrap(address, size_type(model), model) as u64, (**pointee).clone())),
+                value => Err(format!("Cannot convert {:?} to a pointer", value)),
+            },
+            DeclaredType::Array(..) | DeclaredType::Function(..) => return Err(format!("Cannot assign to an object of type {:?}", resolved)),
+            DeclaredType::Base(base) => base,
+        };
+        if base.specifier == TypeSpecifier::Bool {
+            return Ok(Value::Integer(value.truthy()? as i128, NumberType::Int));
+        }
+        if let Some(storage) = integer_storage(&base.specifier) {
+            let integer = match value {
+                Value::Integer(value, _) => value,
+                Value::Floating(value, _) => value.trunc() as i128,
+                Value::Pointer(address, _) => address as i128,
+                value => return Err(format!("Cannot convert {:?} to {:?}", value, resolved)),
+            };
+            return Ok(Value::Integer(wrap(integer, storage, model), promote(storage)));
+        }
+        if let Some(floating) = floating_type(&base.specifier) {
+            let value = match value {
+                Value::Integer(value, _) => value as f64,
+                Value::Floating(value, _) => value,
+                value => return Err(format!("Cannot convert {:?} to {:?}", value, resolved)),
+            };
+            return Ok(Value::Floating(round(value, floating), floating));
+        }
+        match (&base.specifier, value) {
+            (TypeSpecifier::Void, _) => Ok(Value::Void),
+            (TypeSpecifier::Struct(_) | TypeSpecifier::Union(_) | TypeSpecifier::Tagged(_) | TypeSpecifier::Named(_), Value::Record(bytes, from)) => {
+                match self.resolve(&from)? == resolved {
+                    true => Ok(Value::Record(bytes, type_.clone())),
+                    false => Err(format!("Cannot convert {:?} to {:?}", from, resolved)),
+                }
+            },
+            (_, value) => Err(format!("Cannot convert {:?} to {:?}", value, resolved)),
+        }
+    }
+
+    fn pointee_size(&self, pointee: &DeclaredType) -> Result<u64, String> {
+        match self.resolve(pointee)? {
+            DeclaredType::Base(Type {specifier: TypeSpecifier::Void, ..}) => Ok(1),
+            pointee => self.engine.size_of(&pointee),
+        }
+    }
+
+    fn binary(&self, operator: &BinaryOperator, left: Value, right: Value) -> Result<Value, String> {
+        let model = self.model();
+        let boolean = |value: bool| Ok(Value::Integer(value as i128, NumberType::Int));
+        match (left, right) {
+            (Value::Pointer(address, pointee), Value::Integer(offset, _)) |
+            (Value::Integer(offset, _), Value::Pointer(address, pointee))
+                if *operator == BinaryOperator::Add || (*operator == BinaryOperator::Subtract && offset != 0) => {
+                let step = self.pointee_size(&pointee)? as i128 * offset;
+                let address = match operator {
+                    BinaryOperator::Add => address as i128 + step,
+                    _ => address as i128 - step,
+                };
+                Ok(Value::Pointer(wrap(address, size_type(model), model) as u64, pointee))
+            },
+            (Value::Pointer(left, pointee), Value::Pointer(right, _)) if *operator == BinaryOperator::Subtract => {
+                let size = self.pointee_size(&pointee)?.max(1) as i128;
+                Ok(Value::Integer((left as i128 - right as i128) / size, ptrdiff_type(model)))
+            },
+            (left @ Value::Pointer(..), right @ (Value::Pointer(..) | Value::Integer(..))) |
+            (left @ Value::Integer(..), right @ Value::Pointer(..)) => {
+                let (left, right) = (left.integer()? as u64, right.integer()? as u64);
+                match operator {
+                    BinaryOperator::Subtract => Ok(Value::Pointer(left, base(TypeSpecifier::Char))),
+                    BinaryOperator::Equal => boolean(left == right),
+                    BinaryOperator::NotEqual => boolean(left != right),
+                    BinaryOperator::LessThan => boolean(left < right),
+                    BinaryOperator::GreaterThan => boolean(left > right),
+                    BinaryOperator::LessThanOrEqual => boolean(left <= right),
+                    BinaryOperator::GreaterThanOrEqual => boolean(left >= right),
+                    BinaryOperator::LogicalAnd => boolean(left != 0 && right != 0),
+                    BinaryOperator::LogicalOr => boolean(left != 0 || right != 0),
+                    operator => Err(format!("Invalid pointer operands to {:?}", operator)),
+                }
+            },
+            (Value::Integer(left, left_type), Value::Integer(right, right_type)) => {
+                if matches!(operator, BinaryOperator::LeftShift | BinaryOperator::RightShift) {
+                    if right < 0 || right >= width(left_type, model) as i128 {
+                        return Err(format!("Shift by {} is out of range for {:?}", right, left_type));
+                    }
+                    let value = match operator {
+                        BinaryOperator::LeftShift => left << right,
+                        _ => left >> right,
+                    };
+                    return Ok(Value::Integer(wrap(value, left_type, model), left_type));
+                }
+                let type_ = common_type(left_type, right_type, model);
+                let (left, right) = (wrap(left, type_, model), wrap(right, type_, model));
+                let value = match operator {
+                    BinaryOperator::Add => left + right,
+                    BinaryOperator::Subtract => left - right,
+                    BinaryOperator::Multiply => left.wrapping_mul(right),
+                    BinaryOperator::Divide | BinaryOperator::Modulo if right == 0 => return Err("Division by zero".to_string()),
+                    BinaryOperator::Divide => left / right,
+                    BinaryOperator::Modulo => left % right,
+                    BinaryOperator::BitwiseAnd => left & right,
+                    BinaryOperator::BitwiseOr => left | right,
+                    BinaryOperator::BitwiseXor => left ^ right,
+                    BinaryOperator::Equal => return boolean(left == right),
+                    BinaryOperator::NotEqual => return boolean(left != right),
+                    BinaryOperator::LessThan => return boolean(left < right),
+                    BinaryOperator::GreaterThan => return boolean(left > right),
+                    BinaryOperator::LessThanOrEqual => return boolean(left <= right),
+                    BinaryOperator::GreaterThanOrEqual => return boolean(left >= right),
+                    BinaryOperator::LogicalAnd => return boolean(left != 0 && right != 0),
+                    BinaryOperator::LogicalOr => return boolean(left != 0 || right != 0),
+                    operator => return Err(format!("Invalid integer operands to {:?}", operator)),
+                };
+                Ok(Value::Integer(wrap(value, type_, model), type_))
+            },
+            (left @ (Value::Integer(..) | Value::Floating(..)), right @ (Value::Integer(..) | Value::Floating(..))) => {
+                let (Some(left_type), Some(right_type)) = (left.number_type(), right.number_type()) else {
+                    return Err("Expected arithmetic operands".to_string());
+                };
+                let type_ = common_type(left_type, right_type, model);
+                let as_float = |value: Value| match value {
+                    Value::Floating(value, _) => value,
+                    value => value.integer().unwrap_or(0) as f64,
+                };
+                let (left, right) = (as_float(left), as_float(right));
+                let value = match operator {
+                    BinaryOperator::Add => left + right,
+                    BinaryOperator::Subtract => left - right,
+                    BinaryOperator::Multiply => left * right,
+                    BinaryOperator::Divide => left / right,
+                    BinaryOperator::Equal => return boolean(left == right),
+                    BinaryOperator::NotEqual => return boolean(left != right),
+                    BinaryOperator::LessThan => return boolean(left < right),
+                    BinaryOperator::GreaterThan => return boolean(left > right),
+                    BinaryOperator::LessThanOrEqual => return boolean(left <= right),
+                    BinaryOperator::GreaterThanOrEqual => return boolean(left >= right),
+                    BinaryOperator::LogicalAnd => return boolean(left != 0.0 && right != 0.0),
+                    BinaryOperator::LogicalOr => return boolean(left != 0.0 || right != 0.0),
+                    operator => return Err(format!("Invalid floating operands to {:?}", operator)),
+                };
+                Ok(Value::Floating(round(value, type_), type_))
+            },
+            (left, right) => Err(format!("Invalid operands to {:?}: {:?} and {:?}", operator, left, right)),
+        }
+    }
+
+    fn unary(&self, operator: &UnaryOperator, value: Value) -> Result<Value, String> {
+        let model = self.model();
+        match (operator, value) {
+            (UnaryOperator::Not | UnaryOperator::LogicalNot, value) => Ok(Value::Integer(!value.truthy()? as i128, NumberType::Int)),
+            (UnaryOperator::Plus, value @ (Value::Integer(..) | Value::Floating(..))) => Ok(value),
+            (UnaryOperator::Minus, Value::Integer(value, type_)) => Ok(Value::Integer(wrap(-value, type_, model), type_)),
+            (UnaryOperator::Minus, Value::Floating(value, type_)) => Ok(Value::Floating(-value, type_)),
+            (UnaryOperator::BitwiseNot, Value::Integer(value, type_)) => Ok(Value::Integer(wrap(!value, type_, model), type_)),
+            (operator, value) => Err(format!("Invalid operand to {:?}: {:?}", operator, value)),
+        }
+    }
+
+    fn dereference(&self, value: Value) -> Result<Place, String> {
+        match value {
+            Value::Pointer(address, pointee) => Ok(Place::new(address, pointee)),
+            value => Err(format!("Cannot dereference {:?}", value)),
+        }
+    }
+
+    /// Finds the member of an object. Reading a tagged union's payload checks
+    /// that the member is the one the union holds.
+    fn member(&mut self, object: Place, name: &str) -> Result<Place, String> {
+        let record = self.record(&object.type_)?;
+        let field = record.field(name).ok_or_else(|| format!("{:?} has no member named {}", object.type_, name))?;
+        if let Some(variant) = record.variants.iter().position(|variant| variant == name) {
+            let tag = record.field("tag").ok_or("A tagged union has no tag")?;
+            let tag = self.load(&Place::new(object.address + tag.offset, tag.type_.clone()))?.integer()?;
+            if tag != variant as i128 {
+                let held = usize::try_from(tag).ok().and_then(|tag| record.variants.get(tag)).map_or("an unknown member", |held| held.as_str());
+                return Err(format!("Read of {} from a tagged union that holds {}", name, held));
+            }
+        }
+        Ok(Place {address: object.address + field.offset, type_: field.type_.clone(), bits: field.bits})
+    }
+
+    fn place(&mut self, expression: &Expression) -> Result<Place, String> {
+        match expression {
+            Expression::Identifier(name) => self.variable(name)?.ok_or_else(|| format!("Unknown variable {}", name)),
+            Expression::Parentheses(inner) | Expression::Expression(inner) => self.place(inner),
+            Expression::Unary(UnaryOperator::Dereference, operand) => {
+                let value = self.evaluate(operand)?;
+                self.dereference(value)
+            },
+            Expression::Binary(BinaryOperator::ArrayAccess | BinaryOperator::Index, array, index) => {
+                let (array, index) = (self.evaluate(array)?, self.evaluate(index)?);
+                let pointer = self.binary(&BinaryOperator::Add, array, index)?;
+                self.dereference(pointer)
+            },
+            Expression::Binary(operator @ (BinaryOperator::MemberAccess | BinaryOperator::PointerMemberAccess), object, member)
+                if matches!(member.as_ref(), Expression::Identifier(_)) => {
+                let Expression::Identifier(name) = member.as_ref() else {
+                    return Err("Expected a member name".to_string());
+                };
+                let object = match operator {
+                    BinaryOperator::MemberAccess => self.place(object)?,
+                    _ => {
+                        let value = self.evaluate(object)?;
+                        self.dereference(value)?
+                    },
+                };
+                self.member(object, name)
+            },
+            Expression::Literal(Literal::String(string)) => {
+                let (element, _) = string_element(string);
+                let Value::Pointer(address, _) = self.string(string)? else {
+                    return Err("A string literal is not in memory".to_string());
+                };
+                Ok(Place::new(address, DeclaredType::Array(Box::new(element), VariableArray::Size(size_literal(string.value.len() as u64 + 1)))))
+            },
+            Expression::Generic(control, associations) => {
+                let selected = self.select(control, associations)?;
+                self.place(selected)
+            },
+            expression => match self.evaluate(expression)? {
+                // A struct returned by a function lives in a temporary until its scope ends.
+                Value::Record(bytes, type_) => {
+                    let place = self.allocate(&type_, true)?;
+                    self.store(&place, Value::Record(bytes, type_))?;
+                    Ok(place)
+                },
+                _ => Err("Expected an object".to_string()),
+            },
+        }
+    }
+
+    fn string(&mut self, string: &StringLiteral) -> Result<Value, String> {
+        let (element, unit) = string_element(string);
+        if let Some(address) = self.strings.get(&(unit, string.value.clone())) {
+            return Ok(Value::Pointer(*address, element));
+        }
+        let bytes = string.value.iter().chain([0].iter())
+            .flat_map(|value| value.to_le_bytes()[..unit as usize].to_vec())
+            .collect::<Vec<_>>();
+        let address = self.memory.allocate(bytes.len() as u64, false)?;
+        self.memory.write(address, &bytes)?;
+        if let Some(allocation) = self.memory.allocations.get_mut(&address) {
+            allocation.read_only = true;
+        }
+        self.strings.insert((unit, string.value.clone()), address);
+        Ok(Value::Pointer(address, element))
+    }
+
+    fn c_string(&self, value: &Value) -> Result<Vec<u8>, String> {
+        self.memory.c_string(value.integer()? as u64)
+    }
+
+    fn evaluate(&mut self, expression: &Expression) -> Result<Value, String> {
+        match expression {
+            Expression::Blank => Ok(Value::Void),
+            Expression::Identifier(name) => self.identifier(name),
+            Expression::Literal(Literal::Number(number)) => Ok(match number.value {
+                NumberValue::Integer(value) => Value::Integer(value as i128, number.c_type),
+                NumberValue::Floating(value) => Value::Floating(value, number.c_type),
+            }),
+            Expression::Literal(Literal::Char(character)) => {
+                let type_ = match character.encoding {
+                    Encoding::Utf32 => NumberType::UnsignedInt,
+                    _ => NumberType::Int,
+                };
+                Ok(Value::Integer(character.value as i128, type_))
+            },
+            Expression::Literal(Literal::String(string)) => self.string(string),
+            Expression::Literal(Literal::Bool(value)) => Ok(Value::Integer(*value as i128, NumberType::Int)),
+            Expression::Literal(Literal::Nullptr) => Ok(Value::Pointer(0, base(TypeSpecifier::Void))),
+            Expression::Sizeof(operand) => {
+                let type_ = match operand {
+                    TypeOrExpression::Type(type_) => self.concrete_type(type_, None)?,
+                    TypeOrExpression::Expression(expression) => match self.type_name(expression)? {
+                        Some(type_) => type_,
+                        None => self.static_type(expression)?,
+                    },
+                };
+                Ok(Value::Integer(self.engine.size_of(&type_)? as i128, size_type(self.model())))
+            },
+            Expression::Alignof(type_) => Ok(Value::Integer(self.engine.align_of(type_)? as i128, size_type(self.model()))),
+            Expression::Offsetof(type_, member) => {
+                Ok(Value::Integer(self.engine.offset_of(type_, member)? as i128, size_type(self.model())))
+            },
+            Expression::Unary(operator, operand) => self.evaluate_unary(operator, operand),
+            Expression::Binary(operator, left, right) => self.evaluate_binary(operator, left, right),
+            Expression::Ternary(condition, then, otherwise) => {
+                let (taken, other) = match self.condition(condition)? {
+                    true => (then, otherwise),
+                    false => (otherwise, then),
+                };
+                let value = self.evaluate(taken)?;
+                // Arithmetic operands are converted to their common type, whichever is taken.
+                let Some(type_) = value.number_type() else {
+                    return Ok(value);
+                };
+                let other = self.static_type(other).ok().and_then(|other| self.arithmetic_type(&other));
+                match other {
+                    Some(other) => self.convert(value, &number(common_type(type_, other, self.model()))?),
+                    None => Ok(value),
+                }
+            },
+            Expression::CallFunction(name, arguments) => self.call_named(name, arguments),
+            Expression::Call(callee, arguments) => {
+                let callee = self.evaluate(callee)?;
+                self.call_value(callee, arguments)
+            },
+            Expression::Generic(control, associations) => {
+                let selected = self.select(control, associations)?;
+                self.evaluate(selected)
+            },
+            Expression::StatementList(statement_list) => {
+                self.scopes.push(Scope::default());
+                let value = self.statement_expression(statement_list);
+                self.pop_scope();
+                value
+            },
+            Expression::Expression(inner) | Expression::Parentheses(inner) => self.evaluate(inner),
+            Expression::InitializerList(_) => Err("An initializer list can only initialize a declaration".to_string()),
+            Expression::TaggedInitializer(name, _) => Err(format!("{} {{...}} can only be assigned to a tagged union", name)),
+        }
+    }
+
+    /// Runs a GNU statement expression, whose value is that of its last statement.
+    fn statement_expression(&mut self, statement_list: &StatementList) -> Result<Value, String> {
+        let (last, rest) = match statement_list.statements.split_last() {
+            Some((Statement::Expression(last), rest)) => (Some(last), rest),
+            _ => (None, statement_list.statements.as_slice()),
+        };
+        match self.execute_statements(rest)? {
+            Flow::Normal => {},
+            _ => return Err("Cannot jump out of a statement expression".to_string()),
+        }
+        match last {
+            Some(last) => self.evaluate(last),
+            None => Ok(Value::Void),
+        }
+    }
+
+    fn identifier(&mut self, name: &str) -> Result<Value, String> {
+        if let (Some((address, class)), "this") = (&self.this, name) {
+            return Ok(Value::Pointer(*address, base(TypeSpecifier::Named(class.clone()))));
+        }
+        if let Some(place) = self.variable(name)? {
+            return self.load(&place);
+        }
+        if let Some(value) = self.engine.enumerator(name) {
+            return Ok(Value::Integer(value, NumberType::Int));
+        }
+        if let Some(tag) = self.tag(name)? {
+            return Ok(Value::Integer(tag, NumberType::Int));
+        }
+        if let Some(type_) = self.function_type(name) {
+            return Ok(Value::Pointer(self.code(Code::Function(name.to_string())), type_));
+        }
+        Err(format!("Unknown identifier {}", name))
+    }
+
+    /// The tag of a tagged union member named on its own, as in `if (s.tag == circle)`.
+    fn tag(&self, name: &str) -> Result<Option<i128>, String> {
+        let mut found = None;
+        for record in self.records.values() {
+            if let Some(index) = record.variants.iter().position(|variant| variant == name) {
+                match found {
+                    Some(other) if other != index => return Err(format!("{} is a member of several tagged unions", name)),
+                    _ => found = Some(index),
+                }
+            }
+        }
+        Ok(found.map(|index| index as i128))
+    }
+
+    fn condition(&mut self, expression: &Expression) -> Result<bool, String> {
+        self.evaluate(expression)?.truthy()
+    }
+
+    fn increment(&mut self, operand: &Expression, delta: i128, prefix: bool) -> Result<Value, String> {
+        let place = self.place(operand)?;
+        let old = self.load(&place)?;
+        let new = self.binary(&BinaryOperator::Add, old.clone(), Value::Integer(delta, NumberType::Int))?;
+        self.store(&place, new)?;
+        match prefix {
+            true => self.load(&place),
+            false => Ok(old),
+        }
+    }
+
+    fn evaluate_unary(&mut self, operator: &UnaryOperator, operand: &Expression) -> Result<Value, String> {
+        if let Some(symbol) = unary_symbol(operator) {
+            if let Some((object, owner, function)) = self.overload(operand, symbol, 0)? {
+                return self.call_function(&function, Some((object.address, owner)), Vec::new());
+            }
+        }
+        match operator {
+            UnaryOperator::AddressOf => {
+                if let Expression::Identifier(name) = operand {
+                    if let (None, Some(type_)) = (self.variable(name)?, self.function_type(name)) {
+                        return Ok(Value::Pointer(self.code(Code::Function(name.clone())), type_));
+                    }
+                }
+                let place = self.place(operand)?;
+                match place.bits {
+                    Some(_) => Err("Cannot take the address of a bit-field".to_string()),
+                    None => Ok(Value::Pointer(place.address, place.type_)),
+                }
+            },
+            UnaryOperator::Dereference => {
+                let value = self.evaluate(operand)?;
+                let place = self.dereference(value)?;
+                self.load(&place)
+            },
+            UnaryOperator::PreIncrement => self.increment(operand, 1, true),
+            UnaryOperator::PreDecrement => self.increment(operand, -1, true),
+            UnaryOperator::PostIncrement => self.increment(operand, 1, false),
+            UnaryOperator::PostDecrement => self.increment(operand, -1, false),
+            UnaryOperator::Cast(type_) => {
+                let value = self.evaluate(operand)?;
+                let type_ = self.concrete_type(type_, None)?;
+                self.convert(value, &type_)
+            },
+            UnaryOperator::MemberSet => Err("A designator can only appear in an initializer list".to_string()),
+            operator => {
+                let value = self.evaluate(operand)?;
+                self.unary(operator, value)
+            },
+        }
+    }
+
+    fn evaluate_binary(&mut self, operator: &BinaryOperator, left: &Expression, right: &Expression) -> Result<Value, String> {
+        match operator {
+            BinaryOperator::Assign => {
+                let place = self.place(left)?;
+                match self.kind(&place.type_)? {
+                    Kind::Tagged => self.assign_tagged(&place, right)?,
+                    _ => {
+                        let value = self.evaluate(right)?;
+                        self.store(&place, value)?;
+                    },
+                }
+                self.load(&place)
+            },
+            BinaryOperator::LogicalAnd => {
+                let value = self.condition(left)? && self.condition(right)?;
+                Ok(Value::Integer(value as i128, NumberType::Int))
+            },
+            BinaryOperator::LogicalOr => {
+                let value = self.condition(left)? || self.condition(right)?;
+                Ok(Value::Integer(value as i128, NumberType::Int))
+            },
+            BinaryOperator::Comma => {
+                self.evaluate(left)?;
+                self.evaluate(right)
+            },
+            BinaryOperator::MemberAccess | BinaryOperator::PointerMemberAccess => match right {
+                Expression::CallFunction(name, arguments) => {
+                    let object = match operator {
+                        BinaryOperator::MemberAccess => self.place(left)?,
+                        _ => {
+                            let value = self.evaluate(left)?;
+                            self.dereference(value)?
+                        },
+                    };
+                    self.call_method(object, name, arguments)
+                },
+                _ => {
+                    let place = self.place(&Expression::Binary(operator.clone(), Box::new(left.clone()), Box::new(right.clone())))?;
+                    self.load(&place)
+                },
+            },
+            BinaryOperator::ArrayAccess | BinaryOperator::Index => {
+                let (array, index) = (self.evaluate(left)?, self.evaluate(right)?);
+                let pointer = self.binary(&BinaryOperator::Add, array, index)?;
+                let place = self.dereference(pointer)?;
+                self.load(&place)
+            },
+            operator => {
+                if let Some(symbol) = binary_symbol(operator) {
+                    if let Some((object, owner, function)) = self.overload(left, symbol, 1)? {
+                        let argument = self.evaluate(right)?;
+                        return self.call_function(&function, Some((object.address, owner)), vec![argument]);
+                    }
+                }
+                if let Some(arithmetic) = compound_operator(operator) {
+                    let place = self.place(left)?;
+                    let old = self.load(&place)?;
+                    let right = self.evaluate(right)?;
+                    let new = self.binary(&arithmetic, old, right)?;
+                    self.store(&place, new)?;
+                    return self.load(&place);
+                }
+                let left = self.evaluate(left)?;
+                let right = self.evaluate(right)?;
+                self.binary(operator, left, right)
+            },
+        }
+    }
+
+    /// Finds the operator overload an operator applies when its left operand is a class object.
+    fn overload(&mut self, operand: &Expression, symbol: &str, arity: usize) -> Result<Option<(Place, String, Rc<Function>)>, String> {
+        if !self.has_operators {
+            return Ok(None);
+        }
+        let Ok(class) = self.static_type(operand).and_then(|type_| self.class_name(&type_)) else {
+            return Ok(None);
+        };
+        let key = (symbol.to_string(), arity);
+        let mut current = Some(class);
+        while let Some(class) = current {
+            let Some(info) = self.classes.get(&class) else {
+                break;
+            };
+            if let Some(function) = info.operators.get(&key) {
+                let function = function.clone().ok_or_else(|| format!("{}::operator{} has no body", class, symbol))?;
+                let object = self.place(operand)?;
+                return Ok(Some((object, class, function)));
+            }
+            current = info.parent.clone();
+        }
+        Ok(None)
+    }
+
+    /// Finds the class that defines a method, starting from `class` and going up its parents.
+    fn find_method(&self, class: &str, name: &str) -> Option<(String, Option<Rc<Function>>)> {
+        let mut current = Some(class.to_string());
+        while let Some(class) = current {
+            let info = self.classes.get(&class)?;
+            if let Some(method) = info.methods.get(name) {
+                return Some((class, method.clone()));
+            }
+            current = info.parent.clone();
+        }
+        None
+    }
+
+    /// The class an object was created as, read from its vtable pointer.
+    fn dynamic_class(&self, object: &Place) -> Result<String, String> {
+        let class = self.class_name(&object.type_)?;
+        if let Some(offset) = self.record(&object.type_)?.vtable {
+            let vtable = from_le(self.memory.read(object.address + offset, self.model().pointer_size())?) as u64;
+            if let Some(Code::Vtable(dynamic)) = self.code_at(vtable) {
+                return Ok(dynamic.clone());
+            }
+        }
+        Ok(class)
+    }
+
+    fn arguments(&mut self, arguments: &Option<Box<Expression>>) -> Result<Vec<Value>, String> {
+        let mut values = Vec::new();
+        if let Some(arguments) = arguments {
+            for argument in flatten(arguments) {
+                values.push(self.evaluate(argument)?);
+            }
+        }
+        Ok(values)
+    }
+
+    /// Calls a function by name. Inside a method, a name that is not a variable
+    /// may be another method of the same object.
+    fn call_named(&mut self, name: &str, arguments: &Option<Box<Expression>>) -> Result<Value, String> {
+        if let Some(place) = self.variable(name)? {
+            let callee = self.load(&place)?;
+            return self.call_value(callee, arguments);
+        }
+        if let Some((address, class)) = self.this.clone() {
+            if self.find_method(&class, name).is_some() {
+                return self.call_method(Place::new(address, base(TypeSpecifier::Named(class))), name, arguments);
+            }
+        }
+        let values = self.arguments(arguments)?;
+        self.call(name, values)
+    }
+
+    fn call_value(&mut self, callee: Value, arguments: &Option<Box<Expression>>) -> Result<Value, String> {
+        let Value::Pointer(address, _) = callee else {
+            return Err(format!("Cannot call {:?}", callee));
+        };
+        let Some(Code::Function(name)) = self.code_at(address).cloned() else {
+            return Err(format!("Call through {:#x}, which is not a function", address));
+        };
+        let values = self.arguments(arguments)?;
+        self.call(&name, values)
+    }
+
+    /// Calls a method, dispatching on the class the object was created as. A member
+    /// holding a function pointer is called the same way.
+    fn call_method(&mut self, object: Place, name: &str, arguments: &Option<Box<Expression>>) -> Result<Value, String> {
+        if let Ok(field) = self.member(object.clone(), name) {
+            let callee = self.load(&field)?;
+            return self.call_value(callee, arguments);
+        }
+        let class = self.dynamic_class(&object)?;
+        let (owner, method) = self.find_method(&class, name).ok_or_else(|| format!("{} has no method {}", class, name))?;
+        let method = method.ok_or_else(|| format!("{}::{} has no body", owner, name))?;
+        let values = self.arguments(arguments)?;
+        self.call_function(&method, Some((object.address, owner)), values)
+    }
+
+    fn call_function(&mut self, function: &Rc<Function>, this: Option<(u64, String)>, arguments: Vec<Value>) -> Result<Value, String> {
+        let parameters = parameters(&function.arguments);
+        let variadic = function.arguments.iter().any(|argument| matches!(argument, FunctionArgument::Ellipsis));
+        if arguments.len() < parameters.len() || (!variadic && arguments.len() > parameters.len()) {
+            return Err(format!("{} takes {} arguments but was given {}", function.name, parameters.len(), arguments.len()));
+        }
+        if self.depth >= MAX_DEPTH {
+            return Err(format!("Calls nest more than {} deep in {}", MAX_DEPTH, function.name));
+        }
+        let scopes = std::mem::replace(&mut self.scopes, vec![Scope::default()]);
+        let this = std::mem::replace(&mut self.this, this);
+        self.depth += 1;
+        let result = self.run_function(function, &parameters, arguments);
+        self.depth -= 1;
+        while !self.scopes.is_empty() {
+            self.pop_scope();
+        }
+        self.scopes = scopes;
+        self.this = this;
+        result
+    }
+
+    fn run_function(&mut self, function: &Function, parameters: &[&Parameter], arguments: Vec<Value>) -> Result<Value, String> {
+        for (parameter, argument) in parameters.iter().zip(arguments) {
+            // Array and function parameters are pointers.
+            let type_ = match &parameter.type_ {
+                DeclaredType::Array(element, _) => pointer_to((**element).clone()),
+                function @ DeclaredType::Function(..) => pointer_to(function.clone()),
+                type_ => type_.clone(),
+            };
+            let place = self.allocate(&type_, true)?;
+            self.store(&place, argument)?;
+            if let Some(name) = &parameter.name {
+                self.bind(name, place, true);
+            }
+        }
+        let CodeBlock::Code(body) = &function.body;
+        match self.execute_statements(&body.statements)? {
+            Flow::Return(value) => match function.return_type.base().specifier == TypeSpecifier::Void && !matches!(function.return_type, DeclaredType::Pointer(..)) {
+                true => Ok(Value::Void),
+                false => self.convert(value, &function.return_type),
+            },
+            Flow::Normal => Ok(Value::Void),
+            Flow::Break | Flow::Continue => Err(format!("break or continue outside of a loop in {}", function.name)),
+            Flow::Goto(label) => Err(format!("{} has no label {}", function.name, label)),
+        }
+    }
+
+    fn call_builtin(&mut self, name: &str, arguments: Vec<Value>) -> Result<Value, String> {
+        let model = self.model();
+        let count = |count: usize| match arguments.len() == count {
+            true => Ok(()),
+            false => Err(format!("{} takes {} arguments but was given {}", name, count, arguments.len())),
+        };
+        let size = |value: &Value| {
+            let size = value.integer()?;
+            u64::try_from(size).ok().filter(|size| *size <= MAX_ALLOCATION).ok_or_else(|| format!("{} cannot handle {} bytes", name, size))
+        };
+        match name {
+            "printf" => self.printf(&arguments),
+            "puts" => {
+                count(1)?;
+                let mut text = self.c_string(&arguments[0])?;
+                text.push(b'\n');
+                let written = text.len();
+                self.output.extend(text);
+                Ok(Value::Integer(written as i128, NumberType::Int))
+            },
+            "putchar" => {
+                count(1)?;
+                let character = arguments[0].integer()? as u8;
+                self.output.push(character);
+                Ok(Value::Integer(character as i128, NumberType::Int))
+            },
+            "malloc" => {
+                count(1)?;
+                let address = self.memory.allocate(size(&arguments[0])?, true)?;
+                Ok(Value::Pointer(address, base(TypeSpecifier::Void)))
+            },
+            "calloc" => {
+                count(2)?;
+                let total = size(&arguments[0])?.checked_mul(size(&arguments[1])?).ok_or("calloc size overflows")?;
+                let address = self.memory.allocate(total, true)?;
+                Ok(Value::Pointer(address, base(TypeSpecifier::Void)))
+            },
+            "free" => {
+                count(1)?;
+                self.memory.free(arguments[0].integer()? as u64)?;
+                Ok(Value::Void)
+            },
+            "strlen" => {
+                count(1)?;
+                let length = self.c_string(&arguments[0])?.len();
+                Ok(Value::Integer(length as i128, size_type(model)))
+            },
+            "memcpy" => {
+                count(3)?;
+                let (destination, source) = (arguments[0].integer()? as u64, arguments[1].integer()? as u64);
+                let bytes = self.memory.read(source, size(&arguments[2])?)?.to_vec();
+                self.memory.write(destination, &bytes)?;
+                Ok(Value::Pointer(destination, base(TypeSpecifier::Void)))
+            },
+            "memset" => {
+                count(3)?;
+                let destination = arguments[0].integer()? as u64;
+                let bytes = vec![arguments[1].integer()? as u8; size(&arguments[2])? as usize];
+                self.memory.write(destination, &bytes)?;
+                Ok(Value::Pointer(destination, base(TypeSpecifier::Void)))
+            },
+            _ => Err(format!("Unknown function {}", name)),
+        }
+    }
+
+    fn printf(&mut self, arguments: &[Value]) -> Result<Value, String> {
+        let model = self.model();
+        let format = self.c_string(arguments.first().ok_or("printf needs a format")?)?;
+        let mut arguments = arguments.iter().skip(1);
+        let mut next = || arguments.next().cloned().ok_or_else(|| "printf was given too few arguments for its format".to_string());
+        let mut out = Vec::new();
+        let mut index = 0;
+        let number = |format: &[u8], index: &mut usize| {
+            let mut value = 0usize;
+            while let Some(digit) = format.get(*index).filter(|byte| byte.is_ascii_digit()) {
+                value = value.saturating_mul(10).saturating_add((digit - b'0') as usize);
+                *index += 1;
+            }
+            value
+        };
+        while let Some(&byte) = format.get(index) {
+            index += 1;
+            if byte != b'%' {
+                out.push(byte);
+                continue;
+            }
+            let (mut left, mut plus, mut space, mut alternate, mut zero) = (false, false, false, false, false);
+            while let Some(flag) = format.get(index).filter(|byte| b"-+ #0".contains(byte)) {
+                match flag {
+                    b'-' => left = true,
+                    b'+' => plus = true,
+                    b' ' => space = true,
+                    b'#' => alternate = true,
+                    _ => zero = true,
+                }
+                index += 1;
+            }
+            let mut field_width = match format.get(index) {
+                Some(b'*') => {
+                    index += 1;
+                    let value = next()?.integer()?;
+                    left |= value < 0;
+                    value.unsigned_abs().min(usize::MAX as u128) as usize
+                },
+                _ => number(&format, &mut index),
+            };
+            let precision = match format.get(index) {
+                Some(b'.') => {
+                    index += 1;
+                    match format.get(index) {
+                        Some(b'*') => {
+                            index += 1;
+                            usize::try_from(next()?.integer()?).ok()
+                        },
+                        _ => Some(number(&format, &mut index)),
+                    }
+                },
+                _ => None,
+            };
+            if field_width > MAX_PRINTF_WIDTH || precision.is_some_and(|precision| precision > MAX_PRINTF_WIDTH) {
+                return Err("printf field is too wide".to_string());
+            }
+            let bits = match (format.get(index), format.get(index + 1)) {
+                (Some(b'h'), Some(b'h')) => 8,
+                (Some(b'l'), Some(b'l')) => 64,
+                (Some(b'h'), _) => 16,
+                (Some(b'l'), _) => model.long_size() as u32 * 8,
+                (Some(b'j'), _) => 64,
+                (Some(b'z' | b't'), _) => model.pointer_size() as u32 * 8,
+                _ => 32,
+            };
+            match (format.get(index), format.get(index + 1)) {
+                (Some(b'h'), Some(b'h')) | (Some(b'l'), Some(b'l')) => index += 2,
+                (Some(b'h' | b'l' | b'j' | b'z' | b't' | b'L'), _) => index += 1,
+                _ => {},
+            }
+            let conversion = *format.get(index).ok_or("printf format ends in the middle of a conversion")?;
+            index += 1;
+            let sign = |negative: bool| match (negative, plus, space) {
+                (true, _, _) => "-",
+                (false, true, _) => "+",
+                (false, false, true) => " ",
+                _ => "",
+            };
+            match conversion {
+                b'%' => {
+                    out.push(b'%');
+                    field_width = 0;
+                },
+                b'd' | b'i' => {
+                    let value = wrap(next()?.integer()?, NumberType::BitInt(bits), model);
+                    let digits = integer_digits(value.unsigned_abs(), 10, precision);
+                    pad(&mut out, sign(value < 0).as_bytes(), digits.as_bytes(), field_width, left, zero && precision.is_none());
+                },
+                b'u' | b'o' | b'x' | b'X' => {
+                    let value = wrap(next()?.integer()?, NumberType::UnsignedBitInt(bits), model) as u128;
+                    let radix = match conversion {
+                        b'u' => 10,
+                        b'o' => 8,
+                        _ => 16,
+                    };
+                    let mut digits = integer_digits(value, radix, precision);
+                    if conversion == b'o' && alternate && !digits.starts_with('0') {
+                        digits.insert(0, '0');
+                    }
+                    let prefix = match conversion {
+                        b'x' if alternate && value != 0 => "0x",
+                        b'X' if alternate && value != 0 => "0X",
+                        _ => "",
+                    };
+                    if conversion == b'X' {
+                        digits = digits.to_uppercase();
+                    }
+                    pad(&mut out, prefix.as_bytes(), digits.as_bytes(), field_width, left, zero && precision.is_none());
+                },
+                b'c' => {
+                    let character = next()?.integer()? as u8;
+                    pad(&mut out, b"", &[character], field_width, left, false);
+                },
+                b's' => {
+                    let mut text = match next()? {
+                        Value::Pointer(0, _) => b"(null)".to_vec(),
+                        value => self.c_string(&value)?,
+                    };
+                    if let Some(precision) = precision {
+                        text.truncate(precision);
+                    }
+                    pad(&mut out, b"", &text, field_width, left, false);
+                },
+                b'p' => {
+                    let text = match next()?.integer()? {
+                        0 => "(nil)".to_string(),
+                        address => format!("{:#x}", address),
+                    };
+                    pad(&mut out, b"", text.as_bytes(), field_width, left, false);
+                },
+                b'f' | b'F' | b'e' | b'E' | b'g' | b'G' => {
+                    let value = match next()? {
+                        Value::Floating(value, _) => value,
+                        value => return Err(format!("printf %{} expects a floating argument but was given {:?}", conversion as char, value)),
+                    };
+                    let text = format_float(value.abs(), conversion, precision, alternate);
+                    let negative = value.is_sign_negative() && !value.is_nan();
+                    pad(&mut out, sign(negative).as_bytes(), text.as_bytes(), field_width, left, zero && value.is_finite());
+                },
+                conversion => return Err(format!("printf does not support %{}", conversion as char)),
+            }
+        }
+        let written = out.len();
+        self.output.extend(out);
+        Ok(Value::Integer(written as i128, NumberType::Int))
+    }
+
+    fn execute(&mut self, statement: &Statement) -> Result<Flow, String> {
+        self.step()?;
+        match statement {
+            Statement::Preprocessor(_) | Statement::Label(_) | Statement::Attributes(_) => Ok(Flow::Normal),
+            Statement::VariableList(variable_list) => {
+                self.declare(variable_list, true)?;
+                Ok(Flow::Normal)
+            },
+            Statement::Expression(expression) => {
+                self.evaluate(expression)?;
+                Ok(Flow::Normal)
+            },
+            Statement::Return(expression) => Ok(Flow::Return(self.evaluate(expression)?)),
+            Statement::If(condition, body, else_) => match (self.condition(condition)?, else_) {
+                (true, _) => self.execute_body(body),
+                (false, Some(else_)) => self.execute_body(else_),
+                (false, None) => Ok(Flow::Normal),
+            },
+            Statement::While(condition, body) => {
+                while self.condition(condition)? {
+                    self.step()?;
+                    match self.execute_body(body)? {
+                        Flow::Break => break,
+                        Flow::Normal | Flow::Continue => {},
+                        flow => return Ok(flow),
+                    }
+                }
+                Ok(Flow::Normal)
+            },
+            Statement::DoWhile(condition, body) => {
+                loop {
+                    self.step()?;
+                    match self.execute_body(body)? {
+                        Flow::Break => break,
+                        Flow::Normal | Flow::Continue => {},
+                        flow => return Ok(flow),
+                    }
+                    if !self.condition(condition)? {
+                        break;
+                    }
+                }
+                Ok(Flow::Normal)
+            },
+            Statement::For(initializer, condition, increment, body) => {
+                self.scopes.push(Scope::default());
+                let flow = self.execute_for(initializer.as_deref(), condition.as_ref(), increment.as_ref(), body);
+                self.pop_scope();
+                flow
+            },
+            Statement::Switch(condition, cases) => {
+                self.scopes.push(Scope::default());
+                let flow = self.execute_switch(condition, cases);
+                self.pop_scope();
+                flow
+            },
+            Statement::StaticAssert(assertion) => {
+                self.engine.static_assert(assertion)?;
+                Ok(Flow::Normal)
+            },
+            Statement::Attributed(_, statement) => self.execute(statement),
+            Statement::Break => Ok(Flow::Break),
+            Statement::Continue => Ok(Flow::Continue),
+            Statement::Goto(label) => Ok(Flow::Goto(label.clone())),
+            Statement::Block(block) => {
+                let CodeBlock::Code(statement_list) = block.as_ref();
+                self.execute_block(&statement_list.statements)
+            },
+        }
+    }
+
+    fn execute_block(&mut self, statements: &[Statement]) -> Result<Flow, String> {
+        self.scopes.push(Scope::default());
+        let flow = self.execute_statements(statements);
+        self.pop_scope();
+        flow
+    }
+
+    fn execute_body(&mut self, body: &BlockOrStatement) -> Result<Flow, String> {
+        match body {
+            BlockOrStatement::Block(CodeBlock::Code(statement_list)) => self.execute_block(&statement_list.statements),
+            BlockOrStatement::Statement(statement) => self.execute(statement),
+        }
+    }
+
+    /// Runs a list of statements. A `goto` continues from its label when the label
+    /// is in this list.
+    fn execute_statements(&mut self, statements: &[Statement]) -> Result<Flow, String> {
+        let mut index = 0;
+        while let Some(statement) = statements.get(index) {
+            match self.execute(statement)? {
+                Flow::Normal => index += 1,
+                Flow::Goto(label) => match statements.iter().position(|statement| matches!(statement, Statement::Label(name) if *name == label)) {
+                    Some(target) => index = target,
+                    None => return Ok(Flow::Goto(label)),
+                },
+                flow => return Ok(flow),
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn execute_for(&mut self, initializer: Option<&VariableListOrStatement>, condition: Option<&Expression>,
+                   increment: Option<&Expression>, body: &BlockOrStatement) -> Result<Flow, String> {
+        match initializer {
+            Some(VariableListOrStatement::VariableList(variable_list)) => self.declare(variable_list, true)?,
+            Some(VariableListOrStatement::Statement(statement)) => {
+                self.execute(statement)?;
+            },
+            None => {},
+        }
+        loop {
+            self.step()?;
+            if let Some(condition) = condition {
+                if !self.condition(condition)? {
+                    break;
+                }
+            }
+            match self.execute_body(body)? {
+                Flow::Break => break,
+                Flow::Normal | Flow::Continue => {},
+                flow => return Ok(flow),
+            }
+            if let Some(increment) = increment {
+                self.evaluate(increment)?;
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    /// Runs a switch from the matching case, falling through the cases after it.
+    fn execute_switch(&mut self, condition: &Expression, cases: &[SwitchCase]) -> Result<Flow, String> {
+        let value = self.evaluate(condition)?.integer()?;
+        let mut start = None;
+        for (index, case) in cases.iter().enumerate() {
+            if let Some(label) = &case.expression {
+                if self.evaluate(label)?.integer()? == value {
+                    start = Some(index);
+                    break;
+                }
+            }
+        }
+        let Some(start) = start.or_else(|| cases.iter().position(|case| case.default)) else {
+            return Ok(Flow::Normal);
+        };
+        for case in &cases[start..] {
+            let flow = match case.body.as_ref() {
+                BlockOrStatement::Block(CodeBlock::Code(statement_list)) => self.execute_statements(&statement_list.statements)?,
+                BlockOrStatement::Statement(statement) => self.execute_statements(std::slice::from_ref(statement))?,
+            };
+            match flow {
+                Flow::Normal => {},
+                Flow::Break => return Ok(Flow::Normal),
+                flow => return Ok(flow),
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    /// Declares variables. Automatic variables belong to the innermost scope, while
+    /// global and static ones live until the program ends; a static local is only
+    /// initialized the first time its declaration runs.
+    fn declare(&mut self, variable_list: &VariableList, automatic: bool) -> Result<(), String> {
+        let specifiers = &variable_list.specifiers;
+        let typedef = specifiers.storage == Some(StorageClass::Typedef);
+        if automatic && (typedef || specifiers.definition.is_some()) {
+            self.engine.declare(variable_list)?;
+            self.define_specifiers(specifiers)?;
+        }
+        if typedef {
+            return Ok(());
+        }
+        for variable in &variable_list.variables {
+            if variable.type_.is_function() {
+                continue;
+            }
+            let place = match &specifiers.storage {
+                Some(StorageClass::Extern) if automatic => self.globals.get(&variable.name).cloned()
+                    .ok_or_else(|| format!("extern {} is not defined", variable.name))?,
+                Some(StorageClass::Static) if automatic => {
+                    let key = std::ptr::from_ref(variable) as usize;
+                    match self.statics.get(&key) {
+                        Some(place) => place.clone(),
+                        None => {
+                            let place = self.define_object(variable, false)?;
+                            self.statics.insert(key, place.clone());
+                            place
+                        },
+                    }
+                },
+                _ if automatic => self.define_object(variable, true)?,
+                // A global declared `extern` earlier is the same object as its definition.
+                _ => match self.globals.get(&variable.name).cloned() {
+                    Some(place) => {
+                        if let Some(value) = &variable.value {
+                            self.initialize(&place, value)?;
+                        }
+                        place
+                    },
+                    None => self.define_object(variable, false)?,
+                },
+            };
+            self.bind(&variable.name, place, automatic);
+        }
+        Ok(())
+    }
+
+    fn define_object(&mut self, variable: &Variable, automatic: bool) -> Result<Place, String> {
+        let type_ = self.concrete_type(&variable.type_, variable.value.as_ref())?;
+        let place = self.allocate(&type_, automatic)?;
+        if let Some(value) = &variable.value {
+            self.initialize(&place, value)?;
+        }
+        Ok(place)
+    }
+
+    fn initialize(&mut self, place: &Place, value: &VariableValue) -> Result<(), String> {
+        match value {
+            VariableValue::String(string) => self.initialize_string(place, string),
+            VariableValue::Expression(expression) => self.initialize_expression(place, expression),
+        }
+    }
+
+    fn initialize_string(&mut self, place: &Place, string: &StringLiteral) -> Result<(), String> {
+        let DeclaredType::Array(element, size) = self.resolve(&place.type_)? else {
+            let pointer = self.string(string)?;
+            return self.store(place, pointer);
+        };
+        let length = self.array_length(&size)?;
+        if string.value.len() as u64 > length {
+            return Err(format!("The string {} is too long for an array of {}", string.raw, length));
+        }
+        let step = self.engine.size_of(&element)?;
+        for (index, unit) in string.value.iter().chain([0].iter()).take(length as usize).enumerate() {
+            let element = Place::new(place.address + index as u64 * step, (*element).clone());
+            self.store(&element, Value::Integer(*unit as i128, NumberType::UnsignedInt))?;
+        }
+        Ok(())
+    }
+
+    fn initialize_expression(&mut self, place: &Place, expression: &Expression) -> Result<(), String> {
+        match (expression, self.kind(&place.type_)?) {
+            (Expression::InitializerList(items), _) => self.initialize_list(place, &flatten(items)),
+            (Expression::Literal(Literal::String(string)), Kind::Array) => self.initialize_string(place, string),
+            (expression, Kind::Tagged) => self.assign_tagged(place, expression),
+            (expression, _) => {
+                let value = self.evaluate(expression)?;
+                self.store(place, value)
+            },
+        }
+    }
+
+    fn initialize_list(&mut self, place: &Place, items: &[&Expression]) -> Result<(), String> {
+        match self.kind(&place.type_)? {
+            Kind::Array | Kind::Record => {
+                let mut index = 0;
+                self.fill(place, items, &mut index)?;
+                match items.get(index) {
+                    None => Ok(()),
+                    Some(item) => match designator(item) {
+                        Some((name, _)) => Err(format!("{:?} has no member named {}", place.type_, name)),
+                        None => Err(format!("Too many initializers for {:?}", place.type_)),
+                    },
+                }
+            },
+            Kind::Tagged => match items {
+                [item] => self.assign_tagged(place, item),
+                _ => Err("A tagged union is initialized with one of its members".to_string()),
+            },
+            _ => match items {
+                [] => Ok(()),
+                [item] => self.initialize_expression(place, item),
+                _ => Err(format!("Too many initializers for {:?}", place.type_)),
+            },
+        }
+    }
+
+    /// Initializes the elements or members of an aggregate from a brace-enclosed
+    /// list, taking as many items as it needs when its own braces are left out.
+    fn fill(&mut self, place: &Place, items: &[&Expression], index: &mut usize) -> Result<(), String> {
+        if let DeclaredType::Array(element, size) = self.resolve(&place.type_)? {
+            let step = self.engine.size_of(&element)?;
+            for position in 0..self.array_length(&size)? {
+                match items.get(*index) {
+                    Some(item) if designator(item).is_none() => {
+                        self.initialize_element(&Place::new(place.address + position * step, (*element).clone()), items, index)?;
+                    },
+                    _ => break,
+                }
+            }
+            return Ok(());
+        }
+        let record = self.record(&place.type_)?;
+        let field_place = |field: &Field| Place {address: place.address + field.offset, type_: field.type_.clone(), bits: field.bits};
+        let mut position = 0;
+        while let Some(item) = items.get(*index) {
+            if let Some((name, value)) = designator(item) {
+                // A designator for another member belongs to an enclosing aggregate.
+                let Some(found) = record.fields.iter().position(|field| field.name == name) else {
+                    break;
+                };
+                *index += 1;
+                self.initialize_expression(&field_place(&record.fields[found]), value)?;
+                position = found + 1;
+                continue;
+            }
+            if position >= record.fields.len() || (record.union_ && position > 0) {
+                break;
+            }
+            self.initialize_element(&field_place(&record.fields[position]), items, index)?;
+            position += 1;
+        }
+        Ok(())
+    }
+
+    fn initialize_element(&mut self, place: &Place, items: &[&Expression], index: &mut usize) -> Result<(), String> {
+        let item = items[*index];
+        let kind = self.kind(&place.type_)?;
+        let whole = match item {
+            Expression::InitializerList(_) => true,
+            Expression::Literal(Literal::String(_)) => kind == Kind::Array,
+            item => kind == Kind::Record && self.static_type(item).is_ok_and(|type_| self.kind(&type_) == Ok(Kind::Record)),
+        };
+        match matches!(kind, Kind::Array | Kind::Record) && !whole {
+            true => self.fill(place, items, index),
+            false => {
+                *index += 1;
+                self.initialize_expression(place, item)
+            },
+        }
+    }
+
+    /// Assigns to a tagged union. Naming a member, alone or with its payload as in
+    /// `s = circle {1.5}`, clears the union and sets its tag.
+    fn assign_tagged(&mut self, place: &Place, expression: &Expression) -> Result<(), String> {
+        let record = self.record(&place.type_)?;
+        let (variant, payload) = match expression {
+            Expression::TaggedInitializer(name, payload) => (name, Some(payload)),
+            Expression::Identifier(name) if record.variants.contains(name) && self.variable(name)?.is_none() => (name, None),
+            expression => {
+                let value = self.evaluate(expression)?;
+                return self.store(place, value);
+            },
+        };
+        let tag = record.variants.iter().position(|name| name == variant)
+            .ok_or_else(|| format!("{} is not a member of {:?}", variant, place.type_))?;
+        let size = self.engine.size_of(&place.type_)?;
+        self.memory.write(place.address, &vec![0; size as usize])?;
+        let tag_field = record.field("tag").ok_or("A tagged union has no tag")?;
+        self.store(&Place::new(place.address + tag_field.offset, tag_field.type_.clone()), Value::Integer(tag as i128, NumberType::Int))?;
+        if let Some(payload) = payload {
+            let field = record.field(variant).ok_or_else(|| format!("{} has no payload", variant))?;
+            self.initialize_list(&Place::new(place.address + field.offset, field.type_.clone()), &flatten(payload))?;
+        }
+        Ok(())
+    }
+
+    /// A name used as the operand of `sizeof` that is a type rather than a variable.
+    fn type_name(&self, expression: &Expression) -> Result<Option<DeclaredType>, String> {
+        match expression {
+            Expression::Parentheses(inner) => self.type_name(inner),
+            Expression::Identifier(name) if self.variable(name)?.is_none() && (self.engine.typedef(name).is_some() || self.classes.contains_key(name)) => {
+                Ok(Some(base(TypeSpecifier::Named(name.clone()))))
+            },
+            _ => Ok(None),
+        }
+    }
+
+    fn select<'a>(&mut self, control: &Expression, associations: &'a [GenericAssociation]) -> Result<&'a Expression, String> {
+        let type_ = self.static_type(control)?;
+        let type_ = self.decayed(&type_)?;
+        typecheck::select_for_type(&type_, associations, &self.engine)
+    }
+
+    fn pointee(&self, type_: &DeclaredType) -> Result<DeclaredType, String> {
+        match self.resolve(type_)? {
+            DeclaredType::Pointer(inner, _) | DeclaredType::Array(inner, _) => Ok(*inner),
+            type_ => Err(format!("{:?} is not a pointer", type_)),
+        }
+    }
+
+    fn arithmetic_type(&self, type_: &DeclaredType) -> Option<NumberType> {
+        match self.resolve(type_).ok()? {
+            DeclaredType::Base(base) => integer_storage(&base.specifier).map(promote).or_else(|| floating_type(&base.specifier)),
+            _ => None,
+        }
+    }
+
+    /// Applies lvalue conversion to a type, as `_Generic` does to its controlling expression.
+    fn decayed(&self, type_: &DeclaredType) -> Result<DeclaredType, String> {
+        Ok(match self.resolve(type_)? {
+            DeclaredType::Base(base) => DeclaredType::Base(Type::new(base.specifier)),
+            DeclaredType::Pointer(inner, _) | DeclaredType::Array(inner, _) => DeclaredType::Pointer(inner, TypeQualifiers::default()),
+            function @ DeclaredType::Function(..) => pointer_to(function),
+        })
+    }
+
+    /// The type of an expression, knowing the types of the variables in scope.
+    /// Expressions that do not involve them are left to the type checker.
+    fn static_type(&mut self, expression: &Expression) -> Result<DeclaredType, String> {
+        let model = self.model();
+        match expression {
+            Expression::Identifier(name) => {
+                if let (Some((_, class)), "this") = (&self.this, name.as_str()) {
+                    return Ok(pointer_to(base(TypeSpecifier::Named(class.clone()))));
+                }
+                if let Some(place) = self.variable(name)? {
+                    return Ok(place.type_);
+                }
+                match self.function_type(name) {
+                    Some(type_) => Ok(type_),
+                    None => typecheck::declared_type_of(expression, &self.engine),
+                }
+            },
+            Expression::Literal(Literal::String(string)) => {
+                let (element, _) = string_element(string);
+                Ok(DeclaredType::Array(Box::new(element), VariableArray::Size(size_literal(string.value.len() as u64 + 1))))
+            },
+            Expression::Parentheses(inner) | Expression::Expression(inner) => self.static_type(inner),
+            Expression::Unary(UnaryOperator::Cast(type_), _) => self.concrete_type(type_, None),
+            Expression::Unary(UnaryOperator::Dereference, operand) => {
+                let type_ = self.static_type(operand)?;
+                self.pointee(&type_)
+            },
+            Expression::Unary(UnaryOperator::AddressOf, operand) => Ok(pointer_to(self.static_type(operand)?)),
+            Expression::Unary(UnaryOperator::PreIncrement | UnaryOperator::PreDecrement |
+                              UnaryOperator::PostIncrement | UnaryOperator::PostDecrement, operand) => self.static_type(operand),
+            Expression::Unary(UnaryOperator::Plus | UnaryOperator::Minus | UnaryOperator::BitwiseNot, operand) => {
+                let type_ = self.static_type(operand)?;
+                let promoted = self.arithmetic_type(&type_).ok_or_else(|| format!("Expected an arithmetic operand but found {:?}", type_))?;
+                number(promoted)
+            },
+            Expression::Unary(UnaryOperator::Not | UnaryOperator::LogicalNot, _) => Ok(base(TypeSpecifier::Int)),
+            Expression::Binary(BinaryOperator::ArrayAccess | BinaryOperator::Index, array, index) => {
+                let type_ = self.static_type(array)?;
+                match self.pointee(&type_) {
+                    Ok(element) => Ok(element),
+                    Err(_) => {
+                        let type_ = self.static_type(index)?;
+                        self.pointee(&type_)
+                    },
+                }
+            },
+            Expression::Binary(operator @ (BinaryOperator::MemberAccess | BinaryOperator::PointerMemberAccess), object, member) => {
+                let mut type_ = self.static_type(object)?;
+                if *operator == BinaryOperator::PointerMemberAccess {
+                    type_ = self.pointee(&type_)?;
+                }
+                match member.as_ref() {
+                    Expression::Identifier(name) => self.record(&type_)?.field(name).map(|field| field.type_.clone())
+                        .ok_or_else(|| format!("{:?} has no member named {}", type_, name)),
+                    Expression::CallFunction(name, _) => match self.find_method(&self.class_name(&type_)?, name) {
+                        Some((_, Some(method))) => Ok(method.return_type.clone()),
+                        _ => Err(format!("{:?} has no method {}", type_, name)),
+                    },
+                    _ => Err("Expected a member name".to_string()),
+                }
+            },
+            Expression::Binary(BinaryOperator::Comma, _, right) => self.static_type(right),
+            Expression::Binary(operator, left, _) if *operator == BinaryOperator::Assign || compound_operator(operator).is_some() => {
+                self.static_type(left)
+            },
+            Expression::Binary(BinaryOperator::LessThan | BinaryOperator::GreaterThan | BinaryOperator::LessThanOrEqual |
+                               BinaryOperator::GreaterThanOrEqual | BinaryOperator::Equal | BinaryOperator::NotEqual |
+                               BinaryOperator::LogicalAnd | BinaryOperator::LogicalOr, _, _) => Ok(base(TypeSpecifier::Int)),
+            Expression::Binary(operator, left, right) => {
+                let (left, right) = (self.static_type(left)?, self.static_type(right)?);
+                match (self.arithmetic_type(&left), self.arithmetic_type(&right)) {
+                    (Some(left), Some(_)) if matches!(operator, BinaryOperator::LeftShift | BinaryOperator::RightShift) => number(left),
+                    (Some(left), Some(right)) => number(common_type(left, right, model)),
+                    (None, Some(_)) => self.decayed(&left),
+                    (Some(_), None) => self.decayed(&right),
+                    (None, None) => number(ptrdiff_type(model)),
+                }
+            },
+            Expression::Ternary(_, then, otherwise) => {
+                let (then, otherwise) = (self.static_type(then)?, self.static_type(otherwise)?);
+                match (self.arithmetic_type(&then), self.arithmetic_type(&otherwise)) {
+                    (Some(then), Some(otherwise)) => number(common_type(then, otherwise, model)),
+                    _ => Ok(then),
+                }
+            },
+            Expression::CallFunction(name, _) => {
+                let callee = match self.variable(name)? {
+                    Some(place) => self.pointee(&place.type_).or_else(|_| self.resolve(&place.type_))?,
+                    None => match (&self.this, self.function_type(name)) {
+                        (Some((_, class)), _) if self.find_method(class, name).is_some() => {
+                            match self.find_method(class, name) {
+                                Some((_, Some(method))) => return Ok(method.return_type.clone()),
+                                _ => return Err(format!("{} has no body", name)),
+                            }
+                        },
+                        (_, Some(type_)) => type_,
+                        _ => return Err(format!("Unknown function {}", name)),
+                    },
+                };
+                match callee {
+                    DeclaredType::Function(return_type, _) => Ok(*return_type),
+                    type_ => Err(format!("{:?} is not a function", type_)),
+                }
+            },
+            Expression::Generic(control, associations) => {
+                let selected = self.select(control, associations)?;
+                self.static_type(selected)
+            },
+            expression => typecheck::declared_type_of(expression, &self.engine),
+        }
+    }
+}
+
+fn base(specifier: TypeSpecifier) -> DeclaredType {
+    DeclaredType::Base(Type::new(specifier))
+}
+
+fn pointer_to(type_: DeclaredType) -> DeclaredType {
+    DeclaredType::Pointer(Box::new(type_), TypeQualifiers::default())
+}
+
+fn void_pointer() -> DeclaredType {
+    pointer_to(base(TypeSpecifier::Void))
+}
+
+fn ptrdiff_type(model: DataModel) -> NumberType {
+    match model {
+        DataModel::LP64 => NumberType::Long,
+        DataModel::LLP64 => NumberType::LongLong,
+        DataModel::ILP32 => NumberType::Int,
+    }
+}
+
+fn builtin_return_type(name: &str, model: DataModel) -> DeclaredType {
+    match name {
+        "malloc" | "calloc" | "memcpy" | "memset" => void_pointer(),
+        "free" => base(TypeSpecifier::Void),
+        "strlen" => number(size_type(model)).unwrap_or_else(|_| base(TypeSpecifier::UnsignedLong)),
+        _ => base(TypeSpecifier::Int),
+    }
+}
+
+/// The type an integer type is stored as. Types narrower than `int` are bit-precise
+/// types of their width so that `wrap` truncates to it.
+fn integer_storage(specifier: &TypeSpecifier) -> Option<NumberType> {
+    Some(match specifier {
+        TypeSpecifier::Bool | TypeSpecifier::UnsignedChar => NumberType::UnsignedBitInt(8),
+        TypeSpecifier::Char | TypeSpecifier::SignedChar => NumberType::BitInt(8),
+        TypeSpecifier::Short => NumberType::BitInt(16),
+        TypeSpecifier::UnsignedShort => NumberType::UnsignedBitInt(16),
+        TypeSpecifier::Int | TypeSpecifier::Enum(_) => NumberType::Int,
+        TypeSpecifier::UnsignedInt => NumberType::UnsignedInt,
+        TypeSpecifier::Long => NumberType::Long,
+        TypeSpecifier::UnsignedLong => NumberType::UnsignedLong,
+        TypeSpecifier::LongLong => NumberType::LongLong,
+        TypeSpecifier::UnsignedLongLong => NumberType::UnsignedLongLong,
+        _ => return None,
+    })
+}
+
+fn promote(storage: NumberType) -> NumberType {
+    match storage {
+        NumberType::BitInt(_) | NumberType::UnsignedBitInt(_) => NumberType::Int,
+        type_ => type_,
+    }
+}
+
+fn floating_type(specifier: &TypeSpecifier) -> Option<NumberType> {
+    match specifier {
+        TypeSpecifier::Float => Some(NumberType::Float),
+        TypeSpecifier::Double => Some(NumberType::Double),
+        TypeSpecifier::LongDouble => Some(NumberType::LongDouble),
+        _ => None,
+    }
+}
+
+fn round(value: f64, type_: NumberType) -> f64 {
+    match type_ {
+        NumberType::Float => value as f32 as f64,
+        _ => value,
+    }
+}
+
+fn from_le(bytes: &[u8]) -> u128 {
+    bytes.iter().rev().fold(0, |value, byte| (value << 8) | *byte as u128)
+}
+
+/// The element type of a string literal's array and the size of its units in bytes.
+fn string_element(string: &StringLiteral) -> (DeclaredType, u32) {
+    let specifier = match string.encoding {
+        Encoding::Char => TypeSpecifier::Char,
+        Encoding::Utf8 => TypeSpecifier::UnsignedChar,
+        Encoding::Utf16 => TypeSpecifier::UnsignedShort,
+        Encoding::Utf32 => TypeSpecifier::UnsignedInt,
+        Encoding::Wide => TypeSpecifier::Int,
+    };
+    (base(specifier), string.encoding.unit_bits() / 8)
+}
+
+/// The items of a comma separated list, such as the arguments of a call.
+fn flatten(expression: &Expression) -> Vec<&Expression> {
+    match expression {
+        Expression::Blank => Vec::new(),
+        Expression::Binary(BinaryOperator::Comma, left, right) => {
+            let mut items = flatten(left);
+            items.push(right);
+            items
+        },
+        expression => vec![expression],
+    }
+}
+
+/// The member and value of a designated initializer such as `.x = 1`.
+fn designator(expression: &Expression) -> Option<(&str, &Expression)> {
+    match expression {
+        Expression::Binary(BinaryOperator::Assign, target, value) => match target.as_ref() {
+            Expression::Unary(UnaryOperator::MemberSet, member) => match member.as_ref() {
+                Expression::Identifier(name) => Some((name, value)),
+                _ => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn parameters(arguments: &[FunctionArgument]) -> Vec<&Parameter> {
+    let parameters = arguments.iter()
+        .filter_map(|argument| match argument {
+            FunctionArgument::Parameter(parameter) => Some(parameter),
+            FunctionArgument::Ellipsis => None,
+        })
+        .collect::<Vec<_>>();
+    match parameters.as_slice() {
+        [Parameter {name: None, type_: DeclaredType::Base(Type {specifier: TypeSpecifier::Void, ..}), ..}] => Vec::new(),
+        _ => parameters,
+    }
+}
+
+fn compound_operator(operator: &BinaryOperator) -> Option<BinaryOperator> {
+    Some(match operator {
+        BinaryOperator::AddAssign => BinaryOperator::Add,
+        BinaryOperator::SubtractAssign => BinaryOperator::Subtract,
+        BinaryOperator::MultiplyAssign => BinaryOperator::Multiply,
+        BinaryOperator::DivideAssign => BinaryOperator::Divide,
+        BinaryOperator::ModuloAssign => BinaryOperator::Modulo,
+        BinaryOperator::LeftShiftAssign => BinaryOperator::LeftShift,
+        BinaryOperator::RightShiftAssign => BinaryOperator::RightShift,
+        BinaryOperator::BitwiseAndAssign => BinaryOperator::BitwiseAnd,
+        BinaryOperator::BitwiseOrAssign => BinaryOperator::BitwiseOr,
+        BinaryOperator::BitwiseXorAssign => BinaryOperator::BitwiseXor,
+        _ => return None,
+    })
+}
+
+/// How an operator overload spells a binary operator.
+fn binary_symbol(operator: &BinaryOperator) -> Option<&'static str> {
+    Some(match operator {
+        BinaryOperator::Add => "+",
+        BinaryOperator::Subtract => "-",
+        BinaryOperator::Multiply => "*",
+        BinaryOperator::Divide => "/",
+        BinaryOperator::Modulo => "%",
+        BinaryOperator::LeftShift => "<<",
+        BinaryOperator::RightShift => ">>",
+        BinaryOperator::LessThan => "<",
+        BinaryOperator::GreaterThan => ">",
+        BinaryOperator::LessThanOrEqual => "<=",
+        BinaryOperator::GreaterThanOrEqual => ">=",
+        BinaryOperator::NotEqual => "!=",
+        BinaryOperator::BitwiseAnd => "&",
+        BinaryOperator::BitwiseOr => "|",
+        BinaryOperator::BitwiseXor => "^",
+        _ => return None,
+    })
+}
+
+fn unary_symbol(operator: &UnaryOperator) -> Option<&'static str> {
+    Some(match operator {
+        UnaryOperator::Plus => "+",
+        UnaryOperator::Minus => "-",
+        UnaryOperator::LogicalNot => "!",
+        UnaryOperator::BitwiseNot => "~",
+        UnaryOperator::PreIncrement => "++",
+        UnaryOperator::PreDecrement => "--",
+        _ => return None,
+    })
+}
+
+fn integer_digits(value: u128, radix: u32, precision: Option<usize>) -> String {
+    let digits = match (value, precision) {
+        (0, Some(0)) => String::new(),
+        _ => match radix {
+            8 => format!("{:o}", value),
+            16 => format!("{:x}", value),
+            _ => value.to_string(),
+        },
+    };
+    format!("{:0>width$}", digits, width = precision.unwrap_or(0))
+}
+
+fn pad(out: &mut Vec<u8>, prefix: &[u8], body: &[u8], width: usize, left: bool, zero: bool) {
+    let fill = width.saturating_sub(prefix.len() + body.len());
+    match (left, zero) {
+        (true, _) => {
+            out.extend(prefix);
+            out.extend(body);
+            out.extend(std::iter::repeat_n(b' ', fill));
+        },
+        (false, true) => {
+            out.extend(prefix);
+            out.extend(std::iter::repeat_n(b'0', fill));
+            out.extend(body);
+        },
+        (false, false) => {
+            out.extend(std::iter::repeat_n(b' ', fill));
+            out.extend(prefix);
+            out.extend(body);
+        },
+    }
+}
+
+/// Formats a non-negative number for `%f`, `%e` or `%g` as C does.
+fn format_float(value: f64, conversion: u8, precision: Option<usize>, alternate: bool) -> String {
+    let text = if value.is_infinite() {
+        "inf".to_string()
+    }
+    else if value.is_nan() {
+        "nan".to_string()
+    }
+    else {
+        let precision = precision.unwrap_or(6);
+        match conversion.to_ascii_lowercase() {
+            b'f' => {
+                let mut text = format!("{:.*}", precision, value);
+                if alternate && precision == 0 {
+                    text.push('.');
+                }
+                text
+            },
+            b'e' => exponential(value, precision, alternate),
+            _ => {
+                let precision = precision.max(1);
+                let exponent = exponential(value, precision - 1, false).split_once('e')
+                    .and_then(|(_, exponent)| exponent.parse::<i32>().ok())
+                    .unwrap_or(0);
+                let text = match exponent < -4 || exponent >= precision as i32 {
+                    true => exponential(value, precision - 1, alternate),
+                    false => format!("{:.*}", (precision as i32 - 1 - exponent) as usize, value),
+                };
+                match alternate {
+                    true => text,
+                    false => match text.split_once('e') {
+                        Some((mantissa, exponent)) => format!("{}e{}", trim_fraction(mantissa), exponent),
+                        None => trim_fraction(&text).to_string(),
+                    },
+                }
+            },
+        }
+    };
+    match conversion.is_ascii_uppercase() {
+        true => text.to_uppercase(),
+        false => text,
+    }
+}
+
+fn exponential(value: f64, precision: usize, alternate: bool) -> String {
+    let text = format!("{:.*e}", precision, value);
+    let (mantissa, exponent) = text.split_once('e').unwrap_or((&text, "0"));
+    let exponent = exponent.parse::<i32>().unwrap_or(0);
+    let point = match alternate && precision == 0 {
+        true => ".",
+        false => "",
+    };
+    format!("{}{}e{}{:02}", mantissa, point, if exponent < 0 { '-' } else { '+' }, exponent.abs())
+}
+
+fn trim_fraction(text: &str) -> &str {
+    match text.contains('.') {
+        true => text.trim_end_matches('0').trim_end_matches('.'),
+        false => text,
+    }
+}
+
+/// Gives every anonymous struct and union defined in declaration specifiers a tag,
+/// so the objects declared with them have a type the interpreter can look up.
+/// C11 anonymous members stay anonymous, since their members belong to the parent.
+fn name_anonymous_types(header: &mut Header) {
+    let mut count = 0;
+    for statement in &mut header.statements {
+        match statement {
+            HeaderStatement::Struct(struct_) => name_in_members(&mut struct_.members, &mut count),
+            HeaderStatement::Union(union_) => name_in_members(&mut union_.members, &mut count),
+            HeaderStatement::TaggedUnion(tagged) => name_in_tagged_union(tagged, &mut count),
+            HeaderStatement::Variable(variable_list) => name_in_list(variable_list, &mut count),
+            HeaderStatement::Function(function) => name_in_block(&mut function.body, &mut count),
+            HeaderStatement::Class(class) => {
+                for member in &mut class.members {
+                    match member {
+                        ClassMember::Field(member) => name_in_members(std::slice::from_mut(member), &mut count),
+                        ClassMember::Method(Method::Normal(function)) => name_in_block(&mut function.body, &mut count),
+                        ClassMember::OperatorOverload(OperatorOverload::Normal {body, ..}) => name_in_block(body, &mut count),
+                        _ => {},
+                    }
+                }
+            },
+            _ => {},
+        }
+    }
+}
+
+fn name_in_list(variable_list: &mut VariableList, count: &mut usize) {
+    let specifier = match variable_list.specifiers.definition.as_deref_mut() {
+        Some(Definition::Struct(struct_)) => {
+            name_in_members(&mut struct_.members, count);
+            match &struct_.name {
+                Some(_) => return,
+                None => {
+                    *count += 1;
+                    struct_.name = Some(format!("<anonymous {}>", count));
+                    TypeSpecifier::Struct(struct_.name.clone())
+                },
+            }
+        },
+        Some(Definition::Union(union_)) => {
+            name_in_members(&mut union_.members, count);
+            match &union_.name {
+                Some(_) => return,
+                None => {
+                    *count += 1;
+                    union_.name = Some(format!("<anonymous {}>", count));
+                    TypeSpecifier::Union(union_.name.clone())
+                },
+            }
+        },
+        Some(Definition::TaggedUnion(tagged)) => return name_in_tagged_union(tagged, count),
+        Some(Definition::Enum(_)) | None => return,
+    };
+    variable_list.specifiers.type_.specifier = specifier.clone();
+    for variable in &mut variable_list.variables {
+        set_base(&mut variable.type_, &specifier);
+    }
+}
+
+fn set_base(type_: &mut DeclaredType, specifier: &TypeSpecifier) {
+    match type_ {
+        DeclaredType::Base(base) => base.specifier = specifier.clone(),
+        DeclaredType::Pointer(inner, _) | DeclaredType::Array(inner, _) | DeclaredType::Function(inner, _) => set_base(inner, specifier),
+    }
+}
+
+fn name_in_members(members: &mut [Member], count: &mut usize) {
+    for member in members {
+        match member {
+            Member::Variables(variable_list) => name_in_list(variable_list, count),
+            Member::Struct(struct_) => name_in_members(&mut struct_.members, count),
+            Member::Union(union_) => name_in_members(&mut union_.members, count),
+            Member::BitFields(_) => {},
+        }
+    }
+}
+
+fn name_in_tagged_union(tagged: &mut TaggedUnion, count: &mut usize) {
+    for member in &mut tagged.members {
+        if let Some(members) = &mut member.value {
+            name_in_members(members, count);
+        }
+    }
+}
+
+fn name_in_block(block: &mut CodeBlock, count: &mut usize) {
+    let CodeBlock::Code(statement_list) = block;
+    for statement in &mut statement_list.statements {
+        name_in_statement(statement, count);
+    }
+}
+
+fn name_in_body(body: &mut BlockOrStatement, count: &mut usize) {
+    match body {
+        BlockOrStatement::Block(block) => name_in_block(block, count),
+        BlockOrStatement::Statement(statement) => name_in_statement(statement, count),
+    }
+}
+
+fn name_in_statement(statement: &mut Statement, count: &mut usize) {
+    match statement {
+        Statement::VariableList(variable_list) => name_in_list(variable_list, count),
+        Statement::If(_, body, else_) => {
+            name_in_body(body, count);
+            if let Some(else_) = else_ {
+                name_in_body(else_, count);
+            }
+        },
+        Statement::While(_, body) | Statement::DoWhile(_, body) => name_in_body(body, count),
+        Statement::For(initializer, _, _, body) => {
+            match initializer.as_deref_mut() {
+                Some(VariableListOrStatement::VariableList(variable_list)) => name_in_list(variable_list, count),
+                Some(VariableListOrStatement::Statement(statement)) => name_in_statement(statement, count),
+                None => {},
+            }
+            name_in_body(body, count);
+        },
+        Statement::Switch(_, cases) => {
+            for case in cases {
+                name_in_body(&mut case.body, count);
+            }
+        },
+        Statement::Attributed(_, statement) => name_in_statement(statement, count),
+        Statement::Block(block) => name_in_block(block, count),
+        _ => {},
+    }
+}
+
+#[cfg(test)]
+mod interpreter_tests {
+    use super::*;
+    use crate::logos_lexer::lex;
+    use crate::parser::Parser;
+
+    fn interpreter(input: &str) -> Interpreter {
+        let header = Parser::new(&lex(input).unwrap()).parse().unwrap();
+        match Interpreter::from_header(&header, DataModel::LP64) {
+            Ok(interpreter) => interpreter,
+            Err(err) => panic!("Failed to prepare {}: {}", input, err),
+        }
+    }
+
+    fn run(input: &str) -> (i32, String) {
+        let mut interpreter = interpreter(input);
+        match interpreter.run_main() {
+            Ok(status) => (status, interpreter.stdout()),
+            Err(err) => panic!("Failed to run {}: {}", input, err),
+        }
+    }
+
+    fn error(body: &str) -> String {
+        let input = format!("void *malloc(unsigned long size);\nvoid free(void *pointer);\nint deep(int n) {{ return deep(n + 1); }}\nint main(void) {{ {} }}\n", body);
+        match interpreter(&input).run_main() {
+            Ok(status) => panic!("Expected {} to fail but it returned {}", body, status),
+            Err(err) => err,
+        }
+    }
+
+    #[test]
+    fn test_scalars_and_control_flow() {
+        let (status, output) = run("int printf(const char *format, ...);\nint fib(int n) {\n    if (n < 2) return n;\n    return fib(n - 1) + fib(n - 2);\n}\nint main(void) {\n    int total = 0;\n    for (int i = 0; i < 10; i++) {\n        if (i % 2 == 0) continue;\n        else if (i == 7) break;\n        else total += i;\n    }\n    unsigned char c = 250;\n    c += 10;\n    int x = 5;\n    while (x > 0) x--;\n    do { x++; } while (x < 3);\n    switch (total) {\n        case 9: printf(\"nine \");\n        case 10: printf(\"ten \"); break;\n        default: printf(\"other \");\n    }\n    unsigned u = 0;\n    u--;\n    char big = 127;\n    big++;\n    printf(\"%d %d %u %d %u %d %d\\n\", total, fib(10), c, x, u, big, (int)-3.7);\n    return total > 5 ? total : -1;\n}\n");
+        assert_eq!(status, 9);
+        assert_eq!(output, "nine ten 9 55 4 3 4294967295 -128 -3\n");
+
+        // The else belongs to the inner if.
+        let (_, output) = run("int printf(const char *format, ...);\nint main(void) {\n    int a = 1, b = 0;\n    if (a) if (b) printf(\"x\"); else printf(\"y\");\n    if (b) if (a) printf(\"x\"); else printf(\"z\");\n    printf(\"\\n\");\n    return 0;\n}\n");
+        assert_eq!(output, "y\n");
+    }
+
+    #[test]
+    fn test_pointers_arrays_and_strings() {
+        let (status, output) = run("int printf(const char *format, ...);\nvoid *malloc(unsigned long size);\nvoid free(void *pointer);\nunsigned long strlen(const char *s);\nint table[2][3] = { 1, 2, 3, {4, 5} };\nvoid swap(int *a, int *b) { int t = *a; *a = *b; *b = t; }\nint main(int argc, char **argv) {\n    int a = 1, b = 2;\n    swap(&a, &b);\n    int values[5] = {5, 4, 3};\n    int *p = values + 1;\n    p[1] = 7;\n    char name[] = \"hello\";\n    name[0] = 'j';\n    char *copy = malloc(strlen(name) + 1);\n    for (int i = 0; i <= strlen(name); i++) copy[i] = name[i];\n    int n = 4;\n    int vla[n];\n    printf(\"%d %d %d %d %ld %s %s %zu %zu\\n\", a, b, values[2], *p, &values[4] - p, name, copy, sizeof name, sizeof vla);\n    printf(\"%d %d %zu %d %d\\n\", table[0][2], table[1][1], sizeof table, argc, argv[0] == 0);\n    free(copy);\n    return 0;\n}\n");
+        assert_eq!(status, 0);
+        assert_eq!(output, "2 1 7 4 3 jello jello 6 16\n3 5 24 0 1\n");
+
+        let (_, output) = run("int printf(const char *format, ...);\ntypedef int (*binary)(int, int);\nint add(int a, int b) { return a + b; }\nint mul(int a, int b) { return a * b; }\nint apply(binary op, int a, int b) { return op(a, b); }\nint counter(void) { static int calls = 0; return ++calls; }\nint main(void) {\n    counter();\n    counter();\n    float f = 1.0f / 3;\n    const char *kind = _Generic(f, float: \"float\", default: \"other\");\n    printf(\"%d %d %d %s %.9f\\n\", apply(add, 2, 3), apply(&mul, 2, 3), counter(), kind, f);\n    return 0;\n}\n");
+        assert_eq!(output, "5 6 3 float 0.333333343\n");
+    }
+
+    #[test]
+    fn test_structs_unions_and_bit_fields() {
+        let (_, output) = run("int printf(const char *format, ...);\nvoid *calloc(unsigned long count, unsigned long size);\nstruct point { int x; int y; };\nstruct flags { unsigned a : 3; int b : 4; unsigned c : 1; };\nunion word { unsigned int value; unsigned char bytes[4]; };\nstruct node { int value; struct node *next; };\nstruct point origin = { .y = 2 };\nstruct point moved(struct point p, int dx) { p.x += dx; return p; }\nint main(void) {\n    struct point q = moved(origin, 3);\n    struct point *r = &q;\n    r->y *= 10;\n    struct flags f = {7, -3, 1};\n    f.a++;\n    union word w;\n    w.value = 0x01020304;\n    struct node *head = 0;\n    for (int i = 1; i <= 3; i++) {\n        struct node *n = calloc(1, sizeof(struct node));\n        n->value = i * 10;\n        n->next = head;\n        head = n;\n    }\n    int sum = 0;\n    for (struct node *n = head; n; n = n->next) sum += n->value;\n    printf(\"%d %d %d %d %d %d %d %d %zu\\n\", origin.x, q.x, q.y, f.a, f.b, f.c, w.bytes[0], sum, offsetof(struct node, next));\n    return 0;\n}\n");
+        assert_eq!(output, "0 3 20 0 -3 1 4 60 8\n");
+    }
+
+    #[test]
+    fn test_classes_and_tagged_unions() {
+        let (_, output) = run("int printf(const char *format, ...);\nabstract class Shape {\n    int id;\n    int area();\n    int describe() { return printf(\"shape %d has area %d\\n\", id, area()); }\n};\nclass Square : Shape {\n    int side;\n    int area() { return side * side; }\n};\nclass Rect : Shape {\n    int w;\n    int h;\n    int area() { return w * h; }\n};\nclass Vec {\n    int x;\n    int y;\n    Vec operator(+)(Vec other) {\n        typeof(other) sum;\n        sum.x = x + other.x;\n        sum.y = this->y + other.y;\n        return sum;\n    }\n};\nSquare square;\nRect rect;\nVec a;\nVec b;\nVec c;\nint main(void) {\n    square.id = 1;\n    square.side = 3;\n    rect.id = 2;\n    rect.w = 2;\n    rect.h = 5;\n    Square *s = &square;\n    s->describe();\n    rect.describe();\n    a.x = 1; a.y = 2; b.x = 10; b.y = 20;\n    c = a + b;\n    printf(\"%d %d\\n\", c.x, c.y);\n    return 0;\n}\n");
+        assert_eq!(output, "shape 1 has area 9\nshape 2 has area 10\n11 22\n");
+
+        let (_, output) = run("int printf(const char *format, ...);\ntypedef int myint;\nclass A {\n    int x;\n    A twice() { A copy; copy.x = x * 2; return copy; }\n};\nint main(void) {\n    A a;\n    a.x = 21;\n    myint *p = 0;\n    myint n = 2;\n    A b = a.twice();\n    printf(\"%d %d %d\\n\", b.x, p == 0, n);\n    return 0;\n}\n");
+        assert_eq!(output, "42 1 2\n");
+
+        let (_, output) = run("int printf(const char *format, ...);\ntagged number { integer {int value;}, real {double value;}, none };\ntagged number n;\ndouble twice(tagged number m) {\n    if (m.tag == integer) return m.integer.value * 2;\n    else if (m.tag == real) return m.real.value * 2;\n    return 0;\n}\nint main(void) {\n    n = integer {21};\n    printf(\"%g \", twice(n));\n    n = real {1.25};\n    printf(\"%g \", twice(n));\n    n = none;\n    printf(\"%g\\n\", twice(n));\n    return 0;\n}\n");
+        assert_eq!(output, "42 2.5 0\n");
+    }
+
+    #[test]
+    fn test_printf() {
+        // The expected output is what glibc prints for the same calls.
+        let (_, output) = run("int printf(const char *format, ...);\nint main(void) {\n    printf(\"[%5d|%-5d|%05d|%+d|% d|%.3d|%x|%#X|%#o|%o]\\n\", 42, 42, -42, 7, 7, 5, 255, 255, 8, 0);\n    printf(\"[%c|%3c|%s|%.2s|%-6s|%*d|%-*d|%.*s]\\n\", 'A', 'b', \"text\", \"text\", \"ab\", 4, 1, 3, 2, 2, \"xyz\");\n    printf(\"[%f|%.2f|%8.3f|%-8.1f|%e|%.0e|%E|%g|%g|%g|%G|%#g|%.3g]\\n\", 3.14159, 2.5, -1.0, 0.25, 12345.678, 5.5, 0.000123, 100000.0, 1000000.0, 0.0001, 1e-5, 1.5, 1234.5);\n    printf(\"[%hhd|%hd|%lu|%lld|%%|%5.1f|%+.2e|%010.4f]\\n\", 300, 70000, 4294967296UL, -9LL, 9.96, -0.0, 3.14159);\n    printf(\"[%s|%p]\\n\", (char *)0, (void *)0);\n    return 0;\n}\n");
+        assert_eq!(output, "[   42|42   |-0042|+7| 7|005|ff|0XFF|010|0]\n[A|  b|text|te|ab    |   1|2  |xy]\n[3.141590|2.50|  -1.000|0.2     |1.234568e+04|6e+00|1.230000E-04|100000|1e+06|0.0001|1E-05|1.50000|1.23e+03]\n[44|4464|4294967296|-9|%| 10.0|-0.00e+00|00003.1416]\n[(null)|(nil)]\n");
+    }
+
+    #[test]
+    fn test_runtime_errors() {
+        assert_eq!(error("int *p = 0; return *p;"), "Null pointer dereference");
+        assert!(error("int a[3]; return a[3];").contains("outside of any live object"));
+        assert!(error("int *p = malloc(4); free(p); return *p;").contains("after it was freed"));
+        assert!(error("int *p = malloc(4); free(p); free(p); return 0;").starts_with("Double free"));
+        assert!(error("int x; free(&x); return 0;").contains("not returned by malloc"));
+        assert!(error("char *s = \"abc\"; s[0] = 'x'; return 0;").starts_with("Write to the string literal"));
+        assert_eq!(error("int zero = 0; return 1 / zero;"), "Division by zero");
+        assert!(error("return 1 << 40;").starts_with("Shift by 40"));
+        assert!(error("return deep(0);").starts_with("Calls nest"));
+        assert!(error("for (;;) {} return 0;").contains("did not finish"));
+        assert!(error("return missing(1);").contains("Unknown function missing"));
+
+        let mut tagged = interpreter("tagged t { a {int x;}, b {int y;} };\ntagged t v;\nint main(void) { v = a {1}; return v.b.y; }\n");
+        assert_eq!(tagged.run_main(), Err("Read of b from a tagged union that holds a".to_string()));
+    }
+
+    #[test]
+    fn test_call() {
+        let mut interpreter = interpreter("int scale = 3;\nlong multiply(int a, long b) { return a * b * scale; }\nvoid greet(const char *name) { printf(\"hi %s\", name); }\nint printf(const char *format, ...);\n");
+        let arguments = vec![Value::Integer(2, NumberType::Int), Value::Integer(-7, NumberType::Long)];
+        assert_eq!(interpreter.call("multiply", arguments), Ok(Value::Integer(-42, NumberType::Long)));
+        assert!(interpreter.call("multiply", vec![Value::Integer(1, NumberType::Int)]).is_err());
+        let name = interpreter.call("malloc", vec![Value::Integer(3, NumberType::UnsignedLong)]).unwrap();
+        for (index, byte) in [b'a', b'l', 0].iter().enumerate() {
+            let address = name.integer().unwrap() as u64 + index as u64;
+            interpreter.memory.write(address, &[*byte]).unwrap();
+        }
+        assert_eq!(interpreter.call("greet", vec![name]), Ok(Value::Void));
+        assert_eq!(interpreter.stdout(), "hi al");
+        assert!(interpreter.run_main().is_err());
+    }
+}
@@ -0,0 +1,1080 @@
+use crate::ast::*;
+use crate::constant;
+use crate::literal::{NumberLiteral, NumberType, NumberValue};
+use crate::parser::Parser;
+use crate::printer::print_node;
+use crate::scoped::ScopedMap;
+use crate::typecheck;
+use crate::visit::{walk_expression, Visitor};
+
+
+/// The sizes of `long`, `long double` and pointers on a target. Every scalar is
+/// aligned to its size, as on ARM, and bit-fields follow the System V rules in
+/// every model.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum DataModel {
+    /// 64-bit Unix: 64-bit `long` and pointers.
+    #[default]
+    LP64,
+    /// 64-bit Windows: 32-bit `long`, 64-bit pointers.
+    LLP64,
+    /// 32-bit targets: 32-bit `int`, `long` and pointers.
+    ILP32,
+}
+
+impl DataModel {
+    pub fn pointer_size(&self) -> u64 {
+        match self {
+            DataModel::LP64 | DataModel::LLP64 => 8,
+            DataModel::ILP32 => 4,
+        }
+    }
+
+    pub fn long_size(&self) -> u64 {
+        match self {
+            DataModel::LP64 => 8,
+            DataModel::LLP64 | DataModel::ILP32 => 4,
+        }
+    }
+
+    pub fn long_double_size(&self) -> u64 {
+        match self {
+            DataModel::LP64 => 16,
+            DataModel::LLP64 | DataModel::ILP32 => 8,
+        }
+    }
+}
+
+/// Where a named member lives inside its struct, union, tagged union or class.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldLayout {
+    pub name: String,
+    /// The offset in bytes from the start of the enclosing type.
+    pub offset: u64,
+    pub bits: Option<BitRange>,
+}
+
+/// The bits a bit-field occupies, counted from the byte at its offset.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BitRange {
+    pub start: u64,
+    pub width: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layout {
+    pub size: u64,
+    pub align: u64,
+    /// The named members in declaration order, with the members of anonymous
+    /// structs and unions flattened into their parent and inherited members first.
+    pub fields: Vec<FieldLayout>,
+    /// The offset of a class's vtable pointer.
+    pub vtable: Option<u64>,
+}
+
+impl Layout {
+    pub fn field(&self, name: &str) -> Option<&FieldLayout> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct TypedefLayout {
+    type_: DeclaredType,
+    /// The layout of the anonymous struct or union the typedef was declared with.
+    anonymous: Option<Layout>,
+}
+
+/// Places members one after another, or all at offset zero in a union. Members
+/// of a packed type are only byte aligned.
+struct Placer {
+    union_: bool,
+    packed: bool,
+    bit: u64,
+    end: u64,
+    align: u64,
+    fields: Vec<FieldLayout>,
+}
+
+impl Placer {
+    fn new(union_: bool) -> Self {
+        Self {union_, packed: false, bit: 0, end: 0, align: 1, fields: Vec::new()}
+    }
+
+    /// Reserves room for an object and returns its offset in bytes.
+    fn place(&mut self, size: u64, align: u64) -> u64 {
+        if self.union_ {
+            self.bit = 0;
+        }
+        self.bit = round_up(self.bit, align * 8);
+        let offset = self.bit / 8;
+        self.bit += size * 8;
+        self.align = self.align.max(align);
+        self.end = self.end.max(self.bit);
+        offset
+    }
+
+    /// Places an anonymous member or base class and takes over its fields.
+    fn place_flattened(&mut self, layout: Layout) {
+        let offset = self.place(layout.size, layout.align);
+        self.fields.extend(layout.fields.into_iter().map(|field| FieldLayout {
+            offset: field.offset + offset,
+            ..field
+        }));
+    }
+
+    fn finish(self, vtable: Option<u64>) -> Layout {
+        Layout {
+            size: round_up(self.end.div_ceil(8), self.align),
+            align: self.align,
+            fields: self.fields,
+            vtable,
+        }
+    }
+}
+
+/// Lays out types for a target data model. Tags, typedefs and classes are
+/// remembered as they are laid out so later types can refer to them.
+#[derive(Debug, Clone, Default)]
+pub struct LayoutEngine {
+    model: DataModel,
+    structs: ScopedMap<Layout>,
+    unions: ScopedMap<Layout>,
+    tagged_unions: ScopedMap<Layout>,
+    classes: ScopedMap<Layout>,
+    typedefs: ScopedMap<TypedefLayout>,
+    enumerators: ScopedMap<i128>,
+    variables: ScopedMap<DeclaredType>,
+}
+
+fn round_up(value: u64, align: u64) -> u64 {
+    value.div_ceil(align) * align
+}
+
+/// An array size written as a number literal, for arrays whose size is worked out.
+pub(crate) fn size_literal(size: u64) -> Expression {
+    Expression::Literal(Literal::Number(NumberLiteral {
+        raw: size.to_string(),
+        value: NumberValue::Integer(size as u128),
+        c_type: NumberType::UnsignedLongLong,
+    }))
+}
+
+fn is_packed(attributes: &[Attribute]) -> bool {
+    attributes.iter().any(|attribute| attribute.is("packed"))
+}
+
+impl LayoutEngine {
+    pub fn new(model: DataModel) -> Self {
+        Self {
+            model,
+            ..Self::default()
+        }
+    }
+
+    pub fn model(&self) -> DataModel {
+        self.model
+    }
+
+    /// Lays out every type and typedef declared at the top level of a header and
+    /// numbers the members of its enums. Static assertions and `constexpr`
+    /// initializers are checked along the way, including those inside functions.
+    pub fn from_header(header: &Header, model: DataModel) -> Result<Self, Finding> {
+        let mut engine = Self::new(model);
+        for (index, statement) in header.statements.iter().enumerate() {
+            engine.header_statement(statement).map_err(|message| Finding::new(message, index, statement.name()))?;
+        }
+        Ok(engine)
+    }
+
+    fn header_statement(&mut self, statement: &HeaderStatement) -> Result<(), String> {
+        match statement {
+            HeaderStatement::Struct(struct_) => {
+                self.layout_struct(struct_)?;
+            },
+            HeaderStatement::Union(union_) => {
+                self.layout_union(union_)?;
+            },
+            HeaderStatement::TaggedUnion(tagged) => {
+                self.layout_tagged_union(tagged)?;
+            },
+            HeaderStatement::Enum(enum_) => {
+                self.enumerate(enum_)?;
+            },
+            HeaderStatement::Class(class) => {
+                self.layout_class(class)?;
+            },
+            HeaderStatement::Variable(variable_list) => {
+                self.declare(variable_list)?;
+            },
+            HeaderStatement::StaticAssert(assertion) => {
+                self.static_assert(assertion)?;
+            },
+            HeaderStatement::FunctionPrototype(prototype) => {
+                self.variables.insert(prototype.name.clone(),
+                    DeclaredType::Function(Box::new(prototype.return_type.clone()), prototype.arguments.clone()));
+            },
+            HeaderStatement::Function(function) => {
+                self.variables.insert(function.name.clone(),
+                    DeclaredType::Function(Box::new(function.return_type.clone()), function.arguments.clone()));
+                self.scoped(|scope| {
+                    for argument in &function.arguments {
+                        if let FunctionArgument::Parameter(Parameter {name: Some(name), type_, ..}) = argument {
+                            scope.variables.insert(name.clone(), type_.clone());
+                        }
+                    }
+                    let CodeBlock::Code(body) = &function.body;
+                    scope.check_block(&body.statements)
+                })?;
+            },
+            _ => {},
+        }
+        Ok(())
+    }
+
+    pub fn struct_layout(&self, name: &str) -> Option<&Layout> {
+        self.structs.get(name)
+    }
+
+    pub fn union_layout(&self, name: &str) -> Option<&Layout> {
+        self.unions.get(name)
+    }
+
+    pub fn tagged_union_layout(&self, name: &str) -> Option<&Layout> {
+        self.tagged_unions.get(name)
+    }
+
+    pub fn class_layout(&self, name: &str) -> Option<&Layout> {
+        self.classes.get(name)
+    }
+
+    /// The value of an enum member declared so far.
+    pub fn enumerator(&self, name: &str) -> Option<i128> {
+        self.enumerators.get(name).copied()
+    }
+
+    /// The declared type of a variable or function in scope.
+    pub fn variable(&self, name: &str) -> Option<&DeclaredType> {
+        self.variables.get(name)
+    }
+
+    /// The type a typedef name stands for.
+    pub fn typedef(&self, name: &str) -> Option<&DeclaredType> {
+        self.typedefs.get(name).map(|typedef| &typedef.type_)
+    }
+
+    /// Numbers the members of an enum. A member without a value is one more than
+    /// the member before it, and the first one is zero. Every value must fit in an `int`.
+    pub fn enumerate(&mut self, enum_: &Enum) -> Result<(), String> {
+        let mut next = 0;
+        for member in &enum_.members {
+            let value = match &member.value {
+                Some(expression) => constant::integer(expression, self)?,
+                None => next,
+            };
+            if i32::try_from(value).is_err() {
+                return Err(format!("The value of {} does not fit in an int", member.name));
+            }
+            self.enumerators.insert(member.name.clone(), value);
+            next = value + 1;
+        }
+        Ok(())
+    }
+
+    pub fn static_assert(&self, assertion: &StaticAssert) -> Result<(), String> {
+        if constant::integer(&assertion.condition, self)? != 0 {
+            return Ok(());
+        }
+        match &assertion.message {
+            Some(message) => Err(format!("Static assertion failed: {}", message.to_text())),
+            None => Err("Static assertion failed".to_string()),
+        }
+    }
+
+    /// Runs a check in a new block scope, forgetting the names declared in it
+    /// afterwards.
+    fn scoped(&mut self, check: impl FnOnce(&mut Self) -> Result<(), String>) -> Result<(), String> {
+        self.structs.open();
+        self.unions.open();
+        self.tagged_unions.open();
+        self.classes.open();
+        self.typedefs.open();
+        self.enumerators.open();
+        self.variables.open();
+        let result = check(self);
+        self.structs.close();
+        self.unions.close();
+        self.tagged_unions.close();
+        self.classes.close();
+        self.typedefs.close();
+        self.enumerators.close();
+        self.variables.close();
+        result
+    }
+
+    /// Checks the declarations, static assertions and `case` labels in a block.
+    /// Call it through [`LayoutEngine::scoped`] to keep the block's names to itself.
+    fn check_block(&mut self, statements: &[Statement]) -> Result<(), String> {
+        for statement in statements {
+            match statement {
+                Statement::VariableList(variable_list) => self.declare(variable_list)?,
+                Statement::StaticAssert(assertion) => self.static_assert(assertion)?,
+                Statement::Expression(expression) | Statement::Return(expression) => self.check_expression(expression)?,
+                Statement::Switch(condition, cases) => {
+                    self.check_expression(condition)?;
+                    constant::case_values(cases, self)?;
+                    for case in cases {
+                        self.check_body(&case.body)?;
+                    }
+                },
+                Statement::If(condition, body, else_) => {
+                    self.check_expression(condition)?;
+                    self.check_body(body)?;
+                    if let Some(else_) = else_ {
+                        self.check_body(else_)?;
+                    }
+                },
+                Statement::While(condition, body) | Statement::DoWhile(condition, body) => {
+                    self.check_expression(condition)?;
+                    self.check_body(body)?;
+                },
+                Statement::Attributed(_, statement) => self.check_block(std::slice::from_ref(statement))?,
+                Statement::For(init, condition, step, body) => self.scoped(|scope| {
+                    match init.as_deref() {
+                        Some(VariableListOrStatement::VariableList(variable_list)) => scope.declare(variable_list)?,
+                        Some(VariableListOrStatement::Statement(statement)) => scope.check_block(std::slice::from_ref(statement))?,
+                        None => {},
+                    }
+                    for expression in condition.iter().chain(step.iter()) {
+                        scope.check_expression(expression)?;
+                    }
+                    scope.check_body(body)
+                })?,
+                Statement::Block(block) => {
+                    let CodeBlock::Code(list) = block.as_ref();
+                    self.scoped(|scope| scope.check_block(&list.statements))?;
+                },
+                _ => {},
+            }
+        }
+        Ok(())
+    }
+
+    fn check_body(&mut self, body: &BlockOrStatement) -> Result<(), String> {
+        match body {
+            BlockOrStatement::Block(CodeBlock::Code(list)) => self.scoped(|scope| scope.check_block(&list.statements)),
+            BlockOrStatement::Statement(statement) => self.scoped(|scope| scope.check_block(std::slice::from_ref(statement))),
+        }
+    }
+
+    /// Resolves the `_Generic` selections in an expression. A selection whose
+    /// controlling type is known must have a matching association.
+    fn check_expression(&mut self, expression: &Expression) -> Result<(), String> {
+        match expression {
+            Expression::Generic(control, associations) => {
+                self.check_expression(control)?;
+                match typecheck::type_of(control, self) {
+                    Ok(type_) => self.check_expression(typecheck::select_for_type(&type_, associations, self)?),
+                    Err(_) => Ok(()),
+                }
+            },
+            Expression::Unary(_, operand) | Expression::InitializerList(operand) |
+            Expression::TaggedInitializer(_, operand) | Expression::Expression(operand) |
+            Expression::Parentheses(operand) => self.check_expression(operand),
+            Expression::Sizeof(TypeOrExpression::Expression(operand)) => self.check_expression(operand),
+            Expression::Binary(_, left, right) => {
+                self.check_expression(left)?;
+                self.check_expression(right)
+            },
+            Expression::Ternary(condition, then, otherwise) => {
+                self.check_expression(condition)?;
+                self.check_expression(then)?;
+                self.check_expression(otherwise)
+            },
+            Expression::CallFunction(_, arguments) => match arguments {
+                Some(arguments) => self.check_expression(arguments),
+                None => Ok(()),
+            },
+            Expression::Call(callee, arguments) => {
+                self.check_expression(callee)?;
+                match arguments {
+                    Some(arguments) => self.check_expression(arguments),
+                    None => Ok(()),
+                }
+            },
+            Expression::StatementList(list) => self.scoped(|scope| scope.check_block(&list.statements)),
+            Expression::Blank | Expression::Identifier(_) | Expression::Literal(_) |
+            Expression::Sizeof(TypeOrExpression::Type(_)) | Expression::Alignof(_) | Expression::Offsetof(..) => Ok(()),
+        }
+    }
+
+    /// Records the typedefs and nested definitions of a declaration and checks its
+    /// alignment and `constexpr` initializers.
+    pub fn declare(&mut self, variable_list: &VariableList) -> Result<(), String> {
+        let anonymous = self.define(&variable_list.specifiers)?;
+        let variable_length = self.variables.in_block() &&
+            !matches!(variable_list.specifiers.storage, Some(StorageClass::Static | StorageClass::Extern));
+        for variable in &variable_list.variables {
+            self.array_sizes(&variable.type_, variable_length)
+                .map_err(|err| format!("Invalid size for array {}: {}", variable.name, err))?;
+            if !variable_list.specifiers.alignas.is_empty() {
+                let (_, align) = self.type_layout(&variable.type_, anonymous.as_ref())?;
+                self.alignment(&variable_list.specifiers, align)?;
+            }
+            self.attribute_alignment(&variable_list.specifiers.attributes)?;
+            self.attribute_alignment(&variable.attributes)?;
+            if let (true, Some(value)) = (variable_list.specifiers.constexpr, &variable.value) {
+                constant::check_constexpr(&variable.type_, value, self)
+                    .map_err(|err| format!("Invalid constexpr {}: {}", variable.name, err))?;
+            }
+            if let Some(VariableValue::Expression(value)) = &variable.value {
+                self.check_expression(value)?;
+            }
+            if variable_list.specifiers.storage != Some(StorageClass::Typedef) {
+                let type_ = self.completed_type(&variable.type_, variable.value.as_ref());
+                self.variables.insert(variable.name.clone(), type_);
+            }
+        }
+        if variable_list.specifiers.storage == Some(StorageClass::Typedef) {
+            for variable in &variable_list.variables {
+                self.typedefs.insert(variable.name.clone(), TypedefLayout {
+                    type_: variable.type_.clone(),
+                    anonymous: anonymous.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the arrays in a declarator have constant, non-negative sizes. A
+    /// size that reads a variable is allowed where `variable_length` arrays are.
+    fn array_sizes(&self, type_: &DeclaredType, variable_length: bool) -> Result<(), String> {
+        match type_ {
+            DeclaredType::Array(element, size) => {
+                if let VariableArray::Size(size) = size {
+                    if !(variable_length && self.reads_variable(size)) {
+                        self.constant(size)?;
+                    }
+                }
+                self.array_sizes(element, variable_length)
+            },
+            DeclaredType::Pointer(inner, _) => self.array_sizes(inner, variable_length),
+            DeclaredType::Base(_) | DeclaredType::Function(..) => Ok(()),
+        }
+    }
+
+    /// Whether an expression names anything other than an enum member.
+    fn reads_variable(&self, expression: &Expression) -> bool {
+        struct Names<'e>(&'e LayoutEngine, bool);
+
+        impl<'ast> Visitor<'ast> for Names<'_> {
+            fn visit_expression(&mut self, expression: &'ast Expression) {
+                match expression {
+                    Expression::Identifier(name) if self.0.enumerator(name).is_none() => self.1 = true,
+                    _ => walk_expression(self, expression),
+                }
+            }
+        }
+
+        let mut names = Names(self, false);
+        names.visit_expression(expression);
+        names.1
+    }
+
+    /// The type of an object, with the length of an array without a size taken from
+    /// its initializer the way the interpreter does.
+    fn completed_type(&self, type_: &DeclaredType, value: Option<&VariableValue>) -> DeclaredType {
+        if let (DeclaredType::Array(element, VariableArray::NoSize), Some(value)) = (type_, value) {
+            if let Some(length) = self.initializer_length(element, value) {
+                return DeclaredType::Array(element.clone(), VariableArray::Size(size_literal(length)));
+            }
+        }
+        type_.clone()
+    }
+
+    fn initializer_length(&self, element: &DeclaredType, value: &VariableValue) -> Option<u64> {
+        match value {
+            VariableValue::String(string) | VariableValue::Expression(Expression::Literal(Literal::String(string))) => {
+                Some(string.value.len() as u64 + 1)
+            },
+            VariableValue::Expression(Expression::InitializerList(items)) => {
+                let mut items = items.as_ref();
+                let mut count = 0;
+                let mut braced = true;
+                while let Expression::Binary(BinaryOperator::Comma, rest, item) = items {
+                    count += 1;
+                    braced &= matches!(item.as_ref(), Expression::InitializerList(_));
+                    items = rest;
+                }
+                if !matches!(items, Expression::Blank) {
+                    count += 1;
+                    braced &= matches!(items, Expression::InitializerList(_));
+                }
+                match braced {
+                    true => Some(count),
+                    false => Some(count.div_ceil(self.leaves(element)?.max(1))),
+                }
+            },
+            _ => None,
+        }
+    }
+
+    /// How many scalars initialize an object when its braces are left out. Unknown
+    /// for structs, unions and classes, whose member types are not kept.
+    fn leaves(&self, type_: &DeclaredType) -> Option<u64> {
+        match type_ {
+            DeclaredType::Array(element, VariableArray::Size(size)) => Some(self.constant(size).ok()? * self.leaves(element)?),
+            DeclaredType::Array(_, VariableArray::NoSize) => Some(0),
+            DeclaredType::Pointer(..) => Some(1),
+            DeclaredType::Base(base) => match &base.specifier {
+                TypeSpecifier::Struct(_) | TypeSpecifier::Union(_) | TypeSpecifier::Tagged(_) => None,
+                TypeSpecifier::Named(name) => self.leaves(&self.typedefs.get(name)?.type_),
+                _ => Some(1),
+            },
+            DeclaredType::Function(..) => None,
+        }
+    }
+
+    /// Lays out the type defined by some specifiers and returns its layout.
+    fn define(&mut self, specifiers: &DeclarationSpecifiers) -> Result<Option<Layout>, String> {
+        match specifiers.definition.as_deref() {
+            Some(Definition::Struct(struct_)) => Ok(Some(self.layout_struct(struct_)?)),
+            Some(Definition::Union(union_)) => Ok(Some(self.layout_union(union_)?)),
+            Some(Definition::TaggedUnion(tagged)) => Ok(Some(self.layout_tagged_union(tagged)?)),
+            Some(Definition::Enum(enum_)) => {
+                self.enumerate(enum_)?;
+                Ok(None)
+            },
+            None => Ok(None),
+        }
+    }
+
+    pub fn layout_struct(&mut self, struct_: &Struct) -> Result<Layout, String> {
+        let mut placer = Placer::new(false);
+        placer.packed = is_packed(&struct_.attributes);
+        self.place_members(&mut placer, &struct_.members)?;
+        placer.align = placer.align.max(self.attribute_alignment(&struct_.attributes)?);
+        let layout = placer.finish(None);
+        if let Some(name) = &struct_.name {
+            self.structs.insert(name.clone(), layout.clone());
+        }
+        Ok(layout)
+    }
+
+    pub fn layout_union(&mut self, union_: &Union) -> Result<Layout, String> {
+        let mut placer = Placer::new(true);
+        placer.packed = is_packed(&union_.attributes);
+        self.place_members(&mut placer, &union_.members)?;
+        placer.align = placer.align.max(self.attribute_alignment(&union_.attributes)?);
+        let layout = placer.finish(None);
+        if let Some(name) = &union_.name {
+            self.unions.insert(name.clone(), layout.clone());
+        }
+        Ok(layout)
+    }
+
+    /// Lays out a tagged union as an `int` tag followed by a union of the members'
+    /// payloads, each laid out as a struct. The tag is the field `tag` and each
+    /// payload is a field named after its member.
+    pub fn layout_tagged_union(&mut self, tagged: &TaggedUnion) -> Result<Layout, String> {
+        let mut payloads = Placer::new(true);
+        for member in &tagged.members {
+            if let Some(members) = &member.value {
+                let mut payload = Placer::new(false);
+                self.place_members(&mut payload, members)?;
+                let payload = payload.finish(None);
+                let offset = payloads.place(payload.size, payload.align);
+                payloads.fields.push(FieldLayout {name: member.name.clone(), offset, bits: None});
+            }
+        }
+
+        let mut placer = Placer::new(false);
+        let offset = placer.place(4, 4);
+        placer.fields.push(FieldLayout {name: "tag".to_string(), offset, bits: None});
+        placer.place_flattened(payloads.finish(None));
+        let layout = placer.finish(None);
+        self.tagged_unions.insert(tagged.name.clone(), layout.clone());
+        Ok(layout)
+    }
+
+    /// Lays out a class. A class with methods or operator overloads, or whose parent
+    /// has a vtable, starts with a vtable pointer; the parent's fields come next,
+    /// followed by the class's own.
+    pub fn layout_class(&mut self, class: &Class) -> Result<Layout, String> {
+        let parent = match &class.parent {
+            Some(parent) => match self.classes.get(parent) {
+                Some(layout) => Some(layout.clone()),
+                None => return Err(format!("Unknown parent class {}", parent)),
+            },
+            None => None,
+        };
+        let has_methods = class.members.iter()
+            .any(|member| matches!(member, ClassMember::Method(_) | ClassMember::OperatorOverload(_)));
+
+        let mut placer = Placer::new(false);
+        placer.packed = is_packed(&class.attributes);
+        let vtable = match parent {
+            Some(parent) => {
+                let mut vtable = parent.vtable;
+                if vtable.is_none() && has_methods {
+                    let pointer = self.model.pointer_size();
+                    vtable = Some(placer.place(pointer, pointer));
+                }
+                placer.place_flattened(parent);
+                vtable
+            },
+            None if has_methods => {
+                let pointer = self.model.pointer_size();
+                Some(placer.place(pointer, pointer))
+            },
+            None => None,
+        };
+
+        for member in &class.members {
+            if let ClassMember::Field(member) = member {
+                self.place_members(&mut placer, std::slice::from_ref(member))?;
+            }
+        }
+        placer.align = placer.align.max(self.attribute_alignment(&class.attributes)?);
+        let layout = placer.finish(vtable);
+        self.classes.insert(class.name.clone(), layout.clone());
+        Ok(layout)
+    }
+
+    fn place_members(&mut self, placer: &mut Placer, members: &[Member]) -> Result<(), String> {
+        for member in members {
+            match member {
+                Member::Variables(variable_list) => {
+                    let anonymous = self.define(&variable_list.specifiers)?;
+                    for variable in &variable_list.variables {
+                        let (size, align) = match &variable.type_ {
+                            DeclaredType::Array(element, VariableArray::NoSize) => {
+                                (0, self.type_layout(element, anonymous.as_ref())?.1)
+                            },
+                            type_ => self.type_layout(type_, anonymous.as_ref())?,
+                        };
+                        let packed = placer.packed || is_packed(&variable_list.specifiers.attributes) || is_packed(&variable.attributes);
+                        let align = self.alignment(&variable_list.specifiers, if packed { 1 } else { align })?
+                            .max(self.attribute_alignment(&variable_list.specifiers.attributes)?)
+                            .max(self.attribute_alignment(&variable.attributes)?);
+                        let offset = placer.place(size, align);
+                        placer.fields.push(FieldLayout {name: variable.name.clone(), offset, bits: None});
+                    }
+                },
+                Member::BitFields(bit_fields) => {
+                    let (size, unit_align) = self.type_layout(&DeclaredType::Base(bit_fields.specifiers.type_.clone()), None)?;
+                    let unit = unit_align * 8;
+                    let packed = placer.packed || is_packed(&bit_fields.specifiers.attributes);
+                    for field in &bit_fields.fields {
+                        if placer.union_ {
+                            placer.bit = 0;
+                        }
+                        let width = self.constant(&field.width)?;
+                        if width > size * 8 {
+                            return Err(format!("Bit-field {} is wider than its type", field.name.as_deref().unwrap_or("without a name")));
+                        }
+                        if width == 0 {
+                            if let Some(name) = &field.name {
+                                return Err(format!("Bit-field {} has zero width", name));
+                            }
+                            placer.bit = round_up(placer.bit, unit);
+                            continue;
+                        }
+                        if !packed && placer.bit / unit != (placer.bit + width - 1) / unit {
+                            placer.bit = round_up(placer.bit, unit);
+                        }
+                        if let Some(name) = &field.name {
+                            placer.fields.push(FieldLayout {
+                                name: name.clone(),
+                                offset: placer.bit / 8,
+                                bits: Some(BitRange {start: placer.bit % 8, width}),
+                            });
+                            if !packed {
+                                placer.align = placer.align.max(unit_align);
+                            }
+                        }
+                        placer.bit += width;
+                        placer.end = placer.end.max(placer.bit);
+                    }
+                },
+                Member::Struct(struct_) => {
+                    let layout = self.layout_struct(struct_)?;
+                    if struct_.name.is_none() {
+                        placer.place_flattened(layout);
+                    }
+                },
+                Member::Union(union_) => {
+                    let layout = self.layout_union(union_)?;
+                    if union_.name.is_none() {
+                        placer.place_flattened(layout);
+                    }
+                },
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies the `alignas` specifiers of a declaration to its natural alignment. An
+    /// alignment of zero is ignored; others must be powers of two no weaker than the
+    /// natural alignment.
+    fn alignment(&self, specifiers: &DeclarationSpecifiers, natural: u64) -> Result<u64, String> {
+        let mut align = natural;
+        for operand in &specifiers.alignas {
+            let requested = match operand {
+                TypeOrExpression::Type(type_) => self.align_of(type_)?,
+                TypeOrExpression::Expression(expression) => self.constant(expression)?,
+            };
+            if requested == 0 {
+                continue;
+            }
+            if !requested.is_power_of_two() {
+                return Err(format!("Alignment {} is not a power of two", requested));
+            }
+            if requested < natural {
+                return Err(format!("alignas({}) is weaker than the natural alignment {}", requested, natural));
+            }
+            align = align.max(requested);
+        }
+        Ok(align)
+    }
+
+    /// The strictest alignment GNU `aligned` attributes ask for, or 1 if there are
+    /// none. Without an argument `aligned` means the largest scalar alignment.
+    fn attribute_alignment(&self, attributes: &[Attribute]) -> Result<u64, String> {
+        let mut align = 1;
+        for attribute in attributes.iter().filter(|attribute| attribute.is("aligned")) {
+            let requested = match &attribute.arguments {
+                Some(tokens) => self.constant(&Parser::new(tokens).constant_expression()?)?,
+                None => self.model.long_double_size(),
+            };
+            if !requested.is_power_of_two() {
+                return Err(format!("Alignment {} is not a power of two", requested));
+            }
+            align = align.max(requested);
+        }
+        Ok(align)
+    }
+
+    pub fn size_of(&self, type_: &DeclaredType) -> Result<u64, String> {
+        Ok(self.type_layout(type_, None)?.0)
+    }
+
+    pub fn align_of(&self, type_: &DeclaredType) -> Result<u64, String> {
+        Ok(self.type_layout(type_, None)?.1)
+    }
+
+    /// Returns the offset of a member of a struct, union, tagged union or class type.
+    /// Members of anonymous structs and unions can be named directly.
+    pub fn offset_of(&self, type_: &DeclaredType, member: &str) -> Result<u64, String> {
+        let layout = match type_ {
+            DeclaredType::Base(base) => self.record_layout(&base.specifier)?,
+            _ => return Err(format!("{} has no members", print_node(&AstNode::DeclaredType(type_.clone())))),
+        };
+        match layout.field(member) {
+            Some(FieldLayout {bits: Some(_), ..}) => Err(format!("Cannot take the offset of bit-field {}", member)),
+            Some(field) => Ok(field.offset),
+            None => Err(format!("No member named {}", member)),
+        }
+    }
+
+    /// Finds the layout of a named struct, union, tagged union or class, looking
+    /// through typedefs.
+    fn record_layout(&self, specifier: &TypeSpecifier) -> Result<&Layout, String> {
+        let layout = match specifier {
+            TypeSpecifier::Struct(Some(name)) => self.structs.get(name),
+            TypeSpecifier::Union(Some(name)) => self.unions.get(name),
+            TypeSpecifier::Tagged(name) => self.tagged_unions.get(name),
+            TypeSpecifier::Named(name) => match self.typedefs.get(name) {
+                Some(TypedefLayout {anonymous: Some(layout), type_: DeclaredType::Base(_)}) => Some(layout),
+                Some(TypedefLayout {type_: DeclaredType::Base(base), ..}) => return self.record_layout(&base.specifier),
+                Some(_) => None,
+                None => self.classes.get(name),
+            },
+            _ => None,
+        };
+        layout.ok_or_else(|| format!("{} is not a complete struct, union or class", print_node(&AstNode::TypeSpecifier(specifier.clone()))))
+    }
+
+    /// Returns the size and alignment of a type. `anonymous` is the layout of the
+    /// anonymous struct or union the type's specifiers defined, if any.
+    pub fn type_layout(&self, type_: &DeclaredType, anonymous: Option<&Layout>) -> Result<(u64, u64), String> {
+        match type_ {
+            DeclaredType::Base(base) => self.base_layout(&base.specifier, anonymous),
+            DeclaredType::Pointer(..) => Ok((self.model.pointer_size(), self.model.pointer_size())),
+            DeclaredType::Array(element, VariableArray::Size(size)) => {
+                let (element_size, align) = self.type_layout(element, anonymous)?;
+                match element_size.checked_mul(self.constant(size)?) {
+                    Some(size) => Ok((size, align)),
+                    None => Err("Array is too large".to_string()),
+                }
+            },
+            DeclaredType::Array(_, VariableArray::NoSize) => {
+                Err("Cannot take the size of an array without a size".to_string())
+            },
+            DeclaredType::Function(..) => {
+                Err("Cannot take the size of a function".to_string())
+            },
+        }
+    }
+
+    fn base_layout(&self, specifier: &TypeSpecifier, anonymous: Option<&Layout>) -> Result<(u64, u64), String> {
+        let scalar = match specifier {
+            TypeSpecifier::Void => {
+                return Err("Cannot take the size of void".to_string());
+            },
+            TypeSpecifier::Bool | TypeSpecifier::Char | TypeSpecifier::SignedChar |
+            TypeSpecifier::UnsignedChar => 1,
+            TypeSpecifier::Short | TypeSpecifier::UnsignedShort => 2,
+            TypeSpecifier::Int | TypeSpecifier::UnsignedInt | TypeSpecifier::Float |
+            TypeSpecifier::FloatImaginary | TypeSpecifier::Enum(_) => 4,
+            TypeSpecifier::Long | TypeSpecifier::UnsignedLong => self.model.long_size(),
+            TypeSpecifier::LongLong | TypeSpecifier::UnsignedLongLong | TypeSpecifier::Double |
+            TypeSpecifier::DoubleImaginary => 8,
+            TypeSpecifier::LongDouble | TypeSpecifier::LongDoubleImaginary => self.model.long_double_size(),
+            TypeSpecifier::FloatComplex => return Ok((8, 4)),
+            TypeSpecifier::DoubleComplex => return Ok((16, 8)),
+            TypeSpecifier::LongDoubleComplex => {
+                let size = self.model.long_double_size();
+                return Ok((size * 2, size));
+            },
+            TypeSpecifier::Struct(None) | TypeSpecifier::Union(None) => {
+                return anonymous.map(|layout| (layout.size, layout.align))
+                    .ok_or_else(|| "Missing the definition of an anonymous type".to_string());
+            },
+            TypeSpecifier::Struct(Some(name)) => {
+                return self.structs.get(name).map(|layout| (layout.size, layout.align))
+                    .ok_or_else(|| format!("struct {} is incomplete", name));
+            },
+            TypeSpecifier::Union(Some(name)) => {
+                return self.unions.get(name).map(|layout| (layout.size, layout.align))
+                    .ok_or_else(|| format!("union {} is incomplete", name));
+            },
+            TypeSpecifier::Tagged(name) => {
+                return self.tagged_unions.get(name).map(|layout| (layout.size, layout.align))
+                    .ok_or_else(|| format!("tagged union {} is incomplete", name));
+            },
+            TypeSpecifier::Named(name) => {
+                if let Some(typedef) = self.typedefs.get(name) {
+                    return self.type_layout(&typedef.type_, typedef.anonymous.as_ref());
+                }
+                return self.classes.get(name).map(|layout| (layout.size, layout.align))
+                    .ok_or_else(|| format!("Unknown type {}", name));
+            },
+            TypeSpecifier::Generic => {
+                return Err("Cannot lay out a generic type".to_string());
+            },
+            TypeSpecifier::Typeof(operand) | TypeSpecifier::TypeofUnqual(operand) => {
+                return match operand.as_ref() {
+                    TypeOrExpression::Type(type_) => self.type_layout(type_, anonymous),
+                    TypeOrExpression::Expression(expression) => {
+                        let size = constant::size_of_expression(expression, self)?;
+                        Ok((size, size))
+                    },
+                };
+            },
+        };
+        Ok((scalar, scalar))
+    }
+
+    /// Evaluates an array size or bit-field width, which may not be negative.
+    fn constant(&self, expression: &Expression) -> Result<u64, String> {
+        let value = constant::integer(expression, self)?;
+        u64::try_from(value).map_err(|_| format!("Expected a size but found {}", value))
+    }
+}
+
+
+#[cfg(test)]
+mod layout_tests {
+    use super::*;
+    use crate::logos_lexer::lex;
+    use crate::parser::Parser;
+
+    fn layouts(input: &str) -> LayoutEngine {
+        layouts_for(input, DataModel::LP64)
+    }
+
+    fn layouts_for(input: &str, model: DataModel) -> LayoutEngine {
+        let header = Parser::new(&lex(input).unwrap()).parse().unwrap();
+        match LayoutEngine::from_header(&header, model) {
+            Ok(engine) => engine,
+            Err(err) => panic!("Failed to lay out {}: {}", input, err),
+        }
+    }
+
+    fn offsets(layout: &Layout) -> Vec<(&str, u64, Option<BitRange>)> {
+        layout.fields.iter().map(|field| (field.name.as_str(), field.offset, field.bits)).collect()
+    }
+
+    fn bits(start: u64, width: u64) -> Option<BitRange> {
+        Some(BitRange {start, width})
+    }
+
+    #[test]
+    fn test_bit_fields() {
+        let engine = layouts("struct flags { unsigned a : 3, b : 5; unsigned c : 30; char d; };\nstruct z { char a; int : 0; char b; };\nstruct u { char a; char : 4; char b : 2; long : 0; short c; };\n");
+
+        let flags = engine.struct_layout("flags").unwrap();
+        assert_eq!((flags.size, flags.align), (12, 4));
+        assert_eq!(offsets(flags), vec![("a", 0, bits(0, 3)), ("b", 0, bits(3, 5)), ("c", 4, bits(0, 30)), ("d", 8, None)]);
+
+        let z = engine.struct_layout("z").unwrap();
+        assert_eq!((z.size, z.align, z.field("b").unwrap().offset), (5, 1, 4));
+
+        let u = engine.struct_layout("u").unwrap();
+        assert_eq!((u.size, u.align), (10, 2));
+        assert_eq!(offsets(u), vec![("a", 0, None), ("b", 1, bits(4, 2)), ("c", 8, None)]);
+    }
+
+    #[test]
+    fn test_anonymous_and_nested_members() {
+        let engine = layouts("union pair { int a, b; char c[6]; };\nstruct anon { int tag; union { float f; double d; }; struct { char x, y; }; };\nstruct nested { struct inner { char c; long l; } in; char after; };\ntypedef struct { short n; int data[]; } fam;\n");
+
+        let pair = engine.union_layout("pair").unwrap();
+        assert_eq!((pair.size, pair.align), (8, 4));
+        assert_eq!(offsets(pair), vec![("a", 0, None), ("b", 0, None), ("c", 0, None)]);
+
+        let anon = engine.struct_layout("anon").unwrap();
+        assert_eq!((anon.size, anon.align), (24, 8));
+        assert_eq!(offsets(anon), vec![("tag", 0, None), ("f", 8, None), ("d", 8, None), ("x", 16, None), ("y", 17, None)]);
+
+        let nested = engine.struct_layout("nested").unwrap();
+        assert_eq!((nested.size, nested.align, nested.field("after").unwrap().offset), (24, 8, 16));
+        assert_eq!(engine.struct_layout("inner").map(|inner| inner.size), Some(16));
+
+        let fam = engine.type_layout(&DeclaredType::Base(Type::new(TypeSpecifier::Named("fam".to_string()))), None);
+        assert_eq!(fam, Ok((4, 4)));
+    }
+
+    #[test]
+    fn test_data_models() {
+        let input = "struct mix { char c; long l; long double d; void *p; short s; };\n";
+        let sizes = |model| {
+            let engine = layouts_for(input, model);
+            let mix = engine.struct_layout("mix").unwrap();
+            (mix.size, mix.align, offsets(mix).into_iter().map(|(_, offset, _)| offset).collect::<Vec<_>>())
+        };
+        assert_eq!(sizes(DataModel::LP64), (48, 16, vec![0, 8, 16, 32, 40]));
+        assert_eq!(sizes(DataModel::LLP64), (32, 8, vec![0, 4, 8, 16, 24]));
+        assert_eq!(sizes(DataModel::ILP32), (24, 8, vec![0, 4, 8, 16, 20]));
+    }
+
+    #[test]
+    fn test_tagged_unions_and_classes() {
+        let engine = layouts("tagged shape { circle {double radius;}, square {int side;}, empty };\nclass Base { int id; int area(); };\nclass Derived : Base { char flag; };\nclass Plain { char c; };\nclass Counted : Plain { int n; int count(); };\n");
+
+        let shape = engine.tagged_union_layout("shape").unwrap();
+        assert_eq!((shape.size, shape.align), (16, 8));
+        assert_eq!(offsets(shape), vec![("tag", 0, None), ("circle", 8, None), ("square", 8, None)]);
+
+        let base = engine.class_layout("Base").unwrap();
+        assert_eq!((base.size, base.vtable, base.field("id").unwrap().offset), (16, Some(0), 8));
+
+        let derived = engine.class_layout("Derived").unwrap();
+        assert_eq!((derived.size, derived.vtable), (24, Some(0)));
+        assert_eq!(offsets(derived), vec![("id", 8, None), ("flag", 16, None)]);
+
+        let counted = engine.class_layout("Counted").unwrap();
+        assert_eq!((counted.size, counted.vtable), (16, Some(0)));
+        assert_eq!(offsets(counted), vec![("c", 8, None), ("n", 12, None)]);
+        assert_eq!(engine.class_layout("Plain").map(|plain| plain.vtable), Some(None));
+    }
+
+    #[test]
+    fn test_sizeof_alignof_and_offsetof() {
+        let engine = layouts("struct anon { int tag; union { float f; double d; }; unsigned bits : 3; };\ntypedef struct anon anon_t;\nstruct sized { char data[sizeof(struct anon)]; short pad[alignof(struct anon)]; };\nclass Widget { int w; };\n\
+                             typedef struct { long a; int x, y; } B;\nstatic_assert(offsetof(B, y) == 12);\nstatic_assert(offsetof(struct anon, f) == 8 + offsetof(Widget, w));\n");
+        let named = |name: &str| DeclaredType::Base(Type::new(TypeSpecifier::Named(name.to_string())));
+
+        assert_eq!(engine.size_of(&named("anon_t")), Ok(24));
+        assert_eq!(engine.align_of(&named("anon_t")), Ok(8));
+        assert_eq!(engine.offset_of(&named("anon_t"), "d"), Ok(8));
+        assert_eq!(engine.struct_layout("sized").map(|sized| sized.size), Some(40));
+        assert_eq!(engine.offset_of(&named("Widget"), "w"), Ok(0));
+        assert!(engine.offset_of(&named("anon_t"), "bits").is_err());
+        assert!(engine.offset_of(&named("anon_t"), "missing").is_err());
+        assert_eq!(engine.size_of(&DeclaredType::Pointer(Box::new(named("Widget")), TypeQualifiers::default())), Ok(8));
+        assert!(engine.size_of(&DeclaredType::Base(Type::new(TypeSpecifier::Void))).is_err());
+    }
+
+    #[test]
+    fn test_static_assertions_alignas_and_constexpr() {
+        let engine = layouts("struct s { char c; alignas(16) int x; };\nstatic_assert(sizeof(struct s) == 32, \"aligned member\");\nalignas(struct s) char buffer[8];\nconstexpr unsigned char small = 255;\nconstexpr float exact = 0.5;\nconstexpr bool flag = 1;\nconstexpr long *none = nullptr;\nstruct t { typeof(1 + 1L) wide; typeof_unqual(const short) s; char c; };\nint f(int x) { static_assert(alignof(struct s) == 16); switch (x) { case 1: return 2; case 2: { enum { LOCAL = 3 }; static_assert(LOCAL == 3); } } return x; }\ntypedef long n;\nint g(void) { { typedef char n; struct s { char c; }; static_assert(sizeof(n) == 1 && sizeof(struct s) == 1); } static_assert(sizeof(n) == 8); return 0; }\n_Static_assert(_Alignof(long) == 8, \"C11 spelling\");\n_Thread_local _Alignas(16) int counter;\n_Noreturn void die(void);\n");
+        let s = engine.struct_layout("s").unwrap();
+        assert_eq!((s.size, s.align, s.field("x").unwrap().offset), (32, 16, 16));
+        let t = engine.struct_layout("t").unwrap();
+        assert_eq!((t.size, offsets(t)), (16, vec![("wide", 0, None), ("s", 8, None), ("c", 10, None)]));
+        assert_eq!(engine.enumerator("LOCAL"), None);
+        assert_eq!(engine.size_of(engine.typedef("n").unwrap()), Ok(8));
+
+        for input in ["static_assert(sizeof(int) == 8, \"int is 64 bits\");", "static_assert(x);", "alignas(3) int x;", "alignas(1) int x;",
+                      "struct s { alignas(2) int x; };", "constexpr unsigned char c = 256;", "constexpr float f = 0.1;",
+                      "constexpr int i = 1.5;", "constexpr int *p = 1;", "constexpr int n = nullptr;",
+                      "int f(int x) { static_assert(0); return x; }", "int f(int x) { while (x) { static_assert(0); } return x; }",
+                      "int f(int x) { switch (x) { case 1: case 1: return 0; } return x; }"] {
+            let header = Parser::new(&lex(input).unwrap()).parse().unwrap();
+            assert!(LayoutEngine::from_header(&header, DataModel::LP64).is_err(), "Accepted invalid declaration: {}", input);
+        }
+        let header = Parser::new(&lex("static_assert(0, \"broken\");").unwrap()).parse().unwrap();
+        assert_eq!(LayoutEngine::from_header(&header, DataModel::LP64).err().map(String::from), Some("Static assertion failed: broken".to_string()));
+        let header = Parser::new(&lex("int a;\nstruct s { alignas(3) int x; };").unwrap()).parse().unwrap();
+        assert_eq!(LayoutEngine::from_header(&header, DataModel::LP64).err().map(|err| (err.statement, err.name)), Some((1, Some("s".to_string()))));
+    }
+
+    #[test]
+    fn test_array_sizes() {
+        layouts("enum { N = 4 };\nint a[N * 2];\ntypedef char t[sizeof(a)];\nint f(int n) { int v[n][N]; int (*p)[n + 1]; typedef char row[n]; return 0; }\n");
+
+        for input in ["int a[1/0];", "int a[-1];", "int x; int a[x];", "typedef int t[1/0];", "int *p[2][-1];",
+                      "int f(void) { int a[1/0]; return 0; }", "int f(int n) { static int a[n]; return 0; }"] {
+            let header = Parser::new(&lex(input).unwrap()).parse().unwrap();
+            assert!(LayoutEngine::from_header(&header, DataModel::LP64).is_err(), "Accepted invalid array size: {}", input);
+        }
+        let engine = layouts("int a[] = {1, 2, 3};\nstatic_assert(sizeof a / sizeof a[0] == 3);\nchar s[] = \"hello\";\nstatic_assert(sizeof(s) == 6);\nint m[][2] = {1, 2, 3};\nstruct p { int x, y; } ps[] = {{1, 2}, {3, 4}};\nint f(void) { short b[] = {1, 2}; static_assert(sizeof b == 4); return 0; }\n");
+        assert_eq!(engine.variable("m").map(|type_| engine.size_of(type_)), Some(Ok(16)));
+        assert_eq!(engine.variable("ps").map(|type_| engine.size_of(type_)), Some(Ok(16)));
+        assert_eq!(engine.variable("b"), None);
+
+        let header = Parser::new(&lex("int a[-1];").unwrap()).parse().unwrap();
+        assert_eq!(LayoutEngine::from_header(&header, DataModel::LP64).err().map(String::from),
+                   Some("Invalid size for array a: Expected a size but found -1".to_string()));
+    }
+
+    #[test]
+    fn test_generic_selections() {
+        let engine = layouts("typedef int number;\nnumber n;\nchar c;\nconst char *text;\nfloat x;\nint a[_Generic(1.0, double: 4, default: 1)];\nstatic_assert(sizeof(a) == 16, \"double literal\");\nstatic_assert(_Generic(n, int: 1, default: 0), \"typedef\");\nstatic_assert(_Generic(c, char: 1, int: 2) == 1, \"char\");\nstatic_assert(_Generic('c', char: 1, int: 2) == 2, \"character constant\");\nstatic_assert(_Generic(text, const char *: 1, char *: 2) == 1, \"pointer\");\nstatic_assert(_Generic(a, int *: 1, default: 0), \"array\");\nstatic_assert(_Generic(1L, int: 1, default: 2) == 2, \"default\");\nstatic_assert(sizeof(_Generic(x, float: 1.0f, default: 1.0)) == 4, \"float\");\ndouble sin(double x);\nfloat sinf(float x);\nfloat f(float y) { float r = _Generic((y), float: sinf, default: sin)(y); return r; }\n");
+        assert_eq!(engine.variable("a").map(|type_| engine.size_of(type_)), Some(Ok(16)));
+
+        for input in ["float x; int y = _Generic(x, int: 1, double: 2);", "int g(int x) { return _Generic(x, double: 1); }",
+                      "int g(int x) { if (_Generic(x, float: 1)) { return 1; } return 0; }", "static_assert(_Generic(1, long: 1));",
+                      "int h(int x) { for (int i = 0; _Generic(i, long: 1); i++) { } return x; }"] {
+            let header = Parser::new(&lex(input).unwrap()).parse().unwrap();
+            assert!(LayoutEngine::from_header(&header, DataModel::LP64).is_err(), "Accepted unmatched selection: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_packed_and_aligned_attributes() {
+        let engine = layouts("struct __attribute__((packed, aligned(8))) a { char c; int x; };\nstruct b { char c; int x __attribute__((packed)); short s; };\nstruct __attribute__((packed)) c { char c; unsigned x : 12; unsigned y : 7; int z; };\nstruct d { char c; } __attribute__((aligned));\nstruct __attribute__((packed)) e { char c; int x __attribute__((aligned(4))); };\nstruct [[gnu::packed]] f { char c; long l; };\nunion __attribute__((aligned(sizeof(long) * 2))) g { char c; };\n");
+        let a = engine.struct_layout("a").unwrap();
+        assert_eq!((a.size, a.align, offsets(a)), (8, 8, vec![("c", 0, None), ("x", 1, None)]));
+        let b = engine.struct_layout("b").unwrap();
+        assert_eq!((b.size, b.align, offsets(b)), (8, 2, vec![("c", 0, None), ("x", 1, None), ("s", 6, None)]));
+        let c = engine.struct_layout("c").unwrap();
+        assert_eq!((c.size, c.align, offsets(c)), (8, 1, vec![("c", 0, None), ("x", 1, bits(0, 12)), ("y", 2, bits(4, 7)), ("z", 4, None)]));
+        let d = engine.struct_layout("d").unwrap();
+        assert_eq!((d.size, d.align), (16, 16));
+        let e = engine.struct_layout("e").unwrap();
+        assert_eq!((e.size, e.align, e.field("x").unwrap().offset), (8, 4, 4));
+        let f = engine.struct_layout("f").unwrap();
+        assert_eq!((f.size, f.align, f.field("l").unwrap().offset), (9, 1, 1));
+        let g = engine.union_layout("g").unwrap();
+        assert_eq!((g.size, g.align), (16, 16));
+
+        for input in ["struct s { int x; } __attribute__((aligned(3)));", "struct s { int x __attribute__((aligned(y))); };",
+                      "int x __attribute__((aligned(6)));"] {
+            let header = Parser::new(&lex(input).unwrap()).parse().unwrap();
+            assert!(LayoutEngine::from_header(&header, DataModel::LP64).is_err(), "Accepted invalid alignment: {}", input);
+        }
+    }
+}
@@ -0,0 +1,18 @@
+pub mod ast;
+pub mod attributes;
+pub mod constant;
+pub mod dot;
+pub mod format;
+pub mod incremental;
+pub mod interpreter;
+pub mod layout;
+pub mod literal;
+pub mod logos_lexer;
+pub mod parser;
+pub mod printer;
+mod scoped;
+#[cfg(feature = "serde")]
+pub mod sexp;
+pub mod symbols;
+pub mod typecheck;
+pub mod visit;
@@ -0,0 +1,733 @@
+use std::fmt;
+
+use crate::logos_lexer::LexerError;
+
+
+/// The encoding prefix of a character constant or string literal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Encoding {
+    Char,//no prefix
+    Utf8,//u8
+    Utf16,//u
+    Utf32,//U
+    Wide,//L
+}
+
+impl Encoding {
+    pub fn from_prefix(prefix: &str) -> Option<Encoding> {
+        match prefix {
+            "" => Some(Encoding::Char),
+            "u8" => Some(Encoding::Utf8),
+            "u" => Some(Encoding::Utf16),
+            "U" => Some(Encoding::Utf32),
+            "L" => Some(Encoding::Wide),
+            _ => None,
+        }
+    }
+
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            Encoding::Char => "",
+            Encoding::Utf8 => "u8",
+            Encoding::Utf16 => "u",
+            Encoding::Utf32 => "U",
+            Encoding::Wide => "L",
+        }
+    }
+
+    /// Width in bits of one code unit of this encoding (wchar_t is 32 bits).
+    pub fn unit_bits(&self) -> u32 {
+        match self {
+            Encoding::Char | Encoding::Utf8 => 8,
+            Encoding::Utf16 => 16,
+            Encoding::Utf32 | Encoding::Wide => 32,
+        }
+    }
+
+    fn max_unit(&self) -> u32 {
+        match self.unit_bits() {
+            32 => u32::MAX,
+            bits => (1 << bits) - 1,
+        }
+    }
+
+    fn encode(&self, c: char, units: &mut Vec<u32>) {
+        match self {
+            Encoding::Char | Encoding::Utf8 => {
+                let mut buffer = [0; 4];
+                units.extend(c.encode_utf8(&mut buffer).bytes().map(u32::from));
+            },
+            Encoding::Utf16 => {
+                let mut buffer = [0; 2];
+                units.extend(c.encode_utf16(&mut buffer).iter().map(|unit| u32::from(*unit)));
+            },
+            Encoding::Utf32 | Encoding::Wide => {
+                units.push(c as u32);
+            },
+        }
+    }
+}
+
+/// A string literal, possibly made of several adjacent pieces.
+/// `value` holds the code units of the literal without the terminating null.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StringLiteral {
+    pub encoding: Encoding,
+    pub raw: String,
+    pub value: Vec<u32>,
+}
+
+/// A character constant. `value` is the value of the constant as computed by gcc
+/// for an LP64 target, so plain `char` is signed and multi-char constants are packed.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CharLiteral {
+    pub encoding: Encoding,
+    pub raw: String,
+    pub value: i64,
+}
+
+impl StringLiteral {
+    /// The decoded value as text, replacing code units that are not valid characters.
+    pub fn to_text(&self) -> String {
+        match self.encoding {
+            Encoding::Char | Encoding::Utf8 => {
+                let bytes = self.value.iter().map(|unit| *unit as u8).collect::<Vec<u8>>();
+                String::from_utf8_lossy(&bytes).into_owned()
+            },
+            Encoding::Utf16 => {
+                let units = self.value.iter().map(|unit| *unit as u16).collect::<Vec<u16>>();
+                String::from_utf16_lossy(&units)
+            },
+            Encoding::Utf32 | Encoding::Wide => {
+                self.value.iter().map(|unit| char::from_u32(*unit).unwrap_or(char::REPLACEMENT_CHARACTER)).collect()
+            },
+        }
+    }
+}
+
+/// Splits a literal into its encoding and the text between the quotes.
+fn split_literal(raw: &str, quote: char) -> Result<(Encoding, &str), LexerError> {
+    let start = raw.find(quote).ok_or_else(|| LexerError::BadLiteral(raw.to_string()))?;
+    let encoding = Encoding::from_prefix(&raw[..start]).ok_or_else(|| LexerError::BadLiteral(raw.to_string()))?;
+    let body = &raw[start + 1..];
+    match body.strip_suffix(quote) {
+        Some(body) => Ok((encoding, body)),
+        None => Err(LexerError::BadLiteral(raw.to_string())),
+    }
+}
+
+fn universal_character(digits: &str, raw: &str) -> Result<char, LexerError> {
+    let code_point = u32::from_str_radix(digits, 16)
+        .map_err(|_| LexerError::BadEscape(format!("incomplete universal character name in {}", raw)))?;
+    char::from_u32(code_point)
+        .ok_or_else(|| LexerError::BadEscape(format!("\\u{:x} is not a valid universal character in {}", code_point, raw)))
+}
+
+/// Decodes the body of a literal into code units of the given encoding.
+fn decode(body: &str, encoding: Encoding, raw: &str) -> Result<Vec<u32>, LexerError> {
+    let mut units = Vec::new();
+    let mut chars = body.char_indices().peekable();
+
+    while let Some((_, c)) = chars.next() {
+        if c != '\\' {
+            encoding.encode(c, &mut units);
+            continue;
+        }
+        let (index, escape) = chars.next().ok_or_else(|| LexerError::BadEscape(format!("trailing backslash in {}", raw)))?;
+        match escape {
+            '\'' | '"' | '?' | '\\' => encoding.encode(escape, &mut units),
+            'a' => units.push(0x07),
+            'b' => units.push(0x08),
+            'f' => units.push(0x0c),
+            'n' => units.push(0x0a),
+            'r' => units.push(0x0d),
+            't' => units.push(0x09),
+            'v' => units.push(0x0b),
+            '\n' => {},
+            '\r' => {
+                if chars.next_if(|(_, c)| *c == '\n').is_none() {
+                    return Err(LexerError::BadEscape(format!("unknown escape sequence \\r in {}", raw)));
+                }
+            },
+            '0'..='7' => {
+                let mut value = escape.to_digit(8).unwrap_or(0);
+                for _ in 0..2 {
+                    match chars.next_if(|(_, c)| c.is_digit(8)) {
+                        Some((_, digit)) => value = value * 8 + digit.to_digit(8).unwrap_or(0),
+                        None => break,
+                    }
+                }
+                if value > encoding.max_unit() {
+                    return Err(LexerError::BadEscape(format!("octal escape sequence out of range in {}", raw)));
+                }
+                units.push(value);
+            },
+            'x' => {
+                let mut value: u64 = 0;
+                let mut digits = 0;
+                while let Some((_, digit)) = chars.next_if(|(_, c)| c.is_ascii_hexdigit()) {
+                    value = value * 16 + u64::from(digit.to_digit(16).unwrap_or(0));
+                    digits += 1;
+                    if value > u64::from(encoding.max_unit()) {
+                        return Err(LexerError::BadEscape(format!("hex escape sequence out of range in {}", raw)));
+                    }
+                }
+                if digits == 0 {
+                    return Err(LexerError::BadEscape(format!("\\x used with no following hex digits in {}", raw)));
+                }
+                units.push(value as u32);
+            },
+            'u' | 'U' => {
+                let length = if escape == 'u' { 4 } else { 8 };
+                let start = index + 1;
+                let digits = body.get(start..start + length).unwrap_or("");
+                if digits.len() != length || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return Err(LexerError::BadEscape(format!("incomplete universal character name in {}", raw)));
+                }
+                encoding.encode(universal_character(digits, raw)?, &mut units);
+                for _ in 0..length {
+                    chars.next();
+                }
+            },
+            _ => {
+                return Err(LexerError::BadEscape(format!("unknown escape sequence \\{} in {}", escape, raw)));
+            },
+        }
+    }
+
+    Ok(units)
+}
+
+/// Decodes a single string literal token such as `u8"café\n"`.
+pub fn string_literal(raw: &str) -> Result<StringLiteral, LexerError> {
+    concat_string_literals(&[raw])
+}
+
+/// Concatenates adjacent string literal tokens. Pieces without a prefix take the prefix
+/// of the others, and pieces with different prefixes cannot be joined.
+pub fn concat_string_literals(pieces: &[&str]) -> Result<StringLiteral, LexerError> {
+    let mut encoding = Encoding::Char;
+    let mut bodies = Vec::new();
+
+    for piece in pieces {
+        let (piece_encoding, body) = split_literal(piece, '"')?;
+        if piece_encoding != Encoding::Char {
+            if encoding != Encoding::Char && encoding != piece_encoding {
+                return Err(LexerError::MismatchedStringPrefix(pieces.join(" ")));
+            }
+            encoding = piece_encoding;
+        }
+        bodies.push((body, piece));
+    }
+
+    let mut value = Vec::new();
+    for (body, piece) in bodies {
+        value.append(&mut decode(body, encoding, piece)?);
+    }
+
+    Ok(StringLiteral {
+        encoding,
+        raw: pieces.join(" "),
+        value,
+    })
+}
+
+/// Decodes a character constant such as `'\n'`, `L'x'` or the multi-char constant `'ab'`.
+pub fn char_literal(raw: &str) -> Result<CharLiteral, LexerError> {
+    let (encoding, body) = split_literal(raw, '\'')?;
+    let units = decode(body, encoding, raw)?;
+
+    let value = match (encoding, units.as_slice()) {
+        (_, []) => {
+            return Err(LexerError::BadLiteral(format!("empty character constant {}", raw)));
+        },
+        (Encoding::Char, [unit]) => i64::from(*unit as u8 as i8),
+        (Encoding::Char, units) => {
+            //gcc packs multi-char constants big-endian into an int
+            let packed = units.iter().fold(0u32, |acc, unit| (acc << 8) | (unit & 0xff));
+            i64::from(packed as i32)
+        },
+        (Encoding::Wide, units) => {
+            //gcc keeps only the last character of a multi-char wide constant
+            i64::from(units[units.len() - 1] as i32)
+        },
+        (_, [unit]) => i64::from(*unit),
+        (_, _) => {
+            return Err(LexerError::BadLiteral(format!("character constant {} does not fit in a single code unit", raw)));
+        },
+    };
+
+    Ok(CharLiteral {
+        encoding,
+        raw: raw.to_string(),
+        value,
+    })
+}
+
+
+/// The C type of a numeric literal, assuming the LP64 data model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NumberType {
+    Int,
+    UnsignedInt,
+    Long,
+    UnsignedLong,
+    LongLong,
+    UnsignedLongLong,
+    BitInt(u32),
+    UnsignedBitInt(u32),
+    Float,
+    Double,
+    LongDouble,
+}
+
+impl NumberType {
+    pub fn is_integer(&self) -> bool {
+        !matches!(self, NumberType::Float | NumberType::Double | NumberType::LongDouble)
+    }
+
+    pub fn is_unsigned(&self) -> bool {
+        matches!(self, NumberType::UnsignedInt | NumberType::UnsignedLong |
+                 NumberType::UnsignedLongLong | NumberType::UnsignedBitInt(_))
+    }
+
+    /// Largest value an integer type can hold.
+    fn max_value(&self) -> u128 {
+        match self {
+            NumberType::Int => i32::MAX as u128,
+            NumberType::UnsignedInt => u32::MAX as u128,
+            NumberType::Long | NumberType::LongLong => i64::MAX as u128,
+            NumberType::UnsignedLong | NumberType::UnsignedLongLong => u64::MAX as u128,
+            NumberType::BitInt(width) if *width > 128 => u128::MAX,
+            NumberType::BitInt(width) => (1u128 << (width - 1)) - 1,
+            NumberType::UnsignedBitInt(width) if *width >= 128 => u128::MAX,
+            NumberType::UnsignedBitInt(width) => (1u128 << width) - 1,
+            _ => 0,
+        }
+    }
+}
+
+impl fmt::Display for NumberType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NumberType::Int => write!(f, "int"),
+            NumberType::UnsignedInt => write!(f, "unsigned int"),
+            NumberType::Long => write!(f, "long"),
+            NumberType::UnsignedLong => write!(f, "unsigned long"),
+            NumberType::LongLong => write!(f, "long long"),
+            NumberType::UnsignedLongLong => write!(f, "unsigned long long"),
+            NumberType::BitInt(width) => write!(f, "_BitInt({})", width),
+            NumberType::UnsignedBitInt(width) => write!(f, "unsigned _BitInt({})", width),
+            NumberType::Float => write!(f, "float"),
+            NumberType::Double => write!(f, "double"),
+            NumberType::LongDouble => write!(f, "long double"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NumberValue {
+    Integer(u128),
+    Floating(f64),
+}
+
+/// A numeric constant with its spelling, value and type.
+/// Long double values are kept as the nearest `f64`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NumberLiteral {
+    pub raw: String,
+    pub value: NumberValue,
+    pub c_type: NumberType,
+}
+
+impl NumberLiteral {
+    pub fn is_integer(&self) -> bool {
+        self.c_type.is_integer()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Base {
+    Binary,
+    Octal,
+    Decimal,
+    Hexadecimal,
+}
+
+impl Base {
+    fn radix(&self) -> u32 {
+        match self {
+            Base::Binary => 2,
+            Base::Octal => 8,
+            Base::Decimal => 10,
+            Base::Hexadecimal => 16,
+        }
+    }
+}
+
+/// Removes C23 digit separators, checking that each one sits between two digits.
+fn strip_separators(raw: &str) -> Result<String, LexerError> {
+    let chars = raw.chars().collect::<Vec<char>>();
+    let hex = raw.starts_with("0x") || raw.starts_with("0X");
+    let is_digit = |c: Option<&char>| c.is_some_and(|c| if hex { c.is_ascii_hexdigit() } else { c.is_ascii_digit() });
+    let mut output = String::with_capacity(raw.len());
+
+    for (index, c) in chars.iter().enumerate() {
+        if *c != '\'' {
+            output.push(*c);
+        }
+        //the x of a 0x prefix is not a digit, so a separator cannot follow the prefix
+        else if !is_digit(index.checked_sub(1).and_then(|index| chars.get(index))) || !is_digit(chars.get(index + 1)) {
+            return Err(LexerError::BadNumber(format!("misplaced digit separator in {}", raw)));
+        }
+    }
+
+    Ok(output)
+}
+
+fn parse_digits(digits: &str, base: Base, raw: &str) -> Result<u128, LexerError> {
+    let mut value: u128 = 0;
+    for c in digits.chars() {
+        let digit = c.to_digit(base.radix())
+            .ok_or_else(|| LexerError::BadNumber(format!("invalid digit '{}' in {}", c, raw)))?;
+        value = value.checked_mul(u128::from(base.radix()))
+            .and_then(|value| value.checked_add(u128::from(digit)))
+            .ok_or_else(|| LexerError::NumberOutOfRange(format!("integer constant {} is too large", raw)))?;
+    }
+    Ok(value)
+}
+
+fn integer_type(value: u128, base: Base, suffix: &str, raw: &str) -> Result<NumberType, LexerError> {
+    use NumberType::*;
+
+    let (unsigned, size) = match suffix.strip_prefix(['u', 'U']).or_else(|| suffix.strip_suffix(['u', 'U'])) {
+        Some(size) => (true, size),
+        None => (false, suffix),
+    };
+    if !matches!(size, "" | "l" | "L" | "ll" | "LL" | "wb" | "WB") {
+        return Err(LexerError::BadNumber(format!("invalid suffix \"{}\" on integer constant {}", suffix, raw)));
+    }
+    let size = size.to_ascii_lowercase();
+
+    let bits = 128 - value.leading_zeros();
+    let candidates: &[NumberType] = match (size.as_str(), unsigned, base) {
+        ("wb", false, _) => return Ok(BitInt((bits + 1).max(2))),
+        ("wb", true, _) => return Ok(UnsignedBitInt(bits.max(1))),
+        ("", false, Base::Decimal) => &[Int, Long, LongLong],
+        ("", false, _) => &[Int, UnsignedInt, Long, UnsignedLong, LongLong, UnsignedLongLong],
+        ("", true, _) => &[UnsignedInt, UnsignedLong, UnsignedLongLong],
+        ("l", false, Base::Decimal) => &[Long, LongLong],
+        ("l", false, _) => &[Long, UnsignedLong, LongLong, UnsignedLongLong],
+        ("l", true, _) => &[UnsignedLong, UnsignedLongLong],
+        ("ll", false, Base::Decimal) => &[LongLong],
+        ("ll", false, _) => &[LongLong, UnsignedLongLong],
+        _ => &[UnsignedLongLong],
+    };
+    candidates.iter()
+        .find(|candidate| value <= candidate.max_value())
+        .copied()
+        .ok_or_else(|| LexerError::NumberOutOfRange(format!("integer constant {} is too large for its type", raw)))
+}
+
+fn hex_float_value(mantissa: &str, exponent: i32, raw: &str) -> Result<f64, LexerError> {
+    let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    if whole.is_empty() && fraction.is_empty() {
+        return Err(LexerError::BadNumber(format!("no digits in hexadecimal floating constant {}", raw)));
+    }
+    let mut value = 0f64;
+    for c in whole.chars().chain(fraction.chars()) {
+        let digit = c.to_digit(16)
+            .ok_or_else(|| LexerError::BadNumber(format!("invalid digit '{}' in {}", c, raw)))?;
+        value = value * 16.0 + f64::from(digit);
+    }
+    let scale = i64::from(exponent) - 4 * fraction.len() as i64;
+    Ok(value * 2f64.powi(scale.clamp(i64::from(i32::MIN), i64::from(i32::MAX)) as i32))
+}
+
+fn floating_type(suffix: &str, raw: &str) -> Result<NumberType, LexerError> {
+    match suffix {
+        "" => Ok(NumberType::Double),
+        "f" | "F" => Ok(NumberType::Float),
+        "l" | "L" => Ok(NumberType::LongDouble),
+        _ => Err(LexerError::BadNumber(format!("invalid suffix \"{}\" on floating constant {}", suffix, raw))),
+    }
+}
+
+/// Splits `digits` at the first character that cannot be part of the number, returning the
+/// mantissa, the optional exponent (without its marker) and the suffix.
+fn split_number(digits: &str, hex: bool) -> (&str, Option<&str>, &str) {
+    let is_mantissa = |c: char| if hex { c.is_ascii_hexdigit() || c == '.' } else { c.is_ascii_digit() || c == '.' };
+    let is_exponent = |c: char| if hex { c == 'p' || c == 'P' } else { c == 'e' || c == 'E' };
+
+    let mantissa_end = digits.find(|c: char| !is_mantissa(c)).unwrap_or(digits.len());
+    let (mantissa, rest) = digits.split_at(mantissa_end);
+    match rest.chars().next() {
+        Some(c) if is_exponent(c) => {
+            let exponent = &rest[1..];
+            let sign = if exponent.starts_with(['+', '-']) { 1 } else { 0 };
+            let end = exponent[sign..].find(|c: char| !c.is_ascii_digit()).map_or(exponent.len(), |end| end + sign);
+            (mantissa, Some(&exponent[..end]), &exponent[end..])
+        },
+        _ => (mantissa, None, rest),
+    }
+}
+
+/// Classifies and evaluates a numeric constant following the C23 grammar:
+/// decimal, octal, hexadecimal and binary integers, decimal and hexadecimal floats,
+/// digit separators and the `u`, `l`, `ll`, `wb`, `f` and `l` suffixes.
+pub fn number_literal(raw: &str) -> Result<NumberLiteral, LexerError> {
+    let text = strip_separators(raw)?;
+    let lower = text.to_ascii_lowercase();
+
+    let (base, digits) = if lower.starts_with("0x") {
+        (Base::Hexadecimal, &text[2..])
+    }
+    else if lower.starts_with("0b") {
+        (Base::Binary, &text[2..])
+    }
+    else {
+        (Base::Decimal, text.as_str())
+    };
+
+    if base == Base::Binary {
+        let end = digits.find(|c: char| !c.is_ascii_digit()).unwrap_or(digits.len());
+        let (digits, suffix) = digits.split_at(end);
+        if digits.is_empty() {
+            return Err(LexerError::BadNumber(format!("no digits in binary constant {}", raw)));
+        }
+        let value = parse_digits(digits, Base::Binary, raw)?;
+        return Ok(NumberLiteral {
+            raw: raw.to_string(),
+            value: NumberValue::Integer(value),
+            c_type: integer_type(value, Base::Binary, suffix, raw)?,
+        });
+    }
+
+    let hex = base == Base::Hexadecimal;
+    let (mantissa, exponent, suffix) = split_number(digits, hex);
+    let floating = mantissa.contains('.') || exponent.is_some();
+
+    if floating {
+        let exponent = match exponent {
+            Some(exponent) if exponent.trim_start_matches(['+', '-']).is_empty() => {
+                return Err(LexerError::BadNumber(format!("exponent has no digits in {}", raw)));
+            },
+            Some(exponent) => Some(exponent),
+            None if hex => {
+                return Err(LexerError::BadNumber(format!("hexadecimal floating constant {} requires an exponent", raw)));
+            },
+            None => None,
+        };
+        if mantissa.matches('.').count() > 1 || mantissa == "." {
+            return Err(LexerError::BadNumber(format!("invalid floating constant {}", raw)));
+        }
+        let c_type = floating_type(suffix, raw)?;
+        let value = if hex {
+            let exponent = exponent.unwrap_or("0").parse::<i32>().unwrap_or(i32::MAX);
+            hex_float_value(mantissa, exponent, raw)?
+        }
+        else {
+            let text = match exponent {
+                Some(exponent) => format!("{}e{}", mantissa, exponent),
+                None => mantissa.to_string(),
+            };
+            text.parse::<f64>().map_err(|_| LexerError::BadNumber(format!("invalid floating constant {}", raw)))?
+        };
+        let out_of_range = match c_type {
+            NumberType::Float => value > f64::from(f32::MAX),
+            NumberType::Double => value.is_infinite(),
+            //long double has a wider range than the f64 we store it in
+            _ => false,
+        };
+        if out_of_range {
+            return Err(LexerError::NumberOutOfRange(format!("floating constant {} exceeds the range of its type", raw)));
+        }
+        return Ok(NumberLiteral {
+            raw: raw.to_string(),
+            value: NumberValue::Floating(value),
+            c_type,
+        });
+    }
+
+    let base = if base == Base::Decimal && mantissa.len() > 1 && mantissa.starts_with('0') {
+        Base::Octal
+    }
+    else {
+        base
+    };
+    if mantissa.is_empty() {
+        return Err(LexerError::BadNumber(format!("no digits in constant {}", raw)));
+    }
+    let value = parse_digits(mantissa, base, raw)?;
+
+    Ok(NumberLiteral {
+        raw: raw.to_string(),
+        value: NumberValue::Integer(value),
+        c_type: integer_type(value, base, suffix, raw)?,
+    })
+}
+
+#[cfg(test)]
+mod literal_tests {
+    use super::*;
+
+    #[test]
+    fn test_simple_escapes() {
+        let literal = string_literal(r#""\'\"\?\\\a\b\f\n\r\t\v""#).unwrap();
+        assert_eq!(literal.value, vec![0x27, 0x22, 0x3f, 0x5c, 0x07, 0x08, 0x0c, 0x0a, 0x0d, 0x09, 0x0b]);
+        assert_eq!(literal.encoding, Encoding::Char);
+    }
+
+    #[test]
+    fn test_octal_and_hex_escapes() {
+        assert_eq!(string_literal(r#""\0\101\1234""#).unwrap().value, vec![0, 0o101, 0o123, '4' as u32]);
+        assert_eq!(string_literal(r#""\x41\x7fz""#).unwrap().value, vec![0x41, 0x7f, 'z' as u32]);
+        assert!(string_literal(r#""\x100""#).is_err());
+        assert!(string_literal(r#""\400""#).is_err());
+        assert!(string_literal(r#""\x""#).is_err());
+        assert_eq!(string_literal(r#"L"\x100""#).unwrap().value, vec![0x100]);
+    }
+
+    #[test]
+    fn test_universal_characters() {
+        assert_eq!(string_literal(r#""é""#).unwrap().value, vec![0xc3, 0xa9]);
+        assert_eq!(string_literal(r#"u"\U0001F600""#).unwrap().value, vec![0xd83d, 0xde00]);
+        assert_eq!(string_literal(r#"U"\U0001F600""#).unwrap().value, vec![0x1f600]);
+        assert!(string_literal(r#""\uD800""#).is_err());
+        assert!(string_literal(r#""\u12""#).is_err());
+    }
+
+    #[test]
+    fn test_unknown_escape() {
+        assert!(string_literal(r#""\q""#).is_err());
+    }
+
+    #[test]
+    fn test_prefixes() {
+        assert_eq!(string_literal(r#"u8"é""#).unwrap().encoding, Encoding::Utf8);
+        assert_eq!(string_literal(r#"u"é""#).unwrap().value, vec![0xe9]);
+        assert_eq!(string_literal(r#"L"é""#).unwrap().encoding, Encoding::Wide);
+    }
+
+    #[test]
+    fn test_concatenation() {
+        let literal = concat_string_literals(&[r#""Hello, ""#, r#""World\n""#]).unwrap();
+        assert_eq!(literal.to_text(), "Hello, World\n");
+        assert_eq!(literal.raw, r#""Hello, " "World\n""#);
+
+        let literal = concat_string_literals(&[r#""a""#, r#"L"b""#]).unwrap();
+        assert_eq!(literal.encoding, Encoding::Wide);
+
+        //escapes end at the piece boundary
+        let literal = concat_string_literals(&[r#""\x1""#, r#""2""#]).unwrap();
+        assert_eq!(literal.value, vec![0x1, '2' as u32]);
+
+        assert!(concat_string_literals(&[r#"u"a""#, r#"U"b""#]).is_err());
+    }
+
+    #[test]
+    fn test_char_literals() {
+        assert_eq!(char_literal("'a'").unwrap().value, 97);
+        assert_eq!(char_literal(r"'\n'").unwrap().value, 10);
+        assert_eq!(char_literal(r"'\''").unwrap().value, 39);
+        assert_eq!(char_literal(r"'\xff'").unwrap().value, -1);
+        assert_eq!(char_literal(r"'\0'").unwrap().value, 0);
+        assert_eq!(char_literal("'ab'").unwrap().value, 0x6162);
+        assert_eq!(char_literal("L'ab'").unwrap().value, 98);
+        assert_eq!(char_literal(r"U'\U0001F600'").unwrap().value, 0x1f600);
+        assert!(char_literal("''").is_err());
+        assert!(char_literal(r"u'\U0001F600'").is_err());
+        assert!(char_literal("u8'é'").is_err());
+    }
+
+    fn integer(raw: &str) -> (u128, NumberType) {
+        match number_literal(raw) {
+            Ok(NumberLiteral { value: NumberValue::Integer(value), c_type, .. }) => (value, c_type),
+            result => panic!("Expected integer for {}, got {:?}", raw, result),
+        }
+    }
+
+    fn floating(raw: &str) -> (f64, NumberType) {
+        match number_literal(raw) {
+            Ok(NumberLiteral { value: NumberValue::Floating(value), c_type, .. }) => (value, c_type),
+            result => panic!("Expected floating constant for {}, got {:?}", raw, result),
+        }
+    }
+
+    #[test]
+    fn test_integer_bases() {
+        assert_eq!(integer("0"), (0, NumberType::Int));
+        assert_eq!(integer("42"), (42, NumberType::Int));
+        assert_eq!(integer("0x2A"), (42, NumberType::Int));
+        assert_eq!(integer("052"), (42, NumberType::Int));
+        assert_eq!(integer("0b101010"), (42, NumberType::Int));
+        assert_eq!(integer("1'000'000"), (1_000_000, NumberType::Int));
+        assert_eq!(integer("0xFF'FF"), (0xffff, NumberType::Int));
+        assert!(number_literal("08").is_err());
+        assert!(number_literal("0b102").is_err());
+        assert!(number_literal("0x").is_err());
+        assert!(number_literal("1''0").is_err());
+        assert!(number_literal("1'").is_err());
+        assert!(number_literal("0x'1").is_err());
+        assert!(number_literal("0b'1").is_err());
+    }
+
+    #[test]
+    fn test_integer_types() {
+        assert_eq!(integer("2147483647").1, NumberType::Int);
+        assert_eq!(integer("2147483648").1, NumberType::Long);
+        assert_eq!(integer("0x80000000").1, NumberType::UnsignedInt);
+        assert_eq!(integer("0xFFFFFFFFFFFFFFFF").1, NumberType::UnsignedLong);
+        assert_eq!(integer("10u").1, NumberType::UnsignedInt);
+        assert_eq!(integer("10l").1, NumberType::Long);
+        assert_eq!(integer("10LL").1, NumberType::LongLong);
+        assert_eq!(integer("10ull").1, NumberType::UnsignedLongLong);
+        assert_eq!(integer("10LLU").1, NumberType::UnsignedLongLong);
+        assert_eq!(integer("18446744073709551615u").1, NumberType::UnsignedLong);
+        assert_eq!(integer("0wb").1, NumberType::BitInt(2));
+        assert_eq!(integer("255wb").1, NumberType::BitInt(9));
+        assert_eq!(integer("255uwb").1, NumberType::UnsignedBitInt(8));
+        assert_eq!(integer("1WBU").1, NumberType::UnsignedBitInt(1));
+        assert!(number_literal("10lL").is_err());
+        assert!(number_literal("10lul").is_err());
+        assert!(number_literal("10uu").is_err());
+        assert!(number_literal("10wB").is_err());
+        assert!(number_literal("10f").is_err());
+    }
+
+    #[test]
+    fn test_integer_out_of_range() {
+        assert!(matches!(number_literal("18446744073709551615"), Err(LexerError::NumberOutOfRange(_))));
+        assert!(matches!(number_literal("0x10000000000000000"), Err(LexerError::NumberOutOfRange(_))));
+        assert!(matches!(number_literal("999999999999999999999999999999999999999999"), Err(LexerError::NumberOutOfRange(_))));
+    }
+
+    #[test]
+    fn test_floating_constants() {
+        assert_eq!(floating("1.5"), (1.5, NumberType::Double));
+        assert_eq!(floating(".5"), (0.5, NumberType::Double));
+        assert_eq!(floating("1."), (1.0, NumberType::Double));
+        assert_eq!(floating("1e10"), (1e10, NumberType::Double));
+        assert_eq!(floating("1E+2f"), (100.0, NumberType::Float));
+        assert_eq!(floating("2.5e-3L"), (2.5e-3, NumberType::LongDouble));
+        assert_eq!(floating("09.5"), (9.5, NumberType::Double));
+        assert_eq!(floating("1'000.000'1"), (1000.0001, NumberType::Double));
+        assert_eq!(floating("0x1.8p3"), (12.0, NumberType::Double));
+        assert_eq!(floating("0x.8p-1f"), (0.25, NumberType::Float));
+        assert_eq!(floating("1e-400").0, 0.0);
+        assert!(number_literal("0x1.8").is_err());
+        assert!(number_literal("1e").is_err());
+        assert!(number_literal("1e+").is_err());
+        assert!(number_literal("1.2.3").is_err());
+        assert!(number_literal("1.0u").is_err());
+        assert!(matches!(number_literal("1e400"), Err(LexerError::NumberOutOfRange(_))));
+        assert!(matches!(number_literal("1e39f"), Err(LexerError::NumberOutOfRange(_))));
+    }
+
+}
@@ -1,6 +1,9 @@
 use logos::{Logos, Lexer};
 
+use crate::literal::{self, CharLiteral, NumberLiteral, StringLiteral};
+
 use std::fmt;
+use std::ops::Range;
 
 
 #[derive(Logos, Debug, PartialEq)]
@@ -8,30 +11,25 @@ pub enum TokenPreparse<'input> {
 
     #[token("\n")]
     Newline,
-    #[regex(" ")]
-    Space,
-    #[token("\t")]
-    Tab,
+    #[regex("[ \t\x0b\x0c]+", |lex| lex.slice())]
+    Whitespace(&'input str),
     #[token("\r\n")]
     CarriageReturnNewline,
 
     #[regex("[a-zA-Z_][a-zA-Z0-9_]*", |lex| lex.slice())]
     Word(&'input str),
 
-    #[regex("[0-9]*[.]?[0-9]*([eE]-[^0][0-9]+[uUlL]?[lL]?[lL]?)?|0[xX][0-9a-fA-F]+[uUlL]?[lL]?[lL]?|[0-9]+[uUlL]?[lL]?[lL]?|0[0-7]+", |lex| lex.slice())]
+    #[regex(r"\.?[0-9]([0-9a-zA-Z_.]|[eEpP][+-]|'[0-9a-zA-Z_])*", |lex| lex.slice())]
     Number(&'input str),
 
-    //#[regex("\"[^\"]*\"", |lex| lex.slice())]
-    //String(&'input str),
-    #[token("\"")]
-    StringDelimiter,
-    #[token("'")]
-    SingleQuote,
-    
-    #[regex("'[^']'", |lex| lex.slice())]
-    Character(&'input str),
-    #[regex(r#""/\*~(.*\*/.*)\*/""#, |lex| lex.slice())]
-    Comment(&'input str),
+    #[regex(r#"(u8|u|U|L)?""#, |lex| quoted(lex, '"'))]
+    String((&'input str, bool)),
+    #[regex("(u8|u|U|L)?'", |lex| quoted(lex, '\''))]
+    Character((&'input str, bool)),
+    #[regex("//[^\n]*", |lex| lex.slice())]
+    LineComment(&'input str),
+    #[token("/*", block_comment)]
+    BlockComment((&'input str, bool)),
     #[token(";")]
     SemiColon,
     #[token(":")]
@@ -199,8 +197,10 @@ pub enum TokenPreparse<'input> {
     #[token("inline")]
     Inline,
     #[token("alignas")]
+    #[token("_Alignas")]
     Alignas,
     #[token("alignof")]
+    #[token("_Alignof")]
     Alignof,
     #[token("atomic")]
     Atomic,
@@ -210,13 +210,18 @@ pub enum TokenPreparse<'input> {
     Complex,
     #[token("generic")]
     Generic,
+    #[token("_Generic")]
+    GenericSelection,
     #[token("imaginary")]
     Imaginary,
     #[token("noreturn")]
+    #[token("_Noreturn")]
     Noreturn,
     #[token("static_assert")]
+    #[token("_Static_assert")]
     StaticAssert,
     #[token("thread_local")]
+    #[token("_Thread_local")]
     ThreadLocal,
     #[token("constexpr")]
     ConstExpr,
@@ -230,6 +235,9 @@ pub enum TokenPreparse<'input> {
     Typeof,
     #[token("typeof_unqual")]
     TypeofUnqual,
+    #[token("__attribute__")]
+    #[token("__attribute")]
+    Attribute,
 
     
     #[token("tagged")]
@@ -244,18 +252,56 @@ pub enum TokenPreparse<'input> {
     Class,
 }
 
+/// Scans the rest of a string literal or character constant after its opening quote.
+/// Returns the spelling of the literal and whether it was terminated before the end of the line.
+fn quoted<'input>(lex: &mut Lexer<'input, TokenPreparse<'input>>, quote: char) -> (&'input str, bool) {
+    let mut chars = lex.remainder().char_indices();
+    let mut terminated = false;
+    let mut length = lex.remainder().len();
+
+    while let Some((index, c)) = chars.next() {
+        if c == quote {
+            terminated = true;
+            length = index + 1;
+            break;
+        }
+        else if c == '\\' {
+            chars.next();
+        }
+        else if c == '\n' {
+            length = index;
+            break;
+        }
+    }
+    lex.bump(length);
+
+    (lex.slice(), terminated)
+}
+
+/// Scans the rest of a block comment after its opening `/*`.
+/// Returns the spelling of the comment and whether a closing `*/` was found.
+fn block_comment<'input>(lex: &mut Lexer<'input, TokenPreparse<'input>>) -> (&'input str, bool) {
+    let (length, terminated) = match lex.remainder().find("*/") {
+        Some(index) => (index + 2, true),
+        None => (lex.remainder().len(), false),
+    };
+    lex.bump(length);
+
+    (lex.slice(), terminated)
+}
+
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Token {
 
     Newline,
     Word(String),
-    Number(String),
-    String(String),
+    Number(NumberLiteral),
+    String(StringLiteral),
     Include(String),
     Macro(String),
     Preprocessor(String),
-    Character(String),
-    Comment(String),
+    Character(CharLiteral),
     SemiColon,
     Colon,
     Comma,
@@ -303,27 +349,26 @@ pub enum Token {
     QuestionMark,
     Backslash,
     Hash,
-    Type(String),
-    //Auto,
-    //Double,
-    //Int,
+    Auto,
+    Double,
+    Int,
     Struct,
     Break,
     Else,
-    //Long,
+    Long,
     Switch,
     Case,
     Enum,
-    //Register,
+    Register,
     Typedef,
-    //Char,
-    //Extern,
+    Char,
+    Extern,
     Return,
     Union,
     Continue,
     For,
-    //Signed,
-    //Void,
+    Signed,
+    Void,
     Do,
     If,
     Static,
@@ -331,11 +376,11 @@ pub enum Token {
     Default,
     Goto,
     Sizeof,
-    //Volatile,
-    //Const,
-    //Float,
-    //Short,
-    //Unsigned,
+    Volatile,
+    Const,
+    Float,
+    Short,
+    Unsigned,
     
     //todo check some of these for correctness
     Restrict,// can only appear as * restrict
@@ -343,10 +388,11 @@ pub enum Token {
     Alignas,
     Alignof,
     Atomic,
-    //Bool,
-    //Complex,
+    Bool,
+    Complex,
     Generic,
-    //Imaginary,
+    GenericSelection,
+    Imaginary,
     Noreturn,
     StaticAssert,
     ThreadLocal,
@@ -356,6 +402,7 @@ pub enum Token {
     False,
     Typeof,
     TypeofUnqual,
+    Attribute,
 
     Tagged,
     Abstract,
@@ -369,13 +416,12 @@ impl fmt::Display for Token {
         match self {
             Token::Newline => write!(f, "Newline"),
             Token::Word(s) => write!(f, "{}", s),
-            Token::Number(s) => write!(f, "{}", s),
-            Token::String(s) => write!(f, "{}", s),
+            Token::Number(s) => write!(f, "{}", s.raw),
+            Token::String(s) => write!(f, "{}", s.raw),
             Token::Include(s) => write!(f, "{}", s),
             Token::Macro(s) => write!(f, "{}", s),
             Token::Preprocessor(s) => write!(f, "{}", s),
-            Token::Character(s) => write!(f, "{}", s),
-            Token::Comment(s) => write!(f, "{}", s),
+            Token::Character(s) => write!(f, "{}", s.raw),
             Token::SemiColon => write!(f, ";"),
             Token::Colon => write!(f, ":"),
             Token::Comma => write!(f, ","),
@@ -391,7 +437,7 @@ impl fmt::Display for Token {
             Token::Star => write!(f, "*"),
             Token::Divide => write!(f, "/"),
             Token::Modulo => write!(f, "%"),
-            Token::Equals => write!(f, "="),
+            Token::Equals => write!(f, "=="),
             Token::NotEquals => write!(f, "!="),
             Token::LessThan => write!(f, "<"),
             Token::LessThanOrEqual => write!(f, "<="),
@@ -423,7 +469,23 @@ impl fmt::Display for Token {
             Token::QuestionMark => write!(f, "?"),
             Token::Backslash => write!(f, "\\"),
             Token::Hash => write!(f, "#"),
-            Token::Type(s) => write!(f, "{}", s),
+            Token::Auto => write!(f, "auto"),
+            Token::Double => write!(f, "double"),
+            Token::Int => write!(f, "int"),
+            Token::Long => write!(f, "long"),
+            Token::Register => write!(f, "register"),
+            Token::Char => write!(f, "char"),
+            Token::Extern => write!(f, "extern"),
+            Token::Signed => write!(f, "signed"),
+            Token::Void => write!(f, "void"),
+            Token::Volatile => write!(f, "volatile"),
+            Token::Const => write!(f, "const"),
+            Token::Float => write!(f, "float"),
+            Token::Short => write!(f, "short"),
+            Token::Unsigned => write!(f, "unsigned"),
+            Token::Bool => write!(f, "bool"),
+            Token::Complex => write!(f, "complex"),
+            Token::Imaginary => write!(f, "imaginary"),
             Token::Struct => write!(f, "struct"),
             Token::Break => write!(f, "break"),
             Token::Else => write!(f, "else"),
@@ -448,6 +510,7 @@ impl fmt::Display for Token {
             Token::Alignof => write!(f, "alignof"),
             Token::Atomic => write!(f, "atomic"),
             Token::Generic => write!(f, "generic"),
+            Token::GenericSelection => write!(f, "_Generic"),
             Token::Noreturn => write!(f, "noreturn"),
             Token::StaticAssert => write!(f, "static_assert"),
             Token::ThreadLocal => write!(f, "thread_local"),
@@ -457,6 +520,7 @@ impl fmt::Display for Token {
             Token::False => write!(f, "false"),
             Token::Typeof => write!(f, "typeof"),
             Token::TypeofUnqual => write!(f, "typeof_unqual"),
+            Token::Attribute => write!(f, "__attribute__"),
             Token::Private => write!(f, "private"),
             Token::Class => write!(f, "class"),
             Token::Tagged => write!(f, "tagged"),
@@ -468,2578 +532,707 @@ impl fmt::Display for Token {
 }
 
 
-#[derive(Debug)]
+/// Text between tokens that the parser does not need but a formatter must keep.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Trivia {
+    Whitespace(String),
+    Comment(String),
+    DocComment(String),
+}
+
+impl Trivia {
+    fn comment(text: &str) -> Trivia {
+        let line_doc = text.starts_with("///") && !text.starts_with("////");
+        let block_doc = text.starts_with("/**") && !text.starts_with("/***") && text != "/**/";
+        if line_doc || block_doc {
+            Trivia::DocComment(text.to_string())
+        }
+        else {
+            Trivia::Comment(text.to_string())
+        }
+    }
+
+    /// The text of a doc comment with its `///`, `/**`, `*/` and leading `*` markers removed.
+    pub fn doc_text(&self) -> Option<String> {
+        let Trivia::DocComment(text) = self else {
+            return None;
+        };
+        if let Some(line) = text.strip_prefix("///") {
+            return Some(line.strip_prefix(' ').unwrap_or(line).trim_end().to_string());
+        }
+
+        let body = text.trim_start_matches("/**").trim_end_matches("*/");
+        let lines = body.lines().map(|line| {
+            let line = line.trim_start();
+            let line = line.strip_prefix('*').unwrap_or(line);
+            line.strip_prefix(' ').unwrap_or(line).trim_end()
+        }).collect::<Vec<&str>>();
+        let start = lines.iter().position(|line| !line.is_empty()).unwrap_or(lines.len());
+        let end = lines.iter().rposition(|line| !line.is_empty()).map_or(start, |index| index + 1);
+
+        Some(lines[start..end].join("\n"))
+    }
+}
+
+/// A token together with its byte range, the trivia before it and the trivia after it on the same line.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TokenWithTrivia {
+    pub token: Token,
+    pub span: Range<usize>,
+    pub leading: Vec<Trivia>,
+    pub trailing: Vec<Trivia>,
+    /// The literals of a string joined from adjacent ones, with the trivia between them.
+    /// The leading trivia of the first and the trailing trivia of the last are the token's own.
+    pub pieces: Vec<TokenWithTrivia>,
+}
+
+impl TokenWithTrivia {
+    /// The joined text of the doc comments in front of this token, if there are any.
+    pub fn doc_comment(&self) -> Option<String> {
+        let docs = self.leading.iter().filter_map(Trivia::doc_text).collect::<Vec<String>>();
+        if docs.is_empty() {
+            None
+        }
+        else {
+            Some(docs.join("\n"))
+        }
+    }
+}
+
+/// Decides which token owns each piece of trivia as the lexer produces tokens.
+/// Trivia on the same line as the previous token trails it, everything else leads the next token.
+#[derive(Default)]
+struct TriviaCollector {
+    leading: Vec<Vec<Trivia>>,
+    trailing: Vec<Vec<Trivia>>,
+    pending: Vec<Trivia>,
+    line_ended: bool,
+}
+
+impl TriviaCollector {
+    /// Gives the pending trivia to the first of the tokens pushed since the last call.
+    fn attach(&mut self, token_count: usize) {
+        while self.leading.len() < token_count {
+            self.leading.push(std::mem::take(&mut self.pending));
+            self.trailing.push(Vec::new());
+            self.line_ended = false;
+        }
+    }
+
+    /// Ends the line after the last token, for tokens such as preprocessor lines that
+    /// take their newline with them.
+    fn end_line(&mut self, token_count: usize) {
+        self.attach(token_count);
+        self.line_ended = true;
+    }
+
+    fn push(&mut self, trivia: Trivia) {
+        if let Trivia::Whitespace(text) = &trivia {
+            if text.contains('\n') {
+                self.line_ended = true;
+            }
+        }
+        let doc = matches!(trivia, Trivia::DocComment(_));
+
+        match self.trailing.last_mut() {
+            Some(trailing) if !doc && !self.line_ended && self.pending.is_empty() => {
+                Self::append(trailing, trivia);
+            },
+            _ => Self::append(&mut self.pending, trivia),
+        }
+    }
+
+    fn append(list: &mut Vec<Trivia>, trivia: Trivia) {
+        match (list.last_mut(), trivia) {
+            (Some(Trivia::Whitespace(text)), Trivia::Whitespace(more)) => text.push_str(&more),
+            (_, trivia) => list.push(trivia),
+        }
+    }
+
+    fn finish(mut self, tokens: Vec<(Token, Range<usize>)>) -> Vec<TokenWithTrivia> {
+        self.attach(tokens.len());
+        let pending = std::mem::take(&mut self.pending);
+        if let Some(trailing) = self.trailing.last_mut() {
+            for trivia in pending {
+                Self::append(trailing, trivia);
+            }
+        }
+
+        tokens.into_iter()
+            .zip(self.leading.into_iter().zip(self.trailing))
+            .map(|((token, span), (leading, trailing))| TokenWithTrivia {token, span, leading, trailing, pieces: Vec::new()})
+            .collect()
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum LexerError {
     UnterminatedString,
     UnterminatedCharacter,
+    UnterminatedComment,
+    BadEscape(String),
+    BadLiteral(String),
+    MismatchedStringPrefix(String),
+    BadNumber(String),
+    NumberOutOfRange(String),
     UnrecognizedToken(String),
-    BadType(String),
     Empty,
 }
 
 
-enum ParserState {
-    Normal,
-    InString(String, bool),
-    InPreprocessor(String, PreprocessorState),
-    InType(String, LastType),
+/// A lexer error together with the byte range of the input it was found in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub error: LexerError,
+    pub span: Range<usize>,
 }
 
-enum LastType {
-    Type,//   //bool, void, char, short, int, long, float, double, signed, unsigned
-    PrefixMod,//const, volatile, auto, register
-    SuffixMod,//complex, imaginary
+/// A token straight from logos with its byte range in the input.
+struct RawToken<'input> {
+    kind: TokenPreparse<'input>,
+    span: Range<usize>,
 }
 
-
-#[derive(Debug, Copy,Clone, PartialEq)]
-enum PreprocessorState {
-    None,
-    InDefine(bool),
-    InInclude,
-    InIf(bool),
-    InError,
-    InPragma,
-    InUndef,
-    HashOperator,
+/// The token for raw tokens that stand for themselves.
+fn plain_token(kind: &TokenPreparse) -> Option<Token> {
+    let token = match kind {
+        TokenPreparse::Word(word) => Token::Word(word.to_string()),
+        TokenPreparse::SemiColon => Token::SemiColon,
+        TokenPreparse::Colon => Token::Colon,
+        TokenPreparse::Comma => Token::Comma,
+        TokenPreparse::Period => Token::Period,
+        TokenPreparse::LeftParen => Token::LeftParen,
+        TokenPreparse::RightParen => Token::RightParen,
+        TokenPreparse::LeftBrace => Token::LeftBrace,
+        TokenPreparse::RightBrace => Token::RightBrace,
+        TokenPreparse::LeftBracket => Token::LeftBracket,
+        TokenPreparse::RightBracket => Token::RightBracket,
+        TokenPreparse::Plus => Token::Plus,
+        TokenPreparse::Minus => Token::Minus,
+        TokenPreparse::Star => Token::Star,
+        TokenPreparse::Divide => Token::Divide,
+        TokenPreparse::Modulo => Token::Modulo,
+        TokenPreparse::Equals => Token::Equals,
+        TokenPreparse::NotEquals => Token::NotEquals,
+        TokenPreparse::LessThan => Token::LessThan,
+        TokenPreparse::LessThanOrEqual => Token::LessThanOrEqual,
+        TokenPreparse::GreaterThan => Token::GreaterThan,
+        TokenPreparse::GreaterThanOrEqual => Token::GreaterThanOrEqual,
+        TokenPreparse::LogicalAnd => Token::LogicalAnd,
+        TokenPreparse::LogicalOr => Token::LogicalOr,
+        TokenPreparse::LogicalNot => Token::LogicalNot,
+        TokenPreparse::BitwiseAnd => Token::BitwiseAnd,
+        TokenPreparse::BitwiseOr => Token::BitwiseOr,
+        TokenPreparse::BitwiseNot => Token::BitwiseNot,
+        TokenPreparse::BitwiseXor => Token::BitwiseXor,
+        TokenPreparse::BitwiseLeftShift => Token::BitwiseLeftShift,
+        TokenPreparse::BitwiseRightShift => Token::BitwiseRightShift,
+        TokenPreparse::Assignment => Token::Assignment,
+        TokenPreparse::PlusEquals => Token::PlusEquals,
+        TokenPreparse::MinusEquals => Token::MinusEquals,
+        TokenPreparse::StarEquals => Token::StarEquals,
+        TokenPreparse::DivideEquals => Token::DivideEquals,
+        TokenPreparse::ModuloEquals => Token::ModuloEquals,
+        TokenPreparse::BitwiseAndEquals => Token::BitwiseAndEquals,
+        TokenPreparse::BitwiseOrEquals => Token::BitwiseOrEquals,
+        TokenPreparse::BitwiseXorEquals => Token::BitwiseXorEquals,
+        TokenPreparse::BitwiseLeftShiftEquals => Token::BitwiseLeftShiftEquals,
+        TokenPreparse::BitwiseRightShiftEquals => Token::BitwiseRightShiftEquals,
+        TokenPreparse::Increment => Token::Increment,
+        TokenPreparse::Decrement => Token::Decrement,
+        TokenPreparse::Arrow => Token::Arrow,
+        TokenPreparse::QuestionMark => Token::QuestionMark,
+        TokenPreparse::Backslash => Token::Backslash,
+        TokenPreparse::Hash => Token::Hash,
+        TokenPreparse::Auto => Token::Auto,
+        TokenPreparse::Register => Token::Register,
+        TokenPreparse::Extern => Token::Extern,
+        TokenPreparse::Const => Token::Const,
+        TokenPreparse::Volatile => Token::Volatile,
+        TokenPreparse::Void => Token::Void,
+        TokenPreparse::Char => Token::Char,
+        TokenPreparse::Short => Token::Short,
+        TokenPreparse::Int => Token::Int,
+        TokenPreparse::Long => Token::Long,
+        TokenPreparse::Float => Token::Float,
+        TokenPreparse::Double => Token::Double,
+        TokenPreparse::Signed => Token::Signed,
+        TokenPreparse::Unsigned => Token::Unsigned,
+        TokenPreparse::Bool => Token::Bool,
+        TokenPreparse::Complex => Token::Complex,
+        TokenPreparse::Imaginary => Token::Imaginary,
+        TokenPreparse::Struct => Token::Struct,
+        TokenPreparse::Break => Token::Break,
+        TokenPreparse::Else => Token::Else,
+        TokenPreparse::Switch => Token::Switch,
+        TokenPreparse::Case => Token::Case,
+        TokenPreparse::Enum => Token::Enum,
+        TokenPreparse::Typedef => Token::Typedef,
+        TokenPreparse::Return => Token::Return,
+        TokenPreparse::Union => Token::Union,
+        TokenPreparse::Continue => Token::Continue,
+        TokenPreparse::For => Token::For,
+        TokenPreparse::Do => Token::Do,
+        TokenPreparse::If => Token::If,
+        TokenPreparse::Static => Token::Static,
+        TokenPreparse::While => Token::While,
+        TokenPreparse::Default => Token::Default,
+        TokenPreparse::Goto => Token::Goto,
+        TokenPreparse::Sizeof => Token::Sizeof,
+        TokenPreparse::Restrict => Token::Restrict,
+        TokenPreparse::Inline => Token::Inline,
+        TokenPreparse::Alignas => Token::Alignas,
+        TokenPreparse::Alignof => Token::Alignof,
+        TokenPreparse::Atomic => Token::Atomic,
+        TokenPreparse::Generic => Token::Generic,
+        TokenPreparse::GenericSelection => Token::GenericSelection,
+        TokenPreparse::Noreturn => Token::Noreturn,
+        TokenPreparse::StaticAssert => Token::StaticAssert,
+        TokenPreparse::ThreadLocal => Token::ThreadLocal,
+        TokenPreparse::ConstExpr => Token::ConstExpr,
+        TokenPreparse::Nullptr => Token::Nullptr,
+        TokenPreparse::True => Token::True,
+        TokenPreparse::False => Token::False,
+        TokenPreparse::Typeof => Token::Typeof,
+        TokenPreparse::TypeofUnqual => Token::TypeofUnqual,
+        TokenPreparse::Attribute => Token::Attribute,
+        TokenPreparse::Tagged => Token::Tagged,
+        TokenPreparse::Abstract => Token::Abstract,
+        TokenPreparse::Operator => Token::Operator,
+        TokenPreparse::Private => Token::Private,
+        TokenPreparse::Class => Token::Class,
+        _ => return None,
+    };
+    Some(token)
 }
 
-
-pub fn lex<'input>(input: &'input str) -> Result<Vec<Token>, LexerError> {
-    let mut tokens = Vec::new();
-    
+/// Phase one: splits the input into raw tokens. Characters logos does not recognize are
+/// reported and skipped so that lexing carries on.
+fn raw_tokens<'input>(input: &'input str, errors: &mut Vec<Diagnostic>) -> Vec<RawToken<'input>> {
     let mut lexer = TokenPreparse::lexer(input);
-    
-    let mut state = ParserState::Normal;
+    let mut tokens = Vec::new();
 
-    while let Some(Ok(token)) = lexer.next() {
+    while let Some(token) = lexer.next() {
         match token {
-            TokenPreparse::StringDelimiter => {
-                match state {
-                    ParserState::Normal => {
-                        state = ParserState::InString(String::new(), false);
-                    },
-                    ParserState::InString(mut string, false) => {
-                        tokens.push(Token::String(string));
-                        state = ParserState::Normal;
-                        continue;
-                    },
-                    ParserState::InString(mut string, true) => {
-                        string.push('"');
-                        state = ParserState::InString(string, false);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string, preproc_state) => {
-                        string.push('"');
-                        state = ParserState::InPreprocessor(string, preproc_state);
-                        continue;
-                    },
-                    _ => {},
-                }
-
-                //in_string(&mut lexer)?
-            },
-            TokenPreparse::Newline => {
-                match state {
-                    ParserState::InString(mut string,_) => {
-                        string.push('\n');
-                        state = ParserState::InString(string, false);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push('\n');
-                        
-                        match preproc_state {
-                            PreprocessorState::InDefine(false) => {
-                                tokens.push(Token::Macro(string));
-                                state = ParserState::Normal;
-                                continue;
-                            },
-                            PreprocessorState::InIf(true) => {
-                                tokens.push(Token::Preprocessor(string));
-                                state = ParserState::Normal;
-                                continue;
-                            },
-                            PreprocessorState::InInclude => {
-                                tokens.push(Token::Include(string));
-                                state = ParserState::Normal;
-                                continue;
-                            },
-                            _ => {
-                                tokens.push(Token::Preprocessor(string));
-                                state = ParserState::Normal;
-                                continue;
-                            },
-                        }
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Space => {
-                match state {
-                    ParserState::InString(mut string,_) => {
-                        string.push(' ');
-                        state = ParserState::InString(string, false);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,_) => {
-                        string.push(' ');
-                        state = ParserState::InPreprocessor(string,PreprocessorState::None);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Tab => {
-                match state {
-                    ParserState::InString(mut string, _) => {
-                        string.push('\t');
-                        state = ParserState::InString(string, false);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,_) => {
-                        string.push('\t');
-                        state = ParserState::InPreprocessor(string,PreprocessorState::None);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Backslash => {
-                match state {
-                    ParserState::InString(mut string, _) => {
-                        string.push('\\');
-                        state = ParserState::InString(string,true);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string, mut preproc_state) => {
-                        match preproc_state {
-                            PreprocessorState::InDefine(false) => {
-                                string.push('\\');
-                                state = ParserState::InPreprocessor(string,PreprocessorState::InDefine(true));
-                                continue;
-                            },
-                            _ => {},
-                        }
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Hash => {
-                match state {
-                    ParserState::Normal => {
-                        state = ParserState::InPreprocessor("#".to_string(),PreprocessorState::None);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push('#');
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push('#');
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-
-                //in_preprocessor(&mut lexer)?
-            },
-            TokenPreparse::CarriageReturnNewline => {
-                match state {
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("\r\n");
-                        state = ParserState::InString(string, false);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string, preproc_state) => {
-                        string.push_str("\r\n");
-                        
-                        match preproc_state {
-                            PreprocessorState::InDefine(false) => {
-                                tokens.push(Token::Macro(string));
-                                state = ParserState::Normal;
-                                continue;
-                            },
-                            PreprocessorState::InIf(true) => {
-                                tokens.push(Token::Preprocessor(string));
-                                state = ParserState::Normal;
-                                continue;
-                            },
-                            PreprocessorState::InInclude => {
-                                tokens.push(Token::Include(string));
-                                state = ParserState::Normal;
-                                continue;
-                            },
-                            _ => {
-                                tokens.push(Token::Preprocessor(string));
-                                state = ParserState::Normal;
-                                continue;
-                            },
-                        }
-                    },
+            Ok(kind) => tokens.push(RawToken {kind, span: lexer.span()}),
+            Err(()) => errors.push(Diagnostic {
+                error: LexerError::UnrecognizedToken(lexer.slice().to_string()),
+                span: lexer.span(),
+            }),
+        }
+    }
 
-                    _ => {},
-                }
-            },
-            TokenPreparse::Character(character) => {
-                match state {
-                    ParserState::InString(mut string, _) => {
-                        string.push_str(character);
-                        state = ParserState::InString(string, false);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str(character);
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::SingleQuote => {
-                match state {
-                    ParserState::InString(mut string, _) => {
-                        string.push('\'');
-                        state = ParserState::InString(string, false);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string, preproc_state) => {
-                        string.push('\'');
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    _ => {
-                        return Err(LexerError::UnterminatedCharacter);
-                    },
-                }
-            },
-            TokenPreparse::Comment(comment) => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Comment(comment.to_string()));
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str(comment);
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str(comment);
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    ParserState::InType(string, _) => {
-                        tokens.push(Token::Type(string.to_string()));
-                        tokens.push(Token::Comment(comment.to_string()));
-                        state = ParserState::Normal;
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::SemiColon => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::SemiColon);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push(';');
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push(';');
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    ParserState::InType(string, _) => {
-                        tokens.push(Token::Type(string.to_string()));
-                        tokens.push(Token::SemiColon);
-                        state = ParserState::Normal;
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Colon => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Colon);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push(':');
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push(':');
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    ParserState::InType(string, _) => {
-                        tokens.push(Token::Type(string.to_string()));
-                        tokens.push(Token::Colon);
-                        state = ParserState::Normal;
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Comma => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Comma);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push(',');
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push(',');
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    ParserState::InType(string, _) => {
-                        tokens.push(Token::Type(string.to_string()));
-                        tokens.push(Token::Comma);
-                        state = ParserState::Normal;
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Period => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Period);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push('.');
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push('.');
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::LeftParen => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::LeftParen);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string, preproc_state) => {
-                        string.push('(');
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push('(');
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    ParserState::InType(string, _) => {
-                        tokens.push(Token::Type(string.to_string()));
-                        tokens.push(Token::LeftParen);
-                        state = ParserState::Normal;
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::RightParen => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::RightParen);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string, preproc_state) => {
-                        string.push(')');
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push(')');
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    ParserState::InType(string, _) => {
-                        tokens.push(Token::Type(string.to_string()));
-                        tokens.push(Token::RightParen);
-                        state = ParserState::Normal;
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::LeftBrace => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::LeftBrace);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push('{');
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push('{');
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::RightBrace => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::RightBrace);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string, preproc_state) => {
-                        string.push('}');
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push('}');
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::LeftBracket => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::LeftBracket);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string, preproc_state) => {
-                        string.push('[');
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push('[');
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    ParserState::InType(string, _) => {
-                        tokens.push(Token::Type(string.to_string()));
-                        tokens.push(Token::LeftBracket);
-                        state = ParserState::Normal;
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::RightBracket => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::RightBracket);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string, preproc_state) => {
-                        string.push(']');
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push(']');
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Plus => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Plus);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string, preproc_state) => {
-                        string.push('+');
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push('+');
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Minus => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Minus);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string, preproc_state) => {
-                        string.push('-');
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push('-');
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Star => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Star);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string, preproc_state) => {
-                        string.push('*');
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push('*');
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    ParserState::InType(string, _) => {
-                        tokens.push(Token::Type(string.to_string()));
-                        tokens.push(Token::Star);
-                        state = ParserState::Normal;
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Divide => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Divide);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push('/');
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push('/');
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Modulo => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Modulo);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string, preproc_state) => {
-                        string.push('%');
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push('%');
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Equals => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Equals);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("==");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("==");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::NotEquals => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::NotEquals);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string, preproc_state) => {
-                        string.push_str("!=");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("!=");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::LessThan => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::LessThan);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push('<');
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push('<');
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::LessThanOrEqual => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::LessThanOrEqual);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("<=");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("<=");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::GreaterThan => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::GreaterThan);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push('>');
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push('>');
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::GreaterThanOrEqual => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::GreaterThanOrEqual);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str(">=");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str(">=");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::LogicalAnd => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::LogicalAnd);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("&&");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("&&");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::LogicalOr => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::LogicalOr);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("||");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("||");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::LogicalNot => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::LogicalNot);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push('!');
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push('!');
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::BitwiseAnd => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::BitwiseAnd);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push('&');
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push('&');
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::BitwiseOr => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::BitwiseOr);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push('|');
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push('|');
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::BitwiseNot => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::BitwiseNot);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string, preproc_state) => {
-                        string.push('~');
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push('~');
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::BitwiseXor => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::BitwiseXor);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push('^');
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push('^');
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::BitwiseLeftShift => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::BitwiseLeftShift);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string, preproc_state) => {
-                        string.push_str("<<");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("<<");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::BitwiseRightShift => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::BitwiseRightShift);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str(">>");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str(">>");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Assignment => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Assignment);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string, preproc_state) => {
-                        string.push('=');
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push('=');
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::PlusEquals => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::PlusEquals);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string, preproc_state) => {
-                        string.push_str("+=");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("+=");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::MinusEquals => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::MinusEquals);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("-=");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("-=");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::StarEquals => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::StarEquals);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string, preproc_state) => {
-                        string.push_str("*=");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("*=");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::DivideEquals => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::DivideEquals);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("/=");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("/=");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::ModuloEquals => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::ModuloEquals);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("%=");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("%=");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::BitwiseAndEquals => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::BitwiseAndEquals);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("&=");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("&=");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::BitwiseOrEquals => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::BitwiseOrEquals);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string, preproc_state) => {
-                        string.push_str("|=");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("|=");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::BitwiseXorEquals => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::BitwiseXorEquals);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("^=");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("^=");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::BitwiseLeftShiftEquals => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::BitwiseLeftShiftEquals);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("<<=");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("<<=");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::BitwiseRightShiftEquals => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::BitwiseRightShiftEquals);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str(">>=");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str(">>=");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Increment => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Increment);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("++");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("++");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Decrement => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Decrement);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string, preproc_state) => {
-                        string.push_str("--");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("--");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Arrow => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Arrow);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("->");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("->");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::QuestionMark => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::QuestionMark);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("?");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("?");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Auto => {
-                match state {
-                    ParserState::Normal => {
-                        state = ParserState::InType("auto".to_string(),LastType::PrefixMod);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("auto");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("auto");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    ParserState::InType(mut string, LastType::PrefixMod) => {
-                        string.push_str(" auto");
-                        state = ParserState::InType(string,LastType::PrefixMod);
-                        continue;
-                    },
-                    ParserState::InType(mut string, _) => {
-                        return Err(LexerError::BadType(string));
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Double => {
-                match state {
-                    ParserState::Normal => {
-                        state = ParserState::InType("double".to_string(),LastType::Type);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("double");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("double");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    ParserState::InType(mut string, LastType::SuffixMod) => {
-                        return Err(LexerError::BadType(string));
-                    },
-                    ParserState::InType(mut string, _) => {
-                        string.push_str(" double");
-                        state = ParserState::InType(string,LastType::SuffixMod);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Int => {
-                match state {
-                    ParserState::Normal => {
-                        state = ParserState::InType("int".to_string(),LastType::Type);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("int");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("int");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    ParserState::InType(mut string, LastType::SuffixMod) => {
-                        return Err(LexerError::BadType(format!("{} int",string)));
-                    },
-                    ParserState::InType(mut string, _) => {
-                        string.push_str(" int");
-                        state = ParserState::InType(string,LastType::SuffixMod);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Struct => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Struct);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("struct");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("struct");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Break => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Break);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("break");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("break");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Else => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Else);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("else");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("else");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Long => {
-                match state {
-                    ParserState::Normal => {
-                        state = ParserState::InType("long".to_string(), LastType::Type);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("long");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("long");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    ParserState::InType(mut string, LastType::SuffixMod) => {
-                        return Err(LexerError::BadType(string));
-                    },
-                    ParserState::InType(mut string, _) => {
-                        string.push_str(" long");
-                        state = ParserState::InType(string,LastType::SuffixMod);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Switch => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Switch);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("switch");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("switch");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Case => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Case);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("case");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("case");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Enum => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Enum);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("enum");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("enum");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Register => {
-                match state {
-                    ParserState::Normal => {
-                        state = ParserState::InType("register".to_string(), LastType::PrefixMod);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("register");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("register");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    ParserState::InType(mut string, LastType::PrefixMod) => {
-                        string.push_str(" register");
-                        state = ParserState::InType(string,LastType::PrefixMod);
-                        continue;
-                    },
-                    ParserState::InType(mut string, _) => {
-                        return Err(LexerError::BadType(string));
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Typedef => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Typedef);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("typedef");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("typedef");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Char => {
-                match state {
-                    ParserState::Normal => {
-                        state = ParserState::InType("char".to_string(), LastType::Type);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("char");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("char");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    ParserState::InType(mut string, LastType::SuffixMod) => {
-                        return Err(LexerError::BadType(string));
-                    },
-                    ParserState::InType(mut string, _) => {
-                        string.push_str(" char");
-                        state = ParserState::InType(string,LastType::Type);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Extern => {
-                match state {
-                    ParserState::Normal => {
-                        state = ParserState::InType("extern".to_string(), LastType::PrefixMod);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("extern");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("extern");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    ParserState::InType(mut string, LastType::PrefixMod) => {
-                        string.push_str(" extern");
-                        state = ParserState::InType(string,LastType::PrefixMod);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Return => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Return);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("return");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("return");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Union => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Union);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("union");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("union");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Continue => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Continue);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("continue");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("continue");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::For => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::For);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("for");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("for");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Signed => {
-                match state {
-                    ParserState::Normal => {
-                        state = ParserState::InType("signed".to_string(), LastType::Type);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("signed");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("signed");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    ParserState::InType(mut string,LastType::PrefixMod) => {
-                        string.push_str(" signed");
-                        state = ParserState::InType(string,LastType::Type);
-                        continue;
-                    },
-                    ParserState::InType(mut string,_) => {
-                        return Err(LexerError::BadType(format!("{} signed",string)));
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Void => {
-                match state {
-                    ParserState::Normal => {
-                        state = ParserState::InType("void".to_string(), LastType::Type);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("void");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("void");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    ParserState::InType(mut string, LastType::PrefixMod) => {
-                        string.push_str(" void");
-                        state = ParserState::InType(string,LastType::Type);
-                        continue;
-                    },
-                    ParserState::InType(mut string,_) => {
-                        return Err(LexerError::BadType(format!("{} void",string)));
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Default => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Default);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("default");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("default");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Goto => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Goto);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string, preproc_state) => {
-                        string.push_str("goto");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("goto");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Sizeof => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Sizeof);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("sizeof");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("sizeof");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Volatile => {
-                match state {
-                    ParserState::Normal => {
-                        state = ParserState::InType("volatile".to_string(), LastType::PrefixMod);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("volatile");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("volatile");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    ParserState::InType(mut string, LastType::PrefixMod) => {
-                        string.push_str(" volatile");
-                        state = ParserState::InType(string,LastType::PrefixMod);
-                        continue;
-                    },
-                    ParserState::InType(mut string,_) => {
-                        return Err(LexerError::BadType(format!("{} volatile",string)));
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Do => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Do);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string, preproc_state) => {
-                        string.push_str("do");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("do");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::If => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::If);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,mut preproc_state) => {
-                        string.push_str("if");
-                        match preproc_state {
-                            PreprocessorState::None => {
-                                preproc_state = PreprocessorState::InIf(false);
-                            },
-                            _ => {},
+    tokens
+}
+
+/// Phase two: turns raw tokens into tokens. Preprocessor lines become a single token,
+/// literals are decoded and whitespace and comments become trivia.
+struct Cooker<'input> {
+    input: &'input str,
+    tokens: Vec<(Token, Range<usize>)>,
+    trivia: TriviaCollector,
+    errors: Vec<Diagnostic>,
+}
+
+impl<'input> Cooker<'input> {
+    fn push(&mut self, token: Token, span: Range<usize>) {
+        self.tokens.push((token, span));
+    }
+
+    fn error(&mut self, error: LexerError, span: Range<usize>) {
+        self.errors.push(Diagnostic {error, span});
+    }
+
+    fn trivia(&mut self, trivia: Trivia) {
+        self.trivia.push(trivia);
+    }
+
+    /// Consumes a preprocessor line starting at the `#` at `start` and returns the index of
+    /// the first raw token after it. Lines ending in a backslash continue onto the next line.
+    fn directive(&mut self, raw: &[RawToken], start: usize) -> usize {
+        let mut end = start + 1;
+        let mut continued = false;
+        while end < raw.len() {
+            match raw[end].kind {
+                TokenPreparse::Newline | TokenPreparse::CarriageReturnNewline if !continued => {
+                    end += 1;
+                    break;
+                },
+                TokenPreparse::Backslash => continued = true,
+                TokenPreparse::Whitespace(_) => {},
+                _ => continued = false,
+            }
+            end += 1;
+        }
+
+        let span = raw[start].span.start..raw.get(end - 1).map_or(self.input.len(), |token| token.span.end);
+        let text = self.input[span.clone()].to_string();
+        let newline = text.ends_with('\n');
+        let name = raw[start + 1..end].iter()
+            .find(|token| !matches!(token.kind, TokenPreparse::Whitespace(_)))
+            .map(|token| &self.input[token.span.clone()]);
+        let token = match name {
+            Some("include") => Token::Include(text),
+            Some("define") => Token::Macro(text),
+            _ => Token::Preprocessor(text),
+        };
+        self.push(token, span);
+        if newline {
+            self.trivia.end_line(self.tokens.len());
+        }
+
+        end
+    }
+
+    fn cook(&mut self, raw: Vec<RawToken<'input>>) {
+        let mut line_start = true;
+        let mut index = 0;
+
+        while index < raw.len() {
+            self.trivia.attach(self.tokens.len());
+            let RawToken {kind, span} = &raw[index];
+            let span = span.clone();
+            index += 1;
+
+            match *kind {
+                TokenPreparse::Newline => {
+                    self.trivia(Trivia::Whitespace("\n".to_string()));
+                    line_start = true;
+                    continue;
+                },
+                TokenPreparse::CarriageReturnNewline => {
+                    self.trivia(Trivia::Whitespace("\r\n".to_string()));
+                    line_start = true;
+                    continue;
+                },
+                TokenPreparse::Whitespace(space) => self.trivia(Trivia::Whitespace(space.to_string())),
+                TokenPreparse::LineComment(comment) => self.trivia(Trivia::comment(comment)),
+                TokenPreparse::BlockComment((comment, terminated)) => {
+                    if !terminated {
+                        self.error(LexerError::UnterminatedComment, span);
+                    }
+                    self.trivia(Trivia::comment(comment));
+                },
+                TokenPreparse::Hash if line_start => {
+                    index = self.directive(&raw, index - 1);
+                    continue;
+                },
+                TokenPreparse::DoubleHash => {
+                    self.error(LexerError::UnrecognizedToken("##".to_string()), span);
+                },
+                TokenPreparse::String((string, terminated)) => {
+                    if !terminated {
+                        self.error(LexerError::UnterminatedString, span);
+                    }
+                    else {
+                        match literal::string_literal(string) {
+                            Ok(string) => self.push(Token::String(string), span),
+                            Err(error) => self.error(error, span),
                         }
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("if");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Short => {
-                match state {
-                    ParserState::Normal => {
-                        state = ParserState::InType("short".to_string(), LastType::Type);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("short");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("short");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    ParserState::InType(mut string, LastType::SuffixMod) => {
-                        return Err(LexerError::BadType(format!("{} short",string)));
-                    },
-                    ParserState::InType(mut string, _) => {
-                        string.push_str(" short");
-                        state = ParserState::InType(string,LastType::Type);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Unsigned => {
-                match state {
-                    ParserState::Normal => {
-                        state = ParserState::InType("unsigned".to_string(), LastType::Type);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("unsigned");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("unsigned");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    ParserState::InType(mut string, LastType::SuffixMod) => {
-                        return Err(LexerError::BadType(format!("{} unsigned",string)));
-                    },
-                    ParserState::InType(mut string, _) => {
-                        string.push_str(" unsigned");
-                        state = ParserState::InType(string,LastType::Type);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::While => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::While);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("while");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("while");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Default => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Default);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("default");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("default");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Static => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Static);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("static");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("static");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Const => {
-                match state {
-                    ParserState::Normal => {
-                        state = ParserState::InType("const".to_string(), LastType::PrefixMod);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("const");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("const");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    ParserState::InType(mut string, LastType::PrefixMod) => {
-                        string.push_str(" const");
-                        state = ParserState::InType(string,LastType::PrefixMod);
                     }
-                    ParserState::InType(mut string, _) => {
-                        return Err(LexerError::BadType(format!("{} const",string)));
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Float => {
-                match state {
-                    ParserState::Normal => {
-                        state = ParserState::InType("float".to_string(), LastType::Type);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("float");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("float");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    ParserState::InType(mut string, LastType::PrefixMod) => {
-                        string.push_str(" float");
-                        state = ParserState::InType(string,LastType::Type);
-                    },
-                    ParserState::InType(mut string, _) => {
-                        return Err(LexerError::BadType(format!("{} float",string)));
-                    },
-
-                    _ => {},
-                }
-            },
-            TokenPreparse::Restrict => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Restrict);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("restrict");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("restrict");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Bool => {
-                match state {
-                    ParserState::Normal => {
-                        state = ParserState::InType("bool".to_string(), LastType::Type);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string, preproc_state) => {
-                        string.push_str("bool");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("bool");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    ParserState::InType(mut string, LastType::PrefixMod) => {
-                        string.push_str(" bool");
-                        state = ParserState::InType(string,LastType::Type);
-                    },
-                    ParserState::InType(mut string, _) => {
-                        return Err(LexerError::BadType(format!("{} bool",string)));
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Complex => {
-                match state {
-                    ParserState::Normal => {
-                        state = ParserState::InType("complex".to_string(), LastType::SuffixMod);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("complex");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("complex");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    ParserState::InType(mut string, LastType::Type) => {
-                        string.push_str(" complex");
-                        state = ParserState::InType(string,LastType::SuffixMod);
-                    },
-                    ParserState::InType(mut string, _) => {
-                        return Err(LexerError::BadType(format!("{} complex",string)));
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Imaginary => {
-                match state {
-                    ParserState::Normal => {
-                        state = ParserState::InType("imaginary".to_string(), LastType::SuffixMod);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("imaginary");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("imaginary");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    ParserState::InType(mut string, LastType::Type) => {
-                        string.push_str(" imaginary");
-                        state = ParserState::InType(string,LastType::SuffixMod);
-                    },
-                    ParserState::InType(mut string, _) => {
-                        return Err(LexerError::BadType(format!("{} imaginary",string)));
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Inline => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Inline);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("inline");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("inline");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Noreturn => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Noreturn);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("_Noreturn");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("_Noreturn");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::ThreadLocal => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::ThreadLocal);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("thread_local");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("thread_local");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Goto => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Goto);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("goto");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("goto");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Generic => {
-                match  state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Generic);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("generic");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("generic");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::StaticAssert => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::StaticAssert);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("static_assert");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("static_assert");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Alignas => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Alignas);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("alignas");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("alignas");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Alignof => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Alignof);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("alignof");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("alignof");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Atomic => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Atomic);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("atomic");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("atomic");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Nullptr => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Nullptr);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string, preproc_state) => {
-                        string.push_str("nullptr");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("nullptr");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::ConstExpr => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::ConstExpr);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("constexpr");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("constexpr");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::True => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::True);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("true");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("true");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::False => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::False);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("false");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("false");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Typeof => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Typeof);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("typeof");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("typeof");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::TypeofUnqual => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::TypeofUnqual);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("typeof_unqual");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("typeof_unqual");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Private => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Private);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("private");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("private");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Class => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Class);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("class");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("class");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Word(word) => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Word(word.to_string()));
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,mut preproc_state) => {
-                        string.push_str(word);
-                        match preproc_state {
-                            PreprocessorState::None => {
-                                match word {
-                                    "define" => {
-                                        preproc_state = PreprocessorState::InDefine(false);
-                                    },
-                                    "include" => {
-                                        preproc_state = PreprocessorState::InInclude;
-                                    },
-                                    "if" => {
-                                        preproc_state = PreprocessorState::InIf(false);
-                                    },
-                                    "ifdef" => {
-                                        preproc_state = PreprocessorState::InIf(false);
-                                    },
-                                    "ifndef" => {
-                                        preproc_state = PreprocessorState::InIf(false);
-                                    },
-                                    "error" => {
-                                        preproc_state = PreprocessorState::InError;
-                                    },
-                                    "pragma" => {
-                                        preproc_state = PreprocessorState::InPragma;
-                                    },
-                                    "undef" => {
-                                        preproc_state = PreprocessorState::InUndef;
-                                    },
-                                    _ => {
-                                        preproc_state = PreprocessorState::HashOperator;
-                                    }
-                                }
-                            },
-                            PreprocessorState::InIf(false) => {
-
-                                match word {
-                                    "endif" => {
-                                        preproc_state = PreprocessorState::InIf(true);
-                                    },
-                                    _ => {},
-                                }
-                            }
-                            _ => {},
+                },
+                TokenPreparse::Character((character, terminated)) => {
+                    if !terminated {
+                        self.error(LexerError::UnterminatedCharacter, span);
+                    }
+                    else {
+                        match literal::char_literal(character) {
+                            Ok(character) => self.push(Token::Character(character), span),
+                            Err(error) => self.error(error, span),
                         }
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str(word);
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    ParserState::InType(string, _) => {
-                        tokens.push(Token::Type(string.to_string()));
-                        tokens.push(Token::Word(word.to_string()));
-                        state = ParserState::Normal;
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Number(number) => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Number(number.to_string()));
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str(&number.to_string());
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str(&number.to_string());
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Tagged => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Tagged);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("tagged");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("tagged");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Abstract => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Abstract);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("abstract");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("abstract");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-            },
-            TokenPreparse::Operator => {
-                match state {
-                    ParserState::Normal => {
-                        tokens.push(Token::Operator);
-                        continue;
-                    },
-                    ParserState::InPreprocessor(mut string,preproc_state) => {
-                        string.push_str("operator");
-                        state = ParserState::InPreprocessor(string,preproc_state);
-                        continue;
-                    },
-                    ParserState::InString(mut string,_) => {
-                        string.push_str("operator");
-                        state = ParserState::InString(string,false);
-                        continue;
-                    },
-                    _ => {},
-                }
-
-            },
-            _ => return Err(LexerError::UnrecognizedToken(format!("{:?}",token))),
+                    }
+                },
+                TokenPreparse::Number(number) => {
+                    match literal::number_literal(number) {
+                        Ok(number) => self.push(Token::Number(number), span),
+                        Err(error) => self.error(error, span),
+                    }
+                },
+                ref kind => {
+                    if let Some(token) = plain_token(kind) {
+                        self.push(token, span);
+                    }
+                },
+            }
+            line_start = matches!(raw[index - 1].kind,
+                TokenPreparse::Whitespace(_) | TokenPreparse::LineComment(_) | TokenPreparse::BlockComment(_)) && line_start;
         }
+    }
+}
 
 
+pub fn lex(input: &str) -> Result<Vec<Token>, Vec<Diagnostic>> {
+    Ok(lex_with_trivia(input)?.into_iter().map(|token| token.token).collect())
+}
+
+/// Lexes like [`lex`] but keeps whitespace and comments attached to the neighbouring tokens.
+/// Lexing does not stop at the first error; every error in the input is returned.
+pub fn lex_with_trivia(input: &str) -> Result<Vec<TokenWithTrivia>, Vec<Diagnostic>> {
+    let mut errors = Vec::new();
+    let raw = raw_tokens(input, &mut errors);
 
+    let mut cooker = Cooker {
+        input,
+        tokens: Vec::with_capacity(raw.len()),
+        trivia: TriviaCollector::default(),
+        errors,
+    };
+    cooker.cook(raw);
 
+    let Cooker {tokens, trivia, mut errors, ..} = cooker;
+    let tokens = concat_adjacent_strings(trivia.finish(tokens), &mut errors);
+    if tokens.is_empty() && errors.is_empty() {
+        errors.push(Diagnostic {error: LexerError::Empty, span: 0..input.len()});
     }
 
-    if tokens.len() == 0 {
-        return Err(LexerError::Empty);
+    if errors.is_empty() {
+        Ok(tokens)
+    }
+    else {
+        errors.sort_by_key(|diagnostic| diagnostic.span.start);
+        Err(errors)
     }
+}
+
+/// Joins adjacent string literal tokens into one literal as C does in translation phase 6.
+/// The joined token keeps the leading trivia of the first piece and the trailing trivia of the last,
+/// and the pieces themselves.
+fn concat_adjacent_strings(tokens: Vec<TokenWithTrivia>, errors: &mut Vec<Diagnostic>) -> Vec<TokenWithTrivia> {
+    let mut output = Vec::with_capacity(tokens.len());
+    let mut pieces = Vec::new();
 
-    Ok(tokens)
+    for token in tokens {
+        match token.token {
+            Token::String(_) => {
+                pieces.push(token);
+            },
+            _ => {
+                flush_string_pieces(&mut pieces, &mut output, errors);
+                output.push(token);
+            },
+        }
+    }
+    flush_string_pieces(&mut pieces, &mut output, errors);
+
+    output
+}
+
+fn flush_string_pieces(pieces: &mut Vec<TokenWithTrivia>, output: &mut Vec<TokenWithTrivia>, errors: &mut Vec<Diagnostic>) {
+    if pieces.len() < 2 {
+        output.append(pieces);
+        return;
+    }
+
+    let raw = pieces.iter().map(|piece| piece.token.to_string()).collect::<Vec<String>>();
+    let raw = raw.iter().map(String::as_str).collect::<Vec<&str>>();
+    let leading = std::mem::take(&mut pieces[0].leading);
+    let start = pieces[0].span.start;
+    let Some(last) = pieces.last_mut() else {
+        return;
+    };
+    let trailing = std::mem::take(&mut last.trailing);
+    let end = last.span.end;
+    let pieces = std::mem::take(pieces);
+
+    match literal::concat_string_literals(&raw) {
+        Ok(string) => output.push(TokenWithTrivia {
+            token: Token::String(string),
+            span: start..end,
+            leading,
+            trailing,
+            pieces,
+        }),
+        Err(error) => errors.push(Diagnostic {error, span: start..end}),
+    }
 }
 
 
+#[cfg(test)]
 mod lexer_test {
     use super::*;
-    use logos::{Logos, Lexer};
+    use crate::literal::{Encoding, NumberType, NumberValue};
+
+    fn first_error(input: &str) -> LexerError {
+        match lex(input) {
+            Ok(tokens) => panic!("Expected an error, got {:?}", tokens),
+            Err(errors) => errors[0].error.clone(),
+        }
+    }
 
     #[test]
-    fn test_parser() {
-        let input = "int main() { return 0; }";
-        let mut lexer = TokenPreparse::lexer("int main() { return 0; }");
-
-        println!("{:?}", lexer.next());
-        println!("{:?}", lexer.next());
-        println!("{:?}", lexer.next());
-        println!("{:?}", lexer.next());
+    fn test_string_escapes() {
+        let tokens = lex(r#"char *s = "tab\there \"quoted\" \x41\101\n";"#).unwrap();
+        match &tokens[4] {
+            Token::String(string) => {
+                assert_eq!(string.raw, r#""tab\there \"quoted\" \x41\101\n""#);
+                assert_eq!(string.to_text(), "tab\there \"quoted\" AA\n");
+            },
+            token => panic!("Expected string, got {:?}", token),
+        }
     }
 
     #[test]
-    fn test_preproc_if() {
-        let input = "#if 1\nint main() { return 0; }\n#endif";
-        println!("{}", input);
-        let mut lexer = TokenPreparse::lexer(input);
-
-        println!("{:?}", lexer.next());
-        println!("{:?}", lexer.next());
-        println!("{:?}", lexer.next());
-        println!("{:?}", lexer.next());
-        println!("{:?}", lexer.next());
-        println!("{:?}", lexer.next());
-        println!("{:?}", lexer.next());
-        println!("{:?}", lexer.next());
-        println!("{:?}", lexer.next());
-        println!("{:?}", lexer.next());
-        println!("{:?}", lexer.next());
-        println!("{:?}", lexer.next());
-        println!("{:?}", lexer.next());
-        println!("{:?}", lexer.next());
-        println!("{:?}", lexer.next());
-        println!("{:?}", lexer.next());
-        println!("{:?}", lexer.next());
-        println!("{:?}", lexer.next());
-        println!("{:?}", lexer.next());
-        println!("{:?}", lexer.next());
-        println!("{:?}", lexer.next());
-        println!("{:?}", lexer.next());
-        println!("{:?}", lexer.next());
-        println!("{:?}", lexer.next());
+    fn test_string_prefixes() {
+        let tokens = lex(r#"u8"a"; u"b"; U"c"; L"d"; u8 L;"#).unwrap();
+        let encodings = tokens.iter().filter_map(|token| match token {
+            Token::String(string) => Some(string.encoding),
+            _ => None,
+        }).collect::<Vec<Encoding>>();
+        assert_eq!(encodings, vec![Encoding::Utf8, Encoding::Utf16, Encoding::Utf32, Encoding::Wide]);
+        assert_eq!(tokens[8], Token::Word("u8".to_string()));
+        assert_eq!(tokens[9], Token::Word("L".to_string()));
     }
 
+    #[test]
+    fn test_string_concatenation() {
+        let tokens = lex("char *s = \"Hello, \"\n    \"World\" L\"!\";").unwrap();
+        assert_eq!(tokens.len(), 6);
+        match &tokens[4] {
+            Token::String(string) => {
+                assert_eq!(string.encoding, Encoding::Wide);
+                assert_eq!(string.to_text(), "Hello, World!");
+            },
+            token => panic!("Expected string, got {:?}", token),
+        }
+        assert!(matches!(first_error(r#"u"a" U"b";"#), LexerError::MismatchedStringPrefix(_)));
+    }
 
     #[test]
-    fn test_parser_int() {
-        let input = "int main() { return 0; }";
-        let mut lexer = TokenPreparse::lexer("int");
-
-        println!("{:?}", lexer.next());
-        println!("{:?}", lexer.next());
-        println!("{:?}", lexer.next());
-        println!("{:?}", lexer.next());
+    fn test_character_literals() {
+        let tokens = lex(r"x = '\n' + '\'' + 'ab' + L'\x263A' + '\177';").unwrap();
+        let values = tokens.iter().filter_map(|token| match token {
+            Token::Character(character) => Some(character.value),
+            _ => None,
+        }).collect::<Vec<i64>>();
+        assert_eq!(values, vec![10, 39, 0x6162, 0x263a, 127]);
     }
-    
+
     #[test]
-    fn test_parser_hello_world() {
-        let input = "#include <stdio.h>\n int main() {\n printf(\"Hello, World!\");\n return 0;\n }";
-        let mut lexer = TokenPreparse::lexer(input);
+    fn test_unterminated_literals() {
+        assert!(matches!(first_error("char *s = \"abc;\n"), LexerError::UnterminatedString));
+        assert!(matches!(first_error("char c = 'a;\n"), LexerError::UnterminatedCharacter));
+        assert!(matches!(first_error(r#"char *s = "\q";"#), LexerError::BadEscape(_)));
+        assert!(lex("#error don't do this\nint a;\n").is_ok());
+    }
 
-        println!("{:?}", lexer.next());
-        println!("{:?}", lexer.next());
-        println!("{:?}", lexer.next());
-        println!("{:?}", lexer.next());
-        println!("{:?}", lexer.next());
-        println!("{:?}", lexer.next());
-        println!("{:?}", lexer.next());
-        println!("{:?}", lexer.next());
+    #[test]
+    fn test_numbers() {
+        let tokens = lex("x = 0x1.8p3 + 1'000 + .5f + 1e10 + 0b11u + a.b;").unwrap();
+        let numbers = tokens.iter().filter_map(|token| match token {
+            Token::Number(number) => Some((number.raw.as_str(), number.value, number.c_type)),
+            _ => None,
+        }).collect::<Vec<_>>();
+        assert_eq!(numbers, vec![
+            ("0x1.8p3", NumberValue::Floating(12.0), NumberType::Double),
+            ("1'000", NumberValue::Integer(1000), NumberType::Int),
+            (".5f", NumberValue::Floating(0.5), NumberType::Float),
+            ("1e10", NumberValue::Floating(1e10), NumberType::Double),
+            ("0b11u", NumberValue::Integer(3), NumberType::UnsignedInt),
+        ]);
+        assert_eq!(&tokens[tokens.len() - 4..], &[
+            Token::Word("a".to_string()), Token::Period, Token::Word("b".to_string()), Token::SemiColon,
+        ]);
+        assert!(matches!(first_error("x = 99999999999999999999;"), LexerError::NumberOutOfRange(_)));
+        assert!(matches!(first_error("x = 1.2.3;"), LexerError::BadNumber(_)));
+        assert!(lex("#define BAD 1.2.3\nint a;\n").is_ok());
+    }
 
+    #[test]
+    fn test_comments() {
+        let tokens = lex("int a; // line comment\n/* block\n comment */ int b; /**/\n").unwrap();
+        assert_eq!(tokens.len(), 6);
+        assert!(matches!(first_error("int a; /* never closed\n"), LexerError::UnterminatedComment));
+        assert!(lex("#define A 1 // one\nint a;\n").is_ok());
+    }
+
+    #[test]
+    fn test_trivia() {
+        let tokens = lex_with_trivia("int a; // after a\n\n  /* before b */ int b;").unwrap();
+        assert_eq!(tokens[2].token, Token::SemiColon);
+        assert_eq!(tokens[2].trailing, vec![
+            Trivia::Whitespace(" ".to_string()),
+            Trivia::Comment("// after a".to_string()),
+        ]);
+        assert_eq!(tokens[3].leading, vec![
+            Trivia::Whitespace("\n\n  ".to_string()),
+            Trivia::Comment("/* before b */".to_string()),
+            Trivia::Whitespace(" ".to_string()),
+        ]);
+        assert_eq!(tokens[0].trailing, vec![Trivia::Whitespace(" ".to_string())]);
+        assert!(tokens[1].leading.is_empty());
+
+        // A preprocessor line ends its own line, so the comment below it leads `int`.
+        let tokens = lex_with_trivia("#define N 1\n// below\nint c;").unwrap();
+        assert!(tokens[0].trailing.is_empty());
+        assert_eq!(tokens[1].leading[0], Trivia::Comment("// below".to_string()));
+    }
+
+    #[test]
+    fn test_doc_comments() {
+        let tokens = lex_with_trivia("/// first\n/// second\nint a; //// not docs\n/**\n * Block docs.\n */\nint b; /***/ int c;").unwrap();
+        assert_eq!(tokens[0].doc_comment(), Some("first\nsecond".to_string()));
+        assert_eq!(tokens[3].doc_comment(), Some("Block docs.".to_string()));
+        assert_eq!(tokens[6].doc_comment(), None);
+        assert_eq!(tokens[2].trailing[1], Trivia::Comment("//// not docs".to_string()));
+    }
+
+    #[test]
+    fn test_directives() {
+        let tokens = lex("#include <stdio.h>\n#define MAX(a, b) \\\n    ((a) > (b) ? (a) : (b))\n  #if 1\nint x = 1 # 2;\n#endif").unwrap();
+        assert_eq!(tokens[0], Token::Include("#include <stdio.h>\n".to_string()));
+        assert_eq!(tokens[1], Token::Macro("#define MAX(a, b) \\\n    ((a) > (b) ? (a) : (b))\n".to_string()));
+        assert_eq!(tokens[2], Token::Preprocessor("#if 1\n".to_string()));
+        assert_eq!(&tokens[6..8], &[Token::Number(literal::number_literal("1").unwrap()), Token::Hash]);
+        assert_eq!(tokens.last(), Some(&Token::Preprocessor("#endif".to_string())));
+    }
+
+    #[test]
+    fn test_type_keywords() {
+        let tokens = lex("const unsigned long long int x; long double complex z;").unwrap();
+        assert_eq!(&tokens[0..6], &[Token::Const, Token::Unsigned, Token::Long, Token::Long, Token::Int, Token::Word("x".to_string())]);
+        assert_eq!(&tokens[7..10], &[Token::Long, Token::Double, Token::Complex]);
+    }
+
+    #[test]
+    fn test_underscore_keywords() {
+        let tokens = lex("_Static_assert _Alignas _Alignof _Thread_local _Noreturn _Generic").unwrap();
+        assert_eq!(tokens, vec![Token::StaticAssert, Token::Alignas, Token::Alignof, Token::ThreadLocal, Token::Noreturn, Token::GenericSelection]);
+    }
+
+    #[test]
+    fn test_reports_every_error() {
+        let errors = lex("int a = 1.2.3;\nint @b;\nchar *s = \"\\q\";\nchar c = 'x").unwrap_err();
+        let spans = errors.iter().map(|diagnostic| diagnostic.span.clone()).collect::<Vec<_>>();
+        assert_eq!(spans, vec![8..13, 19..20, 33..37, 48..50]);
+        assert!(matches!(&errors[..], [
+            Diagnostic {error: LexerError::BadNumber(_), ..},
+            Diagnostic {error: LexerError::UnrecognizedToken(token), ..},
+            Diagnostic {error: LexerError::BadEscape(_), ..},
+            Diagnostic {error: LexerError::UnterminatedCharacter, ..},
+        ] if token == "@"));
+        assert!(matches!(first_error(""), LexerError::Empty));
+    }
+
+    #[test]
+    fn test_spans() {
+        let input = "unsigned  int x = \"a\" \"b\";";
+        let tokens = lex_with_trivia(input).unwrap();
+        let spelled = tokens.iter().map(|token| &input[token.span.clone()]).collect::<Vec<&str>>();
+        assert_eq!(spelled, vec!["unsigned", "int", "x", "=", "\"a\" \"b\"", ";"]);
     }
 
     #[test]
-    fn test_full_parser() {
+    fn test_hello_world() {
         let input = "#include <stdio.h>\n int main() {\n printf(\"Hello, World!\");\n return 0;\n }";
-        
-        println!("{:?}", lex(input));
+        let tokens = lex(input).unwrap();
+        let text = tokens.iter().map(Token::to_string).collect::<Vec<String>>();
+        assert_eq!(text, ["#include <stdio.h>\n", "int", "main", "(", ")", "{", "printf", "(", "\"Hello, World!\"", ")", ";",
+                          "return", "0", ";", "}"]);
+        assert!(matches!(&tokens[1], Token::Int));
+        assert!(matches!(&tokens[8], Token::String(string) if string.to_text() == "Hello, World!"));
     }
 
 }
@@ -1,6 +1,3 @@
-mod ast;
-mod logos_lexer;
-mod parser;
 
 fn main() {
     println!("Hello, world!");
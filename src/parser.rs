@@ -1,900 +1,1261 @@
-
+use std::collections::HashMap;
+use std::ops::Range;
 
 use crate::ast::*;
-use crate::logos_lexer::Token;
+use crate::logos_lexer::{Token, TokenWithTrivia};
+
+
+/// Logs a parser decision when the `tracing` feature is enabled, and compiles to nothing otherwise.
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::trace!($($arg)*);
+    };
+}
 
-use std::ops::{Range, RangeInclusive};
+/// The tokens a parser reads, with or without the trivia around them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Tokens<'a> {
+    Plain(&'a [Token]),
+    Trivia(&'a [TokenWithTrivia]),
+}
+
+impl<'a> Tokens<'a> {
+    fn get(&self, index: usize) -> Option<&'a Token> {
+        match self {
+            Tokens::Plain(tokens) => tokens.get(index),
+            Tokens::Trivia(tokens) => tokens.get(index).map(|token| &token.token),
+        }
+    }
 
+    fn len(&self) -> usize {
+        match self {
+            Tokens::Plain(tokens) => tokens.len(),
+            Tokens::Trivia(tokens) => tokens.len(),
+        }
+    }
 
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
 
+/// A recursive descent parser over borrowed tokens. It only moves forward through them,
+/// looking at most a few tokens ahead, so parsing takes time linear in their number.
 #[derive(Debug, PartialEq)]
-pub struct Parser {
-    tokens: Vec<Token>,
+pub struct Parser<'a> {
+    tokens: Tokens<'a>,
     head: usize,
-    node_buffer: Vec<AstNode>,
+    depth: usize,
+    /// The names declared in each enclosing scope, innermost last, and whether each
+    /// names a type. A word is read as a type in a block only if it names one here.
+    scopes: Vec<HashMap<String, bool>>,
 }
 
-pub trait Merge<R> {
-    fn merge_tokens(&mut self, other: R);
+/// How deeply blocks, statements, expressions and declarators may nest. Deeper input is
+/// rejected rather than allowed to overflow the stack.
+const MAX_DEPTH: usize = 128;
+
+/// Whether a token can start the declaration specifiers of a declaration.
+fn is_specifier(token: &Token) -> bool {
+    matches!(token,
+        Token::Typedef | Token::Extern | Token::Static | Token::Auto | Token::Register |
+        Token::ThreadLocal | Token::ConstExpr | Token::Inline | Token::Noreturn | Token::Alignas |
+        Token::Const | Token::Volatile | Token::Restrict | Token::Atomic | Token::Typeof | Token::TypeofUnqual |
+        Token::Void | Token::Bool | Token::Char | Token::Short | Token::Int | Token::Long |
+        Token::Float | Token::Double | Token::Signed | Token::Unsigned | Token::Complex | Token::Imaginary |
+        Token::Struct | Token::Union | Token::Enum | Token::Tagged | Token::Generic)
 }
 
-impl Merge<Range<usize>> for Parser {
-    fn merge_tokens(&mut self, range: Range<usize>) {
-        let mut merge = self.tokens[range.clone()].iter().fold(String::new(), |mut acc, token| {
-            acc.push_str(&token.to_string());
-            acc.push_str(" ");
-            acc
-        });
-        merge.pop();
+/// Whether a token can start a type name, as in a cast or `sizeof`.
+fn is_type_name_start(token: &Token) -> bool {
+    is_specifier(token) && !matches!(token,
+        Token::Typedef | Token::Extern | Token::Static | Token::Auto | Token::Register |
+        Token::ThreadLocal | Token::ConstExpr | Token::Inline | Token::Noreturn | Token::Alignas)
+}
+
+/// Whether a statement anywhere in a block returns, for checking `noreturn` functions.
+fn contains_return(statements: &[Statement]) -> bool {
+    statements.iter().any(|statement| match statement {
+        Statement::Return(_) => true,
+        Statement::If(_, body, else_) => block_returns(body) || else_.as_deref().is_some_and(block_returns),
+        Statement::While(_, body) | Statement::DoWhile(_, body) | Statement::For(_, _, _, body) => block_returns(body),
+        Statement::Switch(_, cases) => cases.iter().any(|case| block_returns(&case.body)),
+        Statement::Attributed(_, statement) => contains_return(std::slice::from_ref(statement)),
+        Statement::Block(block) => {
+            let CodeBlock::Code(list) = block.as_ref();
+            contains_return(&list.statements)
+        },
+        _ => false,
+    })
+}
 
-        self.tokens.drain(range.clone());
-        self.tokens.insert(range.start, Token::Type(merge));
-        println!("Merged tokens: {:?}", self.tokens);
+fn block_returns(body: &BlockOrStatement) -> bool {
+    match body {
+        BlockOrStatement::Block(CodeBlock::Code(list)) => contains_return(&list.statements),
+        BlockOrStatement::Statement(statement) => contains_return(std::slice::from_ref(statement)),
     }
 }
 
-impl Merge<RangeInclusive<usize>> for Parser {
-    fn merge_tokens(&mut self, range: RangeInclusive<usize>) {
-        let mut merge = self.tokens[range.clone()].iter().fold(String::new(), |mut acc, token| {
-            acc.push_str(&token.to_string());
-            acc.push_str(" ");
-            acc
-        });
-        merge.pop();
+/// One step of a declarator, applied to the type built so far.
+enum Derivation {
+    Pointer(TypeQualifiers),
+    Array(VariableArray),
+    Function(Vec<FunctionArgument>),
+}
+
+/// Applies the steps of a declarator to the base type of its declaration.
+fn derive(base: Type, derivations: Vec<Derivation>) -> Result<DeclaredType, String> {
+    if base.qualifiers.restrict && !matches!(base.specifier, TypeSpecifier::Named(_)) &&
+        !matches!(derivations.first(), Some(Derivation::Pointer(_))) {
+        return Err("Restrict can only be used with pointers".to_string());
+    }
+
+    let mut type_ = DeclaredType::Base(base);
+    for derivation in derivations {
+        type_ = match derivation {
+            Derivation::Pointer(qualifiers) => {
+                if qualifiers.restrict && type_.is_function() {
+                    return Err("Restrict cannot be used on a pointer to a function".to_string());
+                }
+                DeclaredType::Pointer(Box::new(type_), qualifiers)
+            },
+            Derivation::Array(size) => {
+                if type_.is_function() {
+                    return Err("Arrays of functions are not allowed".to_string());
+                }
+                DeclaredType::Array(Box::new(type_), size)
+            },
+            Derivation::Function(arguments) => {
+                if matches!(type_, DeclaredType::Function(..) | DeclaredType::Array(..)) {
+                    return Err("Functions cannot return functions or arrays".to_string());
+                }
+                DeclaredType::Function(Box::new(type_), arguments)
+            },
+        };
+    }
+    Ok(type_)
+}
 
-        self.tokens.drain(range.clone());
-        self.tokens.insert(*range.start(), Token::Type(merge));
-        println!("Merged tokens: {:?}", self.tokens);
+/// Checks that a flexible array member such as `char data[];` comes last, after another
+/// named member, and that unions have none. A class passes its fields without its methods.
+fn check_flexible_array<'m>(members: impl IntoIterator<Item = &'m Member>, union_: bool) -> Result<(), String> {
+    let mut members = members.into_iter().peekable();
+    let mut first = true;
+    while let Some(member) = members.next() {
+        let last = members.peek().is_none();
+        if let Member::Variables(variable_list) = member {
+            for (j, variable) in variable_list.variables.iter().enumerate() {
+                if !matches!(variable.type_, DeclaredType::Array(_, VariableArray::NoSize)) {
+                    continue;
+                }
+                if union_ {
+                    return Err(format!("Flexible array member {} is not allowed in a union", variable.name));
+                }
+                if !last || j + 1 != variable_list.variables.len() {
+                    return Err(format!("Flexible array member {} must be the last member", variable.name));
+                }
+                if first && j == 0 {
+                    return Err(format!("Flexible array member {} must follow another named member", variable.name));
+                }
+            }
+        }
+        first = false;
     }
+    Ok(())
 }
 
-impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Parser {
+impl<'a> Parser<'a> {
+    pub fn new(tokens: &'a [Token]) -> Parser<'a> {
         Parser {
-            tokens: tokens,
+            tokens: Tokens::Plain(tokens),
             head: 0,
-            node_buffer: Vec::new(),
+            depth: 0,
+            scopes: vec![HashMap::new()],
         }
     }
 
+    /// Creates a parser that attaches the doc comments found in the trivia to declarations.
+    pub fn with_trivia(tokens: &'a [TokenWithTrivia]) -> Parser<'a> {
+        Parser {
+            tokens: Tokens::Trivia(tokens),
+            head: 0,
+            depth: 0,
+            scopes: vec![HashMap::new()],
+        }
+    }
 
-    fn preprocessors(&mut self) -> Result<Vec<Preprocessor>, String> {
-        let mut preprocessor = Vec::new();
+    /// The index of the token the parser is at in the tokens it was created with, which is
+    /// where the error is after `parse` fails.
+    pub fn position(&self) -> usize {
+        self.head
+    }
 
-        while self.head < self.tokens.len() {
-            match &self.tokens[self.head] {
-                Token::Preprocessor(data) => {
-                    preprocessor.push(Preprocessor {value: data.clone()});
-                    self.head += 1;
-                },
-                _ => {
-                    break;
-                },
-            }
+    /// The token at the head, so that running out of tokens is an error rather than a panic.
+    fn current(&self) -> Result<&'a Token, String> {
+        self.tokens.get(self.head).ok_or_else(|| "Unexpected end of file".to_string())
+    }
+
+    /// Runs `parse` one level of nesting deeper.
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T, String>) -> Result<T, String> {
+        if self.depth == MAX_DEPTH {
+            return Err(format!("Nesting deeper than {} levels is not supported", MAX_DEPTH));
         }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
 
-        Ok(preprocessor)
+    /// Runs `parse` in a new scope, so that the names it declares are forgotten after it.
+    fn scoped<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T, String>) -> Result<T, String> {
+        self.scopes.push(HashMap::new());
+        let result = parse(self);
+        self.scopes.pop();
+        result
     }
 
-    fn variable_value(&mut self) -> Result<VariableValue,String> {
+    /// Declares `name` in the innermost scope, as a type or as something else that hides
+    /// a type of the same name.
+    fn declare_name(&mut self, name: &str, type_: bool) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), type_);
+        }
+    }
 
-        match &self.tokens[self.head] {
-            Token::String(data) => {
-                self.head += 1;
-                Ok(VariableValue::String(data.clone()))
-            },
-            _ => {
-                Ok(VariableValue::Expression(self.expression(None)?))
-            },
+    /// Declares the names of a variable list: typedef names, the tagged union its
+    /// specifiers define, and the variables, which hide types of the same name.
+    fn declare_variables(&mut self, variable_list: &VariableList) {
+        if let Some(Definition::TaggedUnion(tagged)) = variable_list.specifiers.definition.as_deref() {
+            self.declare_name(&tagged.name, true);
+        }
+        let typedef = variable_list.specifiers.storage == Some(StorageClass::Typedef);
+        for variable in &variable_list.variables {
+            self.declare_name(&variable.name, typedef);
+        }
+    }
+
+    /// Declares the names a top-level statement introduces, as parsing it does. This lets
+    /// a parser that `seek`s into a file know the types declared before that point.
+    pub fn declare(&mut self, statement: &HeaderStatement) {
+        match statement {
+            HeaderStatement::Variable(variable_list) => self.declare_variables(variable_list),
+            HeaderStatement::Class(class) => self.declare_name(&class.name, true),
+            HeaderStatement::TaggedUnion(tagged) => self.declare_name(&tagged.name, true),
+            _ => {},
         }
+    }
 
+    /// The names declared at file scope so far, and whether each names a type.
+    pub fn file_scope(&self) -> &HashMap<String, bool> {
+        &self.scopes[0]
+    }
 
+    /// Whether `name` names a type in the current scope.
+    fn is_type(&self, name: &str) -> bool {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name)).copied().unwrap_or(false)
     }
 
-    fn variable_list(&mut self) -> Result<Vec<Variable>,String> {
+    /// Whether a token starts a declaration in a block: a specifier, or a word that names a type.
+    fn starts_declaration(&self, token: &Token) -> bool {
+        is_specifier(token) || matches!(token, Token::Word(name) if self.is_type(name))
+    }
 
-        let mut variable_list = Vec::new();
-        let mut var_name = String::new();
-        let mut pointer = 0;
-        let mut value = None;
-        let mut array = None;
-        let mut restrict = false;
-        
-        while self.head < self.tokens.len() {
-            match &self.tokens[self.head] {
-                Token::Word(name) => {
-                    var_name = name.clone();
-                    self.head += 1;
-                },
-                Token::Star => {
-                    pointer += 1;
-                    self.head += 1;
-                },
-                Token::Restrict => {
-                    if pointer != 0 {
-                        return Err("Restrict can only be used with pointers".to_string());
-                    }
-                    restrict = true;
-                    self.head += 1;
-                },
-                Token::Assignment => {
-                    value = Some(self.variable_value()?);
-                    self.head += 1;
-                },
-                Token::SemiColon => {
-                    variable_list.push(Variable::BasicVar {
-                        name: var_name.clone(),
-                        pointer: pointer,
-                        restrict: restrict,
-                        array: array,
-                        value: value,
-                    });
-                    pointer = 0;
-                    value = None;
-                    array = None;
-                    restrict = false;
-                    return Ok(variable_list);
-                },
-                Token::Comma => {
-                    variable_list.push(Variable::BasicVar {
-                        name: var_name.clone(),
-                        pointer: pointer,
-                        restrict: restrict,
-                        array: array,
-                        value: value,
-                    });
-                    pointer = 0;
-                    value = None;
-                    array = None;
-                    restrict = false;
-                    self.head += 1;
-                },
-                Token::LeftBracket => {
-                    array = Some(self.variable_array()?);
-                },
-                _ => {
-                    return Err(format!("Unexpected token in var list: {:?}", self.tokens[self.head]));
-                },
-            }
+    /// Whether a token starts a type name, as in a cast or `sizeof`.
+    fn starts_type_name(&self, token: &Token) -> bool {
+        is_type_name_start(token) || matches!(token, Token::Word(name) if self.is_type(name))
+    }
+
+    fn doc_comment(&self, index: usize) -> Option<String> {
+        match self.tokens {
+            Tokens::Plain(_) => None,
+            Tokens::Trivia(tokens) => tokens.get(index).and_then(TokenWithTrivia::doc_comment),
         }
-        Err("Unexpected end of file".to_string())
     }
 
-    fn variable_dec(&mut self) -> Result<AstNode, String> {
+    /// Parses storage classes, qualifiers, type specifiers and function specifiers in any order
+    /// and checks that they combine as C allows. A word is taken as a typedef or class name
+    /// when no other type specifier has been seen.
+    fn declaration_specifiers(&mut self) -> Result<DeclarationSpecifiers, String> {
+        let mut storage = None;
+        let mut thread_local = false;
+        let mut constexpr = false;
+        let mut inline = false;
+        let mut noreturn = false;
+        let mut alignas = Vec::new();
+        let mut attributes = Vec::new();
+        let mut qualifiers = TypeQualifiers::default();
+        let mut keywords = Vec::new();
+        let mut specifier = None;
+        let mut definition = None;
 
-        let start = self.head;
-        let mut struct_enum_union = false;
-        let mut node = AstNode::None;
-        let mut type_pos = 0;
-        let mut var_name = String::new();
-        let mut the_type = None;
-        let mut pointer = 0;
-        let mut value = None;
-        let mut restrict = false;
-        let mut variable_list = Vec::new();
-        let mut array = None;
-        let mut generic = false;
-        
-        
         while self.head < self.tokens.len() {
-            let token = self.tokens[self.head].clone();
-            match token {
-                Token::Type(_) => {
-                    type_pos = self.head;
-                    self.head += 1;
-                    //the_type = Some(Type::from_token(token.clone())?);
-                },
-                Token::Word(name) => {
-                    if struct_enum_union {
-                        self.merge_tokens(start..self.head);
-                        struct_enum_union = false;
-                        self.head = self.head - (self.head - start);
-                        
-                        the_type = Some(Type::from_token(self.tokens[start].clone())?);
-                    }
-                    else {
-                        if generic {
-                            the_type = Some(Type::from_token(self.tokens[type_pos].clone())?);
+            let token = self.current()?;
+            let class = match token {
+                Token::Typedef => Some(StorageClass::Typedef),
+                Token::Extern => Some(StorageClass::Extern),
+                Token::Static => Some(StorageClass::Static),
+                Token::Auto => Some(StorageClass::Auto),
+                Token::Register => Some(StorageClass::Register),
+                _ => None,
+            };
+            let keyword = match token {
+                Token::Void => Some(TypeKeyword::Void),
+                Token::Bool => Some(TypeKeyword::Bool),
+                Token::Char => Some(TypeKeyword::Char),
+                Token::Short => Some(TypeKeyword::Short),
+                Token::Int => Some(TypeKeyword::Int),
+                Token::Long => Some(TypeKeyword::Long),
+                Token::Float => Some(TypeKeyword::Float),
+                Token::Double => Some(TypeKeyword::Double),
+                Token::Signed => Some(TypeKeyword::Signed),
+                Token::Unsigned => Some(TypeKeyword::Unsigned),
+                Token::Complex => Some(TypeKeyword::Complex),
+                Token::Imaginary => Some(TypeKeyword::Imaginary),
+                _ => None,
+            };
+
+            if let Some(class) = class {
+                if let Some(previous) = storage {
+                    return Err(format!("Storage classes {:?} and {:?} cannot be combined", previous, class));
+                }
+                storage = Some(class);
+            }
+            else if let Some(keyword) = keyword {
+                keywords.push(keyword);
+            }
+            else {
+                match token {
+                    Token::ThreadLocal => thread_local = true,
+                    Token::ConstExpr => constexpr = true,
+                    Token::Inline => inline = true,
+                    Token::Noreturn => noreturn = true,
+                    Token::Const => qualifiers.const_ = true,
+                    Token::Volatile => qualifiers.volatile = true,
+                    Token::Restrict => qualifiers.restrict = true,
+                    Token::Atomic => qualifiers.atomic = true,
+                    Token::LeftBracket | Token::Attribute if self.at_attribute() => {
+                        attributes.extend(self.attributes()?);
+                        continue;
+                    },
+                    Token::Struct | Token::Union | Token::Enum | Token::Tagged => {
+                        let keyword = token.clone();
+                        self.head += 1;
+                        let mut tag_attributes = self.attributes()?;
+                        let name = match self.tokens.get(self.head) {
+                            Some(Token::Word(name)) => {
+                                let name = name.clone();
+                                self.head += 1;
+                                Some(name)
+                            },
+                            _ => None,
+                        };
+                        if self.tokens.get(self.head) == Some(&Token::LeftBrace) {
+                            self.head += 1;
+                            let mut defined = match keyword {
+                                Token::Struct => Definition::Struct(self.struct_dec(name.clone())?),
+                                Token::Union => Definition::Union(self.union_dec(name.clone())?),
+                                Token::Enum => Definition::Enum(self.enum_dec(name.clone())?),
+                                _ => {
+                                    let Some(name) = name.clone() else {
+                                        return Err("Tagged unions must be named".to_string());
+                                    };
+                                    Definition::TaggedUnion(self.tagged_union_dec(name)?)
+                                },
+                            };
+                            tag_attributes.extend(self.attributes()?);
+                            *defined.attributes_mut() = tag_attributes;
+                            definition = Some(Box::new(defined));
+                        }
+                        else if name.is_none() {
+                            return Err(format!("Expected a name after {}", keyword));
                         }
                         else {
-                            the_type = Some(Type::from_token(self.tokens[type_pos].clone())?);
+                            attributes.extend(tag_attributes);
                         }
-                        var_name = name.clone();
-                    }
-                    self.head += 1;
-                },
-                Token::Star => {
-                    if struct_enum_union {
-                        the_type = Some(Type::from_token(self.tokens[start].clone())?);
-                    }
-                    else {
-                        if generic {
-                            the_type = Some(Type::from_token(self.tokens[type_pos].clone())?);
+
+                        let tag = match keyword {
+                            Token::Struct => TypeSpecifier::Struct(name),
+                            Token::Union => TypeSpecifier::Union(name),
+                            Token::Enum => TypeSpecifier::Enum(name),
+                            _ => TypeSpecifier::Tagged(name.unwrap_or_default()),
+                        };
+                        if specifier.replace(tag).is_some() {
+                            return Err("Two types in one declaration".to_string());
                         }
-                        else {
-                            the_type = Some(Type::from_token(self.tokens[type_pos].clone())?);
+                        continue;
+                    },
+                    Token::Generic => {
+                        if specifier.replace(TypeSpecifier::Generic).is_some() {
+                            return Err("Two types in one declaration".to_string());
                         }
-                    }
-                    self.head += 1;
-                    pointer += 1;
-                },
-                Token::Restrict => {
-                    self.head += 1;
-                    if pointer != 0 {
-                        return Err("Restrict can only be used with pointers".to_string());
-                    }
-                    restrict = true;
-                },
-                Token::Assignment => {
-                    self.head += 1;
-                    value = Some(self.variable_value()?);
-                },
-                Token::Comma => {
-                    self.head += 1;
-                    variable_list.push(Variable::BasicVar {
-                        name: var_name.clone(),
-                        pointer: pointer,
-                        restrict: restrict,
-                        array: array,
-                        value: value.clone(),
-                    });
-                    pointer = 0;
-                    value = None;
-                    restrict = false;
-                    array = None;
-                    
-                    
-                    let mut temp = self.variable_list()?;
-                    variable_list.append(&mut temp); 
-                },
-                Token::SemiColon => {
-                    self.head += 1;
-                    variable_list.push(Variable::BasicVar {
-                        name: var_name.clone(),
-                        pointer: pointer,
-                        restrict: restrict,
-                        array: array,
-                        value: value.clone(),
-                    });
-                    pointer = 0;
-                    value = None;
-                    restrict = false;
-                    array = None;
-                    return Ok(AstNode::VariableList(VariableList::BasicVars {
-                        type_: the_type.expect("no type"),
-                        variables: variable_list,
-                        generic: generic,
-                    }));
-                },
-                Token::LeftBracket => {
-                    array = Some(self.variable_array()?);
+                    },
+                    Token::Alignas => {
+                        self.head += 1;
+                        alignas.push(self.parenthesized_type_or_expression("alignas")?);
+                        continue;
+                    },
+                    Token::Typeof | Token::TypeofUnqual => {
+                        let unqualified = *token == Token::TypeofUnqual;
+                        self.head += 1;
+                        let operand = Box::new(self.parenthesized_type_or_expression("typeof")?);
+                        let typeof_ = match unqualified {
+                            true => TypeSpecifier::TypeofUnqual(operand),
+                            false => TypeSpecifier::Typeof(operand),
+                        };
+                        if specifier.replace(typeof_).is_some() {
+                            return Err("Two types in one declaration".to_string());
+                        }
+                        continue;
+                    },
+                    Token::Word(name) if specifier.is_none() && keywords.is_empty() => {
+                        specifier = Some(TypeSpecifier::Named(name.clone()));
+                    },
+                    _ => break,
+                }
+            }
+            self.head += 1;
+        }
 
-                },
-                Token::Struct | Token::Enum | Token::Union | Token::Tagged => {
-                    self.head += 1;
-                    struct_enum_union = true;
-                },
-                Token::Generic => {
-                    generic = true;
-                    type_pos = self.head;
+        let specifier = match (specifier, keywords.is_empty()) {
+            (Some(specifier), true) => specifier,
+            (None, false) => TypeSpecifier::from_keywords(&keywords)?,
+            (Some(_), false) => {
+                return Err("Two types in one declaration".to_string());
+            },
+            (None, true) => {
+                return Err(format!("Expected a type but found {:?}", self.tokens.get(self.head)));
+            },
+        };
+
+        if thread_local && !matches!(storage, None | Some(StorageClass::Static) | Some(StorageClass::Extern)) {
+            return Err(format!("thread_local cannot be combined with {:?}", storage));
+        }
+        if constexpr && !matches!(storage, None | Some(StorageClass::Static) | Some(StorageClass::Register) | Some(StorageClass::Auto)) {
+            return Err(format!("constexpr cannot be combined with {:?}", storage));
+        }
+        if constexpr && (thread_local || qualifiers.volatile || qualifiers.atomic) {
+            return Err("constexpr cannot be combined with thread_local, volatile or atomic".to_string());
+        }
+        if storage == Some(StorageClass::Typedef) && (thread_local || constexpr || inline || noreturn || !alignas.is_empty()) {
+            return Err("typedef cannot be combined with other specifiers".to_string());
+        }
+        if storage == Some(StorageClass::Register) && !alignas.is_empty() {
+            return Err("alignas cannot be used on register variables".to_string());
+        }
+
+        Ok(DeclarationSpecifiers {
+            storage,
+            thread_local,
+            constexpr,
+            inline,
+            noreturn,
+            alignas,
+            attributes,
+            type_: Type {specifier, qualifiers},
+            definition,
+        })
+    }
+
+    /// Parses a type name such as `const char *` or `int (*)(void)`.
+    fn type_name(&mut self) -> Result<DeclaredType, String> {
+        let specifiers = self.declaration_specifiers()?;
+        if specifiers.storage.is_some() || specifiers.thread_local || specifiers.constexpr ||
+            specifiers.inline || specifiers.noreturn || !specifiers.alignas.is_empty() || !specifiers.attributes.is_empty() {
+            return Err("A type name cannot have storage classes, function specifiers, alignas or attributes".to_string());
+        }
+
+        match self.declarator(true)? {
+            (None, derivations) => derive(specifiers.type_, derivations),
+            (Some(name), _) => Err(format!("A type name cannot declare {}", name)),
+        }
+    }
+
+
+    /// Parses the parenthesized operand of `alignas` or `typeof`, which may be a type name
+    /// or an expression.
+    fn parenthesized_type_or_expression(&mut self, keyword: &str) -> Result<TypeOrExpression, String> {
+        match self.tokens.get(self.head) {
+            Some(Token::LeftParen) => {
+                self.head += 1;
+            },
+            token => {
+                return Err(format!("Expected ( after {} but found {:?}", keyword, token));
+            },
+        }
+        let operand = match self.tokens.get(self.head) {
+            Some(token) if self.starts_type_name(token) => TypeOrExpression::Type(Box::new(self.nested(Self::type_name)?)),
+            _ => TypeOrExpression::Expression(Box::new(self.expression()?)),
+        };
+        match self.tokens.get(self.head) {
+            Some(Token::RightParen) => {
+                self.head += 1;
+                Ok(operand)
+            },
+            token => Err(format!("Expected ) after {} but found {:?}", keyword, token)),
+        }
+    }
+
+    /// Whether the next tokens start a `[[...]]` or `__attribute__((...))` specifier.
+    fn at_attribute(&self) -> bool {
+        match self.tokens.get(self.head) {
+            Some(Token::Attribute) => true,
+            Some(Token::LeftBracket) => self.tokens.get(self.head + 1) == Some(&Token::LeftBracket),
+            _ => false,
+        }
+    }
+
+    /// Parses any number of attribute specifiers in either syntax. Empty attributes,
+    /// as in `[[ , ]]`, are allowed and dropped.
+    fn attributes(&mut self) -> Result<Vec<Attribute>, String> {
+        let mut attributes = Vec::new();
+        while self.at_attribute() {
+            let syntax = match self.current()? {
+                Token::Attribute => {
                     self.head += 1;
+                    for _ in 0..2 {
+                        match self.tokens.get(self.head) {
+                            Some(Token::LeftParen) => {
+                                self.head += 1;
+                            },
+                            token => {
+                                return Err(format!("Expected (( after __attribute__ but found {:?}", token));
+                            },
+                        }
+                    }
+                    AttributeSyntax::Gnu
                 },
                 _ => {
-                    return Err(format!("Unexpected token in var dec: {:?}", token));
+                    self.head += 2;
+                    AttributeSyntax::Standard
                 },
+            };
+            let close = match syntax {
+                AttributeSyntax::Standard => Token::RightBracket,
+                AttributeSyntax::Gnu => Token::RightParen,
+            };
+
+            loop {
+                match self.tokens.get(self.head) {
+                    Some(Token::Comma) => {
+                        self.head += 1;
+                        continue;
+                    },
+                    Some(token) if *token == close => {
+                        if self.tokens.get(self.head + 1) != Some(&close) {
+                            return Err(format!("Expected {}{} to close attributes", close, close));
+                        }
+                        self.head += 2;
+                        break;
+                    },
+                    _ => {},
+                }
+                let mut prefix = None;
+                let mut name = self.attribute_name()?;
+                if syntax == AttributeSyntax::Standard && self.tokens.get(self.head) == Some(&Token::Colon) {
+                    if self.tokens.get(self.head + 1) != Some(&Token::Colon) {
+                        return Err(format!("Expected :: after attribute prefix {}", name));
+                    }
+                    self.head += 2;
+                    prefix = Some(name);
+                    name = self.attribute_name()?;
+                }
+                let arguments = self.attribute_arguments()?;
+                attributes.push(Attribute {syntax, prefix, name, arguments});
+                match self.tokens.get(self.head) {
+                    Some(Token::Comma) => {},
+                    Some(token) if *token == close => {},
+                    token => {
+                        return Err(format!("Expected , or {} after attribute but found {:?}", close, token));
+                    },
+                }
             }
         }
-        Err("Unexpected end of file in var dec".to_string())
+        Ok(attributes)
+    }
+
+    /// Parses the name of an attribute, which may also be a keyword such as `const`.
+    fn attribute_name(&mut self) -> Result<String, String> {
+        let name = match self.tokens.get(self.head) {
+            Some(Token::Word(name)) => name.clone(),
+            Some(token) if !matches!(token, Token::Newline | Token::Number(_) | Token::String(_) | Token::Character(_) |
+                                     Token::Include(_) | Token::Macro(_) | Token::Preprocessor(_)) &&
+                token.to_string().chars().all(|c| c.is_ascii_alphanumeric() || c == '_') => token.to_string(),
+            token => {
+                return Err(format!("Expected an attribute name but found {:?}", token));
+            },
+        };
+        self.head += 1;
+        Ok(name)
     }
 
-    fn variable_array(&mut self) -> Result<Vec<VariableArray>,String> {
-        let mut array = Vec::new();
+    /// Parses the parenthesized arguments of an attribute, if it has any, as a balanced
+    /// sequence of tokens.
+    fn attribute_arguments(&mut self) -> Result<Option<Vec<Token>>, String> {
+        if self.tokens.get(self.head) != Some(&Token::LeftParen) {
+            return Ok(None);
+        }
         self.head += 1;
-        while self.tokens[self.head] == Token::LeftBracket ||(
-            self.tokens[self.head] != Token::SemiColon &&
-                self.tokens[self.head] != Token::Comma &&
-                self.tokens[self.head] != Token::Assignment) {
-            match self.tokens[self.head] {
-                Token::RightBracket => {
-                    self.head += 1;
-                    array.push(VariableArray::NoSize);
-                },
-                Token::LeftBracket => {
-                    self.head += 1;
-                },
-                _ => {
-                    array.push(VariableArray::Size(self.expression(None)?));
-                    self.head += 1;
+        let mut arguments = Vec::new();
+        let mut open = Vec::new();
+        loop {
+            let Some(token) = self.tokens.get(self.head) else {
+                return Err("Unexpected end of file in attribute arguments".to_string());
+            };
+            self.head += 1;
+            match token {
+                Token::LeftParen | Token::LeftBracket | Token::LeftBrace => open.push(token.clone()),
+                Token::RightParen if open.is_empty() => return Ok(Some(arguments)),
+                Token::RightParen | Token::RightBracket | Token::RightBrace => {
+                    let matching = match open.pop() {
+                        Some(Token::LeftParen) => Token::RightParen,
+                        Some(Token::LeftBracket) => Token::RightBracket,
+                        Some(_) => Token::RightBrace,
+                        None => return Err(format!("Unbalanced {} in attribute arguments", token)),
+                    };
+                    if *token != matching {
+                        return Err(format!("Unbalanced {} in attribute arguments", token));
+                    }
                 },
+                _ => {},
             }
+            arguments.push(token.clone());
         }
-
-        Ok(array)
-            
     }
 
-    fn function_pointer_dec(&mut self) -> Result<AstNode, String> {
+    /// Parses a `static_assert` declaration, including its semicolon.
+    fn static_assert(&mut self) -> Result<StaticAssert, String> {
+        self.head += 1;
+        match self.tokens.get(self.head) {
+            Some(Token::LeftParen) => {
+                self.head += 1;
+            },
+            token => {
+                return Err(format!("Expected ( after static_assert but found {:?}", token));
+            },
+        }
+        let condition = self.ternary_expression()?;
+        let message = match (self.tokens.get(self.head), self.tokens.get(self.head + 1)) {
+            (Some(Token::Comma), Some(Token::String(message))) => {
+                let message = message.clone();
+                self.head += 2;
+                Some(message)
+            },
+            (Some(Token::Comma), token) => {
+                return Err(format!("Expected a string literal in static_assert but found {:?}", token));
+            },
+            _ => None,
+        };
+        match self.tokens.get(self.head) {
+            Some(Token::RightParen) => {
+                self.head += 1;
+            },
+            token => {
+                return Err(format!("Expected ) in static_assert but found {:?}", token));
+            },
+        }
+        match self.tokens.get(self.head) {
+            Some(Token::SemiColon) => {
+                self.head += 1;
+                Ok(StaticAssert {condition, message})
+            },
+            token => Err(format!("Expected ; after static_assert but found {:?}", token)),
+        }
+    }
 
-        let mut node = AstNode::None;
-        let mut name = None;
-        let mut return_type = None;
-        let mut return_pointer = 0;
-        let mut seen_first_star = false;
-        let mut pointer = 0;
-        let mut array = None;
-        let mut seen_open_paren = false;
-        let mut seen_close_paren = false;
+    fn preprocessors(&mut self) -> Result<Vec<Preprocessor>, String> {
+        let mut preprocessor = Vec::new();
 
         while self.head < self.tokens.len() {
-            let token = &self.tokens[self.head];
-            match token {
-                Token::Type(_) => {
-                    return_type = Some(Type::from_token(token.clone())?);
-                    self.head += 1;
-                },
-                Token::LeftParen => {
-                    self.head += 1;
-                    if seen_close_paren {
-
-                        return Ok(AstNode::VariableList(VariableList::FunctionPointer(Variable::FunctionPointer {
-                            name: name,
-                            return_type: return_type.expect("no return type"),
-                            return_pointer: return_pointer,
-                            pointer: pointer,
-                            array: array,
-                            arguments: self.function_arguments()?,
-                        })));
-                    }
-                    else {
-                        seen_open_paren = true;
-                    }
-                },
-                Token::RightParen => {
-                    seen_close_paren = true;
-                    self.head += 1;
-                },
-                Token::Star => {
-                    if seen_open_paren && seen_first_star {
-                        return_pointer += 1;
-                    }
-                    else if seen_first_star {
-                        pointer += 1;
-                    }
-                    else {
-                        seen_first_star = true;
-                    }
-                    self.head += 1;
-                },
-                Token::Word(val) => {
+            match self.current()? {
+                Token::Preprocessor(data) | Token::Include(data) | Token::Macro(data) => {
+                    preprocessor.push(Preprocessor {value: data.clone()});
                     self.head += 1;
-                    if seen_open_paren && seen_first_star {
-                        name = Some(val.clone());
-                    }
-                    else {
-                        return Err("Malformed function pointer declaration".to_string());
-                    }
-                },//TODO: add array
-                Token::LeftBracket => {
-                    array = Some(self.variable_array()?);
                 },
                 _ => {
-                    return Err(format!("Unexpected token in function pointer: {:?}", token));
+                    break;
                 },
-
             }
-
         }
-        
-        Ok(node)
+
+        Ok(preprocessor)
     }
- 
-    fn variable_list_or_function(&mut self) -> Result<AstNode, String> {
-        let mut node = AstNode::None;
-        let mut word_seen = false;
-        let mut buffer = self.head;
-        self.head -= 1;
 
-        while buffer < self.tokens.len() {
-            match &self.tokens[buffer] {
-                Token::LeftParen => {
-                    if !word_seen {
-                        node = self.function_pointer_dec()?;
-                    }
-                    else {
-                        node = self.function()?;
-                    }
-                    break;
-                },
-                Token::Comma | Token::SemiColon | Token::Assignment => {
-                    node = self.variable_dec()?;
-                    break;
-                },
-                Token::Star | Token::Restrict => {
-                    buffer += 1;
-                },
-                Token::Word(_) => {
-                    word_seen = true;
-                    buffer += 1;
-                },//TODO: add array
-                Token::LeftBracket => {
-                    node = self.variable_dec()?;
-                    break;
-                },
-                Token::Generic => {
-                    buffer += 1;
-                },
-                _ => {
-                    return Err(format!("Unexpected token in var list or func: {:?}", self.tokens[buffer]));
-                },
+    fn variable_value(&mut self) -> Result<VariableValue,String> {
+
+        match self.current()? {
+            Token::String(data) => {
+                self.head += 1;
+                Ok(VariableValue::String(data.clone()))
+            },
+            _ => {
+                Ok(VariableValue::Expression(self.assignment_expression()?))
+            },
+        }
+
+
+    }
+
+    /// Parses the qualifiers after a `*` in a declarator, e.g. `const` in `char *const p`.
+    fn pointer_qualifiers(&mut self) -> TypeQualifiers {
+        let mut qualifiers = TypeQualifiers::default();
+        while let Some(token) = self.tokens.get(self.head) {
+            match token {
+                Token::Const => qualifiers.const_ = true,
+                Token::Volatile => qualifiers.volatile = true,
+                Token::Restrict => qualifiers.restrict = true,
+                Token::Atomic => qualifiers.atomic = true,
+                _ => break,
             }
+            self.head += 1;
         }
-        Ok(node)
+        qualifiers
     }
 
-    fn function_arguments(&mut self) -> Result<Vec<FunctionArgument>,String> {
+    /// Parses the pointers at the start of a declarator, in the order they apply to the base type.
+    fn pointers(&mut self) -> Vec<Derivation> {
+        let mut pointers = Vec::new();
+        while self.tokens.get(self.head) == Some(&Token::Star) {
+            self.head += 1;
+            pointers.push(Derivation::Pointer(self.pointer_qualifiers()));
+        }
+        pointers
+    }
 
-        let mut arguments = Vec::new();
-        let mut var_name = None;
-        let mut the_type = None;
-        let mut pointer = 0;
-        let mut num_periods = 0;
-        let mut restrict = false;
-        
-        while self.head < self.tokens.len() {
-            let token = &self.tokens[self.head];
-            match token {
-                Token::Type(_) => {
-                    self.head += 1;
-                    the_type = Some(Type::from_token(token.clone())?);
-                },
-                Token::Word(name) => {
-                    self.head += 1;
-                    var_name = Some(name.clone());
-                },
-                Token::Star => {
-                    self.head += 1;
-                    pointer += 1;
-                },
-                Token::Period => {
-                    self.head += 1;
-                    num_periods += 1;
-                    if num_periods > 3 {
-                        return Err("Too many periods in function argument".to_string());
-                    }
-                },
-                Token::Restrict => {
-                    self.head += 1;
-                    if pointer != 0 {
-                        return Err("Restrict can only be used with pointers".to_string());
-                    }
-                    restrict = true;
-                },
-                Token::Comma => {
-                    self.head += 1;
-                    if num_periods != 0 {
-                        return Err("Ellipsis in wrong spot.".to_string());
-                    }
-                    else if var_name.is_none() {
-                        arguments.push(FunctionArgument::Type(the_type.expect("no type"), pointer));
-                        the_type = None;
-                        pointer = 0;
-                    }
-                    else {
-                        arguments.push(FunctionArgument::Variable(the_type.expect("no type"),Variable::BasicVar {
-                            name: var_name.expect("no name"),
-                            array: None,
-                            value: None,
-                            pointer: pointer,
-                            restrict: restrict,
-                        }));
-                        var_name = None;
-                        the_type = None;
-                        pointer = 0;
-                        restrict = false;
-                    }
+    /// Parses a declarator and returns its name along with the steps that build its type
+    /// from the base type, innermost first. Abstract declarators, as in parameters and type
+    /// names, may leave out the name.
+    fn declarator(&mut self, abstract_: bool) -> Result<(Option<String>, Vec<Derivation>), String> {
+        self.nested(|parser| {
+            let pointers = parser.pointers();
+            let mut name = None;
+            let mut inner = Vec::new();
 
-                },
-                Token::RightParen => {
-                    self.head += 1;
-                    if num_periods == 3 {
-                        arguments.push(FunctionArgument::Ellipsis);
-                        self.head += 1;
-                    }
-                    else if num_periods < 3 && num_periods > 0 {
-                        return Err("Too few periods in function argument".to_string());
-                    }
-                    else if var_name.is_none() && the_type.is_some() {
-                        arguments.push(FunctionArgument::Type(the_type.expect("no type"), pointer));
-                        the_type = None;
-                        pointer = 0;
-                        self.head += 1;
-                    }
-                    else if the_type.is_some() && var_name.is_some() {
-                        arguments.push(FunctionArgument::Variable(the_type.expect("no type"),Variable::BasicVar {
-                            name: var_name.expect("no name"),
-                            array: None,
-                            value: None,
-                            pointer: pointer,
-                            restrict: restrict,
-                        }));
-                        var_name = None;
-                        the_type = None;
-                        pointer = 0;
-                        restrict = false;
-                        self.head += 1;
-                    }
-                    return Ok(arguments);
-                },
-                Token::LeftParen => {
-                    self.head -= 1;
-                    self.head -= pointer;
-                    let temp = self.function_pointer_dec()?;
-                    match temp {
-                        AstNode::VariableList(func_def) => {
-                            match func_def {
-                                VariableList::FunctionPointer(func) => {
-                                    arguments.push(FunctionArgument::FunctionPointer(func));
-                                },
-                                _ => {
-                                    return Err("Expected function".to_string());
-                                },
-                            }
+            match parser.tokens.get(parser.head) {
+                Some(Token::Word(word)) => {
+                    name = Some(word.clone());
+                    parser.head += 1;
+                },
+                Some(Token::LeftParen) if matches!(parser.tokens.get(parser.head + 1),
+                                                   Some(Token::Star) | Some(Token::LeftParen) | Some(Token::Word(_))) => {
+                    parser.head += 1;
+                    (name, inner) = parser.declarator(abstract_)?;
+                    match parser.tokens.get(parser.head) {
+                        Some(Token::RightParen) => {
+                            parser.head += 1;
                         },
-                        _ => {
-                            return Err("Expected function".to_string());
+                        token => {
+                            return Err(format!("Expected ) in declarator but found {:?}", token));
                         },
                     }
-                    
                 },
-                _ => {
-                    return Err(format!("Unexpected token in function arguments: {:?}", token));
+                _ if abstract_ => {},
+                token => {
+                    return Err(format!("Expected a name in declarator but found {:?}", token));
                 },
-
             }
 
-        }
+            let mut suffixes = Vec::new();
+            loop {
+                match parser.tokens.get(parser.head) {
+                    Some(Token::LeftBracket) if !parser.at_attribute() => {
+                        parser.head += 1;
+                        let size = match parser.tokens.get(parser.head) {
+                            Some(Token::RightBracket) => VariableArray::NoSize,
+                            _ => VariableArray::Size(parser.assignment_expression()?),
+                        };
+                        match parser.tokens.get(parser.head) {
+                            Some(Token::RightBracket) => {
+                                parser.head += 1;
+                            },
+                            token => {
+                                return Err(format!("Expected ] in declarator but found {:?}", token));
+                            },
+                        }
+                        suffixes.push(Derivation::Array(size));
+                    },
+                    Some(Token::LeftParen) => {
+                        parser.head += 1;
+                        suffixes.push(Derivation::Function(parser.function_arguments()?));
+                    },
+                    _ => break,
+                }
+            }
 
-        Err("Unexpected end of file".to_string())
+            let mut derivations = pointers;
+            derivations.extend(suffixes.into_iter().rev());
+            derivations.extend(inner);
+            Ok((name, derivations))
+        })
     }
 
-    fn function(&mut self) -> Result<AstNode, String> {
-        let mut name = None;
-        let mut arguments = None;
-        let mut return_type = None;
-        let mut return_pointer = 0;
-        let mut inline = false;
-        let mut static_ = false;
-        let mut generic = false;
+    /// Parses a parameter list after its `(`, up to and including the `)`.
+    fn function_arguments(&mut self) -> Result<Vec<FunctionArgument>,String> {
+        let mut arguments = Vec::new();
 
-        while self.head < self.tokens.len() {
-            let token = &self.tokens[self.head];
-            println!("Function: {:?}", token);
-            match token {
-                Token::Word(word) => {
-                    name = Some(word.clone());
-                    self.head += 1;
-                },
-                Token::LeftParen => {
-                    self.head += 1;
-                    arguments = Some(self.function_arguments()?);
-                },
-                Token::LeftBrace => {
-                    self.head += 1;
-                    let code_block = self.code_block()?;
+        if self.tokens.get(self.head) == Some(&Token::RightParen) {
+            self.head += 1;
+            return Ok(arguments);
+        }
 
-                    return Ok(AstNode::Function(Function {
-                        name: name.expect("no name"),
-                        arguments: arguments.expect("no arguments"),
-                        return_type: return_type.expect("no return type"),
-                        return_pointer: return_pointer,
-                        body: code_block,
-                        inline: inline,
-                        static_: static_,
-                        generic: generic,
-                    }));
-                },
-                Token::Star => {
-                    self.head += 1;
-                    return_pointer += 1;
-                },
-                Token::Type(_) => {
-                    self.head += 1;
-                    return_type = Some(Type::from_token(token.clone())?);
-                },
-                Token::Inline => {
-                    self.head += 1;
-                    inline = true;
-                },
-                Token::Static => {
-                    self.head += 1;
-                    static_ = true;
-                },
-                Token::SemiColon => {
-                    self.head += 1;
-                    return Ok(AstNode::FunctionPrototype(FunctionPrototype {
-                        name: name.expect("no name"),
-                        arguments: arguments.expect("no arguments"),
-                        return_type: return_type.expect("no return type"),
-                        return_pointer: return_pointer,
-                    }));
-                },
-                Token::Generic => {
+        loop {
+            if self.tokens.get(self.head) == Some(&Token::Period) {
+                for _ in 0..3 {
+                    if self.tokens.get(self.head) != Some(&Token::Period) {
+                        return Err("Expected ... in function arguments".to_string());
+                    }
                     self.head += 1;
-                    generic = true;
-                },
-                _ => {
-                    return Err(format!("Unexpected token in function: {:?}", token));
-                },
+                }
+                arguments.push(FunctionArgument::Ellipsis);
+                return match self.tokens.get(self.head) {
+                    Some(Token::RightParen) => {
+                        self.head += 1;
+                        Ok(arguments)
+                    },
+                    _ => Err("Ellipsis must be the last function argument".to_string()),
+                };
             }
-            //self.head += 1;
-        }
-
-        return Err("Unexpected end of file".to_string());
-    }
 
-    fn code_block(&mut self) -> Result<CodeBlock, String> {
-        let mut statements = Vec::new();
+            let specifiers = self.declaration_specifiers()?;
+            if !matches!(specifiers.storage, None | Some(StorageClass::Register)) {
+                return Err(format!("Function arguments cannot have storage class {:?}", specifiers.storage));
+            }
+            if specifiers.thread_local || specifiers.constexpr || !specifiers.alignas.is_empty() {
+                return Err("Function arguments cannot be thread_local, constexpr or aligned".to_string());
+            }
+            let (name, derivations) = self.declarator(true)?;
+            let mut attributes = specifiers.attributes;
+            attributes.extend(self.attributes()?);
+            arguments.push(FunctionArgument::Parameter(Parameter {
+                register: specifiers.storage == Some(StorageClass::Register),
+                attributes,
+                name,
+                type_: derive(specifiers.type_, derivations)?,
+            }));
 
-        while self.head < self.tokens.len() {
-            let token = &self.tokens[self.head];
-            match token {
-                Token::RightBrace => {
-                    self.head += 1;
-                    return Ok(CodeBlock::Code(StatementList {statements}));
-                },
-                Token::Preprocessor(_) => {
+            match self.tokens.get(self.head) {
+                Some(Token::Comma) => {
                     self.head += 1;
-                    statements.append(self.preprocessors()?.iter().map(|x| Statement::Preprocessor(x.clone())).collect::<Vec<Statement>>().as_mut());
                 },
-                Token::Comment(value) => {
-                    statements.push(Statement::Comment(value.clone()));
+                Some(Token::RightParen) => {
                     self.head += 1;
+                    return Ok(arguments);
                 },
-                Token::Struct | Token::Union | Token::Enum | Token::Tagged |Token::Type(_) => {
-                    self.head += 1;
-                    let node = self.variable_list_or_function()?;
-                    match node {
-                        AstNode::VariableList(variable_list) => {
-                            statements.push(Statement::VariableList(variable_list));
-                        },
-                        AstNode::Function(_) => {
-                            return Err("Functions cannot be declared inside of a code block".to_string());
-                        },
-                        _ => {
-                            return Err("Expected variable list".to_string());
-                        },
-                    }
-                },
-                Token::LeftBrace => {
-                    let code_block = self.code_block()?;
-                    statements.push(Statement::Block(Box::new(code_block)));
-                },
-                Token::Return | Token::If | Token::Else | Token::While | Token::For |
-                Token::Do | Token::Switch | Token::Case | Token::Default | Token::Break |
-                Token::Continue | Token::Goto | Token::SemiColon => {
-                    let statement = self.statement()?;
-                    statements.push(statement);
-                },
-                _ => {
-                    let expression = self.expression(None)?;
-                    statements.push(Statement::Expression(expression));
-                    match &self.tokens[self.head] {
-                        Token::SemiColon => {
-                            self.head += 1;
-                        },
-                        _ => {
-                            return Err(format!("Expected semicolon in code block but found {:?} instead", self.tokens[self.head]));
-                        },
-                    }
+                token => {
+                    return Err(format!("Unexpected token in function arguments: {:?}", token));
                 },
             }
         }
-
-        Err("Unexpected end of file in code block".to_string())
-        
     }
 
-    fn statement(&mut self) -> Result<Statement,String> {
-
-        let mut requires_semicolon = false;
-        let mut expression = None;
-        let statement = None;
-        
-        while self.head < self.tokens.len() {
-            let token = &self.tokens[self.head];
-
-            match token {
-                Token::Return => {
-                    self.head += 1;
-                    expression = Some(self.expression(None)?);
-                    requires_semicolon = true;
-                },
-                Token::Break => {
-                    self.head += 1;
-                    requires_semicolon = true;
-                },
-                Token::Continue => {
-                    self.head += 1;
-                    requires_semicolon = true;
-                },
-                Token::Word(word) => {
-                    self.head += 1;
-                    match &self.tokens[self.head] {
-                        Token::Colon => {
+    /// Parses the declarators after the declaration specifiers: a list of variables, a function
+    /// prototype, or a function definition.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all, fields(position = self.head), err(level = "trace")))]
+    fn variable_list_or_function(&mut self, mut specifiers: DeclarationSpecifiers) -> Result<AstNode, String> {
+        let generic = specifiers.type_.specifier == TypeSpecifier::Generic;
+        let mut variables = Vec::new();
+
+        loop {
+            let (name, derivations) = self.declarator(false)?;
+            let name = name.ok_or("Expected a name in declarator")?;
+            let attributes = self.attributes()?;
+            let type_ = derive(specifiers.type_.clone(), derivations)?;
+            if type_.is_function() && (specifiers.thread_local || specifiers.constexpr || !specifiers.alignas.is_empty()) {
+                return Err(format!("Function {} cannot be thread_local, constexpr or aligned", name));
+            }
+            if !type_.is_function() && specifiers.noreturn {
+                return Err(format!("noreturn can only be used on functions, not {}", name));
+            }
+            let type_ = match type_ {
+                DeclaredType::Function(return_type, arguments) if variables.is_empty() &&
+                    matches!(self.tokens.get(self.head), Some(Token::LeftBrace) | Some(Token::SemiColon)) => {
+                    specifiers.attributes.extend(attributes);
+                    match self.tokens.get(self.head) {
+                        Some(Token::LeftBrace) => {
+                            trace!(name, "function definition");
                             self.head += 1;
-                            return Ok(Statement::Label(word.clone()));
+                            let body = self.function_body(&arguments)?;
+                            let CodeBlock::Code(list) = &body;
+                            if specifiers.noreturn && contains_return(&list.statements) {
+                                return Err(format!("noreturn function {} contains a return statement", name));
+                            }
+                            return Ok(AstNode::Function(Function {
+                                specifiers,
+                                generic,
+                                return_type: *return_type,
+                                name,
+                                arguments,
+                                body,
+                                doc: None,
+                            }));
                         },
-                        _ => {
-                            self.head -= 1;
-                            expression = Some(self.expression(None)?);
-                            requires_semicolon = true;
+                        Some(Token::SemiColon) => {
+                            trace!(name, "function prototype");
+                            self.head += 1;
+                            return Ok(AstNode::FunctionPrototype(FunctionPrototype {
+                                specifiers,
+                                return_type: *return_type,
+                                name,
+                                arguments,
+                                doc: None,
+                            }));
                         },
+                        _ => unreachable!("checked before taking the function apart"),
                     }
                 },
-                Token::SemiColon => {
-                    self.head += 1;
-                    if statement.is_some() {
-                        return Ok(statement.expect("no statement"));
-                    } else {
-                        return Ok(Statement::Expression(expression.expect("no expression")));
-                    }
-                    
-                },
-                Token::If => {
-                    self.head += 1;
-                    let statement = self.statement_if()?;
-                    return Ok(statement);
-                },
-                Token::Else => {
-                    self.head += 1;
-                    let block_or_statement = self.block_or_statement()?;
-                    return Ok(Statement::Else(Box::new(block_or_statement)));
-                },
-                Token::While => {
-                    self.head += 1;
-                    let statement = self.statement_while()?;
-                    return Ok(statement);
-                },
-                Token::For => {
-                    self.head += 1;
-                    let statement = self.statement_for()?;
-                    return Ok(statement);
-                },
-                Token::Do => {
+                type_ => type_,
+            };
+            trace!(name, "variable");
+            let variable = self.variable_initializer(name, attributes, type_)?;
+            if specifiers.constexpr && variable.value.is_none() {
+                return Err(format!("constexpr variable {} must be initialized", variable.name));
+            }
+            variables.push(variable);
+
+            match self.tokens.get(self.head) {
+                Some(Token::Comma) => {
                     self.head += 1;
-                    let statement = self.statement_do()?;
-                    return Ok(statement);
                 },
-                Token::Switch => {
+                Some(Token::SemiColon) => {
                     self.head += 1;
-                    let statement = self.statement_switch()?;
-                    return Ok(statement);
+                    return Ok(AstNode::VariableList(VariableList {
+                        specifiers,
+                        generic,
+                        variables,
+                        doc: None,
+                    }));
                 },
-                Token::Goto => {
-                    self.head += 1;
-                    match &self.tokens[self.head] {
-                        Token::Word(word) => {
-                            self.head += 1;
-                            match self.tokens[self.head] {
-                                Token::SemiColon => {
-                                    self.head += 1;
-                                    return Ok(Statement::Goto(word.clone()));
-                                },
-                                _ => {
-                                    return Err("Expected semicolon in Goto Statement".to_string());
-                                },
-                            }
-                        },
-                        _ => {
-                            return Err("Expected label".to_string());
-                        },
-                    }
-
+                Some(token) => {
+                    return Err(format!("Unexpected token in var list: {:?}", token));
                 },
-                _ => {
-                    expression = Some(self.expression(None)?);
-                    requires_semicolon = true;
+                None => {
+                    return Err("Unexpected end of file in var list".to_string());
                 },
-                
-
             }
-            
-
-        }
-
-        if requires_semicolon {
-            return Err("Expected semicolon in statement".to_string());
         }
-
-        return Ok(Statement::Expression(expression.expect("no expression")));
-
     }
-    fn statement_if(&mut self) -> Result<Statement,String> {
-        let expression = self.conditional_expression()?;
-        let block_or_statement = self.block_or_statement()?;
 
-        return Ok(Statement::If(expression, Box::new(block_or_statement)));
+    fn variable_initializer(&mut self, name: String, attributes: Vec<Attribute>, type_: DeclaredType) -> Result<Variable, String> {
+        let value = match self.tokens.get(self.head) {
+            Some(Token::Assignment) => {
+                self.head += 1;
+                Some(self.variable_value()?)
+            },
+            _ => None,
+        };
+        Ok(Variable {name, attributes, type_, value})
     }
 
-    fn statement_else(&mut self) -> Result<Statement,String> {
-        let block_or_statement = self.block_or_statement()?;
-
-        return Ok(Statement::Else(Box::new(block_or_statement)));
+    /// Parses the body of a function, in which its parameters hide types of the same name.
+    fn function_body(&mut self, arguments: &[FunctionArgument]) -> Result<CodeBlock, String> {
+        self.scoped(|parser| {
+            for argument in arguments {
+                if let FunctionArgument::Parameter(Parameter {name: Some(name), ..}) = argument {
+                    parser.declare_name(name, false);
+                }
+            }
+            parser.code_block()
+        })
     }
 
-    fn statement_while(&mut self) -> Result<Statement,String> {
-        let expression = self.conditional_expression()?;
-        let block_or_statement = self.block_or_statement()?;
-
-        return Ok(Statement::While(expression, Box::new(block_or_statement)));
-    }
+    fn code_block(&mut self) -> Result<CodeBlock, String> {
+        self.nested(|parser| parser.scoped(|parser| {
+            let mut statements = Vec::new();
 
-    fn statement_do(&mut self) -> Result<Statement,String> {
-        let block_or_statement = self.block_or_statement()?;
-        match self.tokens[self.head] {
-            Token::While => {
-                self.head += 1;
-                let expression = self.conditional_expression()?;
-                match self.tokens[self.head] {
-                    Token::SemiColon => {
-                        self.head += 1;
-                        return Ok(Statement::DoWhile(expression,Box::new(block_or_statement)));
+            while parser.head < parser.tokens.len() {
+                let token = parser.current()?;
+                match token {
+                    Token::RightBrace => {
+                        parser.head += 1;
+                        return Ok(CodeBlock::Code(StatementList {statements}));
+                    },
+                    Token::Preprocessor(_) | Token::Include(_) | Token::Macro(_) => {
+                        statements.append(parser.preprocessors()?.iter().map(|x| Statement::Preprocessor(x.clone())).collect::<Vec<Statement>>().as_mut());
+                    },
+                    Token::Word(_) if parser.tokens.get(parser.head + 1) == Some(&Token::Colon) => {
+                        let statement = parser.statement()?;
+                        statements.push(statement);
+                    },
+                    token if parser.starts_declaration(token) => {
+                        let specifiers = parser.declaration_specifiers()?;
+                        statements.push(parser.block_declaration(specifiers)?);
+                    },
+                    Token::LeftBracket | Token::Attribute if parser.at_attribute() => {
+                        statements.push(parser.attributed_statement()?);
+                    },
+                    Token::LeftBrace => {
+                        parser.head += 1;
+                        let code_block = parser.code_block()?;
+                        statements.push(Statement::Block(Box::new(code_block)));
+                    },
+                    Token::Return | Token::If | Token::Else | Token::While | Token::For |
+                    Token::Do | Token::Switch | Token::Case | Token::Default | Token::Break |
+                    Token::Continue | Token::Goto | Token::SemiColon | Token::StaticAssert => {
+                        let statement = parser.statement()?;
+                        statements.push(statement);
                     },
                     _ => {
-                        return Err("Expected semicolon".to_string());
+                        let expression = parser.expression()?;
+                        statements.push(Statement::Expression(expression));
+                        match parser.current()? {
+                            Token::SemiColon => {
+                                parser.head += 1;
+                            },
+                            _ => {
+                                return Err(format!("Expected semicolon in code block but found {:?} instead", parser.current()?));
+                            },
+                        }
                     },
                 }
+            }
+
+            Err("Unexpected end of file in code block".to_string())
+
+        }))
+    }
+
+    /// Parses a declaration in a block after its specifiers.
+    fn block_declaration(&mut self, specifiers: DeclarationSpecifiers) -> Result<Statement, String> {
+        if specifiers.thread_local && !matches!(specifiers.storage, Some(StorageClass::Static) | Some(StorageClass::Extern)) {
+            return Err("thread_local variables in a block must be static or extern".to_string());
+        }
+        if specifiers.definition.is_some() && self.tokens.get(self.head) == Some(&Token::SemiColon) {
+            // A struct, union or enum defined on its own is kept with its specifiers.
+            self.head += 1;
+            let variable_list = VariableList {specifiers, generic: false, variables: Vec::new(), doc: None};
+            self.declare_variables(&variable_list);
+            return Ok(Statement::VariableList(variable_list));
+        }
+        match self.variable_list_or_function(specifiers)? {
+            AstNode::VariableList(variable_list) => {
+                self.declare_variables(&variable_list);
+                Ok(Statement::VariableList(variable_list))
             },
-            _ => {
-                return Err("Expected while".to_string());
-            },
+            AstNode::Function(_) => Err("Functions cannot be declared inside of a code block".to_string()),
+            _ => Err("Expected variable list".to_string()),
         }
     }
 
-    fn statement_for(&mut self) -> Result<Statement,String> {
-        match self.tokens[self.head] {
-            Token::LeftParen => {
+    /// Parses a statement or declaration that starts with attributes. Attributes on
+    /// their own before a semicolon form an attribute declaration such as `[[fallthrough]];`.
+    fn attributed_statement(&mut self) -> Result<Statement, String> {
+        let attributes = self.attributes()?;
+        match self.tokens.get(self.head) {
+            Some(Token::SemiColon) => {
+                self.head += 1;
+                Ok(Statement::Attributes(attributes))
+            },
+            Some(token) if self.starts_declaration(token) => {
+                let mut specifiers = self.declaration_specifiers()?;
+                specifiers.attributes.splice(0..0, attributes);
+                self.block_declaration(specifiers)
+            },
+            Some(Token::LeftBrace) => {
+                self.head += 1;
+                let block = Statement::Block(Box::new(self.code_block()?));
+                Ok(Statement::Attributed(attributes, Box::new(block)))
+            },
+            Some(_) => Ok(Statement::Attributed(attributes, Box::new(self.statement()?))),
+            None => Err("Unexpected end of file after attributes".to_string()),
+        }
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all, fields(position = self.head), err(level = "trace")))]
+    fn statement(&mut self) -> Result<Statement,String> {
+        self.nested(|parser| {
+            let mut requires_semicolon = false;
+            let mut statement = None;
+
+            while parser.head < parser.tokens.len() {
+                let token = parser.current()?;
+
+                match token {
+                    Token::StaticAssert => {
+                        return Ok(Statement::StaticAssert(parser.static_assert()?));
+                    },
+                    Token::LeftBracket | Token::Attribute if statement.is_none() && parser.at_attribute() => {
+                        return parser.attributed_statement();
+                    },
+                    Token::Return => {
+                        parser.head += 1;
+                        let expression = match parser.current()? {
+                            Token::SemiColon => Expression::Blank,
+                            _ => parser.expression()?,
+                        };
+                        statement = Some(Statement::Return(expression));
+                        requires_semicolon = true;
+                    },
+                    Token::Break => {
+                        parser.head += 1;
+                        statement = Some(Statement::Break);
+                        requires_semicolon = true;
+                    },
+                    Token::Continue => {
+                        parser.head += 1;
+                        statement = Some(Statement::Continue);
+                        requires_semicolon = true;
+                    },
+                    Token::Word(word) => {
+                        parser.head += 1;
+                        match parser.current()? {
+                            Token::Colon => {
+                                parser.head += 1;
+                                return Ok(Statement::Label(word.clone()));
+                            },
+                            _ => {
+                                parser.head -= 1;
+                                statement = Some(Statement::Expression(parser.expression()?));
+                                requires_semicolon = true;
+                            },
+                        }
+                    },
+                    Token::SemiColon => {
+                        parser.head += 1;
+                        return Ok(statement.unwrap_or(Statement::Expression(Expression::Blank)));
+                    },
+                    Token::If => {
+                        parser.head += 1;
+                        let statement = parser.statement_if()?;
+                        return Ok(statement);
+                    },
+                    Token::Else => {
+                        return Err("Expected if before else".to_string());
+                    },
+                    Token::While => {
+                        parser.head += 1;
+                        let statement = parser.statement_while()?;
+                        return Ok(statement);
+                    },
+                    Token::For => {
+                        parser.head += 1;
+                        // The declaration in a for loop is in scope until the end of its body.
+                        let statement = parser.scoped(Self::statement_for)?;
+                        return Ok(statement);
+                    },
+                    Token::Do => {
+                        parser.head += 1;
+                        let statement = parser.statement_do()?;
+                        return Ok(statement);
+                    },
+                    Token::Switch => {
+                        parser.head += 1;
+                        let statement = parser.statement_switch()?;
+                        return Ok(statement);
+                    },
+                    Token::Goto => {
+                        parser.head += 1;
+                        match parser.current()? {
+                            Token::Word(word) => {
+                                parser.head += 1;
+                                match parser.current()? {
+                                    Token::SemiColon => {
+                                        parser.head += 1;
+                                        return Ok(Statement::Goto(word.clone()));
+                                    },
+                                    _ => {
+                                        return Err("Expected semicolon in Goto Statement".to_string());
+                                    },
+                                }
+                            },
+                            _ => {
+                                return Err("Expected label".to_string());
+                            },
+                        }
+
+                    },
+                    _ => {
+                        statement = Some(Statement::Expression(parser.expression()?));
+                        requires_semicolon = true;
+                    },
+
+
+                }
+
+
+            }
+
+            if requires_semicolon {
+                return Err("Expected semicolon in statement".to_string());
+            }
+
+            Err("Unexpected end of file in statement".to_string())
+        })
+    }
+    /// Parses an `if` and its `else`, which belongs to the innermost `if` without one.
+    fn statement_if(&mut self) -> Result<Statement,String> {
+        let expression = self.conditional_expression()?;
+        let block_or_statement = self.block_or_statement()?;
+        let else_ = match self.tokens.get(self.head) {
+            Some(Token::Else) => {
+                self.head += 1;
+                Some(Box::new(self.block_or_statement()?))
+            },
+            _ => None,
+        };
+
+        Ok(Statement::If(expression, Box::new(block_or_statement), else_))
+    }
+
+    fn statement_while(&mut self) -> Result<Statement,String> {
+        let expression = self.conditional_expression()?;
+        let block_or_statement = self.block_or_statement()?;
+
+        Ok(Statement::While(expression, Box::new(block_or_statement)))
+    }
+
+    fn statement_do(&mut self) -> Result<Statement,String> {
+        let block_or_statement = self.block_or_statement()?;
+        match self.current()? {
+            Token::While => {
+                self.head += 1;
+                let expression = self.conditional_expression()?;
+                match self.current()? {
+                    Token::SemiColon => {
+                        self.head += 1;
+                        Ok(Statement::DoWhile(expression,Box::new(block_or_statement)))
+                    },
+                    _ => {
+                        Err("Expected semicolon".to_string())
+                    },
+                }
+            },
+            _ => {
+                Err("Expected while".to_string())
+            },
+        }
+    }
+
+    fn statement_for(&mut self) -> Result<Statement,String> {
+        match self.current()? {
+            Token::LeftParen => {
                 self.head += 1;
             },
             _ => {
                 return Err("Expected left parenthesis".to_string());
             },
         }
-        let mut found_first = false;
-        let variable_list_or_statement = match self.tokens[self.head] {
+        // Both a declaration and a statement end with their own semicolon.
+        let variable_list_or_statement = match self.current()? {
             Token::SemiColon => {
                 self.head += 1;
                 None
             },
-            _ => {
-                found_first = true;
-                Some(Box::new(self.variable_list_or_statement()?))
-            },
+            _ => Some(Box::new(self.variable_list_or_statement()?)),
         };
-        if found_first {
-            match self.tokens[self.head] {
-                Token::SemiColon => {
-                    self.head += 1;
-                },
-                _ => {
-                    return Err("Expected semicolon".to_string());
-                },
-            }
-        }
 
         let mut found_second = false;
-        let expression1 = match self.tokens[self.head] {
+        let expression1 = match self.current()? {
             Token::SemiColon => {
                 self.head += 1;
                 None
             },
             _ => {
                 found_second = true;
-                Some(self.expression(None)?)
+                Some(self.expression()?)
             },
         };
         if found_second {
-            match self.tokens[self.head] {
+            match self.current()? {
                 Token::SemiColon => {
                     self.head += 1;
                 },
@@ -905,18 +1266,18 @@ impl Parser {
         }
 
         let mut found_third = false;
-        let expression2 = match self.tokens[self.head] {
+        let expression2 = match self.current()? {
             Token::RightParen => {
                 self.head += 1;
                 None
             },
             _ => {
                 found_third = true;
-                Some(self.expression(None)?)
+                Some(self.expression()?)
             },
         };
         if found_third {
-            match self.tokens[self.head] {
+            match self.current()? {
                 Token::RightParen => {
                     self.head += 1;
                 },
@@ -928,41 +1289,30 @@ impl Parser {
         
         let block_or_statement = self.block_or_statement()?;
 
-        return Ok(Statement::For(variable_list_or_statement, expression1, expression2, Box::new(block_or_statement)));
+        Ok(Statement::For(variable_list_or_statement, expression1, expression2, Box::new(block_or_statement)))
     }
 
     fn statement_switch(&mut self) -> Result<Statement, String> {
         let expression = self.conditional_expression()?;
+        match self.tokens.get(self.head) {
+            Some(Token::LeftBrace) => {
+                self.head += 1;
+            },
+            _ => {
+                return Err("Expected left brace after switch".to_string());
+            },
+        }
         let mut cases = Vec::new();
 
         while self.head < self.tokens.len() {
-            match self.tokens[self.head] {
+            let label = match self.current()? {
                 Token::Case => {
                     self.head += 1;
-                    let expression = self.conditional_expression()?;
-                    match self.tokens[self.head] {
-                        Token::Colon => {
-                            self.head += 1;
-                            let block_or_statement = self.block_or_statement()?;
-                            cases.push(SwitchCase {default: false, expression: Some(expression), body: Box::new(block_or_statement)});
-                        },
-                        _ => {
-                            return Err("Expected colon".to_string());
-                        },
-                    }
+                    Some(self.ternary_expression()?)
                 },
                 Token::Default => {
                     self.head += 1;
-                    match self.tokens[self.head] {
-                        Token::Colon => {
-                            self.head += 1;
-                            let block_or_statement = self.block_or_statement()?;
-                            cases.push(SwitchCase {default: true, expression: None, body: Box::new(block_or_statement)});
-                        },
-                        _ => {
-                            return Err("Expected colon".to_string());
-                        },
-                    }
+                    None
                 },
                 Token::RightBrace => {
                     self.head += 1;
@@ -971,697 +1321,602 @@ impl Parser {
                 _ => {
                     return Err("Expected case or default".to_string());
                 },
+            };
+            match self.tokens.get(self.head) {
+                Some(Token::Colon) => {
+                    self.head += 1;
+                },
+                _ => {
+                    return Err("Expected colon".to_string());
+                },
             }
+            let body = self.case_body()?;
+            cases.push(SwitchCase {default: label.is_none(), expression: label, body: Box::new(body)});
         }
 
+        Err("Unexpected end of file in switch".to_string())
+    }
 
-        return Ok(Statement::Switch(expression, cases))
+    /// Parses the statements after a case label, up to the next label or the end of the switch.
+    fn case_body(&mut self) -> Result<BlockOrStatement, String> {
+        let mut statements = Vec::new();
+        while let Some(token) = self.tokens.get(self.head) {
+            if matches!(token, Token::Case | Token::Default | Token::RightBrace) {
+                break;
+            }
+            statements.push(match self.block_or_statement()? {
+                BlockOrStatement::Block(block) => Statement::Block(Box::new(block)),
+                BlockOrStatement::Statement(statement) => statement,
+            });
+        }
+        if statements.len() == 1 {
+            return Ok(BlockOrStatement::Statement(statements.remove(0)));
+        }
+        Ok(BlockOrStatement::Block(CodeBlock::Code(StatementList {statements})))
     }
-    
 
     fn conditional_expression(&mut self) -> Result<Expression,String> {
-        match self.tokens[self.head] {
+        match self.current()? {
             Token::LeftParen => {
                 self.head += 1;
-                let expression = self.expression(None)?;
-                match self.tokens[self.head] {
+                let expression = self.expression()?;
+                match self.current()? {
                     Token::RightParen => {
                         self.head += 1;
-                        return Ok(expression);
+                        Ok(expression)
                     },
                     _ => {
-                        return Err("Expected right parenthesis".to_string());
+                        Err("Expected right parenthesis".to_string())
                     },
                 }
             },
             _ => {
-                return Err("Expected left parenthesis".to_string());
+                Err("Expected left parenthesis".to_string())
             },
         }
     }
 
     fn block_or_statement(&mut self) -> Result<BlockOrStatement,String> {
-        match self.tokens[self.head] {
+        match self.current()? {
             Token::LeftBrace => {
                 self.head += 1;
                 let code_block = self.code_block()?;
-                return Ok(BlockOrStatement::Block(code_block));
+                Ok(BlockOrStatement::Block(code_block))
             },
             _ => {
                 let statement = self.statement()?;
-                return Ok(BlockOrStatement::Statement(statement));
+                Ok(BlockOrStatement::Statement(statement))
             },
         }
     }
 
     fn variable_list_or_statement(&mut self) -> Result<VariableListOrStatement,String> {
-        match self.tokens[self.head] {
-            Token::Type(_) => {
-                let variable_list = self.variable_list_or_function()?;
+        match self.current()? {
+            token if self.starts_declaration(token) => {
+                let specifiers = self.declaration_specifiers()?;
+                let variable_list = self.variable_list_or_function(specifiers)?;
 
                 match variable_list {
                     AstNode::VariableList(variable_list) => {
-                        return Ok(VariableListOrStatement::VariableList(variable_list));
+                        self.declare_variables(&variable_list);
+                        Ok(VariableListOrStatement::VariableList(variable_list))
                     },
                     _ => {
-                        return Err("Expected variable list".to_string());
+                        Err("Expected variable list".to_string())
                     },
                 }
                 
-            },//todo: add in structs, unions, enums, etc
+            },
             _ => {
                 let statement = self.statement()?;
-                return Ok(VariableListOrStatement::Statement(statement));
+                Ok(VariableListOrStatement::Statement(statement))
             },
         }
     }
 
-    fn expression(&mut self, expression: Option<Expression>) -> Result<Expression,String> {
-
-        let mut full_expression = None;
-
-        match expression {
-            Some(expression) => {
-                match self.tokens[self.head] {
-                    Token::LeftBracket => {
-                        let expr = self.expression(None)?;
-                        match &self.tokens[self.head] {
-                            RightBracket => {
-                                self.head += 1;
-                                full_expression = Some(self.expression(Some(Expression::Binary(
-                                    BinaryOperator::ArrayAccess,
-                                    Box::new(expression),
-                                    Box::new(expr),)))?);
-                            },
-                            _ => {
-                                return Err("Expected right bracket".to_string());
-                            },
-                        }
-                        
-                    },
-                    Token::Period => {
-                        self.head += 1;
-                        let terminal_expr = self.expression(None)?;
-                        full_expression = Some(self.expression(Some(Expression::Binary(
-                            BinaryOperator::MemberAccess,Box::new(expression),
-                            Box::new(terminal_expr))))?);
-                    },
-                    Token::Arrow => {
-                        self.head += 1;
-                        let terminal_expr = self.expression(None)?;
-                        full_expression = Some(self.expression(Some(Expression::Binary(
-                            BinaryOperator::PointerMemberAccess,Box::new(expression),
-                            Box::new(terminal_expr))))?);
-                    },
-                    Token::LeftParen => {
-                        self.head += 1;
-
-                        match &self.tokens[self.head] {
-                            Token::RightParen => {
-                                self.head += 1;
-                                full_expression = Some(self.expression(Some(
-                                    Expression::CallFunction(expression.get_value().expect("Not identifier"), None)))?);
-                            },
-                            _ => {
-                                let args = self.expression(None)?;
-                                match &self.tokens[self.head] {
-                                    Token::RightParen => {
-                                        self.head += 1;
-                                        full_expression = Some(self.expression(Some(
-                                            Expression::CallFunction(expression.get_value().expect("Not identifier"), Some(Box::new(args)))))?);
-                                    },
-                                    _ => {
-                                        return Err("Expected right parenthesis".to_string());
-                                    },
-                                }
-                            },
-                        }
-                    },
-                    Token::LeftBrace => {
-                        self.head += 1;
-                        match expression {
-                            Expression::Identifier(ident) => {
-                                full_expression = Some(
-                                    Expression::TaggedInitializer(ident, Box::new(self.expression(None)?)));
-                                match self.tokens[self.head] {
-                                    Token::RightBrace => {
-                                        self.head += 1;
-                                    },
-                                    _ => return Err(format!("Expected right brace, got {:?}", self.tokens[self.head])),
-                                }
-                            }
-                            _ => {
-                                return Err(format!("Expected identifier, got {:?}", expression));
-                            }
+    /// Parses a full expression, including the comma operator.
+    fn expression(&mut self) -> Result<Expression,String> {
+        self.nested(|parser| {
+            let mut expression = parser.assignment_expression()?;
+            while parser.tokens.get(parser.head) == Some(&Token::Comma) {
+                parser.head += 1;
+                expression = Expression::Binary(
+                    BinaryOperator::Comma,
+                    Box::new(expression),
+                    Box::new(parser.assignment_expression()?));
+            }
+            Ok(expression)
+        })
+    }
+
+    /// Parses an expression that stops at a top-level comma, as in initializers and arguments.
+    /// Assignment is right associative, so the targets are collected and the chain is built
+    /// from the right.
+    fn assignment_expression(&mut self) -> Result<Expression,String> {
+        let mut targets = Vec::new();
+        let mut expression = self.ternary_expression()?;
+        loop {
+            let operator = match self.tokens.get(self.head) {
+                Some(Token::Assignment) => BinaryOperator::Assign,
+                Some(Token::PlusEquals) => BinaryOperator::AddAssign,
+                Some(Token::MinusEquals) => BinaryOperator::SubtractAssign,
+                Some(Token::StarEquals) => BinaryOperator::MultiplyAssign,
+                Some(Token::DivideEquals) => BinaryOperator::DivideAssign,
+                Some(Token::ModuloEquals) => BinaryOperator::ModuloAssign,
+                Some(Token::BitwiseLeftShiftEquals) => BinaryOperator::LeftShiftAssign,
+                Some(Token::BitwiseRightShiftEquals) => BinaryOperator::RightShiftAssign,
+                Some(Token::BitwiseAndEquals) => BinaryOperator::BitwiseAndAssign,
+                Some(Token::BitwiseXorEquals) => BinaryOperator::BitwiseXorAssign,
+                Some(Token::BitwiseOrEquals) => BinaryOperator::BitwiseOrAssign,
+                _ => break,
+            };
+            self.head += 1;
+            targets.push((operator, expression));
+            expression = self.ternary_expression()?;
+        }
+        Ok(targets.into_iter().rev().fold(expression, |value, (operator, target)|
+            Expression::Binary(operator, Box::new(target), Box::new(value))))
+    }
 
-                        }
+    /// Parses a whole token list as one constant expression, such as the condition of an `#if`.
+    pub fn constant_expression(&mut self) -> Result<Expression,String> {
+        let expression = self.ternary_expression()?;
+        match self.tokens.get(self.head) {
+            None => Ok(expression),
+            Some(token) => Err(format!("Unexpected {:?} after constant expression", token)),
+        }
+    }
 
+    /// Parses a conditional expression, which is also the grammar of a constant expression.
+    /// A chain of them nests to the right, so it is built from the right like assignments.
+    fn ternary_expression(&mut self) -> Result<Expression,String> {
+        let mut branches = Vec::new();
+        let mut condition = self.binary_expression(0)?;
+        while self.tokens.get(self.head) == Some(&Token::QuestionMark) {
+            self.head += 1;
+            let then = self.expression()?;
+            match self.tokens.get(self.head) {
+                Some(Token::Colon) => {
+                    self.head += 1;
+                },
+                _ => {
+                    return Err("Expected colon".to_string());
+                },
+            }
+            branches.push((condition, then));
+            condition = self.binary_expression(0)?;
+        }
+        Ok(branches.into_iter().rev().fold(condition, |otherwise, (condition, then)|
+            Expression::Ternary(Box::new(condition), Box::new(then), Box::new(otherwise))))
+    }
+
+    /// Parses binary operators by precedence climbing; every level is left associative.
+    fn binary_expression(&mut self, min_precedence: u8) -> Result<Expression,String> {
+        let mut expression = self.unary_expression()?;
+        loop {
+            let (operator, precedence) = match self.tokens.get(self.head) {
+                Some(Token::LogicalOr) => (BinaryOperator::LogicalOr, 1),
+                Some(Token::LogicalAnd) => (BinaryOperator::LogicalAnd, 2),
+                Some(Token::BitwiseOr) => (BinaryOperator::BitwiseOr, 3),
+                Some(Token::BitwiseXor) => (BinaryOperator::BitwiseXor, 4),
+                Some(Token::BitwiseAnd) => (BinaryOperator::BitwiseAnd, 5),
+                Some(Token::Equals) => (BinaryOperator::Equal, 6),
+                Some(Token::NotEquals) => (BinaryOperator::NotEqual, 6),
+                Some(Token::LessThan) => (BinaryOperator::LessThan, 7),
+                Some(Token::GreaterThan) => (BinaryOperator::GreaterThan, 7),
+                Some(Token::LessThanOrEqual) => (BinaryOperator::LessThanOrEqual, 7),
+                Some(Token::GreaterThanOrEqual) => (BinaryOperator::GreaterThanOrEqual, 7),
+                Some(Token::BitwiseLeftShift) => (BinaryOperator::LeftShift, 8),
+                Some(Token::BitwiseRightShift) => (BinaryOperator::RightShift, 8),
+                Some(Token::Plus) => (BinaryOperator::Add, 9),
+                Some(Token::Minus) => (BinaryOperator::Subtract, 9),
+                Some(Token::Star) => (BinaryOperator::Multiply, 10),
+                Some(Token::Divide) => (BinaryOperator::Divide, 10),
+                Some(Token::Modulo) => (BinaryOperator::Modulo, 10),
+                _ => break,
+            };
+            if precedence <= min_precedence {
+                break;
+            }
+            self.head += 1;
+            let right = self.binary_expression(precedence)?;
+            expression = Expression::Binary(operator, Box::new(expression), Box::new(right));
+        }
+        Ok(expression)
+    }
+
+    fn unary_expression(&mut self) -> Result<Expression,String> {
+        self.nested(|parser| {
+            let operator = match parser.tokens.get(parser.head) {
+                Some(Token::Increment) => UnaryOperator::PreIncrement,
+                Some(Token::Decrement) => UnaryOperator::PreDecrement,
+                Some(Token::Star) => UnaryOperator::Dereference,
+                Some(Token::BitwiseAnd) => UnaryOperator::AddressOf,
+                Some(Token::Plus) => UnaryOperator::Plus,
+                Some(Token::Minus) => UnaryOperator::Minus,
+                Some(Token::LogicalNot) => UnaryOperator::LogicalNot,
+                Some(Token::BitwiseNot) => UnaryOperator::BitwiseNot,
+                Some(Token::Sizeof) => {
+                    parser.head += 1;
+                    return Ok(Expression::Sizeof(parser.type_or_expression()?));
+                },
+                Some(Token::Alignof) => {
+                    parser.head += 1;
+                    return match parser.type_or_expression()? {
+                        TypeOrExpression::Type(type_) => Ok(Expression::Alignof(type_)),
+                        TypeOrExpression::Expression(_) => Err("alignof requires a type name".to_string()),
+                    };
+                },
+                Some(Token::Word(name)) if name == "offsetof" && parser.tokens.get(parser.head + 1) == Some(&Token::LeftParen) => {
+                    parser.head += 2;
+                    let type_ = parser.type_name()?;
+                    if parser.tokens.get(parser.head) != Some(&Token::Comma) {
+                        return Err("Expected a comma after the type in offsetof".to_string());
                     }
-                    Token::Plus => {
-                        self.head += 1;
-                        full_expression = Some(Expression::Binary(
-                            BinaryOperator::Add,
-                            Box::new(expression),
-                            Box::new(self.expression(None)?),));
-
-                    },
-                    Token::Minus => {
-                        self.head += 1;
-                        full_expression = Some(Expression::Binary(
-                            BinaryOperator::Subtract,
-                            Box::new(expression),
-                            Box::new(self.expression(None)?),));
-
-                    },
-                    Token::Star => {
-                        self.head += 1;
-                        full_expression = Some(Expression::Binary(
-                            BinaryOperator::Multiply,
-                            Box::new(expression),
-                            Box::new(self.expression(None)?),));
-
-                    },
-                    Token::Divide => {
-                        self.head += 1;
-                        full_expression = Some(Expression::Binary(
-                            BinaryOperator::Divide,
-                            Box::new(expression),
-                            Box::new(self.expression(None)?),));
-
-                    },
-                    Token::Modulo => {
-                        self.head += 1;
-                        full_expression = Some(Expression::Binary(
-                            BinaryOperator::Modulo,
-                            Box::new(expression),
-                            Box::new(self.expression(None)?),));
-
-                    },
-                    Token::BitwiseLeftShift => {
-                        self.head += 1;
-                        full_expression = Some(Expression::Binary(
-                            BinaryOperator::LeftShift,
-                            Box::new(expression),
-                            Box::new(self.expression(None)?),));
-
-                    },
-                    Token::BitwiseRightShift => {
-                        self.head += 1;
-                        full_expression = Some(Expression::Binary(
-                            BinaryOperator::RightShift,
-                            Box::new(expression),
-                            Box::new(self.expression(None)?),));
-
-                    },
-                    Token::BitwiseAnd => {
-                        self.head += 1;
-                        full_expression = Some(Expression::Binary(
-                            BinaryOperator::BitwiseAnd,
-                            Box::new(expression),
-                            Box::new(self.expression(None)?),));
-
-                    },
-                    Token::BitwiseOr => {
-                        self.head += 1;
-                        full_expression = Some(Expression::Binary(
-                            BinaryOperator::BitwiseOr,
-                            Box::new(expression),
-                            Box::new(self.expression(None)?),));
-
-                    },
-                    Token::BitwiseXor => {
-                        self.head += 1;
-                        full_expression = Some(Expression::Binary(
-                            BinaryOperator::BitwiseXor,
-                            Box::new(expression),
-                            Box::new(self.expression(None)?),));
-
-                    },
-                    Token::Equals => {
-                        self.head += 1;
-                        full_expression = Some(Expression::Binary(
-                            BinaryOperator::Equal,
-                            Box::new(expression),
-                            Box::new(self.expression(None)?),));
-
-                    },
-                    Token::NotEquals => {
-                        self.head += 1;
-                        full_expression = Some(Expression::Binary(
-                            BinaryOperator::NotEqual,
-                            Box::new(expression),
-                            Box::new(self.expression(None)?),));
-
-                    },
-                    Token::LessThan => {
-                        self.head += 1;
-                        full_expression = Some(Expression::Binary(
-                            BinaryOperator::LessThan,
-                            Box::new(expression),
-                            Box::new(self.expression(None)?),));
-
-                    },
-                    Token::LessThanOrEqual => {
-                        self.head += 1;
-                        full_expression = Some(Expression::Binary(
-                            BinaryOperator::LessThanOrEqual,
-                            Box::new(expression),
-                            Box::new(self.expression(None)?),));
-
-                    },
-                    Token::GreaterThan => {
-                        self.head += 1;
-                        full_expression = Some(Expression::Binary(
-                            BinaryOperator::GreaterThan,
-                            Box::new(expression),
-                            Box::new(self.expression(None)?),));
-
-                    },
-                    Token::GreaterThanOrEqual => {
-                        self.head += 1;
-                        full_expression = Some(Expression::Binary(
-                            BinaryOperator::GreaterThanOrEqual,
-                            Box::new(expression),
-                            Box::new(self.expression(None)?),));
-
-                    },
-                    Token::LogicalAnd => {
-                        self.head += 1;
-                        full_expression = Some(Expression::Binary(
-                            BinaryOperator::LogicalAnd,
-                            Box::new(expression),
-                            Box::new(self.expression(None)?),));
-
-                    },
-                    Token::LogicalOr => {
-                        self.head += 1;
-                        full_expression = Some(Expression::Binary(
-                            BinaryOperator::LogicalOr,
-                            Box::new(expression),
-                            Box::new(self.expression(None)?),));
-
-                    },
-                    Token::Increment => {
-                        self.head += 1;
-                        full_expression = Some(Expression::Unary(
-                            UnaryOperator::PostIncrement,
-                            Box::new(expression),));
-
-                    },
-                    Token::Decrement => {
-                        self.head += 1;
-                        full_expression = Some(Expression::Unary(
-                            UnaryOperator::PostDecrement,
-                            Box::new(expression),));
-
-                    },
-                    Token::Assignment => {
-                        self.head += 1;
-                        full_expression = Some(Expression::Binary(
-                            BinaryOperator::Assign,
-                            Box::new(expression),
-                            Box::new(self.expression(None)?),));
-
-                    },
-                    Token::PlusEquals => {
-                        self.head += 1;
-                        full_expression = Some(Expression::Binary(
-                            BinaryOperator::AddAssign,
-                            Box::new(expression),
-                            Box::new(self.expression(None)?),));
-
-                    },
-                    Token::MinusEquals => {
-                        self.head += 1;
-                        full_expression = Some(Expression::Binary(
-                            BinaryOperator::SubtractAssign,
-                            Box::new(expression),
-                            Box::new(self.expression(None)?),));
-
-                    },
-                    Token::StarEquals => {
-                        self.head += 1;
-                        full_expression = Some(Expression::Binary(
-                            BinaryOperator::MultiplyAssign,
-                            Box::new(expression),
-                            Box::new(self.expression(None)?),));
-
-                    },
-                    Token::DivideEquals => {
-                        self.head += 1;
-                        full_expression = Some(Expression::Binary(
-                            BinaryOperator::DivideAssign,
-                            Box::new(expression),
-                            Box::new(self.expression(None)?),));
-
-                    },
-                    Token::ModuloEquals => {
-                        self.head += 1;
-                        full_expression = Some(Expression::Binary(
-                            BinaryOperator::ModuloAssign,
-                            Box::new(expression),
-                            Box::new(self.expression(None)?),));
-
-                    },
-                    Token::BitwiseLeftShiftEquals => {
-                        self.head += 1;
-                        full_expression = Some(Expression::Binary(
-                            BinaryOperator::LeftShiftAssign,
-                            Box::new(expression),
-                            Box::new(self.expression(None)?),));
-
-                    },
-                    Token::BitwiseRightShiftEquals => {
-                        self.head += 1;
-                        full_expression = Some(Expression::Binary(
-                            BinaryOperator::RightShiftAssign,
-                            Box::new(expression),
-                            Box::new(self.expression(None)?),));
-
-                    },
-                    Token::BitwiseAndEquals => {
-                        self.head += 1;
-                        full_expression = Some(Expression::Binary(
-                            BinaryOperator::BitwiseAndAssign,
-                            Box::new(expression),
-                            Box::new(self.expression(None)?),));
-
-                    },
-                    Token::BitwiseXorEquals => {
-                        self.head += 1;
-                        full_expression = Some(Expression::Binary(
-                            BinaryOperator::BitwiseXorAssign,
-                            Box::new(expression),
-                            Box::new(self.expression(None)?),));
+                    parser.head += 1;
+                    let member = match parser.tokens.get(parser.head) {
+                        Some(Token::Word(member)) => member.clone(),
+                        _ => return Err("Expected a member name in offsetof".to_string()),
+                    };
+                    parser.head += 1;
+                    if parser.tokens.get(parser.head) != Some(&Token::RightParen) {
+                        return Err("Expected right parenthesis".to_string());
+                    }
+                    parser.head += 1;
+                    return Ok(Expression::Offsetof(Box::new(type_), member));
+                },
+                Some(Token::LeftParen) if parser.tokens.get(parser.head + 1).is_some_and(|token| parser.starts_type_name(token)) => {
+                    parser.head += 1;
+                    let type_ = parser.type_name()?;
+                    match parser.tokens.get(parser.head) {
+                        Some(Token::RightParen) => {
+                            parser.head += 1;
+                        },
+                        _ => {
+                            return Err("Expected right parenthesis".to_string());
+                        },
+                    }
+                    return Ok(Expression::Unary(UnaryOperator::Cast(Box::new(type_)), Box::new(parser.unary_expression()?)));
+                },
+                _ => return parser.postfix_expression(),
+            };
+            parser.head += 1;
+            Ok(Expression::Unary(operator, Box::new(parser.unary_expression()?)))
+        })
+    }
 
-                    },
-                    Token::BitwiseOrEquals => {
-                        self.head += 1;
-                        full_expression = Some(Expression::Binary(
-                            BinaryOperator::BitwiseOrAssign,
-                            Box::new(expression),
-                            Box::new(self.expression(None)?),));
+    /// Parses the arguments of a call after its `(`, up to and including the `)`.
+    fn call_arguments(&mut self) -> Result<Option<Box<Expression>>,String> {
+        let arguments = match self.tokens.get(self.head) {
+            Some(Token::RightParen) => None,
+            _ => Some(Box::new(self.expression()?)),
+        };
+        match self.tokens.get(self.head) {
+            Some(Token::RightParen) => {
+                self.head += 1;
+                Ok(arguments)
+            },
+            _ => {
+                Err("Expected right parenthesis".to_string())
+            },
+        }
+    }
 
-                    },
-                    Token::Comma => {
-                        self.head += 1;
-                        full_expression = Some(Expression::Binary(
-                            BinaryOperator::Comma,
-                            Box::new(expression),
-                            Box::new(self.expression(None)?),));
+    fn postfix_expression(&mut self) -> Result<Expression,String> {
+        let mut expression = self.primary_expression()?;
+        loop {
+            match self.tokens.get(self.head) {
+                Some(Token::LeftBracket) => {
+                    self.head += 1;
+                    let index = self.expression()?;
+                    match self.tokens.get(self.head) {
+                        Some(Token::RightBracket) => {
+                            self.head += 1;
+                        },
+                        _ => {
+                            return Err("Expected right bracket".to_string());
+                        },
+                    }
+                    expression = Expression::Binary(BinaryOperator::ArrayAccess, Box::new(expression), Box::new(index));
+                },
+                Some(Token::LeftParen) => {
+                    self.head += 1;
+                    expression = match expression.get_value() {
+                        Some(name) => Expression::CallFunction(name, self.call_arguments()?),
+                        None => Expression::Call(Box::new(expression), self.call_arguments()?),
+                    };
+                },
+                Some(token @ (Token::Period | Token::Arrow)) => {
+                    let operator = match token {
+                        Token::Period => BinaryOperator::MemberAccess,
+                        _ => BinaryOperator::PointerMemberAccess,
+                    };
+                    self.head += 1;
+                    let member = match self.tokens.get(self.head) {
+                        Some(Token::Word(name)) => name.clone(),
+                        _ => {
+                            return Err("Expected identifier".to_string());
+                        },
+                    };
+                    self.head += 1;
+                    let member = match self.tokens.get(self.head) {
+                        Some(Token::LeftParen) => {
+                            self.head += 1;
+                            Expression::CallFunction(member, self.call_arguments()?)
+                        },
+                        _ => Expression::Identifier(member),
+                    };
+                    expression = Expression::Binary(operator, Box::new(expression), Box::new(member));
+                },
+                Some(Token::Increment) => {
+                    self.head += 1;
+                    expression = Expression::Unary(UnaryOperator::PostIncrement, Box::new(expression));
+                },
+                Some(Token::Decrement) => {
+                    self.head += 1;
+                    expression = Expression::Unary(UnaryOperator::PostDecrement, Box::new(expression));
+                },
+                Some(Token::LeftBrace) => {
+                    let Expression::Identifier(ident) = expression else {
+                        return Ok(expression);
+                    };
+                    self.head += 1;
+                    let value = self.expression()?;
+                    match self.tokens.get(self.head) {
+                        Some(Token::RightBrace) => {
+                            self.head += 1;
+                        },
+                        token => {
+                            return Err(format!("Expected right brace, got {:?}", token));
+                        },
+                    }
+                    expression = Expression::TaggedInitializer(ident, Box::new(value));
+                },
+                _ => return Ok(expression),
+            }
+        }
+    }
 
-                    },
-                    Token::QuestionMark => {
-                        self.head += 1;
-                        let expr = self.expression(None)?;
-                        match self.tokens[self.head] {
-                            Token::Colon => {
-                                self.head += 1;
-                                full_expression = Some(Expression::Ternary(
-                                    Box::new(expression),
-                                    Box::new(expr),
-                                    Box::new(self.expression(None)?),));
-                            },
-                            _ => {
-                                return Err("Expected colon".to_string());
-                            },
-                        }
+    /// Parses a `_Generic` selection after the keyword. At most one association may be
+    /// `default`, and no type may appear twice.
+    fn generic_selection(&mut self) -> Result<Expression,String> {
+        match self.tokens.get(self.head) {
+            Some(Token::LeftParen) => {
+                self.head += 1;
+            },
+            token => {
+                return Err(format!("Expected ( after _Generic but found {:?}", token));
+            },
+        }
+        let control = self.assignment_expression()?;
+        let mut associations: Vec<GenericAssociation> = Vec::new();
+        loop {
+            match self.tokens.get(self.head) {
+                Some(Token::Comma) => {
+                    self.head += 1;
+                },
+                Some(Token::RightParen) if !associations.is_empty() => {
+                    self.head += 1;
+                    return Ok(Expression::Generic(Box::new(control), associations));
+                },
+                token => {
+                    return Err(format!("Expected , or ) in _Generic but found {:?}", token));
+                },
+            }
+            let type_ = match self.tokens.get(self.head) {
+                Some(Token::Default) => {
+                    self.head += 1;
+                    None
+                },
+                _ => Some(self.type_name()?),
+            };
+            match self.tokens.get(self.head) {
+                Some(Token::Colon) => {
+                    self.head += 1;
+                },
+                token => {
+                    return Err(format!("Expected : in _Generic association but found {:?}", token));
+                },
+            }
+            if associations.iter().any(|association| association.type_ == type_) {
+                return match type_ {
+                    Some(type_) => Err(format!("_Generic has two associations for {:?}", type_)),
+                    None => Err("_Generic has two default associations".to_string()),
+                };
+            }
+            if matches!(&type_, Some(DeclaredType::Function(..))) {
+                return Err("_Generic associations cannot have function types".to_string());
+            }
+            associations.push(GenericAssociation {type_, expression: self.assignment_expression()?});
+        }
+    }
 
+    fn primary_expression(&mut self) -> Result<Expression,String> {
+        let Some(token) = self.tokens.get(self.head).cloned() else {
+            return Err("Unexpected end of file in expression".to_string());
+        };
+        self.head += 1;
+        match token {
+            Token::Word(ident) => Ok(Expression::Identifier(ident)),
+            Token::Number(num) => Ok(Expression::Literal(Literal::Number(num))),
+            Token::String(string) => Ok(Expression::Literal(Literal::String(string))),
+            Token::Character(character) => Ok(Expression::Literal(Literal::Char(character))),
+            Token::True => Ok(Expression::Literal(Literal::Bool(true))),
+            Token::False => Ok(Expression::Literal(Literal::Bool(false))),
+            Token::Nullptr => Ok(Expression::Literal(Literal::Nullptr)),
+            Token::GenericSelection => self.generic_selection(),
+            Token::Period => {
+                match self.tokens.get(self.head) {
+                    Some(Token::Word(ident)) => {
+                        let ident = ident.clone();
+                        self.head += 1;
+                        Ok(Expression::Unary(UnaryOperator::MemberSet, Box::new(Expression::Identifier(ident))))
                     },
                     _ => {
-                        full_expression = Some(Expression::Blank);
+                        Err("Expected identifier".to_string())
                     },
                 }
-                return Ok(full_expression.unwrap());
             },
-            None => {
-                match self.tokens[self.head].clone() {
-                    Token::Word(ident) => {//check for function call or typedef
-                        self.head += 1;
-                        full_expression = Some(self.expression(Some(Expression::Identifier(ident)))?);
-                    },
-                    Token::Number(num) => {
-                        self.head += 1;
-                        full_expression = Some(self.expression(Some(Expression::Literal(Literal::Number(num))))?);
-                    },
-                    Token::String(string) => {
-                        self.head += 1;
-                        full_expression = Some(self.expression(Some(Expression::Literal(Literal::String(string))))?);
-                    },
-                    Token::Character(character) => {
-                        self.head += 1;
-                        full_expression = Some(self.expression(Some(Expression::Literal(Literal::Char(character))))?);
-                    },
-                    Token::True => {
-                        self.head += 1;
-                        full_expression = Some(self.expression(Some(Expression::Literal(Literal::Bool(true))))?);
-                    },
-                    Token::False => {
-                        self.head += 1;
-                        full_expression = Some(self.expression(Some(Expression::Literal(Literal::Bool(false))))?);
-                    },
-                    Token::Period => {
-                        self.head += 1;
-
-                        match &self.tokens[self.head] {
-                            Token::Word(ident) => {
-                                self.head += 1;
-                                full_expression = Some(self.expression(Some(Expression::Unary(UnaryOperator::MemberSet, Box::new(Expression::Identifier(ident.clone())))))?);
-                            },
-                            _ => {
-                                return Err("Expected identifier".to_string());
-                            },
-                        }
-                        
-                    },
-                     Token::LeftParen => {//check for cast, compoundLiteral
-                        self.head += 1;
-                        match &self.tokens[self.head] {
-                            Token::Type(the_type) => {
-                                self.head += 1;
-                                match self.tokens[self.head] {
-                                    Token::RightParen => {
-                                        self.head += 1;
-                                        let pointer = match self.tokens[self.head] {
-                                            Token::Star => {
-                                                self.head += 1;
-                                                let mut ptr = 1;
-                                                while self.tokens[self.head] == Token::Star {
-                                                    self.head += 1;
-                                                    ptr += 1;
-                                                }
-                                                ptr
-                                            },
-                                            _ => {
-                                                0
-                                            },
-                                        };
-                                        full_expression = Some(
-                                            Expression::Unary(UnaryOperator::Cast(Type::from_token(self.tokens[self.head - 1].clone())?,
-                                                                                  pointer),
-                                                              Box::new(self.expression(None)?)));
-                                    },
-                                    _ => {
-                                        return Err("Expected right parenthesis".to_string());
-                                    },
-                                }
-                            },
-                            _ => {
-                                let expression = self.expression(None)?;
-                                match self.tokens[self.head] {
-                                    Token::RightParen => {
-                                        self.head += 1;
-                                        full_expression = Some(Expression::Parentheses(Box::new(expression)));
-                                    },
-                                    _ => {
-                                        return Err("Expected right parenthesis".to_string());
-                                    },
-                                }
-                            },
-
-                        }
-                    },
-                    Token::Increment => {
-                        self.head += 1;
-                        full_expression = Some(Expression::Unary(UnaryOperator::PreIncrement, Box::new(self.expression(None)?)));
-                    },
-                    Token::Decrement => {
-                        self.head += 1;
-                        full_expression = Some(Expression::Unary(UnaryOperator::PreDecrement, Box::new(self.expression(None)?)));
-                    },
-                    Token::Star => {
-                        self.head += 1;
-                        full_expression = Some(Expression::Unary(UnaryOperator::Dereference, Box::new(self.expression(None)?)));
-                    },
-                    Token::BitwiseAnd => {
-                        self.head += 1;
-                        full_expression = Some(Expression::Unary(UnaryOperator::AddressOf, Box::new(self.expression(None)?)));
-                    },
-                    Token::Plus => {
-                        self.head += 1;
-                        full_expression = Some(Expression::Unary(UnaryOperator::Plus, Box::new(self.expression(None)?)));
-                    },
-                    Token::Minus => {
-                        self.head += 1;
-                        full_expression = Some(Expression::Unary(UnaryOperator::Minus, Box::new(self.expression(None)?)));
-                    },
-                    Token::LogicalNot => {
-                        self.head += 1;
-                        full_expression = Some(Expression::Unary(UnaryOperator::LogicalNot, Box::new(self.expression(None)?)));
-                    },
-                    Token::BitwiseNot => {
-                        self.head += 1;
-                        full_expression = Some(Expression::Unary(UnaryOperator::BitwiseNot, Box::new(self.expression(None)?)));
-                    },
-                    Token::Sizeof => {
+            Token::LeftParen => {
+                let expression = self.expression()?;
+                match self.tokens.get(self.head) {
+                    Some(Token::RightParen) => {
                         self.head += 1;
-                        let type_or_expression = self.type_or_expression()?;
-
-                        full_expression = Some(Expression::Sizeof(type_or_expression));
-
-                    },
-                    Token::LeftBrace => {
-                        self.head += 1;
-
-                        let expr = self.expression(None)?;
-
-                        match self.tokens[self.head] {
-                            Token::RightBrace => {
-                                self.head += 1;
-                                full_expression = Some(Expression::InitializerList(Box::new(expr)));
-                            },
-                            _ => {
-                                return Err("Expected right brace".to_string());
-                            },
-                        }
+                        Ok(Expression::Parentheses(Box::new(expression)))
                     },
                     _ => {
-                        return Err(format!("Unexpected token in expression: {:?}", self.tokens[self.head]));
+                        Err("Expected right parenthesis".to_string())
                     },
-                    
                 }
-
-                return Ok(full_expression.unwrap());
             },
-        }
-    }
-
-    fn type_or_expression(&mut self) -> Result<TypeOrExpression, String> {
-        match self.tokens[self.head] {
-            Token::LeftParen => {
-                self.head += 1;
-                match self.tokens[self.head] {
-                    Token::Type(_) => {
-                        let type_ = Type::from_token(self.tokens[self.head].clone())?;
+            Token::LeftBrace => {
+                let expression = match self.tokens.get(self.head) {
+                    Some(Token::RightBrace) => Expression::Blank,
+                    _ => self.expression()?,
+                };
+                match self.tokens.get(self.head) {
+                    Some(Token::RightBrace) => {
                         self.head += 1;
-                        let mut pointer = 0;
-                        while self.tokens[self.head] == Token::Star {
-                            self.head += 1;
-                            pointer += 1;
-                        }
-
-                        match self.tokens[self.head] {
-                            Token::RightParen => {
-                                self.head += 1;
-                                return Ok(TypeOrExpression::Type(type_, pointer));
-                            },
-                            _ => {
-                                return Err("Expected right parenthesis".to_string());
-                            },
-                        }
-
+                        Ok(Expression::InitializerList(Box::new(expression)))
                     },
                     _ => {
-                        return Err("Expected type".to_string());
+                        Err("Expected right brace".to_string())
                     },
-                
-            }
+                }
             },
-            _ => {
-                return Ok(TypeOrExpression::Expression(Box::new(self.expression(None)?)));
+            token => {
+                self.head -= 1;
+                Err(format!("Unexpected token in expression: {:?}", token))
             },
-            
         }
     }
 
-    fn struct_dec(&mut self, name: &str) -> Result<AstNode, String> {
-        let mut members = Vec::new();
-        while self.tokens[self.head] != Token::RightBrace {
-            self.head += 1;
-            match self.variable_list_or_function()? {
-                AstNode::VariableList(variable_list) => {
-                    members.push(variable_list);
-                },
-                AstNode::Function(function) => {
-                    return Err("Functions not allowed in structs".to_string());
-                },
-                _ => {
-                    return Err("Expected variable list or function".to_string());
-                },
-            }
-        }
-
-        match self.tokens[self.head] {
-            Token::RightBrace => {
+    fn type_or_expression(&mut self) -> Result<TypeOrExpression, String> {
+        match self.tokens.get(self.head + 1) {
+            Some(token) if *self.current()? == Token::LeftParen && self.starts_type_name(token) => {
                 self.head += 1;
-                match self.tokens[self.head] {
-                    Token::SemiColon => {
+                let type_ = self.type_name()?;
+
+                match self.current()? {
+                    Token::RightParen => {
                         self.head += 1;
+                        Ok(TypeOrExpression::Type(Box::new(type_)))
                     },
                     _ => {
-                        return Err("Expected semicolon in Struct".to_string());
+                        Err("Expected right parenthesis".to_string())
                     },
                 }
-                return Ok(AstNode::Struct(Struct {name: name.to_string(), members}));
             },
             _ => {
-                return Err("Expected right brace".to_string());
+                Ok(TypeOrExpression::Expression(Box::new(self.unary_expression()?)))
             },
+            
         }
-        
     }
 
-    fn union_dec(&mut self, name: &str) -> Result<AstNode, String> {
-        let mut members = Vec::new();
-        while self.tokens[self.head] != Token::RightBrace {
-            self.head += 1;
-            match self.variable_list_or_function()? {
-                AstNode::VariableList(variable_list) => {
-                    members.push(variable_list);
-                },
-                AstNode::Function(function) => {
-                    return Err("Functions not allowed in unions".to_string());
-                },
-                _ => {
-                    return Err("Expected variable list or function".to_string());
-                },
+    /// Parses the members of a struct, union or tagged union payload, up to and including the `}`.
+    fn members(&mut self, owner: &str) -> Result<Vec<Member>, String> {
+        self.nested(|parser| {
+            let mut members = Vec::new();
+            while parser.tokens.get(parser.head) != Some(&Token::RightBrace) {
+                let doc = parser.doc_comment(parser.head);
+                let specifiers = parser.declaration_specifiers()?;
+                let mut member = match parser.member(specifiers)? {
+                    AstNode::VariableList(variable_list) => Member::Variables(variable_list),
+                    AstNode::Member(member) => member,
+                    _ => {
+                        return Err(format!("Functions not allowed in {}", owner));
+                    },
+                };
+                member.set_doc(doc);
+                members.push(member);
             }
-        }
+            parser.head += 1;
+            Ok(members)
+        })
+    }
 
-        match self.tokens[self.head] {
-            Token::RightBrace => {
+    /// Parses a member after its specifiers: a nested definition, a list of bit-fields,
+    /// or the declarators of a variable list or method.
+    fn member(&mut self, specifiers: DeclarationSpecifiers) -> Result<AstNode, String> {
+        match (self.tokens.get(self.head), self.tokens.get(self.head + 1)) {
+            (Some(Token::SemiColon), _) => {
                 self.head += 1;
-                match self.tokens[self.head] {
-                    Token::SemiColon => {
-                        self.head += 1;
-                    },
-                    _ => {
-                        return Err("Expected semicolon in Union".to_string());
-                    },
+                match specifiers.definition.map(|definition| *definition) {
+                    Some(Definition::Struct(struct_)) => Ok(AstNode::Member(Member::Struct(struct_))),
+                    Some(Definition::Union(union_)) => Ok(AstNode::Member(Member::Union(union_))),
+                    _ => Err("Member declaration does not declare anything".to_string()),
+                }
+            },
+            (Some(Token::Colon), _) | (Some(Token::Word(_)), Some(Token::Colon)) => {
+                if !specifiers.alignas.is_empty() {
+                    return Err("alignas cannot be used on bit-fields".to_string());
                 }
-                return Ok(AstNode::Union(Union {name: name.to_string(), members}));
+                Ok(AstNode::Member(Member::BitFields(self.bit_fields(specifiers)?)))
             },
             _ => {
-                return Err("Expected right brace".to_string());
+                self.variable_list_or_function(specifiers)
             },
         }
     }
 
-    fn enum_dec(&mut self, name: &str) -> Result<AstNode, String> {
+    fn bit_fields(&mut self, specifiers: DeclarationSpecifiers) -> Result<BitFieldList, String> {
+        if !specifiers.type_.specifier.is_integer() {
+            return Err(format!("Bit-fields must have an integer type, not {:?}", specifiers.type_.specifier));
+        }
+
+        let mut fields = Vec::new();
+        loop {
+            let name = match self.tokens.get(self.head) {
+                Some(Token::Word(name)) => {
+                    let name = name.clone();
+                    self.head += 1;
+                    Some(name)
+                },
+                _ => None,
+            };
+            match self.tokens.get(self.head) {
+                Some(Token::Colon) => {
+                    self.head += 1;
+                },
+                token => {
+                    return Err(format!("Expected : in bit-field but found {:?}", token));
+                },
+            }
+            fields.push(BitField {name, width: self.ternary_expression()?});
+
+            match self.tokens.get(self.head) {
+                Some(Token::Comma) => {
+                    self.head += 1;
+                },
+                Some(Token::SemiColon) => {
+                    self.head += 1;
+                    return Ok(BitFieldList {specifiers, fields, doc: None});
+                },
+                token => {
+                    return Err(format!("Unexpected token in bit-fields: {:?}", token));
+                },
+            }
+        }
+    }
+
+    fn struct_dec(&mut self, name: Option<String>) -> Result<Struct, String> {
+        let members = self.members("structs")?;
+        check_flexible_array(&members, false)?;
+        Ok(Struct {name, attributes: Vec::new(), members, doc: None})
+    }
+
+    fn union_dec(&mut self, name: Option<String>) -> Result<Union, String> {
+        let members = self.members("unions")?;
+        check_flexible_array(&members, true)?;
+        Ok(Union {name, attributes: Vec::new(), members, doc: None})
+    }
+
+    fn enum_dec(&mut self, name: Option<String>) -> Result<Enum, String> {
         let mut members = Vec::new();
-        while self.tokens[self.head] != Token::RightBrace {
+        while *self.current()? != Token::RightBrace {
             members.push(self.enum_member()?);
-            match self.tokens[self.head] {
+            match self.current()? {
                 Token::Comma => {
                     self.head += 1;
                 },
@@ -1673,57 +1928,39 @@ impl Parser {
                 },
             }
         }
-
-        match self.tokens[self.head] {
-            Token::RightBrace => {
-                self.head += 1;
-                match self.tokens[self.head] {
-                    Token::SemiColon => {
-                        self.head += 1;
-                    },
-                    _ => {
-                        return Err("Expected semicolon in Enum".to_string());
-                    },
-                }
-                return Ok(AstNode::Enum(Enum {name: name.to_string(), members}));
-            },
-            _ => {
-                return Err("Expected right brace".to_string());
-            },
-        }
+        self.head += 1;
+        Ok(Enum {name, attributes: Vec::new(), members, doc: None})
     }
 
     fn enum_member(&mut self) -> Result<EnumMember,String> {
-        let mut name = None;
         let mut value = None;
+        let doc = self.doc_comment(self.head);
 
-        match &self.tokens[self.head] {
+        let name = match self.current()? {
             Token::Word(val) => {
-                name = Some(val.clone());
+                let name = val.clone();
                 self.head += 1;
-                match &self.tokens[self.head] {
-                    Token::Assignment => {
-                        self.head += 1;
-                        value = Some(self.expression(None)?);
-                    },
-                    _ => {
-                    },
-                }
+                name
             },
 
             _ => {
                 return Err("Expected identifier".to_string());
             },
+        };
+        let attributes = self.attributes()?;
+        if self.tokens.get(self.head) == Some(&Token::Assignment) {
+            self.head += 1;
+            value = Some(self.ternary_expression()?);
         }
 
-        return Ok(EnumMember {name: name.unwrap(), value});
+        Ok(EnumMember {name, attributes, value, doc})
     }
 
-    fn tagged_union_dec(&mut self, name: &str) -> Result<AstNode, String> {
+    fn tagged_union_dec(&mut self, name: String) -> Result<TaggedUnion, String> {
         let mut members = Vec::new();
-        while self.tokens[self.head] != Token::RightBrace {
+        while *self.current()? != Token::RightBrace {
             members.push(self.tagged_union_member()?);
-            match self.tokens[self.head] {
+            match self.current()? {
                 Token::Comma => {
                     self.head += 1;
                 },
@@ -1735,73 +1972,26 @@ impl Parser {
                 },
             }
         }
-
-        match self.tokens[self.head] {
-            Token::RightBrace => {
-                self.head += 1;
-                match self.tokens[self.head] {
-                    Token::SemiColon => {
-                        self.head += 1;
-                    },
-                    _ => {
-                        return Err("Expected semicolon in Tagged Union".to_string());
-                    },
-                }
-                return Ok(AstNode::TaggedUnion(TaggedUnion {name: name.to_string(), members}));
-            },
-            _ => {
-                return Err("Expected right brace".to_string());
-            },
-        }
+        self.head += 1;
+        Ok(TaggedUnion {name, attributes: Vec::new(), members, doc: None})
     }
 
     fn tagged_union_member(&mut self) -> Result<TaggedUnionMember,String> {
         let mut name = None;
         let mut value = None;
+        let doc = self.doc_comment(self.head);
 
-        while self.tokens[self.head] != Token::Comma && self.tokens[self.head] != Token::RightBrace {
-            match &self.tokens[self.head] {
+        while *self.current()? != Token::Comma && *self.current()? != Token::RightBrace {
+            match self.current()? {
                 Token::Word(val) => {
                     self.head += 1;
                     name = Some(val.clone());
                 },
                 Token::LeftBrace => {
                     self.head += 1;
-                    match &self.tokens[self.head] {
-                        Token::Word(_) | Token::Type(_) | Token::Enum |
-                        Token::Struct | Token::Union | Token::Tagged => {
-                            self.head += 1;
-                        },
-                        _ => {
-                            return Err("Expected identifier".to_string());
-                        },
-                    }
-                    value = Some(Vec::new());
-                    while self.tokens[self.head] != Token::RightBrace {
-                        value.as_mut().unwrap().push(match self.variable_list_or_function()? {
-                            AstNode::VariableList(variable_list) => {
-                                variable_list
-                            },
-                            AstNode::Function(_) => {
-                                return Err("Functions not allowed in tagged unions".to_string());
-                            },
-                            _ => {
-                                return Err("Expected variable list or function".to_string());
-                            },
-                        });
-                        match self.tokens[self.head] {
-                            Token::SemiColon => {
-                                self.head += 1;
-                            },
-                            Token::RightBrace => {
-                                self.head += 1;
-                                break;
-                            },
-                            _ => {
-                                self.head += 1;
-                            },
-                        }
-                    }
+                    let members = self.members("tagged unions")?;
+                    check_flexible_array(&members, false)?;
+                    value = Some(members);
                 },
                 _ => {
                     return Err("Expected identifier or left brace".to_string());
@@ -1810,126 +2000,42 @@ impl Parser {
 
         }
         
-        return Ok(TaggedUnionMember {name: name.unwrap(), value});
+        let name = name.ok_or_else(|| "Expected a name for the tagged union member".to_string())?;
+        Ok(TaggedUnionMember {name, value, doc})
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all, fields(position = self.head), err(level = "trace")))]
     fn compound_type_dec_or_vlist_or_func(&mut self) -> Result<AstNode, String> {
-        enum State {
-            None,
-            Struct,
-            Union,
-            Enum,
-            Tagged
-        }
-        let mut node = AstNode::None;
-        let mut buffer = self.head - 1;
-        let mut word_seen = false;
-        let mut state = State::None;
-        let mut name = None;
-        self.head -= 1;
+        let mut specifiers = self.declaration_specifiers()?;
 
-        while buffer < self.tokens.len() {
-            let token = &self.tokens[buffer];
-            match token {
-                Token::Struct => {
-                    buffer += 1;
-                    match state {
-                        State::None => {
-                            state = State::Struct;
-                        },
-                        _ => {
-                            return Err(format!("Unexpected token: {:?}", token));
-                        },
-                    }
-                },
-                Token::Enum => {
-                    buffer += 1;
-                    match state {
-                        State::None => {
-                            state = State::Enum;
-                        },
-                        _ => {
-                            return Err(format!("Unexpected token: {:?}", token));
-                        },
-                    }
-                },
-                Token::Union => {
-                    buffer += 1;
-                    match state {
-                        State::None => {
-                            state = State::Union;
-                        },
-                        _ => {
-                            return Err(format!("Unexpected token: {:?}", token));
-                        },
-                    }
-                },
-                Token::Tagged => {
-                    buffer += 1;
-                    match state {
-                        State::None => {
-                            state = State::Tagged;
-                        },
-                        _ => {
-                            return Err(format!("Unexpected token: {:?}", token));
-                        },
-                    }
-                },
-                Token::Word(val) => {
-                    buffer += 1;
-                    if word_seen {
-                        self.merge_tokens(0..=buffer);
-                        return self.variable_list_or_function();
-                    }
-                    else {
-                        name = Some(val.clone());
-                        word_seen = true;
-                    }
-                },
-                Token::Type(_) => {
-                    buffer += 1;
-                },
-                Token::LeftBrace => {
-                    buffer += 1;
-                    self.head = buffer;
-                    match state {
-                        State::Struct => {
-                            node = self.struct_dec(&name.expect("Struct has no name"))?;
-                        },
-                        State::Union => {
-                            node = self.union_dec(&name.expect("Union has no name"))?;
-                        },
-                        State::Enum => {
-                            node = self.enum_dec(&name.expect("Enum has no name"))?;
-                        },
-                        State::Tagged => {
-                            node = self.tagged_union_dec(&name.expect("Tagged has no name"))?;
-                        },
-                        _ => {
-                            return Err(format!("Unexpected token: {:?}", token));
-                        },
-                    }
-                    return Ok(node);
-                },
-                _ => {
-                    return Err(format!("Unexpected token: {:?}", token));
-                },
-                
+        if self.tokens.get(self.head) == Some(&Token::SemiColon) {
+            if let Some(mut definition) = specifiers.definition.take() {
+                trace!("type definition");
+                self.head += 1;
+                definition.attributes_mut().splice(0..0, specifiers.attributes);
+                return Ok(match *definition {
+                    Definition::Struct(struct_) => AstNode::Struct(struct_),
+                    Definition::Union(union_) => AstNode::Union(union_),
+                    Definition::Enum(enum_) => AstNode::Enum(enum_),
+                    Definition::TaggedUnion(tagged) => AstNode::TaggedUnion(tagged),
+                });
             }
         }
-        Err("Unexpected end of file".to_string())
+        self.variable_list_or_function(specifiers)
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all, fields(position = self.head), err(level = "trace")))]
     pub fn class(&mut self) -> Result<Class, String> {
         let mut abstract_ = false;
-        let mut generic = None;
+        let generic = None;
         let mut parent = None;
         let mut name = None;
+        let mut attributes = Vec::new();
         let mut members = Vec::new();
 
 
         while self.head < self.tokens.len() {
-            match &self.tokens[self.head] {
+            match self.current()? {
                 Token::Class => {
                     self.head += 1;
                 },
@@ -1937,29 +2043,12 @@ impl Parser {
                     abstract_ = true;
                     self.head += 1;
                 },
-                /*Token::LessThan => {
-                    self.head += 1;
-                    match &self.tokens[self.head] {
-                        Token::Word(val) => {
-                            generic = Some(val.clone());
-                            self.head += 1;
-                        },
-                        _ => {
-                            return Err("Expected identifier".to_string());
-                        },
-                    }
-                    match &self.tokens[self.head] {
-                        Token::GreaterThan => {
-                            self.head += 1;
-                        },
-                        _ => {
-                            return Err("Expected >".to_string());
-                        },
-                    }
-                },*/
+                Token::LeftBracket | Token::Attribute if self.at_attribute() => {
+                    attributes.extend(self.attributes()?);
+                },
                 Token::Colon => {
                     self.head += 1;
-                    match &self.tokens[self.head] {
+                    match self.current()? {
                         Token::Word(val) => {
                             parent = Some(val.clone());
                             self.head += 1;
@@ -1970,18 +2059,33 @@ impl Parser {
                     }
                 },
                 Token::Word(val) => {
+                    // Declared before the members so that methods can use it.
+                    self.declare_name(val, true);
                     name = Some(val.clone());
                     self.head += 1;
                 },
                 Token::LeftBrace => {
                     self.head += 1;
-                    while self.tokens[self.head] != Token::RightBrace {
-                        members.push(self.class_member(abstract_)?);
+                    while *self.current()? != Token::RightBrace {
+                        let doc = self.doc_comment(self.head);
+                        let mut member = self.class_member(abstract_)?;
+                        member.set_doc(doc);
+                        members.push(member);
                     }
-                    match &self.tokens[self.head] {
+                    match self.current()? {
                         Token::RightBrace => {
                             self.head += 1;
-                            return Ok(Class {abstract_, generic, parent, name: name.unwrap(), members});
+                            if self.tokens.get(self.head) == Some(&Token::SemiColon) {
+                                self.head += 1;
+                            }
+                            let Some(name) = name else {
+                                return Err("Expected a class name".to_string());
+                            };
+                            check_flexible_array(members.iter().filter_map(|member| match member {
+                                ClassMember::Field(field) => Some(field),
+                                _ => None,
+                            }), false)?;
+                            return Ok(Class {abstract_, generic, parent, name, attributes, members, doc: None});
                         },
                         _ => {
                             return Err("Expected }".to_string());
@@ -1997,90 +2101,53 @@ impl Parser {
         Err("Unexpected end of file in Class".to_string())
     }
 
-    fn class_member(&mut self, abstract_: bool) -> Result<ClassMember,String> {
-        let mut word_seen = false;
-        let mut buffer = self.head;
-        self.head -= 1;
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all, fields(position = self.head), err(level = "trace")))]
+    fn class_member(&mut self, _abstract_: bool) -> Result<ClassMember,String> {
+        let specifiers = self.declaration_specifiers()?;
 
-        while buffer < self.tokens.len() {
-            match &self.tokens[buffer] {
-                Token::LeftParen => {
-                    if !word_seen {
-                        match self.function_pointer_dec()? {
-                            AstNode::VariableList(val) => {
-                                return Ok(ClassMember::Variable(val));
-                            },
-                            _ => {
-                                return Err("Expected variable list".to_string());
-                            },
-                        }
-                    }
-                    else {
-                        match self.function()? {
-                            AstNode::Function(val) => {
-                                return Ok(ClassMember::Method(Method::Normal((val))));
-                            },
-                            AstNode::FunctionPrototype(val) => {
-                                return Ok(ClassMember::Method(Method::Abstract((val))));
-                            },
-                            _ => {
-                                return Err("Expected function".to_string());
-                            },
-                        }
-                    }
-                },
-                Token::Comma | Token::SemiColon | Token::Assignment => {
-                    match self.variable_dec()? {
-                        AstNode::VariableList(val) => {
-                            return Ok(ClassMember::Variable(val));
-                        },
-                        _ => {
-                            return Err("Expected variable list".to_string());
-                        },
-                    }
-                },
-                Token::Star | Token::Restrict => {
-                    buffer += 1;
-                },
-                Token::Word(_) => {
-                    word_seen = true;
-                    buffer += 1;
-                },
-                Token::LeftBracket => {
-                    match self.variable_dec()? {
-                        AstNode::VariableList(val) => {
-                            return Ok(ClassMember::Variable(val));
-                        },
-                        _ => {
-                            return Err("Expected variable list".to_string());
-                        },
-                    }
-                },
-                Token::Operator => {
-                    return Ok(ClassMember::OperatorOverload(self.operator()?));
+        // Look past the pointer qualifiers of the return type for `operator`; each is read
+        // at most twice, so this stays linear.
+        let mut ahead = self.head;
+        while matches!(self.tokens.get(ahead), Some(Token::Star | Token::Const | Token::Volatile | Token::Restrict | Token::Atomic)) {
+            ahead += 1;
+        }
+        if self.tokens.get(ahead) == Some(&Token::Operator) {
+            trace!("operator overload");
+            return Ok(ClassMember::OperatorOverload(self.operator(specifiers)?));
+        }
 
-                },
-                _ => {
-                    return Err(format!("Unexpected token in class member: {:?}", self.tokens[buffer]));
-                },
-            }
+        match self.member(specifiers)? {
+            AstNode::VariableList(val) => {
+                Ok(ClassMember::Field(Member::Variables(val)))
+            },
+            AstNode::Member(member) => {
+                Ok(ClassMember::Field(member))
+            },
+            AstNode::Function(val) => {
+                Ok(ClassMember::Method(Method::Normal(val)))
+            },
+            AstNode::FunctionPrototype(val) => {
+                Ok(ClassMember::Method(Method::Abstract(val)))
+            },
+            _ => {
+                Err("Expected variable list or method".to_string())
+            },
         }
-        Err("Unexpected end of file in class member".to_string())
     }
 
-    fn operator(&mut self) -> Result<OperatorOverload,String> {
+    fn operator(&mut self, specifiers: DeclarationSpecifiers) -> Result<OperatorOverload,String> {
         let mut name = None;
         let mut arguments = None;
-        let mut return_type = None;
-        let mut return_pointer = 0;
+        let pointers = self.pointers();
+        let return_type = derive(specifiers.type_, pointers)?;
 
         while self.head < self.tokens.len() {
-            let token = &self.tokens[self.head];
+            let token = self.current()?;
             match token {
                 Token::LeftParen => {
                     self.head += 1;
 
-                    name = match &self.tokens[self.head] {
+                    name = match self.current()? {
                         Token::Plus | Token::Minus | Token::Star | Token::Divide |
                         Token::Modulo | Token::BitwiseAnd | Token::BitwiseOr |
                         Token::BitwiseXor | Token::BitwiseNot | Token::BitwiseLeftShift |
@@ -2088,13 +2155,13 @@ impl Parser {
                         Token::LessThan | Token::GreaterThan | Token::LessThanOrEqual |
                         Token::GreaterThanOrEqual | Token::LogicalAnd | Token::LogicalOr |
                         Token::LogicalNot | Token::Increment | Token::Decrement =>
-                            Some(self.tokens[self.head].to_string()),
+                            Some(self.current()?.to_string()),
                         _ => {
                             return Err("Expected operator".to_string());
                         },
                     };
                     self.head += 1;
-                    match &self.tokens[self.head] {
+                    match self.current()? {
                         Token::RightParen => {
                             self.head += 1;
                         },
@@ -2102,152 +2169,150 @@ impl Parser {
                             return Err("Expected )".to_string());
                         },
                     }
-                    match &self.tokens[self.head] {
-                        Token::LeftParen => {
-                            self.head += 1;
-                        },
-                        _ => {
-                            return Err("Expected (".to_string());
-                        },
-                    }
-                    arguments = Some(self.function_arguments()?);
-                },
-                Token::LeftBrace => {
-                    self.head += 1;
-                    let code_block = self.code_block()?;
-
-                    return Ok(OperatorOverload::Normal {
-                        op: name.expect("no name"),
-                        arguments: arguments.expect("no arguments"),
-                        return_type: return_type.expect("no return type"),
-                        return_pointer: return_pointer,
-                        body: code_block,
-                    });
-                },
-                Token::Star => {
-                    self.head += 1;
-                    return_pointer += 1;
-                },
-                Token::Type(_) => {
-                    self.head += 1;
-                    return_type = Some(Type::from_token(token.clone())?);
-                },
-                Token::SemiColon => {
-                    self.head += 1;
-                    return Ok(OperatorOverload::Abstract {
-                        op: name.expect("no name"),
-                        arguments: arguments.expect("no arguments"),
-                        return_type: return_type.expect("no return type"),
-                        return_pointer: return_pointer,
-                    });
-                }
-                _ => {
-                    return Err(format!("Unexpected token in function: {:?}", token));
-                },
-            }
-            //self.head += 1;
-        }
-
-        return Err("Unexpected end of file".to_string());
-    }
-
-    pub fn parse(&mut self) -> Result<Header, String> {
-        let mut header_statements = Vec::new();
-
-        if self.tokens.len() == 0 {
-            return Err("No tokens".to_string());
-        }
-
-        while self.tokens.len() != 0 {
-            println!("{:?}", self.tokens);
-            println!("Parsing token {:?}", self.tokens[0]);
-            match self.tokens[0] {
-                Token::Preprocessor(_) => {
-                    header_statements.append(self.preprocessors()?.iter().map(|x| HeaderStatement::Preprocessor(x.clone())).collect::<Vec<HeaderStatement>>().as_mut());
-
-                },
-                Token::Typedef => {
-                    self.head += 1;
-
-                },
-                Token::Struct | Token::Enum | Token::Union | Token::Tagged => {
-                    self.head += 1;
-                    let node = self.compound_type_dec_or_vlist_or_func()?;
-
-                    match node {
-                        AstNode::Struct(struct_) => {
-                            header_statements.push(HeaderStatement::Struct(struct_));
-                        },
-                        AstNode::VariableList(variable_list) => {
-                            header_statements.push(HeaderStatement::Variable(variable_list));
-                        },
-                        AstNode::Function(function) => {
-                            header_statements.push(HeaderStatement::Function(function));
-                        },
-                        AstNode::Enum(enum_) => {
-                            header_statements.push(HeaderStatement::Enum(enum_));
-                        },
-                        AstNode::Union(union_) => {
-                            header_statements.push(HeaderStatement::Union(union_));
-                        },
-                        AstNode::TaggedUnion(tagged) => {
-                            header_statements.push(HeaderStatement::TaggedUnion(tagged));
-                        },
-                        _ => {
-                            return Err(format!("Unexpected node: {:?}", node));
-                        },
-                    }
-                    
-                },
-                Token::Type(_) => {//Variable, Function
-                    self.head += 1;
-                    let node = self.variable_list_or_function()?;
-                    println!("Node: {:?}", node);
-                    match node {
-                        AstNode::VariableList(variable_list) => {
-                            header_statements.push(HeaderStatement::Variable(variable_list));
-                        },
-                        AstNode::Function(function) => {
-                            header_statements.push(HeaderStatement::Function(function));
-                        },
-                        _ => {
-                            return Err(format!("Unexpected node: {:?}", node));
-                        },
-                    }
-
-                },
-                Token::Class | Token::Abstract => {
-                    header_statements.push(HeaderStatement::Class(self.class()?));
-                },
-                Token::Static | Token::Inline => {
-                    self.head += 1;
-                    let node = self.variable_list_or_function()?;
-                    match node {
-                        AstNode::Function(function) => {
-                            header_statements.push(HeaderStatement::Function(function));
+                    match self.current()? {
+                        Token::LeftParen => {
+                            self.head += 1;
                         },
                         _ => {
-                            return Err(format!("Unexpected node: {:?}", node));
+                            return Err("Expected (".to_string());
                         },
                     }
+                    arguments = Some(self.function_arguments()?);
+                },
+                Token::LeftBrace => {
+                    self.head += 1;
+                    let code_block = self.function_body(arguments.as_deref().unwrap_or_default())?;
 
+                    return Ok(OperatorOverload::Normal {
+                        attributes: specifiers.attributes,
+                        op: name.ok_or_else(|| "Expected an operator in parentheses".to_string())?,
+                        arguments: arguments.ok_or_else(|| "Expected the arguments of the operator".to_string())?,
+                        return_type,
+                        body: code_block,
+                        doc: None,
+                    });
                 },
-                Token::Newline => {
+                Token::Operator => {
                     self.head += 1;
-                    header_statements.push(HeaderStatement::Whitespace);
                 },
+                Token::SemiColon => {
+                    self.head += 1;
+                    return Ok(OperatorOverload::Abstract {
+                        attributes: specifiers.attributes,
+                        op: name.ok_or_else(|| "Expected an operator in parentheses".to_string())?,
+                        arguments: arguments.ok_or_else(|| "Expected the arguments of the operator".to_string())?,
+                        return_type,
+                        doc: None,
+                    });
+                }
                 _ => {
-                    return Err(format!("Unexpected token: {:?}", self.tokens[0]));
-
+                    return Err(format!("Unexpected token in function: {:?}", token));
                 },
             }
-            self.tokens = self.tokens[self.head..].to_vec();
-            self.head = 0;
         }
 
+        Err("Unexpected end of file".to_string())
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all, fields(position = self.head), err(level = "trace")))]
+    pub fn parse(&mut self) -> Result<Header, String> {
+        Ok(self.parse_with_ranges()?.0)
+    }
+
+    /// Parses the whole input and also returns the range of tokens each top-level
+    /// statement was parsed from, for placing what later checks find in it.
+    pub fn parse_with_ranges(&mut self) -> Result<(Header, Vec<Range<usize>>), String> {
+        let mut header_statements = Vec::new();
+        let mut ranges = Vec::new();
+
+        if self.tokens.is_empty() {
+            return Err("No tokens".to_string());
+        }
+
+        while !self.is_done() {
+            let start = self.head;
+            let mut statements = self.header_statements()?;
+            ranges.extend(std::iter::repeat_n(start..self.head, statements.len()));
+            header_statements.append(&mut statements);
+        }
+
+        Ok((Header { statements: header_statements }, ranges))
+    }
+
+    /// Moves the parser to a token, to parse again from there.
+    pub fn seek(&mut self, position: usize) {
+        self.head = position.min(self.tokens.len());
+    }
+
+    /// Whether the parser has reached the end of its tokens.
+    pub fn is_done(&self) -> bool {
+        self.head >= self.tokens.len()
+    }
+
+    /// Parses one top-level declaration, or a run of preprocessor lines, at the parser's position.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all, fields(position = self.head), err(level = "trace")))]
+    pub fn header_statements(&mut self) -> Result<Vec<HeaderStatement>, String> {
+        let mut header_statements = Vec::new();
+        let doc = self.doc_comment(self.head);
+        trace!(token = ?self.tokens.get(self.head), "top-level statement");
+        match self.current()? {
+            Token::Preprocessor(_) | Token::Include(_) | Token::Macro(_) => {
+                header_statements.append(self.preprocessors()?.iter().map(|x| HeaderStatement::Preprocessor(x.clone())).collect::<Vec<HeaderStatement>>().as_mut());
+
+            },
+            token if is_specifier(token) || matches!(token, Token::Word(_) | Token::LeftBracket | Token::Attribute) => {//Variable, Function, Type
+                let node = self.compound_type_dec_or_vlist_or_func()?;
 
+                match node {
+                    AstNode::Struct(struct_) => {
+                        header_statements.push(HeaderStatement::Struct(struct_));
+                    },
+                    AstNode::VariableList(variable_list) => {
+                        header_statements.push(HeaderStatement::Variable(variable_list));
+                    },
+                    AstNode::Function(function) => {
+                        header_statements.push(HeaderStatement::Function(function));
+                    },
+                    AstNode::FunctionPrototype(prototype) => {
+                        header_statements.push(HeaderStatement::FunctionPrototype(prototype));
+                    },
+                    AstNode::Enum(enum_) => {
+                        header_statements.push(HeaderStatement::Enum(enum_));
+                    },
+                    AstNode::Union(union_) => {
+                        header_statements.push(HeaderStatement::Union(union_));
+                    },
+                    AstNode::TaggedUnion(tagged) => {
+                        header_statements.push(HeaderStatement::TaggedUnion(tagged));
+                    },
+                    _ => {
+                        return Err(format!("Unexpected node: {:?}", node));
+                    },
+                }
+                
+            },
+            Token::Class | Token::Abstract => {
+                header_statements.push(HeaderStatement::Class(self.class()?));
+            },
+            Token::StaticAssert => {
+                header_statements.push(HeaderStatement::StaticAssert(self.static_assert()?));
+            },
+            Token::Newline => {
+                self.head += 1;
+                header_statements.push(HeaderStatement::Whitespace);
+            },
+            token => {
+                return Err(format!("Unexpected token: {:?}", token));
 
-        Ok(Header { statements: header_statements })
+            },
+        }
+        if let Some(statement) = header_statements.first_mut() {
+            statement.set_doc(doc);
+        }
+        for statement in &header_statements {
+            self.declare(statement);
+        }
+        Ok(header_statements)
     }
 }
 
@@ -2259,601 +2324,579 @@ impl Parser {
 #[cfg(test)]
 mod ast_tests {
     use super::*;
-    use crate::logos_lexer::lex;
+    use crate::logos_lexer::{lex, lex_with_trivia};
 
     #[test]
-    fn test_preprocessor() {
-        let input = "#include <stdio.h>\n";
-        println!("Input: {}", input);
-        let tokens = match lex(input) {
-            Ok(tokens) => tokens,
-            Err(err) => {
-                println!("Error: {:?}", err);
-                assert!(false);
-                return;
-            }
-        };
+    fn test_char_and_string_literals() {
+        let input = "int main() { char c = '\\n'; puts(\"a\\tb\" \"c\"); return c; }\n";
+        let tokens = lex(input).expect("Failed to lex input");
+        let mut parser = Parser::new(&tokens);
+        let result = parser.parse();
+        assert!(result.is_ok(), "Failed to parse char and string literals");
 
-        let mut parser = Parser::new(tokens);
-        
-        assert!(parser.parse().is_ok(), "Failed to parse preprocessor directive");
-        //assert!(parser.consume_tokens(), "Failed to parse preprocessor directive");
+        let function = match &result.unwrap().statements[0] {
+            HeaderStatement::Function(function) => function.clone(),
+            statement => panic!("Expected function, got {:?}", statement),
+        };
+        let CodeBlock::Code(body) = function.body;
+        match &body.statements[0] {
+            Statement::VariableList(VariableList { variables, .. }) => match &variables[0] {
+                Variable { value: Some(VariableValue::Expression(Expression::Literal(Literal::Char(c)))), .. } => {
+                    assert_eq!(c.value, 10);
+                    assert_eq!(c.raw, "'\\n'");
+                },
+                variable => panic!("Expected char initializer, got {:?}", variable),
+            },
+            statement => panic!("Expected variable list, got {:?}", statement),
+        }
+        match &body.statements[1] {
+            Statement::Expression(Expression::CallFunction(_, Some(argument))) => match argument.as_ref() {
+                Expression::Literal(Literal::String(string)) => assert_eq!(string.to_text(), "a\tbc"),
+                argument => panic!("Expected string argument, got {:?}", argument),
+            },
+            statement => panic!("Expected call, got {:?}", statement),
+        }
+        assert!(matches!(body.statements[2], Statement::Return(_)));
     }
 
     #[test]
-    fn test_macros() {
-        let input = "#define MAX(a, b) ((a) > (b) ? (a) : (b))\n";
-        println!("Input: {}", input);
-        let tokens = match lex(input) {
+    fn test_doc_comments() {
+        let input = "/// A point.\nstruct point {\n    /// Horizontal.\n    int x;\n    int y; // no docs\n};\n\n/**\n * Entry point.\n */\nint main() {\n    // just a comment\n    return 0;\n}\n";
+        let tokens = match lex_with_trivia(input) {
             Ok(tokens) => tokens,
-            Err(err) => {
-                println!("Error: {:?}", err);
-                assert!(false);
-                return;
-            }
+            Err(err) => panic!("Failed to lex input: {:?}", err),
         };
 
-        let mut parser = Parser::new(tokens);
-        
-        assert!(parser.preprocessors().is_ok(), "Failed to parse preprocessor directive");
-        //assert!(parser.consume_tokens(), "Failed to parse preprocessor directive");
+        let header = match Parser::with_trivia(&tokens).parse() {
+            Ok(header) => header,
+            Err(err) => panic!("Failed to parse doc comments: {}", err),
+        };
+
+        match &header.statements[..] {
+            [HeaderStatement::Struct(point), HeaderStatement::Function(main)] => {
+                assert_eq!(point.doc.as_deref(), Some("A point."));
+                assert!(matches!(&point.members[0], Member::Variables(VariableList { doc: Some(doc), .. }) if doc == "Horizontal."));
+                assert!(matches!(&point.members[1], Member::Variables(VariableList { doc: None, .. })));
+                assert_eq!(main.doc.as_deref(), Some("Entry point."));
+                assert_eq!(main.body, CodeBlock::Code(StatementList {
+                    statements: vec![Statement::Return(Expression::Literal(Literal::Number(
+                        crate::literal::number_literal("0").unwrap())))],
+                }));
+            },
+            statements => panic!("Unexpected statements: {:?}", statements),
+        }
     }
 
+    #[cfg(feature = "tracing")]
     #[test]
-    fn test_global_variable() {
-        let input = "int a;\n";
-        println!("Input: {}", input);
-        let tokens = match lex(input) {
-            Ok(tokens) => tokens,
-            Err(err) => {
-                println!("Error: {:?}", err);
-                assert!(false);
-                return;
-            }
-        };
+    fn test_tracing() {
+        use std::sync::{Arc, Mutex};
 
-        let mut parser = Parser::new(tokens);
+        #[derive(Clone, Default)]
+        struct Log(Arc<Mutex<Vec<u8>>>);
 
-        assert!(parser.parse().is_ok(), "Failed to parse global variable");
-    }
+        impl std::io::Write for Log {
+            fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().write(bytes)
+            }
 
-    #[test]
-    fn test_global_variables() {
-        let input = "int a, b, c;\n";
-        println!("Input: {}", input);
-        let tokens = match lex(input) {
-            Ok(tokens) => tokens,
-            Err(err) => {
-                println!("Error: {:?}", err);
-                assert!(false);
-                return;
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
             }
-        };
+        }
 
-        let mut parser = Parser::new(tokens);
+        let log = Log::default();
+        let writer = log.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::TRACE)
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        let tokens = lex("int x, y;\nint f(void);\n").unwrap();
+        tracing::subscriber::with_default(subscriber, || Parser::new(&tokens).parse().unwrap());
 
-        assert!(parser.parse().is_ok(), "Failed to parse global variables");
+        let log = String::from_utf8(log.0.lock().unwrap().clone()).unwrap();
+        assert!(log.contains("variable name=\"y\""), "{}", log);
+        assert!(log.contains("variable_list_or_function{position=6}: c_with_classes::parser: function prototype name=\"f\""), "{}", log);
     }
 
     #[test]
-    fn test_global_var_string() {
-        let input = "char *a = \"Hello World\";\n";
-        println!("Input: {}", input);
+    fn test_error_position() {
+        let input = "int x;\nint y = 1 +;\n";
         let tokens = match lex(input) {
             Ok(tokens) => tokens,
-            Err(err) => {
-                println!("Error: {:?}", err);
-                assert!(false);
-                return;
-            }
+            Err(err) => panic!("Failed to lex input: {:?}", err),
         };
-        println!("Tokens: {:?}", tokens);
 
-        let mut parser = Parser::new(tokens);
-        let result = parser.parse();
-        println!("Result: {:?}", result);
-        assert!(result.is_ok(), "Failed to parse global variable");
+        let mut parser = Parser::new(&tokens);
+        assert!(parser.parse().is_err());
+        assert_eq!(tokens[parser.position()], Token::SemiColon);
+        assert!(parser.position() > 3);
     }
 
     #[test]
-    fn test_global_var_pointers() {
-        let input = "int *a, *b, *c;\n";
-        println!("Input: {}", input);
-        let tokens = match lex(input) {
-            Ok(tokens) => tokens,
-            Err(err) => {
-                println!("Error: {:?}", err);
-                assert!(false);
-                return;
-            }
-        };
+    fn test_truncated_input() {
+        let input = "tagged t { a {int x;}, b };\nclass C { int operator(+)(int n); };\n\
+                     int main() { for (int i = 0; i < 3; i++) { switch (i) { case 1: return (int) -i; } } }\n";
+        let tokens = lex(input).unwrap();
+        for end in 0..tokens.len() {
+            let _ = Parser::new(&tokens[..end]).parse();
+        }
 
-        println!("Tokens: {:?}", tokens);
-        let mut parser = Parser::new(tokens);
-        assert!(parser.parse().is_ok(), "Failed to parse global variable");
+        for input in ["tagged t { {int x;} };", "class C { int operator; };", "class C { int operator(+); };"] {
+            assert!(Parser::new(&lex(input).unwrap()).parse().is_err(), "{}", input);
+        }
     }
 
     #[test]
-    fn test_global_var_pointer_mix() {
-        let input = "int *a, b, *c;\n";
-        println!("Input: {}", input);
-        let tokens = match lex(input) {
-            Ok(tokens) => tokens,
-            Err(err) => {
-                println!("Error: {:?}", err);
-                assert!(false);
-                return;
-            }
-        };
+    fn test_nesting_limit() {
+        let n = MAX_DEPTH * 4;
+        let inputs = [
+            format!("int x = {}1{};", "(".repeat(n), ")".repeat(n)),
+            format!("int x = {}1;", "-".repeat(n)),
+            format!("int x = {}1{};", "a ? ".repeat(n), " : 1".repeat(n)),
+            format!("int main() {{ {} }}", "{".repeat(n)),
+            format!("int {}x{};", "(".repeat(n), ")".repeat(n)),
+            format!("struct a {{ {} }};", "struct b {".repeat(n)),
+        ];
+        for input in inputs {
+            let error = Parser::new(&lex(&input).unwrap()).parse().unwrap_err();
+            assert!(error.starts_with("Nesting deeper than"), "{}", error);
+        }
 
-        println!("Tokens: {:?}", tokens);
-        let mut parser = Parser::new(tokens);
-        assert!(parser.parse().is_ok(), "Failed to parse global variable");
+        // C guarantees 63 levels of parentheses and 127 of blocks.
+        let input = format!("int x = {}1{};", "(".repeat(63), ")".repeat(63));
+        assert!(Parser::new(&lex(&input).unwrap()).parse().is_ok());
+        let input = format!("int main() {{ {}{} }}", "{".repeat(126), "}".repeat(126));
+        assert!(Parser::new(&lex(&input).unwrap()).parse().is_ok());
     }
 
     #[test]
-    fn test_global_var_fail() {
-        let input = "int a, b, c\n";
-        println!("Input: {}", input);
+    fn test_declaration_specifiers() {
+        let input = "long unsigned const static x;
+extern volatile struct point *p;
+char signed c;
+";
         let tokens = match lex(input) {
             Ok(tokens) => tokens,
-            Err(err) => {
-                println!("Error: {:?}", err);
-                assert!(false);
-                return;
-            }
+            Err(err) => panic!("Failed to lex input: {:?}", err),
         };
 
-        println!("Tokens: {:?}", tokens);
-        let mut parser = Parser::new(tokens);
-        assert!(parser.parse().is_err(), "Parse global variable when it should have failed");
-    }
-
-    #[test]
-    fn test_global_function_ptr() {
-        let input = "int (*func)(int, int);\n";
-        println!("Input: {}", input);
-        let tokens = match lex(input) {
-            Ok(tokens) => tokens,
-            Err(err) => {
-                println!("Error: {:?}", err);
-                assert!(false);
-                return;
-            },
+        let header = match Parser::new(&tokens).parse() {
+            Ok(header) => header,
+            Err(err) => panic!("Failed to parse declaration specifiers: {}", err),
         };
 
-        println!("Tokens: {:?}", tokens);
-        let mut parser = Parser::new(tokens);
-        assert!(parser.parse().is_ok(), "Failed to parse global function pointer");
+        let specifiers = header.statements.iter().filter_map(|statement| match statement {
+            HeaderStatement::Variable(VariableList { specifiers, .. }) => Some(specifiers.clone()),
+            _ => None,
+        }).collect::<Vec<DeclarationSpecifiers>>();
+
+        assert_eq!(specifiers.len(), 3);
+        assert_eq!(specifiers[0].storage, Some(StorageClass::Static));
+        assert_eq!(specifiers[0].type_, Type { specifier: TypeSpecifier::UnsignedLong, qualifiers: TypeQualifiers { const_: true, ..Default::default() } });
+        assert_eq!(specifiers[1].storage, Some(StorageClass::Extern));
+        assert_eq!(specifiers[1].type_, Type { specifier: TypeSpecifier::Struct(Some("point".to_string())), qualifiers: TypeQualifiers { volatile: true, ..Default::default() } });
+        assert_eq!(specifiers[2].type_, Type::new(TypeSpecifier::SignedChar));
     }
 
     #[test]
-    fn test_global_function_ptr_var() {
-        let input = "int (*func)(int x, int y);\n";
-        println!("Input: {}", input);
-        let tokens = match lex(input) {
-            Ok(tokens) => tokens,
-            Err(err) => {
-                println!("Error: {:?}", err);
-                assert!(false);
-                return
-            },
-        };
-
-        println!("Tokens: {:?}", tokens);
-        let mut parser = Parser::new(tokens);
-        assert!(parser.parse().is_ok(), "Failed to parse global function pointer");
+    fn test_invalid_specifiers() {
+        for input in ["short long x;", "static extern int x;", "int int x;", "unsigned float x;",
+                      "int struct point x;", "thread_local register int x;", "static;"] {
+            let tokens = match lex(input) {
+                Ok(tokens) => tokens,
+                Err(err) => panic!("Failed to lex input: {:?}", err),
+            };
+            assert!(Parser::new(&tokens).parse().is_err(), "Parsed invalid specifiers: {}", input);
+        }
     }
 
     #[test]
-    fn test_global_nested_function_ptr() {
-        let input = "int (*func)(int (*)(int, int), int);\n";
-        println!("Input: {}", input);
+    fn test_casts_and_sizeof() {
+        let input = "int main() { long x = (unsigned long long) 1; x = sizeof(const char *); x = sizeof (x); return 0; }\n";
         let tokens = match lex(input) {
             Ok(tokens) => tokens,
-            Err(err) => {
-                println!("Error: {:?}", err);
-                assert!(false);
-                return
-            },
+            Err(err) => panic!("Failed to lex input: {:?}", err),
         };
 
-        println!("Tokens: {:?}", tokens);
-        let mut parser = Parser::new(tokens);
-        assert!(parser.parse().is_ok(), "Failed to parse global function pointer");
-    }
+        let header = match Parser::new(&tokens).parse() {
+            Ok(header) => header,
+            Err(err) => panic!("Failed to parse casts: {}", err),
+        };
 
-    #[test]
-    fn test_simple_function() {
-        let input = "int main() { return 0; }\n";
-        println!("Input: {}", input);
+        let HeaderStatement::Function(main) = &header.statements[0] else {
+            panic!("Expected a function: {:?}", header);
+        };
+        let CodeBlock::Code(body) = &main.body;
+        let unsigned_long_long = DeclaredType::Base(Type::new(TypeSpecifier::UnsignedLongLong));
+        let const_char_pointer = DeclaredType::Pointer(
+            Box::new(DeclaredType::Base(Type { specifier: TypeSpecifier::Char, qualifiers: TypeQualifiers { const_: true, ..Default::default() } })),
+            TypeQualifiers::default());
+        assert!(matches!(&body.statements[0], Statement::VariableList(VariableList { variables, .. })
+            if matches!(&variables[0].value, Some(VariableValue::Expression(Expression::Unary(UnaryOperator::Cast(type_), _))) if **type_ == unsigned_long_long)));
+        assert!(matches!(&body.statements[1], Statement::Expression(Expression::Binary(_, _, sizeof))
+            if matches!(&**sizeof, Expression::Sizeof(TypeOrExpression::Type(type_)) if **type_ == const_char_pointer)));
+        assert!(matches!(&body.statements[2], Statement::Expression(Expression::Binary(_, _, sizeof))
+            if matches!(&**sizeof, Expression::Sizeof(TypeOrExpression::Expression(_)))));
+    }
+
+    fn parse_header(input: &str) -> Header {
         let tokens = match lex(input) {
             Ok(tokens) => tokens,
-            Err(err) => {
-                println!("Error: {:?}", err);
-                assert!(false);
-                return
-            },
+            Err(err) => panic!("Failed to lex input: {:?}", err),
         };
+        match Parser::new(&tokens).parse() {
+            Ok(header) => header,
+            Err(err) => panic!("Failed to parse {}: {}", input, err),
+        }
+    }
 
-        println!("Tokens: {:?}", tokens);
-        let mut parser = Parser::new(tokens);
-        let result = parser.parse();
-        println!("Result: {:?}", result);
-        assert!(result.is_ok(), "Failed to parse simple function");
+    fn int() -> DeclaredType {
+        DeclaredType::Base(Type::new(TypeSpecifier::Int))
     }
 
-    #[test]
-    fn test_simple_program() {
-        let input = "int main() { return 0; }\nint a;\nint b;\nint c;\n";
-        println!("Input: {}", input);
-        let tokens = match lex(input) {
-            Ok(tokens) => tokens,
-            Err(err) => {
-                println!("Error: {:?}", err);
-                return
-            },
-        };
+    fn pointer(type_: DeclaredType, qualifiers: TypeQualifiers) -> DeclaredType {
+        DeclaredType::Pointer(Box::new(type_), qualifiers)
+    }
 
-        println!("Tokens: {:?}", tokens);
-        let mut parser = Parser::new(tokens);
-        let result = parser.parse();
-        println!("Result: {:?}", result);
-        assert!(result.is_ok(), "Failed to parse simple program");
+    fn char_parameter() -> FunctionArgument {
+        FunctionArgument::Parameter(Parameter {
+            register: false,
+            attributes: Vec::new(),
+            name: None,
+            type_: DeclaredType::Base(Type::new(TypeSpecifier::Char)),
+        })
     }
 
     #[test]
-    fn test_hello_world_program() {
-        let input = "#include <stdio.h>\nint main() { printf(\"Hello World\"); return 0; }\n";
-        println!("Input: {}", input);
-        let tokens = match lex(input) {
-            Ok(tokens) => tokens,
-            Err(err) => {
-                println!("Error: {:?}", err);
-                return
-            },
-        };
+    fn test_declarators() {
+        let header = parse_header("int *(*fp[4])(char);\nconst char *const p, **q;\nint (*pick(int which))(char) { return 0; }\n");
+        let const_ = TypeQualifiers { const_: true, ..Default::default() };
 
-        println!("Tokens: {:?}", tokens);
-        let mut parser = Parser::new(tokens);
-        let result = parser.parse();
-        println!("Result: {:?}", result);
-        assert!(result.is_ok(), "Failed to parse hello world program");
-}
-    #[test]
-    fn test_math_program() {
-        let input = "int main() { return 1 + 2 * 3; }\n";
-        println!("Input: {}", input);
-        let tokens = match lex(input) {
-            Ok(tokens) => tokens,
-            Err(err) => {
-                println!("Error: {:?}", err);
-                return
-            },
+        let HeaderStatement::Variable(fp) = &header.statements[0] else {
+            panic!("Expected a variable: {:?}", header.statements[0]);
+        };
+        let function = DeclaredType::Function(Box::new(pointer(int(), TypeQualifiers::default())), vec![char_parameter()]);
+        let size = Expression::Literal(Literal::Number(crate::literal::number_literal("4").unwrap()));
+        assert_eq!(fp.variables[0].name, "fp");
+        assert_eq!(fp.variables[0].type_, DeclaredType::Array(
+            Box::new(pointer(function, TypeQualifiers::default())), VariableArray::Size(size)));
+
+        let HeaderStatement::Variable(p) = &header.statements[1] else {
+            panic!("Expected a variable: {:?}", header.statements[1]);
         };
+        let const_char = DeclaredType::Base(Type { specifier: TypeSpecifier::Char, qualifiers: const_ });
+        assert_eq!(p.variables[0].type_, pointer(const_char.clone(), const_));
+        assert_eq!(p.variables[1].type_, pointer(pointer(const_char, TypeQualifiers::default()), TypeQualifiers::default()));
 
-        println!("Tokens: {:?}", tokens);
-        let mut parser = Parser::new(tokens);
-        let result = parser.parse();
-        println!("Result: {:?}", result);
-        assert!(result.is_ok(), "Failed to parse math program");
+        let HeaderStatement::Function(pick) = &header.statements[2] else {
+            panic!("Expected a function: {:?}", header.statements[2]);
+        };
+        assert_eq!(pick.name, "pick");
+        assert_eq!(pick.return_type, pointer(DeclaredType::Function(Box::new(int()), vec![char_parameter()]), TypeQualifiers::default()));
+        assert!(matches!(&pick.arguments[..], [FunctionArgument::Parameter(Parameter { name: Some(name), .. })] if name == "which"));
     }
 
     #[test]
-    fn test_nested_expressions() {
-        let input = "int main() { return 1 + (2 * 3); }\n";
-        println!("Input: {}", input);
-        let tokens = match lex(input) {
-            Ok(tokens) => tokens,
-            Err(err) => {
-                println!("Error: {:?}", err);
-                return
-            },
+    fn test_function_pointer_parameters() {
+        let header = parse_header("void sort(void *base, int (*compare)(const void *, const void *), ...);\n");
+        let HeaderStatement::FunctionPrototype(sort) = &header.statements[0] else {
+            panic!("Expected a prototype: {:?}", header.statements[0]);
         };
-
-        println!("Tokens: {:?}", tokens);
-        let mut parser = Parser::new(tokens);
-        let result = parser.parse();
-        println!("Result: {:?}", result);
-        assert!(result.is_ok(), "Failed to parse nested expressions");
+        assert_eq!(sort.arguments.len(), 3);
+        assert!(matches!(&sort.arguments[1], FunctionArgument::Parameter(Parameter { name: Some(name), type_: DeclaredType::Pointer(function, _), .. })
+            if name == "compare" && matches!(&**function, DeclaredType::Function(_, arguments) if arguments.len() == 2)));
+        assert_eq!(sort.arguments[2], FunctionArgument::Ellipsis);
     }
 
     #[test]
-    fn test_variables_in_function() {
-        let input = "int main() { int a; int b; int c; return 0; }\n";
-        println!("Input: {}", input);
-        let tokens = match lex(input) {
-            Ok(tokens) => tokens,
-            Err(err) => {
-                println!("Error: {:?}", err);
-                return
-            },
-        };
-
-        println!("Tokens: {:?}", tokens);
-        let mut parser = Parser::new(tokens);
-        let result = parser.parse();
-        println!("Result: {:?}", result);
-        assert!(result.is_ok(), "Failed to parse variables in function");
+    fn test_invalid_declarators() {
+        for input in ["int f(void)[3];", "int a[3](char);", "int f(char)(char);", "restrict int x;", "int (*)(char);"] {
+            let tokens = match lex(input) {
+                Ok(tokens) => tokens,
+                Err(err) => panic!("Failed to lex input: {:?}", err),
+            };
+            assert!(Parser::new(&tokens).parse().is_err(), "Parsed invalid declarator: {}", input);
+        }
     }
 
     #[test]
-    fn test_global_var_assignment() {
-        let input = "int a = 0;\nint b = 1;\nint c = 2;\n";
-        println!("Input: {}", input);
-        let tokens = match lex(input) {
-            Ok(tokens) => tokens,
-            Err(err) => {
-                println!("Error: {:?}", err);
-                return
-            },
+    fn test_struct_members() {
+        let header = parse_header("struct packet {\n    unsigned ready : 1, : 3, mode : 4;\n    union { int i; float f; };\n    struct header { short length; } head;\n    struct footer { char check; };\n    char data[];\n};\n");
+        let HeaderStatement::Struct(packet) = &header.statements[0] else {
+            panic!("Expected a struct: {:?}", header.statements[0]);
         };
 
-        println!("Tokens: {:?}", tokens);
-        let mut parser = Parser::new(tokens);
-        let result = parser.parse();
-        println!("Result: {:?}", result);
-        assert!(result.is_ok(), "Failed to parse global variable assignment");
+        let Member::BitFields(bit_fields) = &packet.members[0] else {
+            panic!("Expected bit-fields: {:?}", packet.members[0]);
+        };
+        assert_eq!(bit_fields.fields.iter().map(|field| field.name.as_deref()).collect::<Vec<_>>(), vec![Some("ready"), None, Some("mode")]);
+        assert!(matches!(&packet.members[1], Member::Union(Union { name: None, members, .. }) if members.len() == 2));
+        assert!(matches!(&packet.members[2], Member::Variables(VariableList { specifiers, variables, .. })
+            if variables[0].name == "head" && matches!(specifiers.definition.as_deref(), Some(Definition::Struct(Struct { name: Some(name), .. })) if name == "header")));
+        assert!(matches!(&packet.members[3], Member::Struct(Struct { name: Some(name), .. }) if name == "footer"));
+        assert!(matches!(&packet.members[4], Member::Variables(VariableList { variables, .. })
+            if variables[0].type_ == DeclaredType::Array(Box::new(DeclaredType::Base(Type::new(TypeSpecifier::Char))), VariableArray::NoSize)));
     }
 
     #[test]
-    fn test_global_var_assignment_with_expr() {
-        let input = "int a = 0 + 1;\nint b = 1 + 2;\nint c = 2 + 3;\n";
-        println!("Input: {}", input);
-        let tokens = match lex(input) {
-            Ok(tokens) => tokens,
-            Err(err) => {
-                println!("Error: {:?}", err);
-                return
-            },
+    fn test_members_in_classes_and_tagged_unions() {
+        let header = parse_header("class Widget {\n    unsigned visible : 1;\n    struct { int x, y; } position;\n    int area();\n};\ntagged shape { circle {unsigned filled : 1; int radius;}, square {struct { int w, h; };} };\n");
+        let HeaderStatement::Class(widget) = &header.statements[0] else {
+            panic!("Expected a class: {:?}", header.statements[0]);
         };
-        
-        println!("Tokens: {:?}", tokens);
-        let mut parser = Parser::new(tokens);
-        let result = parser.parse();
-        println!("Result: {:?}", result);
-        assert!(result.is_ok(), "Failed to parse global variable assignment with expression");
-    }
+        assert!(matches!(&widget.members[0], ClassMember::Field(Member::BitFields(_))));
+        assert!(matches!(&widget.members[1], ClassMember::Field(Member::Variables(VariableList { specifiers, .. }))
+            if specifiers.type_.specifier == TypeSpecifier::Struct(None) && specifiers.definition.is_some()));
+        assert!(matches!(&widget.members[2], ClassMember::Method(Method::Abstract(_))));
 
-    #[test]
-    fn test_struct_access() {
-        let input = "struct foo { int a; int b; int c; };\nint main() { struct foo f; f.a = 0; f.b = 1; f.c = 2; return 0; }\n";
-        println!("Input: {}", input);
-        let tokens = match lex(input) {
-            Ok(tokens) => tokens,
-            Err(err) => {
-                println!("Error: {:?}", err);
-                return
-            },
+        let HeaderStatement::TaggedUnion(shape) = &header.statements[1] else {
+            panic!("Expected a tagged union: {:?}", header.statements[1]);
         };
-        
-        println!("Tokens: {:?}", tokens);
-        let mut parser = Parser::new(tokens);
-        let result = parser.parse();
-        println!("Result: {:?}", result);
-        assert!(result.is_ok(), "Failed to parse struct access");
+        assert!(matches!(shape.members[0].value.as_deref(), Some([Member::BitFields(_), Member::Variables(_)])));
+        assert!(matches!(shape.members[1].value.as_deref(), Some([Member::Struct(Struct { name: None, .. })])));
     }
 
     #[test]
-    fn test_method_call() {
-        let input = "int main() {\n clss.method();\n return 0;\n}\n";
-        println!("Input: {}", input);
-        let tokens = match lex(input) {
-            Ok(tokens) => tokens,
-            Err(err) => {
-                println!("Error: {:?}", err);
-                assert!(false);
-                return
-            },
-        };
-        println!("Tokens: {:?}", tokens);
-        let mut parser = Parser::new(tokens);
-        let result = parser.parse();
-        println!("Result: {:?}", result);
-        assert!(result.is_ok(), "Failed to parse method call");
+    fn test_invalid_members() {
+        for input in ["struct s { char data[]; int n; };", "struct s { char data[]; };", "union u { int n; char data[]; };",
+                      "struct s { float f : 3; };", "struct s { int; };", "struct s { int f(void) { return 0; } };",
+                      "class D { char data[]; int x; };", "tagged t { a { char d[]; int x; } };", "class E { char data[]; };"] {
+            let tokens = match lex(input) {
+                Ok(tokens) => tokens,
+                Err(err) => panic!("Failed to lex input: {:?}", err),
+            };
+            assert!(Parser::new(&tokens).parse().is_err(), "Parsed invalid member: {}", input);
+        }
+        parse_header("class D { int x; char data[]; int size(void) { return x; } };\ntagged t { a { int x; char d[]; }, b };\n");
     }
 
     #[test]
-    fn test_union_access() {
-        let input = "union foo { int a; int b; int c; };\nint main() { union foo f; f.a = 0; f.b = 1; f.c = 2; return 0; }\n";
-        println!("Input: {}", input);
-        let tokens = match lex(input) {
-            Ok(tokens) => tokens,
-            Err(err) => {
-                assert!(false);
-                println!("Error: {:?}", err);
-                return
-            },
+    fn test_expression_precedence() {
+        let header = parse_header("int x = 2 * 3 + 1, y = a = b ? c : d || e && f;\n");
+        let HeaderStatement::Variable(list) = &header.statements[0] else {
+            panic!("Expected a variable: {:?}", header.statements[0]);
         };
-        
-        println!("Tokens: {:?}", tokens);
-        let mut parser = Parser::new(tokens);
-        let result = parser.parse();
-        println!("Result: {:?}", result);
-        assert!(result.is_ok(), "Failed to parse union access");
+        let number = |raw| Box::new(Expression::Literal(Literal::Number(crate::literal::number_literal(raw).unwrap())));
+        let name = |name: &str| Box::new(Expression::Identifier(name.to_string()));
+        assert_eq!(list.variables[0].value, Some(VariableValue::Expression(Expression::Binary(BinaryOperator::Add,
+            Box::new(Expression::Binary(BinaryOperator::Multiply, number("2"), number("3"))), number("1")))));
+        assert_eq!(list.variables[1].value, Some(VariableValue::Expression(Expression::Binary(BinaryOperator::Assign, name("a"),
+            Box::new(Expression::Ternary(name("b"), name("c"),
+                Box::new(Expression::Binary(BinaryOperator::LogicalOr, name("d"),
+                    Box::new(Expression::Binary(BinaryOperator::LogicalAnd, name("e"), name("f")))))))))));
     }
 
     #[test]
-    fn test_enum() {
-        let input = "enum foo { a, b, c };\nint main() { enum foo f; f = a; return 0; }\n";
-        println!("Input: {}", input);
-        let tokens = match lex(input) {
-            Ok(tokens) => tokens,
-            Err(err) => {
-                assert!(false);
-                println!("Error: {:?}", err);
-                return
-            },
+    fn test_c11_and_c23_keywords() {
+        let header = parse_header("static_assert(sizeof(int) == 4, \"int is 32 bits\");\nstatic_assert(1);\nalignas(16) char buffer[64];\nalignas(double) int aligned;\nstatic thread_local int counter;\nconstexpr int *none = nullptr;\nnoreturn void fail(void);\ntypeof(counter) copy;\ntypeof_unqual(const int) plain;\nint f(void) { static_assert(2 > 1); static thread_local int calls; return 0; }\n");
+        let statements = header.statements.iter().filter(|statement| **statement != HeaderStatement::Whitespace).collect::<Vec<_>>();
+
+        assert!(matches!(statements[0], HeaderStatement::StaticAssert(StaticAssert { message: Some(message), .. }) if message.to_text() == "int is 32 bits"));
+        assert!(matches!(statements[1], HeaderStatement::StaticAssert(StaticAssert { message: None, .. })));
+        let HeaderStatement::Variable(buffer) = statements[2] else {
+            panic!("Expected a variable: {:?}", statements[2]);
         };
-        
-        println!("Tokens: {:?}", tokens);
-        let mut parser = Parser::new(tokens);
-        let result = parser.parse();
-        println!("Result: {:?}", result);
-        assert!(result.is_ok(), "Failed to parse enum");
+        assert!(matches!(buffer.specifiers.alignas.as_slice(), [TypeOrExpression::Expression(_)]));
+        let HeaderStatement::Variable(aligned) = statements[3] else {
+            panic!("Expected a variable: {:?}", statements[3]);
+        };
+        assert!(matches!(aligned.specifiers.alignas.as_slice(), [TypeOrExpression::Type(_)]));
+        assert!(matches!(statements[4], HeaderStatement::Variable(VariableList { specifiers, .. })
+            if specifiers.thread_local && specifiers.storage == Some(StorageClass::Static)));
+        assert!(matches!(statements[5], HeaderStatement::Variable(VariableList { specifiers, variables, .. })
+            if specifiers.constexpr && variables[0].value == Some(VariableValue::Expression(Expression::Literal(Literal::Nullptr)))));
+        assert!(matches!(statements[6], HeaderStatement::FunctionPrototype(FunctionPrototype { specifiers, .. }) if specifiers.noreturn));
+        assert!(matches!(statements[7], HeaderStatement::Variable(VariableList { specifiers, .. })
+            if specifiers.type_.specifier == TypeSpecifier::Typeof(Box::new(TypeOrExpression::Expression(Box::new(Expression::Identifier("counter".to_string())))))));
+        assert!(matches!(statements[8], HeaderStatement::Variable(VariableList { specifiers, .. })
+            if matches!(&specifiers.type_.specifier, TypeSpecifier::TypeofUnqual(operand) if matches!(operand.as_ref(), TypeOrExpression::Type(_)))));
+
+        let HeaderStatement::Function(function) = statements[9] else {
+            panic!("Expected a function: {:?}", statements[9]);
+        };
+        let CodeBlock::Code(body) = &function.body;
+        assert!(matches!(&body.statements[0], Statement::StaticAssert(_)));
+        assert!(matches!(&body.statements[1], Statement::VariableList(VariableList { specifiers, .. }) if specifiers.thread_local));
     }
 
     #[test]
-    fn test_simple_tagged_union() {
-        let input = "tagged foo { a,b,c};\nint main() { tagged foo f; f = a; return 0; }\n";
-        println!("Input: {}", input);
-        let tokens = match lex(input) {
-            Ok(tokens) => tokens,
-            Err(err) => {
-                assert!(false);
-                println!("Error: {:?}", err);
-                return
-            },
-        };
-
-        println!("Tokens: {:?}", tokens);
-        let mut parser = Parser::new(tokens);
-        let result = parser.parse();
-        println!("Result: {:?}", result);
-        assert!(result.is_ok(),"Failed to parse simple tagged union");
-
+    fn test_invalid_c11_and_c23_keywords() {
+        for input in ["alignas(8) typedef int t;", "register alignas(8) int x;", "struct s { alignas(4) int x : 3; };",
+                      "int f(alignas(4) int x);", "alignas(8) int f(void);", "constexpr int x;", "constexpr int f(void);",
+                      "constexpr volatile int x = 1;", "constexpr thread_local int x = 1;", "thread_local int f(void);",
+                      "int f(void) { thread_local int x; return 0; }", "noreturn int x;", "noreturn void f(int x) { if (x) { return; } }",
+                      "static_assert(1, 2);", "static_assert 1;", "static_assert(1)", "typeof(int) long x;", "typeof int x;",
+                      "int x = sizeof(alignas(4) int);"] {
+            let tokens = match lex(input) {
+                Ok(tokens) => tokens,
+                Err(err) => panic!("Failed to lex input: {:?}", err),
+            };
+            assert!(Parser::new(&tokens).parse().is_err(), "Parsed invalid declaration: {}", input);
+        }
     }
 
     #[test]
-    fn test_complex_tagged_union() {
-        let input = "tagged foo { a {int a, b, c;}, b {char *a; int b;}, c {int a; char *b;}};\nint main() { tagged foo f; f = a {1,2,3}; return 0; }\n";
-        println!("Input: {}", input);
-        let tokens = match lex(input) {
-            Ok(tokens) => tokens,
-            Err(err) => {
-                assert!(false);
-                println!("Error: {:?}", err);
-                return
-            },
-        };
+    fn test_generic_selections() {
+        let header = parse_header("double r = _Generic((x), float: sinf, long double: sinl, default: sin)(x);\nint kind = _Generic(p, char *: 1, const int *: 2, struct s: 3, default: 0);\n");
+        let statements = header.statements.iter().filter(|statement| **statement != HeaderStatement::Whitespace).collect::<Vec<_>>();
 
-        println!("Tokens: {:?}", tokens);
-        let mut parser = Parser::new(tokens);
-        let result = parser.parse();
-        println!("Result: {:?}", result);
-        assert!(result.is_ok(),"Failed to parse complex tagged union");
+        let HeaderStatement::Variable(r) = statements[0] else {
+            panic!("Expected a variable: {:?}", statements[0]);
+        };
+        let Some(VariableValue::Expression(Expression::Call(callee, Some(argument)))) = &r.variables[0].value else {
+            panic!("Expected a call: {:?}", r.variables[0].value);
+        };
+        assert_eq!(**argument, Expression::Identifier("x".to_string()));
+        let Expression::Generic(control, associations) = callee.as_ref() else {
+            panic!("Expected a _Generic selection: {:?}", callee);
+        };
+        assert_eq!(**control, Expression::Parentheses(Box::new(Expression::Identifier("x".to_string()))));
+        assert_eq!(associations.iter().map(|association| association.type_.clone()).collect::<Vec<_>>(),
+                   vec![Some(DeclaredType::Base(Type::new(TypeSpecifier::Float))), Some(DeclaredType::Base(Type::new(TypeSpecifier::LongDouble))), None]);
+        assert_eq!(associations[2].expression, Expression::Identifier("sin".to_string()));
 
+        let HeaderStatement::Variable(kind) = statements[1] else {
+            panic!("Expected a variable: {:?}", statements[1]);
+        };
+        let Some(VariableValue::Expression(Expression::Generic(_, associations))) = &kind.variables[0].value else {
+            panic!("Expected a _Generic selection: {:?}", kind.variables[0].value);
+        };
+        assert!(matches!(&associations[0].type_, Some(DeclaredType::Pointer(inner, _)) if inner.base().specifier == TypeSpecifier::Char));
+        assert!(matches!(&associations[1].type_, Some(DeclaredType::Pointer(inner, _)) if inner.base().qualifiers.const_));
+        assert!(matches!(&associations[2].type_, Some(DeclaredType::Base(base)) if base.specifier == TypeSpecifier::Struct(Some("s".to_string()))));
     }
 
     #[test]
-    fn test_global_array_dec() {
-        let input = "int a[10];\nint main() { return 0; }\n";
-        println!("Input: {}", input);
-        let tokens = match lex(input) {
-            Ok(tokens) => tokens,
-            Err(err) => {
-                assert!(false);
-                println!("Error: {:?}", err);
-                return
-            },
-        };
-
-        println!("Tokens: {:?}", tokens);
-        let mut parser = Parser::new(tokens);
-        let result = parser.parse();
-        println!("Result: {:?}", result);
-        assert!(result.is_ok(),"Failed to parse global array declaration");
+    fn test_invalid_generic_selections() {
+        for input in ["int x = _Generic(1);", "int x = _Generic(1, default: 1, default: 2);", "int x = _Generic(1, int: 1, int: 2);",
+                      "int x = _Generic(1, int(void): 1);", "int x = _Generic(1, int 1);", "int x = _Generic 1, int: 1;",
+                      "int x = _Generic(1, int: 1;"] {
+            let tokens = match lex(input) {
+                Ok(tokens) => tokens,
+                Err(err) => panic!("Failed to lex input: {:?}", err),
+            };
+            assert!(Parser::new(&tokens).parse().is_err(), "Parsed invalid selection: {}", input);
+        }
     }
 
     #[test]
-    fn test_global_var_mix_with_array() {
-        let input = "int a[10], b;\nint main() { return 0; }\n";
-        println!("Input: {}", input);
-        let tokens = match lex(input) {
-            Ok(tokens) => tokens,
-            Err(err) => {
-                assert!(false);
-                println!("Error: {:?}", err);
-                return
-            },
+    fn test_attributes() {
+        let header = parse_header("[[nodiscard(\"check it\"), gnu::cold]] int f(void) __attribute__((warn_unused_result));\nstruct __attribute__((packed)) [[deprecated]] s { int x [[maybe_unused]]; char c; } __attribute__((aligned(8)));\nenum e { A [[deprecated]] = 1, B };\nint g([[maybe_unused]] int x, int y __attribute__((unused))) { switch (y) { case 1: y++; [[fallthrough]]; case 2: return 0; } [[gnu::hot]] y--; return y; }\nclass C { [[nodiscard]] int get() { return 1; } __attribute__((cold)) int operator(+)(C other); };\n");
+        let statements = header.statements.iter().filter(|statement| **statement != HeaderStatement::Whitespace).collect::<Vec<_>>();
+        let names = |attributes: &[Attribute]| attributes.iter().map(|attribute| attribute.to_string()).collect::<Vec<_>>();
+
+        let HeaderStatement::FunctionPrototype(f) = statements[0] else {
+            panic!("Expected a prototype: {:?}", statements[0]);
         };
+        assert_eq!(names(&f.specifiers.attributes), ["[[nodiscard(\"check it\")]]", "[[gnu::cold]]", "__attribute__((warn_unused_result))"]);
+        assert_eq!(f.specifiers.attributes[0].message(), Some("check it".to_string()));
+        assert!(f.specifiers.attributes[1].is("cold") && f.specifiers.attributes[2].syntax == AttributeSyntax::Gnu);
 
-        println!("Tokens: {:?}", tokens);
-        let mut parser = Parser::new(tokens);
-        let result = parser.parse();
-        println!("Result: {:?}", result);
-        assert!(result.is_ok(),"Failed to parse global array declaration");
-    }
+        let HeaderStatement::Struct(s) = statements[1] else {
+            panic!("Expected a struct: {:?}", statements[1]);
+        };
+        assert_eq!(names(&s.attributes), ["__attribute__((packed))", "[[deprecated]]", "__attribute__((aligned(8)))"]);
+        assert!(matches!(&s.members[0], Member::Variables(VariableList { variables, .. }) if names(&variables[0].attributes) == ["[[maybe_unused]]"]));
 
-    #[test]
-    fn test_global_var_string_array_assign() {
-        let input = "char *a[10] = {\"Hello\", \"World\"};\nint main() { return 0; }\n";
-        println!("Input: {}", input);
-        let tokens = match lex(input) {
-            Ok(tokens) => tokens,
-            Err(err) => {
-                assert!(false);
-                println!("Error: {:?}", err);
-                return
-            },
+        let HeaderStatement::Enum(e) = statements[2] else {
+            panic!("Expected an enum: {:?}", statements[2]);
         };
+        assert!(e.members[0].attributes[0].is("deprecated") && e.members[1].attributes.is_empty());
 
-        println!("Tokens: {:?}", tokens);
-        let mut parser = Parser::new(tokens);
-        let result = parser.parse();
-        println!("Result: {:?}", result);
-        assert!(result.is_ok(),"Failed to parse global array declaration");
-    }
+        let HeaderStatement::Function(g) = statements[3] else {
+            panic!("Expected a function: {:?}", statements[3]);
+        };
+        let attributes = g.arguments.iter().map(|argument| match argument {
+            FunctionArgument::Parameter(parameter) => names(&parameter.attributes),
+            FunctionArgument::Ellipsis => Vec::new(),
+        }).collect::<Vec<_>>();
+        assert_eq!(attributes, [["[[maybe_unused]]"], ["__attribute__((unused))"]]);
+        let CodeBlock::Code(body) = &g.body;
+        let Statement::Switch(_, cases) = &body.statements[0] else {
+            panic!("Expected a switch: {:?}", body.statements[0]);
+        };
+        assert!(matches!(cases[0].body.as_ref(), BlockOrStatement::Block(CodeBlock::Code(list))
+            if matches!(&list.statements[1], Statement::Attributes(attributes) if attributes[0].is("fallthrough"))));
+        assert!(matches!(&body.statements[1], Statement::Attributed(attributes, statement)
+            if attributes[0].is("hot") && matches!(statement.as_ref(), Statement::Expression(_))));
 
-    #[test]
-    fn test_global_var_string_array_assign_no_size() {
-        let input = "char a[] = \"Hello\";\nint main() { return 0; }\n";
-        println!("Input: {}", input);
-        let tokens = match lex(input) {
-            Ok(tokens) => tokens,
-            Err(err) => {
-                println!("Error: {:?}", err);
-                assert!(false);
-                return
-            },
+        let HeaderStatement::Class(class) = statements[4] else {
+            panic!("Expected a class: {:?}", statements[4]);
         };
+        assert!(matches!(&class.members[0], ClassMember::Method(Method::Normal(get)) if get.specifiers.attributes[0].is("nodiscard")));
+        assert!(matches!(&class.members[1], ClassMember::OperatorOverload(OperatorOverload::Abstract { attributes, .. }) if attributes[0].is("cold")));
 
-        println!("Tokens: {:?}", tokens);
-        let mut parser = Parser::new(tokens);
-        let result = parser.parse();
-        println!("Result: {:?}", result);
-        assert!(result.is_ok(),"Failed to parse global array declaration");
+        let header = parse_header("[[vendor::thing(1, [2], {f(x)}), , __deprecated__]] int x, y [[]];\n");
+        let HeaderStatement::Variable(variables) = &header.statements[0] else {
+            panic!("Expected a variable: {:?}", header.statements[0]);
+        };
+        assert_eq!(names(&variables.specifiers.attributes), ["[[vendor::thing(1, [ 2 ], { f(x) })]]", "[[__deprecated__]]"]);
+        assert!(variables.specifiers.attributes[1].is("deprecated") && !variables.specifiers.attributes[0].is("thing"));
     }
-    
 
     #[test]
-    fn test_global_array_dec_4d() {
-        let input = "int a[10][10][10][10];\nint main() { return 0; }\n";
-        println!("Input: {}", input);
-        let tokens = match lex(input) {
-            Ok(tokens) => tokens,
-            Err(err) => {
-                assert!(false);
-                println!("Error: {:?}", err);
-                return
-            },
+    fn test_blocks_labels_and_for_initializers() {
+        let header = parse_header("int f(int n) {\n    int i;\n    for (i = 0; i < n; i++) { { n--; } }\nagain:\n    if (n) goto again;\n    return n;\n}\n");
+        let HeaderStatement::Function(f) = &header.statements[0] else {
+            panic!("Expected a function: {:?}", header.statements[0]);
         };
-
-        println!("Tokens: {:?}", tokens);
-        let mut parser = Parser::new(tokens);
-        let result = parser.parse();
-        println!("Result: {:?}", result);
-        assert!(result.is_ok(),"Failed to parse global array declaration");
+        let CodeBlock::Code(body) = &f.body;
+        let Statement::For(Some(initializer), Some(_), Some(_), loop_body) = &body.statements[1] else {
+            panic!("Expected a for loop: {:?}", body.statements[1]);
+        };
+        assert!(matches!(initializer.as_ref(), VariableListOrStatement::Statement(Statement::Expression(_))));
+        assert!(matches!(loop_body.as_ref(), BlockOrStatement::Block(CodeBlock::Code(list))
+            if matches!(list.statements[..], [Statement::Block(_)])));
+        assert_eq!(body.statements[2], Statement::Label("again".to_string()));
     }
 
     #[test]
-    fn test_array_in_function() {
-        let input = "int main() { int a[10]; return 0; }\n";
-        println!("Input: {}", input);
-        let tokens = match lex(input) {
-            Ok(tokens) => tokens,
-            Err(err) => {
-                println!("Error: {:?}", err);
-                assert!(false);
-                return
-            },
+    fn test_type_names_in_blocks() {
+        let header = parse_header("typedef int myint;\nclass A { int x; A copy() { A a; return a; } };\n\
+                                   int f(myint n) {\n    A a;\n    myint *p = 0;\n    n = (myint)sizeof(A);\n\
+                                   for (myint i = 0; i < n; i++) {}\n    { int myint = 2; myint * n; }\n    return n;\n}\n\
+                                   int g(int myint) { myint * 2; return myint; }\n");
+        let declaration = |statement: &Statement| match statement {
+            Statement::VariableList(list) => Some((list.specifiers.type_.specifier.clone(), list.variables[0].type_.clone())),
+            _ => None,
         };
-
-        println!("Tokens: {:?}", tokens);
-        let mut parser = Parser::new(tokens);
-        let result = parser.parse();
-        println!("Result: {:?}", result);
-        assert!(result.is_ok(),"Failed to parse array in function");
+        let named = |name: &str| Type::new(TypeSpecifier::Named(name.to_string()));
+        let HeaderStatement::Function(f) = &header.statements[2] else {
+            panic!("Expected a function: {:?}", header.statements[2]);
+        };
+        let CodeBlock::Code(body) = &f.body;
+        assert_eq!(declaration(&body.statements[0]), Some((named("A").specifier, DeclaredType::Base(named("A")))));
+        assert_eq!(declaration(&body.statements[1]),
+                   Some((named("myint").specifier, pointer(DeclaredType::Base(named("myint")), TypeQualifiers::default()))));
+        assert!(matches!(&body.statements[2], Statement::Expression(Expression::Binary(BinaryOperator::Assign, _, value))
+            if matches!(value.as_ref(), Expression::Unary(UnaryOperator::Cast(_), _))));
+        assert!(matches!(&body.statements[3], Statement::For(Some(initializer), ..)
+            if matches!(initializer.as_ref(), VariableListOrStatement::VariableList(_))));
+        // A variable hides a type of the same name until the end of its block.
+        let Statement::Block(block) = &body.statements[4] else {
+            panic!("Expected a block: {:?}", body.statements[4]);
+        };
+        let CodeBlock::Code(inner) = block.as_ref();
+        assert!(matches!(inner.statements[1], Statement::Expression(Expression::Binary(BinaryOperator::Multiply, ..))));
+        let HeaderStatement::Function(g) = &header.statements[3] else {
+            panic!("Expected a function: {:?}", header.statements[3]);
+        };
+        let CodeBlock::Code(body) = &g.body;
+        assert!(matches!(body.statements[0], Statement::Expression(Expression::Binary(BinaryOperator::Multiply, ..))));
     }
 
+    #[test]
+    fn test_invalid_attributes() {
+        for input in ["[[nodiscard] int f(void);", "[[deprecated(]] int x;", "[[a::]] int x;", "[[a:b]] int x;", "[[1]] int x;",
+                      "__attribute__(packed) int x;", "__attribute__((packed) int x;", "[[x(1)) ]] int x;",
+                      "int x = sizeof(__attribute__((packed)) int);", "int f(int x) { [[fallthrough]] }"] {
+            let tokens = match lex(input) {
+                Ok(tokens) => tokens,
+                Err(err) => panic!("Failed to lex input: {:?}", err),
+            };
+            assert!(Parser::new(&tokens).parse().is_err(), "Parsed invalid attribute: {}", input);
+        }
+    }
 }
@@ -0,0 +1,1495 @@
+use crate::ast::*;
+
+
+/// Where the opening brace of a block or definition goes.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum BraceStyle {
+    /// At the end of the line that opens the block, as in K&R.
+    #[default]
+    SameLine,
+    /// On a line of its own, as in Allman.
+    NextLine,
+}
+
+/// The layout choices of the printer.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Style {
+    /// How many spaces each level of nesting is indented by.
+    pub indent_width: usize,
+    pub brace_style: BraceStyle,
+    /// The column that argument, parameter and initializer lists are broken to stay within.
+    pub line_width: usize,
+    /// Whether every operand that is itself an operation, and every named declarator nested
+    /// inside another, is put in parentheses to show how it was grouped.
+    pub parenthesize: bool,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style {indent_width: 4, brace_style: BraceStyle::SameLine, line_width: 100, parenthesize: false}
+    }
+}
+
+/// The levels of the expression grammar from loosest to tightest. An operand
+/// printed where the parser expects a tighter level is put in parentheses.
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+enum Level {
+    Comma,
+    Assignment,
+    Conditional,
+    /// A binary operator, with the precedence the parser gives it.
+    Binary(u8),
+    Unary,
+    Postfix,
+    Primary,
+}
+
+/// The level of the grammar an expression is parsed at.
+fn expression_level(expression: &Expression) -> Level {
+    match expression {
+        Expression::Expression(inner) => expression_level(inner),
+        Expression::Binary(BinaryOperator::Comma, ..) => Level::Comma,
+        Expression::Binary(operator, ..) => match binary_operator(operator) {
+            (_, Some(precedence)) => Level::Binary(precedence),
+            _ if matches!(operator, BinaryOperator::MemberAccess | BinaryOperator::PointerMemberAccess |
+                          BinaryOperator::Index | BinaryOperator::ArrayAccess) => Level::Postfix,
+            _ => Level::Assignment,
+        },
+        Expression::Ternary(..) => Level::Conditional,
+        Expression::Unary(UnaryOperator::PostIncrement | UnaryOperator::PostDecrement, _) |
+        Expression::CallFunction(..) | Expression::Call(..) | Expression::TaggedInitializer(..) => Level::Postfix,
+        Expression::Unary(UnaryOperator::MemberSet, _) => Level::Primary,
+        Expression::Unary(..) | Expression::Sizeof(_) | Expression::Alignof(_) => Level::Unary,
+        _ => Level::Primary,
+    }
+}
+
+/// The spelling and precedence of a binary operator; `None` for the ones that are
+/// not parsed by precedence climbing.
+fn binary_operator(operator: &BinaryOperator) -> (&'static str, Option<u8>) {
+    match operator {
+        BinaryOperator::LogicalOr => ("||", Some(1)),
+        BinaryOperator::LogicalAnd => ("&&", Some(2)),
+        BinaryOperator::BitwiseOr => ("|", Some(3)),
+        BinaryOperator::BitwiseXor => ("^", Some(4)),
+        BinaryOperator::BitwiseAnd => ("&", Some(5)),
+        BinaryOperator::Equal => ("==", Some(6)),
+        BinaryOperator::NotEqual => ("!=", Some(6)),
+        BinaryOperator::LessThan => ("<", Some(7)),
+        BinaryOperator::GreaterThan => (">", Some(7)),
+        BinaryOperator::LessThanOrEqual => ("<=", Some(7)),
+        BinaryOperator::GreaterThanOrEqual => (">=", Some(7)),
+        BinaryOperator::LeftShift => ("<<", Some(8)),
+        BinaryOperator::RightShift => (">>", Some(8)),
+        BinaryOperator::Add => ("+", Some(9)),
+        BinaryOperator::Subtract => ("-", Some(9)),
+        BinaryOperator::Multiply => ("*", Some(10)),
+        BinaryOperator::Divide => ("/", Some(10)),
+        BinaryOperator::Modulo => ("%", Some(10)),
+        BinaryOperator::Assign => ("=", None),
+        BinaryOperator::AddAssign => ("+=", None),
+        BinaryOperator::SubtractAssign => ("-=", None),
+        BinaryOperator::MultiplyAssign => ("*=", None),
+        BinaryOperator::DivideAssign => ("/=", None),
+        BinaryOperator::ModuloAssign => ("%=", None),
+        BinaryOperator::LeftShiftAssign => ("<<=", None),
+        BinaryOperator::RightShiftAssign => (">>=", None),
+        BinaryOperator::BitwiseAndAssign => ("&=", None),
+        BinaryOperator::BitwiseOrAssign => ("|=", None),
+        BinaryOperator::BitwiseXorAssign => ("^=", None),
+        BinaryOperator::Comma => (",", None),
+        BinaryOperator::MemberAccess => (".", None),
+        BinaryOperator::PointerMemberAccess => ("->", None),
+        BinaryOperator::Index | BinaryOperator::ArrayAccess => ("[]", None),
+    }
+}
+
+/// The spelling of a prefix or postfix operator; casts are spelled by their type.
+fn unary_operator(operator: &UnaryOperator) -> &'static str {
+    match operator {
+        UnaryOperator::Plus => "+",
+        UnaryOperator::Minus => "-",
+        UnaryOperator::Not | UnaryOperator::LogicalNot => "!",
+        UnaryOperator::BitwiseNot => "~",
+        UnaryOperator::Dereference => "*",
+        UnaryOperator::AddressOf => "&",
+        UnaryOperator::PreIncrement | UnaryOperator::PostIncrement => "++",
+        UnaryOperator::PreDecrement | UnaryOperator::PostDecrement => "--",
+        UnaryOperator::MemberSet => ".",
+        UnaryOperator::Cast(_) => "()",
+    }
+}
+
+fn storage_class(storage: StorageClass) -> &'static str {
+    match storage {
+        StorageClass::Typedef => "typedef",
+        StorageClass::Extern => "extern",
+        StorageClass::Static => "static",
+        StorageClass::Auto => "auto",
+        StorageClass::Register => "register",
+    }
+}
+
+fn qualifiers(qualifiers: &TypeQualifiers) -> String {
+    let mut words = Vec::new();
+    if qualifiers.const_ {
+        words.push("const");
+    }
+    if qualifiers.volatile {
+        words.push("volatile");
+    }
+    if qualifiers.restrict {
+        words.push("restrict");
+    }
+    if qualifiers.atomic {
+        words.push("atomic");
+    }
+    words.join(" ")
+}
+
+fn attributes(attributes: &[Attribute]) -> String {
+    attributes.iter().map(Attribute::to_string).collect::<Vec<String>>().join(" ")
+}
+
+/// Whether a top level declaration or class member spans several lines, so that
+/// it is set apart from its neighbours by a blank line.
+fn is_block(statement: &HeaderStatement) -> bool {
+    matches!(statement,
+        HeaderStatement::Struct(_) | HeaderStatement::Union(_) | HeaderStatement::Enum(_) |
+        HeaderStatement::Function(_) | HeaderStatement::Class(_) | HeaderStatement::TaggedUnion(_))
+}
+
+fn is_block_member(member: &ClassMember) -> bool {
+    matches!(member, ClassMember::Method(Method::Normal(_)) | ClassMember::OperatorOverload(OperatorOverload::Normal {..}))
+}
+
+/// Prints a header back to source text that parses to the same tree.
+pub fn print(header: &Header) -> String {
+    print_with_style(header, &Style::default())
+}
+
+/// Prints a header with the indentation, braces and line width of `style`.
+pub fn print_with_style(header: &Header, style: &Style) -> String {
+    let mut printer = Printer {style: *style, ..Printer::default()};
+    printer.header(header);
+    printer.output
+}
+
+/// Prints any node of the tree. Declarations and statements end with a newline,
+/// while types, expressions and the other pieces of a declaration are printed inline.
+pub fn print_node(node: &AstNode) -> String {
+    let mut printer = Printer::default();
+    match node {
+        AstNode::None => {},
+        AstNode::Header(header) => printer.header(header),
+        AstNode::HeaderStatement(statement) => printer.header_statement(statement),
+        AstNode::Preprocessor(preprocessor) => printer.preprocessor(preprocessor),
+        AstNode::Struct(struct_) => printer.record_declaration("struct", &struct_.name, &struct_.attributes, &struct_.members, &struct_.doc),
+        AstNode::Union(union_) => printer.record_declaration("union", &union_.name, &union_.attributes, &union_.members, &union_.doc),
+        AstNode::Enum(enum_) => {
+            printer.doc(&enum_.doc);
+            printer.indentation();
+            printer.enum_(enum_);
+            printer.push(";\n");
+        },
+        AstNode::EnumMember(member) => printer.enum_member(member),
+        AstNode::VariableValue(value) => printer.variable_value(value),
+        AstNode::VariableArray(size) => {
+            let size = printer.array_size(size);
+            printer.push(&format!("[{}]", size));
+        },
+        AstNode::Variable(variable) => {
+            printer.push(&printer.base_type(variable.type_.base()));
+            printer.push(" ");
+            printer.variable(variable);
+        },
+        AstNode::VariableList(variable_list) => printer.variable_list_declaration(variable_list),
+        AstNode::Member(member) => printer.member(member),
+        AstNode::BitFieldList(bit_fields) => printer.member(&Member::BitFields(bit_fields.clone())),
+        AstNode::Definition(definition) => printer.definition(definition),
+        AstNode::Parameter(parameter) => {
+            let parameter = printer.parameter(parameter);
+            printer.push(&parameter);
+        },
+        AstNode::FunctionArgument(argument) => {
+            let argument = printer.arguments(std::slice::from_ref(argument));
+            printer.push(&argument);
+        },
+        AstNode::FunctionPrototype(prototype) => printer.prototype(prototype),
+        AstNode::Function(function) => printer.function(function),
+        AstNode::Class(class) => printer.class(class),
+        AstNode::ClassMember(member) => printer.class_member(member),
+        AstNode::Method(Method::Normal(function)) => printer.function(function),
+        AstNode::Method(Method::Abstract(prototype)) => printer.prototype(prototype),
+        AstNode::DeclarationSpecifiers(specifiers) => printer.specifiers(specifiers, false),
+        AstNode::StorageClass(storage) => printer.push(storage_class(*storage)),
+        AstNode::TypeSpecifier(specifier) => {
+            let specifier = printer.type_specifier(specifier);
+            printer.push(&specifier);
+        },
+        AstNode::TypeQualifiers(type_qualifiers) => printer.push(&qualifiers(type_qualifiers)),
+        AstNode::Type(type_) => printer.push(&printer.base_type(type_)),
+        AstNode::DeclaredType(type_) => {
+            let type_ = printer.type_name(type_);
+            printer.push(&type_);
+        },
+        AstNode::CodeBlock(block) => {
+            printer.block(block);
+            printer.push("\n");
+        },
+        AstNode::StatementList(list) => printer.statements(&list.statements),
+        AstNode::BlockOrStatement(BlockOrStatement::Block(block)) => {
+            printer.block(block);
+            printer.push("\n");
+        },
+        AstNode::BlockOrStatement(BlockOrStatement::Statement(statement)) => printer.statement(statement),
+        AstNode::VariableListOrStatement(VariableListOrStatement::VariableList(variable_list)) => {
+            printer.variable_list_declaration(variable_list);
+        },
+        AstNode::VariableListOrStatement(VariableListOrStatement::Statement(statement)) => printer.statement(statement),
+        AstNode::TypeOrExpression(operand) => printer.type_or_expression(operand),
+        AstNode::Statement(statement) => printer.statement(statement),
+        AstNode::SwitchCase(case) => printer.switch_case(case),
+        AstNode::Expression(expression) => printer.expression(expression, Level::Comma),
+        AstNode::Literal(literal) => printer.literal(literal),
+        AstNode::UnaryOperator(UnaryOperator::Cast(type_)) => {
+            let type_ = printer.type_name(type_);
+            printer.push(&format!("({})", type_));
+        },
+        AstNode::UnaryOperator(operator) => printer.push(unary_operator(operator)),
+        AstNode::BinaryOperator(operator) => printer.push(binary_operator(operator).0),
+        AstNode::Initializer(initializer) => printer.initializer(initializer),
+        AstNode::Designator(designator) => printer.designator(designator),
+        AstNode::TaggedUnion(tagged) => {
+            printer.doc(&tagged.doc);
+            printer.indentation();
+            printer.tagged_union(tagged);
+            printer.push(";\n");
+        },
+        AstNode::StaticAssert(static_assert) => {
+            printer.static_assert(static_assert);
+            printer.push("\n");
+        },
+        AstNode::GenericAssociation(association) => printer.generic_association(association),
+        AstNode::Attribute(attribute) => printer.push(&attribute.to_string()),
+    }
+    printer.output
+}
+
+/// Writes source text with one declaration or statement per line. Statements are
+/// written after the indentation of their line and leave the line open, so that
+/// `else if` and the initializer of a `for` can reuse them.
+#[derive(Default)]
+struct Printer {
+    output: String,
+    depth: usize,
+    style: Style,
+}
+
+impl Printer {
+    fn push(&mut self, text: &str) {
+        self.output.push_str(text);
+    }
+
+    /// Indents a new line. A directive at the end of the input has no newline of its own,
+    /// so one is added before anything that follows it.
+    fn indentation(&mut self) {
+        if !self.output.is_empty() && !self.output.ends_with('\n') {
+            self.output.push('\n');
+        }
+        self.output.push_str(&" ".repeat(self.depth * self.style.indent_width));
+    }
+
+    /// Prints with a fresh printer at the same depth and returns the text, for
+    /// pieces such as declarators that are assembled before they are written.
+    fn render(&self, print: impl FnOnce(&mut Printer)) -> String {
+        let mut printer = Printer {output: String::new(), depth: self.depth, style: self.style};
+        print(&mut printer);
+        printer.output
+    }
+
+    /// Renders without breaking any list, to measure whether it fits on the line.
+    fn flat(&self, print: impl FnOnce(&mut Printer)) -> String {
+        let style = Style {line_width: usize::MAX, ..self.style};
+        let mut printer = Printer {output: String::new(), depth: self.depth, style};
+        print(&mut printer);
+        printer.output
+    }
+
+    fn column(&self) -> usize {
+        let line = match self.output.rfind('\n') {
+            Some(newline) => &self.output[newline + 1..],
+            None => &self.output,
+        };
+        line.chars().count()
+    }
+
+    fn fits(&self, text: &str) -> bool {
+        self.column().saturating_add(text.chars().count()) <= self.style.line_width
+    }
+
+    fn at_line_start(&self) -> bool {
+        let text = self.output.trim_end_matches(' ');
+        text.is_empty() || text.ends_with('\n')
+    }
+
+    /// Opens a block or definition body and starts its first line. A brace that begins
+    /// a line, as for a block on its own, stays where it is in either style.
+    fn open_brace(&mut self) {
+        if self.at_line_start() {
+            self.push("{\n");
+            return;
+        }
+        match self.style.brace_style {
+            BraceStyle::SameLine => self.push(" {\n"),
+            BraceStyle::NextLine => {
+                self.push("\n");
+                self.indentation();
+                self.push("{\n");
+            },
+        }
+    }
+
+    fn doc(&mut self, doc: &Option<String>) {
+        let Some(doc) = doc else {
+            return;
+        };
+        for line in doc.split('\n') {
+            self.indentation();
+            match line.is_empty() {
+                true => self.push("///\n"),
+                false => self.push(&format!("/// {}\n", line)),
+            }
+        }
+    }
+
+    fn header(&mut self, header: &Header) {
+        let mut previous: Option<&HeaderStatement> = None;
+        for statement in &header.statements {
+            if previous.is_some_and(|previous| is_block(previous) || is_block(statement)) {
+                self.push("\n");
+            }
+            self.header_statement(statement);
+            previous = Some(statement);
+        }
+    }
+
+    fn header_statement(&mut self, statement: &HeaderStatement) {
+        match statement {
+            HeaderStatement::Preprocessor(preprocessor) => self.preprocessor(preprocessor),
+            HeaderStatement::Struct(struct_) => {
+                self.record_declaration("struct", &struct_.name, &struct_.attributes, &struct_.members, &struct_.doc);
+            },
+            HeaderStatement::Union(union_) => {
+                self.record_declaration("union", &union_.name, &union_.attributes, &union_.members, &union_.doc);
+            },
+            HeaderStatement::Enum(enum_) => {
+                self.doc(&enum_.doc);
+                self.indentation();
+                self.enum_(enum_);
+                self.push(";\n");
+            },
+            HeaderStatement::Variable(variable_list) => self.variable_list_declaration(variable_list),
+            HeaderStatement::FunctionPrototype(prototype) => self.prototype(prototype),
+            HeaderStatement::Function(function) => self.function(function),
+            HeaderStatement::Class(class) => self.class(class),
+            HeaderStatement::TaggedUnion(tagged) => {
+                self.doc(&tagged.doc);
+                self.indentation();
+                self.tagged_union(tagged);
+                self.push(";\n");
+            },
+            HeaderStatement::StaticAssert(static_assert) => {
+                self.indentation();
+                self.static_assert(static_assert);
+                self.push("\n");
+            },
+            HeaderStatement::Whitespace => self.push("\n"),
+        }
+    }
+
+    /// Writes a directive as it was lexed, including its newline. Directives always start
+    /// a line of their own.
+    fn preprocessor(&mut self, preprocessor: &Preprocessor) {
+        let depth = std::mem::take(&mut self.depth);
+        self.indentation();
+        self.depth = depth;
+        self.push(&preprocessor.value);
+    }
+
+    /// Writes a struct or union declared on its own, as at the top level or as a member.
+    fn record_declaration(&mut self, keyword: &str, name: &Option<String>, attributes: &[Attribute], members: &[Member], doc: &Option<String>) {
+        self.doc(doc);
+        self.indentation();
+        self.record(keyword, name, attributes, members);
+        self.push(";\n");
+    }
+
+    fn record(&mut self, keyword: &str, name: &Option<String>, record_attributes: &[Attribute], members: &[Member]) {
+        self.push(keyword);
+        if !record_attributes.is_empty() {
+            self.push(" ");
+            self.push(&attributes(record_attributes));
+        }
+        if let Some(name) = name {
+            self.push(" ");
+            self.push(name);
+        }
+        self.open_brace();
+        self.depth += 1;
+        for member in members {
+            self.member(member);
+        }
+        self.depth -= 1;
+        self.indentation();
+        self.push("}");
+    }
+
+    fn member(&mut self, member: &Member) {
+        match member {
+            Member::Variables(variable_list) => self.variable_list_declaration(variable_list),
+            Member::BitFields(bit_fields) => {
+                self.doc(&bit_fields.doc);
+                self.indentation();
+                self.specifiers(&bit_fields.specifiers, false);
+                for (i, field) in bit_fields.fields.iter().enumerate() {
+                    self.push(if i == 0 { " " } else { ", " });
+                    if let Some(name) = &field.name {
+                        self.push(name);
+                        self.push(" ");
+                    }
+                    self.push(": ");
+                    self.expression(&field.width, Level::Conditional);
+                }
+                self.push(";\n");
+            },
+            Member::Struct(struct_) => {
+                self.record_declaration("struct", &struct_.name, &struct_.attributes, &struct_.members, &struct_.doc);
+            },
+            Member::Union(union_) => {
+                self.record_declaration("union", &union_.name, &union_.attributes, &union_.members, &union_.doc);
+            },
+        }
+    }
+
+    fn enum_(&mut self, enum_: &Enum) {
+        self.push("enum");
+        if !enum_.attributes.is_empty() {
+            self.push(" ");
+            self.push(&attributes(&enum_.attributes));
+        }
+        if let Some(name) = &enum_.name {
+            self.push(" ");
+            self.push(name);
+        }
+        self.open_brace();
+        self.depth += 1;
+        for member in &enum_.members {
+            self.enum_member(member);
+        }
+        self.depth -= 1;
+        self.indentation();
+        self.push("}");
+    }
+
+    fn enum_member(&mut self, member: &EnumMember) {
+        self.doc(&member.doc);
+        self.indentation();
+        self.push(&member.name);
+        if !member.attributes.is_empty() {
+            self.push(" ");
+            self.push(&attributes(&member.attributes));
+        }
+        if let Some(value) = &member.value {
+            self.push(" = ");
+            self.expression(value, Level::Conditional);
+        }
+        self.push(",\n");
+    }
+
+    fn tagged_union(&mut self, tagged: &TaggedUnion) {
+        self.push("tagged");
+        if !tagged.attributes.is_empty() {
+            self.push(" ");
+            self.push(&attributes(&tagged.attributes));
+        }
+        self.push(" ");
+        self.push(&tagged.name);
+        self.open_brace();
+        self.depth += 1;
+        for member in &tagged.members {
+            self.doc(&member.doc);
+            self.indentation();
+            self.push(&member.name);
+            if let Some(members) = &member.value {
+                self.open_brace();
+                self.depth += 1;
+                for member in members {
+                    self.member(member);
+                }
+                self.depth -= 1;
+                self.indentation();
+                self.push("}");
+            }
+            self.push(",\n");
+        }
+        self.depth -= 1;
+        self.indentation();
+        self.push("}");
+    }
+
+    fn definition(&mut self, definition: &Definition) {
+        match definition {
+            Definition::Struct(struct_) => self.record("struct", &struct_.name, &struct_.attributes, &struct_.members),
+            Definition::Union(union_) => self.record("union", &union_.name, &union_.attributes, &union_.members),
+            Definition::Enum(enum_) => self.enum_(enum_),
+            Definition::TaggedUnion(tagged) => self.tagged_union(tagged),
+        }
+    }
+
+    /// Writes declaration specifiers in a fixed order. Attributes go in front, or after the
+    /// type where a declaration cannot start with them, as in the initializer of a `for`.
+    fn specifiers(&mut self, specifiers: &DeclarationSpecifiers, attributes_last: bool) {
+        let attributes_last = attributes_last && specifiers.definition.is_none();
+        let mut words = Vec::new();
+        if !attributes_last && !specifiers.attributes.is_empty() {
+            words.push(attributes(&specifiers.attributes));
+        }
+        if let Some(storage) = specifiers.storage {
+            words.push(storage_class(storage).to_string());
+        }
+        for (set, keyword) in [(specifiers.thread_local, "thread_local"), (specifiers.constexpr, "constexpr"),
+                               (specifiers.inline, "inline"), (specifiers.noreturn, "noreturn")] {
+            if set {
+                words.push(keyword.to_string());
+            }
+        }
+        for operand in &specifiers.alignas {
+            words.push(format!("alignas({})", self.render(|printer| printer.type_or_expression(operand))));
+        }
+        if !specifiers.type_.qualifiers.is_empty() {
+            words.push(qualifiers(&specifiers.type_.qualifiers));
+        }
+        match &specifiers.definition {
+            Some(definition) => {
+                words.push(self.render(|printer| printer.definition(definition)));
+            },
+            None => {
+                words.push(self.type_specifier(&specifiers.type_.specifier));
+            },
+        }
+        if attributes_last && !specifiers.attributes.is_empty() {
+            words.push(attributes(&specifiers.attributes));
+        }
+        self.push(&words.join(" "));
+    }
+
+    fn type_specifier(&self, specifier: &TypeSpecifier) -> String {
+        let tag = |keyword: &str, name: &Option<String>| match name {
+            Some(name) => format!("{} {}", keyword, name),
+            None => keyword.to_string(),
+        };
+        match specifier {
+            TypeSpecifier::Void => "void".to_string(),
+            TypeSpecifier::Bool => "bool".to_string(),
+            TypeSpecifier::Char => "char".to_string(),
+            TypeSpecifier::SignedChar => "signed char".to_string(),
+            TypeSpecifier::UnsignedChar => "unsigned char".to_string(),
+            TypeSpecifier::Short => "short".to_string(),
+            TypeSpecifier::UnsignedShort => "unsigned short".to_string(),
+            TypeSpecifier::Int => "int".to_string(),
+            TypeSpecifier::UnsignedInt => "unsigned int".to_string(),
+            TypeSpecifier::Long => "long".to_string(),
+            TypeSpecifier::UnsignedLong => "unsigned long".to_string(),
+            TypeSpecifier::LongLong => "long long".to_string(),
+            TypeSpecifier::UnsignedLongLong => "unsigned long long".to_string(),
+            TypeSpecifier::Float => "float".to_string(),
+            TypeSpecifier::Double => "double".to_string(),
+            TypeSpecifier::LongDouble => "long double".to_string(),
+            TypeSpecifier::FloatComplex => "float complex".to_string(),
+            TypeSpecifier::DoubleComplex => "double complex".to_string(),
+            TypeSpecifier::LongDoubleComplex => "long double complex".to_string(),
+            TypeSpecifier::FloatImaginary => "float imaginary".to_string(),
+            TypeSpecifier::DoubleImaginary => "double imaginary".to_string(),
+            TypeSpecifier::LongDoubleImaginary => "long double imaginary".to_string(),
+            TypeSpecifier::Struct(name) => tag("struct", name),
+            TypeSpecifier::Union(name) => tag("union", name),
+            TypeSpecifier::Enum(name) => tag("enum", name),
+            TypeSpecifier::Tagged(name) => format!("tagged {}", name),
+            TypeSpecifier::Named(name) => name.clone(),
+            TypeSpecifier::Generic => "generic".to_string(),
+            TypeSpecifier::Typeof(operand) => {
+                format!("typeof({})", self.render(|printer| printer.type_or_expression(operand)))
+            },
+            TypeSpecifier::TypeofUnqual(operand) => {
+                format!("typeof_unqual({})", self.render(|printer| printer.type_or_expression(operand)))
+            },
+        }
+    }
+
+    /// The qualifiers and specifier of a type without any declarator.
+    fn base_type(&self, type_: &Type) -> String {
+        let specifier = self.type_specifier(&type_.specifier);
+        match type_.qualifiers.is_empty() {
+            true => specifier,
+            false => format!("{} {}", qualifiers(&type_.qualifiers), specifier),
+        }
+    }
+
+    /// A type name as written in a cast or `sizeof`, such as `int (*)(void)`.
+    fn type_name(&self, type_: &DeclaredType) -> String {
+        let base = self.base_type(type_.base());
+        let declarator = self.declarator(type_, String::new());
+        match declarator.is_empty() {
+            true => base,
+            false => format!("{} {}", base, declarator),
+        }
+    }
+
+    /// Builds the declarator of a type around `inner`, the name or the part of the
+    /// declarator built so far. Pointers to arrays and functions are parenthesized.
+    fn declarator(&self, type_: &DeclaredType, inner: String) -> String {
+        let group = self.style.parenthesize && !inner.is_empty();
+        self.grouped_declarator(type_, inner, group)
+    }
+
+    /// Builds a declarator like `declarator`, putting every part of it other than the
+    /// name in parentheses if `group` is set. Abstract declarators are never grouped.
+    fn grouped_declarator(&self, type_: &DeclaredType, inner: String, group: bool) -> String {
+        let grouped = |inner: String| match group && !inner.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            true => format!("({})", inner),
+            false => inner,
+        };
+        match type_ {
+            DeclaredType::Base(_) => inner,
+            DeclaredType::Pointer(pointee, pointer_qualifiers) => {
+                let mut declarator = format!("*{}", qualifiers(pointer_qualifiers));
+                if !pointer_qualifiers.is_empty() && !inner.is_empty() {
+                    declarator.push(' ');
+                }
+                declarator.push_str(&grouped(inner));
+                if matches!(pointee.as_ref(), DeclaredType::Array(..) | DeclaredType::Function(..)) && !group {
+                    declarator = format!("({})", declarator);
+                }
+                self.grouped_declarator(pointee, declarator, group)
+            },
+            DeclaredType::Array(element, size) => {
+                let size = self.array_size(size);
+                self.grouped_declarator(element, format!("{}[{}]", grouped(inner), size), group)
+            },
+            DeclaredType::Function(return_type, arguments) => {
+                let arguments = self.arguments(arguments);
+                self.grouped_declarator(return_type, format!("{}({})", grouped(inner), arguments), group)
+            },
+        }
+    }
+
+    fn array_size(&self, size: &VariableArray) -> String {
+        match size {
+            VariableArray::Size(size) => self.render(|printer| printer.expression(size, Level::Assignment)),
+            VariableArray::NoSize => String::new(),
+        }
+    }
+
+    fn arguments(&self, arguments: &[FunctionArgument]) -> String {
+        arguments.iter().map(|argument| match argument {
+            FunctionArgument::Parameter(parameter) => self.parameter(parameter),
+            FunctionArgument::Ellipsis => "...".to_string(),
+        }).collect::<Vec<String>>().join(", ")
+    }
+
+    /// A parameter, with its attributes after the declarator where the parser reads the last of them.
+    fn parameter(&self, parameter: &Parameter) -> String {
+        let mut text = String::new();
+        if parameter.register {
+            text.push_str("register ");
+        }
+        text.push_str(&self.base_type(parameter.type_.base()));
+        let declarator = self.declarator(&parameter.type_, parameter.name.clone().unwrap_or_default());
+        if !declarator.is_empty() {
+            text.push(' ');
+            text.push_str(&declarator);
+        }
+        if !parameter.attributes.is_empty() {
+            text.push(' ');
+            text.push_str(&attributes(&parameter.attributes));
+        }
+        text
+    }
+
+    fn type_or_expression(&mut self, operand: &TypeOrExpression) {
+        match operand {
+            TypeOrExpression::Type(type_) => {
+                let type_ = self.type_name(type_);
+                self.push(&type_);
+            },
+            TypeOrExpression::Expression(expression) => self.expression(expression, Level::Comma),
+        }
+    }
+
+    fn variable_list_declaration(&mut self, variable_list: &VariableList) {
+        self.doc(&variable_list.doc);
+        self.indentation();
+        self.variable_list(variable_list, false);
+        self.push("\n");
+    }
+
+    fn variable_list(&mut self, variable_list: &VariableList, attributes_last: bool) {
+        self.specifiers(&variable_list.specifiers, attributes_last);
+        for (i, variable) in variable_list.variables.iter().enumerate() {
+            self.push(if i == 0 { " " } else { ", " });
+            self.variable(variable);
+        }
+        self.push(";");
+    }
+
+    fn variable(&mut self, variable: &Variable) {
+        let declarator = self.declarator(&variable.type_, variable.name.clone());
+        self.push(&declarator);
+        if !variable.attributes.is_empty() {
+            self.push(" ");
+            self.push(&attributes(&variable.attributes));
+        }
+        if let Some(value) = &variable.value {
+            self.push(" = ");
+            self.variable_value(value);
+        }
+    }
+
+    fn variable_value(&mut self, value: &VariableValue) {
+        match value {
+            VariableValue::Expression(expression) => self.expression(expression, Level::Assignment),
+            VariableValue::String(string) => self.push(&string.raw),
+        }
+    }
+
+    /// Writes the specifiers and declarator of a function, up to its body or semicolon.
+    /// Parameters that do not fit on the line go one to a line.
+    fn function_head(&mut self, specifiers: &DeclarationSpecifiers, return_type: &DeclaredType, name: &str, arguments: &[FunctionArgument]) {
+        self.specifiers(specifiers, false);
+        self.push(" ");
+        let function = DeclaredType::Function(Box::new(return_type.clone()), arguments.to_vec());
+        let declarator = self.declarator(&function, name.to_string());
+        if arguments.is_empty() || self.fits(&declarator) {
+            self.push(&declarator);
+            return;
+        }
+
+        let inner = self.render(|printer| {
+            printer.push(name);
+            printer.push("(\n");
+            printer.depth += 1;
+            for (i, argument) in arguments.iter().enumerate() {
+                printer.indentation();
+                let argument = printer.arguments(std::slice::from_ref(argument));
+                printer.push(&argument);
+                printer.push(if i + 1 < arguments.len() { ",\n" } else { "\n" });
+            }
+            printer.depth -= 1;
+            printer.indentation();
+            printer.push(")");
+        });
+        let declarator = self.declarator(return_type, inner);
+        self.push(&declarator);
+    }
+
+    fn prototype(&mut self, prototype: &FunctionPrototype) {
+        self.doc(&prototype.doc);
+        self.indentation();
+        self.function_head(&prototype.specifiers, &prototype.return_type, &prototype.name, &prototype.arguments);
+        self.push(";\n");
+    }
+
+    fn function(&mut self, function: &Function) {
+        self.doc(&function.doc);
+        self.indentation();
+        self.function_head(&function.specifiers, &function.return_type, &function.name, &function.arguments);
+        self.block(&function.body);
+        self.push("\n");
+    }
+
+    fn class(&mut self, class: &Class) {
+        self.doc(&class.doc);
+        self.indentation();
+        if class.abstract_ {
+            self.push("abstract ");
+        }
+        self.push("class");
+        if !class.attributes.is_empty() {
+            self.push(" ");
+            self.push(&attributes(&class.attributes));
+        }
+        self.push(" ");
+        self.push(&class.name);
+        if let Some(parent) = &class.parent {
+            self.push(" : ");
+            self.push(parent);
+        }
+        self.open_brace();
+        self.depth += 1;
+        let mut previous: Option<&ClassMember> = None;
+        for member in &class.members {
+            if previous.is_some_and(|previous| is_block_member(previous) || is_block_member(member)) {
+                self.push("\n");
+            }
+            self.class_member(member);
+            previous = Some(member);
+        }
+        self.depth -= 1;
+        self.indentation();
+        self.push("};\n");
+    }
+
+    fn class_member(&mut self, member: &ClassMember) {
+        match member {
+            ClassMember::Field(member) => self.member(member),
+            ClassMember::Method(Method::Normal(function)) => self.function(function),
+            ClassMember::Method(Method::Abstract(prototype)) => self.prototype(prototype),
+            ClassMember::OperatorOverload(operator) => self.operator(operator),
+        }
+    }
+
+    /// Writes an operator overload such as `Vec operator(+)(Vec other) { ... }`. Its return
+    /// type can only add pointers to the specifiers.
+    fn operator(&mut self, operator: &OperatorOverload) {
+        let (operator_attributes, return_type, op, arguments, body, doc) = match operator {
+            OperatorOverload::Normal {attributes, return_type, op, arguments, body, doc} => {
+                (attributes, return_type, op, arguments, Some(body), doc)
+            },
+            OperatorOverload::Abstract {attributes, return_type, op, arguments, doc} => {
+                (attributes, return_type, op, arguments, None, doc)
+            },
+        };
+        self.doc(doc);
+        self.indentation();
+        if !operator_attributes.is_empty() {
+            self.push(&attributes(operator_attributes));
+            self.push(" ");
+        }
+        let base = self.base_type(return_type.base());
+        // The operator and its arguments are one piece that cannot be parenthesized.
+        let declarator = self.grouped_declarator(return_type, format!("operator({})({})", op, self.arguments(arguments)), false);
+        self.push(&format!("{} {}", base, declarator));
+        match body {
+            Some(body) => {
+                self.block(body);
+                self.push("\n");
+            },
+            None => self.push(";\n"),
+        }
+    }
+
+    fn static_assert(&mut self, static_assert: &StaticAssert) {
+        self.push("static_assert(");
+        self.expression(&static_assert.condition, Level::Conditional);
+        if let Some(message) = &static_assert.message {
+            self.push(", ");
+            self.push(&message.raw);
+        }
+        self.push(");");
+    }
+
+    /// Writes a block from its opening brace to its closing one, leaving the line open.
+    /// Empty blocks stay on the line that opens them.
+    fn block(&mut self, block: &CodeBlock) {
+        let CodeBlock::Code(list) = block;
+        if list.statements.is_empty() {
+            if !self.at_line_start() {
+                self.push(" ");
+            }
+            self.push("{}");
+            return;
+        }
+        self.open_brace();
+        self.depth += 1;
+        self.statements(&list.statements);
+        self.depth -= 1;
+        self.indentation();
+        self.push("}");
+    }
+
+    fn statements(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    /// Writes a statement on lines of its own.
+    fn statement(&mut self, statement: &Statement) {
+        if let Statement::Preprocessor(preprocessor) = statement {
+            self.preprocessor(preprocessor);
+            return;
+        }
+        self.indentation();
+        self.statement_inline(statement);
+        self.push("\n");
+    }
+
+    /// Writes a statement after the indentation of the current line, leaving the line open.
+    fn statement_inline(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Preprocessor(preprocessor) => self.push(preprocessor.value.trim_end()),
+            Statement::VariableList(variable_list) => self.variable_list(variable_list, false),
+            Statement::Expression(expression) => {
+                self.expression(expression, Level::Comma);
+                self.push(";");
+            },
+            Statement::Return(Expression::Blank) => self.push("return;"),
+            Statement::Return(expression) => {
+                self.push("return ");
+                self.expression(expression, Level::Comma);
+                self.push(";");
+            },
+            Statement::If(condition, body, else_) => {
+                self.push("if (");
+                self.expression(condition, Level::Comma);
+                self.push(")");
+                self.body(body);
+                // The else goes on its own line at the depth of its if.
+                match else_.as_deref() {
+                    Some(BlockOrStatement::Statement(statement @ Statement::If(..))) => {
+                        self.indentation();
+                        self.push("else ");
+                        self.statement_inline(statement);
+                    },
+                    Some(body) => {
+                        self.indentation();
+                        self.push("else");
+                        self.body(body);
+                    },
+                    None => {},
+                }
+            },
+            Statement::While(condition, body) => {
+                self.push("while (");
+                self.expression(condition, Level::Comma);
+                self.push(")");
+                self.body(body);
+            },
+            Statement::DoWhile(condition, body) => {
+                self.push("do");
+                self.body(body);
+                match body.as_ref() {
+                    BlockOrStatement::Block(_) => self.push(" "),
+                    BlockOrStatement::Statement(_) => {
+                        self.push("\n");
+                        self.indentation();
+                    },
+                }
+                self.push("while (");
+                self.expression(condition, Level::Comma);
+                self.push(");");
+            },
+            Statement::For(initializer, condition, step, body) => {
+                self.push("for (");
+                match initializer.as_deref() {
+                    Some(VariableListOrStatement::VariableList(variable_list)) => self.variable_list(variable_list, true),
+                    Some(VariableListOrStatement::Statement(statement)) => self.statement_inline(statement),
+                    None => self.push(";"),
+                }
+                if let Some(condition) = condition {
+                    self.push(" ");
+                    self.expression(condition, Level::Comma);
+                }
+                self.push(";");
+                if let Some(step) = step {
+                    self.push(" ");
+                    self.expression(step, Level::Comma);
+                }
+                self.push(")");
+                self.body(body);
+            },
+            Statement::Switch(condition, cases) => {
+                self.push("switch (");
+                self.expression(condition, Level::Comma);
+                self.push(")");
+                self.open_brace();
+                self.depth += 1;
+                for case in cases {
+                    self.switch_case(case);
+                }
+                self.depth -= 1;
+                self.indentation();
+                self.push("}");
+            },
+            Statement::StaticAssert(static_assert) => self.static_assert(static_assert),
+            Statement::Attributes(statement_attributes) => {
+                self.push(&attributes(statement_attributes));
+                self.push(";");
+            },
+            Statement::Attributed(statement_attributes, statement) => {
+                self.push(&attributes(statement_attributes));
+                self.push(" ");
+                self.statement_inline(statement);
+            },
+            Statement::Break => self.push("break;"),
+            Statement::Continue => self.push("continue;"),
+            Statement::Goto(label) => self.push(&format!("goto {};", label)),
+            Statement::Label(label) => self.push(&format!("{}:", label)),
+            Statement::Block(block) => self.block(block),
+        }
+    }
+
+    /// Writes the body of a control statement after its head: a block on the same line,
+    /// or a single statement indented on the next.
+    fn body(&mut self, body: &BlockOrStatement) {
+        match body {
+            BlockOrStatement::Block(block) => self.block(block),
+            BlockOrStatement::Statement(statement) => {
+                self.push("\n");
+                self.depth += 1;
+                self.indentation();
+                self.statement_inline(statement);
+                self.depth -= 1;
+            },
+        }
+    }
+
+    /// Writes a case label and the statements under it, which the parser gathers up to the next label.
+    fn switch_case(&mut self, case: &SwitchCase) {
+        self.indentation();
+        match &case.expression {
+            Some(expression) => {
+                self.push("case ");
+                self.expression(expression, Level::Conditional);
+                self.push(":");
+            },
+            None => self.push("default:"),
+        }
+        if let BlockOrStatement::Statement(Statement::Block(block)) = case.body.as_ref() {
+            self.block(block);
+            self.push("\n");
+            return;
+        }
+        self.push("\n");
+        self.depth += 1;
+        match case.body.as_ref() {
+            BlockOrStatement::Block(CodeBlock::Code(list)) => self.statements(&list.statements),
+            BlockOrStatement::Statement(statement) => self.statement(statement),
+        }
+        self.depth -= 1;
+    }
+
+    fn literal(&mut self, literal: &Literal) {
+        match literal {
+            Literal::Number(number) => self.push(&number.raw),
+            Literal::Char(character) => self.push(&character.raw),
+            Literal::String(string) => self.push(&string.raw),
+            Literal::Bool(true) => self.push("true"),
+            Literal::Bool(false) => self.push("false"),
+            Literal::Nullptr => self.push("nullptr"),
+        }
+    }
+
+    /// Writes an operand of an operator where the grammar expects `level`. With
+    /// `Style::parenthesize` it is put in parentheses unless it is a primary expression.
+    fn operand(&mut self, expression: &Expression, level: Level) {
+        if self.style.parenthesize && expression_level(expression) < Level::Primary {
+            self.push("(");
+            self.expression(expression, Level::Comma);
+            self.push(")");
+        }
+        else {
+            self.expression(expression, level);
+        }
+    }
+
+    /// Writes an expression where the grammar expects `level`, in parentheses if it binds more loosely.
+    fn expression(&mut self, expression: &Expression, level: Level) {
+        if let Expression::Expression(inner) = expression {
+            return self.expression(inner, level);
+        }
+        if expression_level(expression) < level {
+            self.push("(");
+            self.expression(expression, Level::Comma);
+            self.push(")");
+            return;
+        }
+
+        match expression {
+            Expression::Blank => {},
+            Expression::Identifier(name) => self.push(name),
+            Expression::Literal(literal) => self.literal(literal),
+            Expression::Sizeof(TypeOrExpression::Type(type_)) => {
+                let type_ = self.type_name(type_);
+                self.push(&format!("sizeof({})", type_));
+            },
+            Expression::Sizeof(TypeOrExpression::Expression(operand)) => {
+                self.push("sizeof ");
+                // `sizeof (int)x` would be read as the size of the type.
+                let level = match operand.as_ref() {
+                    Expression::Unary(UnaryOperator::Cast(_), _) => Level::Primary,
+                    _ => Level::Unary,
+                };
+                self.operand(operand, level);
+            },
+            Expression::Alignof(type_) => {
+                let type_ = self.type_name(type_);
+                self.push(&format!("alignof({})", type_));
+            },
+            Expression::Offsetof(type_, member) => {
+                let type_ = self.type_name(type_);
+                self.push(&format!("offsetof({}, {})", type_, member));
+            },
+            Expression::Unary(UnaryOperator::Cast(type_), operand) => {
+                let type_ = self.type_name(type_);
+                self.push(&format!("({})", type_));
+                self.operand(operand, Level::Unary);
+            },
+            Expression::Unary(operator @ (UnaryOperator::PostIncrement | UnaryOperator::PostDecrement), operand) => {
+                self.operand(operand, Level::Postfix);
+                self.push(unary_operator(operator));
+            },
+            Expression::Unary(UnaryOperator::MemberSet, member) => {
+                self.push(".");
+                self.expression(member, Level::Primary);
+            },
+            Expression::Unary(operator, operand) => {
+                let spelling = unary_operator(operator);
+                self.push(spelling);
+                let start = self.output.len();
+                self.operand(operand, Level::Unary);
+                // Keep `- -x` and `& &x` from being lexed as `--x` and `&&x`.
+                let last = spelling.chars().last();
+                if last.is_some_and(|last| "+-&".contains(last)) && self.output[start..].starts_with(last.unwrap()) {
+                    self.output.insert(start, ' ');
+                }
+            },
+            Expression::Binary(BinaryOperator::Comma, left, right) => {
+                self.operand(left, Level::Comma);
+                self.push(", ");
+                self.operand(right, Level::Assignment);
+            },
+            Expression::Binary(operator @ (BinaryOperator::MemberAccess | BinaryOperator::PointerMemberAccess), object, member) => {
+                self.operand(object, Level::Postfix);
+                self.push(binary_operator(operator).0);
+                self.expression(member, Level::Postfix);
+            },
+            Expression::Binary(BinaryOperator::Index | BinaryOperator::ArrayAccess, array, index) => {
+                self.operand(array, Level::Postfix);
+                self.push("[");
+                self.expression(index, Level::Comma);
+                self.push("]");
+            },
+            Expression::Binary(operator, left, right) => {
+                let (spelling, precedence) = binary_operator(operator);
+                let (left_level, right_level) = match precedence {
+                    Some(precedence) => (Level::Binary(precedence), Level::Binary(precedence + 1)),
+                    None => (Level::Conditional, Level::Assignment),
+                };
+                self.operand(left, left_level);
+                self.push(&format!(" {} ", spelling));
+                self.operand(right, right_level);
+            },
+            Expression::Ternary(condition, then, otherwise) => {
+                self.operand(condition, Level::Binary(1));
+                self.push(" ? ");
+                self.operand(then, Level::Comma);
+                self.push(" : ");
+                self.operand(otherwise, Level::Conditional);
+            },
+            Expression::CallFunction(name, arguments) => {
+                self.push(name);
+                self.call_arguments(arguments);
+            },
+            Expression::Call(callee, arguments) => {
+                self.operand(callee, Level::Postfix);
+                self.call_arguments(arguments);
+            },
+            Expression::Generic(control, associations) => {
+                self.push("_Generic(");
+                self.expression(control, Level::Assignment);
+                for association in associations {
+                    self.push(", ");
+                    self.generic_association(association);
+                }
+                self.push(")");
+            },
+            Expression::InitializerList(elements) => self.list("{", elements, "}"),
+            Expression::TaggedInitializer(name, value) => {
+                self.push(name);
+                self.push(" {");
+                self.expression(value, Level::Comma);
+                self.push("}");
+            },
+            Expression::StatementList(list) => {
+                // A GNU statement expression, which the parser does not read back.
+                self.push("({ ");
+                for statement in &list.statements {
+                    self.statement_inline(statement);
+                    self.push(" ");
+                }
+                self.push("})");
+            },
+            Expression::Expression(_) => unreachable!("unwrapped above"),
+            Expression::Parentheses(inner) => {
+                self.push("(");
+                self.expression(inner, Level::Comma);
+                self.push(")");
+            },
+        }
+    }
+
+    fn call_arguments(&mut self, arguments: &Option<Box<Expression>>) {
+        match arguments {
+            Some(arguments) => self.list("(", arguments, ")"),
+            None => self.push("()"),
+        }
+    }
+
+    /// Writes the comma separated items of a call or initializer list on one line if they
+    /// fit, or else one to a line, indented under the line that opens the list.
+    fn list(&mut self, open: &str, items: &Expression, close: &str) {
+        let flat = self.flat(|printer| {
+            printer.push(open);
+            printer.expression(items, Level::Comma);
+            printer.push(close);
+        });
+        if *items == Expression::Blank || self.fits(&flat) {
+            self.push(open);
+            self.expression(items, Level::Comma);
+            self.push(close);
+            return;
+        }
+
+        let mut list = Vec::new();
+        let mut rest = items;
+        while let Expression::Binary(BinaryOperator::Comma, left, right) = rest {
+            list.push(right.as_ref());
+            rest = left;
+        }
+        list.push(rest);
+        self.push(open);
+        self.push("\n");
+        self.depth += 1;
+        for (i, item) in list.iter().rev().enumerate() {
+            self.indentation();
+            self.expression(item, Level::Assignment);
+            self.push(if i + 1 < list.len() { ",\n" } else { "\n" });
+        }
+        self.depth -= 1;
+        self.indentation();
+        self.push(close);
+    }
+
+    fn generic_association(&mut self, association: &GenericAssociation) {
+        match &association.type_ {
+            Some(type_) => {
+                let type_ = self.type_name(type_);
+                self.push(&type_);
+            },
+            None => self.push("default"),
+        }
+        self.push(": ");
+        self.expression(&association.expression, Level::Assignment);
+    }
+
+    fn initializer(&mut self, initializer: &Initializer) {
+        match initializer {
+            Initializer::Expression(expression) => self.expression(expression, Level::Assignment),
+            Initializer::List(initializers) => {
+                self.push("{");
+                for (i, initializer) in initializers.iter().enumerate() {
+                    if i > 0 {
+                        self.push(", ");
+                    }
+                    self.initializer(initializer);
+                }
+                self.push("}");
+            },
+            Initializer::Designated(designator, initializer) => {
+                self.designator(designator);
+                self.push(" = ");
+                self.initializer(initializer);
+            },
+        }
+    }
+
+    fn designator(&mut self, designator: &Designator) {
+        match designator {
+            Designator::Member(name) => self.push(&format!(".{}", name)),
+            Designator::Index(index) => {
+                self.push("[");
+                self.expression(index, Level::Conditional);
+                self.push("]");
+            },
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod printer_tests {
+    use super::*;
+    use crate::logos_lexer::lex_with_trivia;
+    use crate::parser::Parser;
+
+    fn parse(input: &str) -> Result<Header, String> {
+        let tokens = lex_with_trivia(input).map_err(|errors| format!("{:?}", errors))?;
+        Parser::with_trivia(&tokens).parse()
+    }
+
+    /// Prints the parse of `input` and checks that the printed source parses to the same tree.
+    fn round_trip(input: &str) -> String {
+        let header = match parse(input) {
+            Ok(header) => header,
+            Err(err) => panic!("Failed to parse {}: {}", input, err),
+        };
+        let printed = print(&header);
+        match parse(&printed) {
+            Ok(reparsed) => assert_eq!(reparsed, header, "Printing {} gave\n{}", input, printed),
+            Err(err) => panic!("Failed to parse the printed form of {}: {}\n{}", input, err, printed),
+        }
+        printed
+    }
+
+    /// The string literals in the tests of a source file, as the text they stand for.
+    fn test_strings(source: &str) -> Vec<String> {
+        let tests = &source[source.find("#[cfg(test)]").unwrap_or(source.len())..];
+        let mut strings = Vec::new();
+        let mut chars = tests.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\'' => {
+                    // Skip char literals such as '"', but not lifetimes.
+                    if chars.peek() == Some(&'\\') {
+                        chars.next();
+                    }
+                    chars.next();
+                    if chars.peek() == Some(&'\'') {
+                        chars.next();
+                    }
+                },
+                '/' if chars.peek() == Some(&'/') => {
+                    for c in chars.by_ref() {
+                        if c == '\n' {
+                            break;
+                        }
+                    }
+                },
+                '"' => {
+                    let mut string = String::new();
+                    while let Some(c) = chars.next() {
+                        match c {
+                            '"' => break,
+                            '\\' => match chars.next() {
+                                Some('n') => string.push('\n'),
+                                Some('t') => string.push('\t'),
+                                Some('r') => string.push('\r'),
+                                Some('0') => string.push('\0'),
+                                Some('\n') => {
+                                    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                                        chars.next();
+                                    }
+                                },
+                                Some(c) => string.push(c),
+                                None => {},
+                            },
+                            c => string.push(c),
+                        }
+                    }
+                    strings.push(string);
+                },
+                _ => {},
+            }
+        }
+        strings
+    }
+
+    #[test]
+    fn test_round_trip_program() {
+        let input = "#include <stdio.h>\n/// A point\n/// in space.\nstruct [[gnu::packed]] point { int x, y; unsigned flags : 3, : 2; struct { int a; }; };\n\
+                     enum color { RED, GREEN [[deprecated]] = 5, BLUE = GREEN + 1 };\n\
+                     tagged Shape { Circle { double r; }, /// No payload.\nEmpty };\n\
+                     typedef struct { int x; } pair;\ntypedef int (*handler)(int, char **);\nint (*table[4])(void);\n\
+                     static const char *const names[] = {\"a\" \"b\", \"c\"};\nthread_local static alignas(16) int z;\n\
+                     abstract class Vec : Base {\n    int x;\n    /// Adds.\n    Vec operator(+)(Vec other) { return Vec {x + other.x}; }\n\
+                     Vec *const operator(-)(Vec other);\n    int get(void) { return this->x; }\n    int set(int v);\n};\n\
+                     int f(int n [[maybe_unused]], ...) {\n    int i;\n    for (i = 0; i < n; i++) { if (i == 2) continue; else if (i == 3) break; else n--; }\n\
+                     for (int [[maybe_unused]] j = 0;;) break;\n\
+                     switch (n) { case 1: case 2: n += 1; [[fallthrough]]; default: { n = 0; } }\n    do n--; while (n > 0);\n\
+                     x = -(-n) + - -n + *&*p + (a, b) + (a = b) + (a ? b : c) * sizeof(int) + sizeof n + alignof(long) + offsetof(struct point, y);\n\
+                     y = (int)(char)x + ((struct point){1, 2}).x + _Generic(x, int: 1, default: 2) + s.get() + t->u[i + 1]++;\n\
+                     end:\n    goto end;\n    return (n);\n}\nstatic_assert(sizeof(int) == 4, \"int\");\n#endif";
+        round_trip(input);
+    }
+
+    #[test]
+    fn test_print_declarators() {
+        assert_eq!(round_trip("int (*(*f)(int))[3];"), "int (*(*f)(int))[3];\n");
+        assert_eq!(round_trip("char * const*p;"), "char *const *p;\n");
+        assert_eq!(round_trip("void (*signal(int sig, void (*)(int)))(int);"), "void (*signal(int sig, void (*)(int)))(int);\n");
+        assert_eq!(round_trip("long unsigned x [[maybe_unused]] = sizeof (int [2]);"),
+                   "unsigned long x [[maybe_unused]] = sizeof(int [2]);\n");
+        assert_eq!(round_trip("int main() { for(int i=0;i<3;i++) if (i) {} else i--; }"),
+                   "int main() {\n    for (int i = 0; i < 3; i++)\n        if (i) {}\n        else\n            i--;\n}\n");
+        assert_eq!(round_trip("void f() { if (a) if (b) x(); else y(); }"),
+                   "void f() {\n    if (a)\n        if (b)\n            x();\n        else\n            y();\n}\n");
+    }
+
+    #[test]
+    fn test_print_parentheses() {
+        let name = |name: &str| Box::new(Expression::Identifier(name.to_string()));
+        let binary = |operator, left, right| Box::new(Expression::Binary(operator, left, right));
+        let cases = [
+            (binary(BinaryOperator::Multiply, binary(BinaryOperator::Add, name("a"), name("b")), name("c")), "(a + b) * c"),
+            (binary(BinaryOperator::Subtract, name("a"), binary(BinaryOperator::Subtract, name("b"), name("c"))), "a - (b - c)"),
+            (binary(BinaryOperator::Subtract, binary(BinaryOperator::Subtract, name("a"), name("b")), name("c")), "a - b - c"),
+            (binary(BinaryOperator::Assign, name("a"), binary(BinaryOperator::Assign, name("b"), name("c"))), "a = b = c"),
+            (binary(BinaryOperator::Add, name("a"), binary(BinaryOperator::Comma, name("b"), name("c"))), "a + (b, c)"),
+            (Box::new(Expression::Unary(UnaryOperator::Minus, Box::new(Expression::Unary(UnaryOperator::PreDecrement, name("x"))))), "- --x"),
+            (Box::new(Expression::Unary(UnaryOperator::AddressOf, Box::new(Expression::Unary(UnaryOperator::AddressOf, name("x"))))), "& &x"),
+            (Box::new(Expression::Unary(UnaryOperator::Dereference, binary(BinaryOperator::Add, name("p"), name("i")))), "*(p + i)"),
+            (binary(BinaryOperator::PointerMemberAccess, Box::new(Expression::Unary(UnaryOperator::Dereference, name("p"))), name("x")), "(*p)->x"),
+            (Box::new(Expression::Sizeof(TypeOrExpression::Expression(Box::new(Expression::Unary(
+                UnaryOperator::Cast(Box::new(DeclaredType::Base(Type::new(TypeSpecifier::Int)))), name("x")))))), "sizeof ((int)x)"),
+        ];
+        for (expression, expected) in cases {
+            assert_eq!(print_node(&AstNode::Expression(*expression)), expected);
+        }
+    }
+
+    #[test]
+    fn test_print_parenthesized() {
+        let style = Style {parenthesize: true, ..Style::default()};
+        let cases = [
+            ("int x = a + b * c - -d;", "int x = (a + (b * c)) - (-d);\n"),
+            ("int x = a = b ? c , d : e || f;", "int x = a = (b ? (c, d) : (e || f));\n"),
+            ("int x = sizeof *p++ + (int)~y[i + 1];", "int x = (sizeof (*(p++))) + ((int)(~(y[i + 1])));\n"),
+            ("int *x[3];", "int *(x[3]);\n"),
+            ("int (*x)[3];", "int (*x)[3];\n"),
+            ("char *(*f(int))(void);", "char *((*(f(int)))(void));\n"),
+        ];
+        for (input, expected) in cases {
+            let header = parse(input).unwrap();
+            let printed = print_with_style(&header, &style);
+            assert_eq!(printed, expected);
+            // The parentheses are kept by the parser, so printing again changes nothing.
+            assert_eq!(print_with_style(&parse(&printed).unwrap(), &style), printed);
+        }
+    }
+
+    #[test]
+    fn test_round_trip_test_corpus() {
+        let sources = [
+            include_str!("attributes.rs"),
+            include_str!("constant.rs"),
+            include_str!("interpreter.rs"),
+            include_str!("layout.rs"),
+            include_str!("parser.rs"),
+            include_str!("typecheck.rs"),
+        ];
+        let cases = std::fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/cases")).unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "cwc"))
+            .map(|path| std::fs::read_to_string(path).unwrap());
+        let mut count = 0;
+        for input in sources.into_iter().flat_map(test_strings).chain(cases) {
+            if parse(&input).is_ok() {
+                round_trip(&input);
+                count += 1;
+            }
+        }
+        assert!(count > 100, "Only {} programs in the corpus", count);
+    }
+}
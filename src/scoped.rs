@@ -0,0 +1,47 @@
+use std::collections::HashMap;
+
+
+/// Names declared so far. Entries made inside a block are undone when it ends.
+#[derive(Debug, Clone)]
+pub(crate) struct ScopedMap<V> {
+    entries: HashMap<String, V>,
+    /// For each open block, the names it declared and the entries they hid.
+    blocks: Vec<Vec<(String, Option<V>)>>,
+}
+
+impl<V> Default for ScopedMap<V> {
+    fn default() -> Self {
+        Self {entries: HashMap::new(), blocks: Vec::new()}
+    }
+}
+
+impl<V> ScopedMap<V> {
+    pub(crate) fn get(&self, name: &str) -> Option<&V> {
+        self.entries.get(name)
+    }
+
+    pub(crate) fn insert(&mut self, name: String, value: V) {
+        let hidden = self.entries.insert(name.clone(), value);
+        if let Some(block) = self.blocks.last_mut() {
+            block.push((name, hidden));
+        }
+    }
+
+    /// Whether a block is open, so that new entries are local to it.
+    pub(crate) fn in_block(&self) -> bool {
+        !self.blocks.is_empty()
+    }
+
+    pub(crate) fn open(&mut self) {
+        self.blocks.push(Vec::new());
+    }
+
+    pub(crate) fn close(&mut self) {
+        for (name, hidden) in self.blocks.pop().unwrap_or_default().into_iter().rev() {
+            match hidden {
+                Some(value) => self.entries.insert(name, value),
+                None => self.entries.remove(&name),
+            };
+        }
+    }
+}
@@ -0,0 +1,371 @@
+use std::fmt;
+
+use serde::ser::{self, Serialize};
+
+
+/// Writes a value as a compact S-expression. Structs and enum variants become lists headed
+/// by their name, and struct fields become `(field value)` pairs. Fields that are `None`,
+/// `false` or empty are left out, and a variant that holds a struct of the same name, such
+/// as `HeaderStatement::Struct`, is written as just the struct.
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String, Error> {
+    let mut serializer = Serializer { output: String::new() };
+    value.serialize(&mut serializer)?;
+    Ok(serializer.output)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Error(String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        Error(message.to_string())
+    }
+}
+
+/// Whether a value written by the serializer says nothing, such as `None` or a struct with
+/// only empty fields.
+fn is_empty(value: &str) -> bool {
+    matches!(value, "nil" | "()" | "false") ||
+        value.starts_with('(') && value.ends_with(')') && !value[1..value.len() - 1].contains([' ', '(', '"'])
+}
+
+pub struct Serializer {
+    output: String,
+}
+
+impl Serializer {
+    fn atom(&mut self, atom: impl fmt::Display) -> Result<(), Error> {
+        self.output += &atom.to_string();
+        Ok(())
+    }
+
+    /// Starts a list, headed by `name` unless it is empty.
+    fn open(&mut self, name: &str) -> Result<List<'_>, Error> {
+        self.output.push('(');
+        self.output += name;
+        Ok(List { serializer: self, first: name.is_empty() })
+    }
+}
+
+/// A list being written; `first` is whether nothing has been written into it yet.
+pub struct List<'a> {
+    serializer: &'a mut Serializer,
+    first: bool,
+}
+
+impl List<'_> {
+    fn element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        if !self.first {
+            self.serializer.output.push(' ');
+        }
+        self.first = false;
+        value.serialize(&mut *self.serializer)
+    }
+
+    fn field<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) -> Result<(), Error> {
+        let value = to_string(value)?;
+        if is_empty(&value) {
+            return Ok(());
+        }
+        if !self.first {
+            self.serializer.output.push(' ');
+        }
+        self.first = false;
+        self.serializer.output += &format!("({} {})", key, value);
+        Ok(())
+    }
+
+    fn close(self) -> Result<(), Error> {
+        self.serializer.output.push(')');
+        Ok(())
+    }
+}
+
+impl<'a> ser::Serializer for &'a mut Serializer {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = List<'a>;
+    type SerializeTuple = List<'a>;
+    type SerializeTupleStruct = List<'a>;
+    type SerializeTupleVariant = List<'a>;
+    type SerializeMap = List<'a>;
+    type SerializeStruct = List<'a>;
+    type SerializeStructVariant = List<'a>;
+
+    fn serialize_bool(self, value: bool) -> Result<(), Error> {
+        self.atom(value)
+    }
+
+    fn serialize_i8(self, value: i8) -> Result<(), Error> {
+        self.atom(value)
+    }
+
+    fn serialize_i16(self, value: i16) -> Result<(), Error> {
+        self.atom(value)
+    }
+
+    fn serialize_i32(self, value: i32) -> Result<(), Error> {
+        self.atom(value)
+    }
+
+    fn serialize_i64(self, value: i64) -> Result<(), Error> {
+        self.atom(value)
+    }
+
+    fn serialize_i128(self, value: i128) -> Result<(), Error> {
+        self.atom(value)
+    }
+
+    fn serialize_u8(self, value: u8) -> Result<(), Error> {
+        self.atom(value)
+    }
+
+    fn serialize_u16(self, value: u16) -> Result<(), Error> {
+        self.atom(value)
+    }
+
+    fn serialize_u32(self, value: u32) -> Result<(), Error> {
+        self.atom(value)
+    }
+
+    fn serialize_u64(self, value: u64) -> Result<(), Error> {
+        self.atom(value)
+    }
+
+    fn serialize_u128(self, value: u128) -> Result<(), Error> {
+        self.atom(value)
+    }
+
+    fn serialize_f32(self, value: f32) -> Result<(), Error> {
+        self.atom(value)
+    }
+
+    fn serialize_f64(self, value: f64) -> Result<(), Error> {
+        self.atom(value)
+    }
+
+    fn serialize_char(self, value: char) -> Result<(), Error> {
+        self.serialize_str(value.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, value: &str) -> Result<(), Error> {
+        self.atom(format!("{:?}", value))
+    }
+
+    fn serialize_bytes(self, value: &[u8]) -> Result<(), Error> {
+        let mut list = self.open("")?;
+        for byte in value {
+            list.element(byte)?;
+        }
+        list.close()
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        self.atom("nil")
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        self.atom("()")
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<(), Error> {
+        self.atom(name)
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<(), Error> {
+        self.atom(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, name: &'static str, value: &T) -> Result<(), Error> {
+        let mut list = self.open(name)?;
+        list.element(value)?;
+        list.close()
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(self, _name: &'static str, _index: u32, variant: &'static str,
+                                                        value: &T) -> Result<(), Error> {
+        let value = to_string(value)?;
+        match value.strip_prefix('(').and_then(|value| value.strip_prefix(variant)) {
+            Some(rest) if rest.starts_with([' ', ')']) => self.atom(value),
+            _ => self.atom(format!("({} {})", variant, value)),
+        }
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<List<'a>, Error> {
+        self.open("")
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<List<'a>, Error> {
+        self.open("")
+    }
+
+    fn serialize_tuple_struct(self, name: &'static str, _len: usize) -> Result<List<'a>, Error> {
+        self.open(name)
+    }
+
+    fn serialize_tuple_variant(self, _name: &'static str, _index: u32, variant: &'static str,
+                               _len: usize) -> Result<List<'a>, Error> {
+        self.open(variant)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<List<'a>, Error> {
+        self.open("")
+    }
+
+    fn serialize_struct(self, name: &'static str, _len: usize) -> Result<List<'a>, Error> {
+        self.open(name)
+    }
+
+    fn serialize_struct_variant(self, _name: &'static str, _index: u32, variant: &'static str,
+                                _len: usize) -> Result<List<'a>, Error> {
+        self.open(variant)
+    }
+}
+
+impl ser::SerializeSeq for List<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.close()
+    }
+}
+
+impl ser::SerializeTuple for List<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.close()
+    }
+}
+
+impl ser::SerializeTupleStruct for List<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.close()
+    }
+}
+
+impl ser::SerializeTupleVariant for List<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.close()
+    }
+}
+
+impl ser::SerializeMap for List<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        if !self.first {
+            self.serializer.output.push(' ');
+        }
+        self.first = false;
+        self.serializer.output.push('(');
+        key.serialize(&mut *self.serializer)
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.serializer.output.push(' ');
+        value.serialize(&mut *self.serializer)?;
+        self.serializer.output.push(')');
+        Ok(())
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.close()
+    }
+}
+
+impl ser::SerializeStruct for List<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Error> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.close()
+    }
+}
+
+impl ser::SerializeStructVariant for List<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Error> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.close()
+    }
+}
+
+
+#[cfg(test)]
+mod sexp_tests {
+    use super::*;
+    use crate::ast::Header;
+    use crate::logos_lexer::lex;
+    use crate::parser::Parser;
+    use crate::printer::print;
+
+    const SOURCE: &str = "struct point { int x, y; };\nint main(void) { return 1 + 2 * 3; }\n";
+
+    #[test]
+    fn test_s_expressions() {
+        let header = Parser::new(&lex(SOURCE).unwrap()).parse().unwrap();
+        let sexp = to_string(&header).unwrap();
+        assert!(sexp.starts_with("(Header (statements ((Struct (name \"point\") (members ((Variables (VariableList \
+            (specifiers (DeclarationSpecifiers (type_ (Type (specifier Int))))) \
+            (variables ((Variable (name \"x\") (type_ (Base (Type (specifier Int))))) (Variable (name \"y\")"), "{}", sexp);
+        assert!(sexp.contains("(Return (Binary Add (Literal (Number (NumberLiteral (raw \"1\") (value (Integer 1)) \
+            (c_type Int)))) (Binary Multiply"), "{}", sexp);
+        assert_eq!(sexp.matches('(').count(), sexp.matches(')').count());
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let header = Parser::new(&lex(SOURCE).unwrap()).parse().unwrap();
+        let json = serde_json::to_string(&header).unwrap();
+        let read = serde_json::from_str::<Header>(&json).unwrap();
+        assert_eq!(read, header);
+        assert_eq!(print(&read), print(&header));
+    }
+}
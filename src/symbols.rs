@@ -0,0 +1,1120 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::ast::*;
+use crate::logos_lexer::{lex_with_trivia, Token, TokenWithTrivia};
+use crate::parser::Parser;
+use crate::printer::print_node;
+
+
+/// What a name declares.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SymbolKind {
+    Variable,
+    Parameter,
+    Function,
+    Typedef,
+    Struct,
+    Union,
+    Enum,
+    EnumMember,
+    TaggedUnion,
+    /// A member of a tagged union, which is both a name of its own and the field holding its payload.
+    Variant,
+    Class,
+    Field,
+    Method,
+    Label,
+}
+
+impl SymbolKind {
+    /// Whether the symbol is named after `struct`, `union`, `enum` or `tagged`.
+    fn is_tag(self) -> bool {
+        matches!(self, SymbolKind::Struct | SymbolKind::Union | SymbolKind::Enum | SymbolKind::TaggedUnion)
+    }
+
+    /// Whether the symbol is named by a plain identifier rather than a tag, a member access or a `goto`.
+    fn is_ordinary(self) -> bool {
+        !self.is_tag() && !matches!(self, SymbolKind::Field | SymbolKind::Method | SymbolKind::Label)
+    }
+}
+
+/// A declared name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    /// Empty for structs, unions and enums without a tag.
+    pub name: String,
+    pub kind: SymbolKind,
+    /// The byte range of the name where it is declared.
+    pub span: Range<usize>,
+    /// The byte range the name can be used in, which ends with the block it is declared in.
+    pub scope: Range<usize>,
+    /// Whether the symbol is declared inside a function.
+    pub local: bool,
+    /// The struct, union, enum, tagged union or class the symbol is a member of.
+    pub parent: Option<usize>,
+    /// The declared type of variables, parameters, fields, functions, methods and typedefs.
+    pub type_: Option<DeclaredType>,
+    /// The struct or union without a tag that the type of the symbol is built on.
+    pub record: Option<usize>,
+    /// The class a class inherits from.
+    pub base: Option<usize>,
+    /// The byte range of the body of a function or method.
+    pub body: Option<Range<usize>>,
+    pub doc: Option<String>,
+}
+
+/// A place in the source that names a symbol, its declaration included.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reference {
+    pub span: Range<usize>,
+    pub symbol: usize,
+}
+
+/// How a name is looked up.
+#[derive(Debug, Clone)]
+enum Role {
+    Ordinary,
+    Tag,
+    Label,
+    /// A member of the value of an expression, accessed with `->` when the flag is set.
+    /// Designators name members of a type the walk does not know.
+    Member(Option<(Expression, bool)>),
+}
+
+/// A name that refers to a declaration somewhere else.
+struct Use {
+    token: usize,
+    name: String,
+    role: Role,
+    /// The class whose method the name is used in.
+    class: Option<usize>,
+}
+
+/// The declarations of a source file and the names that refer to them. The tree has no
+/// positions, so the walk visits names in the order they are written and pairs each one
+/// with the next token that spells it.
+pub struct SymbolIndex {
+    pub source: String,
+    pub header: Header,
+    pub tokens: Vec<TokenWithTrivia>,
+    pub symbols: Vec<Symbol>,
+    /// Every resolved name in the source, in order.
+    pub references: Vec<Reference>,
+    by_name: HashMap<String, Vec<usize>>,
+    children: Vec<Vec<usize>>,
+}
+
+impl SymbolIndex {
+    pub fn new(source: &str) -> Result<SymbolIndex, String> {
+        let tokens = lex_with_trivia(source).map_err(|errors| format!("{:?}", errors))?;
+        let header = Parser::with_trivia(&tokens).parse()?;
+        Ok(SymbolIndex::from_tree(source, tokens, header))
+    }
+
+    /// Indexes a source that has already been lexed and parsed.
+    pub fn from_tree(source: &str, tokens: Vec<TokenWithTrivia>, header: Header) -> SymbolIndex {
+        let mut walker = Walker::new(&tokens, source.len());
+        walker.header(&header);
+        let Walker {symbols, uses, bases, declarations, ..} = walker;
+
+        let mut by_name: HashMap<String, Vec<usize>> = HashMap::new();
+        let mut children = vec![Vec::new(); symbols.len()];
+        for (index, symbol) in symbols.iter().enumerate() {
+            by_name.entry(symbol.name.clone()).or_default().push(index);
+            if let Some(parent) = symbol.parent {
+                children[parent].push(index);
+            }
+        }
+        let mut index = SymbolIndex {source: source.to_string(), header, tokens, symbols, references: declarations, by_name, children};
+
+        for (class, token) in bases {
+            let offset = index.tokens[token].span.start;
+            let name = match &index.tokens[token].token {
+                Token::Word(name) => name.clone(),
+                _ => continue,
+            };
+            let base = index.lookup(&name, offset, None).filter(|&base| index.symbols[base].kind == SymbolKind::Class);
+            if let Some(base) = base.filter(|&base| base != class) {
+                index.references.push(Reference {span: index.tokens[token].span.clone(), symbol: base});
+                index.symbols[class].base = Some(base);
+            }
+        }
+        for use_ in uses {
+            let span = index.tokens[use_.token].span.clone();
+            let symbol = match &use_.role {
+                Role::Ordinary => index.lookup(&use_.name, span.start, use_.class),
+                Role::Tag => index.find(&use_.name, span.start, SymbolKind::is_tag),
+                Role::Label => index.find(&use_.name, span.start, |kind| kind == SymbolKind::Label),
+                Role::Member(Some((object, arrow))) => index.type_of(object, span.start, use_.class)
+                    .and_then(|(type_, record)| index.container(&type_, record, *arrow, span.start, 0))
+                    .and_then(|container| index.member(container, &use_.name)),
+                Role::Member(None) => None,
+            };
+            if let Some(symbol) = symbol {
+                index.references.push(Reference {span, symbol});
+            }
+        }
+        index.references.sort_by_key(|reference| reference.span.start);
+
+        index
+    }
+
+    /// The reference whose name covers or ends at a byte offset.
+    pub fn reference_at(&self, offset: usize) -> Option<&Reference> {
+        let after = self.references.partition_point(|reference| reference.span.end < offset);
+        self.references.get(after).filter(|reference| reference.span.start <= offset)
+    }
+
+    pub fn references_to(&self, symbol: usize) -> impl Iterator<Item = &Reference> {
+        self.references.iter().filter(move |reference| reference.symbol == symbol)
+    }
+
+    /// The symbols declared directly inside a struct, union, enum, tagged union or class.
+    pub fn children(&self, symbol: usize) -> &[usize] {
+        &self.children[symbol]
+    }
+
+    /// The fields, methods and tagged union members that can follow `.` or `->` on a value
+    /// of a type, including those of anonymous structs inside it and of the classes it inherits from.
+    pub fn members(&self, container: usize) -> Vec<usize> {
+        let mut members = Vec::new();
+        let mut current = Some(container);
+        let mut depth = 0;
+        while let Some(container) = current.filter(|_| depth < 32) {
+            for &child in &self.children[container] {
+                match self.symbols[child].kind {
+                    SymbolKind::Field | SymbolKind::Method | SymbolKind::Variant => members.push(child),
+                    SymbolKind::Struct | SymbolKind::Union if self.symbols[child].name.is_empty() => {
+                        members.extend(self.members(child));
+                    },
+                    _ => {},
+                }
+            }
+            current = self.symbols[container].base;
+            depth += 1;
+        }
+        members
+    }
+
+    /// The declaration, as it would be written in the source.
+    pub fn signature(&self, symbol: usize) -> String {
+        let symbol = &self.symbols[symbol];
+        let parent = symbol.parent.map_or("", |parent| self.symbols[parent].name.as_str());
+        let declaration = || match &symbol.type_ {
+            Some(type_) => print_node(&AstNode::Variable(Variable {
+                name: symbol.name.clone(),
+                attributes: Vec::new(),
+                type_: type_.clone(),
+                value: None,
+            })),
+            None => symbol.name.clone(),
+        };
+        match symbol.kind {
+            SymbolKind::Variable | SymbolKind::Parameter | SymbolKind::Function | SymbolKind::Field |
+            SymbolKind::Method => declaration(),
+            SymbolKind::Typedef => format!("typedef {}", declaration()),
+            SymbolKind::Struct => format!("struct {}", symbol.name),
+            SymbolKind::Union => format!("union {}", symbol.name),
+            SymbolKind::Enum => format!("enum {}", symbol.name),
+            SymbolKind::TaggedUnion => format!("tagged {}", symbol.name),
+            SymbolKind::EnumMember => format!("enum {} {}", parent, symbol.name),
+            SymbolKind::Variant => format!("tagged {} {}", parent, symbol.name),
+            SymbolKind::Class => match symbol.base {
+                Some(base) => format!("class {} : {}", symbol.name, self.symbols[base].name),
+                None => format!("class {}", symbol.name),
+            },
+            SymbolKind::Label => format!("{}:", symbol.name),
+        }
+    }
+
+    /// The members that can complete `object.` or `object->` when `source` ends there at `offset`,
+    /// with or without part of the member name typed. The index may be of an older version of
+    /// the source, since an unfinished member access does not parse.
+    pub fn member_completions(&self, source: &str, offset: usize) -> Vec<usize> {
+        let Some(prefix) = source.get(..offset) else {
+            return Vec::new();
+        };
+        let Ok(tokens) = lex_with_trivia(prefix) else {
+            return Vec::new();
+        };
+        let mut tokens = tokens.into_iter().map(|token| token.token).collect::<Vec<Token>>();
+        if matches!(tokens.last(), Some(Token::Word(_))) && !prefix.ends_with(char::is_whitespace) {
+            tokens.pop();
+        }
+        let arrow = match tokens.pop() {
+            Some(Token::Period) => false,
+            Some(Token::Arrow) => true,
+            _ => return Vec::new(),
+        };
+
+        // Names are looked up where the edit starts, which is at the same place in the indexed source.
+        let common = self.source.bytes().zip(source.bytes()).take_while(|(old, new)| old == new).count();
+        let mut offset = offset.min(common);
+        while !self.source.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let class = self.symbols.iter()
+            .find(|symbol| symbol.kind == SymbolKind::Method && symbol.body.as_ref().is_some_and(|body| body.contains(&offset)))
+            .and_then(|method| method.parent);
+        chain(&tokens)
+            .and_then(|object| self.type_of(&object, offset, class))
+            .and_then(|(type_, record)| self.container(&type_, record, arrow, offset, 0))
+            .map(|container| self.members(container))
+            .unwrap_or_default()
+    }
+
+    /// The symbol of a kind `accept` allows that a name refers to at an offset: the
+    /// innermost declaration before it, or failing that a declaration at the top level after it.
+    fn find(&self, name: &str, offset: usize, accept: impl Fn(SymbolKind) -> bool) -> Option<usize> {
+        let candidates = self.by_name.get(name)?.iter().copied()
+            .filter(|&symbol| accept(self.symbols[symbol].kind))
+            .collect::<Vec<usize>>();
+        candidates.iter().copied()
+            .filter(|&symbol| self.symbols[symbol].scope.contains(&offset) || self.symbols[symbol].scope.end == offset)
+            .max_by_key(|&symbol| self.symbols[symbol].span.start)
+            .or_else(|| candidates.iter().copied().find(|&symbol| !self.symbols[symbol].local))
+    }
+
+    /// Looks up a plain identifier: locals first, then the members of the class whose
+    /// method it is used in, then everything at the top level.
+    fn lookup(&self, name: &str, offset: usize, class: Option<usize>) -> Option<usize> {
+        let local = self.find(name, offset, SymbolKind::is_ordinary).filter(|&symbol| self.symbols[symbol].local);
+        local.or_else(|| class.and_then(|class| self.member(class, name)))
+            .or_else(|| {
+                let candidates = self.by_name.get(name)?;
+                candidates.iter().copied()
+                    .filter(|&symbol| self.symbols[symbol].kind.is_ordinary() && !self.symbols[symbol].local)
+                    .filter(|&symbol| self.symbols[symbol].span.start <= offset)
+                    .max_by_key(|&symbol| self.symbols[symbol].span.start)
+                    .or_else(|| candidates.iter().copied()
+                        .find(|&symbol| self.symbols[symbol].kind.is_ordinary() && !self.symbols[symbol].local))
+            })
+    }
+
+    fn member(&self, container: usize, name: &str) -> Option<usize> {
+        self.members(container).into_iter().find(|&member| self.symbols[member].name == name)
+    }
+
+    fn symbol_type(&self, symbol: usize) -> Option<(DeclaredType, Option<usize>)> {
+        let symbol_ = &self.symbols[symbol];
+        match symbol_.kind {
+            SymbolKind::Variant => Some((DeclaredType::Base(Type::new(TypeSpecifier::Struct(None))), Some(symbol))),
+            _ => Some((symbol_.type_.clone()?, symbol_.record)),
+        }
+    }
+
+    /// Replaces typedef names at the top of a type with the types they stand for.
+    fn resolve(&self, type_: DeclaredType, record: Option<usize>, offset: usize) -> (DeclaredType, Option<usize>) {
+        let (mut type_, mut record) = (type_, record);
+        for _ in 0..32 {
+            let DeclaredType::Base(Type {specifier: TypeSpecifier::Named(name), ..}) = &type_ else {
+                break;
+            };
+            let Some(typedef) = self.lookup(name, offset, None).filter(|&symbol| self.symbols[symbol].kind == SymbolKind::Typedef) else {
+                break;
+            };
+            let Some(resolved) = self.symbols[typedef].type_.clone() else {
+                break;
+            };
+            type_ = resolved;
+            record = self.symbols[typedef].record;
+        }
+        (type_, record)
+    }
+
+    /// The type of an expression, as far as naming its members needs.
+    fn type_of(&self, expression: &Expression, offset: usize, class: Option<usize>) -> Option<(DeclaredType, Option<usize>)> {
+        let pointee = |(type_, record)| match self.resolve(type_, record, offset) {
+            (DeclaredType::Pointer(inner, _) | DeclaredType::Array(inner, _), record) => Some((*inner, record)),
+            _ => None,
+        };
+        let returned = |(type_, record)| match self.resolve(type_, record, offset) {
+            (DeclaredType::Function(returned, _), record) => Some((*returned, record)),
+            (DeclaredType::Pointer(inner, _), record) => match *inner {
+                DeclaredType::Function(returned, _) => Some((*returned, record)),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        match expression {
+            Expression::Identifier(name) if name == "this" => {
+                let class = DeclaredType::Base(Type::new(TypeSpecifier::Named(self.symbols[class?].name.clone())));
+                Some((DeclaredType::Pointer(Box::new(class), TypeQualifiers::default()), None))
+            },
+            Expression::Identifier(name) => self.symbol_type(self.lookup(name, offset, class)?),
+            Expression::Parentheses(inner) | Expression::Expression(inner) => self.type_of(inner, offset, class),
+            Expression::Unary(UnaryOperator::Dereference, operand) => pointee(self.type_of(operand, offset, class)?),
+            Expression::Unary(UnaryOperator::AddressOf, operand) => {
+                let (type_, record) = self.type_of(operand, offset, class)?;
+                Some((DeclaredType::Pointer(Box::new(type_), TypeQualifiers::default()), record))
+            },
+            Expression::Unary(UnaryOperator::Cast(type_), _) => Some((type_.as_ref().clone(), None)),
+            Expression::Binary(BinaryOperator::ArrayAccess, array, _) => pointee(self.type_of(array, offset, class)?),
+            Expression::Binary(operator @ (BinaryOperator::MemberAccess | BinaryOperator::PointerMemberAccess), object, member) => {
+                let (type_, record) = self.type_of(object, offset, class)?;
+                let container = self.container(&type_, record, *operator == BinaryOperator::PointerMemberAccess, offset, 0)?;
+                match member.as_ref() {
+                    Expression::Identifier(name) => self.symbol_type(self.member(container, name)?),
+                    Expression::CallFunction(name, _) => returned(self.symbol_type(self.member(container, name)?)?),
+                    _ => None,
+                }
+            },
+            Expression::Binary(BinaryOperator::Comma, _, right) => self.type_of(right, offset, class),
+            Expression::Binary(BinaryOperator::Assign, left, _) => self.type_of(left, offset, class),
+            Expression::Ternary(_, left, _) => self.type_of(left, offset, class),
+            Expression::CallFunction(name, _) => returned(self.symbol_type(self.lookup(name, offset, class)?)?),
+            Expression::Call(callee, _) => returned(self.type_of(callee, offset, class)?),
+            _ => None,
+        }
+    }
+
+    /// The struct, union, tagged union or class whose members follow `.` on a value of a
+    /// type, or `->` on a pointer to one.
+    fn container(&self, type_: &DeclaredType, record: Option<usize>, arrow: bool, offset: usize, depth: usize) -> Option<usize> {
+        if depth > 32 {
+            return None;
+        }
+        match type_ {
+            DeclaredType::Pointer(inner, _) | DeclaredType::Array(inner, _) if arrow => {
+                self.container(inner, record, false, offset, depth + 1)
+            },
+            DeclaredType::Base(base) => match &base.specifier {
+                TypeSpecifier::Struct(Some(tag)) | TypeSpecifier::Union(Some(tag)) | TypeSpecifier::Tagged(tag) => {
+                    self.find(tag, offset, SymbolKind::is_tag)
+                },
+                TypeSpecifier::Struct(None) | TypeSpecifier::Union(None) => record,
+                TypeSpecifier::Named(name) => {
+                    let symbol = self.lookup(name, offset, None)?;
+                    match self.symbols[symbol].kind {
+                        SymbolKind::Class => Some(symbol),
+                        SymbolKind::Typedef => {
+                            let type_ = self.symbols[symbol].type_.as_ref()?;
+                            self.container(type_, self.symbols[symbol].record, arrow, offset, depth + 1)
+                        },
+                        _ => None,
+                    }
+                },
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Rebuilds the object of an unfinished member access from the tokens in front of the
+/// `.` or `->`: a name followed by member accesses, calls and subscripts.
+fn chain(tokens: &[Token]) -> Option<Expression> {
+    // Walk back to where the chain starts.
+    let mut start = tokens.len();
+    loop {
+        match tokens[..start].last()? {
+            Token::RightParen | Token::RightBracket => {
+                let mut depth = 0;
+                loop {
+                    start = start.checked_sub(1)?;
+                    match tokens[start] {
+                        Token::RightParen | Token::RightBracket => depth += 1,
+                        Token::LeftParen | Token::LeftBracket => depth -= 1,
+                        _ => {},
+                    }
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                // A group is a call or subscript only when something comes before it.
+                if !matches!(tokens[..start].last(), Some(Token::Word(_) | Token::RightParen | Token::RightBracket)) {
+                    break;
+                }
+            },
+            Token::Word(_) => {
+                start -= 1;
+                match start.checked_sub(1).map(|index| &tokens[index]) {
+                    Some(Token::Period | Token::Arrow) => start -= 1,
+                    _ => break,
+                }
+            },
+            _ => return None,
+        }
+    }
+
+    let tokens = &tokens[start..];
+    let mut position = 0;
+    let mut object = match tokens.first()? {
+        Token::Word(name) => Expression::Identifier(name.clone()),
+        Token::LeftParen => {
+            let end = closing(tokens, 0)?;
+            let inner = &tokens[1..end];
+            position = end;
+            match inner.first()? {
+                Token::Star => Expression::Unary(UnaryOperator::Dereference, Box::new(chain(&inner[1..])?)),
+                Token::BitwiseAnd => Expression::Unary(UnaryOperator::AddressOf, Box::new(chain(&inner[1..])?)),
+                _ => Expression::Parentheses(Box::new(chain(inner)?)),
+            }
+        },
+        _ => return None,
+    };
+    position += 1;
+
+    while position < tokens.len() {
+        match &tokens[position] {
+            token @ (Token::Period | Token::Arrow) => {
+                let operator = match token {
+                    Token::Period => BinaryOperator::MemberAccess,
+                    _ => BinaryOperator::PointerMemberAccess,
+                };
+                let Some(Token::Word(name)) = tokens.get(position + 1) else {
+                    return None;
+                };
+                position += 2;
+                let member = match tokens.get(position) {
+                    Some(Token::LeftParen) => {
+                        position = closing(tokens, position)? + 1;
+                        Expression::CallFunction(name.clone(), None)
+                    },
+                    _ => Expression::Identifier(name.clone()),
+                };
+                object = Expression::Binary(operator, Box::new(object), Box::new(member));
+            },
+            Token::LeftBracket => {
+                position = closing(tokens, position)? + 1;
+                object = Expression::Binary(BinaryOperator::ArrayAccess, Box::new(object), Box::new(Expression::Blank));
+            },
+            Token::LeftParen => {
+                position = closing(tokens, position)? + 1;
+                object = match object {
+                    Expression::Identifier(name) => Expression::CallFunction(name, None),
+                    object => Expression::Call(Box::new(object), None),
+                };
+            },
+            _ => return None,
+        }
+    }
+    Some(object)
+}
+
+/// The index of the bracket that closes the one at `open`.
+fn closing(tokens: &[Token], open: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (index, token) in tokens.iter().enumerate().skip(open) {
+        match token {
+            Token::LeftParen | Token::LeftBracket | Token::LeftBrace => depth += 1,
+            Token::RightParen | Token::RightBracket | Token::RightBrace => depth -= 1,
+            _ => {},
+        }
+        if depth == 0 {
+            return Some(index);
+        }
+    }
+    None
+}
+
+/// Visits the names of a tree in source order, declaring symbols and recording uses.
+struct Walker<'a> {
+    tokens: &'a [TokenWithTrivia],
+    length: usize,
+    /// The `{` each token is directly inside of, and the `}` that closes each `{`.
+    enclosing: Vec<Option<usize>>,
+    matching: Vec<Option<usize>>,
+    /// The next token a name can be paired with.
+    cursor: usize,
+    symbols: Vec<Symbol>,
+    declarations: Vec<Reference>,
+    uses: Vec<Use>,
+    /// Each class with a base class, and the token naming the base.
+    bases: Vec<(usize, usize)>,
+    parent: Option<usize>,
+    class: Option<usize>,
+    /// The body of the function being walked.
+    body: Option<Range<usize>>,
+}
+
+impl<'a> Walker<'a> {
+    fn new(tokens: &'a [TokenWithTrivia], length: usize) -> Walker<'a> {
+        let mut enclosing = Vec::with_capacity(tokens.len());
+        let mut matching = vec![None; tokens.len()];
+        let mut open: Vec<usize> = Vec::new();
+        for (index, token) in tokens.iter().enumerate() {
+            if token.token == Token::RightBrace {
+                if let Some(start) = open.pop() {
+                    matching[start] = Some(index);
+                }
+            }
+            enclosing.push(open.last().copied());
+            if token.token == Token::LeftBrace {
+                open.push(index);
+            }
+        }
+        Walker {
+            tokens,
+            length,
+            enclosing,
+            matching,
+            cursor: 0,
+            symbols: Vec::new(),
+            declarations: Vec::new(),
+            uses: Vec::new(),
+            bases: Vec::new(),
+            parent: None,
+            class: None,
+            body: None,
+        }
+    }
+
+    /// Pairs a name with the next token that spells it.
+    fn occurrence(&mut self, name: &str) -> Option<usize> {
+        let found = self.tokens[self.cursor..].iter()
+            .position(|token| matches!(&token.token, Token::Word(word) if word == name))?;
+        self.cursor += found + 1;
+        Some(self.cursor - 1)
+    }
+
+    /// The byte offset where the block around a `{` ends, or the end of the file.
+    fn block_end(&self, open: Option<usize>) -> usize {
+        open.and_then(|open| self.matching[open])
+            .map_or(self.length, |close| self.tokens[close].span.end)
+    }
+
+    /// The byte range of the braces of the next block from a token.
+    fn next_block(&self, from: usize) -> Option<Range<usize>> {
+        let open = from + self.tokens.get(from..)?.iter().position(|token| token.token == Token::LeftBrace)?;
+        let close = self.matching[open]?;
+        Some(self.tokens[open].span.start..self.tokens[close].span.end)
+    }
+
+    fn declare(&mut self, name: &str, kind: SymbolKind, type_: Option<DeclaredType>, doc: Option<String>) -> Option<usize> {
+        let token = self.occurrence(name)?;
+        let span = self.tokens[token].span.clone();
+        let end = match kind {
+            // Enum and tagged union members are in the scope of the enum, not its braces.
+            SymbolKind::EnumMember | SymbolKind::Variant => {
+                self.block_end(self.enclosing[token].and_then(|open| self.enclosing[open]))
+            },
+            SymbolKind::Label => self.body.as_ref().map_or(span.end, |body| body.end),
+            _ => self.block_end(self.enclosing[token]),
+        };
+        let scope = match kind {
+            SymbolKind::Label => self.body.clone().unwrap_or(span.clone()),
+            _ => span.start..end,
+        };
+        Some(self.push(Symbol {
+            name: name.to_string(),
+            kind,
+            span,
+            scope,
+            local: self.body.is_some(),
+            parent: self.parent,
+            type_,
+            record: None,
+            base: None,
+            body: None,
+            doc,
+        }))
+    }
+
+    /// Declares a struct, union or enum without a tag.
+    fn anonymous(&mut self, kind: SymbolKind, doc: Option<String>) -> usize {
+        let span = self.tokens.get(self.cursor).map_or(self.length..self.length, |token| token.span.clone());
+        self.push(Symbol {
+            name: String::new(),
+            kind,
+            span: span.clone(),
+            scope: span,
+            local: self.body.is_some(),
+            parent: self.parent,
+            type_: None,
+            record: None,
+            base: None,
+            body: None,
+            doc,
+        })
+    }
+
+    fn push(&mut self, symbol: Symbol) -> usize {
+        if !symbol.name.is_empty() {
+            self.declarations.push(Reference {span: symbol.span.clone(), symbol: self.symbols.len()});
+        }
+        self.symbols.push(symbol);
+        self.symbols.len() - 1
+    }
+
+    fn use_(&mut self, name: &str, role: Role) {
+        if let Some(token) = self.occurrence(name) {
+            self.uses.push(Use {token, name: name.to_string(), role, class: self.class});
+        }
+    }
+
+    fn header(&mut self, header: &Header) {
+        for statement in &header.statements {
+            match statement {
+                HeaderStatement::Preprocessor(_) | HeaderStatement::Whitespace => {},
+                HeaderStatement::Struct(struct_) => {
+                    self.record(SymbolKind::Struct, &struct_.name, &struct_.members, &struct_.doc);
+                },
+                HeaderStatement::Union(union_) => {
+                    self.record(SymbolKind::Union, &union_.name, &union_.members, &union_.doc);
+                },
+                HeaderStatement::Enum(enum_) => self.enum_(enum_),
+                HeaderStatement::Variable(variable_list) => self.variable_list(variable_list, SymbolKind::Variable),
+                HeaderStatement::FunctionPrototype(prototype) => self.prototype(prototype, SymbolKind::Function),
+                HeaderStatement::Function(function) => self.function(function, SymbolKind::Function),
+                HeaderStatement::Class(class) => self.class(class),
+                HeaderStatement::TaggedUnion(tagged) => self.tagged_union(tagged),
+                HeaderStatement::StaticAssert(assertion) => self.expression(&assertion.condition),
+            }
+        }
+    }
+
+    fn record(&mut self, kind: SymbolKind, name: &Option<String>, members: &[Member], doc: &Option<String>) -> Option<usize> {
+        let symbol = match name {
+            Some(name) => self.declare(name, kind, None, doc.clone()),
+            None => Some(self.anonymous(kind, doc.clone())),
+        };
+        let parent = std::mem::replace(&mut self.parent, symbol);
+        self.members(members);
+        self.parent = parent;
+        symbol
+    }
+
+    fn members(&mut self, members: &[Member]) {
+        for member in members {
+            match member {
+                Member::Variables(variable_list) => self.variable_list(variable_list, SymbolKind::Field),
+                Member::BitFields(bit_fields) => {
+                    self.specifiers(&bit_fields.specifiers);
+                    for field in &bit_fields.fields {
+                        if let Some(name) = &field.name {
+                            let type_ = DeclaredType::Base(bit_fields.specifiers.type_.clone());
+                            self.declare(name, SymbolKind::Field, Some(type_), bit_fields.doc.clone());
+                        }
+                        self.expression(&field.width);
+                    }
+                },
+                Member::Struct(struct_) => {
+                    self.record(SymbolKind::Struct, &struct_.name, &struct_.members, &struct_.doc);
+                },
+                Member::Union(union_) => {
+                    self.record(SymbolKind::Union, &union_.name, &union_.members, &union_.doc);
+                },
+            }
+        }
+    }
+
+    fn enum_(&mut self, enum_: &Enum) {
+        let symbol = match &enum_.name {
+            Some(name) => self.declare(name, SymbolKind::Enum, None, enum_.doc.clone()),
+            None => Some(self.anonymous(SymbolKind::Enum, enum_.doc.clone())),
+        };
+        let parent = std::mem::replace(&mut self.parent, symbol);
+        let type_ = DeclaredType::Base(Type::new(TypeSpecifier::Enum(enum_.name.clone())));
+        for member in &enum_.members {
+            self.declare(&member.name, SymbolKind::EnumMember, Some(type_.clone()), member.doc.clone());
+            if let Some(value) = &member.value {
+                self.expression(value);
+            }
+        }
+        self.parent = parent;
+    }
+
+    fn tagged_union(&mut self, tagged: &TaggedUnion) {
+        let symbol = self.declare(&tagged.name, SymbolKind::TaggedUnion, None, tagged.doc.clone());
+        let parent = self.parent;
+        for member in &tagged.members {
+            self.parent = symbol;
+            let variant = self.declare(&member.name, SymbolKind::Variant, None, member.doc.clone());
+            if let Some(members) = &member.value {
+                self.parent = variant;
+                self.members(members);
+            }
+        }
+        self.parent = parent;
+    }
+
+    /// Visits declaration specifiers and returns the struct or union without a tag they define.
+    fn specifiers(&mut self, specifiers: &DeclarationSpecifiers) -> Option<usize> {
+        for alignas in &specifiers.alignas {
+            self.type_or_expression(alignas);
+        }
+        let Some(definition) = &specifiers.definition else {
+            self.base_type(&specifiers.type_);
+            return None;
+        };
+        // A type defined in a declaration is not a member of the struct around it.
+        let parent = self.parent.take();
+        let record = match definition.as_ref() {
+            Definition::Struct(struct_) => {
+                let symbol = self.record(SymbolKind::Struct, &struct_.name, &struct_.members, &struct_.doc);
+                symbol.filter(|_| struct_.name.is_none())
+            },
+            Definition::Union(union_) => {
+                let symbol = self.record(SymbolKind::Union, &union_.name, &union_.members, &union_.doc);
+                symbol.filter(|_| union_.name.is_none())
+            },
+            Definition::Enum(enum_) => {
+                self.enum_(enum_);
+                None
+            },
+            Definition::TaggedUnion(tagged) => {
+                self.tagged_union(tagged);
+                None
+            },
+        };
+        self.parent = parent;
+        record
+    }
+
+    fn base_type(&mut self, type_: &Type) {
+        match &type_.specifier {
+            TypeSpecifier::Struct(Some(tag)) | TypeSpecifier::Union(Some(tag)) | TypeSpecifier::Enum(Some(tag)) |
+            TypeSpecifier::Tagged(tag) => self.use_(tag, Role::Tag),
+            TypeSpecifier::Named(name) => self.use_(name, Role::Ordinary),
+            TypeSpecifier::Typeof(operand) | TypeSpecifier::TypeofUnqual(operand) => self.type_or_expression(operand),
+            _ => {},
+        }
+    }
+
+    fn type_name(&mut self, type_: &DeclaredType) {
+        self.base_type(type_.base());
+        self.declarator(type_);
+    }
+
+    fn type_or_expression(&mut self, operand: &TypeOrExpression) {
+        match operand {
+            TypeOrExpression::Type(type_) => self.type_name(type_),
+            TypeOrExpression::Expression(expression) => self.expression(expression),
+        }
+    }
+
+    /// Visits the array sizes and parameters of a declarator, which follow its name.
+    fn declarator(&mut self, type_: &DeclaredType) {
+        match type_ {
+            DeclaredType::Base(_) => {},
+            DeclaredType::Pointer(inner, _) => self.declarator(inner),
+            DeclaredType::Array(inner, size) => {
+                if let VariableArray::Size(size) = size {
+                    self.expression(size);
+                }
+                self.declarator(inner);
+            },
+            DeclaredType::Function(inner, arguments) => {
+                self.parameters(arguments, None);
+                self.declarator(inner);
+            },
+        }
+    }
+
+    /// Declares parameters, which can be used up to the end of the function body if there is one.
+    fn parameters(&mut self, arguments: &[FunctionArgument], body: Option<&Range<usize>>) {
+        for argument in arguments {
+            let FunctionArgument::Parameter(parameter) = argument else {
+                continue;
+            };
+            self.base_type(parameter.type_.base());
+            if let Some(name) = &parameter.name {
+                let symbol = self.declare(name, SymbolKind::Parameter, Some(parameter.type_.clone()), None);
+                if let Some(symbol) = symbol {
+                    let symbol = &mut self.symbols[symbol];
+                    symbol.scope.end = body.map_or(symbol.span.end, |body| body.end);
+                    symbol.local = body.is_some();
+                }
+            }
+            self.declarator(&parameter.type_);
+        }
+    }
+
+    fn variable_list(&mut self, variable_list: &VariableList, kind: SymbolKind) {
+        let record = self.specifiers(&variable_list.specifiers);
+        let typedef = variable_list.specifiers.storage == Some(StorageClass::Typedef);
+        for variable in &variable_list.variables {
+            let kind = match kind {
+                _ if typedef => SymbolKind::Typedef,
+                SymbolKind::Variable if variable.type_.is_function() => SymbolKind::Function,
+                SymbolKind::Field if variable.type_.is_function() => SymbolKind::Method,
+                kind => kind,
+            };
+            if let Some(symbol) = self.declare(&variable.name, kind, Some(variable.type_.clone()), variable_list.doc.clone()) {
+                self.symbols[symbol].record = record;
+            }
+            self.declarator(&variable.type_);
+            if let Some(VariableValue::Expression(value)) = &variable.value {
+                self.expression(value);
+            }
+        }
+    }
+
+    fn prototype(&mut self, prototype: &FunctionPrototype, kind: SymbolKind) {
+        self.specifiers(&prototype.specifiers);
+        let type_ = DeclaredType::Function(Box::new(prototype.return_type.clone()), prototype.arguments.clone());
+        self.declare(&prototype.name, kind, Some(type_), prototype.doc.clone());
+        self.parameters(&prototype.arguments, None);
+        self.declarator(&prototype.return_type);
+    }
+
+    fn function(&mut self, function: &Function, kind: SymbolKind) {
+        self.specifiers(&function.specifiers);
+        let type_ = DeclaredType::Function(Box::new(function.return_type.clone()), function.arguments.clone());
+        let symbol = self.declare(&function.name, kind, Some(type_), function.doc.clone());
+        let body = self.next_block(self.cursor);
+        if let Some(symbol) = symbol {
+            self.symbols[symbol].body = body.clone();
+        }
+        self.parameters(&function.arguments, body.as_ref());
+        self.declarator(&function.return_type);
+        self.code_block(&function.body, body);
+    }
+
+    fn code_block(&mut self, block: &CodeBlock, body: Option<Range<usize>>) {
+        let parent = self.parent.take();
+        let outer = self.body.replace(body.unwrap_or(self.length..self.length));
+        let CodeBlock::Code(list) = block;
+        self.statements(&list.statements);
+        self.body = outer;
+        self.parent = parent;
+    }
+
+    fn class(&mut self, class: &Class) {
+        let symbol = self.declare(&class.name, SymbolKind::Class, None, class.doc.clone());
+        if let Some(parent) = &class.parent {
+            if let (Some(symbol), Some(token)) = (symbol, self.occurrence(parent)) {
+                self.bases.push((symbol, token));
+            }
+        }
+        let (parent, outer) = (std::mem::replace(&mut self.parent, symbol), std::mem::replace(&mut self.class, symbol));
+        for member in &class.members {
+            match member {
+                ClassMember::Field(member) => self.members(std::slice::from_ref(member)),
+                ClassMember::Method(Method::Normal(function)) => self.function(function, SymbolKind::Method),
+                ClassMember::Method(Method::Abstract(prototype)) => self.prototype(prototype, SymbolKind::Method),
+                ClassMember::OperatorOverload(OperatorOverload::Normal {return_type, arguments, body, ..}) => {
+                    self.base_type(return_type.base());
+                    let operator = self.tokens[self.cursor..].iter().position(|token| token.token == Token::Operator)
+                        .map(|found| self.cursor + found);
+                    let range = operator.and_then(|operator| self.next_block(operator));
+                    self.parameters(arguments, range.as_ref());
+                    self.code_block(body, range);
+                },
+                ClassMember::OperatorOverload(OperatorOverload::Abstract {return_type, arguments, ..}) => {
+                    self.base_type(return_type.base());
+                    self.parameters(arguments, None);
+                },
+            }
+        }
+        self.parent = parent;
+        self.class = outer;
+    }
+
+    fn statements(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn body(&mut self, body: &BlockOrStatement) {
+        match body {
+            BlockOrStatement::Block(CodeBlock::Code(list)) => self.statements(&list.statements),
+            BlockOrStatement::Statement(statement) => self.statement(statement),
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Preprocessor(_) | Statement::Break | Statement::Continue | Statement::Attributes(_) => {},
+            Statement::VariableList(variable_list) => self.variable_list(variable_list, SymbolKind::Variable),
+            Statement::Expression(expression) | Statement::Return(expression) => self.expression(expression),
+            Statement::If(condition, body, else_) => {
+                self.expression(condition);
+                self.body(body);
+                if let Some(else_) = else_ {
+                    self.body(else_);
+                }
+            },
+            Statement::While(condition, body) => {
+                self.expression(condition);
+                self.body(body);
+            },
+            Statement::DoWhile(condition, body) => {
+                self.body(body);
+                self.expression(condition);
+            },
+            Statement::For(init, condition, step, body) => {
+                match init.as_deref() {
+                    Some(VariableListOrStatement::VariableList(variable_list)) => self.variable_list(variable_list, SymbolKind::Variable),
+                    Some(VariableListOrStatement::Statement(statement)) => self.statement(statement),
+                    None => {},
+                }
+                for expression in [condition, step].into_iter().flatten() {
+                    self.expression(expression);
+                }
+                self.body(body);
+            },
+            Statement::Switch(expression, cases) => {
+                self.expression(expression);
+                for case in cases {
+                    if let Some(value) = &case.expression {
+                        self.expression(value);
+                    }
+                    self.body(&case.body);
+                }
+            },
+            Statement::StaticAssert(assertion) => self.expression(&assertion.condition),
+            Statement::Attributed(_, statement) => self.statement(statement),
+            Statement::Goto(label) => self.use_(label, Role::Label),
+            Statement::Label(label) => {
+                self.declare(label, SymbolKind::Label, None, None);
+            },
+            Statement::Block(block) => {
+                let CodeBlock::Code(list) = block.as_ref();
+                self.statements(&list.statements);
+            },
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression) {
+        match expression {
+            Expression::Blank | Expression::Literal(_) => {},
+            Expression::Identifier(name) if name == "this" => {},
+            Expression::Identifier(name) => self.use_(name, Role::Ordinary),
+            Expression::Sizeof(operand) => self.type_or_expression(operand),
+            Expression::Alignof(type_) | Expression::Offsetof(type_, _) => self.type_name(type_),
+            Expression::Unary(UnaryOperator::Cast(type_), operand) => {
+                self.type_name(type_);
+                self.expression(operand);
+            },
+            Expression::Unary(UnaryOperator::MemberSet, operand) => match operand.as_ref() {
+                Expression::Identifier(name) => self.use_(name, Role::Member(None)),
+                operand => self.expression(operand),
+            },
+            Expression::Unary(_, operand) => self.expression(operand),
+            Expression::Binary(operator @ (BinaryOperator::MemberAccess | BinaryOperator::PointerMemberAccess), object, member) => {
+                self.expression(object);
+                let object = Some((object.as_ref().clone(), *operator == BinaryOperator::PointerMemberAccess));
+                match member.as_ref() {
+                    Expression::Identifier(name) => self.use_(name, Role::Member(object)),
+                    Expression::CallFunction(name, arguments) => {
+                        self.use_(name, Role::Member(object));
+                        if let Some(arguments) = arguments {
+                            self.expression(arguments);
+                        }
+                    },
+                    member => self.expression(member),
+                }
+            },
+            Expression::Binary(_, left, right) => {
+                self.expression(left);
+                self.expression(right);
+            },
+            Expression::Ternary(condition, left, right) => {
+                self.expression(condition);
+                self.expression(left);
+                self.expression(right);
+            },
+            Expression::CallFunction(name, arguments) => {
+                self.use_(name, Role::Ordinary);
+                if let Some(arguments) = arguments {
+                    self.expression(arguments);
+                }
+            },
+            Expression::Call(callee, arguments) => {
+                self.expression(callee);
+                if let Some(arguments) = arguments {
+                    self.expression(arguments);
+                }
+            },
+            Expression::Generic(control, associations) => {
+                self.expression(control);
+                for association in associations {
+                    if let Some(type_) = &association.type_ {
+                        self.type_name(type_);
+                    }
+                    self.expression(&association.expression);
+                }
+            },
+            Expression::TaggedInitializer(name, value) => {
+                self.use_(name, Role::Ordinary);
+                self.expression(value);
+            },
+            Expression::InitializerList(inner) | Expression::Expression(inner) | Expression::Parentheses(inner) => {
+                self.expression(inner);
+            },
+            Expression::StatementList(list) => self.statements(&list.statements),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod symbols_tests {
+    use super::*;
+
+    /// The byte offset of the `nth` occurrence of `needle` in `source`.
+    fn at(source: &str, needle: &str, nth: usize) -> usize {
+        source.match_indices(needle).nth(nth).map(|(offset, _)| offset).unwrap()
+    }
+
+    fn definition(index: &SymbolIndex, offset: usize) -> Option<Range<usize>> {
+        index.reference_at(offset).map(|reference| index.symbols[reference.symbol].span.clone())
+    }
+
+    #[test]
+    fn test_scopes_and_shadowing() {
+        let source = "int x;\nint f(int x) {\n    x = 1;\n    { int x = 2; x++; }\n    return x;\n}\nint g() { goto end; end: return x; }";
+        let index = SymbolIndex::new(source).unwrap();
+        let parameter = at(source, "x", 1);
+        assert_eq!(definition(&index, at(source, "x = 1", 0)), Some(parameter..parameter + 1));
+        let inner = at(source, "x = 2", 0);
+        assert_eq!(definition(&index, at(source, "x++", 0)), Some(inner..inner + 1));
+        assert_eq!(definition(&index, at(source, "x;\n}", 0)), Some(parameter..parameter + 1));
+        assert_eq!(definition(&index, at(source, "x; }", 0)), Some(4..5));
+        let label = at(source, "end:", 0);
+        assert_eq!(definition(&index, at(source, "end;", 0)), Some(label..label + 3));
+
+        let global = index.reference_at(4).unwrap().symbol;
+        assert_eq!(index.references_to(global).count(), 2);
+        assert_eq!(index.signature(index.reference_at(at(source, "f(", 0)).unwrap().symbol), "int f(int x)");
+    }
+
+    #[test]
+    fn test_members() {
+        let source = "typedef struct { int x, y; } point;\nstruct line { point from, to; struct { int width; }; };\n\
+                      class Shape { int id; int get(void) { return this->id + id; } };\nclass Circle : Shape { double r; };\n\
+                      tagged Value { Number { double n; }, Empty };\n\
+                      int f(struct line *l, Circle c, tagged Value v) { return l->from.x + l->width + c.get() + c.r + v.Number.n; }";
+        let index = SymbolIndex::new(source).unwrap();
+        let name = |offset| index.symbols[index.reference_at(offset).unwrap().symbol].name.clone();
+        let kind = |offset| index.symbols[index.reference_at(offset).unwrap().symbol].kind;
+        assert_eq!(definition(&index, at(source, "x +", 0)), Some(at(source, "x,", 0)..at(source, "x,", 0) + 1));
+        assert_eq!(name(at(source, "width +", 0)), "width");
+        assert_eq!(definition(&index, at(source, "get()", 0)), definition(&index, at(source, "get(void)", 0)));
+        assert_eq!(kind(at(source, "r +", 0)), SymbolKind::Field);
+        assert_eq!(kind(at(source, "Number.n", 0)), SymbolKind::Variant);
+        assert_eq!(kind(at(source, "n;", 1)), SymbolKind::Field);
+        assert_eq!(definition(&index, at(source, "id;", 1)), Some(at(source, "id;", 0)..at(source, "id;", 0) + 2));
+
+        let circle = index.reference_at(at(source, "Circle :", 0)).unwrap().symbol;
+        let members = index.members(circle).iter().map(|&member| index.symbols[member].name.clone()).collect::<Vec<String>>();
+        assert_eq!(members, ["r", "id", "get"]);
+        assert_eq!(index.signature(circle), "class Circle : Shape");
+    }
+
+    #[test]
+    fn test_member_completions() {
+        let source = "struct point { int x, y; };\nstruct point *origin(void);\nclass Box { struct point corner; int area(void) { return 0; } };\n\
+                      int main(Box boxes[2]) { struct point p; return 0; }";
+        let index = SymbolIndex::new(source).unwrap();
+        let names = |edited: &str, offset: usize| index.member_completions(edited, offset).iter()
+            .map(|&member| index.symbols[member].name.clone()).collect::<Vec<String>>();
+
+        let cursor = at(source, "return 0; }", 1);
+        let edit = |text: &str| format!("{}{}", &source[..cursor], text);
+        assert_eq!(names(&edit("p."), cursor + 2), ["x", "y"]);
+        assert_eq!(names(&edit("origin()->y"), cursor + 11), ["x", "y"]);
+        assert_eq!(names(&edit("boxes[1]."), cursor + 9), ["corner", "area"]);
+        assert_eq!(names(&edit("boxes[0].corner."), cursor + 16), ["x", "y"]);
+        assert_eq!(names(&edit("(&p)->"), cursor + 6), ["x", "y"]);
+        assert!(names(&edit("p + "), cursor + 4).is_empty());
+
+        let cursor = at(source, "return 0; } }", 0);
+        assert_eq!(names(&format!("{}this->", &source[..cursor]), cursor + 6), ["corner", "area"]);
+    }
+}
@@ -0,0 +1,361 @@
+use crate::ast::*;
+use crate::constant;
+use crate::layout::{DataModel, LayoutEngine};
+use crate::literal::{Encoding, NumberType};
+use crate::printer::print_node;
+
+
+fn base(specifier: TypeSpecifier) -> DeclaredType {
+    DeclaredType::Base(Type::new(specifier))
+}
+
+/// A type as it is written in C, for error messages.
+fn type_name(type_: &DeclaredType) -> String {
+    print_node(&AstNode::DeclaredType(type_.clone()))
+}
+
+pub(crate) fn number(type_: NumberType) -> Result<DeclaredType, String> {
+    let specifier = match type_ {
+        NumberType::Int => TypeSpecifier::Int,
+        NumberType::UnsignedInt => TypeSpecifier::UnsignedInt,
+        NumberType::Long => TypeSpecifier::Long,
+        NumberType::UnsignedLong => TypeSpecifier::UnsignedLong,
+        NumberType::LongLong => TypeSpecifier::LongLong,
+        NumberType::UnsignedLongLong => TypeSpecifier::UnsignedLongLong,
+        NumberType::Float => TypeSpecifier::Float,
+        NumberType::Double => TypeSpecifier::Double,
+        NumberType::LongDouble => TypeSpecifier::LongDouble,
+        NumberType::BitInt(_) | NumberType::UnsignedBitInt(_) => {
+            return Err("Bit-precise integer types are not supported yet".to_string());
+        },
+    };
+    Ok(base(specifier))
+}
+
+/// The type an arithmetic type has after the integer promotions, or `None` for
+/// types that are not arithmetic.
+fn promoted(type_: &DeclaredType) -> Option<NumberType> {
+    let DeclaredType::Base(base) = type_ else {
+        return None;
+    };
+    let promoted = match base.specifier {
+        TypeSpecifier::Bool | TypeSpecifier::Char | TypeSpecifier::SignedChar | TypeSpecifier::UnsignedChar |
+        TypeSpecifier::Short | TypeSpecifier::UnsignedShort | TypeSpecifier::Int | TypeSpecifier::Enum(_) => NumberType::Int,
+        TypeSpecifier::UnsignedInt => NumberType::UnsignedInt,
+        TypeSpecifier::Long => NumberType::Long,
+        TypeSpecifier::UnsignedLong => NumberType::UnsignedLong,
+        TypeSpecifier::LongLong => NumberType::LongLong,
+        TypeSpecifier::UnsignedLongLong => NumberType::UnsignedLongLong,
+        TypeSpecifier::Float => NumberType::Float,
+        TypeSpecifier::Double => NumberType::Double,
+        TypeSpecifier::LongDouble => NumberType::LongDouble,
+        _ => return None,
+    };
+    Some(promoted)
+}
+
+/// Applies lvalue conversion: qualifiers are dropped and arrays and functions
+/// become pointers.
+fn converted(type_: DeclaredType) -> DeclaredType {
+    match type_ {
+        DeclaredType::Base(base) => DeclaredType::Base(Type::new(base.specifier)),
+        DeclaredType::Pointer(inner, _) => DeclaredType::Pointer(inner, TypeQualifiers::default()),
+        DeclaredType::Array(inner, _) => DeclaredType::Pointer(inner, TypeQualifiers::default()),
+        function @ DeclaredType::Function(..) => DeclaredType::Pointer(Box::new(function), TypeQualifiers::default()),
+    }
+}
+
+/// Replaces typedef names and `typeof` at the top of a type with the types they stand for.
+pub(crate) fn resolve(type_: &DeclaredType, engine: &LayoutEngine) -> Result<DeclaredType, String> {
+    let DeclaredType::Base(base) = type_ else {
+        return Ok(type_.clone());
+    };
+    let resolved = match &base.specifier {
+        TypeSpecifier::Named(name) => match engine.typedef(name) {
+            Some(type_) => resolve(type_, engine)?,
+            None => return Ok(type_.clone()),
+        },
+        TypeSpecifier::Typeof(operand) | TypeSpecifier::TypeofUnqual(operand) => match operand.as_ref() {
+            TypeOrExpression::Type(type_) => resolve(type_, engine)?,
+            TypeOrExpression::Expression(expression) => type_of(expression, engine)?,
+        },
+        _ => return Ok(type_.clone()),
+    };
+    let unqualified = matches!(base.specifier, TypeSpecifier::TypeofUnqual(_));
+    Ok(match resolved {
+        DeclaredType::Base(inner) if !unqualified => DeclaredType::Base(Type {
+            specifier: inner.specifier,
+            qualifiers: TypeQualifiers {
+                const_: inner.qualifiers.const_ || base.qualifiers.const_,
+                volatile: inner.qualifiers.volatile || base.qualifiers.volatile,
+                restrict: inner.qualifiers.restrict || base.qualifiers.restrict,
+                atomic: inner.qualifiers.atomic || base.qualifiers.atomic,
+            },
+        }),
+        resolved if unqualified => unqualified_type(resolved),
+        resolved => resolved,
+    })
+}
+
+fn unqualified_type(type_: DeclaredType) -> DeclaredType {
+    match type_ {
+        DeclaredType::Base(base) => DeclaredType::Base(Type::new(base.specifier)),
+        DeclaredType::Pointer(inner, _) => DeclaredType::Pointer(inner, TypeQualifiers::default()),
+        type_ => type_,
+    }
+}
+
+/// Finds the type of an expression after lvalue conversion, as the controlling
+/// expression of a `_Generic` sees it. The expression is not evaluated.
+pub fn type_of(expression: &Expression, engine: &LayoutEngine) -> Result<DeclaredType, String> {
+    Ok(converted(declared_type_of(expression, engine)?))
+}
+
+/// Finds the type of an expression before arrays and functions decay to pointers.
+pub(crate) fn declared_type_of(expression: &Expression, engine: &LayoutEngine) -> Result<DeclaredType, String> {
+    let model = engine.model();
+    let arithmetic = |expression: &Expression| -> Result<NumberType, String> {
+        let type_ = type_of(expression, engine)?;
+        promoted(&type_).ok_or_else(|| format!("Expected an arithmetic operand but found {}", type_name(&type_)))
+    };
+    let type_ = match expression {
+        Expression::Literal(Literal::Number(literal)) => number(literal.c_type)?,
+        Expression::Literal(Literal::Char(character)) => match character.encoding {
+            Encoding::Utf8 => base(TypeSpecifier::UnsignedChar),
+            Encoding::Utf16 => base(TypeSpecifier::UnsignedShort),
+            Encoding::Utf32 => base(TypeSpecifier::UnsignedInt),
+            Encoding::Char | Encoding::Wide => base(TypeSpecifier::Int),
+        },
+        Expression::Literal(Literal::String(string)) => {
+            let unit = match string.encoding {
+                Encoding::Char => TypeSpecifier::Char,
+                Encoding::Utf8 => TypeSpecifier::UnsignedChar,
+                Encoding::Utf16 => TypeSpecifier::UnsignedShort,
+                Encoding::Utf32 => TypeSpecifier::UnsignedInt,
+                Encoding::Wide => TypeSpecifier::Int,
+            };
+            DeclaredType::Pointer(Box::new(base(unit)), TypeQualifiers::default())
+        },
+        Expression::Literal(Literal::Bool(_)) => base(TypeSpecifier::Bool),
+        Expression::Literal(Literal::Nullptr) => {
+            return Err("nullptr_t is not supported yet".to_string());
+        },
+        Expression::Identifier(name) => match (engine.variable(name), engine.enumerator(name)) {
+            (Some(type_), _) => resolve(type_, engine)?,
+            (None, Some(_)) => base(TypeSpecifier::Int),
+            (None, None) => return Err(format!("Unknown identifier {}", name)),
+        },
+        Expression::Parentheses(inner) | Expression::Expression(inner) => declared_type_of(inner, engine)?,
+        Expression::Sizeof(_) | Expression::Alignof(_) | Expression::Offsetof(..) => number(match model {
+            DataModel::LP64 => NumberType::UnsignedLong,
+            DataModel::LLP64 => NumberType::UnsignedLongLong,
+            DataModel::ILP32 => NumberType::UnsignedInt,
+        })?,
+        Expression::Unary(operator, operand) => match operator {
+            UnaryOperator::Cast(type_) => resolve(type_, engine)?,
+            UnaryOperator::Plus | UnaryOperator::Minus | UnaryOperator::BitwiseNot => number(arithmetic(operand)?)?,
+            UnaryOperator::Not | UnaryOperator::LogicalNot => base(TypeSpecifier::Int),
+            UnaryOperator::AddressOf => DeclaredType::Pointer(Box::new(declared_type_of(operand, engine)?), TypeQualifiers::default()),
+            UnaryOperator::Dereference => match type_of(operand, engine)? {
+                DeclaredType::Pointer(inner, _) => resolve(&inner, engine)?,
+                type_ => return Err(format!("Cannot dereference {}", type_name(&type_))),
+            },
+            UnaryOperator::PreIncrement | UnaryOperator::PreDecrement | UnaryOperator::PostIncrement |
+            UnaryOperator::PostDecrement => type_of(operand, engine)?,
+            UnaryOperator::MemberSet => return Err("A designator has no type".to_string()),
+        },
+        Expression::Binary(operator, left, right) => match operator {
+            BinaryOperator::Add | BinaryOperator::Subtract | BinaryOperator::Multiply | BinaryOperator::Divide |
+            BinaryOperator::Modulo | BinaryOperator::BitwiseAnd | BinaryOperator::BitwiseOr | BinaryOperator::BitwiseXor => {
+                let (left_type, right_type) = (type_of(left, engine)?, type_of(right, engine)?);
+                match (promoted(&left_type), promoted(&right_type)) {
+                    (Some(left), Some(right)) => number(constant::common_type(left, right, model))?,
+                    (None, Some(_)) if matches!(operator, BinaryOperator::Add | BinaryOperator::Subtract) => left_type,
+                    (Some(_), None) if *operator == BinaryOperator::Add => right_type,
+                    (None, None) if *operator == BinaryOperator::Subtract => number(match model {
+                        DataModel::LP64 => NumberType::Long,
+                        DataModel::LLP64 => NumberType::LongLong,
+                        DataModel::ILP32 => NumberType::Int,
+                    })?,
+                    _ => return Err(format!("Invalid operands to {}: {} and {}", print_node(&AstNode::BinaryOperator(operator.clone())),
+                                            type_name(&left_type), type_name(&right_type))),
+                }
+            },
+            BinaryOperator::LeftShift | BinaryOperator::RightShift => number(arithmetic(left)?)?,
+            BinaryOperator::LessThan | BinaryOperator::GreaterThan | BinaryOperator::LessThanOrEqual |
+            BinaryOperator::GreaterThanOrEqual | BinaryOperator::Equal | BinaryOperator::NotEqual |
+            BinaryOperator::LogicalAnd | BinaryOperator::LogicalOr => base(TypeSpecifier::Int),
+            BinaryOperator::Comma => type_of(right, engine)?,
+            BinaryOperator::Assign | BinaryOperator::AddAssign | BinaryOperator::SubtractAssign |
+            BinaryOperator::MultiplyAssign | BinaryOperator::DivideAssign | BinaryOperator::ModuloAssign |
+            BinaryOperator::LeftShiftAssign | BinaryOperator::RightShiftAssign | BinaryOperator::BitwiseAndAssign |
+            BinaryOperator::BitwiseOrAssign | BinaryOperator::BitwiseXorAssign => type_of(left, engine)?,
+            BinaryOperator::Index | BinaryOperator::ArrayAccess => match type_of(left, engine)? {
+                DeclaredType::Pointer(inner, _) => resolve(&inner, engine)?,
+                type_ => return Err(format!("Cannot index {}", type_name(&type_))),
+            },
+            BinaryOperator::MemberAccess | BinaryOperator::PointerMemberAccess => {
+                return Err("The types of members are not tracked yet".to_string());
+            },
+        },
+        Expression::Ternary(_, then, otherwise) => {
+            let (then_type, otherwise_type) = (type_of(then, engine)?, type_of(otherwise, engine)?);
+            match (promoted(&then_type), promoted(&otherwise_type)) {
+                (Some(then), Some(otherwise)) => number(constant::common_type(then, otherwise, model))?,
+                _ => then_type,
+            }
+        },
+        Expression::CallFunction(name, _) => match engine.variable(name).map(|type_| resolve(type_, engine)) {
+            Some(Ok(DeclaredType::Function(return_type, _))) => resolve(&return_type, engine)?,
+            Some(Ok(DeclaredType::Pointer(inner, _))) if inner.is_function() => {
+                let DeclaredType::Function(return_type, _) = *inner else {
+                    unreachable!("checked by is_function");
+                };
+                resolve(&return_type, engine)?
+            },
+            Some(result) => return Err(format!("{} is not a function: {}", name, type_name(&result?))),
+            None => return Err(format!("Unknown function {}", name)),
+        },
+        Expression::Call(callee, _) => match type_of(callee, engine)? {
+            DeclaredType::Pointer(inner, _) => match *inner {
+                DeclaredType::Function(return_type, _) => resolve(&return_type, engine)?,
+                type_ => return Err(format!("Cannot call {}", type_name(&type_))),
+            },
+            type_ => return Err(format!("Cannot call {}", type_name(&type_))),
+        },
+        Expression::Generic(control, associations) => {
+            declared_type_of(select_association(control, associations, engine)?, engine)?
+        },
+        Expression::Blank | Expression::InitializerList(_) | Expression::TaggedInitializer(..) |
+        Expression::StatementList(_) => {
+            return Err(format!("{} has no type", print_node(&AstNode::Expression(expression.clone()))));
+        },
+    };
+    Ok(type_)
+}
+
+/// Picks the association of a `_Generic` selection whose type matches the type of
+/// the controlling expression, falling back to `default`.
+pub fn select_association<'a>(control: &Expression, associations: &'a [GenericAssociation], engine: &LayoutEngine) -> Result<&'a Expression, String> {
+    select_for_type(&type_of(control, engine)?, associations, engine)
+}
+
+/// Picks the association of a `_Generic` selection for a controlling type that is
+/// already known.
+pub fn select_for_type<'a>(control: &DeclaredType, associations: &'a [GenericAssociation], engine: &LayoutEngine) -> Result<&'a Expression, String> {
+    let mut default = None;
+    for association in associations {
+        match &association.type_ {
+            Some(type_) if resolve(type_, engine)? == *control => return Ok(&association.expression),
+            Some(_) => {},
+            None => default = Some(&association.expression),
+        }
+    }
+    default.ok_or_else(|| format!("No _Generic association matches {}", type_name(control)))
+}
+
+
+#[cfg(test)]
+mod typecheck_tests {
+    use super::*;
+    use crate::logos_lexer::lex;
+    use crate::parser::Parser;
+
+    const DECLARATIONS: &str = "typedef const char *string;\ntypedef int (*callback)(int);\nchar *p;\nconst char *cp;\n\
+                                char *const pc;\nconst volatile int ci;\nstring s;\nint a[4];\nint f(int);\ncallback g;\n";
+
+    fn engine() -> LayoutEngine {
+        let header = Parser::new(&lex(DECLARATIONS).unwrap()).parse().unwrap();
+        match LayoutEngine::from_header(&header, DataModel::LP64) {
+            Ok(engine) => engine,
+            Err(err) => panic!("Failed to lay out declarations: {}", err),
+        }
+    }
+
+    fn expression(input: &str) -> Expression {
+        match Parser::new(&lex(input).unwrap()).constant_expression() {
+            Ok(expression) => expression,
+            Err(err) => panic!("Failed to parse {}: {}", input, err),
+        }
+    }
+
+    /// The value of the association a `_Generic` selection picks.
+    fn selected(input: &str, engine: &LayoutEngine) -> Result<String, String> {
+        let Expression::Generic(control, associations) = expression(input) else {
+            panic!("Expected a generic selection: {}", input);
+        };
+        Ok(select_association(&control, &associations, engine)?.get_value().unwrap_or_default())
+    }
+
+    fn pointer(type_: DeclaredType) -> DeclaredType {
+        DeclaredType::Pointer(Box::new(type_), TypeQualifiers::default())
+    }
+
+    fn const_char() -> DeclaredType {
+        DeclaredType::Base(Type {specifier: TypeSpecifier::Char, qualifiers: TypeQualifiers {const_: true, ..TypeQualifiers::default()}})
+    }
+
+    #[test]
+    fn test_qualified_pointers() {
+        let engine = engine();
+        let generic = |control: &str| selected(&format!("_Generic({}, const char *: 1, char *: 2, default: 3)", control), &engine);
+        assert_eq!(generic("cp"), Ok("1".to_string()));
+        assert_eq!(generic("p"), Ok("2".to_string()));
+        // The pointer itself being const does not matter after lvalue conversion.
+        assert_eq!(generic("pc"), Ok("2".to_string()));
+        assert_eq!(generic("\"text\""), Ok("2".to_string()));
+        assert_eq!(generic("&ci"), Ok("3".to_string()));
+    }
+
+    #[test]
+    fn test_typedef_resolution() {
+        let engine = engine();
+        assert_eq!(type_of(&expression("s"), &engine), Ok(pointer(const_char())));
+        assert_eq!(selected("_Generic(cp, string: 1, default: 2)", &engine), Ok("1".to_string()));
+        assert_eq!(type_of(&expression("g(1)"), &engine), Ok(base(TypeSpecifier::Int)));
+        assert_eq!(resolve(&DeclaredType::Base(Type::new(TypeSpecifier::Named("missing".to_string()))), &engine),
+                   Ok(DeclaredType::Base(Type::new(TypeSpecifier::Named("missing".to_string())))));
+    }
+
+    #[test]
+    fn test_array_and_function_decay() {
+        let engine = engine();
+        let int = || base(TypeSpecifier::Int);
+        let array = DeclaredType::Array(Box::new(int()), VariableArray::Size(expression("4")));
+        assert_eq!(declared_type_of(&expression("a"), &engine), Ok(array));
+        assert_eq!(type_of(&expression("a"), &engine), Ok(pointer(int())));
+        assert_eq!(type_of(&expression("a + 1"), &engine), Ok(pointer(int())));
+        assert_eq!(type_of(&expression("a[2]"), &engine), Ok(int()));
+
+        let function = declared_type_of(&expression("f"), &engine).unwrap();
+        assert!(function.is_function(), "Expected a function type: {:?}", function);
+        assert_eq!(type_of(&expression("f"), &engine), Ok(pointer(function)));
+        assert_eq!(selected("_Generic(f, int (*)(int): 1, default: 2)", &engine), Ok("1".to_string()));
+    }
+
+    #[test]
+    fn test_lvalue_conversion_drops_qualifiers() {
+        let engine = engine();
+        let qualified = DeclaredType::Base(Type {
+            specifier: TypeSpecifier::Int,
+            qualifiers: TypeQualifiers {const_: true, volatile: true, ..TypeQualifiers::default()},
+        });
+        assert_eq!(declared_type_of(&expression("ci"), &engine), Ok(qualified.clone()));
+        assert_eq!(type_of(&expression("ci"), &engine), Ok(base(TypeSpecifier::Int)));
+        // Qualifiers below the top level stay.
+        assert_eq!(type_of(&expression("&ci"), &engine), Ok(pointer(qualified)));
+        assert_eq!(selected("_Generic(ci, int: 1, const volatile int: 2)", &engine), Ok("1".to_string()));
+    }
+
+    #[test]
+    fn test_default_association_and_no_match() {
+        let engine = engine();
+        assert_eq!(selected("_Generic(1.0, int: 1, default: 2, float: 3)", &engine), Ok("2".to_string()));
+        assert_eq!(selected("_Generic(1.0f, int: 1, default: 2, float: 3)", &engine), Ok("3".to_string()));
+        let err = selected("_Generic(1.0, int: 1, char *: 2)", &engine).unwrap_err();
+        assert!(err.starts_with("No _Generic association matches"), "{}", err);
+        assert_eq!(select_for_type(&base(TypeSpecifier::Long), &[], &engine),
+                   Err("No _Generic association matches long".to_string()));
+        assert!(selected("_Generic(missing, default: 1)", &engine).unwrap_err().contains("Unknown identifier missing"));
+    }
+}
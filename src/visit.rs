@@ -0,0 +1,1374 @@
+use crate::ast::*;
+
+
+/// Walks a syntax tree by shared reference. Every method visits one kind of node and by
+/// default walks into its children with the `walk_` function of the same name, so a pass
+/// overrides the nodes it cares about and calls the `walk_` function to keep descending.
+pub trait Visitor<'ast> {
+    fn visit_header(&mut self, header: &'ast Header) {
+        walk_header(self, header);
+    }
+
+    fn visit_header_statement(&mut self, statement: &'ast HeaderStatement) {
+        walk_header_statement(self, statement);
+    }
+
+    fn visit_preprocessor(&mut self, _preprocessor: &'ast Preprocessor) {}
+
+    fn visit_static_assert(&mut self, static_assert: &'ast StaticAssert) {
+        walk_static_assert(self, static_assert);
+    }
+
+    fn visit_struct(&mut self, struct_: &'ast Struct) {
+        walk_struct(self, struct_);
+    }
+
+    fn visit_union(&mut self, union_: &'ast Union) {
+        walk_union(self, union_);
+    }
+
+    fn visit_member(&mut self, member: &'ast Member) {
+        walk_member(self, member);
+    }
+
+    fn visit_bit_field_list(&mut self, bit_fields: &'ast BitFieldList) {
+        walk_bit_field_list(self, bit_fields);
+    }
+
+    fn visit_bit_field(&mut self, bit_field: &'ast BitField) {
+        walk_bit_field(self, bit_field);
+    }
+
+    fn visit_definition(&mut self, definition: &'ast Definition) {
+        walk_definition(self, definition);
+    }
+
+    fn visit_enum(&mut self, enum_: &'ast Enum) {
+        walk_enum(self, enum_);
+    }
+
+    fn visit_enum_member(&mut self, member: &'ast EnumMember) {
+        walk_enum_member(self, member);
+    }
+
+    fn visit_tagged_union(&mut self, tagged: &'ast TaggedUnion) {
+        walk_tagged_union(self, tagged);
+    }
+
+    fn visit_tagged_union_member(&mut self, member: &'ast TaggedUnionMember) {
+        walk_tagged_union_member(self, member);
+    }
+
+    fn visit_variable_list(&mut self, variable_list: &'ast VariableList) {
+        walk_variable_list(self, variable_list);
+    }
+
+    fn visit_variable(&mut self, variable: &'ast Variable) {
+        walk_variable(self, variable);
+    }
+
+    fn visit_variable_value(&mut self, value: &'ast VariableValue) {
+        walk_variable_value(self, value);
+    }
+
+    fn visit_variable_array(&mut self, array: &'ast VariableArray) {
+        walk_variable_array(self, array);
+    }
+
+    fn visit_function_prototype(&mut self, prototype: &'ast FunctionPrototype) {
+        walk_function_prototype(self, prototype);
+    }
+
+    fn visit_function(&mut self, function: &'ast Function) {
+        walk_function(self, function);
+    }
+
+    fn visit_function_argument(&mut self, argument: &'ast FunctionArgument) {
+        walk_function_argument(self, argument);
+    }
+
+    fn visit_parameter(&mut self, parameter: &'ast Parameter) {
+        walk_parameter(self, parameter);
+    }
+
+    fn visit_class(&mut self, class: &'ast Class) {
+        walk_class(self, class);
+    }
+
+    fn visit_class_member(&mut self, member: &'ast ClassMember) {
+        walk_class_member(self, member);
+    }
+
+    fn visit_method(&mut self, method: &'ast Method) {
+        walk_method(self, method);
+    }
+
+    fn visit_operator_overload(&mut self, overload: &'ast OperatorOverload) {
+        walk_operator_overload(self, overload);
+    }
+
+    fn visit_declaration_specifiers(&mut self, specifiers: &'ast DeclarationSpecifiers) {
+        walk_declaration_specifiers(self, specifiers);
+    }
+
+    fn visit_attribute(&mut self, _attribute: &'ast Attribute) {}
+
+    fn visit_type(&mut self, type_: &'ast Type) {
+        walk_type(self, type_);
+    }
+
+    fn visit_type_specifier(&mut self, specifier: &'ast TypeSpecifier) {
+        walk_type_specifier(self, specifier);
+    }
+
+    fn visit_declared_type(&mut self, type_: &'ast DeclaredType) {
+        walk_declared_type(self, type_);
+    }
+
+    fn visit_type_or_expression(&mut self, operand: &'ast TypeOrExpression) {
+        walk_type_or_expression(self, operand);
+    }
+
+    fn visit_code_block(&mut self, block: &'ast CodeBlock) {
+        walk_code_block(self, block);
+    }
+
+    fn visit_statement_list(&mut self, list: &'ast StatementList) {
+        walk_statement_list(self, list);
+    }
+
+    fn visit_block_or_statement(&mut self, body: &'ast BlockOrStatement) {
+        walk_block_or_statement(self, body);
+    }
+
+    fn visit_variable_list_or_statement(&mut self, init: &'ast VariableListOrStatement) {
+        walk_variable_list_or_statement(self, init);
+    }
+
+    fn visit_statement(&mut self, statement: &'ast Statement) {
+        walk_statement(self, statement);
+    }
+
+    fn visit_switch_case(&mut self, case: &'ast SwitchCase) {
+        walk_switch_case(self, case);
+    }
+
+    fn visit_expression(&mut self, expression: &'ast Expression) {
+        walk_expression(self, expression);
+    }
+
+    fn visit_generic_association(&mut self, association: &'ast GenericAssociation) {
+        walk_generic_association(self, association);
+    }
+
+    fn visit_literal(&mut self, _literal: &'ast Literal) {}
+
+    fn visit_unary_operator(&mut self, operator: &'ast UnaryOperator) {
+        walk_unary_operator(self, operator);
+    }
+
+    fn visit_binary_operator(&mut self, _operator: &'ast BinaryOperator) {}
+
+    fn visit_initializer(&mut self, initializer: &'ast Initializer) {
+        walk_initializer(self, initializer);
+    }
+
+    fn visit_designator(&mut self, designator: &'ast Designator) {
+        walk_designator(self, designator);
+    }
+}
+
+pub fn walk_header<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, header: &'ast Header) {
+    for statement in &header.statements {
+        visitor.visit_header_statement(statement);
+    }
+}
+
+pub fn walk_header_statement<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, statement: &'ast HeaderStatement) {
+    match statement {
+        HeaderStatement::Preprocessor(preprocessor) => visitor.visit_preprocessor(preprocessor),
+        HeaderStatement::Struct(struct_) => visitor.visit_struct(struct_),
+        HeaderStatement::Union(union_) => visitor.visit_union(union_),
+        HeaderStatement::Enum(enum_) => visitor.visit_enum(enum_),
+        HeaderStatement::Variable(variable_list) => visitor.visit_variable_list(variable_list),
+        HeaderStatement::FunctionPrototype(prototype) => visitor.visit_function_prototype(prototype),
+        HeaderStatement::Function(function) => visitor.visit_function(function),
+        HeaderStatement::Class(class) => visitor.visit_class(class),
+        HeaderStatement::TaggedUnion(tagged) => visitor.visit_tagged_union(tagged),
+        HeaderStatement::StaticAssert(static_assert) => visitor.visit_static_assert(static_assert),
+        HeaderStatement::Whitespace => {},
+    }
+}
+
+pub fn walk_static_assert<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, static_assert: &'ast StaticAssert) {
+    visitor.visit_expression(&static_assert.condition);
+}
+
+pub fn walk_struct<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, struct_: &'ast Struct) {
+    for attribute in &struct_.attributes {
+        visitor.visit_attribute(attribute);
+    }
+    for member in &struct_.members {
+        visitor.visit_member(member);
+    }
+}
+
+pub fn walk_union<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, union_: &'ast Union) {
+    for attribute in &union_.attributes {
+        visitor.visit_attribute(attribute);
+    }
+    for member in &union_.members {
+        visitor.visit_member(member);
+    }
+}
+
+pub fn walk_member<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, member: &'ast Member) {
+    match member {
+        Member::Variables(variable_list) => visitor.visit_variable_list(variable_list),
+        Member::BitFields(bit_fields) => visitor.visit_bit_field_list(bit_fields),
+        Member::Struct(struct_) => visitor.visit_struct(struct_),
+        Member::Union(union_) => visitor.visit_union(union_),
+    }
+}
+
+pub fn walk_bit_field_list<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, bit_fields: &'ast BitFieldList) {
+    visitor.visit_declaration_specifiers(&bit_fields.specifiers);
+    for bit_field in &bit_fields.fields {
+        visitor.visit_bit_field(bit_field);
+    }
+}
+
+pub fn walk_bit_field<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, bit_field: &'ast BitField) {
+    visitor.visit_expression(&bit_field.width);
+}
+
+pub fn walk_definition<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, definition: &'ast Definition) {
+    match definition {
+        Definition::Struct(struct_) => visitor.visit_struct(struct_),
+        Definition::Union(union_) => visitor.visit_union(union_),
+        Definition::Enum(enum_) => visitor.visit_enum(enum_),
+        Definition::TaggedUnion(tagged) => visitor.visit_tagged_union(tagged),
+    }
+}
+
+pub fn walk_enum<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, enum_: &'ast Enum) {
+    for attribute in &enum_.attributes {
+        visitor.visit_attribute(attribute);
+    }
+    for member in &enum_.members {
+        visitor.visit_enum_member(member);
+    }
+}
+
+pub fn walk_enum_member<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, member: &'ast EnumMember) {
+    for attribute in &member.attributes {
+        visitor.visit_attribute(attribute);
+    }
+    if let Some(value) = &member.value {
+        visitor.visit_expression(value);
+    }
+}
+
+pub fn walk_tagged_union<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, tagged: &'ast TaggedUnion) {
+    for attribute in &tagged.attributes {
+        visitor.visit_attribute(attribute);
+    }
+    for member in &tagged.members {
+        visitor.visit_tagged_union_member(member);
+    }
+}
+
+pub fn walk_tagged_union_member<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, member: &'ast TaggedUnionMember) {
+    for member in member.value.iter().flatten() {
+        visitor.visit_member(member);
+    }
+}
+
+pub fn walk_variable_list<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, variable_list: &'ast VariableList) {
+    visitor.visit_declaration_specifiers(&variable_list.specifiers);
+    for variable in &variable_list.variables {
+        visitor.visit_variable(variable);
+    }
+}
+
+pub fn walk_variable<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, variable: &'ast Variable) {
+    for attribute in &variable.attributes {
+        visitor.visit_attribute(attribute);
+    }
+    visitor.visit_declared_type(&variable.type_);
+    if let Some(value) = &variable.value {
+        visitor.visit_variable_value(value);
+    }
+}
+
+pub fn walk_variable_value<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, value: &'ast VariableValue) {
+    match value {
+        VariableValue::Expression(expression) => visitor.visit_expression(expression),
+        VariableValue::String(_) => {},
+    }
+}
+
+pub fn walk_variable_array<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, array: &'ast VariableArray) {
+    match array {
+        VariableArray::Size(size) => visitor.visit_expression(size),
+        VariableArray::NoSize => {},
+    }
+}
+
+pub fn walk_function_prototype<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, prototype: &'ast FunctionPrototype) {
+    visitor.visit_declaration_specifiers(&prototype.specifiers);
+    visitor.visit_declared_type(&prototype.return_type);
+    for argument in &prototype.arguments {
+        visitor.visit_function_argument(argument);
+    }
+}
+
+pub fn walk_function<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, function: &'ast Function) {
+    visitor.visit_declaration_specifiers(&function.specifiers);
+    visitor.visit_declared_type(&function.return_type);
+    for argument in &function.arguments {
+        visitor.visit_function_argument(argument);
+    }
+    visitor.visit_code_block(&function.body);
+}
+
+pub fn walk_function_argument<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, argument: &'ast FunctionArgument) {
+    match argument {
+        FunctionArgument::Parameter(parameter) => visitor.visit_parameter(parameter),
+        FunctionArgument::Ellipsis => {},
+    }
+}
+
+pub fn walk_parameter<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, parameter: &'ast Parameter) {
+    for attribute in &parameter.attributes {
+        visitor.visit_attribute(attribute);
+    }
+    visitor.visit_declared_type(&parameter.type_);
+}
+
+pub fn walk_class<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, class: &'ast Class) {
+    for attribute in &class.attributes {
+        visitor.visit_attribute(attribute);
+    }
+    for member in &class.members {
+        visitor.visit_class_member(member);
+    }
+}
+
+pub fn walk_class_member<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, member: &'ast ClassMember) {
+    match member {
+        ClassMember::Field(member) => visitor.visit_member(member),
+        ClassMember::Method(method) => visitor.visit_method(method),
+        ClassMember::OperatorOverload(overload) => visitor.visit_operator_overload(overload),
+    }
+}
+
+pub fn walk_method<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, method: &'ast Method) {
+    match method {
+        Method::Normal(function) => visitor.visit_function(function),
+        Method::Abstract(prototype) => visitor.visit_function_prototype(prototype),
+    }
+}
+
+pub fn walk_operator_overload<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, overload: &'ast OperatorOverload) {
+    let (OperatorOverload::Normal { attributes, return_type, arguments, .. } |
+         OperatorOverload::Abstract { attributes, return_type, arguments, .. }) = overload;
+    for attribute in attributes {
+        visitor.visit_attribute(attribute);
+    }
+    visitor.visit_declared_type(return_type);
+    for argument in arguments {
+        visitor.visit_function_argument(argument);
+    }
+    if let OperatorOverload::Normal { body, .. } = overload {
+        visitor.visit_code_block(body);
+    }
+}
+
+pub fn walk_declaration_specifiers<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, specifiers: &'ast DeclarationSpecifiers) {
+    for alignas in &specifiers.alignas {
+        visitor.visit_type_or_expression(alignas);
+    }
+    for attribute in &specifiers.attributes {
+        visitor.visit_attribute(attribute);
+    }
+    visitor.visit_type(&specifiers.type_);
+    if let Some(definition) = &specifiers.definition {
+        visitor.visit_definition(definition);
+    }
+}
+
+pub fn walk_type<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, type_: &'ast Type) {
+    visitor.visit_type_specifier(&type_.specifier);
+}
+
+pub fn walk_type_specifier<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, specifier: &'ast TypeSpecifier) {
+    if let TypeSpecifier::Typeof(operand) | TypeSpecifier::TypeofUnqual(operand) = specifier {
+        visitor.visit_type_or_expression(operand);
+    }
+}
+
+pub fn walk_declared_type<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, type_: &'ast DeclaredType) {
+    match type_ {
+        DeclaredType::Base(type_) => visitor.visit_type(type_),
+        DeclaredType::Pointer(inner, _) => visitor.visit_declared_type(inner),
+        DeclaredType::Array(inner, array) => {
+            visitor.visit_declared_type(inner);
+            visitor.visit_variable_array(array);
+        },
+        DeclaredType::Function(inner, arguments) => {
+            visitor.visit_declared_type(inner);
+            for argument in arguments {
+                visitor.visit_function_argument(argument);
+            }
+        },
+    }
+}
+
+pub fn walk_type_or_expression<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, operand: &'ast TypeOrExpression) {
+    match operand {
+        TypeOrExpression::Type(type_) => visitor.visit_declared_type(type_),
+        TypeOrExpression::Expression(expression) => visitor.visit_expression(expression),
+    }
+}
+
+pub fn walk_code_block<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, block: &'ast CodeBlock) {
+    let CodeBlock::Code(list) = block;
+    visitor.visit_statement_list(list);
+}
+
+pub fn walk_statement_list<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, list: &'ast StatementList) {
+    for statement in &list.statements {
+        visitor.visit_statement(statement);
+    }
+}
+
+pub fn walk_block_or_statement<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, body: &'ast BlockOrStatement) {
+    match body {
+        BlockOrStatement::Block(block) => visitor.visit_code_block(block),
+        BlockOrStatement::Statement(statement) => visitor.visit_statement(statement),
+    }
+}
+
+pub fn walk_variable_list_or_statement<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, init: &'ast VariableListOrStatement) {
+    match init {
+        VariableListOrStatement::VariableList(variable_list) => visitor.visit_variable_list(variable_list),
+        VariableListOrStatement::Statement(statement) => visitor.visit_statement(statement),
+    }
+}
+
+pub fn walk_statement<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, statement: &'ast Statement) {
+    match statement {
+        Statement::Preprocessor(preprocessor) => visitor.visit_preprocessor(preprocessor),
+        Statement::VariableList(variable_list) => visitor.visit_variable_list(variable_list),
+        Statement::Expression(expression) | Statement::Return(expression) => visitor.visit_expression(expression),
+        Statement::If(condition, body, else_) => {
+            visitor.visit_expression(condition);
+            visitor.visit_block_or_statement(body);
+            if let Some(else_) = else_ {
+                visitor.visit_block_or_statement(else_);
+            }
+        },
+        Statement::While(condition, body) => {
+            visitor.visit_expression(condition);
+            visitor.visit_block_or_statement(body);
+        },
+        Statement::DoWhile(condition, body) => {
+            visitor.visit_block_or_statement(body);
+            visitor.visit_expression(condition);
+        },
+        Statement::For(init, condition, step, body) => {
+            if let Some(init) = init {
+                visitor.visit_variable_list_or_statement(init);
+            }
+            if let Some(condition) = condition {
+                visitor.visit_expression(condition);
+            }
+            if let Some(step) = step {
+                visitor.visit_expression(step);
+            }
+            visitor.visit_block_or_statement(body);
+        },
+        Statement::Switch(expression, cases) => {
+            visitor.visit_expression(expression);
+            for case in cases {
+                visitor.visit_switch_case(case);
+            }
+        },
+        Statement::StaticAssert(static_assert) => visitor.visit_static_assert(static_assert),
+        Statement::Attributes(attributes) => {
+            for attribute in attributes {
+                visitor.visit_attribute(attribute);
+            }
+        },
+        Statement::Attributed(attributes, statement) => {
+            for attribute in attributes {
+                visitor.visit_attribute(attribute);
+            }
+            visitor.visit_statement(statement);
+        },
+        Statement::Block(block) => visitor.visit_code_block(block),
+        Statement::Break | Statement::Continue | Statement::Goto(_) | Statement::Label(_) => {},
+    }
+}
+
+pub fn walk_switch_case<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, case: &'ast SwitchCase) {
+    if let Some(expression) = &case.expression {
+        visitor.visit_expression(expression);
+    }
+    visitor.visit_block_or_statement(&case.body);
+}
+
+pub fn walk_expression<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, expression: &'ast Expression) {
+    match expression {
+        Expression::Literal(literal) => visitor.visit_literal(literal),
+        Expression::Sizeof(operand) => visitor.visit_type_or_expression(operand),
+        Expression::Alignof(type_) | Expression::Offsetof(type_, _) => visitor.visit_declared_type(type_),
+        Expression::Unary(operator, operand) => {
+            visitor.visit_unary_operator(operator);
+            visitor.visit_expression(operand);
+        },
+        Expression::Binary(operator, left, right) => {
+            visitor.visit_expression(left);
+            visitor.visit_binary_operator(operator);
+            visitor.visit_expression(right);
+        },
+        Expression::Ternary(condition, then, otherwise) => {
+            visitor.visit_expression(condition);
+            visitor.visit_expression(then);
+            visitor.visit_expression(otherwise);
+        },
+        Expression::CallFunction(_, arguments) => {
+            if let Some(arguments) = arguments {
+                visitor.visit_expression(arguments);
+            }
+        },
+        Expression::Call(callee, arguments) => {
+            visitor.visit_expression(callee);
+            if let Some(arguments) = arguments {
+                visitor.visit_expression(arguments);
+            }
+        },
+        Expression::Generic(controlling, associations) => {
+            visitor.visit_expression(controlling);
+            for association in associations {
+                visitor.visit_generic_association(association);
+            }
+        },
+        Expression::InitializerList(operand) | Expression::TaggedInitializer(_, operand) |
+        Expression::Expression(operand) | Expression::Parentheses(operand) => visitor.visit_expression(operand),
+        Expression::StatementList(list) => visitor.visit_statement_list(list),
+        Expression::Blank | Expression::Identifier(_) => {},
+    }
+}
+
+pub fn walk_generic_association<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, association: &'ast GenericAssociation) {
+    if let Some(type_) = &association.type_ {
+        visitor.visit_declared_type(type_);
+    }
+    visitor.visit_expression(&association.expression);
+}
+
+pub fn walk_unary_operator<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, operator: &'ast UnaryOperator) {
+    if let UnaryOperator::Cast(type_) = operator {
+        visitor.visit_declared_type(type_);
+    }
+}
+
+pub fn walk_initializer<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, initializer: &'ast Initializer) {
+    match initializer {
+        Initializer::Expression(expression) => visitor.visit_expression(expression),
+        Initializer::List(initializers) => {
+            for initializer in initializers {
+                visitor.visit_initializer(initializer);
+            }
+        },
+        Initializer::Designated(designator, initializer) => {
+            visitor.visit_designator(designator);
+            visitor.visit_initializer(initializer);
+        },
+    }
+}
+
+pub fn walk_designator<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, designator: &'ast Designator) {
+    match designator {
+        Designator::Member(_) => {},
+        Designator::Index(index) => visitor.visit_expression(index),
+    }
+}
+
+
+pub trait VisitMut {
+    fn visit_header_mut(&mut self, header: &mut Header) {
+        walk_header_mut(self, header);
+    }
+
+    fn visit_header_statement_mut(&mut self, statement: &mut HeaderStatement) {
+        walk_header_statement_mut(self, statement);
+    }
+
+    fn visit_preprocessor_mut(&mut self, _preprocessor: &mut Preprocessor) {}
+
+    fn visit_static_assert_mut(&mut self, static_assert: &mut StaticAssert) {
+        walk_static_assert_mut(self, static_assert);
+    }
+
+    fn visit_struct_mut(&mut self, struct_: &mut Struct) {
+        walk_struct_mut(self, struct_);
+    }
+
+    fn visit_union_mut(&mut self, union_: &mut Union) {
+        walk_union_mut(self, union_);
+    }
+
+    fn visit_member_mut(&mut self, member: &mut Member) {
+        walk_member_mut(self, member);
+    }
+
+    fn visit_bit_field_list_mut(&mut self, bit_fields: &mut BitFieldList) {
+        walk_bit_field_list_mut(self, bit_fields);
+    }
+
+    fn visit_bit_field_mut(&mut self, bit_field: &mut BitField) {
+        walk_bit_field_mut(self, bit_field);
+    }
+
+    fn visit_definition_mut(&mut self, definition: &mut Definition) {
+        walk_definition_mut(self, definition);
+    }
+
+    fn visit_enum_mut(&mut self, enum_: &mut Enum) {
+        walk_enum_mut(self, enum_);
+    }
+
+    fn visit_enum_member_mut(&mut self, member: &mut EnumMember) {
+        walk_enum_member_mut(self, member);
+    }
+
+    fn visit_tagged_union_mut(&mut self, tagged: &mut TaggedUnion) {
+        walk_tagged_union_mut(self, tagged);
+    }
+
+    fn visit_tagged_union_member_mut(&mut self, member: &mut TaggedUnionMember) {
+        walk_tagged_union_member_mut(self, member);
+    }
+
+    fn visit_variable_list_mut(&mut self, variable_list: &mut VariableList) {
+        walk_variable_list_mut(self, variable_list);
+    }
+
+    fn visit_variable_mut(&mut self, variable: &mut Variable) {
+        walk_variable_mut(self, variable);
+    }
+
+    fn visit_variable_value_mut(&mut self, value: &mut VariableValue) {
+        walk_variable_value_mut(self, value);
+    }
+
+    fn visit_variable_array_mut(&mut self, array: &mut VariableArray) {
+        walk_variable_array_mut(self, array);
+    }
+
+    fn visit_function_prototype_mut(&mut self, prototype: &mut FunctionPrototype) {
+        walk_function_prototype_mut(self, prototype);
+    }
+
+    fn visit_function_mut(&mut self, function: &mut Function) {
+        walk_function_mut(self, function);
+    }
+
+    fn visit_function_argument_mut(&mut self, argument: &mut FunctionArgument) {
+        walk_function_argument_mut(self, argument);
+    }
+
+    fn visit_parameter_mut(&mut self, parameter: &mut Parameter) {
+        walk_parameter_mut(self, parameter);
+    }
+
+    fn visit_class_mut(&mut self, class: &mut Class) {
+        walk_class_mut(self, class);
+    }
+
+    fn visit_class_member_mut(&mut self, member: &mut ClassMember) {
+        walk_class_member_mut(self, member);
+    }
+
+    fn visit_method_mut(&mut self, method: &mut Method) {
+        walk_method_mut(self, method);
+    }
+
+    fn visit_operator_overload_mut(&mut self, overload: &mut OperatorOverload) {
+        walk_operator_overload_mut(self, overload);
+    }
+
+    fn visit_declaration_specifiers_mut(&mut self, specifiers: &mut DeclarationSpecifiers) {
+        walk_declaration_specifiers_mut(self, specifiers);
+    }
+
+    fn visit_attribute_mut(&mut self, _attribute: &mut Attribute) {}
+
+    fn visit_type_mut(&mut self, type_: &mut Type) {
+        walk_type_mut(self, type_);
+    }
+
+    fn visit_type_specifier_mut(&mut self, specifier: &mut TypeSpecifier) {
+        walk_type_specifier_mut(self, specifier);
+    }
+
+    fn visit_declared_type_mut(&mut self, type_: &mut DeclaredType) {
+        walk_declared_type_mut(self, type_);
+    }
+
+    fn visit_type_or_expression_mut(&mut self, operand: &mut TypeOrExpression) {
+        walk_type_or_expression_mut(self, operand);
+    }
+
+    fn visit_code_block_mut(&mut self, block: &mut CodeBlock) {
+        walk_code_block_mut(self, block);
+    }
+
+    fn visit_statement_list_mut(&mut self, list: &mut StatementList) {
+        walk_statement_list_mut(self, list);
+    }
+
+    fn visit_block_or_statement_mut(&mut self, body: &mut BlockOrStatement) {
+        walk_block_or_statement_mut(self, body);
+    }
+
+    fn visit_variable_list_or_statement_mut(&mut self, init: &mut VariableListOrStatement) {
+        walk_variable_list_or_statement_mut(self, init);
+    }
+
+    fn visit_statement_mut(&mut self, statement: &mut Statement) {
+        walk_statement_mut(self, statement);
+    }
+
+    fn visit_switch_case_mut(&mut self, case: &mut SwitchCase) {
+        walk_switch_case_mut(self, case);
+    }
+
+    fn visit_expression_mut(&mut self, expression: &mut Expression) {
+        walk_expression_mut(self, expression);
+    }
+
+    fn visit_generic_association_mut(&mut self, association: &mut GenericAssociation) {
+        walk_generic_association_mut(self, association);
+    }
+
+    fn visit_literal_mut(&mut self, _literal: &mut Literal) {}
+
+    fn visit_unary_operator_mut(&mut self, operator: &mut UnaryOperator) {
+        walk_unary_operator_mut(self, operator);
+    }
+
+    fn visit_binary_operator_mut(&mut self, _operator: &mut BinaryOperator) {}
+
+    fn visit_initializer_mut(&mut self, initializer: &mut Initializer) {
+        walk_initializer_mut(self, initializer);
+    }
+
+    fn visit_designator_mut(&mut self, designator: &mut Designator) {
+        walk_designator_mut(self, designator);
+    }
+}
+
+pub fn walk_header_mut<V: VisitMut + ?Sized>(visitor: &mut V, header: &mut Header) {
+    for statement in &mut header.statements {
+        visitor.visit_header_statement_mut(statement);
+    }
+}
+
+pub fn walk_header_statement_mut<V: VisitMut + ?Sized>(visitor: &mut V, statement: &mut HeaderStatement) {
+    match statement {
+        HeaderStatement::Preprocessor(preprocessor) => visitor.visit_preprocessor_mut(preprocessor),
+        HeaderStatement::Struct(struct_) => visitor.visit_struct_mut(struct_),
+        HeaderStatement::Union(union_) => visitor.visit_union_mut(union_),
+        HeaderStatement::Enum(enum_) => visitor.visit_enum_mut(enum_),
+        HeaderStatement::Variable(variable_list) => visitor.visit_variable_list_mut(variable_list),
+        HeaderStatement::FunctionPrototype(prototype) => visitor.visit_function_prototype_mut(prototype),
+        HeaderStatement::Function(function) => visitor.visit_function_mut(function),
+        HeaderStatement::Class(class) => visitor.visit_class_mut(class),
+        HeaderStatement::TaggedUnion(tagged) => visitor.visit_tagged_union_mut(tagged),
+        HeaderStatement::StaticAssert(static_assert) => visitor.visit_static_assert_mut(static_assert),
+        HeaderStatement::Whitespace => {},
+    }
+}
+
+pub fn walk_static_assert_mut<V: VisitMut + ?Sized>(visitor: &mut V, static_assert: &mut StaticAssert) {
+    visitor.visit_expression_mut(&mut static_assert.condition);
+}
+
+pub fn walk_struct_mut<V: VisitMut + ?Sized>(visitor: &mut V, struct_: &mut Struct) {
+    for attribute in &mut struct_.attributes {
+        visitor.visit_attribute_mut(attribute);
+    }
+    for member in &mut struct_.members {
+        visitor.visit_member_mut(member);
+    }
+}
+
+pub fn walk_union_mut<V: VisitMut + ?Sized>(visitor: &mut V, union_: &mut Union) {
+    for attribute in &mut union_.attributes {
+        visitor.visit_attribute_mut(attribute);
+    }
+    for member in &mut union_.members {
+        visitor.visit_member_mut(member);
+    }
+}
+
+pub fn walk_member_mut<V: VisitMut + ?Sized>(visitor: &mut V, member: &mut Member) {
+    match member {
+        Member::Variables(variable_list) => visitor.visit_variable_list_mut(variable_list),
+        Member::BitFields(bit_fields) => visitor.visit_bit_field_list_mut(bit_fields),
+        Member::Struct(struct_) => visitor.visit_struct_mut(struct_),
+        Member::Union(union_) => visitor.visit_union_mut(union_),
+    }
+}
+
+pub fn walk_bit_field_list_mut<V: VisitMut + ?Sized>(visitor: &mut V, bit_fields: &mut BitFieldList) {
+    visitor.visit_declaration_specifiers_mut(&mut bit_fields.specifiers);
+    for bit_field in &mut bit_fields.fields {
+        visitor.visit_bit_field_mut(bit_field);
+    }
+}
+
+pub fn walk_bit_field_mut<V: VisitMut + ?Sized>(visitor: &mut V, bit_field: &mut BitField) {
+    visitor.visit_expression_mut(&mut bit_field.width);
+}
+
+pub fn walk_definition_mut<V: VisitMut + ?Sized>(visitor: &mut V, definition: &mut Definition) {
+    match definition {
+        Definition::Struct(struct_) => visitor.visit_struct_mut(struct_),
+        Definition::Union(union_) => visitor.visit_union_mut(union_),
+        Definition::Enum(enum_) => visitor.visit_enum_mut(enum_),
+        Definition::TaggedUnion(tagged) => visitor.visit_tagged_union_mut(tagged),
+    }
+}
+
+pub fn walk_enum_mut<V: VisitMut + ?Sized>(visitor: &mut V, enum_: &mut Enum) {
+    for attribute in &mut enum_.attributes {
+        visitor.visit_attribute_mut(attribute);
+    }
+    for member in &mut enum_.members {
+        visitor.visit_enum_member_mut(member);
+    }
+}
+
+pub fn walk_enum_member_mut<V: VisitMut + ?Sized>(visitor: &mut V, member: &mut EnumMember) {
+    for attribute in &mut member.attributes {
+        visitor.visit_attribute_mut(attribute);
+    }
+    if let Some(value) = &mut member.value {
+        visitor.visit_expression_mut(value);
+    }
+}
+
+pub fn walk_tagged_union_mut<V: VisitMut + ?Sized>(visitor: &mut V, tagged: &mut TaggedUnion) {
+    for attribute in &mut tagged.attributes {
+        visitor.visit_attribute_mut(attribute);
+    }
+    for member in &mut tagged.members {
+        visitor.visit_tagged_union_member_mut(member);
+    }
+}
+
+pub fn walk_tagged_union_member_mut<V: VisitMut + ?Sized>(visitor: &mut V, member: &mut TaggedUnionMember) {
+    for member in member.value.iter_mut().flatten() {
+        visitor.visit_member_mut(member);
+    }
+}
+
+pub fn walk_variable_list_mut<V: VisitMut + ?Sized>(visitor: &mut V, variable_list: &mut VariableList) {
+    visitor.visit_declaration_specifiers_mut(&mut variable_list.specifiers);
+    for variable in &mut variable_list.variables {
+        visitor.visit_variable_mut(variable);
+    }
+}
+
+pub fn walk_variable_mut<V: VisitMut + ?Sized>(visitor: &mut V, variable: &mut Variable) {
+    for attribute in &mut variable.attributes {
+        visitor.visit_attribute_mut(attribute);
+    }
+    visitor.visit_declared_type_mut(&mut variable.type_);
+    if let Some(value) = &mut variable.value {
+        visitor.visit_variable_value_mut(value);
+    }
+}
+
+pub fn walk_variable_value_mut<V: VisitMut + ?Sized>(visitor: &mut V, value: &mut VariableValue) {
+    match value {
+        VariableValue::Expression(expression) => visitor.visit_expression_mut(expression),
+        VariableValue::String(_) => {},
+    }
+}
+
+pub fn walk_variable_array_mut<V: VisitMut + ?Sized>(visitor: &mut V, array: &mut VariableArray) {
+    match array {
+        VariableArray::Size(size) => visitor.visit_expression_mut(size),
+        VariableArray::NoSize => {},
+    }
+}
+
+pub fn walk_function_prototype_mut<V: VisitMut + ?Sized>(visitor: &mut V, prototype: &mut FunctionPrototype) {
+    visitor.visit_declaration_specifiers_mut(&mut prototype.specifiers);
+    visitor.visit_declared_type_mut(&mut prototype.return_type);
+    for argument in &mut prototype.arguments {
+        visitor.visit_function_argument_mut(argument);
+    }
+}
+
+pub fn walk_function_mut<V: VisitMut + ?Sized>(visitor: &mut V, function: &mut Function) {
+    visitor.visit_declaration_specifiers_mut(&mut function.specifiers);
+    visitor.visit_declared_type_mut(&mut function.return_type);
+    for argument in &mut function.arguments {
+        visitor.visit_function_argument_mut(argument);
+    }
+    visitor.visit_code_block_mut(&mut function.body);
+}
+
+pub fn walk_function_argument_mut<V: VisitMut + ?Sized>(visitor: &mut V, argument: &mut FunctionArgument) {
+    match argument {
+        FunctionArgument::Parameter(parameter) => visitor.visit_parameter_mut(parameter),
+        FunctionArgument::Ellipsis => {},
+    }
+}
+
+pub fn walk_parameter_mut<V: VisitMut + ?Sized>(visitor: &mut V, parameter: &mut Parameter) {
+    for attribute in &mut parameter.attributes {
+        visitor.visit_attribute_mut(attribute);
+    }
+    visitor.visit_declared_type_mut(&mut parameter.type_);
+}
+
+pub fn walk_class_mut<V: VisitMut + ?Sized>(visitor: &mut V, class: &mut Class) {
+    for attribute in &mut class.attributes {
+        visitor.visit_attribute_mut(attribute);
+    }
+    for member in &mut class.members {
+        visitor.visit_class_member_mut(member);
+    }
+}
+
+pub fn walk_class_member_mut<V: VisitMut + ?Sized>(visitor: &mut V, member: &mut ClassMember) {
+    match member {
+        ClassMember::Field(member) => visitor.visit_member_mut(member),
+        ClassMember::Method(method) => visitor.visit_method_mut(method),
+        ClassMember::OperatorOverload(overload) => visitor.visit_operator_overload_mut(overload),
+    }
+}
+
+pub fn walk_method_mut<V: VisitMut + ?Sized>(visitor: &mut V, method: &mut Method) {
+    match method {
+        Method::Normal(function) => visitor.visit_function_mut(function),
+        Method::Abstract(prototype) => visitor.visit_function_prototype_mut(prototype),
+    }
+}
+
+pub fn walk_operator_overload_mut<V: VisitMut + ?Sized>(visitor: &mut V, overload: &mut OperatorOverload) {
+    let (OperatorOverload::Normal { attributes, return_type, arguments, .. } |
+         OperatorOverload::Abstract { attributes, return_type, arguments, .. }) = overload;
+    for attribute in attributes {
+        visitor.visit_attribute_mut(attribute);
+    }
+    visitor.visit_declared_type_mut(return_type);
+    for argument in arguments {
+        visitor.visit_function_argument_mut(argument);
+    }
+    if let OperatorOverload::Normal { body, .. } = overload {
+        visitor.visit_code_block_mut(body);
+    }
+}
+
+pub fn walk_declaration_specifiers_mut<V: VisitMut + ?Sized>(visitor: &mut V, specifiers: &mut DeclarationSpecifiers) {
+    for alignas in &mut specifiers.alignas {
+        visitor.visit_type_or_expression_mut(alignas);
+    }
+    for attribute in &mut specifiers.attributes {
+        visitor.visit_attribute_mut(attribute);
+    }
+    visitor.visit_type_mut(&mut specifiers.type_);
+    if let Some(definition) = &mut specifiers.definition {
+        visitor.visit_definition_mut(definition);
+    }
+}
+
+pub fn walk_type_mut<V: VisitMut + ?Sized>(visitor: &mut V, type_: &mut Type) {
+    visitor.visit_type_specifier_mut(&mut type_.specifier);
+}
+
+pub fn walk_type_specifier_mut<V: VisitMut + ?Sized>(visitor: &mut V, specifier: &mut TypeSpecifier) {
+    if let TypeSpecifier::Typeof(operand) | TypeSpecifier::TypeofUnqual(operand) = specifier {
+        visitor.visit_type_or_expression_mut(operand);
+    }
+}
+
+pub fn walk_declared_type_mut<V: VisitMut + ?Sized>(visitor: &mut V, type_: &mut DeclaredType) {
+    match type_ {
+        DeclaredType::Base(type_) => visitor.visit_type_mut(type_),
+        DeclaredType::Pointer(inner, _) => visitor.visit_declared_type_mut(inner),
+        DeclaredType::Array(inner, array) => {
+            visitor.visit_declared_type_mut(inner);
+            visitor.visit_variable_array_mut(array);
+        },
+        DeclaredType::Function(inner, arguments) => {
+            visitor.visit_declared_type_mut(inner);
+            for argument in arguments {
+                visitor.visit_function_argument_mut(argument);
+            }
+        },
+    }
+}
+
+pub fn walk_type_or_expression_mut<V: VisitMut + ?Sized>(visitor: &mut V, operand: &mut TypeOrExpression) {
+    match operand {
+        TypeOrExpression::Type(type_) => visitor.visit_declared_type_mut(type_),
+        TypeOrExpression::Expression(expression) => visitor.visit_expression_mut(expression),
+    }
+}
+
+pub fn walk_code_block_mut<V: VisitMut + ?Sized>(visitor: &mut V, block: &mut CodeBlock) {
+    let CodeBlock::Code(list) = block;
+    visitor.visit_statement_list_mut(list);
+}
+
+pub fn walk_statement_list_mut<V: VisitMut + ?Sized>(visitor: &mut V, list: &mut StatementList) {
+    for statement in &mut list.statements {
+        visitor.visit_statement_mut(statement);
+    }
+}
+
+pub fn walk_block_or_statement_mut<V: VisitMut + ?Sized>(visitor: &mut V, body: &mut BlockOrStatement) {
+    match body {
+        BlockOrStatement::Block(block) => visitor.visit_code_block_mut(block),
+        BlockOrStatement::Statement(statement) => visitor.visit_statement_mut(statement),
+    }
+}
+
+pub fn walk_variable_list_or_statement_mut<V: VisitMut + ?Sized>(visitor: &mut V, init: &mut VariableListOrStatement) {
+    match init {
+        VariableListOrStatement::VariableList(variable_list) => visitor.visit_variable_list_mut(variable_list),
+        VariableListOrStatement::Statement(statement) => visitor.visit_statement_mut(statement),
+    }
+}
+
+pub fn walk_statement_mut<V: VisitMut + ?Sized>(visitor: &mut V, statement: &mut Statement) {
+    match statement {
+        Statement::Preprocessor(preprocessor) => visitor.visit_preprocessor_mut(preprocessor),
+        Statement::VariableList(variable_list) => visitor.visit_variable_list_mut(variable_list),
+        Statement::Expression(expression) | Statement::Return(expression) => visitor.visit_expression_mut(expression),
+        Statement::If(condition, body, else_) => {
+            visitor.visit_expression_mut(condition);
+            visitor.visit_block_or_statement_mut(body);
+            if let Some(else_) = else_ {
+                visitor.visit_block_or_statement_mut(else_);
+            }
+        },
+        Statement::While(condition, body) => {
+            visitor.visit_expression_mut(condition);
+            visitor.visit_block_or_statement_mut(body);
+        },
+        Statement::DoWhile(condition, body) => {
+            visitor.visit_block_or_statement_mut(body);
+            visitor.visit_expression_mut(condition);
+        },
+        Statement::For(init, condition, step, body) => {
+            if let Some(init) = init {
+                visitor.visit_variable_list_or_statement_mut(init);
+            }
+            if let Some(condition) = condition {
+                visitor.visit_expression_mut(condition);
+            }
+            if let Some(step) = step {
+                visitor.visit_expression_mut(step);
+            }
+            visitor.visit_block_or_statement_mut(body);
+        },
+        Statement::Switch(expression, cases) => {
+            visitor.visit_expression_mut(expression);
+            for case in cases {
+                visitor.visit_switch_case_mut(case);
+            }
+        },
+        Statement::StaticAssert(static_assert) => visitor.visit_static_assert_mut(static_assert),
+        Statement::Attributes(attributes) => {
+            for attribute in attributes {
+                visitor.visit_attribute_mut(attribute);
+            }
+        },
+        Statement::Attributed(attributes, statement) => {
+            for attribute in attributes {
+                visitor.visit_attribute_mut(attribute);
+            }
+            visitor.visit_statement_mut(statement);
+        },
+        Statement::Block(block) => visitor.visit_code_block_mut(block),
+        Statement::Break | Statement::Continue | Statement::Goto(_) | Statement::Label(_) => {},
+    }
+}
+
+pub fn walk_switch_case_mut<V: VisitMut + ?Sized>(visitor: &mut V, case: &mut SwitchCase) {
+    if let Some(expression) = &mut case.expression {
+        visitor.visit_expression_mut(expression);
+    }
+    visitor.visit_block_or_statement_mut(&mut case.body);
+}
+
+pub fn walk_expression_mut<V: VisitMut + ?Sized>(visitor: &mut V, expression: &mut Expression) {
+    match expression {
+        Expression::Literal(literal) => visitor.visit_literal_mut(literal),
+        Expression::Sizeof(operand) => visitor.visit_type_or_expression_mut(operand),
+        Expression::Alignof(type_) | Expression::Offsetof(type_, _) => visitor.visit_declared_type_mut(type_),
+        Expression::Unary(operator, operand) => {
+            visitor.visit_unary_operator_mut(operator);
+            visitor.visit_expression_mut(operand);
+        },
+        Expression::Binary(operator, left, right) => {
+            visitor.visit_expression_mut(left);
+            visitor.visit_binary_operator_mut(operator);
+            visitor.visit_expression_mut(right);
+        },
+        Expression::Ternary(condition, then, otherwise) => {
+            visitor.visit_expression_mut(condition);
+            visitor.visit_expression_mut(then);
+            visitor.visit_expression_mut(otherwise);
+        },
+        Expression::CallFunction(_, arguments) => {
+            if let Some(arguments) = arguments {
+                visitor.visit_expression_mut(arguments);
+            }
+        },
+        Expression::Call(callee, arguments) => {
+            visitor.visit_expression_mut(callee);
+            if let Some(arguments) = arguments {
+                visitor.visit_expression_mut(arguments);
+            }
+        },
+        Expression::Generic(controlling, associations) => {
+            visitor.visit_expression_mut(controlling);
+            for association in associations {
+                visitor.visit_generic_association_mut(association);
+            }
+        },
+        Expression::InitializerList(operand) | Expression::TaggedInitializer(_, operand) |
+        Expression::Expression(operand) | Expression::Parentheses(operand) => visitor.visit_expression_mut(operand),
+        Expression::StatementList(list) => visitor.visit_statement_list_mut(list),
+        Expression::Blank | Expression::Identifier(_) => {},
+    }
+}
+
+pub fn walk_generic_association_mut<V: VisitMut + ?Sized>(visitor: &mut V, association: &mut GenericAssociation) {
+    if let Some(type_) = &mut association.type_ {
+        visitor.visit_declared_type_mut(type_);
+    }
+    visitor.visit_expression_mut(&mut association.expression);
+}
+
+pub fn walk_unary_operator_mut<V: VisitMut + ?Sized>(visitor: &mut V, operator: &mut UnaryOperator) {
+    if let UnaryOperator::Cast(type_) = operator {
+        visitor.visit_declared_type_mut(type_);
+    }
+}
+
+pub fn walk_initializer_mut<V: VisitMut + ?Sized>(visitor: &mut V, initializer: &mut Initializer) {
+    match initializer {
+        Initializer::Expression(expression) => visitor.visit_expression_mut(expression),
+        Initializer::List(initializers) => {
+            for initializer in initializers {
+                visitor.visit_initializer_mut(initializer);
+            }
+        },
+        Initializer::Designated(designator, initializer) => {
+            visitor.visit_designator_mut(designator);
+            visitor.visit_initializer_mut(initializer);
+        },
+    }
+}
+
+pub fn walk_designator_mut<V: VisitMut + ?Sized>(visitor: &mut V, designator: &mut Designator) {
+    match designator {
+        Designator::Member(_) => {},
+        Designator::Index(index) => visitor.visit_expression_mut(index),
+    }
+}
+
+
+#[cfg(test)]
+mod visit_tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::logos_lexer::lex;
+    use crate::parser::Parser;
+    use crate::printer::print;
+
+    const SOURCE: &str = "#include <stdio.h>\n\
+        static_assert(sizeof(int) == 4, \"int is 32 bits\");\n\
+        struct flags { unsigned ready : 1; struct { int x; } inner; union { int i; float f; } [[deprecated]] u; } __attribute__((packed));\n\
+        union number { int i; double d; };\n\
+        enum color { RED, GREEN = 2 };\n\
+        tagged shape { circle { int radius; }, empty };\n\
+        alignas(16) typeof(int) values[4] = {1, 2}, *first;\n\
+        char greeting[] = \"hi\";\n\
+        int printf(const char *format, ...);\n\
+        class Counter {\n\
+            int count;\n\
+            int get() { return count; }\n\
+            int reset();\n\
+            int operator(+)(int x) { return count + in_operator; }\n\
+            int operator(-)(int x);\n\
+        };\n\
+        int main(int x) {\n\
+            tagged shape s;\n\
+            s = circle {1};\n\
+            if (x) x++; else { x--; }\n\
+            while (x < 10) x += 1;\n\
+            do { continue; } while (0);\n\
+            for (int i = 0; i < 2; i++) break;\n\
+            switch (x) { case 1: in_case = 2; [[fallthrough]]; default: { x = (int) alignof(long); } }\n\
+            static_assert(1);\n\
+            [[maybe_unused]] x = x ? sizeof x : _Generic(x, int: abs, default: labs)(x);\n\
+            again: goto again;\n\
+            { return (x + 'a') * 2.0; }\n\
+        }\n";
+
+    fn header() -> Header {
+        Parser::new(&lex(SOURCE).unwrap()).parse().unwrap()
+    }
+
+    /// Records the type of every node it is called on.
+    #[derive(Default)]
+    struct Kinds(BTreeSet<&'static str>);
+
+    fn leaf<T>(_: &mut Kinds, _: &T) {}
+
+    macro_rules! record {
+        ($($visit:ident: $type_:ty => $walk:expr,)*) => {
+            impl<'ast> Visitor<'ast> for Kinds {
+                $(fn $visit(&mut self, node: &'ast $type_) {
+                    self.0.insert(stringify!($type_));
+                    $walk(self, node);
+                })*
+            }
+        };
+    }
+
+    record! {
+        visit_header: Header => walk_header,
+        visit_header_statement: HeaderStatement => walk_header_statement,
+        visit_preprocessor: Preprocessor => leaf,
+        visit_static_assert: StaticAssert => walk_static_assert,
+        visit_struct: Struct => walk_struct,
+        visit_union: Union => walk_union,
+        visit_member: Member => walk_member,
+        visit_bit_field_list: BitFieldList => walk_bit_field_list,
+        visit_bit_field: BitField => walk_bit_field,
+        visit_definition: Definition => walk_definition,
+        visit_enum: Enum => walk_enum,
+        visit_enum_member: EnumMember => walk_enum_member,
+        visit_tagged_union: TaggedUnion => walk_tagged_union,
+        visit_tagged_union_member: TaggedUnionMember => walk_tagged_union_member,
+        visit_variable_list: VariableList => walk_variable_list,
+        visit_variable: Variable => walk_variable,
+        visit_variable_value: VariableValue => walk_variable_value,
+        visit_variable_array: VariableArray => walk_variable_array,
+        visit_function_prototype: FunctionPrototype => walk_function_prototype,
+        visit_function: Function => walk_function,
+        visit_function_argument: FunctionArgument => walk_function_argument,
+        visit_parameter: Parameter => walk_parameter,
+        visit_class: Class => walk_class,
+        visit_class_member: ClassMember => walk_class_member,
+        visit_method: Method => walk_method,
+        visit_operator_overload: OperatorOverload => walk_operator_overload,
+        visit_declaration_specifiers: DeclarationSpecifiers => walk_declaration_specifiers,
+        visit_attribute: Attribute => leaf,
+        visit_type: Type => walk_type,
+        visit_type_specifier: TypeSpecifier => walk_type_specifier,
+        visit_declared_type: DeclaredType => walk_declared_type,
+        visit_type_or_expression: TypeOrExpression => walk_type_or_expression,
+        visit_code_block: CodeBlock => walk_code_block,
+        visit_statement_list: StatementList => walk_statement_list,
+        visit_block_or_statement: BlockOrStatement => walk_block_or_statement,
+        visit_variable_list_or_statement: VariableListOrStatement => walk_variable_list_or_statement,
+        visit_statement: Statement => walk_statement,
+        visit_switch_case: SwitchCase => walk_switch_case,
+        visit_expression: Expression => walk_expression,
+        visit_generic_association: GenericAssociation => walk_generic_association,
+        visit_literal: Literal => leaf,
+        visit_unary_operator: UnaryOperator => walk_unary_operator,
+        visit_binary_operator: BinaryOperator => leaf,
+        visit_initializer: Initializer => walk_initializer,
+        visit_designator: Designator => walk_designator,
+    }
+
+    /// Collects the names used in expressions.
+    #[derive(Default)]
+    struct Names<'ast>(Vec<&'ast str>);
+
+    impl<'ast> Visitor<'ast> for Names<'ast> {
+        fn visit_expression(&mut self, expression: &'ast Expression) {
+            if let Expression::Identifier(name) | Expression::CallFunction(name, _) = expression {
+                self.0.push(name);
+            }
+            walk_expression(self, expression);
+        }
+    }
+
+    /// Renames a variable wherever an expression uses it.
+    struct Rename<'a>(&'a str, &'a str);
+
+    impl VisitMut for Rename<'_> {
+        fn visit_expression_mut(&mut self, expression: &mut Expression) {
+            match expression {
+                Expression::Identifier(name) if name == self.0 => *name = self.1.to_string(),
+                _ => walk_expression_mut(self, expression),
+            }
+        }
+    }
+
+    #[test]
+    fn test_visitor_reaches_every_node_kind() {
+        let header = header();
+        // Initializers only appear on their own.
+        let initializer = Initializer::List(vec![
+            Initializer::Designated(Designator::Index(Box::new(Expression::Identifier("i".to_string()))),
+                                    Box::new(Initializer::Expression(Box::new(Expression::Blank)))),
+        ]);
+
+        let mut kinds = Kinds::default();
+        kinds.visit_header(&header);
+        kinds.visit_initializer(&initializer);
+        let unreached = ["Header", "HeaderStatement", "Preprocessor", "StaticAssert",
+            "Struct", "Union", "Member", "BitFieldList", "BitField", "Definition", "Enum", "EnumMember",
+            "TaggedUnion", "TaggedUnionMember", "VariableList", "Variable", "VariableValue", "VariableArray",
+            "FunctionPrototype", "Function", "FunctionArgument", "Parameter", "Class", "ClassMember", "Method",
+            "OperatorOverload", "DeclarationSpecifiers", "Attribute", "Type", "TypeSpecifier", "DeclaredType",
+            "TypeOrExpression", "CodeBlock", "StatementList", "BlockOrStatement", "VariableListOrStatement",
+            "Statement", "SwitchCase", "Expression", "GenericAssociation", "Literal", "UnaryOperator",
+            "BinaryOperator", "Initializer", "Designator"]
+            .into_iter()
+            .filter(|kind| !kinds.0.contains(kind))
+            .collect::<Vec<&str>>();
+        assert_eq!(unreached, Vec::<&str>::new());
+
+        let header = self::header();
+        let mut names = Names::default();
+        names.visit_header(&header);
+        assert!(names.0.contains(&"in_case"), "{:?}", names.0);
+        assert!(names.0.contains(&"in_operator"), "{:?}", names.0);
+        assert!(names.0.contains(&"abs"), "{:?}", names.0);
+    }
+
+    #[test]
+    fn test_visit_mut_rewrites_in_place() {
+        let mut header = header();
+        Rename("in_case", "renamed_case").visit_header_mut(&mut header);
+        Rename("in_operator", "renamed_operator").visit_header_mut(&mut header);
+
+        let printed = print(&header);
+        assert!(!printed.contains("in_case") && !printed.contains("in_operator"), "{}", printed);
+        assert_eq!(printed, print(&Parser::new(&lex(&printed).unwrap()).parse().unwrap()));
+        let mut names = Names::default();
+        names.visit_header(&header);
+        assert!(names.0.contains(&"renamed_case") && names.0.contains(&"renamed_operator"), "{:?}", names.0);
+    }
+}
@@ -0,0 +1,264 @@
+//! Checks that the parser groups plain C the way a C compiler does. Random expressions and
+//! declarations are written with as few parentheses as C allows, parsed with `Parser`, and
+//! printed back with `Style::parenthesize` so that every grouping is spelled out. Both texts
+//! go to the compiler in `$CC` (by default `gcc`):
+//!
+//! - an expression is compiled in two functions, and the trees that `-fdump-tree-original`
+//!   gives for them have to be the same;
+//! - a declaration is made twice under two names, and `__builtin_types_compatible_p` has to find the same type.
+//!
+//! Cases the compiler rejects are discarded. A mismatch fails with the smallest case proptest
+//! shrinks it to. The test passes with a note when no compiler is found, and `PROPTEST_CASES`
+//! sets the number of cases.
+
+use std::fs;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+
+use proptest::prelude::*;
+use proptest::test_runner::{FileFailurePersistence, TestCaseError};
+
+use c_with_classes::logos_lexer::lex_with_trivia;
+use c_with_classes::parser::Parser;
+use c_with_classes::printer::{print_with_style, Style};
+
+
+/// The levels of the C expression grammar from loosest to tightest, with the binary
+/// operators in between. An operand below the level its operator expects is parenthesized.
+const COMMA: u8 = 0;
+const ASSIGNMENT: u8 = 1;
+const CONDITIONAL: u8 = 2;
+const CAST: u8 = 13;
+const UNARY: u8 = 14;
+const POSTFIX: u8 = 15;
+const PRIMARY: u8 = 16;
+
+const BINARY_OPERATORS: [(&str, u8); 18] = [
+    ("||", 3), ("&&", 4), ("|", 5), ("^", 6), ("&", 7), ("==", 8), ("!=", 8), ("<", 9), (">", 9), ("<=", 9),
+    (">=", 9), ("<<", 10), (">>", 10), ("+", 11), ("-", 11), ("*", 12), ("/", 12), ("%", 12),
+];
+const ASSIGNMENT_OPERATORS: [&str; 11] = ["=", "+=", "-=", "*=", "/=", "%=", "<<=", ">>=", "&=", "|=", "^="];
+const VARIABLES: [&str; 4] = ["a", "b", "c", "d"];
+
+/// The base types of generated declarations and the parameter types of generated functions.
+const BASE_TYPES: [&str; 9] = ["int", "char", "short", "long", "unsigned", "unsigned long long", "double", "const int",
+                               "volatile char"];
+const PARAMETER_TYPES: [&str; 7] = ["int", "const char *", "double", "long *", "int (*)[4]", "void (*)(int)",
+                                    "unsigned int"];
+
+/// An expression as source text and the level it binds at.
+type Source = (String, u8);
+
+/// The text of an operand where its operator expects `level`.
+fn operand((text, own): Source, level: u8) -> String {
+    match own < level {
+        true => format!("({})", text),
+        false => text,
+    }
+}
+
+/// Integer expressions over the variables `a` to `d`, with the parentheses C needs and no others.
+fn expression() -> impl Strategy<Value = Source> {
+    let variable = prop::sample::select(&VARIABLES[..]);
+    let leaf = prop_oneof![
+        4 => variable.clone().prop_map(|name| (name.to_string(), PRIMARY)),
+        2 => (0..100u32).prop_map(|number| (number.to_string(), PRIMARY)),
+        1 => (variable.clone(), prop::sample::select(&["++", "--"][..]), any::<bool>())
+            .prop_map(|(name, operator, prefix)| match prefix {
+                true => (format!("{}{}", operator, name), UNARY),
+                false => (format!("{}{}", name, operator), POSTFIX),
+            }),
+    ];
+    leaf.prop_recursive(6, 48, 3, move |inner| prop_oneof![
+        6 => (prop::sample::select(&BINARY_OPERATORS[..]), inner.clone(), inner.clone())
+            .prop_map(|((operator, level), left, right)| {
+                (format!("{} {} {}", operand(left, level), operator, operand(right, level + 1)), level)
+            }),
+        2 => (prop::sample::select(&["-", "+", "!", "~"][..]), inner.clone()).prop_map(|(operator, operand_)| {
+            let text = operand(operand_, CAST);
+            let separator = if text.starts_with(['+', '-']) { " " } else { "" };
+            (format!("{}{}{}", operator, separator, text), UNARY)
+        }),
+        1 => (prop::sample::select(&["long", "unsigned", "char", "short"][..]), inner.clone())
+            .prop_map(|(type_, operand_)| (format!("({}){}", type_, operand(operand_, CAST)), CAST)),
+        1 => inner.clone().prop_map(|operand_| (format!("sizeof {}", operand(operand_, UNARY)), UNARY)),
+        2 => (variable.clone(), prop::sample::select(&ASSIGNMENT_OPERATORS[..]), inner.clone())
+            .prop_map(|(name, operator, value)| (format!("{} {} {}", name, operator, operand(value, ASSIGNMENT)), ASSIGNMENT)),
+        2 => (inner.clone(), inner.clone(), inner.clone()).prop_map(|(condition, then, otherwise)| {
+            (format!("{} ? {} : {}", operand(condition, 3), operand(then, COMMA), operand(otherwise, CONDITIONAL)), CONDITIONAL)
+        }),
+        1 => (inner.clone(), inner).prop_map(|(left, right)| {
+            (format!("{}, {}", operand(left, COMMA), operand(right, ASSIGNMENT)), COMMA)
+        }),
+    ])
+}
+
+/// One step of a declarator, read outwards from the name.
+#[derive(Debug, Clone)]
+enum Step {
+    Pointer { const_: bool, volatile: bool },
+    Array(u8),
+    Function(Vec<&'static str>),
+}
+
+/// Writes a declarator for `name` with the parentheses C needs and no others.
+fn declarator(name: &str, steps: &[Step]) -> String {
+    let mut declarator = name.to_string();
+    let mut pointer = false;
+    for step in steps {
+        match step {
+            Step::Pointer {const_, volatile} => {
+                let qualifiers = [(*const_, "const "), (*volatile, "volatile ")].iter()
+                    .filter(|(present, _)| *present)
+                    .map(|(_, qualifier)| *qualifier)
+                    .collect::<String>();
+                declarator = format!("*{}{}", qualifiers, declarator);
+            },
+            Step::Array(_) | Step::Function(_) if pointer => {
+                declarator = format!("({})", declarator);
+            },
+            _ => {},
+        }
+        match step {
+            Step::Pointer {..} => {},
+            Step::Array(size) => declarator += &format!("[{}]", size),
+            Step::Function(parameters) if parameters.is_empty() => declarator += "(void)",
+            Step::Function(parameters) => declarator += &format!("({})", parameters.join(", ")),
+        }
+        pointer = matches!(step, Step::Pointer {..});
+    }
+    declarator
+}
+
+/// Declarations of `original` with up to five steps to their declarator, with a pointer put in
+/// wherever C does not allow arrays of functions or functions returning arrays or functions.
+fn declaration() -> impl Strategy<Value = String> {
+    let step = prop_oneof![
+        3 => (any::<bool>(), any::<bool>()).prop_map(|(const_, volatile)| Step::Pointer {const_, volatile}),
+        2 => (1..8u8).prop_map(Step::Array),
+        2 => prop::collection::vec(prop::sample::select(&PARAMETER_TYPES[..]), 0..3).prop_map(Step::Function),
+    ];
+    (prop::sample::select(&BASE_TYPES[..]), prop::collection::vec(step, 0..6)).prop_map(|(base, steps)| {
+        let mut valid: Vec<Step> = Vec::new();
+        for step in steps {
+            let needs_pointer = matches!((valid.last(), &step),
+                (Some(Step::Function(_)), Step::Array(_) | Step::Function(_)) | (Some(Step::Array(_)), Step::Function(_)));
+            if needs_pointer {
+                valid.push(Step::Pointer {const_: false, volatile: false});
+            }
+            valid.push(step);
+        }
+        format!("extern {} {};", base, declarator("original", &valid))
+    })
+}
+
+/// The compiler to compare against, if there is one that can dump its trees.
+fn compiler() -> Option<&'static str> {
+    static COMPILER: OnceLock<Option<String>> = OnceLock::new();
+    COMPILER.get_or_init(|| {
+        let compiler = std::env::var("CC").unwrap_or_else(|_| "gcc".to_string());
+        match compile(&compiler, "int x;") {
+            Ok(_) => Some(compiler),
+            Err(err) => {
+                eprintln!("Skipping the differential tests, {} cannot be used: {}", compiler, err);
+                None
+            },
+        }
+    }).as_deref()
+}
+
+/// Compiles `source`, returning the dumped trees of its functions or the compiler's errors.
+fn compile(compiler: &str, source: &str) -> Result<String, String> {
+    static CASES: AtomicUsize = AtomicUsize::new(0);
+    let base = std::env::temp_dir()
+        .join(format!("cwc-differential-{}-{}", std::process::id(), CASES.fetch_add(1, Ordering::Relaxed)));
+    let file = base.with_extension("c");
+    let dump = base.with_extension("dump");
+    fs::write(&file, source).map_err(|err| err.to_string())?;
+    let output = Command::new(compiler)
+        .args(["-std=gnu17", "-w", "-c", "-o", "/dev/null"])
+        .arg(format!("-fdump-tree-original={}", dump.display()))
+        .arg(&file)
+        .output();
+    let result = match output {
+        Ok(output) if output.status.success() => Ok(fs::read_to_string(&dump).unwrap_or_default()),
+        Ok(output) => Err(String::from_utf8_lossy(&output.stderr).into_owned()),
+        Err(err) => Err(err.to_string()),
+    };
+    let _ = fs::remove_file(file);
+    let _ = fs::remove_file(dump);
+    result
+}
+
+/// Compiles a case together with its printed form in `source`. A case the compiler rejects
+/// on its own is discarded; otherwise its errors fail the case, after `context`.
+fn compile_case(compiler: &str, original: &str, source: &str, context: &str) -> Result<String, TestCaseError> {
+    compile(compiler, source).map_err(|err| match compile(compiler, original) {
+        Ok(_) => TestCaseError::fail(format!("{}\n{}", context, err)),
+        Err(_) => TestCaseError::reject("The compiler rejects the case"),
+    })
+}
+
+/// The dumped tree of the function `name`, without the line that names it.
+fn function_tree<'a>(dump: &'a str, name: &str) -> &'a str {
+    dump.split(";; Function ")
+        .find(|function| function.split_whitespace().next() == Some(name))
+        .map_or("", |function| function[function.find('\n').unwrap_or(function.len())..].trim())
+}
+
+/// Parses `source` and prints it with every grouping in parentheses.
+fn parenthesize(source: &str) -> Result<String, TestCaseError> {
+    let tokens = lex_with_trivia(source).map_err(|errors| TestCaseError::fail(format!("Failed to lex {}: {:?}", source, errors)))?;
+    let header = Parser::with_trivia(&tokens).parse().map_err(|err| TestCaseError::fail(format!("Failed to parse {}: {}", source, err)))?;
+    Ok(print_with_style(&header, &Style {parenthesize: true, ..Style::default()}))
+}
+
+fn check_expression(compiler: &str, expression: &str) -> Result<(), TestCaseError> {
+    let prelude = "int a, b, c, d;\n";
+    let original = format!("{}void original(void) {{ {}; }}\n", prelude, expression);
+    let printed = parenthesize(&format!("void printed(void) {{ {}; }}", expression))?;
+    let dump = compile_case(compiler, &original, &format!("{}{}", original, printed),
+                            &format!("The printed form of {} does not compile:\n{}", expression, printed))?;
+    prop_assert_eq!(function_tree(&dump, "original"), function_tree(&dump, "printed"),
+                    "{} is grouped differently by the compiler than as\n{}", expression, printed);
+    Ok(())
+}
+
+fn check_declaration(compiler: &str, declaration: &str) -> Result<(), TestCaseError> {
+    let printed = parenthesize(&declaration.replace("original", "printed"))?;
+    let source = format!("{}\n{}_Static_assert(__builtin_types_compatible_p(__typeof__(&original), __typeof__(&printed)), \"\");\n",
+                         declaration, printed);
+    compile_case(compiler, declaration, &source,
+                 &format!("{} does not declare the same type as\n{}", declaration, printed))?;
+    Ok(())
+}
+
+/// 64 cases unless `PROPTEST_CASES` says otherwise, as each case runs the compiler. Failures
+/// are saved next to this file to be tried first on the next run.
+fn config() -> ProptestConfig {
+    let default = ProptestConfig::default();
+    ProptestConfig {
+        cases: if std::env::var_os("PROPTEST_CASES").is_some() { default.cases } else { 64 },
+        failure_persistence: Some(Box::new(FileFailurePersistence::WithSource("regressions"))),
+        ..default
+    }
+}
+
+proptest! {
+    #![proptest_config(config())]
+
+    #[test]
+    fn test_expressions_match_c(expression in expression()) {
+        if let Some(compiler) = compiler() {
+            check_expression(compiler, &expression.0)?;
+        }
+    }
+
+    #[test]
+    fn test_declarations_match_c(declaration in declaration()) {
+        if let Some(compiler) = compiler() {
+            check_declaration(compiler, &declaration)?;
+        }
+    }
+}
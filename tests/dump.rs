@@ -0,0 +1,42 @@
+//! Runs the `cwc-dump` binary on a small program.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+
+const SOURCE: &str = "\
+/// A point.
+struct point { int x, y; };
+
+int main(void) {
+    return 1 + 2 * 3;
+}
+";
+
+fn dump(args: &[&str], input: &str) -> String {
+    let mut dump = Command::new(env!("CARGO_BIN_EXE_cwc-dump"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Failed to start cwc-dump");
+    dump.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = dump.wait_with_output().unwrap();
+    assert!(output.status.success(), "cwc-dump {:?} failed", args);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_json_back_to_c() {
+    let json = dump(&["--emit", "json"], SOURCE);
+    assert!(json.contains("\"doc\": \"A point.\""), "{}", json);
+    assert_eq!(dump(&["--from-json", "--emit", "c"], &json), dump(&["--emit", "c"], SOURCE));
+}
+
+#[test]
+fn test_s_expressions() {
+    let sexp = dump(&[], SOURCE);
+    assert!(sexp.starts_with("(Header (statements ((Struct (name \"point\")"), "{}", sexp);
+    let tokens = dump(&["--tokens", "-"], SOURCE);
+    assert!(tokens.starts_with("((TokenWithTrivia (token Struct) (span (Range (start 13) (end 19)))"), "{}", tokens);
+}
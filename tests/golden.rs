@@ -0,0 +1,140 @@
+//! Checks every `tests/cases/*.cwc` file against the snapshots stored next to it: the tokens
+//! (`.tokens`), the syntax tree (`.ast`), the diagnostics (`.diagnostics`) and the source the
+//! printer writes back (`.printed`), which is still C with Classes rather than C. A missing
+//! snapshot stands for empty output. Run with `BLESS=1` to write the snapshots from what the
+//! lexer, parser and printer produce now.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use c_with_classes::attributes;
+use c_with_classes::ast::Header;
+use c_with_classes::layout::{DataModel, LayoutEngine};
+use c_with_classes::logos_lexer::{lex_with_trivia, TokenWithTrivia};
+use c_with_classes::parser::Parser;
+use c_with_classes::printer::print;
+
+
+/// The output of one case, in the order the snapshots are compared.
+struct Output {
+    tokens: String,
+    ast: String,
+    diagnostics: String,
+    printed: String,
+}
+
+/// Turns a byte offset into a one-based `line:column`.
+fn location(source: &str, offset: usize) -> String {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.len() - before.rfind('\n').map_or(0, |newline| newline + 1) + 1;
+    format!("{}:{}", line, column)
+}
+
+fn parse(tokens: &[TokenWithTrivia]) -> (Result<Header, String>, usize) {
+    let mut parser = Parser::with_trivia(tokens);
+    let header = parser.parse();
+    (header, parser.position())
+}
+
+/// Lexes, parses, checks and prints a case, reporting diagnostics the way the language server does.
+fn run(source: &str) -> Output {
+    let mut output = Output {tokens: String::new(), ast: String::new(), diagnostics: String::new(), printed: String::new()};
+    let tokens = match lex_with_trivia(source) {
+        Ok(tokens) => tokens,
+        Err(errors) => {
+            for error in errors {
+                output.diagnostics += &format!("{}: error: {:?}\n", location(source, error.span.start), error.error);
+            }
+            return output;
+        },
+    };
+    for token in &tokens {
+        output.tokens += &format!("{}..{} {:?}\n", token.span.start, token.span.end, token.token);
+    }
+
+    let header = match parse(&tokens) {
+        (Ok(header), _) => header,
+        (Err(err), position) => {
+            let offset = tokens.get(position).map_or(source.len(), |token| token.span.start);
+            output.diagnostics += &format!("{}: error: {}\n", location(source, offset), err);
+            return output;
+        },
+    };
+    output.ast = format!("{:#?}\n", header);
+    if let Err(err) = LayoutEngine::from_header(&header, DataModel::default()) {
+        output.diagnostics += &format!("error: {}\n", err);
+    }
+    match attributes::check(&header) {
+        Ok(warnings) => for warning in warnings {
+            output.diagnostics += &format!("warning: {}\n", warning);
+        },
+        Err(err) => output.diagnostics += &format!("error: {}\n", err),
+    }
+    output.printed = print(&header);
+    output
+}
+
+/// Describes where two snapshots first differ.
+fn difference(expected: &str, actual: &str) -> String {
+    let line = expected.lines().zip(actual.lines()).take_while(|(expected, actual)| expected == actual).count();
+    format!("first difference at line {}:\n  expected: {:?}\n  actual:   {:?}",
+            line + 1, expected.lines().nth(line), actual.lines().nth(line))
+}
+
+fn cases() -> Vec<PathBuf> {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/cases");
+    let mut cases = fs::read_dir(&directory)
+        .expect("Failed to read tests/cases")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "cwc"))
+        .collect::<Vec<PathBuf>>();
+    cases.sort();
+    cases
+}
+
+#[test]
+fn test_golden() {
+    let bless = std::env::var_os("BLESS").is_some();
+    let cases = cases();
+    assert!(!cases.is_empty(), "No cases in tests/cases");
+
+    let mut failures = Vec::new();
+    for case in &cases {
+        let name = case.file_stem().unwrap().to_string_lossy();
+        let source = fs::read_to_string(case).unwrap();
+        let output = run(&source);
+
+        for (extension, actual) in [("tokens", &output.tokens), ("ast", &output.ast),
+                                    ("diagnostics", &output.diagnostics), ("printed", &output.printed)] {
+            let path = case.with_extension(extension);
+            let expected = fs::read_to_string(&path).unwrap_or_default();
+            if *actual == expected {
+                continue;
+            }
+            if !bless {
+                failures.push(format!("{}.{}: {}", name, extension, difference(&expected, actual)));
+            }
+            else if actual.is_empty() {
+                fs::remove_file(&path).unwrap();
+            }
+            else {
+                fs::write(&path, actual).unwrap();
+            }
+        }
+
+        // The printed source has to parse back to the same tree.
+        if !output.printed.is_empty() {
+            let reparsed = lex_with_trivia(&output.printed).map_err(|errors| format!("{:?}", errors))
+                .and_then(|tokens| parse(&tokens).0)
+                .map(|header| format!("{:#?}\n", header));
+            if reparsed.as_ref() != Ok(&output.ast) {
+                failures.push(format!("{}.printed does not parse back to the same tree: {}", name,
+                                      reparsed.map_or_else(|err| err, |ast| difference(&output.ast, &ast))));
+            }
+        }
+    }
+
+    assert!(failures.is_empty(), "{} of {} cases failed (run with BLESS=1 to update the snapshots):\n\n{}",
+            failures.len(), cases.len(), failures.join("\n\n"));
+}
@@ -0,0 +1,208 @@
+//! Drives the `cwc-lsp` binary over stdio the way an editor would.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+use serde_json::{json, Value};
+
+
+const URI: &str = "file:///test.cwc";
+
+const SOURCE: &str = "\
+/// A point on the screen.
+struct point { int x, y; };
+
+class Shape {
+    struct point origin;
+    int area(void) { return 0; }
+};
+
+tagged Value { Number { double n; }, Empty };
+
+int main(Shape *shape) {
+    int width = shape->origin.x;
+    return width;
+}
+";
+
+struct Client {
+    server: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    next_id: u64,
+}
+
+impl Client {
+    fn start() -> Client {
+        let mut server = Command::new(env!("CARGO_BIN_EXE_cwc-lsp"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("Failed to start cwc-lsp");
+        let stdin = server.stdin.take().unwrap();
+        let stdout = BufReader::new(server.stdout.take().unwrap());
+        Client {server, stdin, stdout, next_id: 1}
+    }
+
+    fn send(&mut self, message: Value) {
+        let body = message.to_string();
+        write!(self.stdin, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+        self.stdin.flush().unwrap();
+    }
+
+    fn receive(&mut self) -> Value {
+        let mut length = 0;
+        loop {
+            let mut line = String::new();
+            self.stdout.read_line(&mut line).unwrap();
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some(value) = line.strip_prefix("Content-Length: ") {
+                length = value.parse().unwrap();
+            }
+        }
+        let mut body = vec![0; length];
+        self.stdout.read_exact(&mut body).unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    fn notify(&mut self, method: &str, params: Value) {
+        self.send(json!({"jsonrpc": "2.0", "method": method, "params": params}));
+    }
+
+    /// Sends a request and returns its result, skipping the notifications in between.
+    fn request(&mut self, method: &str, params: Value) -> Value {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send(json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params}));
+        loop {
+            let message = self.receive();
+            if message["id"] == json!(id) {
+                assert!(message.get("error").is_none(), "{} failed: {}", method, message);
+                return message["result"].clone();
+            }
+        }
+    }
+
+    /// Waits for the diagnostics of the next version of the document.
+    fn diagnostics(&mut self) -> Vec<Value> {
+        loop {
+            let message = self.receive();
+            if message["method"] == "textDocument/publishDiagnostics" {
+                return message["params"]["diagnostics"].as_array().unwrap().clone();
+            }
+        }
+    }
+
+    fn change(&mut self, version: i32, text: &str) {
+        self.notify("textDocument/didChange", json!({
+            "textDocument": {"uri": URI, "version": version},
+            "contentChanges": [{"text": text}],
+        }));
+    }
+
+    /// Sends the edit replacing the first `needle` in `text` with `replacement`, and returns the edited text.
+    fn replace(&mut self, version: i32, text: &str, needle: &str, replacement: &str) -> String {
+        let range = json!({"start": position(text, needle, 0, 0), "end": position(text, needle, 0, needle.len())});
+        self.notify("textDocument/didChange", json!({
+            "textDocument": {"uri": URI, "version": version},
+            "contentChanges": [{"range": range, "text": replacement}],
+        }));
+        text.replacen(needle, replacement, 1)
+    }
+}
+
+/// The LSP position of the `nth` occurrence of `needle` in `text`, plus `shift` characters.
+fn position(text: &str, needle: &str, nth: usize, shift: usize) -> Value {
+    let offset = text.match_indices(needle).nth(nth).unwrap().0 + shift;
+    let line = text[..offset].matches('\n').count();
+    let character = offset - text[..offset].rfind('\n').map_or(0, |newline| newline + 1);
+    json!({"line": line, "character": character})
+}
+
+fn at(text: &str, needle: &str, nth: usize) -> Value {
+    json!({"textDocument": {"uri": URI}, "position": position(text, needle, nth, 0)})
+}
+
+#[test]
+fn test_session() {
+    let mut client = Client::start();
+    let capabilities = client.request("initialize", json!({"capabilities": {}}));
+    assert_eq!(capabilities["capabilities"]["textDocumentSync"], 2);
+    assert_eq!(capabilities["capabilities"]["completionProvider"]["triggerCharacters"], json!([".", ">"]));
+    client.notify("initialized", json!({}));
+
+    client.notify("textDocument/didOpen", json!({
+        "textDocument": {"uri": URI, "languageId": "cwc", "version": 1, "text": SOURCE},
+    }));
+    assert_eq!(client.diagnostics(), Vec::<Value>::new());
+
+    let definition = client.request("textDocument/definition", at(SOURCE, "origin.x", 0));
+    assert_eq!(definition["range"]["start"], position(SOURCE, "origin", 0, 0));
+    let definition = client.request("textDocument/definition", at(SOURCE, "x;", 0));
+    assert_eq!(definition["range"]["start"], position(SOURCE, "x,", 0, 0));
+
+    let mut request = at(SOURCE, "width", 0);
+    request["context"] = json!({"includeDeclaration": true});
+    let references = client.request("textDocument/references", request);
+    let starts = references.as_array().unwrap().iter().map(|location| location["range"]["start"].clone()).collect::<Vec<Value>>();
+    assert_eq!(starts, [position(SOURCE, "width", 0, 0), position(SOURCE, "width", 1, 0)]);
+
+    let hover = client.request("textDocument/hover", at(SOURCE, "point origin", 0));
+    assert_eq!(hover["contents"]["value"], "```c\nstruct point\n```\n\nA point on the screen.");
+    let hover = client.request("textDocument/hover", at(SOURCE, "shape->", 0));
+    assert_eq!(hover["contents"]["value"], "```c\nShape *shape\n```");
+
+    let symbols = client.request("textDocument/documentSymbol", json!({"textDocument": {"uri": URI}}));
+    let outline = symbols.as_array().unwrap().iter()
+        .map(|symbol| {
+            let children = symbol["children"].as_array().map_or(Vec::new(), |children| children.iter()
+                .map(|child| child["name"].as_str().unwrap().to_string()).collect());
+            (symbol["name"].as_str().unwrap().to_string(), children)
+        })
+        .collect::<Vec<(String, Vec<String>)>>();
+    let names = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<String>>();
+    assert_eq!(outline, [
+        ("point".to_string(), names(&["x", "y"])),
+        ("Shape".to_string(), names(&["origin", "area"])),
+        ("Value".to_string(), names(&["Number", "Empty"])),
+        ("main".to_string(), Vec::new()),
+    ]);
+
+    // Completion works from the last version that parsed while the member access is unfinished.
+    let edited = client.replace(2, SOURCE, "return width;", "shape->origin.\n    return width;");
+    assert_eq!(client.diagnostics().len(), 1);
+    let completion = client.request("textDocument/completion", json!({
+        "textDocument": {"uri": URI},
+        "position": position(&edited, "origin.\n", 0, 7),
+    }));
+    let labels = completion.as_array().unwrap().iter().map(|item| item["label"].clone()).collect::<Vec<Value>>();
+    assert_eq!(labels, [json!("x"), json!("y")]);
+
+    let edited = client.replace(3, &edited, "shape->origin.\n    return width;", "return shape->");
+    assert_eq!(edited, SOURCE.replace("return width;", "return shape->"));
+    let diagnostics = client.diagnostics();
+    assert_eq!(diagnostics[0]["range"]["start"], position(&edited, "}", edited.matches('}').count() - 1, 0));
+    let completion = client.request("textDocument/completion", json!({
+        "textDocument": {"uri": URI},
+        "position": position(&edited, "shape->", 0, 7),
+    }));
+    let labels = completion.as_array().unwrap().iter().map(|item| item["label"].clone()).collect::<Vec<Value>>();
+    assert_eq!(labels, [json!("origin"), json!("area")]);
+
+    // Checks place what they find at the name it is about, or at the declaration.
+    let edited = format!("{}static_assert(sizeof(int) == 8);\n", SOURCE.replace("return width;", "int spare;\n    return width;"));
+    client.change(4, &edited);
+    let diagnostics = client.diagnostics();
+    let ranges = diagnostics.iter().map(|diagnostic| (diagnostic["message"].clone(), diagnostic["range"].clone())).collect::<Vec<(Value, Value)>>();
+    assert_eq!(ranges, [
+        (json!("Static assertion failed"), json!({"start": position(&edited, "static_assert", 0, 0), "end": position(&edited, "== 8);", 0, 6)})),
+        (json!("Unused variable spare"), json!({"start": position(&edited, "spare", 0, 0), "end": position(&edited, "spare", 0, 5)})),
+    ]);
+
+    client.request("shutdown", Value::Null);
+    client.notify("exit", Value::Null);
+    assert!(client.server.wait().unwrap().success());
+}